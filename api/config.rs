@@ -0,0 +1,116 @@
+//! Transit server settings, read from the environment.
+//!
+//! | variable                          | meaning                                              |
+//! |-----------------------------------|------------------------------------------------------|
+//! | `KYBER_API_HOST`, `KYBER_API_PORT` | listen address, default `127.0.0.1:8200`            |
+//! | `KYBER_TRANSIT_TOKENS`            | `token=principal` pairs, comma separated (required)  |
+//! | `KYBER_TRANSIT_POLICY`            | path of a JSON file holding the KMS access policy; without it every call is denied |
+//! | `KYBER_TRANSIT_STORE`             | key store directory; keys are in memory only without it |
+//! | `KYBER_TRANSIT_KEK_DIR`           | master-key KEK directory, required with a store      |
+//! | `KYBER_TRANSIT_PASSPHRASE_FILE`   | file holding the KEK passphrase, required with a store |
+//! | `KYBER_TRANSIT_TLS_CERT`, `KYBER_TRANSIT_TLS_KEY` | PEM certificate chain and key; serves HTTPS when set |
+//! | `KYBER_TRANSIT_TLS_CLIENT_CA`     | PEM CAs client certificates must chain to; requires TLS |
+
+use anyhow::{anyhow, bail, Context, Result};
+use pqc_kyber::crypto::kms::{AccessPolicy, CallerContext};
+use pqc_kyber::crypto::secure::SecureSecret;
+use pqc_kyber::utils::tls::{self, TlsIdentity};
+use std::env;
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+const DEFAULT_HOST: &str = "127.0.0.1";
+// Vault's port, so clients only need the host changed
+const DEFAULT_PORT: u16 = 8200;
+
+pub struct TransitConfig {
+    pub host: String,
+    pub port: u16,
+    pub tokens: Vec<(String, CallerContext)>,
+    pub policy: AccessPolicy,
+    pub store: Option<StoreConfig>,
+    pub tls: Option<TlsConfig>,
+}
+
+/// Sealed file store opened through a passphrase-protected KEK directory
+pub struct StoreConfig {
+    pub root: PathBuf,
+    pub kek_dir: PathBuf,
+    pub passphrase: SecureSecret,
+}
+
+/// Listener certificate, and the CAs clients must present a certificate from
+pub struct TlsConfig {
+    pub identity: TlsIdentity,
+    pub client_ca_pem: Option<Vec<u8>>,
+}
+
+impl TransitConfig {
+    pub fn from_env() -> Result<Self> {
+        let host = env::var("KYBER_API_HOST").unwrap_or_else(|_| DEFAULT_HOST.to_string());
+        let port = match env::var("KYBER_API_PORT") {
+            Ok(port) => port.parse().with_context(|| format!("Invalid KYBER_API_PORT {}", port))?,
+            Err(_) => DEFAULT_PORT,
+        };
+
+        let tokens = parse_tokens(&env::var("KYBER_TRANSIT_TOKENS").context("KYBER_TRANSIT_TOKENS is not set")?)?;
+        let policy = match env::var("KYBER_TRANSIT_POLICY") {
+            Ok(path) => {
+                let json = std::fs::read(&path).with_context(|| format!("Failed to read {}", path))?;
+                serde_json::from_slice(&json).with_context(|| format!("Invalid access policy in {}", path))?
+            }
+            Err(_) => AccessPolicy::new(),
+        };
+
+        let store = match env::var_os("KYBER_TRANSIT_STORE") {
+            Some(root) => {
+                let kek_dir = env::var_os("KYBER_TRANSIT_KEK_DIR").context("KYBER_TRANSIT_KEK_DIR is not set")?;
+                let passphrase_file =
+                    env::var("KYBER_TRANSIT_PASSPHRASE_FILE").context("KYBER_TRANSIT_PASSPHRASE_FILE is not set")?;
+                let passphrase = Zeroizing::new(
+                    std::fs::read_to_string(&passphrase_file).with_context(|| format!("Failed to read {}", passphrase_file))?,
+                );
+                Some(StoreConfig {
+                    root: root.into(),
+                    kek_dir: kek_dir.into(),
+                    passphrase: SecureSecret::from_bytes(passphrase.trim_end_matches(['\r', '\n']).as_bytes()),
+                })
+            }
+            None => None,
+        };
+
+        let tls = match (env::var("KYBER_TRANSIT_TLS_CERT"), env::var("KYBER_TRANSIT_TLS_KEY")) {
+            (Ok(certificate), Ok(key)) => {
+                let client_ca_pem = match env::var("KYBER_TRANSIT_TLS_CLIENT_CA") {
+                    Ok(path) => Some(tls::read(&path)?),
+                    Err(_) => None,
+                };
+                Some(TlsConfig { identity: TlsIdentity::from_files(&certificate, &key)?, client_ca_pem })
+            }
+            (Err(_), Err(_)) if env::var_os("KYBER_TRANSIT_TLS_CLIENT_CA").is_some() => {
+                bail!("KYBER_TRANSIT_TLS_CLIENT_CA requires KYBER_TRANSIT_TLS_CERT and KYBER_TRANSIT_TLS_KEY")
+            }
+            (Err(_), Err(_)) => None,
+            _ => bail!("KYBER_TRANSIT_TLS_CERT and KYBER_TRANSIT_TLS_KEY must be set together"),
+        };
+
+        Ok(Self { host, port, tokens, policy, store, tls })
+    }
+}
+
+fn parse_tokens(value: &str) -> Result<Vec<(String, CallerContext)>> {
+    let mut tokens = Vec::new();
+    for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let (token, principal) = pair
+            .split_once('=')
+            .ok_or_else(|| anyhow!("KYBER_TRANSIT_TOKENS entry without '=': expected token=principal"))?;
+        if token.is_empty() || principal.is_empty() {
+            bail!("KYBER_TRANSIT_TOKENS entry with an empty token or principal");
+        }
+        tokens.push((token.to_string(), CallerContext::new(principal)));
+    }
+    if tokens.is_empty() {
+        bail!("KYBER_TRANSIT_TOKENS has no tokens");
+    }
+    Ok(tokens)
+}
@@ -0,0 +1,86 @@
+//! Kyber API server.
+//!
+//! ```text
+//! kyber-api [--transit]
+//! ```
+//!
+//! Without arguments it starts the regular API server on `127.0.0.1:8080`. With `--transit` it
+//! serves the Vault Transit-compatible facade
+//! (`pqc_kyber::integration::api::transit`) instead, configured from the
+//! environment as described in `config.rs`. Tokens are bearer credentials,
+//! so the facade should listen with TLS anywhere but loopback.
+
+mod config;
+
+use anyhow::{Context, Result};
+use actix_web::{web, App, HttpServer};
+use config::TransitConfig;
+use pqc_kyber::crypto::kms::KeyManagementSystem;
+use pqc_kyber::integration::api::{self, transit, ApiConfig};
+use pqc_kyber::kms::SealedFileProvider;
+use pqc_kyber::utils::tls;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+
+const USAGE: &str = "usage: kyber-api [--transit]";
+
+#[actix_web::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [] => api::start_api_server(ApiConfig::default()).await.map_err(anyhow::Error::from),
+        [flag] if flag == "--transit" => serve_transit().await,
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("kyber-api: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn serve_transit() -> Result<()> {
+    let config = TransitConfig::from_env()?;
+    let mut kms = match config.store {
+        Some(store) => {
+            let provider = SealedFileProvider::open(&store.kek_dir, store.passphrase)?;
+            KeyManagementSystem::unseal_store(&store.root, &provider)
+                .with_context(|| format!("Failed to open key store {}", store.root.display()))?
+        }
+        None => {
+            eprintln!("warning: KYBER_TRANSIT_STORE is not set; keys are lost when the server stops");
+            KeyManagementSystem::new()
+        }
+    };
+    kms.set_access_policy(config.policy);
+
+    let transit_api = config
+        .tokens
+        .into_iter()
+        .fold(transit::TransitApi::new(Arc::new(Mutex::new(kms))), |api, (token, caller)| {
+            api.with_token(&token, caller)
+        });
+    let transit_api = web::Data::new(transit_api);
+
+    let server = HttpServer::new(move || App::new().app_data(transit_api.clone()).configure(transit::configure));
+    let server = match config.tls {
+        Some(settings) => {
+            let server_config = tls::server_config(&settings.identity, settings.client_ca_pem.as_deref())?;
+            println!("Serving Vault Transit API on https://{}:{}", config.host, config.port);
+            server.bind_rustls_0_23((config.host.as_str(), config.port), (*server_config).clone())?
+        }
+        None => {
+            eprintln!("warning: KYBER_TRANSIT_TLS_CERT is not set; tokens are sent in plaintext");
+            println!("Serving Vault Transit API on http://{}:{}", config.host, config.port);
+            server.bind((config.host.as_str(), config.port))?
+        }
+    };
+    server.run().await?;
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use criterion::{criterion_group, criterion_main, Criterion, black_box};
+use criterion::{criterion_group, criterion_main, Criterion};
 use pqcrypto_kyber::kyber1024::*;
 use rsa::{RsaPrivateKey, RsaPublicKey, Pkcs1v15Encrypt, rand_core::OsRng};
 use p256::{PublicKey as P256PublicKey, ecdh::EphemeralSecret};
@@ -9,8 +9,6 @@ use std::time::{Instant};
 struct UnifiedBenchmark {
     iterations: usize,
     warmup_iterations: usize,
-    payload_sizes: Vec<usize>,
-    sample_size: usize,
 
     times: Vec<f64>,
     memory_samples: Vec<usize>,
@@ -24,8 +22,6 @@ impl Default for UnifiedBenchmark {
         Self {
             iterations: 1000,
             warmup_iterations: 50,
-            payload_sizes: vec![32, 64, 128, 256, 512, 1024, 2048],
-            sample_size: 30,
             times: Vec::new(),
             memory_samples: Vec::new(),
             peak_memory: 0,
@@ -0,0 +1,26 @@
+//! Creates a versioned Kyber-1024 key in an in-memory KMS, encrypts a message
+//! under it and decrypts it again.
+//!
+//! ```text
+//! cargo run --example basic_usage
+//! ```
+
+use pqc_kyber::crypto::kms::{AccessPolicy, CallerContext, KeyManagementSystem, KmsAction, Role};
+
+fn main() -> Result<(), pqc_kyber::Error> {
+    let admin = CallerContext::new("kms-admin");
+    let mut kms = KeyManagementSystem::new();
+    kms.set_access_policy(
+        AccessPolicy::new()
+            .with_role(Role::new("kms-admin").allow("payments:*", KmsAction::ALL))
+            .assign(&admin.principal, "kms-admin"),
+    );
+
+    kms.generate_versioned_kem_key(&admin, "payments")?;
+    let envelope = kms.encrypt(&admin, "payments", b"settlement batch 42", b"ledger")?;
+    let plaintext = kms.decrypt(&admin, &envelope, b"ledger")?;
+
+    println!("envelope: {} bytes", envelope.len());
+    println!("plaintext: {}", String::from_utf8_lossy(&plaintext));
+    Ok(())
+}
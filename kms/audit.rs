@@ -0,0 +1,578 @@
+//! Tamper-evident audit log of KMS operations.
+//!
+//! Every create, use, rotation, export, state change and destruction of a key
+//! is appended to a JSON-lines file as an [`AuditEntry`]. Each entry carries
+//! the SHA3-256 hash of the one before it, so deleting, reordering or editing
+//! any entry breaks the chain from that point on:
+//!
+//! ```text
+//! hash(n) = SHA3-256("pqc-kyber/kms/audit/v1" | hash(n-1) | sequence | timestamp
+//!                    | actor | key id | operation | outcome | detail)
+//! ```
+//!
+//! Strings are length-prefixed and the first entry chains to 32 zero bytes.
+//! Every `interval` entries the head of the chain is sealed in an SLH-DSA
+//! signed [`SealedCheckpoint`], written into the same file. A checkpoint
+//! proves the history up to its sequence number. [`verify_log`] therefore
+//! demands a checkpoint at least every `interval` entries, so that dropping
+//! one from the middle is caught, and either a checkpoint after the last
+//! entry or a checkpoint kept elsewhere (e.g. handed to the auditors) as an
+//! anchor, so that cutting off the tail is caught. Seal the head with
+//! [`AuditLog::checkpoint`] before handing a log over for verification.
+//!
+//! Lines are fsync'd as they are written. The log is append-only: nothing in
+//! this module rewrites or truncates it.
+
+use crate::crypto::signature::{AuditCheckpoint, SealedCheckpoint, SlhDsaSigningKey, SlhDsaVerifyingKey};
+use crate::error::{ErrorCategory, ErrorCode, KmsError};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ENTRY_HASH_DOMAIN: &[u8] = b"pqc-kyber/kms/audit/v1";
+const HASH_LENGTH: usize = 32;
+const GENESIS_HASH: [u8; HASH_LENGTH] = [0u8; HASH_LENGTH];
+
+/// What was done to a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditOperation {
+    Create,
+    /// Cryptographic use without the material leaving the KMS
+    Use,
+    Rotate,
+    /// Raw key material handed to the caller
+    Export,
+    /// Lifecycle state change other than destruction
+    Transition,
+    Destroy,
+    /// Record removed from the store
+    Delete,
+    /// Reading state or metadata; only denials are recorded
+    Describe,
+    /// Key sealed into a KMS snapshot or backup bundle
+    Backup,
+    /// Key written from a backup bundle
+    Restore,
+    /// Sensitive operation filed for four-eyes approval
+    Request,
+    /// Signed approval or rejection of a pending request
+    Approve,
+}
+
+/// How the operation ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditOutcome {
+    /// Authorized and about to run; an entry with the final outcome follows
+    /// unless the process died or the log failed in between
+    Started,
+    Success,
+    /// Refused by policy or by the key's lifecycle state
+    Denied,
+    Failed,
+}
+
+/// One link of the audit chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Starts at 1 and increases by one per entry
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub actor: String,
+    pub key_id: String,
+    pub operation: AuditOperation,
+    pub outcome: AuditOutcome,
+    /// Operation specifics or the error code of a failure; never key material
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Hex SHA3-256 of the previous entry
+    pub prev_hash: String,
+    /// Hex SHA3-256 of this entry
+    pub hash: String,
+}
+
+/// One line of the log file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditRecord {
+    Entry(AuditEntry),
+    Checkpoint(SealedCheckpoint),
+}
+
+/// Result of a successful [`verify_log`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditVerification {
+    pub entries: u64,
+    pub checkpoints: u64,
+    /// Sequence covered by the last signed checkpoint, 0 if there is none
+    pub sealed_through: u64,
+    /// Hex hash of the last entry
+    pub head_hash: String,
+}
+
+impl AuditOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOperation::Create => "CREATE",
+            AuditOperation::Use => "USE",
+            AuditOperation::Rotate => "ROTATE",
+            AuditOperation::Export => "EXPORT",
+            AuditOperation::Transition => "TRANSITION",
+            AuditOperation::Destroy => "DESTROY",
+            AuditOperation::Delete => "DELETE",
+            AuditOperation::Describe => "DESCRIBE",
+            AuditOperation::Backup => "BACKUP",
+            AuditOperation::Restore => "RESTORE",
+            AuditOperation::Request => "REQUEST",
+            AuditOperation::Approve => "APPROVE",
+        }
+    }
+}
+
+impl AuditOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Started => "STARTED",
+            AuditOutcome::Success => "SUCCESS",
+            AuditOutcome::Denied => "DENIED",
+            AuditOutcome::Failed => "FAILED",
+        }
+    }
+
+    /// Outcome recorded for an operation that failed with `code`
+    pub fn from_error(code: ErrorCode) -> Self {
+        match code.category {
+            ErrorCategory::PermissionDenied | ErrorCategory::Unauthenticated | ErrorCategory::FailedPrecondition => {
+                AuditOutcome::Denied
+            }
+            _ => AuditOutcome::Failed,
+        }
+    }
+}
+
+impl AuditEntry {
+    /// Hash this entry should carry given its fields and `prev_hash`
+    pub fn compute_hash(&self) -> Result<String, KmsError> {
+        let prev_hash = decode_hash(&self.prev_hash, self.sequence)?;
+        let mut hasher = Sha3_256::new();
+        hasher.update(ENTRY_HASH_DOMAIN);
+        hasher.update(prev_hash);
+        hasher.update(self.sequence.to_be_bytes());
+        hasher.update(self.timestamp.to_be_bytes());
+        for field in [
+            self.actor.as_str(),
+            self.key_id.as_str(),
+            self.operation.as_str(),
+            self.outcome.as_str(),
+            self.detail.as_deref().unwrap_or_default(),
+        ] {
+            hasher.update((field.len() as u32).to_be_bytes());
+            hasher.update(field.as_bytes());
+        }
+        Ok(hex::encode(hasher.finalize()))
+    }
+}
+
+struct CheckpointSigner {
+    signer_id: String,
+    key: SlhDsaSigningKey,
+    interval: u64,
+}
+
+struct LogState {
+    file: File,
+    sequence: u64,
+    head: [u8; HASH_LENGTH],
+    sealed_through: u64,
+    signer: Option<CheckpointSigner>,
+}
+
+/// Append-only, hash-chained audit log file
+pub struct AuditLog {
+    path: PathBuf,
+    state: Mutex<LogState>,
+}
+
+impl AuditLog {
+    /// Opens or creates the log at `path`. An existing log must have an
+    /// intact chain; appending to a broken one would hide the break.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, KmsError> {
+        let path = path.as_ref().to_path_buf();
+        let scan = if path.exists() { scan_log(&path, None, None)? } else { Scan::default() };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(io_error("open audit log"))?;
+
+        Ok(Self {
+            path,
+            state: Mutex::new(LogState {
+                file,
+                sequence: scan.entries,
+                head: scan.head,
+                sealed_through: scan.sealed_through,
+                signer: None,
+            }),
+        })
+    }
+
+    /// Seals the chain head with `key` after every `interval` entries
+    pub fn with_checkpoints(self, signer_id: &str, key: SlhDsaSigningKey, interval: u64) -> Self {
+        if let Ok(mut state) = self.state.lock() {
+            state.signer = Some(CheckpointSigner {
+                signer_id: signer_id.to_string(),
+                key,
+                interval: interval.max(1),
+            });
+        }
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Sequence number and hex hash of the last entry
+    pub fn head(&self) -> Result<(u64, String), KmsError> {
+        let state = self.lock()?;
+        Ok((state.sequence, hex::encode(state.head)))
+    }
+
+    /// Appends one entry, and a checkpoint if one is due
+    pub fn record(
+        &self,
+        actor: &str,
+        key_id: &str,
+        operation: AuditOperation,
+        outcome: AuditOutcome,
+        detail: Option<String>,
+    ) -> Result<AuditEntry, KmsError> {
+        let mut state = self.lock()?;
+        let mut entry = AuditEntry {
+            sequence: state.sequence + 1,
+            timestamp: now(),
+            actor: actor.to_string(),
+            key_id: key_id.to_string(),
+            operation,
+            outcome,
+            detail,
+            prev_hash: hex::encode(state.head),
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash()?;
+
+        append(&mut state.file, &AuditRecord::Entry(entry.clone()))?;
+        state.sequence = entry.sequence;
+        state.head = decode_hash(&entry.hash, entry.sequence)?;
+
+        let due = state
+            .signer
+            .as_ref()
+            .is_some_and(|signer| state.sequence - state.sealed_through >= signer.interval);
+        if due {
+            seal_head(&mut state)?;
+        }
+        Ok(entry)
+    }
+
+    /// Seals the current head now, e.g. before handing a checkpoint to auditors
+    pub fn checkpoint(&self) -> Result<SealedCheckpoint, KmsError> {
+        let mut state = self.lock()?;
+        seal_head(&mut state)
+    }
+
+    /// Reopens the file read-only so that every further append fails
+    #[cfg(test)]
+    pub(crate) fn break_writes(&self) {
+        self.lock().unwrap().file = File::open(&self.path).unwrap();
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, LogState>, KmsError> {
+        self.state
+            .lock()
+            .map_err(|_| KmsError::Storage("audit log lock poisoned".to_string()))
+    }
+}
+
+fn seal_head(state: &mut LogState) -> Result<SealedCheckpoint, KmsError> {
+    let signer = state
+        .signer
+        .as_ref()
+        .ok_or_else(|| KmsError::Storage("audit log has no checkpoint signing key".to_string()))?;
+    let sealed = AuditCheckpoint::new(state.sequence, &state.head)
+        .seal(&signer.signer_id, &signer.key)
+        .map_err(|e| KmsError::SealFailed(format!("audit checkpoint: {}", e)))?;
+    append(&mut state.file, &AuditRecord::Checkpoint(sealed.clone()))?;
+    state.sealed_through = state.sequence;
+    Ok(sealed)
+}
+
+/// Checks the whole log at `path`: entry hashes, chain links, consecutive
+/// sequence numbers, every checkpoint signature under `verifying_key`, and
+/// that no more than `interval` entries ever go without a checkpoint.
+///
+/// Without an `anchor` the log must end in a checkpoint. With one (a
+/// checkpoint kept outside the log) the log must instead still contain the
+/// exact entry the anchor was taken at; either way a truncated or rebuilt
+/// log is caught.
+pub fn verify_log(
+    path: impl AsRef<Path>,
+    verifying_key: &SlhDsaVerifyingKey,
+    interval: u64,
+    anchor: Option<&AuditCheckpoint>,
+) -> Result<AuditVerification, KmsError> {
+    let scan = scan_log(path.as_ref(), Some((verifying_key, interval.max(1))), anchor)?;
+    match anchor {
+        Some(anchor) if !scan.anchor_found => {
+            return Err(KmsError::IntegrityCheckFailed(format!(
+                "audit log does not contain anchored entry {}; truncated or rewritten",
+                anchor.sequence
+            )));
+        }
+        None if scan.sealed_through < scan.entries => {
+            return Err(KmsError::IntegrityCheckFailed(format!(
+                "audit entries {}..={} are not sealed by a final checkpoint; truncation cannot be ruled out",
+                scan.sealed_through + 1,
+                scan.entries
+            )));
+        }
+        _ => {}
+    }
+
+    Ok(AuditVerification {
+        entries: scan.entries,
+        checkpoints: scan.checkpoints,
+        sealed_through: scan.sealed_through,
+        head_hash: hex::encode(scan.head),
+    })
+}
+
+#[derive(Default)]
+struct Scan {
+    entries: u64,
+    checkpoints: u64,
+    head: [u8; HASH_LENGTH],
+    sealed_through: u64,
+    anchor_found: bool,
+}
+
+// Walks the log once; checkpoint signatures and spacing are only checked
+// when a key and checkpoint interval are given
+fn scan_log(
+    path: &Path,
+    verification: Option<(&SlhDsaVerifyingKey, u64)>,
+    anchor: Option<&AuditCheckpoint>,
+) -> Result<Scan, KmsError> {
+    let file = File::open(path).map_err(io_error("open audit log"))?;
+    let mut scan = Scan {
+        head: GENESIS_HASH,
+        ..Scan::default()
+    };
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(io_error("read audit log"))?;
+        let tampered = |what: String| KmsError::IntegrityCheckFailed(format!("audit log line {}: {}", index + 1, what));
+        let record: AuditRecord =
+            serde_json::from_str(&line).map_err(|e| tampered(format!("unreadable record ({})", e)))?;
+
+        match record {
+            AuditRecord::Entry(entry) => {
+                let expected = scan.entries + 1;
+                if entry.sequence != expected {
+                    return Err(tampered(format!(
+                        "expected entry {} but found {}; entries deleted or reordered",
+                        expected, entry.sequence
+                    )));
+                }
+                if decode_hash(&entry.prev_hash, entry.sequence)? != scan.head {
+                    return Err(tampered(format!("entry {} does not chain to its predecessor", entry.sequence)));
+                }
+                if entry.compute_hash()? != entry.hash {
+                    return Err(tampered(format!("entry {} was modified", entry.sequence)));
+                }
+                if verification.is_some_and(|(_, interval)| entry.sequence - scan.sealed_through > interval) {
+                    return Err(tampered(format!(
+                        "entry {} is more than the checkpoint interval past checkpoint {}; checkpoint removed",
+                        entry.sequence, scan.sealed_through
+                    )));
+                }
+                scan.entries = entry.sequence;
+                scan.head = decode_hash(&entry.hash, entry.sequence)?;
+                if anchor.is_some_and(|a| a.sequence == scan.entries && a.head_hash[..] == scan.head[..]) {
+                    scan.anchor_found = true;
+                }
+            }
+            AuditRecord::Checkpoint(sealed) => {
+                let checkpoint = &sealed.checkpoint;
+                if checkpoint.sequence != scan.entries || checkpoint.head_hash[..] != scan.head[..] {
+                    return Err(tampered(format!(
+                        "checkpoint for entry {} does not match the log",
+                        checkpoint.sequence
+                    )));
+                }
+                if let Some((key, _)) = verification {
+                    sealed
+                        .verify(key)
+                        .map_err(|_| tampered(format!("checkpoint for entry {} has a bad signature", checkpoint.sequence)))?;
+                }
+                scan.checkpoints += 1;
+                scan.sealed_through = checkpoint.sequence;
+            }
+        }
+    }
+    Ok(scan)
+}
+
+fn append(file: &mut File, record: &AuditRecord) -> Result<(), KmsError> {
+    let mut line = serde_json::to_vec(record)
+        .map_err(|e| KmsError::Storage(format!("encode audit record: {}", e)))?;
+    line.push(b'\n');
+    file.write_all(&line).map_err(io_error("append to audit log"))?;
+    file.sync_data().map_err(io_error("sync audit log"))
+}
+
+fn decode_hash(hash: &str, sequence: u64) -> Result<[u8; HASH_LENGTH], KmsError> {
+    hex::decode(hash)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| KmsError::IntegrityCheckFailed(format!("audit entry {} has a malformed hash", sequence)))
+}
+
+fn io_error(operation: &'static str) -> impl Fn(std::io::Error) -> KmsError {
+    move |e| KmsError::Storage(format!("{}: {}", operation, e))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signature::SlhDsaParameterSet;
+    use tempfile::TempDir;
+
+    fn write_log(dir: &TempDir, key: SlhDsaSigningKey) -> PathBuf {
+        let path = dir.path().join("kms-audit.jsonl");
+        let log = AuditLog::open(&path).unwrap().with_checkpoints("audit-officer", key, 2);
+        log.record("etl-service", "settlement:1", AuditOperation::Create, AuditOutcome::Success, None).unwrap();
+        log.record("etl-service", "settlement:1", AuditOperation::Use, AuditOutcome::Success, Some("ENCAPSULATE".into()))
+            .unwrap();
+        log.record("reporting", "settlement:1", AuditOperation::Export, AuditOutcome::Denied, None).unwrap();
+        log.record("kms-admin", "settlement:1", AuditOperation::Rotate, AuditOutcome::Success, None).unwrap();
+        path
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+    }
+
+    fn rewrite(path: &Path, lines: &[String]) {
+        std::fs::write(path, lines.join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn test_chain_and_checkpoints_verify() {
+        let dir = TempDir::new().unwrap();
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128f);
+        let verifying_key = key.verifying_key().clone();
+        let path = write_log(&dir, key);
+
+        // Four entries, sealed after the second and the fourth
+        assert_eq!(lines(&path).len(), 6);
+        let report = verify_log(&path, &verifying_key, 2, None).unwrap();
+        assert_eq!(report.entries, 4);
+        assert_eq!(report.checkpoints, 2);
+        assert_eq!(report.sealed_through, 4);
+
+        // Reopening continues the chain where it ended
+        let log = AuditLog::open(&path).unwrap();
+        let entry = log.record("kms-admin", "settlement:1", AuditOperation::Destroy, AuditOutcome::Success, None).unwrap();
+        assert_eq!(entry.sequence, 5);
+        assert_eq!(entry.prev_hash, report.head_hash);
+
+        // An unsealed tail only verifies against an anchor
+        let err = verify_log(&path, &verifying_key, 2, None).unwrap_err();
+        assert!(err.to_string().contains("not sealed"), "{}", err);
+        let AuditRecord::Checkpoint(anchor) = serde_json::from_str(&lines(&path)[5]).unwrap() else {
+            panic!("expected a checkpoint");
+        };
+        assert_eq!(verify_log(&path, &verifying_key, 2, Some(&anchor.checkpoint)).unwrap().sealed_through, 4);
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let dir = TempDir::new().unwrap();
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128f);
+        let verifying_key = key.verifying_key().clone();
+        let path = write_log(&dir, key);
+        let original = lines(&path);
+
+        // Modification: an export that was denied now claims success
+        let mut modified = original.clone();
+        modified[3] = modified[3].replace("DENIED", "SUCCESS");
+        rewrite(&path, &modified);
+        let err = verify_log(&path, &verifying_key, 2, None).unwrap_err();
+        assert!(err.to_string().contains("modified"), "{}", err);
+
+        // Deletion of an entry
+        let mut deleted = original.clone();
+        deleted.remove(1);
+        rewrite(&path, &deleted);
+        assert!(verify_log(&path, &verifying_key, 2, None).is_err());
+
+        // Reordering two entries
+        let mut reordered = original.clone();
+        reordered.swap(3, 4);
+        rewrite(&path, &reordered);
+        assert!(verify_log(&path, &verifying_key, 2, None).is_err());
+
+        // Dropping a checkpoint from the middle
+        let mut unsealed = original.clone();
+        unsealed.remove(2);
+        rewrite(&path, &unsealed);
+        let err = verify_log(&path, &verifying_key, 2, None).unwrap_err();
+        assert!(err.to_string().contains("checkpoint removed"), "{}", err);
+        rewrite(&path, &original);
+        assert!(verify_log(&path, &verifying_key, 1, None).is_err());
+
+        // Refusing to append to a broken chain
+        rewrite(&path, &reordered);
+        assert!(matches!(AuditLog::open(&path), Err(KmsError::IntegrityCheckFailed(_))));
+
+        // Checkpoints signed by someone else
+        rewrite(&path, &original);
+        let other = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128f);
+        assert!(verify_log(&path, other.verifying_key(), 2, None).is_err());
+    }
+
+    #[test]
+    fn test_anchor_detects_truncation() {
+        let dir = TempDir::new().unwrap();
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128f);
+        let verifying_key = key.verifying_key().clone();
+        let path = write_log(&dir, key);
+        let original = lines(&path);
+
+        let AuditRecord::Checkpoint(anchor) = serde_json::from_str(&original[5]).unwrap() else {
+            panic!("expected a checkpoint");
+        };
+
+        // Dropping the tail including its checkpoint leaves a valid-looking log
+        rewrite(&path, &original[..3]);
+        assert_eq!(verify_log(&path, &verifying_key, 2, None).unwrap().entries, 2);
+        assert!(verify_log(&path, &verifying_key, 2, Some(&anchor.checkpoint)).is_err());
+
+        rewrite(&path, &original);
+        assert!(verify_log(&path, &verifying_key, 2, Some(&anchor.checkpoint)).is_ok());
+    }
+}
@@ -0,0 +1,21 @@
+//! Key storage, rotation and audit services behind [`crate::crypto::kms::KeyManagementSystem`].
+
+pub mod audit;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+pub mod provider;
+pub mod rotate;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod store;
+pub mod transit;
+
+pub use audit::{verify_log, AuditEntry, AuditLog, AuditOperation, AuditOutcome, AuditRecord, AuditVerification};
+#[cfg(feature = "pkcs11")]
+pub use pkcs11::Pkcs11Provider;
+pub use provider::{MasterKeyProvider, SealedFileProvider, MASTER_KEY_LENGTH};
+pub use rotate::{RotationEngine, RotationEvent, RotationPolicy, RotationTrigger};
+pub use store::{FileKeyStore, KeyMetadata, KeyRecord, KeyStore, MemoryKeyStore};
+pub use transit::{MockTransitServer, VaultTransitProvider};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{KeyQuery, KeySummary, SqliteKeyStore};
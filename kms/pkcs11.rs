@@ -0,0 +1,246 @@
+//! Master-key provider backed by an ML-KEM key pair in a PKCS#11 slot.
+//!
+//! Wrapping runs C_EncapsulateKey (CKM_ML_KEM, PKCS#11 3.2) against the
+//! token's public key, which leaves an AES-256 session key on the token, and
+//! encrypts the store master key with it there (CKM_AES_GCM, the key label
+//! as AAD). Unwrapping needs C_DecapsulateKey with the private key and
+//! C_Decrypt with the session key; neither key ever leaves the token. Key
+//! versions are key pairs labelled `<label>:<version>`; rotation generates
+//! the next one with CKM_ML_KEM_KEY_PAIR_GEN, with a private, sensitive and
+//! non-extractable private key.
+//!
+//! Session keys are sensitive, non-extractable session objects, destroyed
+//! right after use. The module is loaded through the `cryptoki` client, so
+//! any PKCS#11 3.2 library with ML-KEM and AES-GCM works, including this
+//! crate's own provider.
+
+use super::provider::MasterKeyProvider;
+use crate::crypto::kms::KeyVersionId;
+use crate::crypto::secure::SecureSecret;
+use crate::error::KmsError;
+use cryptoki::context::{CInitializeArgs, CInitializeFlags, Pkcs11};
+use cryptoki::error::{Error as CryptokiError, RvError};
+use cryptoki::mechanism::aead::GcmParams;
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, KeyType, MlKemParameterSetType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::slot::Slot;
+use cryptoki::types::AuthPin;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const NONCE_LENGTH: usize = 12;
+const TAG_BITS: u64 = 128;
+const SESSION_KEY_LENGTH: u64 = 32;
+
+/// Wraps store master keys with ML-KEM key pairs in one PKCS#11 slot
+pub struct Pkcs11Provider {
+    pkcs11: Pkcs11,
+    slot: Slot,
+    key_label: String,
+    pin: Option<SecureSecret>,
+}
+
+// Wrapped master key: which key pair, the KEM ciphertext, and the AES-GCM output
+#[derive(Serialize, Deserialize)]
+struct WrappedKey {
+    key_label: String,
+    kem_ciphertext: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl Pkcs11Provider {
+    /// Loads the PKCS#11 library at `module` and uses the token labelled
+    /// `token_label`, or the first slot with a token when it is `None`.
+    /// Key pairs are named `<key_label>:<version>`.
+    pub fn open(
+        module: impl AsRef<Path>,
+        token_label: Option<&str>,
+        key_label: &str,
+        pin: Option<SecureSecret>,
+    ) -> Result<Self, KmsError> {
+        let pkcs11 = Pkcs11::new(module.as_ref()).map_err(unavailable)?;
+        pkcs11
+            .initialize(CInitializeArgs::new(CInitializeFlags::OS_LOCKING_OK))
+            .map_err(unavailable)?;
+        let mut slot = None;
+        for candidate in pkcs11.get_slots_with_token().map_err(unavailable)? {
+            let label = pkcs11.get_token_info(candidate).map_err(unavailable)?.label().trim().to_string();
+            if token_label.is_some_and(|wanted| wanted != label) {
+                continue;
+            }
+            slot = Some(candidate);
+            break;
+        }
+        let slot = slot.ok_or_else(|| {
+            KmsError::ProviderUnavailable(format!("no PKCS#11 token {}", token_label.unwrap_or("present")))
+        })?;
+        Ok(Self { pkcs11, slot, key_label: key_label.to_string(), pin })
+    }
+
+    /// Versions of the key pair on the token, ascending
+    pub fn versions(&self) -> Result<Vec<u32>, KmsError> {
+        let session = self.session()?;
+        let mut versions = Vec::new();
+        for handle in session.find_objects(&[Attribute::Class(ObjectClass::PUBLIC_KEY)]).map_err(unavailable)? {
+            let label = match session.get_attributes(handle, &[AttributeType::Label]).map_err(unavailable)?.as_slice() {
+                [Attribute::Label(label)] => String::from_utf8_lossy(label).into_owned(),
+                _ => continue,
+            };
+            if let Ok(id) = label.parse::<KeyVersionId>() {
+                if id.name == self.key_label {
+                    versions.push(id.version);
+                }
+            }
+        }
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    fn session(&self) -> Result<Session, KmsError> {
+        let session = self.pkcs11.open_rw_session(self.slot).map_err(unavailable)?;
+        if let Some(pin) = &self.pin {
+            let pin = pin
+                .with_exposed(|pin| String::from_utf8(pin.to_vec()))?
+                .map_err(|_| KmsError::ProviderUnavailable("PKCS#11 PIN is not UTF-8".to_string()))?;
+            // Logins are per token, so one from an earlier session may still hold
+            match session.login(UserType::User, Some(&AuthPin::from(pin))) {
+                Ok(()) | Err(CryptokiError::Pkcs11(RvError::UserAlreadyLoggedIn, _)) => {}
+                Err(e) => return Err(KmsError::AccessDenied(format!("{}: {}", self.describe(), e))),
+            }
+        }
+        Ok(session)
+    }
+
+    fn find_key(&self, session: &Session, class: ObjectClass, label: &str) -> Result<ObjectHandle, KmsError> {
+        let found = session
+            .find_objects(&[Attribute::Class(class), Attribute::Label(label.as_bytes().to_vec())])
+            .map_err(unavailable)?;
+        found
+            .first()
+            .copied()
+            .ok_or_else(|| KmsError::UnsealFailed(format!("{} has no key {}", self.describe(), label)))
+    }
+
+    // Runs one AES-GCM operation with a session key on the token, then destroys the key
+    fn with_session_key<R>(
+        &self,
+        session: &Session,
+        session_key: ObjectHandle,
+        nonce: &[u8],
+        key_label: &str,
+        operation: impl FnOnce(&Mechanism) -> Result<R, KmsError>,
+    ) -> Result<R, KmsError> {
+        let mut iv = nonce.to_vec();
+        let result = GcmParams::new(&mut iv, key_label.as_bytes(), TAG_BITS.into())
+            .map_err(unavailable)
+            .and_then(|params| operation(&Mechanism::AesGcm(params)));
+        session.destroy_object(session_key).map_err(unavailable)?;
+        result
+    }
+}
+
+impl MasterKeyProvider for Pkcs11Provider {
+    fn describe(&self) -> String {
+        format!("pkcs11:slot {}/{}", self.slot, self.key_label)
+    }
+
+    /// Wraps under the newest key pair, generating version 1 on first use
+    fn wrap(&self, key: &SecureSecret) -> Result<Vec<u8>, KmsError> {
+        let version = match self.versions()?.last() {
+            Some(version) => *version,
+            None => {
+                self.rotate()?;
+                1
+            }
+        };
+        let key_label = KeyVersionId::new(&self.key_label, version).to_string();
+        let session = self.session()?;
+        let public_key = self.find_key(&session, ObjectClass::PUBLIC_KEY, &key_label)?;
+        let (kem_ciphertext, session_key) = session
+            .encapsulate_key(&Mechanism::MlKem, public_key, &session_key_template())
+            .map_err(unavailable)?;
+
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self.with_session_key(&session, session_key, &nonce, &key_label, |mechanism| {
+            key.with_exposed(|plaintext| session.encrypt(mechanism, session_key, plaintext))?
+                .map_err(|e| KmsError::SealFailed(format!("{}: {}", key_label, e)))
+        })?;
+        let wrapped = WrappedKey { key_label, kem_ciphertext, nonce: nonce.to_vec(), ciphertext };
+        serde_json::to_vec(&wrapped).map_err(|e| KmsError::SealFailed(e.to_string()))
+    }
+
+    fn unwrap(&self, wrapped: &[u8]) -> Result<SecureSecret, KmsError> {
+        let wrapped: WrappedKey =
+            serde_json::from_slice(wrapped).map_err(|e| KmsError::UnsealFailed(format!("malformed wrapped key: {}", e)))?;
+        let id: KeyVersionId = wrapped.key_label.parse()?;
+        if id.name != self.key_label || wrapped.nonce.len() != NONCE_LENGTH {
+            return Err(KmsError::UnsealFailed(format!("{} is not a {} key", wrapped.key_label, self.describe())));
+        }
+        let session = self.session()?;
+        let private_key = self.find_key(&session, ObjectClass::PRIVATE_KEY, &wrapped.key_label)?;
+        let session_key = session
+            .decapsulate_key(&Mechanism::MlKem, private_key, &session_key_template(), &wrapped.kem_ciphertext)
+            .map_err(|e| KmsError::UnsealFailed(format!("{}: {}", wrapped.key_label, e)))?;
+
+        let plaintext = self.with_session_key(&session, session_key, &wrapped.nonce, &wrapped.key_label, |mechanism| {
+            session
+                .decrypt(mechanism, session_key, &wrapped.ciphertext)
+                .map(Zeroizing::new)
+                .map_err(|_| KmsError::UnsealFailed(wrapped.key_label.clone()))
+        })?;
+        Ok(SecureSecret::from_bytes(&plaintext))
+    }
+
+    fn rotate(&self) -> Result<(), KmsError> {
+        let version = self.versions()?.last().map_or(1, |latest| latest + 1);
+        let label = KeyVersionId::new(&self.key_label, version).to_string().into_bytes();
+        let public_template = [
+            Attribute::Token(true),
+            Attribute::ParameterSet(MlKemParameterSetType::ML_KEM_1024.into()),
+            Attribute::Encapsulate(true),
+            Attribute::Label(label.clone()),
+        ];
+        let private_template = [
+            Attribute::Token(true),
+            Attribute::Private(true),
+            Attribute::Sensitive(true),
+            Attribute::Extractable(false),
+            Attribute::Decapsulate(true),
+            Attribute::Label(label),
+        ];
+        self.session()?
+            .generate_key_pair(&Mechanism::MlKemKeyPairGen, &public_template, &private_template)
+            .map(|_| ())
+            .map_err(unavailable)
+    }
+}
+
+impl fmt::Debug for Pkcs11Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pkcs11Provider({})", self.describe())
+    }
+}
+
+// AES-256 session key that can encrypt and decrypt on the token but never be read
+fn session_key_template() -> [Attribute; 8] {
+    [
+        Attribute::Class(ObjectClass::SECRET_KEY),
+        Attribute::KeyType(KeyType::AES),
+        Attribute::ValueLen(SESSION_KEY_LENGTH.into()),
+        Attribute::Token(false),
+        Attribute::Sensitive(true),
+        Attribute::Extractable(false),
+        Attribute::Encrypt(true),
+        Attribute::Decrypt(true),
+    ]
+}
+
+fn unavailable(e: CryptokiError) -> KmsError {
+    KmsError::ProviderUnavailable(format!("PKCS#11: {}", e))
+}
@@ -0,0 +1,195 @@
+//! Master-key providers: where the key that opens a [`FileKeyStore`] lives.
+//!
+//! A file store is encrypted under one 256-bit master key. Rather than hand
+//! that key to the process, the store can keep it wrapped by a
+//! [`MasterKeyProvider`] in `<root>/MASTER` and ask the provider to unwrap it
+//! on open (see [`FileKeyStore::open_with_provider`]). The provider's own key
+//! never has to enter this process:
+//!
+//! | provider                                   | wrapping key                         |
+//! |--------------------------------------------|--------------------------------------|
+//! | [`SealedFileProvider`]                     | Kyber-1024 KEKs in passphrase-protected PKCS#8 files |
+//! | [`Pkcs11Provider`](super::pkcs11::Pkcs11Provider) | ML-KEM key pair in a PKCS#11 slot (`pkcs11` feature) |
+//! | [`VaultTransitProvider`](super::transit::VaultTransitProvider) | named key in a Vault Transit-compatible service |
+//!
+//! Providers are versioned: [`rotate`](MasterKeyProvider::rotate) starts
+//! wrapping under a new version while older wrapped keys still unwrap, and
+//! [`FileKeyStore::rewrap_master_key`] moves the store's wrapped key to the
+//! newest version. The store master key itself does not change.
+//!
+//! [`FileKeyStore`]: super::FileKeyStore
+//! [`FileKeyStore::open_with_provider`]: super::FileKeyStore::open_with_provider
+//! [`FileKeyStore::rewrap_master_key`]: super::FileKeyStore::rewrap_master_key
+
+use crate::crypto::kms::{embedded_public_key, KeyVersionId};
+use crate::crypto::secure::pkcs8::{self, Argon2Params, SecretKeyAlgorithm};
+use crate::crypto::secure::{KeyEncryptionKey, SealedSecret, SecureSecret};
+use crate::error::KmsError;
+use pqcrypto_kyber::kyber1024;
+use pqcrypto_traits::kem::PublicKey as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Length of a store master key
+pub const MASTER_KEY_LENGTH: usize = 32;
+
+const KEK_NAME: &str = "master-kek";
+const KEK_EXTENSION: &str = "pem";
+const MASTER_KEY_PURPOSE: &str = "kms/master-key";
+
+/// Wraps and unwraps a store master key under a key held elsewhere
+pub trait MasterKeyProvider: Send + Sync {
+    /// Names the provider and its key in logs and errors
+    fn describe(&self) -> String;
+
+    /// Wraps `key` under the newest version of the provider's key
+    fn wrap(&self, key: &SecureSecret) -> Result<Vec<u8>, KmsError>;
+
+    /// Unwraps a key wrapped by [`wrap`](Self::wrap) under any version
+    fn unwrap(&self, wrapped: &[u8]) -> Result<SecureSecret, KmsError>;
+
+    /// Adds a new version of the provider's key and wraps under it from now on
+    fn rotate(&self) -> Result<(), KmsError>;
+
+    /// Rewraps under the newest version. Providers that can do this without
+    /// the key leaving them override the default unwrap-and-wrap.
+    fn rewrap(&self, wrapped: &[u8]) -> Result<Vec<u8>, KmsError> {
+        self.wrap(&self.unwrap(wrapped)?)
+    }
+}
+
+/// Kyber-1024 key-encryption keys in a local directory, one passphrase-
+/// protected PKCS#8 file per version (`master-kek-<version>.pem`). The
+/// passphrase is the secret that has to be supplied to open the store;
+/// each wrap and unwrap pays one Argon2id derivation.
+pub struct SealedFileProvider {
+    dir: PathBuf,
+    passphrase: SecureSecret,
+    params: Argon2Params,
+}
+
+impl SealedFileProvider {
+    /// Opens the KEK directory, creating it and a first KEK if it is empty
+    pub fn open(dir: impl AsRef<Path>, passphrase: SecureSecret) -> Result<Self, KmsError> {
+        Self::open_with_params(dir, passphrase, Argon2Params::default())
+    }
+
+    pub fn open_with_params(dir: impl AsRef<Path>, passphrase: SecureSecret, params: Argon2Params) -> Result<Self, KmsError> {
+        params.check().map_err(|e| KmsError::ProviderUnavailable(e.to_string()))?;
+        let provider = Self { dir: dir.as_ref().to_path_buf(), passphrase, params };
+        fs::create_dir_all(&provider.dir).map_err(|e| provider.unavailable(e))?;
+        if provider.versions()?.is_empty() {
+            provider.rotate()?;
+        }
+        Ok(provider)
+    }
+
+    /// Versions present in the directory, ascending
+    pub fn versions(&self) -> Result<Vec<u32>, KmsError> {
+        let mut versions = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(|e| self.unavailable(e))? {
+            let path = entry.map_err(|e| self.unavailable(e))?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(KEK_EXTENSION) {
+                continue;
+            }
+            let version = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.strip_prefix(KEK_NAME)?.strip_prefix('-')?.parse::<u32>().ok());
+            versions.extend(version);
+        }
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    fn kek_path(&self, version: u32) -> PathBuf {
+        self.dir.join(format!("{}-{}.{}", KEK_NAME, version, KEK_EXTENSION))
+    }
+
+    fn load(&self, version: u32) -> Result<KeyEncryptionKey, KmsError> {
+        let pem = fs::read_to_string(self.kek_path(version)).map_err(|e| self.unavailable(e))?;
+        let (_, secret_key) = pkcs8::import_encrypted_pem(&pem, &self.passphrase)
+            .map_err(|e| KmsError::UnsealFailed(format!("{} version {}: {}", self.describe(), version, e)))?;
+        let public_key = embedded_public_key(&secret_key).map_err(|e| KmsError::UnsealFailed(e.to_string()))?;
+        let kek_id = KeyVersionId::new(KEK_NAME, version).to_string();
+        secret_key
+            .with_exposed(|sk| KeyEncryptionKey::from_bytes(&kek_id, public_key.as_bytes(), sk))?
+            .map_err(|e| KmsError::UnsealFailed(e.to_string()))
+    }
+
+    fn unavailable(&self, e: impl std::fmt::Display) -> KmsError {
+        KmsError::ProviderUnavailable(format!("{}: {}", self.describe(), e))
+    }
+}
+
+impl MasterKeyProvider for SealedFileProvider {
+    fn describe(&self) -> String {
+        format!("sealed-file:{}", self.dir.display())
+    }
+
+    fn wrap(&self, key: &SecureSecret) -> Result<Vec<u8>, KmsError> {
+        let version = self.versions()?.last().copied().ok_or_else(|| self.unavailable("no KEK"))?;
+        let kek = self.load(version)?;
+        let sealed = key
+            .seal(kek.public_key(), MASTER_KEY_PURPOSE)
+            .map_err(|_| KmsError::SealFailed(kek.key_id().to_string()))?;
+        serde_json::to_vec(&sealed).map_err(|e| KmsError::SealFailed(e.to_string()))
+    }
+
+    fn unwrap(&self, wrapped: &[u8]) -> Result<SecureSecret, KmsError> {
+        let sealed: SealedSecret =
+            serde_json::from_slice(wrapped).map_err(|e| KmsError::UnsealFailed(format!("malformed wrapped key: {}", e)))?;
+        let key_id: KeyVersionId = sealed.kek_id.parse()?;
+        if key_id.name != KEK_NAME {
+            return Err(KmsError::UnsealFailed(format!("{} is not a {} key", sealed.kek_id, self.describe())));
+        }
+        self.load(key_id.version)?
+            .unseal(&sealed, MASTER_KEY_PURPOSE)
+            .map_err(|_| KmsError::UnsealFailed(sealed.kek_id.clone()))
+    }
+
+    fn rotate(&self) -> Result<(), KmsError> {
+        let version = self.versions()?.last().map_or(1, |latest| latest + 1);
+        let (_, secret_key) = kyber1024::keypair();
+        let pem = pkcs8::export_encrypted_pem(
+            SecretKeyAlgorithm::Kyber1024,
+            &SecureSecret::from_decapsulation_key(&secret_key),
+            &self.passphrase,
+            &self.params,
+        )
+        .map_err(|e| KmsError::SealFailed(e.to_string()))?;
+        // create_new: two rotations racing must not overwrite each other's KEK
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.kek_path(version))
+            .and_then(|mut file| std::io::Write::write_all(&mut file, pem.as_bytes()).and_then(|_| file.sync_all()))
+            .map_err(|e| self.unavailable(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_file_provider_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let passphrase = SecureSecret::from_bytes(b"correct horse battery staple");
+        let provider = SealedFileProvider::open_with_params(dir.path(), passphrase, Argon2Params::MINIMUM).unwrap();
+        let key = SecureSecret::from_bytes(&[9u8; MASTER_KEY_LENGTH]);
+
+        let wrapped = provider.wrap(&key).unwrap();
+        provider.rotate().unwrap();
+        assert_eq!(provider.versions().unwrap(), vec![1, 2]);
+        assert!(provider.unwrap(&wrapped).unwrap().constant_time_eq(&key));
+        let rewrapped = provider.rewrap(&wrapped).unwrap();
+        let sealed: SealedSecret = serde_json::from_slice(&rewrapped).unwrap();
+        assert_eq!(sealed.kek_id, "master-kek:2");
+        assert!(provider.unwrap(&rewrapped).unwrap().constant_time_eq(&key));
+
+        let wrong = SecureSecret::from_bytes(b"incorrect horse battery staple");
+        let reopened = SealedFileProvider::open_with_params(dir.path(), wrong, Argon2Params::MINIMUM).unwrap();
+        assert!(matches!(reopened.unwrap(&wrapped), Err(KmsError::UnsealFailed(_))));
+    }
+}
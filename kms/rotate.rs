@@ -0,0 +1,446 @@
+//! Policy-driven rotation of versioned KMS keys.
+//!
+//! A [`RotationEngine`] owns a [`RotationPolicy`] per versioned key name and
+//! rotates a key when its current version is older than `max_age`, has been
+//! used for `max_encapsulations` encapsulations, has reached a soft limit of
+//! the KMS usage policy, or on demand. Rotation adds
+//! version `n + 1` and deactivates version `n`, which stays decrypt-only. If
+//! the policy sets a `grace_period`, superseded versions are destroyed once it
+//! has elapsed; without one they are kept indefinitely.
+//!
+//! Every rotation, retirement and failure is broadcast as a [`RotationEvent`]
+//! for the audit log and other subscribers. [`RotationEngine::spawn`] runs the
+//! checks periodically on the tokio runtime.
+//!
+//! The engine calls the KMS as its own principal, which needs Describe,
+//! Rotate and Destroy on the keys it manages. If retirement is enabled, that
+//! principal must also be exempt from four-eyes approval, since nobody is
+//! around to approve a scheduled destruction.
+//!
+//! Usage counters are kept in memory by the KMS, so the
+//! `max_encapsulations` limit restarts from zero with the process.
+
+use crate::crypto::kms::{versioned_key_id, CallerContext, KeyManagementSystem, KeyState, TransitionReason};
+use crate::error::KmsError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// When a versioned key is rotated and how long superseded versions are kept
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RotationPolicy {
+    pub max_age: Option<Duration>,
+    pub max_encapsulations: Option<u64>,
+    /// How long a superseded version stays decrypt-only before it is destroyed
+    pub grace_period: Option<Duration>,
+}
+
+/// What caused a rotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RotationTrigger {
+    MaxAge,
+    MaxEncapsulations,
+    /// Soft limit of the KMS [`UsagePolicy`](crate::crypto::kms::UsagePolicy)
+    UsageLimit,
+    OnDemand,
+}
+
+/// Outcome of a rotation step, broadcast to subscribers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RotationEvent {
+    Rotated {
+        name: String,
+        from_version: u32,
+        to_version: u32,
+        trigger: RotationTrigger,
+        at: u64,
+    },
+    /// A superseded version was destroyed after its grace period
+    Retired { name: String, version: u32, at: u64 },
+    Failed {
+        name: String,
+        trigger: Option<RotationTrigger>,
+        error: String,
+        at: u64,
+    },
+}
+
+/// Rotates versioned keys of a shared KMS according to per-key policies
+pub struct RotationEngine {
+    kms: Arc<Mutex<KeyManagementSystem>>,
+    caller: CallerContext,
+    policies: Mutex<HashMap<String, RotationPolicy>>,
+    events: broadcast::Sender<RotationEvent>,
+}
+
+impl RotationPolicy {
+    /// Which limit, if any, the current version has reached
+    pub fn due(&self, created_at: u64, encapsulations: u64, now: u64) -> Option<RotationTrigger> {
+        if self.max_encapsulations.is_some_and(|max| encapsulations >= max) {
+            return Some(RotationTrigger::MaxEncapsulations);
+        }
+        if self.max_age.is_some_and(|max| now.saturating_sub(created_at) >= max.as_secs()) {
+            return Some(RotationTrigger::MaxAge);
+        }
+        None
+    }
+}
+
+impl RotationEngine {
+    /// Engine acting on `kms` as `caller`
+    pub fn new(kms: Arc<Mutex<KeyManagementSystem>>, caller: CallerContext) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            kms,
+            caller,
+            policies: Mutex::new(HashMap::new()),
+            events,
+        }
+    }
+
+    /// Sets or replaces the policy for the versioned key `name`
+    pub fn set_policy(&self, name: &str, policy: RotationPolicy) {
+        if let Ok(mut policies) = self.policies.lock() {
+            policies.insert(name.to_string(), policy);
+        }
+    }
+
+    pub fn remove_policy(&self, name: &str) -> Option<RotationPolicy> {
+        self.policies.lock().ok()?.remove(name)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RotationEvent> {
+        self.events.subscribe()
+    }
+
+    /// Rotates `name` immediately and returns the new version
+    pub fn rotate_now(&self, name: &str) -> Result<u32, KmsError> {
+        let mut events = Vec::new();
+        self.rotate(name, RotationTrigger::OnDemand, &mut events).inspect_err(|e| {
+            let event = RotationEvent::Failed {
+                name: name.to_string(),
+                trigger: Some(RotationTrigger::OnDemand),
+                error: e.to_string(),
+                at: now(),
+            };
+            self.emit(event, &mut events);
+        })
+    }
+
+    /// Applies every policy once at the current time
+    pub fn run_once(&self) -> Vec<RotationEvent> {
+        self.evaluate_at(now())
+    }
+
+    /// Applies every policy once as if the time were `now` (seconds since the epoch)
+    pub fn evaluate_at(&self, now: u64) -> Vec<RotationEvent> {
+        let policies: Vec<(String, RotationPolicy)> = match self.policies.lock() {
+            Ok(policies) => policies.iter().map(|(name, p)| (name.clone(), p.clone())).collect(),
+            Err(_) => return Vec::new(),
+        };
+
+        let mut events = Vec::new();
+        for (name, policy) in policies {
+            let mut trigger = None;
+            if let Err(e) = self.apply_policy(&name, &policy, now, &mut trigger, &mut events) {
+                let event = RotationEvent::Failed {
+                    name,
+                    trigger,
+                    error: e.to_string(),
+                    at: now,
+                };
+                self.emit(event, &mut events);
+            }
+        }
+        events
+    }
+
+    /// Runs [`run_once`](Self::run_once) every `interval` until the handle is aborted
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                for event in self.run_once() {
+                    if let RotationEvent::Failed { name, error, .. } = &event {
+                        tracing::warn!(key = %name, %error, "scheduled key rotation failed");
+                    }
+                }
+            }
+        })
+    }
+
+    fn kms(&self) -> Result<MutexGuard<'_, KeyManagementSystem>, KmsError> {
+        self.kms
+            .lock()
+            .map_err(|_| KmsError::Storage("KMS lock poisoned".to_string()))
+    }
+
+    // `trigger` is set before rotating so failures can be attributed to it
+    fn apply_policy(
+        &self,
+        name: &str,
+        policy: &RotationPolicy,
+        now: u64,
+        trigger: &mut Option<RotationTrigger>,
+        events: &mut Vec<RotationEvent>,
+    ) -> Result<(), KmsError> {
+        *trigger = self.due(name, policy, now)?;
+        if let Some(trigger) = *trigger {
+            self.rotate(name, trigger, events)?;
+        }
+        if let Some(grace) = policy.grace_period {
+            self.retire_superseded(name, grace, now, events)?;
+        }
+        Ok(())
+    }
+
+    fn due(&self, name: &str, policy: &RotationPolicy, now: u64) -> Result<Option<RotationTrigger>, KmsError> {
+        let kms = self.kms()?;
+        let key_id = versioned_key_id(name, kms.current_version(&self.caller, name)?);
+        let metadata = kms.key_metadata(&self.caller, &key_id)?;
+        let usage = kms.key_usage(&self.caller, &key_id)?;
+        if usage.soft_limit_reached {
+            return Ok(Some(RotationTrigger::UsageLimit));
+        }
+        Ok(policy.due(metadata.created_at, usage.counters.encapsulations, now))
+    }
+
+    fn rotate(&self, name: &str, trigger: RotationTrigger, events: &mut Vec<RotationEvent>) -> Result<u32, KmsError> {
+        let (from_version, to_version) = {
+            let mut kms = self.kms()?;
+            (kms.current_version(&self.caller, name)?, kms.rotate_version(&self.caller, name)?)
+        };
+        let event = RotationEvent::Rotated {
+            name: name.to_string(),
+            from_version,
+            to_version,
+            trigger,
+            at: now(),
+        };
+        self.emit(event, events);
+        Ok(to_version)
+    }
+
+    // Destroys superseded versions whose grace period has run out
+    fn retire_superseded(
+        &self,
+        name: &str,
+        grace: Duration,
+        now: u64,
+        events: &mut Vec<RotationEvent>,
+    ) -> Result<(), KmsError> {
+        let mut kms = self.kms()?;
+        let versions = kms.key_versions(&self.caller, name)?;
+        let Some((_, previous)) = versions.split_last() else {
+            return Ok(());
+        };
+
+        for &version in previous {
+            let key_id = versioned_key_id(name, version);
+            let metadata = kms.key_metadata(&self.caller, &key_id)?;
+            let superseded_at = metadata
+                .transitions
+                .iter()
+                .rev()
+                .find(|t| t.to == KeyState::Deactivated && t.reason == TransitionReason::Superseded)
+                .map(|t| t.at);
+
+            let expired = metadata.status == KeyState::Deactivated
+                && superseded_at.is_some_and(|at| now.saturating_sub(at) >= grace.as_secs());
+            if expired {
+                kms.transition_key(&self.caller, &key_id, KeyState::Destroyed, TransitionReason::Expired)?;
+                let event = RotationEvent::Retired {
+                    name: name.to_string(),
+                    version,
+                    at: now,
+                };
+                self.emit(event, events);
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&self, event: RotationEvent, events: &mut Vec<RotationEvent>) {
+        // No subscribers is not an error
+        let _ = self.events.send(event.clone());
+        events.push(event);
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::access::{administrator_policy, test_kms};
+    use crate::crypto::kms::{ApprovalPolicy, KeyOperation, KmsAction, Role};
+    use crate::crypto::secure::SecureSecret;
+
+    fn engine_with_key(name: &str) -> (Arc<Mutex<KeyManagementSystem>>, RotationEngine, CallerContext) {
+        let admin = CallerContext::new("kms-admin");
+        let mut kms = KeyManagementSystem::new();
+        kms.set_access_policy(
+            administrator_policy(&admin.principal)
+                .with_role(Role::new("rotation").allow("*", [KmsAction::Describe, KmsAction::Rotate, KmsAction::Destroy]))
+                .assign("kms-rotation", "rotation"),
+        );
+        kms.set_approval_policy(ApprovalPolicy::default().exempt(&admin.principal).exempt("kms-rotation"));
+        kms.generate_versioned_kem_key(&admin, name).unwrap();
+
+        let kms = Arc::new(Mutex::new(kms));
+        let engine = RotationEngine::new(kms.clone(), CallerContext::new("kms-rotation"));
+        (kms, engine, admin)
+    }
+
+    #[test]
+    fn test_max_age_rotation_keeps_old_version_decrypt_only() {
+        let (kms, engine, admin) = engine_with_key("settlement");
+        let (shared, ciphertext) = kms.lock().unwrap().encapsulate(&admin, "settlement:1").unwrap();
+
+        engine.set_policy("settlement", RotationPolicy {
+            max_age: Some(Duration::from_secs(30 * 24 * 3600)),
+            ..RotationPolicy::default()
+        });
+        assert!(engine.run_once().is_empty());
+
+        let events = engine.evaluate_at(now() + 31 * 24 * 3600);
+        assert!(matches!(
+            events.as_slice(),
+            [RotationEvent::Rotated { from_version: 1, to_version: 2, trigger: RotationTrigger::MaxAge, .. }]
+        ));
+
+        let mut kms = kms.lock().unwrap();
+        assert_eq!(kms.key_versions(&admin, "settlement").unwrap(), vec![1, 2]);
+        assert_eq!(kms.key_state(&admin, "settlement:1").unwrap(), KeyState::Deactivated);
+        assert_eq!(kms.decapsulate(&admin, "settlement:1", &ciphertext).unwrap(), shared);
+        assert!(kms.encapsulate(&admin, "settlement:1").is_err());
+        assert!(kms.encapsulate(&admin, "settlement:2").is_ok());
+    }
+
+    #[test]
+    fn test_encapsulation_limit_and_grace_period() {
+        let (kms, engine, admin) = engine_with_key("inbound");
+        engine.set_policy("inbound", RotationPolicy {
+            max_encapsulations: Some(2),
+            grace_period: Some(Duration::from_secs(3600)),
+            ..RotationPolicy::default()
+        });
+
+        kms.lock().unwrap().encapsulate(&admin, "inbound:1").unwrap();
+        assert!(engine.run_once().is_empty());
+        kms.lock().unwrap().encapsulate(&admin, "inbound:1").unwrap();
+        assert!(matches!(
+            engine.run_once().as_slice(),
+            [RotationEvent::Rotated { trigger: RotationTrigger::MaxEncapsulations, to_version: 2, .. }]
+        ));
+
+        // Inside the grace window version 1 is still there; after it, it is destroyed
+        assert!(engine.evaluate_at(now() + 60).is_empty());
+        let events = engine.evaluate_at(now() + 3600);
+        assert!(matches!(events.as_slice(), [RotationEvent::Retired { version: 1, .. }]));
+
+        let kms = kms.lock().unwrap();
+        assert_eq!(kms.key_state(&admin, "inbound:1").unwrap(), KeyState::Destroyed);
+        assert!(kms.get_secret(&admin, "inbound:2", KeyOperation::Decapsulate).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_soft_usage_limit_triggers_rotation() {
+        use crate::crypto::kms::{UsageLimits, UsagePolicy};
+
+        let (kms, engine, admin) = engine_with_key("inbound");
+        let soft = UsageLimits { max_bytes_protected: Some(1024), ..UsageLimits::default() };
+        kms.lock().unwrap().set_usage_policy(UsagePolicy::new().with_quota("inbound:*", soft, UsageLimits::default()));
+        engine.set_policy("inbound", RotationPolicy::default());
+
+        kms.lock().unwrap().encrypt(&admin, "inbound", &[0u8; 1024], b"").unwrap();
+        assert!(matches!(
+            engine.run_once().as_slice(),
+            [RotationEvent::Rotated { trigger: RotationTrigger::UsageLimit, to_version: 2, .. }]
+        ));
+        assert!(engine.run_once().is_empty());
+    }
+
+    #[test]
+    fn test_on_demand_rotation_and_failures() {
+        let (kms, engine, admin) = engine_with_key("reporting");
+        let mut events = engine.subscribe();
+
+        assert_eq!(engine.rotate_now("reporting").unwrap(), 2);
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            RotationEvent::Rotated { trigger: RotationTrigger::OnDemand, .. }
+        ));
+
+        assert!(matches!(engine.rotate_now("missing"), Err(KmsError::KeyNotFound(_))));
+        assert!(matches!(events.try_recv().unwrap(), RotationEvent::Failed { .. }));
+
+        // A compromised current version cannot be rotated past
+        kms.lock()
+            .unwrap()
+            .transition_key(&admin, "reporting:2", KeyState::Compromised, TransitionReason::KeyCompromise)
+            .unwrap();
+        assert!(engine.rotate_now("reporting").is_err());
+    }
+
+    #[test]
+    fn test_rotate_secret_keeps_previous_version() {
+        let (mut kms, admin) = test_kms();
+        kms.add_secret(&admin, &versioned_key_id("archive", 1), SecureSecret::from_bytes(&[1u8; 32])).unwrap();
+        kms.rotate_secret(&admin, "archive", SecureSecret::from_bytes(&[2u8; 32])).unwrap();
+
+        assert_eq!(kms.key_versions(&admin, "archive").unwrap(), vec![1, 2]);
+        assert_eq!(
+            kms.get_secret(&admin, "archive:1", KeyOperation::Decrypt).unwrap(),
+            Some(SecureSecret::from_bytes(&[1u8; 32]))
+        );
+        assert!(kms.get_secret(&admin, "archive:1", KeyOperation::Encrypt).is_err());
+        assert_eq!(kms.key_state(&admin, "archive:1").unwrap(), KeyState::Deactivated);
+        assert_eq!(kms.key_state(&admin, "archive:2").unwrap(), KeyState::Active);
+
+        // Replacing an unversioned key would strand what it protects
+        kms.add_secret(&admin, "legacy", SecureSecret::from_bytes(&[3u8; 32])).unwrap();
+        assert!(matches!(
+            kms.rotate_secret(&admin, "legacy", SecureSecret::from_bytes(&[4u8; 32])),
+            Err(KmsError::OperationNotPermitted(_))
+        ));
+        assert_eq!(
+            kms.get_secret(&admin, "legacy", KeyOperation::Decrypt).unwrap(),
+            Some(SecureSecret::from_bytes(&[3u8; 32]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_rotation_emits_events() {
+        let (kms, engine, admin) = engine_with_key("settlement");
+        engine.set_policy("settlement", RotationPolicy {
+            max_encapsulations: Some(1),
+            ..RotationPolicy::default()
+        });
+        let engine = Arc::new(engine);
+        let mut events = engine.subscribe();
+        let handle = engine.clone().spawn(Duration::from_millis(10));
+
+        kms.lock().unwrap().encapsulate(&admin, "settlement:1").unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("No rotation within timeout")
+            .unwrap();
+        handle.abort();
+
+        assert!(matches!(event, RotationEvent::Rotated { to_version: 2, .. }));
+    }
+}
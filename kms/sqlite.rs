@@ -0,0 +1,711 @@
+//! SQLite-backed [`KeyStore`] for deployments where several KMS instances
+//! share one database file.
+//!
+//! Key material is AES-256-GCM encrypted per row under a key derived from
+//! the master key, with the key id, version and algorithm as associated data.
+//! Metadata lives in plain, indexed columns so keys can be queried by status,
+//! algorithm, owner and expiry without decrypting anything; usage counters
+//! are a JSON column next to them.
+//!
+//! Every key keeps its full version history. [`SqliteKeyStore::rotate`] inserts the
+//! new version and deactivates the old one in a single transaction,
+//! [`KeyStore::delete`] removes only the current version, and
+//! [`KeyStore::put_batch`] writes all its records and the backup watermark
+//! in one.
+//!
+//! The schema version is tracked in `PRAGMA user_version`. Pending
+//! [`MIGRATIONS`] are applied in order inside one transaction when the store
+//! is opened.
+
+use super::store::{KeyMetadata, KeyRecord, KeyStore};
+use crate::crypto::kms::lifecycle::{KeyState, TransitionReason};
+use crate::crypto::kms::usage::UsageCounters;
+use crate::crypto::secure::{register_label, Aes256Key, IdleProtection, SecureSecret};
+use crate::error::KmsError;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+const NONCE_LENGTH: usize = 12;
+const SQLITE_LABEL: &[u8] = b"kms/sqlite/record";
+const SQLITE_LABEL_OWNER: &str = "kms::sqlite";
+const STORE_CHECK_NAME: &str = "store-check";
+const WATERMARK_NAME: &str = "backup-watermark";
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Schema migrations; entry `n` upgrades the schema from version `n` to `n + 1`
+pub const MIGRATIONS: &[&str] = &[
+    // 1: keys with versions, store metadata, query indexes
+    "CREATE TABLE store_meta (
+         name  TEXT PRIMARY KEY,
+         value BLOB NOT NULL
+     );
+     CREATE TABLE keys (
+         key_id     TEXT    NOT NULL,
+         version    INTEGER NOT NULL,
+         algorithm  TEXT    NOT NULL,
+         owner      TEXT,
+         status     TEXT    NOT NULL,
+         created_at INTEGER NOT NULL,
+         expires_at INTEGER,
+         nonce      BLOB    NOT NULL,
+         ciphertext BLOB    NOT NULL,
+         PRIMARY KEY (key_id, version)
+     );
+     CREATE INDEX idx_keys_status ON keys(status);
+     CREATE INDEX idx_keys_algorithm ON keys(algorithm);
+     CREATE INDEX idx_keys_owner ON keys(owner);
+     CREATE INDEX idx_keys_expires_at ON keys(expires_at);",
+    // 2: record when a version was rotated out
+    "ALTER TABLE keys ADD COLUMN deactivated_at INTEGER;",
+    // 3: lifecycle transition history as JSON
+    "ALTER TABLE keys ADD COLUMN transitions TEXT NOT NULL DEFAULT '[]';",
+    // 4: minimum decryptable version pinned on versioned keys
+    "ALTER TABLE keys ADD COLUMN minimum_version INTEGER;",
+    // 5: usage counters as JSON
+    "ALTER TABLE keys ADD COLUMN usage TEXT NOT NULL DEFAULT '{}';",
+];
+
+/// Filter for [`SqliteKeyStore::find`]; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyQuery {
+    pub status: Option<KeyState>,
+    pub algorithm: Option<String>,
+    pub owner: Option<String>,
+    /// Only keys whose expiry is set and strictly before this timestamp
+    pub expires_before: Option<u64>,
+}
+
+/// Metadata of one stored key version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySummary {
+    pub key_id: String,
+    pub version: u32,
+    pub metadata: KeyMetadata,
+}
+
+/// Key store on a single SQLite database file
+pub struct SqliteKeyStore {
+    connection: Mutex<Connection>,
+    record_key: SecureSecret,
+}
+
+impl SqliteKeyStore {
+    /// Opens (or creates) the database, applies pending migrations and checks the master key
+    pub fn open(path: impl AsRef<Path>, master_key: &SecureSecret) -> Result<Self, KmsError> {
+        let mut connection = Connection::open(path).map_err(sql_error)?;
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(sql_error)?;
+        connection
+            .query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))
+            .map_err(sql_error)?;
+        migrate(&mut connection)?;
+
+        register_label(SQLITE_LABEL, SQLITE_LABEL_OWNER).map_err(|e| KmsError::Storage(e.to_string()))?;
+        let record_key = master_key
+            .derive::<Aes256Key>(SQLITE_LABEL_OWNER, SQLITE_LABEL, &[])
+            .map_err(|e| KmsError::Storage(e.to_string()))?;
+
+        let store = Self {
+            connection: Mutex::new(connection),
+            record_key,
+        };
+        store.check_master_key()?;
+        Ok(store)
+    }
+
+    /// Current schema version of the database
+    pub fn schema_version(&self) -> Result<u32, KmsError> {
+        schema_version(&*self.connection()?)
+    }
+
+    /// Metadata of the current version of every key matching the query
+    pub fn find(&self, query: &KeyQuery) -> Result<Vec<KeySummary>, KmsError> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT k.key_id, k.version, k.algorithm, k.owner, k.status, k.created_at, k.expires_at, k.transitions,
+                        k.minimum_version, k.usage
+                 FROM keys k
+                 WHERE k.version = (SELECT MAX(version) FROM keys WHERE key_id = k.key_id)
+                   AND (?1 IS NULL OR k.status = ?1)
+                   AND (?2 IS NULL OR k.algorithm = ?2)
+                   AND (?3 IS NULL OR k.owner = ?3)
+                   AND (?4 IS NULL OR (k.expires_at IS NOT NULL AND k.expires_at < ?4))
+                 ORDER BY k.key_id",
+            )
+            .map_err(sql_error)?;
+
+        let rows = statement
+            .query_map(
+                params![query.status.map(|s| s.as_str()), query.algorithm, query.owner, query.expires_before.map(|t| t as i64)],
+                read_summary,
+            )
+            .map_err(sql_error)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(sql_error)
+    }
+
+    /// Every stored version of a key, oldest first
+    pub fn versions(&self, key_id: &str) -> Result<Vec<KeySummary>, KmsError> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare(
+                "SELECT key_id, version, algorithm, owner, status, created_at, expires_at, transitions, minimum_version,
+                        usage
+                 FROM keys WHERE key_id = ?1 ORDER BY version",
+            )
+            .map_err(sql_error)?;
+        let rows = statement.query_map(params![key_id], read_summary).map_err(sql_error)?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(sql_error)
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>, KmsError> {
+        self.connection
+            .lock()
+            .map_err(|_| KmsError::Storage("SQLite connection lock poisoned".to_string()))
+    }
+
+    // A fixed row encrypted under the record key; a wrong master key cannot open it
+    fn check_master_key(&self) -> Result<(), KmsError> {
+        let connection = self.connection()?;
+        let (nonce, ciphertext) = self.encrypt(&[], &check_aad(), STORE_CHECK_NAME)?;
+        // Another instance may initialise the store concurrently; the first row wins
+        connection
+            .execute(
+                "INSERT OR IGNORE INTO store_meta (name, value) VALUES (?1, ?2)",
+                params![STORE_CHECK_NAME, [nonce, ciphertext].concat()],
+            )
+            .map_err(sql_error)?;
+
+        let value: Vec<u8> = connection
+            .query_row(
+                "SELECT value FROM store_meta WHERE name = ?1",
+                params![STORE_CHECK_NAME],
+                |row| row.get(0),
+            )
+            .map_err(sql_error)?;
+        if value.len() < NONCE_LENGTH {
+            return Err(KmsError::IntegrityCheckFailed("malformed store check".to_string()));
+        }
+        let (nonce, ciphertext) = value.split_at(NONCE_LENGTH);
+        self.decrypt(nonce, ciphertext, &check_aad())
+            .map(|_| ())
+            .map_err(|_| KmsError::IntegrityCheckFailed("master key does not open this store".to_string()))
+    }
+
+    fn encrypt(&self, plaintext: &[u8], aad: &[u8], key_id: &str) -> Result<(Vec<u8>, Vec<u8>), KmsError> {
+        let mut nonce = vec![0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .record_key
+            .with_exposed(|key| {
+                Aes256Gcm::new_from_slice(key)
+                    .ok()?
+                    .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+                    .ok()
+            })?
+            .ok_or_else(|| KmsError::SealFailed(key_id.to_string()))?;
+        Ok((nonce, ciphertext))
+    }
+
+    fn decrypt(&self, nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<SecureSecret, KmsError> {
+        if nonce.len() != NONCE_LENGTH {
+            return Err(KmsError::IntegrityCheckFailed("bad nonce length".to_string()));
+        }
+        self.record_key
+            .with_exposed(|key| {
+                Aes256Gcm::new_from_slice(key)
+                    .ok()?
+                    .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+                    .ok()
+            })?
+            .map(|plaintext| {
+                let plaintext = Zeroizing::new(plaintext);
+                SecureSecret::protected_or_heap(&plaintext, IdleProtection::NoAccess)
+            })
+            .ok_or_else(|| KmsError::IntegrityCheckFailed("key row failed authentication".to_string()))
+    }
+
+    // Inserts version 1 of a new key, or overwrites the current version in place
+    fn put_in(&self, tx: &Transaction<'_>, record: &KeyRecord) -> Result<(), KmsError> {
+        let version = match current_version(tx, &record.key_id)? {
+            Some(version) => {
+                tx.execute("DELETE FROM keys WHERE key_id = ?1 AND version = ?2", params![record.key_id, version])
+                    .map_err(sql_error)?;
+                version
+            }
+            None => 1,
+        };
+        self.insert_version(tx, record, version)
+    }
+
+    fn insert_version(&self, tx: &Transaction<'_>, record: &KeyRecord, version: u32) -> Result<(), KmsError> {
+        let aad = row_aad(&record.key_id, version, &record.metadata.algorithm);
+        let (nonce, ciphertext) = record
+            .secret
+            .with_exposed(|bytes| self.encrypt(bytes, &aad, &record.key_id))??;
+        let transitions = serde_json::to_string(&record.metadata.transitions)
+            .map_err(|e| KmsError::Storage(e.to_string()))?;
+        let usage = serde_json::to_string(&record.metadata.usage).map_err(|e| KmsError::Storage(e.to_string()))?;
+
+        tx.execute(
+            "INSERT INTO keys (key_id, version, algorithm, owner, status, created_at, expires_at, transitions,
+                               minimum_version, usage, nonce, ciphertext)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                record.key_id,
+                version,
+                record.metadata.algorithm,
+                record.metadata.owner,
+                record.metadata.status.as_str(),
+                record.metadata.created_at as i64,
+                record.metadata.expires_at.map(|t| t as i64),
+                transitions,
+                record.metadata.minimum_version,
+                usage,
+                nonce,
+                ciphertext,
+            ],
+        )
+        .map_err(sql_error)?;
+        Ok(())
+    }
+
+    /// Adds a new active version and deactivates the previous one atomically
+    pub fn rotate(&mut self, key_id: &str, secret: SecureSecret) -> Result<(), KmsError> {
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate).map_err(sql_error)?;
+        let (version, current) = self.get_in(&tx, key_id)?.ok_or_else(|| KmsError::KeyNotFound(key_id.to_string()))?;
+        let retired_at = now();
+        let mut retired = current.metadata.transitions.clone();
+        retired.push(current.metadata.status.transition(KeyState::Deactivated, TransitionReason::Superseded, retired_at)?);
+        let retired = serde_json::to_string(&retired).map_err(|e| KmsError::Storage(e.to_string()))?;
+
+        tx.execute(
+            "UPDATE keys SET status = ?1, deactivated_at = ?2, transitions = ?3 WHERE key_id = ?4 AND version = ?5",
+            params![KeyState::Deactivated.as_str(), retired_at as i64, retired, key_id, version],
+        )
+        .map_err(sql_error)?;
+
+        let metadata = KeyMetadata {
+            created_at: retired_at,
+            status: KeyState::Active,
+            transitions: Vec::new(),
+            usage: UsageCounters::default(),
+            ..current.metadata
+        };
+        self.insert_version(&tx, &KeyRecord::with_metadata(key_id, secret, metadata), version + 1)?;
+        tx.commit().map_err(sql_error)
+    }
+
+    // The current version and its record, read through `connection` so callers can hold a transaction
+    fn get_in(&self, connection: &Connection, key_id: &str) -> Result<Option<(u32, KeyRecord)>, KmsError> {
+        let row = connection
+            .query_row(
+                "SELECT version, algorithm, owner, status, created_at, expires_at, transitions, minimum_version,
+                        usage, nonce, ciphertext
+                 FROM keys WHERE key_id = ?1 ORDER BY version DESC LIMIT 1",
+                params![key_id],
+                |row| {
+                    Ok((
+                        row.get::<_, u32>(0)?,
+                        read_metadata(row, 1)?,
+                        row.get::<_, Vec<u8>>(9)?,
+                        row.get::<_, Vec<u8>>(10)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(sql_error)?;
+
+        let Some((version, metadata, nonce, ciphertext)) = row else {
+            return Ok(None);
+        };
+        let secret = self
+            .decrypt(&nonce, &ciphertext, &row_aad(key_id, version, &metadata.algorithm))
+            .map_err(|_| KmsError::IntegrityCheckFailed(format!("key {} version {} failed authentication", key_id, version)))?;
+        Ok(Some((version, KeyRecord::with_metadata(key_id, secret, metadata))))
+    }
+}
+
+impl KeyStore for SqliteKeyStore {
+    /// Inserts version 1 of a new key, or overwrites the current version in place
+    fn put(&mut self, record: KeyRecord) -> Result<(), KmsError> {
+        let mut connection = self.connection()?;
+        let tx = connection.transaction().map_err(sql_error)?;
+        self.put_in(&tx, &record)?;
+        tx.commit().map_err(sql_error)
+    }
+
+    fn get(&self, key_id: &str) -> Result<Option<KeyRecord>, KmsError> {
+        let connection = self.connection()?;
+        Ok(self.get_in(&connection, key_id)?.map(|(_, record)| record))
+    }
+
+    /// Removes the current version only; earlier versions kept by
+    /// [`SqliteKeyStore::rotate`] stay, and the newest of them becomes current
+    fn delete(&mut self, key_id: &str) -> Result<bool, KmsError> {
+        let mut connection = self.connection()?;
+        let tx = connection.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate).map_err(sql_error)?;
+        let Some(version) = current_version(&tx, key_id)? else {
+            return Ok(false);
+        };
+        tx.execute("DELETE FROM keys WHERE key_id = ?1 AND version = ?2", params![key_id, version])
+            .map_err(sql_error)?;
+        tx.commit().map_err(sql_error)?;
+        Ok(true)
+    }
+
+    fn key_ids(&self) -> Result<Vec<String>, KmsError> {
+        let connection = self.connection()?;
+        let mut statement = connection
+            .prepare("SELECT DISTINCT key_id FROM keys ORDER BY key_id")
+            .map_err(sql_error)?;
+        let rows = statement.query_map([], |row| row.get(0)).map_err(sql_error)?;
+        rows.collect::<Result<Vec<String>, _>>().map_err(sql_error)
+    }
+
+    /// Overwrites the current versions in place, in one transaction
+    fn put_batch(&mut self, records: Vec<KeyRecord>, backup_watermark: Option<u64>) -> Result<(), KmsError> {
+        let mut connection = self.connection()?;
+        let tx = connection.transaction().map_err(sql_error)?;
+        for record in &records {
+            self.put_in(&tx, record)?;
+        }
+        if let Some(watermark) = backup_watermark {
+            let (nonce, ciphertext) = self.encrypt(&watermark.to_be_bytes(), &watermark_aad(), WATERMARK_NAME)?;
+            tx.execute(
+                "INSERT OR REPLACE INTO store_meta (name, value) VALUES (?1, ?2)",
+                params![WATERMARK_NAME, [nonce, ciphertext].concat()],
+            )
+            .map_err(sql_error)?;
+        }
+        tx.commit().map_err(sql_error)
+    }
+
+    fn backup_watermark(&self) -> Result<Option<u64>, KmsError> {
+        let value: Option<Vec<u8>> = self
+            .connection()?
+            .query_row("SELECT value FROM store_meta WHERE name = ?1", params![WATERMARK_NAME], |row| row.get(0))
+            .optional()
+            .map_err(sql_error)?;
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        let malformed = || KmsError::IntegrityCheckFailed("malformed backup watermark".to_string());
+        if value.len() < NONCE_LENGTH {
+            return Err(malformed());
+        }
+        let (nonce, ciphertext) = value.split_at(NONCE_LENGTH);
+        let bytes: [u8; 8] = self
+            .decrypt(nonce, ciphertext, &watermark_aad())?
+            .with_exposed(|bytes| <[u8; 8]>::try_from(bytes))?
+            .map_err(|_| malformed())?;
+        Ok(Some(u64::from_be_bytes(bytes)))
+    }
+
+    fn contains(&self, key_id: &str) -> Result<bool, KmsError> {
+        self.connection()?
+            .query_row("SELECT EXISTS(SELECT 1 FROM keys WHERE key_id = ?1)", params![key_id], |row| row.get(0))
+            .map_err(sql_error)
+    }
+
+}
+
+impl std::fmt::Debug for SqliteKeyStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SqliteKeyStore([REDACTED])")
+    }
+}
+
+fn sql_error(e: rusqlite::Error) -> KmsError {
+    KmsError::Storage(format!("SQLite: {}", e))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn schema_version(connection: &Connection) -> Result<u32, KmsError> {
+    connection
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(sql_error)
+}
+
+/// Applies every migration newer than the database's schema version
+fn migrate(connection: &mut Connection) -> Result<(), KmsError> {
+    let tx = connection
+        .transaction_with_behavior(rusqlite::TransactionBehavior::Exclusive)
+        .map_err(sql_error)?;
+    let current = schema_version(&tx)? as usize;
+    if current > MIGRATIONS.len() {
+        return Err(KmsError::Storage(format!(
+            "database schema version {} is newer than supported version {}",
+            current,
+            MIGRATIONS.len()
+        )));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        tx.execute_batch(migration)
+            .map_err(|e| KmsError::Storage(format!("migration {} failed: {}", index + 1, e)))?;
+        tx.pragma_update(None, "user_version", (index + 1) as u32).map_err(sql_error)?;
+    }
+    tx.commit().map_err(sql_error)
+}
+
+fn current_version(tx: &Transaction<'_>, key_id: &str) -> Result<Option<u32>, KmsError> {
+    tx.query_row("SELECT MAX(version) FROM keys WHERE key_id = ?1", params![key_id], |row| row.get(0))
+        .map_err(sql_error)
+}
+
+fn read_summary(row: &rusqlite::Row<'_>) -> rusqlite::Result<KeySummary> {
+    Ok(KeySummary {
+        key_id: row.get(0)?,
+        version: row.get(1)?,
+        metadata: read_metadata(row, 2)?,
+    })
+}
+
+// algorithm, owner, status, created_at, expires_at, transitions, minimum_version, usage from column `first`
+fn read_metadata(row: &rusqlite::Row<'_>, first: usize) -> rusqlite::Result<KeyMetadata> {
+    let conversion = |index: usize, e: Box<dyn std::error::Error + Send + Sync>| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e)
+    };
+    let status: String = row.get(first + 2)?;
+    let transitions: String = row.get(first + 5)?;
+    let usage: String = row.get(first + 7)?;
+    Ok(KeyMetadata {
+        algorithm: row.get(first)?,
+        owner: row.get(first + 1)?,
+        status: status.parse().map_err(|e: KmsError| conversion(first + 2, e.into()))?,
+        created_at: row.get::<_, i64>(first + 3)? as u64,
+        expires_at: row.get::<_, Option<i64>>(first + 4)?.map(|t| t as u64),
+        transitions: serde_json::from_str(&transitions).map_err(|e| conversion(first + 5, e.into()))?,
+        minimum_version: row.get(first + 6)?,
+        usage: serde_json::from_str(&usage).map_err(|e| conversion(first + 7, e.into()))?,
+    })
+}
+
+fn row_aad(key_id: &str, version: u32, algorithm: &str) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(12 + key_id.len() + algorithm.len());
+    for field in [key_id.as_bytes(), algorithm.as_bytes()] {
+        aad.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        aad.extend_from_slice(field);
+    }
+    aad.extend_from_slice(&version.to_be_bytes());
+    aad
+}
+
+fn check_aad() -> Vec<u8> {
+    row_aad(STORE_CHECK_NAME, 0, "")
+}
+
+fn watermark_aad() -> Vec<u8> {
+    row_aad(WATERMARK_NAME, 0, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn master_key() -> SecureSecret {
+        SecureSecret::from_bytes(&[0x44u8; 32])
+    }
+
+    fn open(dir: &TempDir) -> SqliteKeyStore {
+        SqliteKeyStore::open(dir.path().join("kms.sqlite"), &master_key()).expect("Failed to open store")
+    }
+
+    fn record(key_id: &str, fill: u8, owner: &str, algorithm: &str, expires_at: Option<u64>) -> KeyRecord {
+        KeyRecord::with_metadata(
+            key_id,
+            SecureSecret::from_bytes(&[fill; 32]),
+            KeyMetadata {
+                algorithm: algorithm.to_string(),
+                owner: Some(owner.to_string()),
+                expires_at,
+                ..KeyMetadata::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_round_trip_across_reopen() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut store = open(&dir);
+            store.put(record("settlement", 1, "treasury", "AES-256", None)).unwrap();
+            assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        }
+
+        let store = open(&dir);
+        let loaded = store.get("settlement").unwrap().unwrap();
+        assert_eq!(loaded.secret, SecureSecret::from_bytes(&[1u8; 32]));
+        assert_eq!(loaded.metadata.owner.as_deref(), Some("treasury"));
+        assert!(store.contains("settlement").unwrap());
+        assert!(store.get("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_key_material_encrypted_in_column() {
+        let dir = TempDir::new().unwrap();
+        let mut store = open(&dir);
+        store.put(record("settlement", 0x5a, "treasury", "AES-256", None)).unwrap();
+
+        let ciphertext: Vec<u8> = store
+            .connection()
+            .unwrap()
+            .query_row("SELECT ciphertext FROM keys WHERE key_id = 'settlement'", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(&ciphertext[..32], &[0x5au8; 32][..]);
+    }
+
+    #[test]
+    fn test_query_by_metadata() {
+        let dir = TempDir::new().unwrap();
+        let mut store = open(&dir);
+        store.put(record("a", 1, "treasury", "AES-256", Some(100))).unwrap();
+        store.put(record("b", 2, "treasury", "HMAC-SHA256", Some(500))).unwrap();
+        store.put(record("c", 3, "payments", "AES-256", None)).unwrap();
+
+        let ids = |query: KeyQuery| -> Vec<String> {
+            store.find(&query).unwrap().into_iter().map(|s| s.key_id).collect()
+        };
+        assert_eq!(ids(KeyQuery { owner: Some("treasury".into()), ..Default::default() }), ["a", "b"]);
+        assert_eq!(ids(KeyQuery { algorithm: Some("AES-256".into()), ..Default::default() }), ["a", "c"]);
+        assert_eq!(ids(KeyQuery { expires_before: Some(200), ..Default::default() }), ["a"]);
+        assert_eq!(ids(KeyQuery { status: Some(KeyState::Active), ..Default::default() }), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_rotation_is_atomic() {
+        let dir = TempDir::new().unwrap();
+        let mut store = open(&dir);
+        store.put(record("settlement", 1, "treasury", "AES-256", None)).unwrap();
+        store.rotate("settlement", SecureSecret::from_bytes(&[2u8; 32])).unwrap();
+
+        let versions = store.versions("settlement").unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].metadata.status, KeyState::Deactivated);
+        assert_eq!(versions[0].metadata.transitions[0].reason, TransitionReason::Superseded);
+        assert_eq!(versions[1].metadata.status, KeyState::Active);
+        assert_eq!(versions[1].metadata.owner.as_deref(), Some("treasury"));
+        assert_eq!(store.get("settlement").unwrap().unwrap().secret, SecureSecret::from_bytes(&[2u8; 32]));
+
+        // A failing insert rolls back the deactivation too
+        store
+            .connection()
+            .unwrap()
+            .execute_batch(
+                "CREATE TRIGGER fail_insert BEFORE INSERT ON keys BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+            )
+            .unwrap();
+        assert!(store.rotate("settlement", SecureSecret::from_bytes(&[3u8; 32])).is_err());
+
+        let versions = store.versions("settlement").unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[1].metadata.status, KeyState::Active);
+    }
+
+    #[test]
+    fn test_delete_removes_current_version_only() {
+        let dir = TempDir::new().unwrap();
+        let mut store = open(&dir);
+        store.put(record("settlement", 1, "treasury", "AES-256", None)).unwrap();
+        store.rotate("settlement", SecureSecret::from_bytes(&[2u8; 32])).unwrap();
+
+        assert!(store.delete("settlement").unwrap());
+        let versions = store.versions("settlement").unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].metadata.status, KeyState::Deactivated);
+
+        assert!(store.delete("settlement").unwrap());
+        assert!(!store.delete("settlement").unwrap());
+        assert!(store.get("settlement").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_migrates_old_schema() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("kms.sqlite");
+        {
+            let connection = Connection::open(&path).unwrap();
+            connection.execute_batch(MIGRATIONS[0]).unwrap();
+            connection.pragma_update(None, "user_version", 1).unwrap();
+        }
+
+        let store = SqliteKeyStore::open(&path, &master_key()).unwrap();
+        assert_eq!(store.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        let added: u32 = store
+            .connection()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('keys') WHERE name IN ('deactivated_at', 'transitions', 'minimum_version', 'usage')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(added, 4);
+    }
+
+    #[test]
+    fn test_batch_and_watermark_survive_reopen() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut store = open(&dir);
+            assert_eq!(store.backup_watermark().unwrap(), None);
+            store.put(record("settlement", 1, "treasury", "AES-256", None)).unwrap();
+            let records = vec![record("settlement", 2, "treasury", "AES-256", None), record("reporting", 3, "ops", "AES-256", None)];
+            store.put_batch(records, Some(1_700_000_000)).unwrap();
+        }
+
+        let store = open(&dir);
+        assert_eq!(store.backup_watermark().unwrap(), Some(1_700_000_000));
+        assert_eq!(store.versions("settlement").unwrap().len(), 1);
+        assert_eq!(store.get("settlement").unwrap().unwrap().secret, SecureSecret::from_bytes(&[2u8; 32]));
+        assert_eq!(store.key_ids().unwrap(), ["reporting", "settlement"]);
+    }
+
+    #[test]
+    fn test_wrong_master_key_rejected() {
+        let dir = TempDir::new().unwrap();
+        drop(open(&dir));
+        assert!(matches!(
+            SqliteKeyStore::open(dir.path().join("kms.sqlite"), &SecureSecret::from_bytes(&[0x55u8; 32])),
+            Err(KmsError::IntegrityCheckFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_tampered_row_detected() {
+        let dir = TempDir::new().unwrap();
+        let mut store = open(&dir);
+        store.put(record("settlement", 1, "treasury", "AES-256", None)).unwrap();
+
+        // Relabelling the algorithm breaks the row's associated data
+        store
+            .connection()
+            .unwrap()
+            .execute("UPDATE keys SET algorithm = 'DES' WHERE key_id = 'settlement'", [])
+            .unwrap();
+        assert!(matches!(store.get("settlement"), Err(KmsError::IntegrityCheckFailed(_))));
+    }
+
+    #[test]
+    fn test_two_connections_share_state() {
+        let dir = TempDir::new().unwrap();
+        let mut first = open(&dir);
+        let second = open(&dir);
+        first.put(record("shared", 7, "ops", "AES-256", None)).unwrap();
+        assert_eq!(second.get("shared").unwrap().unwrap().secret, SecureSecret::from_bytes(&[7u8; 32]));
+    }
+}
@@ -0,0 +1,1069 @@
+//! Pluggable key storage for the KMS.
+//!
+//! [`KeyStore`] is the persistence interface used by
+//! [`KeyManagementSystem`](crate::crypto::kms::KeyManagementSystem).
+//! [`MemoryKeyStore`] keeps records in protected memory only.
+//! [`FileKeyStore`] keeps one encrypted file per key in a directory:
+//!
+//! ```text
+//! <root>/LOCK            flock'd for the lifetime of the store
+//! <root>/STORE           store header; opens only under the right master key
+//! <root>/MASTER          master key wrapped by a provider (provider-opened stores)
+//! <root>/INDEX           encrypted generation counter and digest of every record
+//! <root>/WATERMARK       encrypted export time of the newest backup restored
+//! <root>/journal         write-ahead journal of pending record writes
+//! <root>/keys/<hex>.key  AES-256-GCM record, AAD = format version + key id
+//! ```
+//!
+//! Every change is first appended to the journal and fsync'd, then applied
+//! with write-to-temp / fsync / rename / fsync-dir, and only then is the
+//! journal truncated. A crash at any point leaves either the old record, the
+//! new record, or a journal entry that is replayed on the next open. A
+//! [`KeyStore::put_batch`] is a single journal entry, so it is replayed
+//! completely or not at all. Entries
+//! with a bad checksum (a torn final append) are discarded. Journal entries
+//! carry the already-encrypted record, so no plaintext ever reaches the disk.
+//!
+//! On open, every record is decrypted once to verify its integrity; a record
+//! that fails authentication (tampered, truncated or renamed to a different
+//! key id) makes the open fail. The index lists the SHA3-256 digest of every
+//! record file and is rewritten, with its generation counter bumped, by every
+//! change, so a record file that was deleted, added or swapped for an older
+//! copy also makes the open fail. Rolling back the whole directory at once
+//! can only be caught by comparing [`FileKeyStore::generation`] against a
+//! value kept outside the store.
+
+use super::provider::{MasterKeyProvider, MASTER_KEY_LENGTH};
+use crate::crypto::kms::lifecycle::{KeyState, StateTransition};
+use crate::crypto::kms::usage::UsageCounters;
+use crate::crypto::secure::{register_label, Aes256Key, IdleProtection, SecureSecret};
+use crate::error::KmsError;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+pub const DEFAULT_KEY_ALGORITHM: &str = "GENERIC-SECRET";
+
+const STORE_FORMAT_VERSION: u8 = 1;
+const RECORD_MAGIC: &[u8; 4] = b"PQKS";
+const NONCE_LENGTH: usize = 12;
+const CHECKSUM_LENGTH: usize = 8;
+const STORE_LABEL: &[u8] = b"kms/store/record";
+const STORE_LABEL_OWNER: &str = "kms::store";
+const STORE_CHECK_KEY_ID: &str = "\0store-check";
+const WATERMARK_KEY_ID: &str = "\0backup-watermark";
+const INDEX_KEY_ID: &str = "\0store-index";
+const DIGEST_LENGTH: usize = 32;
+
+const LOCK_FILE: &str = "LOCK";
+const HEADER_FILE: &str = "STORE";
+const WATERMARK_FILE: &str = "WATERMARK";
+const INDEX_FILE: &str = "INDEX";
+const MASTER_KEY_FILE: &str = "MASTER";
+const JOURNAL_FILE: &str = "journal";
+const KEYS_DIR: &str = "keys";
+const RECORD_EXTENSION: &str = "key";
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Non-secret attributes stored alongside a key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyMetadata {
+    pub algorithm: String,
+    pub owner: Option<String>,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub status: KeyState,
+    /// State changes since creation, oldest first
+    #[serde(default)]
+    pub transitions: Vec<StateTransition>,
+    /// Oldest version of a versioned key that may still be decrypted; kept on the current version
+    #[serde(default)]
+    pub minimum_version: Option<u32>,
+    /// Uses of this key version, kept with it so that quotas survive restarts
+    #[serde(default)]
+    pub usage: UsageCounters,
+}
+
+impl Default for KeyMetadata {
+    fn default() -> Self {
+        Self {
+            algorithm: DEFAULT_KEY_ALGORITHM.to_string(),
+            owner: None,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            expires_at: None,
+            status: KeyState::Active,
+            transitions: Vec::new(),
+            minimum_version: None,
+            usage: UsageCounters::default(),
+        }
+    }
+}
+
+/// A key as held by a [`KeyStore`]
+#[derive(Debug)]
+pub struct KeyRecord {
+    pub key_id: String,
+    pub metadata: KeyMetadata,
+    pub secret: SecureSecret,
+}
+
+impl KeyRecord {
+    pub fn new(key_id: &str, secret: SecureSecret) -> Self {
+        Self::with_metadata(key_id, secret, KeyMetadata::default())
+    }
+
+    pub fn with_metadata(key_id: &str, secret: SecureSecret, metadata: KeyMetadata) -> Self {
+        Self {
+            key_id: key_id.to_string(),
+            metadata,
+            secret,
+        }
+    }
+
+    // Fresh protected copy; secrets are never cloned implicitly
+    fn duplicate(&self) -> Result<Self, KmsError> {
+        Ok(Self {
+            key_id: self.key_id.clone(),
+            metadata: self.metadata.clone(),
+            secret: self.secret.with_exposed(|bytes| {
+                SecureSecret::protected_or_heap(bytes, IdleProtection::NoAccess)
+            })?,
+        })
+    }
+}
+
+/// Persistence backend for KMS keys
+pub trait KeyStore: Send + Sync {
+    /// Inserts or replaces the record for `record.key_id`
+    fn put(&mut self, record: KeyRecord) -> Result<(), KmsError>;
+
+    fn get(&self, key_id: &str) -> Result<Option<KeyRecord>, KmsError>;
+
+    /// Removes a record, returning whether it existed
+    fn delete(&mut self, key_id: &str) -> Result<bool, KmsError>;
+
+    /// All stored key ids in ascending order
+    fn key_ids(&self) -> Result<Vec<String>, KmsError>;
+
+    /// Inserts or replaces every record and, if given, sets the backup
+    /// watermark as one change: after a failure or crash either all of it
+    /// is stored or none
+    fn put_batch(&mut self, records: Vec<KeyRecord>, backup_watermark: Option<u64>) -> Result<(), KmsError>;
+
+    /// Export time of the newest backup restored into this store
+    fn backup_watermark(&self) -> Result<Option<u64>, KmsError>;
+
+    fn contains(&self, key_id: &str) -> Result<bool, KmsError> {
+        Ok(self.key_ids()?.iter().any(|id| id == key_id))
+    }
+}
+
+/// Volatile store; secrets live in protected memory and vanish with the process
+#[derive(Debug, Default)]
+pub struct MemoryKeyStore {
+    records: HashMap<String, KeyRecord>,
+    backup_watermark: Option<u64>,
+}
+
+impl MemoryKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KeyStore for MemoryKeyStore {
+    fn put(&mut self, record: KeyRecord) -> Result<(), KmsError> {
+        let record = KeyRecord {
+            secret: record.secret.into_protected(IdleProtection::NoAccess),
+            ..record
+        };
+        self.records.insert(record.key_id.clone(), record);
+        Ok(())
+    }
+
+    fn get(&self, key_id: &str) -> Result<Option<KeyRecord>, KmsError> {
+        self.records.get(key_id).map(KeyRecord::duplicate).transpose()
+    }
+
+    fn delete(&mut self, key_id: &str) -> Result<bool, KmsError> {
+        Ok(self.records.remove(key_id).is_some())
+    }
+
+    fn key_ids(&self) -> Result<Vec<String>, KmsError> {
+        let mut ids: Vec<String> = self.records.keys().cloned().collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    fn put_batch(&mut self, records: Vec<KeyRecord>, backup_watermark: Option<u64>) -> Result<(), KmsError> {
+        for record in records {
+            self.put(record)?;
+        }
+        if backup_watermark.is_some() {
+            self.backup_watermark = backup_watermark;
+        }
+        Ok(())
+    }
+
+    fn backup_watermark(&self) -> Result<Option<u64>, KmsError> {
+        Ok(self.backup_watermark)
+    }
+
+    fn contains(&self, key_id: &str) -> Result<bool, KmsError> {
+        Ok(self.records.contains_key(key_id))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JournalOp {
+    Put = 1,
+    Delete = 2,
+    /// Payload: the encoded frames of the batch's entries
+    Batch = 3,
+    /// Payload: the encrypted backup watermark
+    Watermark = 4,
+}
+
+// One pending change: the op, the key id and (for puts) the encrypted record
+#[derive(Debug)]
+struct JournalEntry {
+    op: JournalOp,
+    key_id: String,
+    payload: Vec<u8>,
+}
+
+/// Directory-backed store with per-record AEAD under a master key
+pub struct FileKeyStore {
+    root: PathBuf,
+    record_key: SecureSecret,
+    // Key id → SHA3-256 of its record file, as authenticated by INDEX
+    index: BTreeMap<String, [u8; DIGEST_LENGTH]>,
+    generation: u64,
+    backup_watermark: Option<u64>,
+    journal: File,
+    // Held open for the lifetime of the store; the flock is released on drop
+    _lock: File,
+}
+
+impl FileKeyStore {
+    /// Opens (or creates) a store, replays the journal and verifies every record
+    pub fn open(root: impl AsRef<Path>, master_key: &SecureSecret) -> Result<Self, KmsError> {
+        let root = root.as_ref().to_path_buf();
+        let lock = lock_store(&root)?;
+        Self::open_locked(root, lock, master_key)
+    }
+
+    /// Opens (or creates) a store whose master key is kept in `<root>/MASTER`,
+    /// wrapped by `provider`. A new store gets a random master key, wrapped
+    /// and written before anything is encrypted under it.
+    pub fn open_with_provider(root: impl AsRef<Path>, provider: &dyn MasterKeyProvider) -> Result<Self, KmsError> {
+        let root = root.as_ref().to_path_buf();
+        let lock = lock_store(&root)?;
+        let path = root.join(MASTER_KEY_FILE);
+        let master_key = if path.exists() {
+            provider.unwrap(&fs::read(&path).map_err(io_error("read wrapped master key"))?)?
+        } else if root.join(HEADER_FILE).exists() {
+            return Err(KmsError::IntegrityCheckFailed("store has no provider-wrapped master key".to_string()));
+        } else {
+            let mut bytes = Zeroizing::new([0u8; MASTER_KEY_LENGTH]);
+            rand::thread_rng().fill_bytes(bytes.as_mut());
+            let master_key = SecureSecret::protected_or_heap(bytes.as_ref(), IdleProtection::NoAccess);
+            atomic_write(&root, &path, &provider.wrap(&master_key)?)?;
+            master_key
+        };
+        Self::open_locked(root, lock, &master_key)
+    }
+
+    /// Rewraps the master key of a provider-opened store under the provider's
+    /// newest key version, e.g. after [`MasterKeyProvider::rotate`]. The
+    /// rewrapped key is checked to be this store's before it replaces the old one.
+    pub fn rewrap_master_key(&self, provider: &dyn MasterKeyProvider) -> Result<(), KmsError> {
+        let path = self.root.join(MASTER_KEY_FILE);
+        if !path.exists() {
+            return Err(KmsError::OperationNotPermitted("store was not opened through a master key provider".to_string()));
+        }
+        let rewrapped = provider.rewrap(&fs::read(&path).map_err(io_error("read wrapped master key"))?)?;
+        let record_key = record_key(&provider.unwrap(&rewrapped)?)?;
+        if !record_key.constant_time_eq(&self.record_key) {
+            return Err(KmsError::IntegrityCheckFailed("rewrapped master key does not open this store".to_string()));
+        }
+        atomic_write(&self.root, &path, &rewrapped)
+    }
+
+    fn open_locked(root: PathBuf, lock: File, master_key: &SecureSecret) -> Result<Self, KmsError> {
+        let record_key = record_key(master_key)?;
+        let journal = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(root.join(JOURNAL_FILE))
+            .map_err(io_error("open journal"))?;
+
+        let mut store = Self {
+            root,
+            record_key,
+            index: BTreeMap::new(),
+            generation: 0,
+            backup_watermark: None,
+            journal,
+            _lock: lock,
+        };
+
+        store.check_header()?;
+        store.read_index()?;
+        store.replay_journal()?;
+        store.remove_temp_files()?;
+        store.load_and_verify()?;
+        store.backup_watermark = store.read_watermark()?;
+        Ok(store)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Number of changes committed to the store since it was created. Keep
+    /// the last value seen somewhere the store's disk cannot reach to detect
+    /// the whole directory being restored from an older copy.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // The header is an empty record under a reserved id; a wrong master key cannot open it
+    fn check_header(&mut self) -> Result<(), KmsError> {
+        let path = self.root.join(HEADER_FILE);
+        if path.exists() {
+            let bytes = fs::read(&path).map_err(io_error("read store header"))?;
+            self.decrypt(STORE_CHECK_KEY_ID, &bytes).map_err(|_| {
+                KmsError::IntegrityCheckFailed("master key does not open this store".to_string())
+            })?;
+            Ok(())
+        } else {
+            // The index goes first, so a store with a header always has one
+            if !self.root.join(INDEX_FILE).exists() {
+                self.write_index()?;
+            }
+            let header = self.encrypt(STORE_CHECK_KEY_ID, &[])?;
+            atomic_write(&self.root, &path, &header)
+        }
+    }
+
+    fn record_path(&self, key_id: &str) -> PathBuf {
+        self.root
+            .join(KEYS_DIR)
+            .join(format!("{}.{}", hex::encode(key_id.as_bytes()), RECORD_EXTENSION))
+    }
+
+    fn encrypt(&self, key_id: &str, plaintext: &[u8]) -> Result<Vec<u8>, KmsError> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = self
+            .record_key
+            .with_exposed(|key| {
+                Aes256Gcm::new_from_slice(key)
+                    .ok()?
+                    .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &record_aad(key_id) })
+                    .ok()
+            })?
+            .ok_or_else(|| KmsError::SealFailed(key_id.to_string()))?;
+
+        let mut out = Vec::with_capacity(RECORD_MAGIC.len() + 1 + NONCE_LENGTH + ciphertext.len());
+        out.extend_from_slice(RECORD_MAGIC);
+        out.push(STORE_FORMAT_VERSION);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    fn decrypt(&self, key_id: &str, bytes: &[u8]) -> Result<Zeroizing<Vec<u8>>, KmsError> {
+        let header = RECORD_MAGIC.len() + 1;
+        if bytes.len() < header + NONCE_LENGTH || &bytes[..RECORD_MAGIC.len()] != RECORD_MAGIC {
+            return Err(KmsError::IntegrityCheckFailed(format!("malformed record for key_id {}", key_id)));
+        }
+        if bytes[RECORD_MAGIC.len()] != STORE_FORMAT_VERSION {
+            return Err(KmsError::IntegrityCheckFailed(format!(
+                "unsupported record version {} for key_id {}",
+                bytes[RECORD_MAGIC.len()],
+                key_id
+            )));
+        }
+
+        let (nonce, ciphertext) = bytes[header..].split_at(NONCE_LENGTH);
+        self.record_key
+            .with_exposed(|key| {
+                Aes256Gcm::new_from_slice(key)
+                    .ok()?
+                    .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &record_aad(key_id) })
+                    .ok()
+            })?
+            .map(Zeroizing::new)
+            .ok_or_else(|| KmsError::IntegrityCheckFailed(format!("record for key_id {} failed authentication", key_id)))
+    }
+
+    fn read_record(&self, key_id: &str) -> Result<KeyRecord, KmsError> {
+        let bytes = fs::read(self.record_path(key_id)).map_err(io_error("read key record"))?;
+        let plaintext = self.decrypt(key_id, &bytes)?;
+        decode_record(key_id, &plaintext)
+    }
+
+    fn put_entry(&self, record: KeyRecord) -> Result<JournalEntry, KmsError> {
+        let plaintext = encode_record(&record)?;
+        Ok(JournalEntry {
+            op: JournalOp::Put,
+            payload: self.encrypt(&record.key_id, &plaintext)?,
+            key_id: record.key_id,
+        })
+    }
+
+    fn read_watermark(&self) -> Result<Option<u64>, KmsError> {
+        let path = self.root.join(WATERMARK_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let plaintext = self.decrypt(WATERMARK_KEY_ID, &fs::read(&path).map_err(io_error("read backup watermark"))?)?;
+        let bytes: [u8; 8] = plaintext
+            .as_slice()
+            .try_into()
+            .map_err(|_| KmsError::IntegrityCheckFailed("malformed backup watermark".to_string()))?;
+        Ok(Some(u64::from_be_bytes(bytes)))
+    }
+
+    fn read_index(&mut self) -> Result<(), KmsError> {
+        let path = self.root.join(INDEX_FILE);
+        if !path.exists() {
+            return Err(KmsError::IntegrityCheckFailed("store index is missing".to_string()));
+        }
+        let plaintext = self.decrypt(INDEX_KEY_ID, &fs::read(&path).map_err(io_error("read store index"))?)?;
+        let (generation, index) = decode_index(&plaintext)?;
+        self.generation = generation;
+        self.index = index;
+        Ok(())
+    }
+
+    fn write_index(&mut self) -> Result<(), KmsError> {
+        let generation = self.generation + 1;
+        let bytes = self.encrypt(INDEX_KEY_ID, &encode_index(generation, &self.index))?;
+        atomic_write(&self.root, &self.root.join(INDEX_FILE), &bytes)?;
+        self.generation = generation;
+        Ok(())
+    }
+
+    fn log(&mut self, entry: &JournalEntry) -> Result<(), KmsError> {
+        self.journal.write_all(&encode_journal_entry(entry)).map_err(io_error("append journal"))?;
+        self.journal.sync_all().map_err(io_error("sync journal"))
+    }
+
+    fn apply(&mut self, entry: &JournalEntry) -> Result<(), KmsError> {
+        let path = self.record_path(&entry.key_id);
+        match entry.op {
+            JournalOp::Put => {
+                atomic_write(&self.root.join(KEYS_DIR), &path, &entry.payload)?;
+                self.index.insert(entry.key_id.clone(), Sha3_256::digest(&entry.payload).into());
+            }
+            JournalOp::Delete => {
+                if path.exists() {
+                    fs::remove_file(&path).map_err(io_error("remove key record"))?;
+                    sync_dir(&self.root.join(KEYS_DIR))?;
+                }
+                self.index.remove(&entry.key_id);
+            }
+            JournalOp::Batch => {
+                let entries = decode_journal(&entry.payload);
+                if entries.len() != count_journal_frames(&entry.payload) {
+                    return Err(KmsError::IntegrityCheckFailed("malformed batch in key store journal".to_string()));
+                }
+                for entry in &entries {
+                    self.apply(entry)?;
+                }
+            }
+            JournalOp::Watermark => {
+                atomic_write(&self.root, &self.root.join(WATERMARK_FILE), &entry.payload)?;
+                self.backup_watermark = self.read_watermark()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn clear_journal(&mut self) -> Result<(), KmsError> {
+        self.journal.set_len(0).map_err(io_error("truncate journal"))?;
+        self.journal.sync_all().map_err(io_error("sync journal"))
+    }
+
+    fn commit(&mut self, entry: JournalEntry) -> Result<(), KmsError> {
+        self.log(&entry)?;
+        self.apply(&entry)?;
+        self.write_index()?;
+        self.clear_journal()
+    }
+
+    fn replay_journal(&mut self) -> Result<(), KmsError> {
+        let mut bytes = Vec::new();
+        File::open(self.root.join(JOURNAL_FILE))
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(io_error("read journal"))?;
+
+        let entries = decode_journal(&bytes);
+        if entries.len() < count_journal_frames(&bytes) {
+            tracing::warn!("Discarding torn entry at the end of the key store journal");
+        }
+        for entry in &entries {
+            self.apply(entry)?;
+        }
+        if !entries.is_empty() {
+            self.write_index()?;
+        }
+        self.clear_journal()
+    }
+
+    fn remove_temp_files(&self) -> Result<(), KmsError> {
+        for dir in [self.root.clone(), self.root.join(KEYS_DIR)] {
+            for entry in fs::read_dir(&dir).map_err(io_error("list store directory"))? {
+                let path = entry.map_err(io_error("list store directory"))?.path();
+                if path.extension().and_then(|e| e.to_str()) == Some(TEMP_EXTENSION) {
+                    fs::remove_file(&path).map_err(io_error("remove temporary file"))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Every record file must be in the index with a matching digest, and every indexed record must exist
+    fn load_and_verify(&self) -> Result<(), KmsError> {
+        let mut found = 0;
+        for entry in fs::read_dir(self.root.join(KEYS_DIR)).map_err(io_error("list key records"))? {
+            let path = entry.map_err(io_error("list key records"))?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(RECORD_EXTENSION) {
+                continue;
+            }
+            let key_id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| hex::decode(stem).ok())
+                .and_then(|raw| String::from_utf8(raw).ok())
+                .ok_or_else(|| KmsError::IntegrityCheckFailed(format!("unexpected file {}", path.display())))?;
+
+            let bytes = fs::read(&path).map_err(io_error("read key record"))?;
+            match self.index.get(&key_id) {
+                Some(digest) if *digest == <[u8; DIGEST_LENGTH]>::from(Sha3_256::digest(&bytes)) => {}
+                Some(_) => {
+                    return Err(KmsError::IntegrityCheckFailed(format!(
+                        "record for key_id {} does not match the store index",
+                        key_id
+                    )))
+                }
+                None => {
+                    return Err(KmsError::IntegrityCheckFailed(format!("record for key_id {} is not in the store index", key_id)))
+                }
+            }
+            decode_record(&key_id, &self.decrypt(&key_id, &bytes)?)?;
+            found += 1;
+        }
+        if found != self.index.len() {
+            return Err(KmsError::IntegrityCheckFailed(format!(
+                "store index lists {} records but {} were found",
+                self.index.len(),
+                found
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl KeyStore for FileKeyStore {
+    fn put(&mut self, record: KeyRecord) -> Result<(), KmsError> {
+        let entry = self.put_entry(record)?;
+        self.commit(entry)
+    }
+
+    fn get(&self, key_id: &str) -> Result<Option<KeyRecord>, KmsError> {
+        if !self.index.contains_key(key_id) {
+            return Ok(None);
+        }
+        self.read_record(key_id).map(Some)
+    }
+
+    fn delete(&mut self, key_id: &str) -> Result<bool, KmsError> {
+        if !self.index.contains_key(key_id) {
+            return Ok(false);
+        }
+        self.commit(JournalEntry {
+            op: JournalOp::Delete,
+            key_id: key_id.to_string(),
+            payload: Vec::new(),
+        })?;
+        Ok(true)
+    }
+
+    fn key_ids(&self) -> Result<Vec<String>, KmsError> {
+        Ok(self.index.keys().cloned().collect())
+    }
+
+    fn put_batch(&mut self, records: Vec<KeyRecord>, backup_watermark: Option<u64>) -> Result<(), KmsError> {
+        let mut payload = Vec::new();
+        for record in records {
+            payload.extend_from_slice(&encode_journal_entry(&self.put_entry(record)?));
+        }
+        if let Some(watermark) = backup_watermark {
+            let entry = JournalEntry {
+                op: JournalOp::Watermark,
+                key_id: WATERMARK_KEY_ID.to_string(),
+                payload: self.encrypt(WATERMARK_KEY_ID, &watermark.to_be_bytes())?,
+            };
+            payload.extend_from_slice(&encode_journal_entry(&entry));
+        }
+        self.commit(JournalEntry {
+            op: JournalOp::Batch,
+            key_id: String::new(),
+            payload,
+        })
+    }
+
+    fn backup_watermark(&self) -> Result<Option<u64>, KmsError> {
+        Ok(self.backup_watermark)
+    }
+
+    fn contains(&self, key_id: &str) -> Result<bool, KmsError> {
+        Ok(self.index.contains_key(key_id))
+    }
+}
+
+impl std::fmt::Debug for FileKeyStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FileKeyStore({}, {} keys)", self.root.display(), self.index.len())
+    }
+}
+
+fn lock_store(root: &Path) -> Result<File, KmsError> {
+    fs::create_dir_all(root.join(KEYS_DIR)).map_err(io_error("create store directory"))?;
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(root.join(LOCK_FILE))
+        .map_err(io_error("open lock file"))?;
+    lock_exclusive(&lock, root)?;
+    Ok(lock)
+}
+
+fn record_key(master_key: &SecureSecret) -> Result<SecureSecret, KmsError> {
+    register_label(STORE_LABEL, STORE_LABEL_OWNER).map_err(|e| KmsError::Storage(e.to_string()))?;
+    master_key
+        .derive::<Aes256Key>(STORE_LABEL_OWNER, STORE_LABEL, &[STORE_FORMAT_VERSION])
+        .map_err(|e| KmsError::Storage(e.to_string()))
+}
+
+fn io_error(operation: &'static str) -> impl Fn(std::io::Error) -> KmsError {
+    move |e| KmsError::Storage(format!("{}: {}", operation, e))
+}
+
+#[cfg(unix)]
+fn lock_exclusive(file: &File, root: &Path) -> Result<(), KmsError> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: flock on a file descriptor we own
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return Err(KmsError::StoreLocked(root.display().to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn lock_exclusive(_file: &File, _root: &Path) -> Result<(), KmsError> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<(), KmsError> {
+    File::open(dir)
+        .and_then(|d| d.sync_all())
+        .map_err(io_error("sync directory"))
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<(), KmsError> {
+    Ok(())
+}
+
+// write temp → fsync → rename over target → fsync directory
+fn atomic_write(dir: &Path, path: &Path, bytes: &[u8]) -> Result<(), KmsError> {
+    let temp = path.with_extension(TEMP_EXTENSION);
+    let mut file = File::create(&temp).map_err(io_error("create temporary file"))?;
+    file.write_all(bytes).map_err(io_error("write temporary file"))?;
+    file.sync_all().map_err(io_error("sync temporary file"))?;
+    drop(file);
+    fs::rename(&temp, path).map_err(io_error("rename temporary file"))?;
+    sync_dir(dir)
+}
+
+fn record_aad(key_id: &str) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(RECORD_MAGIC.len() + 5 + key_id.len());
+    aad.extend_from_slice(RECORD_MAGIC);
+    aad.push(STORE_FORMAT_VERSION);
+    aad.extend_from_slice(&(key_id.len() as u32).to_be_bytes());
+    aad.extend_from_slice(key_id.as_bytes());
+    aad
+}
+
+// Plaintext layout: metadata length (u32) | metadata JSON | secret bytes
+fn encode_record(record: &KeyRecord) -> Result<Zeroizing<Vec<u8>>, KmsError> {
+    let metadata = serde_json::to_vec(&record.metadata).map_err(|e| KmsError::Storage(e.to_string()))?;
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(4 + metadata.len() + record.secret.len()));
+    plaintext.extend_from_slice(&(metadata.len() as u32).to_be_bytes());
+    plaintext.extend_from_slice(&metadata);
+    record.secret.with_exposed(|bytes| plaintext.extend_from_slice(bytes))?;
+    Ok(plaintext)
+}
+
+fn decode_record(key_id: &str, plaintext: &[u8]) -> Result<KeyRecord, KmsError> {
+    let malformed = || KmsError::IntegrityCheckFailed(format!("malformed record for key_id {}", key_id));
+    let length = plaintext
+        .get(..4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+        .ok_or_else(malformed)?;
+    let metadata = plaintext.get(4..4 + length).ok_or_else(malformed)?;
+    let metadata: KeyMetadata = serde_json::from_slice(metadata).map_err(|_| malformed())?;
+
+    Ok(KeyRecord {
+        key_id: key_id.to_string(),
+        metadata,
+        secret: SecureSecret::protected_or_heap(&plaintext[4 + length..], IdleProtection::NoAccess),
+    })
+}
+
+// Plaintext layout: generation (u64) | per record: key_id length (u16) | key_id | record digest
+fn encode_index(generation: u64, index: &BTreeMap<String, [u8; DIGEST_LENGTH]>) -> Vec<u8> {
+    let mut plaintext = Vec::with_capacity(8 + index.len() * (2 + DIGEST_LENGTH));
+    plaintext.extend_from_slice(&generation.to_be_bytes());
+    for (key_id, digest) in index {
+        plaintext.extend_from_slice(&(key_id.len() as u16).to_be_bytes());
+        plaintext.extend_from_slice(key_id.as_bytes());
+        plaintext.extend_from_slice(digest);
+    }
+    plaintext
+}
+
+fn decode_index(plaintext: &[u8]) -> Result<(u64, BTreeMap<String, [u8; DIGEST_LENGTH]>), KmsError> {
+    let malformed = || KmsError::IntegrityCheckFailed("malformed store index".to_string());
+    let generation = plaintext
+        .get(..8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_be_bytes)
+        .ok_or_else(malformed)?;
+
+    let mut index = BTreeMap::new();
+    let mut cursor = 8;
+    while cursor < plaintext.len() {
+        let id_length = plaintext
+            .get(cursor..cursor + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(malformed)?;
+        let key_id = plaintext
+            .get(cursor + 2..cursor + 2 + id_length)
+            .and_then(|id| String::from_utf8(id.to_vec()).ok())
+            .ok_or_else(malformed)?;
+        let digest: [u8; DIGEST_LENGTH] = plaintext
+            .get(cursor + 2 + id_length..cursor + 2 + id_length + DIGEST_LENGTH)
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(malformed)?;
+        index.insert(key_id, digest);
+        cursor += 2 + id_length + DIGEST_LENGTH;
+    }
+    Ok((generation, index))
+}
+
+fn journal_checksum(body: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let digest = Sha3_256::digest(body);
+    let mut checksum = [0u8; CHECKSUM_LENGTH];
+    checksum.copy_from_slice(&digest[..CHECKSUM_LENGTH]);
+    checksum
+}
+
+// Frame: body length (u32) | body | checksum; body: op | key_id length (u16) | key_id | payload
+fn encode_journal_entry(entry: &JournalEntry) -> Vec<u8> {
+    let mut body = Vec::with_capacity(3 + entry.key_id.len() + entry.payload.len());
+    body.push(entry.op as u8);
+    body.extend_from_slice(&(entry.key_id.len() as u16).to_be_bytes());
+    body.extend_from_slice(entry.key_id.as_bytes());
+    body.extend_from_slice(&entry.payload);
+
+    let mut frame = Vec::with_capacity(4 + body.len() + CHECKSUM_LENGTH);
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&body);
+    frame.extend_from_slice(&journal_checksum(&body));
+    frame
+}
+
+// Returns the intact prefix of the journal; parsing stops at the first bad frame
+fn decode_journal(bytes: &[u8]) -> Vec<JournalEntry> {
+    let mut entries = Vec::new();
+    let mut cursor = 0;
+
+    while let Some(len_bytes) = bytes.get(cursor..cursor + 4) {
+        let length = u32::from_be_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]) as usize;
+        let body_start = cursor + 4;
+        let Some(body) = bytes.get(body_start..body_start + length) else { break };
+        let Some(checksum) = bytes.get(body_start + length..body_start + length + CHECKSUM_LENGTH) else { break };
+        if checksum != journal_checksum(body) || body.len() < 3 {
+            break;
+        }
+
+        let op = match body[0] {
+            1 => JournalOp::Put,
+            2 => JournalOp::Delete,
+            3 => JournalOp::Batch,
+            4 => JournalOp::Watermark,
+            _ => break,
+        };
+        let id_length = u16::from_be_bytes([body[1], body[2]]) as usize;
+        let Some(key_id) = body.get(3..3 + id_length).and_then(|id| String::from_utf8(id.to_vec()).ok()) else { break };
+
+        entries.push(JournalEntry {
+            op,
+            key_id,
+            payload: body[3 + id_length..].to_vec(),
+        });
+        cursor = body_start + length + CHECKSUM_LENGTH;
+    }
+    entries
+}
+
+// Number of frames the journal appears to contain, including a torn tail
+fn count_journal_frames(bytes: &[u8]) -> usize {
+    let mut count = 0;
+    let mut cursor = 0;
+    while let Some(len_bytes) = bytes.get(cursor..cursor + 4) {
+        count += 1;
+        let length = u32::from_be_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]) as usize;
+        cursor += 4 + length + CHECKSUM_LENGTH;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn master_key() -> SecureSecret {
+        SecureSecret::from_bytes(&[0x11u8; 32])
+    }
+
+    fn secret_bytes(record: &KeyRecord) -> Vec<u8> {
+        record.secret.with_exposed(|bytes| bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_records_survive_reopen() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+            let metadata = KeyMetadata {
+                owner: Some("treasury".to_string()),
+                ..KeyMetadata::default()
+            };
+            store.put(KeyRecord::with_metadata("settlement", SecureSecret::from_bytes(&[1u8; 32]), metadata)).unwrap();
+            store.put(KeyRecord::new("reporting", SecureSecret::from_bytes(&[2u8; 32]))).unwrap();
+            assert!(store.delete("reporting").unwrap());
+        }
+
+        let store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+        assert_eq!(store.key_ids().unwrap(), vec!["settlement".to_string()]);
+        let record = store.get("settlement").unwrap().unwrap();
+        assert_eq!(secret_bytes(&record), vec![1u8; 32]);
+        assert_eq!(record.metadata.owner.as_deref(), Some("treasury"));
+        assert!(store.get("reporting").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_no_plaintext_on_disk() {
+        let dir = TempDir::new().unwrap();
+        let marker = b"PLAINTEXT-MARKER-0123456789abcdef";
+        let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+        store.put(KeyRecord::new("marker", SecureSecret::from_bytes(marker))).unwrap();
+
+        let bytes = fs::read(store.record_path("marker")).unwrap();
+        assert!(!bytes.windows(marker.len()).any(|w| w == marker));
+    }
+
+    #[test]
+    fn test_second_open_is_locked_out() {
+        let dir = TempDir::new().unwrap();
+        let _store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+        assert!(matches!(
+            FileKeyStore::open(dir.path(), &master_key()),
+            Err(KmsError::StoreLocked(_))
+        ));
+    }
+
+    #[test]
+    fn test_wrong_master_key_rejected() {
+        let dir = TempDir::new().unwrap();
+        drop(FileKeyStore::open(dir.path(), &master_key()).unwrap());
+        assert!(matches!(
+            FileKeyStore::open(dir.path(), &SecureSecret::from_bytes(&[0x22u8; 32])),
+            Err(KmsError::IntegrityCheckFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_tampered_record_detected_on_open() {
+        let dir = TempDir::new().unwrap();
+        let path = {
+            let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+            store.put(KeyRecord::new("settlement", SecureSecret::from_bytes(&[1u8; 32]))).unwrap();
+            store.record_path("settlement")
+        };
+
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        fs::write(&path, bytes).unwrap();
+
+        assert!(matches!(
+            FileKeyStore::open(dir.path(), &master_key()),
+            Err(KmsError::IntegrityCheckFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_renamed_record_detected_on_open() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+            store.put(KeyRecord::new("settlement", SecureSecret::from_bytes(&[1u8; 32]))).unwrap();
+            let from = store.record_path("settlement");
+            fs::rename(from, store.record_path("reporting")).unwrap();
+        }
+        assert!(FileKeyStore::open(dir.path(), &master_key()).is_err());
+    }
+
+    #[test]
+    fn test_deleted_record_detected_on_open() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+            store.put(KeyRecord::new("settlement", SecureSecret::from_bytes(&[1u8; 32]))).unwrap();
+            store.put(KeyRecord::new("reporting", SecureSecret::from_bytes(&[2u8; 32]))).unwrap();
+            fs::remove_file(store.record_path("reporting")).unwrap();
+        }
+        assert!(matches!(
+            FileKeyStore::open(dir.path(), &master_key()),
+            Err(KmsError::IntegrityCheckFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_rolled_back_record_detected_on_open() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+            store.put(KeyRecord::new("settlement", SecureSecret::from_bytes(&[1u8; 32]))).unwrap();
+            let path = store.record_path("settlement");
+            let old = fs::read(&path).unwrap();
+            let generation = store.generation();
+            store.put(KeyRecord::new("settlement", SecureSecret::from_bytes(&[2u8; 32]))).unwrap();
+            assert_eq!(store.generation(), generation + 1);
+            fs::write(&path, old).unwrap();
+        }
+        assert!(matches!(
+            FileKeyStore::open(dir.path(), &master_key()),
+            Err(KmsError::IntegrityCheckFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_journal_replayed_after_crash() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+            store.put(KeyRecord::new("old", SecureSecret::from_bytes(&[1u8; 32]))).unwrap();
+
+            // Crash after the journal append but before the record is written
+            let plaintext = encode_record(&KeyRecord::new("pending", SecureSecret::from_bytes(&[9u8; 32]))).unwrap();
+            let payload = store.encrypt("pending", &plaintext).unwrap();
+            store.log(&JournalEntry { op: JournalOp::Put, key_id: "pending".to_string(), payload }).unwrap();
+            store.log(&JournalEntry { op: JournalOp::Delete, key_id: "old".to_string(), payload: Vec::new() }).unwrap();
+        }
+
+        let store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+        assert_eq!(store.key_ids().unwrap(), vec!["pending".to_string()]);
+        assert_eq!(secret_bytes(&store.get("pending").unwrap().unwrap()), vec![9u8; 32]);
+    }
+
+    #[test]
+    fn test_torn_journal_tail_discarded() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+            let plaintext = encode_record(&KeyRecord::new("complete", SecureSecret::from_bytes(&[3u8; 32]))).unwrap();
+            let payload = store.encrypt("complete", &plaintext).unwrap();
+            store.log(&JournalEntry { op: JournalOp::Put, key_id: "complete".to_string(), payload }).unwrap();
+
+            let plaintext = encode_record(&KeyRecord::new("torn", SecureSecret::from_bytes(&[4u8; 32]))).unwrap();
+            let payload = store.encrypt("torn", &plaintext).unwrap();
+            let frame = encode_journal_entry(&JournalEntry { op: JournalOp::Put, key_id: "torn".to_string(), payload });
+            store.journal.write_all(&frame[..frame.len() / 2]).unwrap();
+        }
+
+        let store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+        assert_eq!(store.key_ids().unwrap(), vec!["complete".to_string()]);
+    }
+
+    #[test]
+    fn test_batch_replayed_whole_or_not_at_all() {
+        let dir = TempDir::new().unwrap();
+        let records = || {
+            vec![
+                KeyRecord::new("settlement", SecureSecret::from_bytes(&[1u8; 32])),
+                KeyRecord::new("reporting", SecureSecret::from_bytes(&[2u8; 32])),
+            ]
+        };
+        {
+            let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+            store.put_batch(records(), Some(1_700_000_000)).unwrap();
+
+            // Crash while appending a second batch
+            let entry = store.put_entry(KeyRecord::new("torn", SecureSecret::from_bytes(&[3u8; 32]))).unwrap();
+            let payload = encode_journal_entry(&entry);
+            let frame = encode_journal_entry(&JournalEntry { op: JournalOp::Batch, key_id: String::new(), payload });
+            store.journal.write_all(&frame[..frame.len() - 1]).unwrap();
+        }
+
+        let mut store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+        assert_eq!(store.key_ids().unwrap(), vec!["reporting".to_string(), "settlement".to_string()]);
+        assert_eq!(store.backup_watermark().unwrap(), Some(1_700_000_000));
+
+        // Crash after the append: the whole batch is replayed on open
+        let mut payload = Vec::new();
+        for record in records() {
+            let record = KeyRecord::new(&format!("{}-2", record.key_id), record.secret);
+            payload.extend_from_slice(&encode_journal_entry(&store.put_entry(record).unwrap()));
+        }
+        store.log(&JournalEntry { op: JournalOp::Batch, key_id: String::new(), payload }).unwrap();
+        drop(store);
+        let store = FileKeyStore::open(dir.path(), &master_key()).unwrap();
+        assert_eq!(store.key_ids().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let mut store = MemoryKeyStore::new();
+        store.put(KeyRecord::new("a", SecureSecret::from_bytes(&[5u8; 16]))).unwrap();
+        assert!(store.contains("a").unwrap());
+        assert_eq!(secret_bytes(&store.get("a").unwrap().unwrap()), vec![5u8; 16]);
+        assert!(store.delete("a").unwrap());
+        assert!(!store.delete("a").unwrap());
+    }
+}
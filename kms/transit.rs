@@ -0,0 +1,459 @@
+//! Master-key provider backed by a HashiCorp Vault Transit-compatible API,
+//! and an in-process mock of that API for tests.
+//!
+//! [`VaultTransitProvider`] uses four Transit endpoints under its mount:
+//!
+//! | call                             | request             | response                  |
+//! |----------------------------------|---------------------|---------------------------|
+//! | `POST /v1/<mount>/encrypt/<key>` | `{"plaintext": b64}`  | `{"data": {"ciphertext"}}` |
+//! | `POST /v1/<mount>/decrypt/<key>` | `{"ciphertext": ..}`  | `{"data": {"plaintext"}}`  |
+//! | `POST /v1/<mount>/rewrap/<key>`  | `{"ciphertext": ..}`  | `{"data": {"ciphertext"}}` |
+//! | `POST /v1/<mount>/keys/<key>/rotate` | `{}`            | 200 or 204                |
+//!
+//! Ciphertexts are Vault's `vault:v<version>:<base64>` strings; the wrapped
+//! master key is that string, so a rewrap never exposes the key to this
+//! process. Requests carry the token in `X-Vault-Token` and always go over
+//! TLS; the server certificate must be valid for the address's host and
+//! issued by a CA in the provider's [`client_config`](crate::utils::tls::client_config).
+//!
+//! [`MockTransitServer`] answers the same calls from memory over TLS, with
+//! one AES-256-GCM key per version, so providers and stores can be tested
+//! without a Vault.
+
+use super::provider::MasterKeyProvider;
+use crate::crypto::secure::SecureSecret;
+use crate::error::KmsError;
+use crate::utils::encoding;
+use crate::utils::http::{self, HttpResponse, DEFAULT_TIMEOUT};
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use rustls::{ClientConfig, ServerConfig, ServerConnection, StreamOwned};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+pub const DEFAULT_MOUNT: &str = "transit";
+const TOKEN_HEADER: &str = "X-Vault-Token";
+const CIPHERTEXT_PREFIX: &str = "vault:v";
+const NONCE_LENGTH: usize = 12;
+// Requests to the mock are small JSON documents
+const MAX_REQUEST_LENGTH: usize = 64 * 1024;
+
+/// Wraps store master keys with a named key in a Vault Transit-compatible service
+#[derive(Debug)]
+pub struct VaultTransitProvider {
+    address: String,
+    mount: String,
+    key_name: String,
+    token: SecureSecret,
+    tls: Arc<ClientConfig>,
+    timeout: Duration,
+}
+
+// Every Transit response of interest has its result under "data"
+#[derive(Deserialize)]
+struct TransitResponse {
+    data: TransitData,
+}
+
+#[derive(Deserialize)]
+struct TransitData {
+    #[serde(default)]
+    ciphertext: Option<String>,
+    #[serde(default)]
+    plaintext: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TransitErrors {
+    errors: Vec<String>,
+}
+
+impl VaultTransitProvider {
+    /// Provider for Transit key `key_name` at `address` (`host:port`) under
+    /// the default `transit` mount, verifying the server with `tls`
+    pub fn new(
+        address: impl Into<String>,
+        key_name: impl Into<String>,
+        token: SecureSecret,
+        tls: Arc<ClientConfig>,
+    ) -> Self {
+        Self {
+            address: address.into(),
+            mount: DEFAULT_MOUNT.to_string(),
+            key_name: key_name.into(),
+            token,
+            tls,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_mount(mut self, mount: impl Into<String>) -> Self {
+        self.mount = mount.into();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn post(&self, path: &str, body: Value) -> Result<HttpResponse, KmsError> {
+        let path = format!("/v1/{}/{}", self.mount, path);
+        let body = Zeroizing::new(body.to_string().into_bytes());
+        let token = self
+            .token
+            .with_exposed(|token| String::from_utf8(token.to_vec()))?
+            .map(Zeroizing::new)
+            .map_err(|_| KmsError::ProviderUnavailable("Vault token is not UTF-8".to_string()))?;
+        let headers = [(TOKEN_HEADER, token.as_str()), ("Content-Type", "application/json")];
+        let response = http::request_tls(&self.address, &self.tls, "POST", &path, &headers, Some(&body), self.timeout)
+            .map_err(|e| KmsError::ProviderUnavailable(format!("{}: {}", self.describe(), e)))?;
+        if response.is_success() {
+            return Ok(response);
+        }
+
+        let reason = serde_json::from_slice::<TransitErrors>(&response.body)
+            .map(|errors| errors.errors.join("; "))
+            .unwrap_or_else(|_| format!("HTTP {}", response.status));
+        let message = format!("{} answered {}: {}", self.describe(), response.status, reason);
+        Err(match response.status {
+            // Vault answers a bad or foreign ciphertext with 400
+            400 => KmsError::UnsealFailed(message),
+            401 | 403 => KmsError::AccessDenied(message),
+            _ => KmsError::ProviderUnavailable(message),
+        })
+    }
+
+    fn data(&self, response: HttpResponse) -> Result<TransitData, KmsError> {
+        serde_json::from_slice::<TransitResponse>(&response.body)
+            .map(|response| response.data)
+            .map_err(|e| KmsError::ProviderUnavailable(format!("{}: malformed response: {}", self.describe(), e)))
+    }
+
+    fn ciphertext(&self, data: TransitData) -> Result<Vec<u8>, KmsError> {
+        data.ciphertext
+            .map(String::into_bytes)
+            .ok_or_else(|| KmsError::ProviderUnavailable(format!("{}: response has no ciphertext", self.describe())))
+    }
+}
+
+impl MasterKeyProvider for VaultTransitProvider {
+    fn describe(&self) -> String {
+        format!("vault-transit:{}/{}/{}", self.address, self.mount, self.key_name)
+    }
+
+    fn wrap(&self, key: &SecureSecret) -> Result<Vec<u8>, KmsError> {
+        let plaintext = Zeroizing::new(key.with_exposed(encoding::to_base64)?);
+        let response = self.post(&format!("encrypt/{}", self.key_name), json!({ "plaintext": plaintext.as_str() }))?;
+        self.ciphertext(self.data(response)?)
+    }
+
+    fn unwrap(&self, wrapped: &[u8]) -> Result<SecureSecret, KmsError> {
+        let ciphertext = ciphertext_str(wrapped)?;
+        let response = self.post(&format!("decrypt/{}", self.key_name), json!({ "ciphertext": ciphertext }))?;
+        let plaintext = Zeroizing::new(
+            self.data(response)?
+                .plaintext
+                .ok_or_else(|| KmsError::ProviderUnavailable(format!("{}: response has no plaintext", self.describe())))?,
+        );
+        let key = Zeroizing::new(encoding::from_base64(&plaintext).map_err(|e| KmsError::UnsealFailed(e.to_string()))?);
+        Ok(SecureSecret::from_bytes(&key))
+    }
+
+    fn rotate(&self) -> Result<(), KmsError> {
+        // Always a body, even if empty: HTTP/1.0 servers may reject a POST
+        // without a Content-Length
+        self.post(&format!("keys/{}/rotate", self.key_name), json!({})).map(|_| ())
+    }
+
+    fn rewrap(&self, wrapped: &[u8]) -> Result<Vec<u8>, KmsError> {
+        let ciphertext = ciphertext_str(wrapped)?;
+        let response = self.post(&format!("rewrap/{}", self.key_name), json!({ "ciphertext": ciphertext }))?;
+        self.ciphertext(self.data(response)?)
+    }
+}
+
+fn ciphertext_str(wrapped: &[u8]) -> Result<&str, KmsError> {
+    std::str::from_utf8(wrapped)
+        .ok()
+        .filter(|ciphertext| ciphertext.starts_with(CIPHERTEXT_PREFIX))
+        .ok_or_else(|| KmsError::UnsealFailed("not a Vault Transit ciphertext".to_string()))
+}
+
+/// In-memory Transit service on a loopback TLS port, for tests. Keys are
+/// created on first use, as with Vault's `upsert` policy; each connection
+/// carries one request. Stops when dropped.
+pub struct MockTransitServer {
+    address: SocketAddr,
+    state: Arc<MockState>,
+    stopping: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+struct MockState {
+    token: String,
+    tls: Arc<ServerConfig>,
+    // Key name -> AES-256 key per version, version n at index n - 1
+    keys: Mutex<HashMap<String, Vec<Zeroizing<[u8; 32]>>>>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MockTransitServer {
+    /// Starts the server with the certificate in `tls`; requests must carry `token`
+    pub fn start(token: &str, tls: Arc<ServerConfig>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0))?;
+        let address = listener.local_addr()?;
+        let state = Arc::new(MockState {
+            token: token.to_string(),
+            tls,
+            keys: Mutex::default(),
+            requests: Mutex::default(),
+        });
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        let worker = {
+            let (state, stopping) = (state.clone(), stopping.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopping.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        // A broken client connection only affects that client
+                        let _ = state.serve(stream);
+                    }
+                }
+            })
+        };
+        Ok(Self { address, state, stopping, worker: Some(worker) })
+    }
+
+    /// `host:port` to hand to [`VaultTransitProvider::new`]
+    pub fn address(&self) -> String {
+        self.address.to_string()
+    }
+
+    /// Current version of `key_name`, 0 if it was never used
+    pub fn key_version(&self, key_name: &str) -> u32 {
+        self.state.lock_keys().get(key_name).map_or(0, |versions| versions.len() as u32)
+    }
+
+    /// `METHOD path` of every request served so far
+    pub fn requests(&self) -> Vec<String> {
+        self.state.requests.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl Drop for MockTransitServer {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wakes the accept loop so it sees the flag
+        let _ = TcpStream::connect(self.address);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl MockState {
+    fn lock_keys(&self) -> std::sync::MutexGuard<'_, HashMap<String, Vec<Zeroizing<[u8; 32]>>>> {
+        self.keys.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn serve(&self, stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(DEFAULT_TIMEOUT))?;
+        let connection = ServerConnection::new(self.tls.clone()).map_err(std::io::Error::other)?;
+        let mut reader = BufReader::new(StreamOwned::new(connection, stream));
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or_default().to_string(), parts.next().unwrap_or_default().to_string());
+
+        let (mut token, mut length) = (None, 0);
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "x-vault-token" => token = Some(value.trim().to_string()),
+                    "content-length" => length = value.trim().parse().unwrap_or(0),
+                    _ => {}
+                }
+            }
+        }
+        let mut body = vec![0u8; length.min(MAX_REQUEST_LENGTH)];
+        reader.read_exact(&mut body)?;
+        self.requests.lock().unwrap_or_else(|e| e.into_inner()).push(format!("{} {}", method, path));
+
+        let (status, response) = if token.as_deref() != Some(self.token.as_str()) {
+            (403, json!({ "errors": ["permission denied"] }))
+        } else if method != "POST" {
+            (405, json!({ "errors": ["unsupported method"] }))
+        } else {
+            let request = serde_json::from_slice(&body).unwrap_or(Value::Null);
+            self.handle(&path, &request).unwrap_or_else(|(status, error)| (status, json!({ "errors": [error] })))
+        };
+
+        let body = response.to_string();
+        let reason = if status == 200 { "OK" } else { "Error" };
+        let stream = reader.get_mut();
+        write!(
+            stream,
+            "HTTP/1.0 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            reason,
+            body.len(),
+            body
+        )?;
+        stream.conn.send_close_notify();
+        stream.flush()
+    }
+
+    fn handle(&self, path: &str, request: &Value) -> Result<(u16, Value), (u16, String)> {
+        let route = path.strip_prefix("/v1/").and_then(|rest| rest.split_once('/')).map(|(_, route)| route);
+        let field = |name: &str| request[name].as_str().ok_or((400, format!("missing {}", name)));
+        match route.and_then(|route| route.split_once('/')) {
+            Some(("encrypt", key)) => {
+                let plaintext = Zeroizing::new(encoding::from_base64(field("plaintext")?).map_err(|e| (400, e.to_string()))?);
+                let ciphertext = self.encrypt(key, &plaintext);
+                Ok((200, json!({ "data": { "ciphertext": ciphertext } })))
+            }
+            Some(("decrypt", key)) => {
+                let plaintext = self.decrypt(key, field("ciphertext")?)?;
+                Ok((200, json!({ "data": { "plaintext": encoding::to_base64(&plaintext) } })))
+            }
+            Some(("rewrap", key)) => {
+                let plaintext = self.decrypt(key, field("ciphertext")?)?;
+                Ok((200, json!({ "data": { "ciphertext": self.encrypt(key, &plaintext) } })))
+            }
+            Some(("keys", rest)) => match rest.split_once('/') {
+                Some((key, "rotate")) => {
+                    let mut keys = self.lock_keys();
+                    let versions = keys.entry(key.to_string()).or_default();
+                    versions.push(new_key());
+                    Ok((200, json!({ "data": { "name": key, "latest_version": versions.len() } })))
+                }
+                _ => Err((404, "no handler for route".to_string())),
+            },
+            _ => Err((404, "no handler for route".to_string())),
+        }
+    }
+
+    fn encrypt(&self, key_name: &str, plaintext: &[u8]) -> String {
+        let mut keys = self.lock_keys();
+        let versions = keys.entry(key_name.to_string()).or_default();
+        if versions.is_empty() {
+            versions.push(new_key());
+        }
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let cipher = Aes256Gcm::new_from_slice(versions[versions.len() - 1].as_ref()).expect("32-byte key");
+        let mut sealed = nonce.to_vec();
+        sealed.extend(cipher.encrypt(Nonce::from_slice(&nonce), plaintext).expect("in-memory encryption"));
+        format!("{}{}:{}", CIPHERTEXT_PREFIX, versions.len(), encoding::to_base64(&sealed))
+    }
+
+    fn decrypt(&self, key_name: &str, ciphertext: &str) -> Result<Zeroizing<Vec<u8>>, (u16, String)> {
+        let invalid = || (400, "invalid ciphertext".to_string());
+        let (version, sealed) = ciphertext
+            .strip_prefix(CIPHERTEXT_PREFIX)
+            .and_then(|rest| rest.split_once(':'))
+            .ok_or_else(invalid)?;
+        let version: usize = version.parse().map_err(|_| invalid())?;
+        let sealed = encoding::from_base64(sealed).map_err(|_| invalid())?;
+        if sealed.len() < NONCE_LENGTH {
+            return Err(invalid());
+        }
+        let keys = self.lock_keys();
+        let key = keys
+            .get(key_name)
+            .and_then(|versions| versions.get(version.checked_sub(1)?))
+            .ok_or_else(invalid)?;
+        let (nonce, sealed) = sealed.split_at(NONCE_LENGTH);
+        Aes256Gcm::new_from_slice(key.as_ref())
+            .expect("32-byte key")
+            .decrypt(Nonce::from_slice(nonce), sealed)
+            .map(Zeroizing::new)
+            .map_err(|_| (400, "cipher: message authentication failed".to_string()))
+    }
+}
+
+fn new_key() -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    rand::thread_rng().fill_bytes(key.as_mut());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::access::administrator_policy;
+    use crate::crypto::kms::{CallerContext, KeyManagementSystem};
+    use crate::kms::FileKeyStore;
+    use crate::utils::tls::{self, TestPki};
+
+    fn token() -> SecureSecret {
+        SecureSecret::from_bytes(b"s.test-token")
+    }
+
+    // Mock Vault with a certificate from a fresh test CA, and a client trusting that CA
+    fn vault() -> (MockTransitServer, Arc<ClientConfig>) {
+        let pki = TestPki::new();
+        let vault = MockTransitServer::start("s.test-token", tls::server_config(&pki.server, None).unwrap()).unwrap();
+        (vault, tls::client_config(&pki.ca_pem, None).unwrap())
+    }
+
+    #[test]
+    fn test_store_unsealed_through_transit() {
+        let (vault, client) = vault();
+        let provider = VaultTransitProvider::new(vault.address(), "kms-master", token(), client.clone());
+        let root = tempfile::tempdir().unwrap();
+        let admin = CallerContext::new("kms-admin");
+
+        let mut kms = KeyManagementSystem::unseal_store(root.path(), &provider).unwrap();
+        kms.set_access_policy(administrator_policy(&admin.principal));
+        let public_key = kms.generate_kem_key(&admin, "inbound").unwrap();
+        drop(kms);
+        assert_eq!(vault.key_version("kms-master"), 1);
+
+        // After a Transit rotation the store rewraps in place and still opens
+        provider.rotate().unwrap();
+        FileKeyStore::open_with_provider(root.path(), &provider)
+            .unwrap()
+            .rewrap_master_key(&provider)
+            .unwrap();
+        let wrapped = std::fs::read_to_string(root.path().join("MASTER")).unwrap();
+        assert!(wrapped.starts_with("vault:v2:"));
+        assert!(vault.requests().iter().any(|request| request == "POST /v1/transit/rewrap/kms-master"));
+
+        let other_key = VaultTransitProvider::new(vault.address(), "other", token(), client.clone());
+        assert!(matches!(KeyManagementSystem::unseal_store(root.path(), &other_key), Err(KmsError::UnsealFailed(_))));
+        let mut kms = KeyManagementSystem::unseal_store(root.path(), &provider).unwrap();
+        kms.set_access_policy(administrator_policy(&admin.principal));
+        assert_eq!(kms.kem_public_key(&admin, "inbound").unwrap(), public_key);
+
+        let bad_token = VaultTransitProvider::new(vault.address(), "kms-master", SecureSecret::from_bytes(b"s.wrong"), client);
+        assert!(matches!(bad_token.unwrap(wrapped.as_bytes()), Err(KmsError::AccessDenied(_))));
+    }
+
+    #[test]
+    fn test_untrusted_server_refused() {
+        let (vault, _) = vault();
+        let stranger = tls::client_config(&TestPki::new().ca_pem, None).unwrap();
+        let provider = VaultTransitProvider::new(vault.address(), "kms-master", token(), stranger);
+        let err = provider.wrap(&SecureSecret::from_bytes(&[7u8; 32])).unwrap_err();
+        assert!(matches!(err, KmsError::ProviderUnavailable(ref m) if m.contains("certificate")), "{}", err);
+        assert!(vault.requests().is_empty());
+    }
+}
@@ -0,0 +1,461 @@
+//! Cryptoki types, constants and function lists, per the PKCS#11 3.2 headers.
+//!
+//! Only the subset the provider uses is declared. Structures use the Unix
+//! layout (natural alignment, `CK_ULONG` = `unsigned long`).
+
+use std::os::raw::{c_ulong, c_void};
+
+pub type CK_BYTE = u8;
+pub type CK_CHAR = u8;
+pub type CK_UTF8CHAR = u8;
+pub type CK_BBOOL = u8;
+pub type CK_ULONG = c_ulong;
+pub type CK_FLAGS = CK_ULONG;
+pub type CK_RV = CK_ULONG;
+pub type CK_SLOT_ID = CK_ULONG;
+pub type CK_SESSION_HANDLE = CK_ULONG;
+pub type CK_OBJECT_HANDLE = CK_ULONG;
+pub type CK_OBJECT_CLASS = CK_ULONG;
+pub type CK_KEY_TYPE = CK_ULONG;
+pub type CK_ATTRIBUTE_TYPE = CK_ULONG;
+pub type CK_MECHANISM_TYPE = CK_ULONG;
+pub type CK_USER_TYPE = CK_ULONG;
+pub type CK_STATE = CK_ULONG;
+pub type CK_NOTIFICATION = CK_ULONG;
+pub type CK_VOID_PTR = *mut c_void;
+pub type CK_BYTE_PTR = *mut CK_BYTE;
+pub type CK_ULONG_PTR = *mut CK_ULONG;
+pub type CK_FLAGS_PTR = *mut CK_FLAGS;
+pub type CK_UTF8CHAR_PTR = *mut CK_UTF8CHAR;
+pub type CK_SLOT_ID_PTR = *mut CK_SLOT_ID;
+pub type CK_SESSION_HANDLE_PTR = *mut CK_SESSION_HANDLE;
+pub type CK_OBJECT_HANDLE_PTR = *mut CK_OBJECT_HANDLE;
+pub type CK_MECHANISM_TYPE_PTR = *mut CK_MECHANISM_TYPE;
+pub type CK_NOTIFY = Option<unsafe extern "C" fn(CK_SESSION_HANDLE, CK_NOTIFICATION, CK_VOID_PTR) -> CK_RV>;
+
+pub const CK_TRUE: CK_BBOOL = 1;
+pub const CK_FALSE: CK_BBOOL = 0;
+pub const CK_UNAVAILABLE_INFORMATION: CK_ULONG = !0;
+pub const CK_EFFECTIVELY_INFINITE: CK_ULONG = 0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CK_VERSION {
+    pub major: CK_BYTE,
+    pub minor: CK_BYTE,
+}
+
+#[repr(C)]
+pub struct CK_INFO {
+    pub cryptokiVersion: CK_VERSION,
+    pub manufacturerID: [CK_UTF8CHAR; 32],
+    pub flags: CK_FLAGS,
+    pub libraryDescription: [CK_UTF8CHAR; 32],
+    pub libraryVersion: CK_VERSION,
+}
+
+#[repr(C)]
+pub struct CK_SLOT_INFO {
+    pub slotDescription: [CK_UTF8CHAR; 64],
+    pub manufacturerID: [CK_UTF8CHAR; 32],
+    pub flags: CK_FLAGS,
+    pub hardwareVersion: CK_VERSION,
+    pub firmwareVersion: CK_VERSION,
+}
+
+#[repr(C)]
+pub struct CK_TOKEN_INFO {
+    pub label: [CK_UTF8CHAR; 32],
+    pub manufacturerID: [CK_UTF8CHAR; 32],
+    pub model: [CK_UTF8CHAR; 16],
+    pub serialNumber: [CK_CHAR; 16],
+    pub flags: CK_FLAGS,
+    pub ulMaxSessionCount: CK_ULONG,
+    pub ulSessionCount: CK_ULONG,
+    pub ulMaxRwSessionCount: CK_ULONG,
+    pub ulRwSessionCount: CK_ULONG,
+    pub ulMaxPinLen: CK_ULONG,
+    pub ulMinPinLen: CK_ULONG,
+    pub ulTotalPublicMemory: CK_ULONG,
+    pub ulFreePublicMemory: CK_ULONG,
+    pub ulTotalPrivateMemory: CK_ULONG,
+    pub ulFreePrivateMemory: CK_ULONG,
+    pub hardwareVersion: CK_VERSION,
+    pub firmwareVersion: CK_VERSION,
+    pub utcTime: [CK_CHAR; 16],
+}
+
+#[repr(C)]
+pub struct CK_SESSION_INFO {
+    pub slotID: CK_SLOT_ID,
+    pub state: CK_STATE,
+    pub flags: CK_FLAGS,
+    pub ulDeviceError: CK_ULONG,
+}
+
+#[repr(C)]
+pub struct CK_ATTRIBUTE {
+    pub type_: CK_ATTRIBUTE_TYPE,
+    pub pValue: CK_VOID_PTR,
+    pub ulValueLen: CK_ULONG,
+}
+
+#[repr(C)]
+pub struct CK_MECHANISM {
+    pub mechanism: CK_MECHANISM_TYPE,
+    pub pParameter: CK_VOID_PTR,
+    pub ulParameterLen: CK_ULONG,
+}
+
+#[repr(C)]
+pub struct CK_GCM_PARAMS {
+    pub pIv: CK_BYTE_PTR,
+    pub ulIvLen: CK_ULONG,
+    pub ulIvBits: CK_ULONG,
+    pub pAAD: CK_BYTE_PTR,
+    pub ulAADLen: CK_ULONG,
+    pub ulTagBits: CK_ULONG,
+}
+
+#[repr(C)]
+pub struct CK_MECHANISM_INFO {
+    pub ulMinKeySize: CK_ULONG,
+    pub ulMaxKeySize: CK_ULONG,
+    pub flags: CK_FLAGS,
+}
+
+#[repr(C)]
+pub struct CK_C_INITIALIZE_ARGS {
+    pub CreateMutex: CK_VOID_PTR,
+    pub DestroyMutex: CK_VOID_PTR,
+    pub LockMutex: CK_VOID_PTR,
+    pub UnlockMutex: CK_VOID_PTR,
+    pub flags: CK_FLAGS,
+    pub pReserved: CK_VOID_PTR,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CK_INTERFACE {
+    pub pInterfaceName: *const CK_CHAR,
+    pub pFunctionList: *const c_void,
+    pub flags: CK_FLAGS,
+}
+
+pub type CK_ATTRIBUTE_PTR = *mut CK_ATTRIBUTE;
+pub type CK_MECHANISM_PTR = *mut CK_MECHANISM;
+
+// Return values
+pub const CKR_OK: CK_RV = 0x00;
+pub const CKR_HOST_MEMORY: CK_RV = 0x02;
+pub const CKR_SLOT_ID_INVALID: CK_RV = 0x03;
+pub const CKR_GENERAL_ERROR: CK_RV = 0x05;
+pub const CKR_FUNCTION_FAILED: CK_RV = 0x06;
+pub const CKR_ARGUMENTS_BAD: CK_RV = 0x07;
+pub const CKR_CANT_LOCK: CK_RV = 0x0a;
+pub const CKR_ATTRIBUTE_SENSITIVE: CK_RV = 0x11;
+pub const CKR_ATTRIBUTE_TYPE_INVALID: CK_RV = 0x12;
+pub const CKR_ATTRIBUTE_VALUE_INVALID: CK_RV = 0x13;
+pub const CKR_ACTION_PROHIBITED: CK_RV = 0x1b;
+pub const CKR_DEVICE_ERROR: CK_RV = 0x30;
+pub const CKR_DEVICE_MEMORY: CK_RV = 0x31;
+pub const CKR_ENCRYPTED_DATA_INVALID: CK_RV = 0x40;
+pub const CKR_ENCRYPTED_DATA_LEN_RANGE: CK_RV = 0x41;
+pub const CKR_FUNCTION_NOT_SUPPORTED: CK_RV = 0x54;
+pub const CKR_KEY_HANDLE_INVALID: CK_RV = 0x60;
+pub const CKR_KEY_SIZE_RANGE: CK_RV = 0x62;
+pub const CKR_KEY_TYPE_INCONSISTENT: CK_RV = 0x63;
+pub const CKR_KEY_FUNCTION_NOT_PERMITTED: CK_RV = 0x68;
+pub const CKR_MECHANISM_INVALID: CK_RV = 0x70;
+pub const CKR_MECHANISM_PARAM_INVALID: CK_RV = 0x71;
+pub const CKR_OBJECT_HANDLE_INVALID: CK_RV = 0x82;
+pub const CKR_OPERATION_ACTIVE: CK_RV = 0x90;
+pub const CKR_OPERATION_NOT_INITIALIZED: CK_RV = 0x91;
+pub const CKR_PIN_INCORRECT: CK_RV = 0xa0;
+pub const CKR_PIN_LEN_RANGE: CK_RV = 0xa2;
+pub const CKR_SESSION_HANDLE_INVALID: CK_RV = 0xb3;
+pub const CKR_SESSION_PARALLEL_NOT_SUPPORTED: CK_RV = 0xb4;
+pub const CKR_SESSION_READ_ONLY: CK_RV = 0xb5;
+pub const CKR_TEMPLATE_INCONSISTENT: CK_RV = 0xd1;
+pub const CKR_USER_ALREADY_LOGGED_IN: CK_RV = 0x100;
+pub const CKR_USER_NOT_LOGGED_IN: CK_RV = 0x101;
+pub const CKR_USER_PIN_NOT_INITIALIZED: CK_RV = 0x102;
+pub const CKR_USER_TYPE_INVALID: CK_RV = 0x103;
+pub const CKR_BUFFER_TOO_SMALL: CK_RV = 0x150;
+pub const CKR_CRYPTOKI_NOT_INITIALIZED: CK_RV = 0x190;
+pub const CKR_CRYPTOKI_ALREADY_INITIALIZED: CK_RV = 0x191;
+
+// Flags
+pub const CKF_TOKEN_PRESENT: CK_FLAGS = 0x01;
+pub const CKF_RNG: CK_FLAGS = 0x01;
+pub const CKF_LOGIN_REQUIRED: CK_FLAGS = 0x04;
+pub const CKF_USER_PIN_INITIALIZED: CK_FLAGS = 0x08;
+pub const CKF_TOKEN_INITIALIZED: CK_FLAGS = 0x400;
+pub const CKF_RW_SESSION: CK_FLAGS = 0x02;
+pub const CKF_SERIAL_SESSION: CK_FLAGS = 0x04;
+pub const CKF_OS_LOCKING_OK: CK_FLAGS = 0x02;
+pub const CKF_ENCRYPT: CK_FLAGS = 0x0100;
+pub const CKF_DECRYPT: CK_FLAGS = 0x0200;
+pub const CKF_GENERATE_KEY_PAIR: CK_FLAGS = 0x0001_0000;
+pub const CKF_ENCAPSULATE: CK_FLAGS = 0x1000_0000;
+pub const CKF_DECAPSULATE: CK_FLAGS = 0x2000_0000;
+
+// Session states
+pub const CKS_RO_PUBLIC_SESSION: CK_STATE = 0;
+pub const CKS_RO_USER_FUNCTIONS: CK_STATE = 1;
+pub const CKS_RW_PUBLIC_SESSION: CK_STATE = 2;
+pub const CKS_RW_USER_FUNCTIONS: CK_STATE = 3;
+
+// User types
+pub const CKU_USER: CK_USER_TYPE = 1;
+
+// Object classes and key types
+pub const CKO_PUBLIC_KEY: CK_OBJECT_CLASS = 0x02;
+pub const CKO_PRIVATE_KEY: CK_OBJECT_CLASS = 0x03;
+pub const CKO_SECRET_KEY: CK_OBJECT_CLASS = 0x04;
+pub const CKK_GENERIC_SECRET: CK_KEY_TYPE = 0x10;
+pub const CKK_AES: CK_KEY_TYPE = 0x1f;
+pub const CKK_ML_KEM: CK_KEY_TYPE = 0x49;
+
+// Attributes
+pub const CKA_CLASS: CK_ATTRIBUTE_TYPE = 0x000;
+pub const CKA_TOKEN: CK_ATTRIBUTE_TYPE = 0x001;
+pub const CKA_PRIVATE: CK_ATTRIBUTE_TYPE = 0x002;
+pub const CKA_LABEL: CK_ATTRIBUTE_TYPE = 0x003;
+pub const CKA_VALUE: CK_ATTRIBUTE_TYPE = 0x011;
+pub const CKA_KEY_TYPE: CK_ATTRIBUTE_TYPE = 0x100;
+pub const CKA_ID: CK_ATTRIBUTE_TYPE = 0x102;
+pub const CKA_SENSITIVE: CK_ATTRIBUTE_TYPE = 0x103;
+pub const CKA_ENCRYPT: CK_ATTRIBUTE_TYPE = 0x104;
+pub const CKA_DECRYPT: CK_ATTRIBUTE_TYPE = 0x105;
+pub const CKA_VALUE_LEN: CK_ATTRIBUTE_TYPE = 0x161;
+pub const CKA_EXTRACTABLE: CK_ATTRIBUTE_TYPE = 0x162;
+pub const CKA_LOCAL: CK_ATTRIBUTE_TYPE = 0x163;
+pub const CKA_NEVER_EXTRACTABLE: CK_ATTRIBUTE_TYPE = 0x164;
+pub const CKA_ALWAYS_SENSITIVE: CK_ATTRIBUTE_TYPE = 0x165;
+pub const CKA_MODIFIABLE: CK_ATTRIBUTE_TYPE = 0x170;
+pub const CKA_PARAMETER_SET: CK_ATTRIBUTE_TYPE = 0x61d;
+pub const CKA_ENCAPSULATE: CK_ATTRIBUTE_TYPE = 0x633;
+pub const CKA_DECAPSULATE: CK_ATTRIBUTE_TYPE = 0x634;
+
+// Mechanisms and ML-KEM parameter sets
+pub const CKM_ML_KEM_KEY_PAIR_GEN: CK_MECHANISM_TYPE = 0x0f;
+pub const CKM_ML_KEM: CK_MECHANISM_TYPE = 0x17;
+pub const CKM_AES_GCM: CK_MECHANISM_TYPE = 0x1087;
+pub const CKP_ML_KEM_1024: CK_ULONG = 0x03;
+
+/// `CK_FUNCTION_LIST_3_2`. The 2.40 and 3.0 lists are prefixes of it, so one
+/// layout serves all three interfaces; only `version` differs.
+#[repr(C)]
+pub struct CK_FUNCTION_LIST_3_2 {
+    pub version: CK_VERSION,
+    pub C_Initialize: unsafe extern "C" fn(CK_VOID_PTR) -> CK_RV,
+    pub C_Finalize: unsafe extern "C" fn(CK_VOID_PTR) -> CK_RV,
+    pub C_GetInfo: unsafe extern "C" fn(*mut CK_INFO) -> CK_RV,
+    pub C_GetFunctionList: unsafe extern "C" fn(*mut *const CK_FUNCTION_LIST_3_2) -> CK_RV,
+    pub C_GetSlotList: unsafe extern "C" fn(CK_BBOOL, CK_SLOT_ID_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_GetSlotInfo: unsafe extern "C" fn(CK_SLOT_ID, *mut CK_SLOT_INFO) -> CK_RV,
+    pub C_GetTokenInfo: unsafe extern "C" fn(CK_SLOT_ID, *mut CK_TOKEN_INFO) -> CK_RV,
+    pub C_GetMechanismList: unsafe extern "C" fn(CK_SLOT_ID, CK_MECHANISM_TYPE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_GetMechanismInfo: unsafe extern "C" fn(CK_SLOT_ID, CK_MECHANISM_TYPE, *mut CK_MECHANISM_INFO) -> CK_RV,
+    pub C_InitToken: unsafe extern "C" fn(CK_SLOT_ID, CK_UTF8CHAR_PTR, CK_ULONG, CK_UTF8CHAR_PTR) -> CK_RV,
+    pub C_InitPIN: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_UTF8CHAR_PTR, CK_ULONG) -> CK_RV,
+    pub C_SetPIN: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_UTF8CHAR_PTR, CK_ULONG, CK_UTF8CHAR_PTR, CK_ULONG) -> CK_RV,
+    pub C_OpenSession: unsafe extern "C" fn(CK_SLOT_ID, CK_FLAGS, CK_VOID_PTR, CK_NOTIFY, CK_SESSION_HANDLE_PTR) -> CK_RV,
+    pub C_CloseSession: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    pub C_CloseAllSessions: unsafe extern "C" fn(CK_SLOT_ID) -> CK_RV,
+    pub C_GetSessionInfo: unsafe extern "C" fn(CK_SESSION_HANDLE, *mut CK_SESSION_INFO) -> CK_RV,
+    pub C_GetOperationState: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_SetOperationState: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_OBJECT_HANDLE, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_Login: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_USER_TYPE, CK_UTF8CHAR_PTR, CK_ULONG) -> CK_RV,
+    pub C_Logout: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    pub C_CreateObject: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_ATTRIBUTE_PTR, CK_ULONG, CK_OBJECT_HANDLE_PTR) -> CK_RV,
+    pub C_CopyObject: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_OBJECT_HANDLE, CK_ATTRIBUTE_PTR, CK_ULONG, CK_OBJECT_HANDLE_PTR) -> CK_RV,
+    pub C_DestroyObject: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_GetObjectSize: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_OBJECT_HANDLE, CK_ULONG_PTR) -> CK_RV,
+    pub C_GetAttributeValue: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_OBJECT_HANDLE, CK_ATTRIBUTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_SetAttributeValue: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_OBJECT_HANDLE, CK_ATTRIBUTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_FindObjectsInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_ATTRIBUTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_FindObjects: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_OBJECT_HANDLE_PTR, CK_ULONG, CK_ULONG_PTR) -> CK_RV,
+    pub C_FindObjectsFinal: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    pub C_EncryptInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_Encrypt: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_EncryptUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_EncryptFinal: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_DecryptInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_Decrypt: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_DecryptUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_DecryptFinal: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_DigestInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR) -> CK_RV,
+    pub C_Digest: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_DigestUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_DigestKey: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_DigestFinal: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_SignInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_Sign: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_SignUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_SignFinal: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_SignRecoverInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_SignRecover: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_VerifyInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_Verify: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_VerifyUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_VerifyFinal: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_VerifyRecoverInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_VerifyRecover: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_DigestEncryptUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_DecryptDigestUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_SignEncryptUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_DecryptVerifyUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_GenerateKey: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_ATTRIBUTE_PTR, CK_ULONG, CK_OBJECT_HANDLE_PTR) -> CK_RV,
+    pub C_GenerateKeyPair: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_MECHANISM_PTR,
+        CK_ATTRIBUTE_PTR,
+        CK_ULONG,
+        CK_ATTRIBUTE_PTR,
+        CK_ULONG,
+        CK_OBJECT_HANDLE_PTR,
+        CK_OBJECT_HANDLE_PTR,
+    ) -> CK_RV,
+    pub C_WrapKey: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE, CK_OBJECT_HANDLE, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_UnwrapKey: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_MECHANISM_PTR,
+        CK_OBJECT_HANDLE,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_ATTRIBUTE_PTR,
+        CK_ULONG,
+        CK_OBJECT_HANDLE_PTR,
+    ) -> CK_RV,
+    pub C_DeriveKey: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE, CK_ATTRIBUTE_PTR, CK_ULONG, CK_OBJECT_HANDLE_PTR) -> CK_RV,
+    pub C_SeedRandom: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_GenerateRandom: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_GetFunctionStatus: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    pub C_CancelFunction: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    pub C_WaitForSlotEvent: unsafe extern "C" fn(CK_FLAGS, CK_SLOT_ID_PTR, CK_VOID_PTR) -> CK_RV,
+    // PKCS#11 3.0
+    pub C_GetInterfaceList: unsafe extern "C" fn(*mut CK_INTERFACE, CK_ULONG_PTR) -> CK_RV,
+    pub C_GetInterface: unsafe extern "C" fn(CK_UTF8CHAR_PTR, *mut CK_VERSION, *mut *const CK_INTERFACE, CK_FLAGS) -> CK_RV,
+    pub C_LoginUser: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_USER_TYPE, CK_UTF8CHAR_PTR, CK_ULONG, CK_UTF8CHAR_PTR, CK_ULONG) -> CK_RV,
+    pub C_SessionCancel: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_FLAGS) -> CK_RV,
+    pub C_MessageEncryptInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_EncryptMessage: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_VOID_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG_PTR,
+    ) -> CK_RV,
+    pub C_EncryptMessageBegin: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_VOID_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_EncryptMessageNext: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_VOID_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG_PTR,
+        CK_FLAGS,
+    ) -> CK_RV,
+    pub C_MessageEncryptFinal: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    pub C_MessageDecryptInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_DecryptMessage: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_VOID_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG_PTR,
+    ) -> CK_RV,
+    pub C_DecryptMessageBegin: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_VOID_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_DecryptMessageNext: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_VOID_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG_PTR,
+        CK_FLAGS,
+    ) -> CK_RV,
+    pub C_MessageDecryptFinal: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    pub C_MessageSignInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_SignMessage: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_VOID_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_SignMessageBegin: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_VOID_PTR, CK_ULONG) -> CK_RV,
+    pub C_SignMessageNext: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_VOID_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_MessageSignFinal: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    pub C_MessageVerifyInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE) -> CK_RV,
+    pub C_VerifyMessage: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_VOID_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_VerifyMessageBegin: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_VOID_PTR, CK_ULONG) -> CK_RV,
+    pub C_VerifyMessageNext: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_VOID_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_MessageVerifyFinal: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    // PKCS#11 3.2
+    pub C_EncapsulateKey: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_MECHANISM_PTR,
+        CK_OBJECT_HANDLE,
+        CK_ATTRIBUTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG_PTR,
+        CK_OBJECT_HANDLE_PTR,
+    ) -> CK_RV,
+    pub C_DecapsulateKey: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_MECHANISM_PTR,
+        CK_OBJECT_HANDLE,
+        CK_ATTRIBUTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_OBJECT_HANDLE_PTR,
+    ) -> CK_RV,
+    pub C_VerifySignatureInit: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_MECHANISM_PTR, CK_OBJECT_HANDLE, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_VerifySignature: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_VerifySignatureUpdate: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_VerifySignatureFinal: unsafe extern "C" fn(CK_SESSION_HANDLE) -> CK_RV,
+    pub C_GetSessionValidationFlags: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_ULONG, CK_FLAGS_PTR) -> CK_RV,
+    pub C_AsyncComplete: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_UTF8CHAR_PTR, CK_VOID_PTR) -> CK_RV,
+    pub C_AsyncGetID: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_UTF8CHAR_PTR, CK_ULONG_PTR) -> CK_RV,
+    pub C_AsyncJoin: unsafe extern "C" fn(CK_SESSION_HANDLE, CK_UTF8CHAR_PTR, CK_ULONG, CK_BYTE_PTR, CK_ULONG) -> CK_RV,
+    pub C_WrapKeyAuthenticated: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_MECHANISM_PTR,
+        CK_OBJECT_HANDLE,
+        CK_OBJECT_HANDLE,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG_PTR,
+    ) -> CK_RV,
+    pub C_UnwrapKeyAuthenticated: unsafe extern "C" fn(
+        CK_SESSION_HANDLE,
+        CK_MECHANISM_PTR,
+        CK_OBJECT_HANDLE,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_ATTRIBUTE_PTR,
+        CK_ULONG,
+        CK_BYTE_PTR,
+        CK_ULONG,
+        CK_OBJECT_HANDLE_PTR,
+    ) -> CK_RV,
+}
+
+
+/// Space-padded, unterminated fixed-width string as used in the info structures
+pub(crate) fn padded<const N: usize>(text: &str) -> [u8; N] {
+    let mut field = [b' '; N];
+    let len = text.len().min(N);
+    field[..len].copy_from_slice(&text.as_bytes()[..len]);
+    field
+}
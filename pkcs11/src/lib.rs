@@ -0,0 +1,701 @@
+//! PKCS#11 (Cryptoki) provider exposing the KMS's Kyber-1024 keys.
+//!
+//! Built as a `cdylib`, the module can be loaded by any Cryptoki consumer.
+//! It offers one slot whose token is the [`KeyManagementSystem`](pqc_kyber::crypto::kms::KeyManagementSystem):
+//! C_GenerateKeyPair with CKM_ML_KEM_KEY_PAIR_GEN creates a key pair in the
+//! KMS, C_FindObjects lists the KMS's key pairs, and the PKCS#11 3.2
+//! C_EncapsulateKey / C_DecapsulateKey run CKM_ML_KEM through the KMS, so
+//! its access policy and audit log apply. Single-part C_Encrypt / C_Decrypt
+//! run CKM_AES_GCM with the resulting session keys. C_Login with the user
+//! PIN unlocks a token backed by a file store. The function list is served under
+//! the 2.40, 3.0 and 3.2 interfaces; entries outside that subset return
+//! CKR_FUNCTION_NOT_SUPPORTED. See [`token`] for how C_Initialize reads its
+//! configuration from the environment.
+
+// Cryptoki names, and the contracts of the exported functions are those of
+// the PKCS#11 specification
+#![allow(non_snake_case, non_camel_case_types, clippy::missing_safety_doc)]
+
+pub mod ffi;
+pub mod token;
+mod unsupported;
+
+use ffi::*;
+use rand::RngCore;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use token::{check_slot, ciphertext_len, GcmParameters, TemplateAttribute, Token, TokenConfig, MAX_PIN_LEN, MIN_PIN_LEN, SLOT_ID};
+use unsupported::*;
+
+static TOKEN: Mutex<Option<Token>> = Mutex::new(None);
+
+const INTERFACE_NAME: &[u8] = b"PKCS 11\0";
+const MECHANISMS: [CK_MECHANISM_TYPE; 3] = [CKM_ML_KEM_KEY_PAIR_GEN, CKM_ML_KEM, CKM_AES_GCM];
+
+static FUNCTION_LIST: CK_FUNCTION_LIST_3_2 = function_list(CK_VERSION { major: 2, minor: 40 });
+static FUNCTION_LIST_3_0: CK_FUNCTION_LIST_3_2 = function_list(CK_VERSION { major: 3, minor: 0 });
+static FUNCTION_LIST_3_2: CK_FUNCTION_LIST_3_2 = function_list(CK_VERSION { major: 3, minor: 2 });
+
+struct Interfaces([CK_INTERFACE; 3]);
+
+// The interfaces only point at immutable statics
+unsafe impl Sync for Interfaces {}
+
+// Newest first: C_GetInterface without a version returns the first entry
+static INTERFACES: Interfaces = Interfaces([
+    interface(&FUNCTION_LIST_3_2),
+    interface(&FUNCTION_LIST_3_0),
+    interface(&FUNCTION_LIST),
+]);
+
+const fn interface(list: &'static CK_FUNCTION_LIST_3_2) -> CK_INTERFACE {
+    CK_INTERFACE {
+        pInterfaceName: INTERFACE_NAME.as_ptr(),
+        pFunctionList: list as *const CK_FUNCTION_LIST_3_2 as *const c_void,
+        flags: 0,
+    }
+}
+
+const fn function_list(version: CK_VERSION) -> CK_FUNCTION_LIST_3_2 {
+    CK_FUNCTION_LIST_3_2 {
+        version,
+        C_Initialize,
+        C_Finalize,
+        C_GetInfo,
+        C_GetFunctionList,
+        C_GetSlotList,
+        C_GetSlotInfo,
+        C_GetTokenInfo,
+        C_GetMechanismList,
+        C_GetMechanismInfo,
+        C_InitToken,
+        C_InitPIN,
+        C_SetPIN,
+        C_OpenSession,
+        C_CloseSession,
+        C_CloseAllSessions,
+        C_GetSessionInfo,
+        C_GetOperationState,
+        C_SetOperationState,
+        C_Login,
+        C_Logout,
+        C_CreateObject,
+        C_CopyObject,
+        C_DestroyObject,
+        C_GetObjectSize,
+        C_GetAttributeValue,
+        C_SetAttributeValue,
+        C_FindObjectsInit,
+        C_FindObjects,
+        C_FindObjectsFinal,
+        C_EncryptInit,
+        C_Encrypt,
+        C_EncryptUpdate,
+        C_EncryptFinal,
+        C_DecryptInit,
+        C_Decrypt,
+        C_DecryptUpdate,
+        C_DecryptFinal,
+        C_DigestInit,
+        C_Digest,
+        C_DigestUpdate,
+        C_DigestKey,
+        C_DigestFinal,
+        C_SignInit,
+        C_Sign,
+        C_SignUpdate,
+        C_SignFinal,
+        C_SignRecoverInit,
+        C_SignRecover,
+        C_VerifyInit,
+        C_Verify,
+        C_VerifyUpdate,
+        C_VerifyFinal,
+        C_VerifyRecoverInit,
+        C_VerifyRecover,
+        C_DigestEncryptUpdate,
+        C_DecryptDigestUpdate,
+        C_SignEncryptUpdate,
+        C_DecryptVerifyUpdate,
+        C_GenerateKey,
+        C_GenerateKeyPair,
+        C_WrapKey,
+        C_UnwrapKey,
+        C_DeriveKey,
+        C_SeedRandom,
+        C_GenerateRandom,
+        C_GetFunctionStatus,
+        C_CancelFunction,
+        C_WaitForSlotEvent,
+        C_GetInterfaceList,
+        C_GetInterface,
+        C_LoginUser,
+        C_SessionCancel,
+        C_MessageEncryptInit,
+        C_EncryptMessage,
+        C_EncryptMessageBegin,
+        C_EncryptMessageNext,
+        C_MessageEncryptFinal,
+        C_MessageDecryptInit,
+        C_DecryptMessage,
+        C_DecryptMessageBegin,
+        C_DecryptMessageNext,
+        C_MessageDecryptFinal,
+        C_MessageSignInit,
+        C_SignMessage,
+        C_SignMessageBegin,
+        C_SignMessageNext,
+        C_MessageSignFinal,
+        C_MessageVerifyInit,
+        C_VerifyMessage,
+        C_VerifyMessageBegin,
+        C_VerifyMessageNext,
+        C_MessageVerifyFinal,
+        C_EncapsulateKey,
+        C_DecapsulateKey,
+        C_VerifySignatureInit,
+        C_VerifySignature,
+        C_VerifySignatureUpdate,
+        C_VerifySignatureFinal,
+        C_GetSessionValidationFlags,
+        C_AsyncComplete,
+        C_AsyncGetID,
+        C_AsyncJoin,
+        C_WrapKeyAuthenticated,
+        C_UnwrapKeyAuthenticated,
+    }
+}
+
+// Runs an entry point, turning errors and panics into return values
+fn entry(body: impl FnOnce() -> Result<(), CK_RV>) -> CK_RV {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => CKR_OK,
+        Ok(Err(rv)) => rv,
+        Err(_) => CKR_GENERAL_ERROR,
+    }
+}
+
+fn lock() -> MutexGuard<'static, Option<Token>> {
+    TOKEN.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn with_token<R>(f: impl FnOnce(&mut Token) -> Result<R, CK_RV>) -> Result<R, CK_RV> {
+    f(lock().as_mut().ok_or(CKR_CRYPTOKI_NOT_INITIALIZED)?)
+}
+
+unsafe fn out<T>(ptr: *mut T, value: T) -> Result<(), CK_RV> {
+    if ptr.is_null() {
+        return Err(CKR_ARGUMENTS_BAD);
+    }
+    ptr.write(value);
+    Ok(())
+}
+
+unsafe fn slice<'a, T>(ptr: *const T, len: CK_ULONG) -> Result<&'a [T], CK_RV> {
+    match len {
+        0 => Ok(&[]),
+        _ if ptr.is_null() => Err(CKR_ARGUMENTS_BAD),
+        len => Ok(std::slice::from_raw_parts(ptr, len as usize)),
+    }
+}
+
+unsafe fn slice_mut<'a, T>(ptr: *mut T, len: CK_ULONG) -> Result<&'a mut [T], CK_RV> {
+    match len {
+        0 => Ok(&mut []),
+        _ if ptr.is_null() => Err(CKR_ARGUMENTS_BAD),
+        len => Ok(std::slice::from_raw_parts_mut(ptr, len as usize)),
+    }
+}
+
+unsafe fn template<'a>(attributes: CK_ATTRIBUTE_PTR, count: CK_ULONG) -> Result<Vec<TemplateAttribute<'a>>, CK_RV> {
+    slice(attributes, count)?
+        .iter()
+        .map(|attribute| Ok((attribute.type_, slice(attribute.pValue as *const u8, attribute.ulValueLen)?)))
+        .collect()
+}
+
+// The ML-KEM mechanisms take no parameters
+unsafe fn mechanism_type(mechanism: CK_MECHANISM_PTR) -> Result<CK_MECHANISM_TYPE, CK_RV> {
+    let mechanism = mechanism.as_ref().ok_or(CKR_ARGUMENTS_BAD)?;
+    if !mechanism.pParameter.is_null() || mechanism.ulParameterLen != 0 {
+        return Err(CKR_MECHANISM_PARAM_INVALID);
+    }
+    Ok(mechanism.mechanism)
+}
+
+// CKM_AES_GCM is the one mechanism with parameters
+unsafe fn gcm_mechanism(mechanism: CK_MECHANISM_PTR) -> Result<(CK_MECHANISM_TYPE, GcmParameters), CK_RV> {
+    let mechanism = mechanism.as_ref().ok_or(CKR_ARGUMENTS_BAD)?;
+    if mechanism.mechanism != CKM_AES_GCM {
+        return Err(CKR_MECHANISM_INVALID);
+    }
+    if mechanism.ulParameterLen as usize != std::mem::size_of::<CK_GCM_PARAMS>() {
+        return Err(CKR_MECHANISM_PARAM_INVALID);
+    }
+    let params = (mechanism.pParameter as *const CK_GCM_PARAMS).as_ref().ok_or(CKR_MECHANISM_PARAM_INVALID)?;
+    let parameters = GcmParameters {
+        iv: slice(params.pIv, params.ulIvLen)?.to_vec(),
+        aad: slice(params.pAAD, params.ulAADLen)?.to_vec(),
+        tag_bits: params.ulTagBits,
+    };
+    Ok((mechanism.mechanism, parameters))
+}
+
+// Single-part C_Encrypt / C_Decrypt. A length query or a too-small buffer
+// leaves the operation active; anything else ends it.
+unsafe fn cipher(
+    session: CK_SESSION_HANDLE,
+    encrypt: bool,
+    input: CK_BYTE_PTR,
+    input_len: CK_ULONG,
+    output: CK_BYTE_PTR,
+    output_len: CK_ULONG_PTR,
+) -> Result<(), CK_RV> {
+    let capacity = output_len.as_ref().copied().ok_or(CKR_ARGUMENTS_BAD)? as usize;
+    let input = slice(input, input_len)?;
+    let needed = with_token(|token| token.cipher_output_len(session, encrypt, input.len()))?;
+    *output_len = needed as CK_ULONG;
+    if output.is_null() {
+        return Ok(());
+    }
+    if capacity < needed {
+        return Err(CKR_BUFFER_TOO_SMALL);
+    }
+    let result = with_token(|token| token.cipher(session, encrypt, input))?;
+    ptr::copy_nonoverlapping(result.as_ptr(), output, result.len());
+    *output_len = result.len() as CK_ULONG;
+    Ok(())
+}
+
+// Two-call convention: with a null buffer only the count is reported
+unsafe fn write_list<T: Copy>(items: &[T], buffer: *mut T, count: CK_ULONG_PTR) -> Result<(), CK_RV> {
+    let capacity = count.as_ref().copied().ok_or(CKR_ARGUMENTS_BAD)? as usize;
+    *count = items.len() as CK_ULONG;
+    if buffer.is_null() {
+        return Ok(());
+    }
+    if capacity < items.len() {
+        return Err(CKR_BUFFER_TOO_SMALL);
+    }
+    ptr::copy_nonoverlapping(items.as_ptr(), buffer, items.len());
+    Ok(())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_Initialize(init_args: CK_VOID_PTR) -> CK_RV {
+    entry(|| {
+        if let Some(args) = (init_args as *const CK_C_INITIALIZE_ARGS).as_ref() {
+            if !args.pReserved.is_null() {
+                return Err(CKR_ARGUMENTS_BAD);
+            }
+            let callbacks = [args.CreateMutex, args.DestroyMutex, args.LockMutex, args.UnlockMutex];
+            let given = callbacks.iter().filter(|callback| !callback.is_null()).count();
+            if given != 0 && given != callbacks.len() {
+                return Err(CKR_ARGUMENTS_BAD);
+            }
+            // Application locking callbacks are not used; the token has its own lock
+            if given != 0 && args.flags & CKF_OS_LOCKING_OK == 0 {
+                return Err(CKR_CANT_LOCK);
+            }
+        }
+        let mut token = lock();
+        if token.is_some() {
+            return Err(CKR_CRYPTOKI_ALREADY_INITIALIZED);
+        }
+        *token = Some(Token::open(TokenConfig::from_env()?)?);
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_Finalize(reserved: CK_VOID_PTR) -> CK_RV {
+    entry(|| {
+        if !reserved.is_null() {
+            return Err(CKR_ARGUMENTS_BAD);
+        }
+        lock().take().map(drop).ok_or(CKR_CRYPTOKI_NOT_INITIALIZED)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetInfo(info: *mut CK_INFO) -> CK_RV {
+    entry(|| {
+        with_token(|_| Ok(()))?;
+        out(info, CK_INFO {
+            cryptokiVersion: CK_VERSION { major: 3, minor: 2 },
+            manufacturerID: padded("pqc_kyber"),
+            flags: 0,
+            libraryDescription: padded("Kyber-1024 KMS provider"),
+            libraryVersion: CK_VERSION {
+                major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+                minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+            },
+        })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetFunctionList(list: *mut *const CK_FUNCTION_LIST_3_2) -> CK_RV {
+    entry(|| out(list, &FUNCTION_LIST as *const CK_FUNCTION_LIST_3_2))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetInterfaceList(interfaces: *mut CK_INTERFACE, count: CK_ULONG_PTR) -> CK_RV {
+    entry(|| write_list(&INTERFACES.0, interfaces, count))
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetInterface(
+    name: CK_UTF8CHAR_PTR,
+    version: *mut CK_VERSION,
+    interface: *mut *const CK_INTERFACE,
+    flags: CK_FLAGS,
+) -> CK_RV {
+    entry(|| {
+        if !name.is_null() && std::ffi::CStr::from_ptr(name.cast()).to_bytes_with_nul() != INTERFACE_NAME {
+            return Err(CKR_ARGUMENTS_BAD);
+        }
+        let version = version.as_ref().copied();
+        let found = INTERFACES.0.iter().find(|candidate| {
+            let list = &*(candidate.pFunctionList as *const CK_FUNCTION_LIST_3_2);
+            version.is_none_or(|version| version == list.version) && candidate.flags & flags == flags
+        });
+        out(interface, found.ok_or(CKR_ARGUMENTS_BAD)? as *const CK_INTERFACE)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetSlotList(_token_present: CK_BBOOL, slots: CK_SLOT_ID_PTR, count: CK_ULONG_PTR) -> CK_RV {
+    entry(|| {
+        with_token(|_| Ok(()))?;
+        write_list(&[SLOT_ID], slots, count)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetSlotInfo(slot: CK_SLOT_ID, info: *mut CK_SLOT_INFO) -> CK_RV {
+    entry(|| {
+        with_token(|_| check_slot(slot))?;
+        out(info, CK_SLOT_INFO {
+            slotDescription: padded("pqc_kyber KMS"),
+            manufacturerID: padded("pqc_kyber"),
+            flags: CKF_TOKEN_PRESENT,
+            hardwareVersion: CK_VERSION { major: 0, minor: 0 },
+            firmwareVersion: CK_VERSION { major: 0, minor: 0 },
+        })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetTokenInfo(slot: CK_SLOT_ID, info: *mut CK_TOKEN_INFO) -> CK_RV {
+    entry(|| {
+        let (sessions, rw_sessions, login_required) = with_token(|token| {
+            check_slot(slot)?;
+            let (sessions, rw_sessions) = token.session_counts();
+            Ok((sessions, rw_sessions, token.login_required()))
+        })?;
+        let (flags, max_pin, min_pin) = match login_required {
+            true => (CKF_LOGIN_REQUIRED | CKF_USER_PIN_INITIALIZED, MAX_PIN_LEN, MIN_PIN_LEN),
+            false => (0, 0, 0),
+        };
+        out(info, CK_TOKEN_INFO {
+            label: padded("pqc_kyber KMS"),
+            manufacturerID: padded("pqc_kyber"),
+            model: padded("Kyber-1024 KMS"),
+            serialNumber: padded("0"),
+            flags: CKF_RNG | CKF_TOKEN_INITIALIZED | flags,
+            ulMaxSessionCount: CK_EFFECTIVELY_INFINITE,
+            ulSessionCount: sessions as CK_ULONG,
+            ulMaxRwSessionCount: CK_EFFECTIVELY_INFINITE,
+            ulRwSessionCount: rw_sessions as CK_ULONG,
+            ulMaxPinLen: max_pin as CK_ULONG,
+            ulMinPinLen: min_pin as CK_ULONG,
+            ulTotalPublicMemory: CK_UNAVAILABLE_INFORMATION,
+            ulFreePublicMemory: CK_UNAVAILABLE_INFORMATION,
+            ulTotalPrivateMemory: CK_UNAVAILABLE_INFORMATION,
+            ulFreePrivateMemory: CK_UNAVAILABLE_INFORMATION,
+            hardwareVersion: CK_VERSION { major: 0, minor: 0 },
+            firmwareVersion: CK_VERSION { major: 0, minor: 0 },
+            utcTime: padded(""),
+        })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetMechanismList(slot: CK_SLOT_ID, mechanisms: CK_MECHANISM_TYPE_PTR, count: CK_ULONG_PTR) -> CK_RV {
+    entry(|| {
+        with_token(|_| check_slot(slot))?;
+        write_list(&MECHANISMS, mechanisms, count)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetMechanismInfo(slot: CK_SLOT_ID, mechanism: CK_MECHANISM_TYPE, info: *mut CK_MECHANISM_INFO) -> CK_RV {
+    entry(|| {
+        with_token(|_| check_slot(slot))?;
+        // ML-KEM key sizes are given as parameter sets, AES ones in bytes
+        let (size, flags) = match mechanism {
+            CKM_ML_KEM_KEY_PAIR_GEN => (CKP_ML_KEM_1024, CKF_GENERATE_KEY_PAIR),
+            CKM_ML_KEM => (CKP_ML_KEM_1024, CKF_ENCAPSULATE | CKF_DECAPSULATE),
+            CKM_AES_GCM => (32, CKF_ENCRYPT | CKF_DECRYPT),
+            _ => return Err(CKR_MECHANISM_INVALID),
+        };
+        out(info, CK_MECHANISM_INFO { ulMinKeySize: size, ulMaxKeySize: size, flags })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_OpenSession(
+    slot: CK_SLOT_ID,
+    flags: CK_FLAGS,
+    _application: CK_VOID_PTR,
+    _notify: CK_NOTIFY,
+    session: CK_SESSION_HANDLE_PTR,
+) -> CK_RV {
+    entry(|| {
+        if session.is_null() {
+            return Err(CKR_ARGUMENTS_BAD);
+        }
+        out(session, with_token(|token| token.open_session(slot, flags))?)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_CloseSession(session: CK_SESSION_HANDLE) -> CK_RV {
+    entry(|| with_token(|token| token.close_session(session)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_CloseAllSessions(slot: CK_SLOT_ID) -> CK_RV {
+    entry(|| with_token(|token| token.close_all_sessions(slot)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetSessionInfo(session: CK_SESSION_HANDLE, info: *mut CK_SESSION_INFO) -> CK_RV {
+    entry(|| {
+        let (state, flags) = with_token(|token| token.session_info(session))?;
+        out(info, CK_SESSION_INFO { slotID: SLOT_ID, state, flags, ulDeviceError: 0 })
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_Login(session: CK_SESSION_HANDLE, user_type: CK_USER_TYPE, pin: CK_UTF8CHAR_PTR, pin_len: CK_ULONG) -> CK_RV {
+    entry(|| {
+        // No protected authentication path: the PIN must be passed in
+        if pin.is_null() {
+            return Err(CKR_ARGUMENTS_BAD);
+        }
+        let pin = slice(pin, pin_len)?;
+        with_token(|token| token.login(session, user_type, pin))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_Logout(session: CK_SESSION_HANDLE) -> CK_RV {
+    entry(|| with_token(|token| token.logout(session)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_DestroyObject(session: CK_SESSION_HANDLE, object: CK_OBJECT_HANDLE) -> CK_RV {
+    entry(|| with_token(|token| token.destroy_object(session, object)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GetAttributeValue(
+    session: CK_SESSION_HANDLE,
+    object: CK_OBJECT_HANDLE,
+    template: CK_ATTRIBUTE_PTR,
+    count: CK_ULONG,
+) -> CK_RV {
+    entry(|| with_token(|token| {
+        let object = token.object(session, object)?;
+        // Every attribute is processed; the last failure is reported
+        let mut rv = CKR_OK;
+        for attribute in slice_mut(template, count)? {
+            match object.attribute(attribute.type_) {
+                Ok(value) if attribute.pValue.is_null() => attribute.ulValueLen = value.len() as CK_ULONG,
+                Ok(value) if (attribute.ulValueLen as usize) < value.len() => {
+                    attribute.ulValueLen = CK_UNAVAILABLE_INFORMATION;
+                    rv = CKR_BUFFER_TOO_SMALL;
+                }
+                Ok(value) => {
+                    ptr::copy_nonoverlapping(value.as_ptr(), attribute.pValue as *mut u8, value.len());
+                    attribute.ulValueLen = value.len() as CK_ULONG;
+                }
+                Err(e) => {
+                    attribute.ulValueLen = CK_UNAVAILABLE_INFORMATION;
+                    rv = e;
+                }
+            }
+        }
+        if rv == CKR_OK { Ok(()) } else { Err(rv) }
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_FindObjectsInit(session: CK_SESSION_HANDLE, template: CK_ATTRIBUTE_PTR, count: CK_ULONG) -> CK_RV {
+    entry(|| {
+        let template = self::template(template, count)?;
+        with_token(|token| token.find_objects_init(session, &template))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_FindObjects(
+    session: CK_SESSION_HANDLE,
+    objects: CK_OBJECT_HANDLE_PTR,
+    max_count: CK_ULONG,
+    count: CK_ULONG_PTR,
+) -> CK_RV {
+    entry(|| {
+        if count.is_null() || (objects.is_null() && max_count > 0) {
+            return Err(CKR_ARGUMENTS_BAD);
+        }
+        let found = with_token(|token| token.find_objects(session, max_count as usize))?;
+        if !found.is_empty() {
+            ptr::copy_nonoverlapping(found.as_ptr(), objects, found.len());
+        }
+        out(count, found.len() as CK_ULONG)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_FindObjectsFinal(session: CK_SESSION_HANDLE) -> CK_RV {
+    entry(|| with_token(|token| token.find_objects_final(session)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_EncryptInit(session: CK_SESSION_HANDLE, mechanism: CK_MECHANISM_PTR, key: CK_OBJECT_HANDLE) -> CK_RV {
+    entry(|| {
+        let (mechanism, parameters) = gcm_mechanism(mechanism)?;
+        with_token(|token| token.cipher_init(session, true, mechanism, parameters, key))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_Encrypt(
+    session: CK_SESSION_HANDLE,
+    data: CK_BYTE_PTR,
+    data_len: CK_ULONG,
+    encrypted: CK_BYTE_PTR,
+    encrypted_len: CK_ULONG_PTR,
+) -> CK_RV {
+    entry(|| cipher(session, true, data, data_len, encrypted, encrypted_len))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_DecryptInit(session: CK_SESSION_HANDLE, mechanism: CK_MECHANISM_PTR, key: CK_OBJECT_HANDLE) -> CK_RV {
+    entry(|| {
+        let (mechanism, parameters) = gcm_mechanism(mechanism)?;
+        with_token(|token| token.cipher_init(session, false, mechanism, parameters, key))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_Decrypt(
+    session: CK_SESSION_HANDLE,
+    encrypted: CK_BYTE_PTR,
+    encrypted_len: CK_ULONG,
+    data: CK_BYTE_PTR,
+    data_len: CK_ULONG_PTR,
+) -> CK_RV {
+    entry(|| cipher(session, false, encrypted, encrypted_len, data, data_len))
+}
+
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn C_GenerateKeyPair(
+    session: CK_SESSION_HANDLE,
+    mechanism: CK_MECHANISM_PTR,
+    public_template: CK_ATTRIBUTE_PTR,
+    public_count: CK_ULONG,
+    private_template: CK_ATTRIBUTE_PTR,
+    private_count: CK_ULONG,
+    public_key: CK_OBJECT_HANDLE_PTR,
+    private_key: CK_OBJECT_HANDLE_PTR,
+) -> CK_RV {
+    entry(|| {
+        if public_key.is_null() || private_key.is_null() {
+            return Err(CKR_ARGUMENTS_BAD);
+        }
+        let mechanism = mechanism_type(mechanism)?;
+        let public_template = template(public_template, public_count)?;
+        let private_template = template(private_template, private_count)?;
+        let (public, private) =
+            with_token(|token| token.generate_key_pair(session, mechanism, &public_template, &private_template))?;
+        out(public_key, public)?;
+        out(private_key, private)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn C_GenerateRandom(session: CK_SESSION_HANDLE, data: CK_BYTE_PTR, len: CK_ULONG) -> CK_RV {
+    entry(|| {
+        with_token(|token| token.session_info(session).map(drop))?;
+        rand::rngs::OsRng.fill_bytes(slice_mut(data, len)?);
+        Ok(())
+    })
+}
+
+/// PKCS#11 3.2 C_EncapsulateKey with CKM_ML_KEM. With a null `ciphertext`
+/// only the required length is reported and no key is created.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn C_EncapsulateKey(
+    session: CK_SESSION_HANDLE,
+    mechanism: CK_MECHANISM_PTR,
+    public_key: CK_OBJECT_HANDLE,
+    template: CK_ATTRIBUTE_PTR,
+    count: CK_ULONG,
+    ciphertext: CK_BYTE_PTR,
+    ciphertext_len_ptr: CK_ULONG_PTR,
+    key: CK_OBJECT_HANDLE_PTR,
+) -> CK_RV {
+    entry(|| {
+        let mechanism = mechanism_type(mechanism)?;
+        let capacity = ciphertext_len_ptr.as_ref().copied().ok_or(CKR_ARGUMENTS_BAD)? as usize;
+        *ciphertext_len_ptr = ciphertext_len() as CK_ULONG;
+        if ciphertext.is_null() {
+            return Ok(());
+        }
+        if capacity < ciphertext_len() {
+            return Err(CKR_BUFFER_TOO_SMALL);
+        }
+        if key.is_null() {
+            return Err(CKR_ARGUMENTS_BAD);
+        }
+        let template = self::template(template, count)?;
+        let (encapsulated, handle) = with_token(|token| token.encapsulate(session, mechanism, public_key, &template))?;
+        ptr::copy_nonoverlapping(encapsulated.as_ptr(), ciphertext, encapsulated.len());
+        *ciphertext_len_ptr = encapsulated.len() as CK_ULONG;
+        out(key, handle)
+    })
+}
+
+/// PKCS#11 3.2 C_DecapsulateKey with CKM_ML_KEM
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn C_DecapsulateKey(
+    session: CK_SESSION_HANDLE,
+    mechanism: CK_MECHANISM_PTR,
+    private_key: CK_OBJECT_HANDLE,
+    template: CK_ATTRIBUTE_PTR,
+    count: CK_ULONG,
+    ciphertext: CK_BYTE_PTR,
+    ciphertext_len: CK_ULONG,
+    key: CK_OBJECT_HANDLE_PTR,
+) -> CK_RV {
+    entry(|| {
+        if key.is_null() {
+            return Err(CKR_ARGUMENTS_BAD);
+        }
+        let mechanism = mechanism_type(mechanism)?;
+        let template = self::template(template, count)?;
+        let ciphertext = slice(ciphertext, ciphertext_len)?;
+        let handle = with_token(|token| token.decapsulate(session, mechanism, private_key, &template, ciphertext))?;
+        out(key, handle)
+    })
+}
@@ -0,0 +1,749 @@
+//! The provider's single token: a [`KeyManagementSystem`] plus the sessions
+//! and object handles Cryptoki callers see.
+//!
+//! Every Kyber-1024 key pair in the KMS appears as a CKO_PRIVATE_KEY and a
+//! CKO_PUBLIC_KEY token object, both labelled with the key id. Shared secrets
+//! from C_EncapsulateKey / C_DecapsulateKey are CKO_SECRET_KEY session
+//! objects, destroyed when their session closes. An AES one created with
+//! CKA_ENCRYPT / CKA_DECRYPT can run single-part CKM_AES_GCM, so a caller
+//! can use the shared secret without ever reading it.
+//!
+//! With a file store the token is locked until C_Login: the user PIN is the
+//! passphrase of a [`SealedFileProvider`] KEK directory, which unwraps the
+//! store's master key, so the key never sits in the environment. The first
+//! login against an empty KEK directory sets the PIN. C_Logout drops the
+//! unsealed KMS and every object handle. Without a store the token has no
+//! PIN. Either way, what the module may do is the KMS access policy of its
+//! principal.
+
+use crate::ffi::*;
+use pqc_kyber::crypto::kms::{AccessPolicy, CallerContext, KeyManagementSystem, KeyState, KmsAction, Role, KYBER1024_ALGORITHM};
+use pqc_kyber::crypto::secure::SecureSecret;
+use pqc_kyber::error::{Error, ErrorCategory};
+use pqc_kyber::error::KmsError;
+use pqc_kyber::kms::SealedFileProvider;
+use pqcrypto_kyber::kyber1024;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use std::path::PathBuf;
+use zeroize::Zeroizing;
+
+/// The only slot; its token is always present
+pub const SLOT_ID: CK_SLOT_ID = 0;
+
+/// Directory of a [`FileKeyStore`]; without it keys live in memory until C_Finalize
+pub const STORE_ENV: &str = "PQC_KYBER_PKCS11_STORE";
+/// KEK directory sealing the store's master key under the user PIN; required with a store
+pub const KEK_DIR_ENV: &str = "PQC_KYBER_PKCS11_KEK_DIR";
+/// Principal the module acts as in the KMS, `pkcs11` by default
+pub const PRINCIPAL_ENV: &str = "PQC_KYBER_PKCS11_PRINCIPAL";
+/// JSON [`AccessPolicy`] replacing the default one
+pub const POLICY_ENV: &str = "PQC_KYBER_PKCS11_POLICY";
+
+pub const DEFAULT_PRINCIPAL: &str = "pkcs11";
+pub const MIN_PIN_LEN: usize = 8;
+pub const MAX_PIN_LEN: usize = 256;
+
+const SHARED_SECRET_LEN: usize = 32;
+// CKM_AES_GCM is offered with 96-bit IVs and full 128-bit tags only
+const GCM_IV_LEN: usize = 12;
+const GCM_TAG_LEN: usize = 16;
+
+// Attribute and template values are copied out of and into caller buffers
+pub(crate) type AttributeValue = Zeroizing<Vec<u8>>;
+pub(crate) type TemplateAttribute<'a> = (CK_ATTRIBUTE_TYPE, &'a [u8]);
+
+/// How C_Initialize sets up the token, read from the `PQC_KYBER_PKCS11_*` variables
+pub struct TokenConfig {
+    pub store: Option<StoreConfig>,
+    pub principal: String,
+    pub policy: AccessPolicy,
+}
+
+/// File store and the KEK directory its master key is sealed in
+pub struct StoreConfig {
+    pub root: PathBuf,
+    pub kek_dir: PathBuf,
+}
+
+impl TokenConfig {
+    /// Without a policy file, the principal may create keys, read their
+    /// public halves and run KEM operations, but not export, rotate or destroy
+    pub fn from_env() -> Result<Self, CK_RV> {
+        let principal = std::env::var(PRINCIPAL_ENV).unwrap_or_else(|_| DEFAULT_PRINCIPAL.to_string());
+        let store = match std::env::var_os(STORE_ENV) {
+            Some(root) => {
+                let kek_dir = std::env::var_os(KEK_DIR_ENV)
+                    .ok_or_else(|| config_error(&format!("{} must be set with {}", KEK_DIR_ENV, STORE_ENV)))?;
+                Some(StoreConfig { root: root.into(), kek_dir: kek_dir.into() })
+            }
+            None => None,
+        };
+        let policy = match std::env::var_os(POLICY_ENV) {
+            Some(path) => {
+                let json = std::fs::read(&path).map_err(|e| config_error(&format!("cannot read {:?}: {}", path, e)))?;
+                serde_json::from_slice(&json).map_err(|e| config_error(&format!("invalid policy {:?}: {}", path, e)))?
+            }
+            None => default_policy(&principal),
+        };
+        Ok(Self { store, principal, policy })
+    }
+}
+
+fn default_policy(principal: &str) -> AccessPolicy {
+    let actions = [KmsAction::Create, KmsAction::Describe, KmsAction::Encapsulate, KmsAction::Decapsulate];
+    AccessPolicy::new()
+        .with_role(Role::new("pkcs11-token").allow("*", actions))
+        .assign(principal, "pkcs11-token")
+}
+
+fn unlocked(kms: &mut Option<KeyManagementSystem>) -> Result<&mut KeyManagementSystem, CK_RV> {
+    kms.as_mut().ok_or(CKR_USER_NOT_LOGGED_IN)
+}
+
+fn config_error(message: &str) -> CK_RV {
+    tracing::error!("PKCS#11 provider configuration: {}", message);
+    CKR_GENERAL_ERROR
+}
+
+/// Maps a KMS failure onto the closest Cryptoki return value
+fn kms_error(err: impl Into<Error>) -> CK_RV {
+    match err.into().code().category {
+        ErrorCategory::PermissionDenied | ErrorCategory::FailedPrecondition => CKR_KEY_FUNCTION_NOT_PERMITTED,
+        ErrorCategory::NotFound => CKR_KEY_HANDLE_INVALID,
+        ErrorCategory::AlreadyExists => CKR_ATTRIBUTE_VALUE_INVALID,
+        ErrorCategory::InvalidInput => CKR_ARGUMENTS_BAD,
+        ErrorCategory::Unauthenticated => CKR_USER_NOT_LOGGED_IN,
+        ErrorCategory::ResourceExhausted => CKR_DEVICE_MEMORY,
+        ErrorCategory::Unavailable => CKR_DEVICE_ERROR,
+        ErrorCategory::Internal => CKR_FUNCTION_FAILED,
+    }
+}
+
+fn ulong(value: CK_ULONG) -> AttributeValue {
+    Zeroizing::new(value.to_ne_bytes().to_vec())
+}
+
+fn flag(value: bool) -> AttributeValue {
+    Zeroizing::new(vec![if value { CK_TRUE } else { CK_FALSE }])
+}
+
+fn ulong_value(value: &[u8]) -> Result<CK_ULONG, CK_RV> {
+    let bytes = value.try_into().map_err(|_| CKR_ATTRIBUTE_VALUE_INVALID)?;
+    Ok(CK_ULONG::from_ne_bytes(bytes))
+}
+
+fn flag_value(value: &[u8]) -> Result<bool, CK_RV> {
+    match value {
+        [byte] => Ok(*byte != CK_FALSE),
+        _ => Err(CKR_ATTRIBUTE_VALUE_INVALID),
+    }
+}
+
+pub(crate) enum Object {
+    /// Private half of a KMS key pair; the material never leaves the KMS
+    Private { key_id: String },
+    Public { key_id: String, value: Vec<u8> },
+    /// Shared secret established in `session`
+    Secret {
+        session: CK_SESSION_HANDLE,
+        label: Vec<u8>,
+        key_type: CK_KEY_TYPE,
+        sensitive: bool,
+        extractable: bool,
+        encrypt: bool,
+        decrypt: bool,
+        value: SecureSecret,
+    },
+}
+
+impl Object {
+    fn key_id(&self) -> Option<&str> {
+        match self {
+            Object::Private { key_id } | Object::Public { key_id, .. } => Some(key_id),
+            Object::Secret { .. } => None,
+        }
+    }
+
+    fn class(&self) -> CK_OBJECT_CLASS {
+        match self {
+            Object::Private { .. } => CKO_PRIVATE_KEY,
+            Object::Public { .. } => CKO_PUBLIC_KEY,
+            Object::Secret { .. } => CKO_SECRET_KEY,
+        }
+    }
+
+    /// Value of attribute `kind`, or CKR_ATTRIBUTE_TYPE_INVALID / CKR_ATTRIBUTE_SENSITIVE
+    pub(crate) fn attribute(&self, kind: CK_ATTRIBUTE_TYPE) -> Result<AttributeValue, CK_RV> {
+        use Object::*;
+        let value = match (self, kind) {
+            (_, CKA_CLASS) => ulong(self.class()),
+            (Private { .. }, CKA_PRIVATE) => flag(true),
+            (_, CKA_PRIVATE | CKA_MODIFIABLE) => flag(false),
+            (Secret { .. }, CKA_TOKEN | CKA_LOCAL) => flag(false),
+            (_, CKA_TOKEN | CKA_LOCAL) => flag(true),
+            (Private { key_id } | Public { key_id, .. }, CKA_LABEL | CKA_ID) => Zeroizing::new(key_id.as_bytes().to_vec()),
+            (Private { .. } | Public { .. }, CKA_KEY_TYPE) => ulong(CKK_ML_KEM),
+            (Private { .. } | Public { .. }, CKA_PARAMETER_SET) => ulong(CKP_ML_KEM_1024),
+            (Public { .. }, CKA_ENCAPSULATE) => flag(true),
+            (Public { value, .. }, CKA_VALUE) => Zeroizing::new(value.clone()),
+            (Private { .. }, CKA_DECAPSULATE | CKA_SENSITIVE | CKA_ALWAYS_SENSITIVE | CKA_NEVER_EXTRACTABLE) => flag(true),
+            (Private { .. }, CKA_EXTRACTABLE) => flag(false),
+            (Private { .. }, CKA_VALUE) => return Err(CKR_ATTRIBUTE_SENSITIVE),
+            (Secret { label, .. }, CKA_LABEL) => Zeroizing::new(label.clone()),
+            (Secret { .. }, CKA_ID) => Zeroizing::new(Vec::new()),
+            (Secret { key_type, .. }, CKA_KEY_TYPE) => ulong(*key_type),
+            (Secret { value, .. }, CKA_VALUE_LEN) => ulong(value.len() as CK_ULONG),
+            (Secret { sensitive, .. }, CKA_SENSITIVE) => flag(*sensitive),
+            (Secret { extractable, .. }, CKA_EXTRACTABLE) => flag(*extractable),
+            (Secret { encrypt, .. }, CKA_ENCRYPT) => flag(*encrypt),
+            (Secret { decrypt, .. }, CKA_DECRYPT) => flag(*decrypt),
+            (Secret { sensitive, extractable, value, .. }, CKA_VALUE) => {
+                if *sensitive || !*extractable {
+                    return Err(CKR_ATTRIBUTE_SENSITIVE);
+                }
+                value.with_exposed(|value| Zeroizing::new(value.to_vec())).map_err(|_| CKR_DEVICE_MEMORY)?
+            }
+            _ => return Err(CKR_ATTRIBUTE_TYPE_INVALID),
+        };
+        Ok(value)
+    }
+
+    fn matches(&self, template: &[TemplateAttribute]) -> bool {
+        template.iter().all(|(kind, value)| self.attribute(*kind).is_ok_and(|actual| actual.as_slice() == *value))
+    }
+
+    // A key-pair template may only ask for what the generated object reports
+    fn check_template(&self, template: &[TemplateAttribute]) -> Result<(), CK_RV> {
+        for (kind, value) in template {
+            match self.attribute(*kind) {
+                Ok(actual) if actual.as_slice() == *value => {}
+                Ok(_) | Err(CKR_ATTRIBUTE_SENSITIVE) => return Err(CKR_TEMPLATE_INCONSISTENT),
+                Err(rv) => return Err(rv),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Attributes a KEM template may set on the resulting CKO_SECRET_KEY
+struct SecretKeyTemplate {
+    label: Vec<u8>,
+    key_type: CK_KEY_TYPE,
+    sensitive: bool,
+    extractable: bool,
+    encrypt: bool,
+    decrypt: bool,
+}
+
+impl SecretKeyTemplate {
+    fn parse(template: &[TemplateAttribute]) -> Result<Self, CK_RV> {
+        let mut parsed = Self {
+            label: Vec::new(),
+            key_type: CKK_GENERIC_SECRET,
+            sensitive: false,
+            extractable: true,
+            encrypt: false,
+            decrypt: false,
+        };
+        for (kind, value) in template {
+            match *kind {
+                CKA_LABEL => parsed.label = value.to_vec(),
+                CKA_SENSITIVE => parsed.sensitive = flag_value(value)?,
+                CKA_EXTRACTABLE => parsed.extractable = flag_value(value)?,
+                CKA_ENCRYPT => parsed.encrypt = flag_value(value)?,
+                CKA_DECRYPT => parsed.decrypt = flag_value(value)?,
+                CKA_KEY_TYPE => {
+                    parsed.key_type = match ulong_value(value)? {
+                        key_type @ (CKK_GENERIC_SECRET | CKK_AES) => key_type,
+                        _ => return Err(CKR_TEMPLATE_INCONSISTENT),
+                    }
+                }
+                CKA_CLASS if ulong_value(value)? == CKO_SECRET_KEY => {}
+                CKA_VALUE_LEN if ulong_value(value)? == SHARED_SECRET_LEN as CK_ULONG => {}
+                // Shared secrets are never token objects
+                CKA_TOKEN | CKA_PRIVATE | CKA_MODIFIABLE if !flag_value(value)? => {}
+                CKA_CLASS | CKA_VALUE_LEN | CKA_TOKEN | CKA_PRIVATE | CKA_MODIFIABLE => return Err(CKR_TEMPLATE_INCONSISTENT),
+                _ => return Err(CKR_ATTRIBUTE_TYPE_INVALID),
+            }
+        }
+        // Only AES keys have a mechanism to encrypt with
+        if (parsed.encrypt || parsed.decrypt) && parsed.key_type != CKK_AES {
+            return Err(CKR_TEMPLATE_INCONSISTENT);
+        }
+        Ok(parsed)
+    }
+
+    fn into_object(self, session: CK_SESSION_HANDLE, value: SecureSecret) -> Object {
+        Object::Secret {
+            session,
+            label: self.label,
+            key_type: self.key_type,
+            sensitive: self.sensitive,
+            extractable: self.extractable,
+            encrypt: self.encrypt,
+            decrypt: self.decrypt,
+            value,
+        }
+    }
+}
+
+/// Parameters of CKM_AES_GCM, copied out of the caller's CK_GCM_PARAMS
+pub struct GcmParameters {
+    pub iv: Vec<u8>,
+    pub aad: Vec<u8>,
+    pub tag_bits: CK_ULONG,
+}
+
+// An initialised C_Encrypt or C_Decrypt
+struct CipherOperation {
+    encrypt: bool,
+    key: CK_OBJECT_HANDLE,
+    parameters: GcmParameters,
+}
+
+struct Session {
+    flags: CK_FLAGS,
+    // Remaining results of an active C_FindObjects search
+    search: Option<VecDeque<CK_OBJECT_HANDLE>>,
+    cipher: Option<CipherOperation>,
+}
+
+pub struct Token {
+    // None while a store-backed token is logged out
+    kms: Option<KeyManagementSystem>,
+    store: Option<StoreConfig>,
+    policy: AccessPolicy,
+    caller: CallerContext,
+    sessions: HashMap<CK_SESSION_HANDLE, Session>,
+    objects: BTreeMap<CK_OBJECT_HANDLE, Object>,
+    next_handle: CK_ULONG,
+}
+
+impl Token {
+    pub fn open(config: TokenConfig) -> Result<Self, CK_RV> {
+        let kms = match config.store {
+            Some(_) => None,
+            None => {
+                let mut kms = KeyManagementSystem::new();
+                kms.set_access_policy(config.policy.clone());
+                Some(kms)
+            }
+        };
+        Ok(Self {
+            kms,
+            store: config.store,
+            policy: config.policy,
+            caller: CallerContext::new(&config.principal),
+            sessions: HashMap::new(),
+            objects: BTreeMap::new(),
+            next_handle: 1,
+        })
+    }
+
+    /// Whether C_Login is needed before keys can be used
+    pub fn login_required(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Unseals the store with `pin` as the KEK passphrase. One login covers
+    /// every session, as in PKCS#11.
+    pub fn login(&mut self, session: CK_SESSION_HANDLE, user_type: CK_USER_TYPE, pin: &[u8]) -> Result<(), CK_RV> {
+        self.session(session)?;
+        if user_type != CKU_USER {
+            return Err(CKR_USER_TYPE_INVALID);
+        }
+        let store = self.store.as_ref().ok_or(CKR_USER_PIN_NOT_INITIALIZED)?;
+        if self.kms.is_some() {
+            return Err(CKR_USER_ALREADY_LOGGED_IN);
+        }
+        if !(MIN_PIN_LEN..=MAX_PIN_LEN).contains(&pin.len()) {
+            return Err(CKR_PIN_LEN_RANGE);
+        }
+
+        let provider = SealedFileProvider::open(&store.kek_dir, SecureSecret::from_bytes(pin)).map_err(|e| {
+            tracing::error!("PKCS#11 provider cannot open KEK directory {:?}: {}", store.kek_dir, e);
+            CKR_DEVICE_ERROR
+        })?;
+        let mut kms = KeyManagementSystem::unseal_store(&store.root, &provider).map_err(|e| match e {
+            KmsError::UnsealFailed(_) => CKR_PIN_INCORRECT,
+            e => {
+                tracing::error!("PKCS#11 provider cannot open key store {:?}: {}", store.root, e);
+                CKR_DEVICE_ERROR
+            }
+        })?;
+        kms.set_access_policy(self.policy.clone());
+        self.kms = Some(kms);
+        Ok(())
+    }
+
+    /// Locks the token again; every object handle goes with the unsealed KMS
+    pub fn logout(&mut self, session: CK_SESSION_HANDLE) -> Result<(), CK_RV> {
+        self.session(session)?;
+        if self.store.is_none() || self.kms.take().is_none() {
+            return Err(CKR_USER_NOT_LOGGED_IN);
+        }
+        self.objects.clear();
+        for session in self.sessions.values_mut() {
+            session.search = None;
+            session.cipher = None;
+        }
+        Ok(())
+    }
+
+    fn allocate_handle(&mut self) -> CK_ULONG {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        handle
+    }
+
+    pub fn open_session(&mut self, slot: CK_SLOT_ID, flags: CK_FLAGS) -> Result<CK_SESSION_HANDLE, CK_RV> {
+        check_slot(slot)?;
+        if flags & CKF_SERIAL_SESSION == 0 {
+            return Err(CKR_SESSION_PARALLEL_NOT_SUPPORTED);
+        }
+        let handle = self.allocate_handle();
+        self.sessions.insert(handle, Session { flags, search: None, cipher: None });
+        Ok(handle)
+    }
+
+    /// Closes the session and destroys the session objects created in it
+    pub fn close_session(&mut self, session: CK_SESSION_HANDLE) -> Result<(), CK_RV> {
+        self.sessions.remove(&session).ok_or(CKR_SESSION_HANDLE_INVALID)?;
+        self.objects.retain(|_, object| !matches!(object, Object::Secret { session: owner, .. } if *owner == session));
+        Ok(())
+    }
+
+    pub fn close_all_sessions(&mut self, slot: CK_SLOT_ID) -> Result<(), CK_RV> {
+        check_slot(slot)?;
+        self.sessions.clear();
+        self.objects.retain(|_, object| !matches!(object, Object::Secret { .. }));
+        Ok(())
+    }
+
+    /// Session state and flags, for C_GetSessionInfo
+    pub fn session_info(&self, session: CK_SESSION_HANDLE) -> Result<(CK_STATE, CK_FLAGS), CK_RV> {
+        let flags = self.session(session)?.flags;
+        let state = match (flags & CKF_RW_SESSION != 0, self.login_required() && self.kms.is_some()) {
+            (true, true) => CKS_RW_USER_FUNCTIONS,
+            (true, false) => CKS_RW_PUBLIC_SESSION,
+            (false, true) => CKS_RO_USER_FUNCTIONS,
+            (false, false) => CKS_RO_PUBLIC_SESSION,
+        };
+        Ok((state, flags))
+    }
+
+    /// Number of open sessions, and of read/write sessions among them
+    pub fn session_counts(&self) -> (usize, usize) {
+        let rw = self.sessions.values().filter(|session| session.flags & CKF_RW_SESSION != 0).count();
+        (self.sessions.len(), rw)
+    }
+
+    fn session(&self, session: CK_SESSION_HANDLE) -> Result<&Session, CK_RV> {
+        self.sessions.get(&session).ok_or(CKR_SESSION_HANDLE_INVALID)
+    }
+
+    fn session_mut(&mut self, session: CK_SESSION_HANDLE) -> Result<&mut Session, CK_RV> {
+        self.sessions.get_mut(&session).ok_or(CKR_SESSION_HANDLE_INVALID)
+    }
+
+    pub(crate) fn object(&self, session: CK_SESSION_HANDLE, object: CK_OBJECT_HANDLE) -> Result<&Object, CK_RV> {
+        self.session(session)?;
+        self.objects.get(&object).ok_or(CKR_OBJECT_HANDLE_INVALID)
+    }
+
+    /// Only session objects can be destroyed here; destroying a token key
+    /// goes through the KMS, where it needs a second approver
+    pub fn destroy_object(&mut self, session: CK_SESSION_HANDLE, object: CK_OBJECT_HANDLE) -> Result<(), CK_RV> {
+        match self.object(session, object)? {
+            Object::Secret { .. } => {
+                self.objects.remove(&object);
+                Ok(())
+            }
+            _ => Err(CKR_ACTION_PROHIBITED),
+        }
+    }
+
+    pub fn find_objects_init(&mut self, session: CK_SESSION_HANDLE, template: &[TemplateAttribute]) -> Result<(), CK_RV> {
+        if self.session(session)?.search.is_some() {
+            return Err(CKR_OPERATION_ACTIVE);
+        }
+        self.sync_token_objects()?;
+        let found = self.objects.iter()
+            .filter(|(_, object)| object.matches(template))
+            .map(|(handle, _)| *handle)
+            .collect();
+        self.session_mut(session)?.search = Some(found);
+        Ok(())
+    }
+
+    pub fn find_objects(&mut self, session: CK_SESSION_HANDLE, max: usize) -> Result<Vec<CK_OBJECT_HANDLE>, CK_RV> {
+        let search = self.session_mut(session)?.search.as_mut().ok_or(CKR_OPERATION_NOT_INITIALIZED)?;
+        let count = max.min(search.len());
+        Ok(search.drain(..count).collect())
+    }
+
+    pub fn find_objects_final(&mut self, session: CK_SESSION_HANDLE) -> Result<(), CK_RV> {
+        self.session_mut(session)?.search.take().ok_or(CKR_OPERATION_NOT_INITIALIZED)?;
+        Ok(())
+    }
+
+    // Gives handles to Kyber-1024 keys created in the KMS by other means,
+    // e.g. by another process sharing the file store. A locked token shows none.
+    fn sync_token_objects(&mut self) -> Result<(), CK_RV> {
+        let Some(kms) = &self.kms else {
+            return Ok(());
+        };
+        let mut found = Vec::new();
+        for key_id in kms.key_ids(&self.caller).map_err(kms_error)? {
+            if self.objects.values().any(|object| object.key_id() == Some(key_id.as_str())) {
+                continue;
+            }
+            let metadata = kms.key_metadata(&self.caller, &key_id).map_err(kms_error)?;
+            if metadata.algorithm != KYBER1024_ALGORITHM || metadata.status == KeyState::Destroyed {
+                continue;
+            }
+            let public_key = kms.kem_public_key(&self.caller, &key_id).map_err(kms_error)?;
+            found.push((key_id, public_key));
+        }
+        for (key_id, public_key) in found {
+            self.add_key_pair(key_id, public_key);
+        }
+        Ok(())
+    }
+
+    fn add_key_pair(&mut self, key_id: String, public_key: Vec<u8>) -> (CK_OBJECT_HANDLE, CK_OBJECT_HANDLE) {
+        let public = self.allocate_handle();
+        self.objects.insert(public, Object::Public { key_id: key_id.clone(), value: public_key });
+        let private = self.allocate_handle();
+        self.objects.insert(private, Object::Private { key_id });
+        (public, private)
+    }
+
+    /// Generates a Kyber-1024 key pair in the KMS, named by CKA_LABEL (or
+    /// CKA_ID) of either template, and returns the public and private handles
+    pub fn generate_key_pair(
+        &mut self,
+        session: CK_SESSION_HANDLE,
+        mechanism: CK_MECHANISM_TYPE,
+        public_template: &[TemplateAttribute],
+        private_template: &[TemplateAttribute],
+    ) -> Result<(CK_OBJECT_HANDLE, CK_OBJECT_HANDLE), CK_RV> {
+        let flags = self.session(session)?.flags;
+        if mechanism != CKM_ML_KEM_KEY_PAIR_GEN {
+            return Err(CKR_MECHANISM_INVALID);
+        }
+        if flags & CKF_RW_SESSION == 0 {
+            return Err(CKR_SESSION_READ_ONLY);
+        }
+
+        let named = [CKA_LABEL, CKA_ID].into_iter()
+            .flat_map(|kind| [private_template, public_template].map(|template| (kind, template)))
+            .find_map(|(kind, template)| template.iter().find(|(k, _)| *k == kind).map(|(_, value)| *value));
+        let key_id = match named {
+            Some(name) => String::from_utf8(name.to_vec()).map_err(|_| CKR_ATTRIBUTE_VALUE_INVALID)?,
+            None => format!("pkcs11-{:016x}", rand::random::<u64>()),
+        };
+        Object::Public { key_id: key_id.clone(), value: Vec::new() }.check_template(public_template)?;
+        Object::Private { key_id: key_id.clone() }.check_template(private_template)?;
+
+        let public_key = unlocked(&mut self.kms)?.generate_kem_key(&self.caller, &key_id).map_err(kms_error)?;
+        Ok(self.add_key_pair(key_id, public_key))
+    }
+
+    /// Encapsulates to a public key; returns the ciphertext and the new shared-secret handle
+    pub fn encapsulate(
+        &mut self,
+        session: CK_SESSION_HANDLE,
+        mechanism: CK_MECHANISM_TYPE,
+        public_key: CK_OBJECT_HANDLE,
+        template: &[TemplateAttribute],
+    ) -> Result<(Vec<u8>, CK_OBJECT_HANDLE), CK_RV> {
+        let key_id = match self.kem_key(session, mechanism, public_key)? {
+            Object::Public { key_id, .. } => key_id.clone(),
+            _ => return Err(CKR_KEY_TYPE_INCONSISTENT),
+        };
+        let secret_key = SecretKeyTemplate::parse(template)?;
+        let (shared, ciphertext) = unlocked(&mut self.kms)?.encapsulate(&self.caller, &key_id).map_err(kms_error)?;
+
+        let handle = self.allocate_handle();
+        self.objects.insert(handle, secret_key.into_object(session, shared));
+        Ok((ciphertext, handle))
+    }
+
+    /// Decapsulates with a private key and returns the shared-secret handle
+    pub fn decapsulate(
+        &mut self,
+        session: CK_SESSION_HANDLE,
+        mechanism: CK_MECHANISM_TYPE,
+        private_key: CK_OBJECT_HANDLE,
+        template: &[TemplateAttribute],
+        ciphertext: &[u8],
+    ) -> Result<CK_OBJECT_HANDLE, CK_RV> {
+        let key_id = match self.kem_key(session, mechanism, private_key)? {
+            Object::Private { key_id } => key_id.clone(),
+            _ => return Err(CKR_KEY_TYPE_INCONSISTENT),
+        };
+        let secret_key = SecretKeyTemplate::parse(template)?;
+        if ciphertext.len() != ciphertext_len() {
+            return Err(CKR_ENCRYPTED_DATA_LEN_RANGE);
+        }
+        let shared = unlocked(&mut self.kms)?.decapsulate(&self.caller, &key_id, ciphertext).map_err(kms_error)?;
+
+        let handle = self.allocate_handle();
+        self.objects.insert(handle, secret_key.into_object(session, shared));
+        Ok(handle)
+    }
+
+    /// C_EncryptInit / C_DecryptInit with CKM_AES_GCM and an AES session key
+    /// that allows the operation
+    pub fn cipher_init(
+        &mut self,
+        session: CK_SESSION_HANDLE,
+        encrypt: bool,
+        mechanism: CK_MECHANISM_TYPE,
+        parameters: GcmParameters,
+        key: CK_OBJECT_HANDLE,
+    ) -> Result<(), CK_RV> {
+        if self.session(session)?.cipher.is_some() {
+            return Err(CKR_OPERATION_ACTIVE);
+        }
+        if mechanism != CKM_AES_GCM {
+            return Err(CKR_MECHANISM_INVALID);
+        }
+        if parameters.iv.len() != GCM_IV_LEN || parameters.tag_bits != (GCM_TAG_LEN * 8) as CK_ULONG {
+            return Err(CKR_MECHANISM_PARAM_INVALID);
+        }
+        match self.objects.get(&key).ok_or(CKR_KEY_HANDLE_INVALID)? {
+            Object::Secret { key_type: CKK_AES, encrypt: allowed, .. } if encrypt && *allowed => {}
+            Object::Secret { key_type: CKK_AES, decrypt: allowed, .. } if !encrypt && *allowed => {}
+            Object::Secret { key_type: CKK_AES, .. } => return Err(CKR_KEY_FUNCTION_NOT_PERMITTED),
+            _ => return Err(CKR_KEY_TYPE_INCONSISTENT),
+        }
+        self.session_mut(session)?.cipher = Some(CipherOperation { encrypt, key, parameters });
+        Ok(())
+    }
+
+    /// Output length of the active operation for `input_len` bytes of input;
+    /// an input that cannot be valid ends the operation
+    pub fn cipher_output_len(&mut self, session: CK_SESSION_HANDLE, encrypt: bool, input_len: usize) -> Result<usize, CK_RV> {
+        let state = self.session_mut(session)?;
+        match &state.cipher {
+            Some(operation) if operation.encrypt == encrypt => {}
+            _ => return Err(CKR_OPERATION_NOT_INITIALIZED),
+        }
+        if encrypt {
+            return Ok(input_len + GCM_TAG_LEN);
+        }
+        input_len.checked_sub(GCM_TAG_LEN).ok_or_else(|| {
+            state.cipher = None;
+            CKR_ENCRYPTED_DATA_LEN_RANGE
+        })
+    }
+
+    /// Runs and ends the active single-part operation. Decryption output is
+    /// released only once the tag has been verified.
+    pub fn cipher(&mut self, session: CK_SESSION_HANDLE, encrypt: bool, input: &[u8]) -> Result<AttributeValue, CK_RV> {
+        let operation = match self.session_mut(session)?.cipher.take() {
+            Some(operation) if operation.encrypt == encrypt => operation,
+            other => {
+                self.session_mut(session)?.cipher = other;
+                return Err(CKR_OPERATION_NOT_INITIALIZED);
+            }
+        };
+        let value = match self.objects.get(&operation.key) {
+            Some(Object::Secret { value, .. }) => value,
+            _ => return Err(CKR_KEY_HANDLE_INVALID),
+        };
+        let GcmParameters { iv, aad, .. } = &operation.parameters;
+        value
+            .with_exposed(|key| {
+                let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| CKR_KEY_SIZE_RANGE)?;
+                let mut buffer = Zeroizing::new(Vec::with_capacity(input.len() + GCM_TAG_LEN));
+                buffer.extend_from_slice(input);
+                if encrypt {
+                    cipher.encrypt_in_place(Nonce::from_slice(iv), aad, &mut *buffer).map_err(|_| CKR_FUNCTION_FAILED)?;
+                } else {
+                    cipher.decrypt_in_place(Nonce::from_slice(iv), aad, &mut *buffer).map_err(|_| CKR_ENCRYPTED_DATA_INVALID)?;
+                }
+                Ok(buffer)
+            })
+            .map_err(|_| CKR_DEVICE_MEMORY)?
+    }
+
+    fn kem_key(&self, session: CK_SESSION_HANDLE, mechanism: CK_MECHANISM_TYPE, key: CK_OBJECT_HANDLE) -> Result<&Object, CK_RV> {
+        self.session(session)?;
+        if mechanism != CKM_ML_KEM {
+            return Err(CKR_MECHANISM_INVALID);
+        }
+        self.objects.get(&key).ok_or(CKR_KEY_HANDLE_INVALID)
+    }
+}
+
+pub(crate) fn check_slot(slot: CK_SLOT_ID) -> Result<(), CK_RV> {
+    if slot == SLOT_ID { Ok(()) } else { Err(CKR_SLOT_ID_INVALID) }
+}
+
+/// Length of an ML-KEM-1024 ciphertext, for sizing C_EncapsulateKey output
+pub fn ciphertext_len() -> usize {
+    kyber1024::ciphertext_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token() -> (Token, CK_SESSION_HANDLE) {
+        let config = TokenConfig { store: None, principal: DEFAULT_PRINCIPAL.to_string(), policy: default_policy(DEFAULT_PRINCIPAL) };
+        let mut token = Token::open(config).unwrap();
+        let session = token.open_session(SLOT_ID, CKF_SERIAL_SESSION | CKF_RW_SESSION).unwrap();
+        (token, session)
+    }
+
+    #[test]
+    fn test_key_pair_templates() {
+        let (mut token, session) = token();
+        let token_true = [CK_TRUE];
+        let parameter_set = CKP_ML_KEM_1024.to_ne_bytes();
+        let public = [(CKA_TOKEN, &token_true[..]), (CKA_PARAMETER_SET, &parameter_set[..]), (CKA_LABEL, &b"inbound"[..])];
+        let (public_key, private_key) = token.generate_key_pair(session, CKM_ML_KEM_KEY_PAIR_GEN, &public, &[]).unwrap();
+
+        let private = token.object(session, private_key).unwrap();
+        assert_eq!(private.attribute(CKA_LABEL).unwrap().as_slice(), b"inbound");
+        assert_eq!(private.attribute(CKA_VALUE).unwrap_err(), CKR_ATTRIBUTE_SENSITIVE);
+        let public = token.object(session, public_key).unwrap();
+        assert_eq!(public.attribute(CKA_VALUE).unwrap().len(), kyber1024::public_key_bytes());
+
+        // Only ML-KEM-1024, only in read/write sessions, and labels are key ids
+        let parameter_set = 2 as CK_ULONG;
+        let ml_kem_768 = [(CKA_PARAMETER_SET, &parameter_set.to_ne_bytes()[..])];
+        assert_eq!(token.generate_key_pair(session, CKM_ML_KEM_KEY_PAIR_GEN, &ml_kem_768, &[]), Err(CKR_TEMPLATE_INCONSISTENT));
+        let read_only = token.open_session(SLOT_ID, CKF_SERIAL_SESSION).unwrap();
+        assert_eq!(token.generate_key_pair(read_only, CKM_ML_KEM_KEY_PAIR_GEN, &[], &[]), Err(CKR_SESSION_READ_ONLY));
+        let label = [(CKA_LABEL, &b"inbound"[..])];
+        assert_eq!(token.generate_key_pair(session, CKM_ML_KEM_KEY_PAIR_GEN, &label, &[]), Err(CKR_ATTRIBUTE_VALUE_INVALID));
+    }
+
+    #[test]
+    fn test_shared_secrets_are_session_objects() {
+        let (mut token, session) = token();
+        let (public_key, private_key) = token.generate_key_pair(session, CKM_ML_KEM_KEY_PAIR_GEN, &[], &[]).unwrap();
+        let other = token.open_session(SLOT_ID, CKF_SERIAL_SESSION).unwrap();
+
+        let (ciphertext, sent) = token.encapsulate(other, CKM_ML_KEM, public_key, &[]).unwrap();
+        let sensitive = [(CKA_SENSITIVE, &[CK_TRUE][..])];
+        let received = token.decapsulate(session, CKM_ML_KEM, private_key, &sensitive, &ciphertext).unwrap();
+        assert_eq!(token.object(session, sent).unwrap().attribute(CKA_VALUE_LEN).unwrap().len(), std::mem::size_of::<CK_ULONG>());
+        assert_eq!(token.object(session, received).unwrap().attribute(CKA_VALUE).unwrap_err(), CKR_ATTRIBUTE_SENSITIVE);
+
+        assert_eq!(token.encapsulate(session, CKM_ML_KEM, private_key, &[]).unwrap_err(), CKR_KEY_TYPE_INCONSISTENT);
+        let on_token = [(CKA_TOKEN, &[CK_TRUE][..])];
+        assert_eq!(token.encapsulate(session, CKM_ML_KEM, public_key, &on_token).unwrap_err(), CKR_TEMPLATE_INCONSISTENT);
+        assert_eq!(token.destroy_object(session, private_key), Err(CKR_ACTION_PROHIBITED));
+
+        token.close_session(other).unwrap();
+        assert_eq!(token.object(session, sent).err(), Some(CKR_OBJECT_HANDLE_INVALID));
+        assert!(token.object(session, received).is_ok());
+    }
+}
@@ -0,0 +1,150 @@
+//! Loads the built provider through the `cryptoki` client, the way any
+//! PKCS#11 consumer would. The module keeps one token per process, so the
+//! whole scenario runs in a single test.
+
+use cryptoki::context::{CInitializeArgs, CInitializeFlags, Pkcs11};
+use cryptoki::error::{Error, RvError};
+use cryptoki::mechanism::aead::GcmParams;
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, KeyType, MlKemParameterSetType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::AuthPin;
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::path::PathBuf;
+
+// `cargo test` builds the cdylib into `deps`, next to this test binary; a
+// separate `cargo build` also copies it to the parent directory
+fn module_path() -> PathBuf {
+    let name = format!("{}pqc_kyber_pkcs11{}", DLL_PREFIX, DLL_SUFFIX);
+    let exe = std::env::current_exe().unwrap();
+    let dir = exe.parent().unwrap();
+    let candidates = [Some(dir.join(&name)), dir.parent().map(|parent| parent.join(&name))];
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|path| path.exists())
+        .unwrap_or_else(|| panic!("{} not found next to {}", name, exe.display()))
+}
+
+const PIN: &str = "correct horse battery";
+
+fn login(session: &Session, pin: &str) -> Result<(), Error> {
+    session.login(UserType::User, Some(&AuthPin::from(pin.to_string())))
+}
+
+fn open_token() -> (Pkcs11, Session) {
+    let pkcs11 = Pkcs11::new(module_path()).unwrap();
+    pkcs11.initialize(CInitializeArgs::new(CInitializeFlags::OS_LOCKING_OK)).unwrap();
+    let slot = pkcs11.get_slots_with_token().unwrap()[0];
+    let session = pkcs11.open_rw_session(slot).unwrap();
+    (pkcs11, session)
+}
+
+fn find_key(session: &Session, class: ObjectClass, label: &str) -> ObjectHandle {
+    let found = session
+        .find_objects(&[Attribute::Class(class), Attribute::Label(label.as_bytes().to_vec())])
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    found[0]
+}
+
+fn secret_value(session: &Session, key: ObjectHandle) -> Vec<u8> {
+    match session.get_attributes(key, &[AttributeType::Value]).unwrap().as_slice() {
+        [Attribute::Value(value)] => value.clone(),
+        other => panic!("unexpected attributes {:?}", other),
+    }
+}
+
+fn gcm(iv: &mut [u8]) -> Mechanism<'_> {
+    Mechanism::AesGcm(GcmParams::new(iv, b"inbound", 128.into()).unwrap())
+}
+
+#[test]
+fn test_ml_kem_through_cryptoki() {
+    let store = tempfile::tempdir().unwrap();
+    let kek_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("PQC_KYBER_PKCS11_STORE", store.path());
+    std::env::set_var("PQC_KYBER_PKCS11_KEK_DIR", kek_dir.path());
+
+    let (pkcs11, session) = open_token();
+    let slot = session.get_session_info().unwrap().slot_id();
+    let mechanisms = pkcs11.get_mechanism_list(slot).unwrap();
+    assert!(mechanisms.contains(&Mechanism::MlKemKeyPairGen.mechanism_type()));
+    assert!(mechanisms.contains(&Mechanism::MlKem.mechanism_type()));
+
+    let public_template = [
+        Attribute::Token(true),
+        Attribute::ParameterSet(MlKemParameterSetType::ML_KEM_1024.into()),
+        Attribute::Encapsulate(true),
+        Attribute::Label(b"inbound".to_vec()),
+    ];
+    let private_template = [Attribute::Token(true), Attribute::Sensitive(true), Attribute::Decapsulate(true)];
+    // The store stays sealed until the first login, which sets the PIN
+    let locked = session.generate_key_pair(&Mechanism::MlKemKeyPairGen, &public_template, &private_template);
+    assert!(matches!(locked, Err(Error::Pkcs11(RvError::UserNotLoggedIn, _))));
+    assert!(matches!(login(&session, "short"), Err(Error::Pkcs11(RvError::PinLenRange, _))));
+    login(&session, PIN).unwrap();
+    assert!(matches!(login(&session, PIN), Err(Error::Pkcs11(RvError::UserAlreadyLoggedIn, _))));
+    let (public_key, private_key) = session
+        .generate_key_pair(&Mechanism::MlKemKeyPairGen, &public_template, &private_template)
+        .unwrap();
+    assert_eq!(find_key(&session, ObjectClass::PUBLIC_KEY, "inbound"), public_key);
+    assert_eq!(find_key(&session, ObjectClass::PRIVATE_KEY, "inbound"), private_key);
+    let attributes = session.get_attributes(private_key, &[AttributeType::KeyType]).unwrap();
+    assert!(matches!(attributes.as_slice(), [Attribute::KeyType(KeyType::ML_KEM)]));
+
+    // Both ends of a KEM exchange, each yielding a readable session key
+    let secret_template = [Attribute::Class(ObjectClass::SECRET_KEY), Attribute::KeyType(KeyType::GENERIC_SECRET)];
+    let (ciphertext, shared_handle) = session.encapsulate_key(&Mechanism::MlKem, public_key, &secret_template).unwrap();
+    let received = session.decapsulate_key(&Mechanism::MlKem, private_key, &secret_template, &ciphertext).unwrap();
+    let shared = secret_value(&session, shared_handle);
+    assert_eq!(shared.len(), 32);
+    assert_eq!(secret_value(&session, received), shared);
+
+    // An AES session key stays on the token and encrypts there
+    let aes_template = [
+        Attribute::Class(ObjectClass::SECRET_KEY),
+        Attribute::KeyType(KeyType::AES),
+        Attribute::Sensitive(true),
+        Attribute::Extractable(false),
+        Attribute::Encrypt(true),
+        Attribute::Decrypt(true),
+    ];
+    let (aes_ciphertext, sent) = session.encapsulate_key(&Mechanism::MlKem, public_key, &aes_template).unwrap();
+    let value = session.get_attributes(sent, &[AttributeType::Value]);
+    assert!(matches!(value.as_deref(), Err(_) | Ok([])));
+    let mut iv = [1u8; 12];
+    let sealed = session.encrypt(&gcm(&mut iv), sent, b"master key").unwrap();
+    assert_eq!(sealed.len(), b"master key".len() + 16);
+    let received = session.decapsulate_key(&Mechanism::MlKem, private_key, &aes_template, &aes_ciphertext).unwrap();
+    assert_eq!(session.decrypt(&gcm(&mut iv), received, &sealed).unwrap(), b"master key");
+    let mut tampered = sealed.clone();
+    tampered[0] ^= 1;
+    assert!(session.decrypt(&gcm(&mut iv), received, &tampered).is_err());
+    // Readable generic secrets cannot be used for encryption
+    assert!(session.encrypt(&gcm(&mut iv), shared_handle, b"master key").is_err());
+
+    // The private key never leaves the KMS, and the KMS refuses to destroy it here
+    let value = session.get_attributes(private_key, &[AttributeType::Value]);
+    assert!(matches!(value.as_deref(), Err(_) | Ok([])));
+    assert!(session.destroy_object(private_key).is_err());
+    let truncated = &ciphertext[..ciphertext.len() - 1];
+    assert!(session.decapsulate_key(&Mechanism::MlKem, private_key, &secret_template, truncated).is_err());
+
+    // Logging out locks the keys away again, and only the PIN reopens them
+    session.logout().unwrap();
+    assert!(session.find_objects(&[Attribute::Class(ObjectClass::PRIVATE_KEY)]).unwrap().is_empty());
+    assert!(matches!(login(&session, "wrong horse battery"), Err(Error::Pkcs11(RvError::PinIncorrect, _))));
+
+    // Key pairs are KMS keys: after a restart the file store still has them
+    drop(session);
+    pkcs11.finalize().unwrap();
+    let (pkcs11, session) = open_token();
+    login(&session, PIN).unwrap();
+    assert!(session.find_objects(&[Attribute::Class(ObjectClass::SECRET_KEY)]).unwrap().is_empty());
+    let private_key = find_key(&session, ObjectClass::PRIVATE_KEY, "inbound");
+    let received = session.decapsulate_key(&Mechanism::MlKem, private_key, &secret_template, &ciphertext).unwrap();
+    assert_eq!(secret_value(&session, received), shared);
+    drop(session);
+    pkcs11.finalize().unwrap();
+}
@@ -0,0 +1,57 @@
+//! Unseals a KMS file store through the crate's PKCS#11 master-key provider,
+//! with this crate's own module as the token. Like `cryptoki.rs`, the whole
+//! scenario runs in one test because the module keeps one token per process.
+
+use pqc_kyber::crypto::kms::{AccessPolicy, CallerContext, KeyManagementSystem, KmsAction, Role};
+use pqc_kyber::crypto::secure::SecureSecret;
+use pqc_kyber::kms::{FileKeyStore, MasterKeyProvider, Pkcs11Provider};
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::path::PathBuf;
+
+// `cargo test` builds the cdylib into `deps`, next to this test binary; a
+// separate `cargo build` also copies it to the parent directory
+fn module_path() -> PathBuf {
+    let name = format!("{}pqc_kyber_pkcs11{}", DLL_PREFIX, DLL_SUFFIX);
+    let exe = std::env::current_exe().unwrap();
+    let dir = exe.parent().unwrap();
+    let candidates = [Some(dir.join(&name)), dir.parent().map(|parent| parent.join(&name))];
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|path| path.exists())
+        .unwrap_or_else(|| panic!("{} not found next to {}", name, exe.display()))
+}
+
+#[test]
+fn test_store_unsealed_through_pkcs11() {
+    let token_store = tempfile::tempdir().unwrap();
+    let kek_dir = tempfile::tempdir().unwrap();
+    std::env::set_var("PQC_KYBER_PKCS11_STORE", token_store.path());
+    std::env::set_var("PQC_KYBER_PKCS11_KEK_DIR", kek_dir.path());
+    let pin = SecureSecret::from_bytes(b"token user pin");
+    let provider = Pkcs11Provider::open(module_path(), None, "kms-master", Some(pin)).unwrap();
+
+    let root = tempfile::tempdir().unwrap();
+    let admin = CallerContext::new("kms-admin");
+    let policy = AccessPolicy::new()
+        .with_role(Role::new("kms-admin").allow("*", KmsAction::ALL))
+        .assign(&admin.principal, "kms-admin");
+    let mut kms = KeyManagementSystem::unseal_store(root.path(), &provider).unwrap();
+    kms.set_access_policy(policy.clone());
+    let public_key = kms.generate_kem_key(&admin, "inbound").unwrap();
+    drop(kms);
+    assert_eq!(provider.versions().unwrap(), vec![1]);
+
+    // A new key pair on the token; the store moves to it and still opens
+    provider.rotate().unwrap();
+    FileKeyStore::open_with_provider(root.path(), &provider)
+        .unwrap()
+        .rewrap_master_key(&provider)
+        .unwrap();
+    let wrapped = std::fs::read_to_string(root.path().join("MASTER")).unwrap();
+    assert!(wrapped.contains("\"kms-master:2\""));
+
+    let mut kms = KeyManagementSystem::unseal_store(root.path(), &provider).unwrap();
+    kms.set_access_policy(policy);
+    assert_eq!(kms.kem_public_key(&admin, "inbound").unwrap(), public_key);
+}
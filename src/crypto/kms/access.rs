@@ -0,0 +1,358 @@
+//! Role-based access control for KMS operations.
+//!
+//! Every KMS call carries a [`CallerContext`] naming the principal (service or
+//! operator) it is made for; authenticating that principal is the transport's
+//! job. Principals are assigned roles, and a role is a list of permissions
+//! that allow or deny [`KmsAction`]s on the keys matching a [`KeyPattern`]:
+//!
+//! ```text
+//! settlement     exactly the key `settlement`
+//! settlement:*   every version of the versioned key `settlement`, and the key itself
+//! *              every key
+//! ```
+//!
+//! Access is denied by default. An action is allowed only if one of the
+//! caller's roles allows it on the key and none denies it, so "may encapsulate
+//! with `settlement:*` but never export" is one allow and one deny.
+
+use super::lifecycle::KeyOperation;
+use super::version::KEY_VERSION_SEPARATOR;
+use crate::error::KmsError;
+use crate::kms::audit::AuditOperation;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+const WILDCARD: char = '*';
+
+/// Authenticated principal a KMS call is made on behalf of
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CallerContext {
+    pub principal: String,
+}
+
+/// Anything a caller can do to a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum KmsAction {
+    Create,
+    /// Read state, metadata, versions and usage counters
+    Describe,
+    Encapsulate,
+    Decapsulate,
+    Encrypt,
+    Decrypt,
+    Sign,
+    Verify,
+    /// Receive raw key material, including custodian shares
+    Export,
+    /// Rotate to a new version and pin minimum versions
+    Rotate,
+    /// Lifecycle changes other than destruction
+    Transition,
+    Destroy,
+    Delete,
+    /// Include the key in a KEK-sealed snapshot or backup bundle
+    Backup,
+    /// Write the key from a backup bundle, replacing any existing one
+    Restore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+/// Key id, `prefix*` or `*`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct KeyPattern(String);
+
+/// Allows or denies actions on matching keys
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permission {
+    pub effect: Effect,
+    pub keys: KeyPattern,
+    pub actions: BTreeSet<KmsAction>,
+}
+
+/// Named set of permissions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Role {
+    pub name: String,
+    pub permissions: Vec<Permission>,
+}
+
+/// Roles and the principals assigned to them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessPolicy {
+    roles: HashMap<String, Role>,
+    assignments: HashMap<String, BTreeSet<String>>,
+}
+
+impl CallerContext {
+    pub fn new(principal: &str) -> Self {
+        Self {
+            principal: principal.to_string(),
+        }
+    }
+}
+
+impl KmsAction {
+    pub const ALL: [KmsAction; 15] = [
+        KmsAction::Create,
+        KmsAction::Describe,
+        KmsAction::Encapsulate,
+        KmsAction::Decapsulate,
+        KmsAction::Encrypt,
+        KmsAction::Decrypt,
+        KmsAction::Sign,
+        KmsAction::Verify,
+        KmsAction::Export,
+        KmsAction::Rotate,
+        KmsAction::Transition,
+        KmsAction::Destroy,
+        KmsAction::Delete,
+        KmsAction::Backup,
+        KmsAction::Restore,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KmsAction::Create => "CREATE",
+            KmsAction::Describe => "DESCRIBE",
+            KmsAction::Encapsulate => "ENCAPSULATE",
+            KmsAction::Decapsulate => "DECAPSULATE",
+            KmsAction::Encrypt => "ENCRYPT",
+            KmsAction::Decrypt => "DECRYPT",
+            KmsAction::Sign => "SIGN",
+            KmsAction::Verify => "VERIFY",
+            KmsAction::Export => "EXPORT",
+            KmsAction::Rotate => "ROTATE",
+            KmsAction::Transition => "TRANSITION",
+            KmsAction::Destroy => "DESTROY",
+            KmsAction::Delete => "DELETE",
+            KmsAction::Backup => "BACKUP",
+            KmsAction::Restore => "RESTORE",
+        }
+    }
+}
+
+impl From<KeyOperation> for KmsAction {
+    fn from(operation: KeyOperation) -> Self {
+        match operation {
+            KeyOperation::Encapsulate => KmsAction::Encapsulate,
+            KeyOperation::Decapsulate => KmsAction::Decapsulate,
+            KeyOperation::Encrypt => KmsAction::Encrypt,
+            KeyOperation::Decrypt => KmsAction::Decrypt,
+            KeyOperation::Sign => KmsAction::Sign,
+            KeyOperation::Verify => KmsAction::Verify,
+            KeyOperation::Export => KmsAction::Export,
+        }
+    }
+}
+
+impl From<KmsAction> for AuditOperation {
+    fn from(action: KmsAction) -> Self {
+        match action {
+            KmsAction::Create => AuditOperation::Create,
+            KmsAction::Describe => AuditOperation::Describe,
+            KmsAction::Encapsulate
+            | KmsAction::Decapsulate
+            | KmsAction::Encrypt
+            | KmsAction::Decrypt
+            | KmsAction::Sign
+            | KmsAction::Verify => AuditOperation::Use,
+            KmsAction::Export => AuditOperation::Export,
+            KmsAction::Rotate => AuditOperation::Rotate,
+            KmsAction::Transition => AuditOperation::Transition,
+            KmsAction::Destroy => AuditOperation::Destroy,
+            KmsAction::Delete => AuditOperation::Delete,
+            KmsAction::Backup => AuditOperation::Backup,
+            KmsAction::Restore => AuditOperation::Restore,
+        }
+    }
+}
+
+impl fmt::Display for KmsAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl KeyPattern {
+    pub fn new(pattern: &str) -> Self {
+        Self(pattern.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn matches(&self, key_id: &str) -> bool {
+        let Some(prefix) = self.0.strip_suffix(WILDCARD) else {
+            return self.0 == key_id;
+        };
+        key_id.starts_with(prefix) || prefix.strip_suffix(KEY_VERSION_SEPARATOR) == Some(key_id)
+    }
+}
+
+impl Role {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            permissions: Vec::new(),
+        }
+    }
+
+    pub fn allow(self, keys: &str, actions: impl IntoIterator<Item = KmsAction>) -> Self {
+        self.permit(Effect::Allow, keys, actions)
+    }
+
+    pub fn deny(self, keys: &str, actions: impl IntoIterator<Item = KmsAction>) -> Self {
+        self.permit(Effect::Deny, keys, actions)
+    }
+
+    fn permit(mut self, effect: Effect, keys: &str, actions: impl IntoIterator<Item = KmsAction>) -> Self {
+        self.permissions.push(Permission {
+            effect,
+            keys: KeyPattern::new(keys),
+            actions: actions.into_iter().collect(),
+        });
+        self
+    }
+
+    // Effect of this role for `action` on `key_id`; a deny anywhere wins
+    fn effect(&self, action: KmsAction, key_id: &str) -> Option<Effect> {
+        self.permissions
+            .iter()
+            .filter(|p| p.actions.contains(&action) && p.keys.matches(key_id))
+            .map(|p| p.effect)
+            .max_by_key(|effect| *effect == Effect::Deny)
+    }
+}
+
+impl AccessPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_role(mut self, role: Role) -> Self {
+        self.roles.insert(role.name.clone(), role);
+        self
+    }
+
+    pub fn assign(mut self, principal: &str, role: &str) -> Self {
+        self.assignments
+            .entry(principal.to_string())
+            .or_default()
+            .insert(role.to_string());
+        self
+    }
+
+    pub fn roles_of(&self, principal: &str) -> Vec<&Role> {
+        self.assignments
+            .get(principal)
+            .into_iter()
+            .flatten()
+            .filter_map(|name| self.roles.get(name))
+            .collect()
+    }
+
+    pub fn is_allowed(&self, caller: &CallerContext, action: KmsAction, key_id: &str) -> bool {
+        let effects: Vec<Effect> = self
+            .roles_of(&caller.principal)
+            .iter()
+            .filter_map(|role| role.effect(action, key_id))
+            .collect();
+        effects.contains(&Effect::Allow) && !effects.contains(&Effect::Deny)
+    }
+
+    pub fn check(&self, caller: &CallerContext, action: KmsAction, key_id: &str) -> Result<(), KmsError> {
+        if self.is_allowed(caller, action, key_id) {
+            return Ok(());
+        }
+        Err(KmsError::AccessDenied(format!(
+            "{} may not {} key {}",
+            caller.principal, action, key_id
+        )))
+    }
+}
+
+/// Policy granting `principal` every action on every key, for tests
+#[cfg(test)]
+pub(crate) fn administrator_policy(principal: &str) -> AccessPolicy {
+    AccessPolicy::new()
+        .with_role(Role::new("kms-admin").allow("*", KmsAction::ALL))
+        .assign(principal, "kms-admin")
+}
+
+/// In-memory KMS and a caller allowed to do everything with it, without
+/// four-eyes approval, for tests
+#[cfg(test)]
+pub(crate) fn test_kms() -> (super::KeyManagementSystem, CallerContext) {
+    let mut kms = super::KeyManagementSystem::new();
+    kms.set_access_policy(administrator_policy("kms-admin"));
+    kms.set_approval_policy(super::ApprovalPolicy::default().exempt("kms-admin"));
+    (kms, CallerContext::new("kms-admin"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settlement_policy() -> AccessPolicy {
+        AccessPolicy::new()
+            .with_role(
+                Role::new("settlement-etl")
+                    .allow("settlement:*", [KmsAction::Encapsulate, KmsAction::Encrypt, KmsAction::Describe])
+                    .deny("*", [KmsAction::Export]),
+            )
+            .with_role(Role::new("custodian").allow("*", [KmsAction::Export]))
+            .assign("etl-service", "settlement-etl")
+            .assign("officer", "custodian")
+    }
+
+    #[test]
+    fn test_key_patterns() {
+        let versions = KeyPattern::new("settlement:*");
+        assert!(versions.matches("settlement:3"));
+        assert!(versions.matches("settlement"));
+        assert!(!versions.matches("settlements"));
+        assert!(!versions.matches("reporting:1"));
+
+        assert!(KeyPattern::new("*").matches("anything"));
+        assert!(KeyPattern::new("kms-master").matches("kms-master"));
+        assert!(!KeyPattern::new("kms-master").matches("kms-master:1"));
+    }
+
+    #[test]
+    fn test_least_privilege() {
+        let policy = settlement_policy();
+        let etl = CallerContext::new("etl-service");
+
+        assert!(policy.check(&etl, KmsAction::Encapsulate, "settlement:2").is_ok());
+        assert!(policy.check(&etl, KmsAction::Decapsulate, "settlement:2").is_err());
+        assert!(policy.check(&etl, KmsAction::Encapsulate, "reporting:1").is_err());
+
+        // Custodians may export; unknown principals get nothing at all
+        assert!(policy.check(&CallerContext::new("officer"), KmsAction::Export, "settlement:2").is_ok());
+        let err = policy.check(&CallerContext::new("intruder"), KmsAction::Describe, "settlement:2").unwrap_err();
+        assert_eq!(err.code().name, "KMS_ACCESS_DENIED");
+    }
+
+    #[test]
+    fn test_deny_overrides_allow() {
+        // Even with the custodian role added, the ETL role's deny on export wins
+        let policy = settlement_policy().assign("etl-service", "custodian");
+        let etl = CallerContext::new("etl-service");
+        assert!(!policy.is_allowed(&etl, KmsAction::Export, "settlement:1"));
+
+        let json = serde_json::to_string(&policy).unwrap();
+        let restored: AccessPolicy = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, policy);
+        assert!(json.contains("\"settlement:*\""));
+    }
+}
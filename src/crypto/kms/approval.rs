@@ -0,0 +1,724 @@
+//! Four-eyes approval for sensitive key operations.
+//!
+//! Destroying, deleting or exporting a key, marking it compromised, sealing
+//! keys into a snapshot or backup bundle and restoring a bundle are never
+//! done on one operator's word.
+//! The initiator files a
+//! [`PendingRequest`]; other principals who are themselves allowed to perform
+//! the operation sign an [`Approval`] of it with their ML-DSA key. Once the
+//! quorum is reached, and before the request expires, the initiator executes
+//! it in one step: with [`execute_request`](KeyManagementSystem::execute_request),
+//! or, for snapshots, backups and restores, by repeating the call with
+//! exactly the keys and bundle that were approved.
+//!
+//! Requests and signed decisions are written to the audit log as JSON, so an
+//! auditor can rebuild each request from the trail and re-check every
+//! decision on it with [`verify_approvals`].
+
+use super::access::{CallerContext, KeyPattern, KmsAction};
+use super::backup::{BackupBundle, BackupScope, RestoreOptions};
+use super::lifecycle::{KeyOperation, KeyState, TransitionReason};
+use super::KeyManagementSystem;
+use crate::crypto::secure::{KekPublicKey, SecureSecret};
+use crate::crypto::signature::{DetachedSignature, MlDsaSigningKey, MlDsaVerifyingKey, SigningMode, TrustedSigners};
+use crate::error::KmsError;
+use crate::kms::audit::{AuditOperation, AuditOutcome};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// ML-DSA context string of approval signatures
+pub const APPROVAL_CONTEXT: &[u8] = b"pqc-kyber/kms/approval/v1";
+const DEFAULT_APPROVAL_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const REQUEST_ID_LENGTH: usize = 16;
+const ALL_KEYS: &str = "*";
+
+/// Operation that needs approval by someone other than its initiator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SensitiveOperation {
+    Destroy { key_id: String, reason: TransitionReason },
+    /// Hands the raw key material to the initiator on execution
+    Export { key_id: String },
+    MarkCompromised { key_id: String },
+    /// Removes the key and its history from the store
+    Delete { key_id: String },
+    /// Seals every key to one KEK, named by its fingerprint (`<kek id>:<hex
+    /// SHA3-256 of the public key>`); see [`SensitiveOperation::snapshot`]
+    Snapshot { kek: String },
+    /// Exports the keys matching `scope` sealed to the recovery KEKs, named
+    /// by fingerprint; see [`SensitiveOperation::export_backup`]
+    ExportBackup { scope: KeyPattern, recovery_keys: Vec<String> },
+    /// Restores the bundle whose manifest has this hex SHA3-256 digest;
+    /// see [`SensitiveOperation::restore_backup`]
+    RestoreBackup {
+        scope: KeyPattern,
+        bundle_id: String,
+        manifest_digest: String,
+        /// An approval of a plain restore does not cover a downgrade
+        allow_downgrade: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApprovalDecision {
+    Approve,
+    /// Cancels the request
+    Reject,
+}
+
+/// Signed decision of one approver on one request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Approval {
+    pub request_id: String,
+    pub decision: ApprovalDecision,
+    pub at: u64,
+    /// Made by the approving principal; its signer id is the approver
+    pub signature: DetachedSignature,
+}
+
+/// Sensitive operation waiting for its quorum
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingRequest {
+    pub id: String,
+    pub operation: SensitiveOperation,
+    pub initiator: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    #[serde(default)]
+    pub approvals: Vec<Approval>,
+}
+
+/// Where a request stands after a decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestStatus {
+    Pending { approvals: usize, quorum: usize },
+    /// Quorum reached; the initiator may execute
+    Approved,
+    /// Rejected by an approver and removed from the queue
+    Rejected,
+}
+
+/// How many approvals a sensitive operation needs and for how long a request stays open
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovalPolicy {
+    /// Approvals needed from principals other than the initiator
+    pub quorum: usize,
+    pub ttl: Duration,
+    /// Services whose sensitive operations are pre-approved by policy, e.g.
+    /// the rotation engine destroying retired versions
+    #[serde(default)]
+    pub exempt: BTreeSet<String>,
+}
+
+/// Requests awaiting approval and the keys approvers sign with
+#[derive(Debug, Default)]
+pub(super) struct ApprovalQueue {
+    policy: ApprovalPolicy,
+    approvers: TrustedSigners,
+    pending: HashMap<String, PendingRequest>,
+}
+
+// What an approval signature covers: the request as filed, the decision and its time
+#[derive(Serialize)]
+struct SignedDecision<'a> {
+    request_id: &'a str,
+    operation: &'a SensitiveOperation,
+    initiator: &'a str,
+    created_at: u64,
+    expires_at: u64,
+    decision: ApprovalDecision,
+    at: u64,
+}
+
+impl SensitiveOperation {
+    /// Snapshot sealed to `kek`; approvers see which key it is sealed to
+    pub fn snapshot(kek: &KekPublicKey) -> Self {
+        SensitiveOperation::Snapshot { kek: kek_fingerprint(kek) }
+    }
+
+    /// Backup export of `scope` sealed to `recovery_keys`
+    pub fn export_backup(scope: &BackupScope, recovery_keys: &[KekPublicKey]) -> Self {
+        SensitiveOperation::ExportBackup {
+            scope: scope.pattern(),
+            recovery_keys: recovery_keys.iter().map(kek_fingerprint).collect(),
+        }
+    }
+
+    /// Restore of exactly `bundle` with `options`
+    pub fn restore_backup(bundle: &BackupBundle, options: RestoreOptions) -> Result<Self, KmsError> {
+        let manifest = &bundle.manifest;
+        Ok(SensitiveOperation::RestoreBackup {
+            scope: manifest.scope.clone().unwrap_or_else(|| KeyPattern::new(ALL_KEYS)),
+            bundle_id: manifest.bundle_id.clone(),
+            manifest_digest: manifest.digest()?,
+            allow_downgrade: options.allow_downgrade,
+        })
+    }
+
+    /// Key the operation acts on, or the pattern of the keys it covers
+    pub fn key_id(&self) -> &str {
+        match self {
+            SensitiveOperation::Destroy { key_id, .. }
+            | SensitiveOperation::Export { key_id }
+            | SensitiveOperation::MarkCompromised { key_id }
+            | SensitiveOperation::Delete { key_id } => key_id,
+            SensitiveOperation::Snapshot { .. } => ALL_KEYS,
+            SensitiveOperation::ExportBackup { scope, .. } | SensitiveOperation::RestoreBackup { scope, .. } => scope.as_str(),
+        }
+    }
+
+    /// Action the initiator and every approver must be allowed
+    pub fn action(&self) -> KmsAction {
+        match self {
+            SensitiveOperation::Destroy { .. } => KmsAction::Destroy,
+            SensitiveOperation::Export { .. } => KmsAction::Export,
+            SensitiveOperation::MarkCompromised { .. } => KmsAction::Transition,
+            SensitiveOperation::Delete { .. } => KmsAction::Delete,
+            SensitiveOperation::Snapshot { .. } | SensitiveOperation::ExportBackup { .. } => KmsAction::Backup,
+            SensitiveOperation::RestoreBackup { .. } => KmsAction::Restore,
+        }
+    }
+
+    // Operations on one key run from the queue. The others take or produce
+    // bundles and are carried out by repeating the call once approved.
+    fn is_key_operation(&self) -> bool {
+        !matches!(
+            self,
+            SensitiveOperation::Snapshot { .. } | SensitiveOperation::ExportBackup { .. } | SensitiveOperation::RestoreBackup { .. }
+        )
+    }
+}
+
+impl Approval {
+    /// Signs `decision` on `request` as `approver`
+    pub fn sign(
+        request: &PendingRequest,
+        approver: &str,
+        key: &MlDsaSigningKey,
+        decision: ApprovalDecision,
+    ) -> Result<Self> {
+        let at = now();
+        let payload = request.signed_payload(decision, at)?;
+        Ok(Self {
+            request_id: request.id.clone(),
+            decision,
+            at,
+            signature: DetachedSignature::create(approver, key, &payload, APPROVAL_CONTEXT, SigningMode::Hedged)?,
+        })
+    }
+
+    pub fn approver(&self) -> &str {
+        &self.signature.signer_id
+    }
+
+    /// Checks that this decision was signed for `request` by a trusted approver
+    pub fn verify(&self, request: &PendingRequest, approvers: &TrustedSigners) -> Result<()> {
+        if self.request_id != request.id {
+            return Err(anyhow!("approval is for request {}, not {}", self.request_id, request.id));
+        }
+        let payload = request.signed_payload(self.decision, self.at)?;
+        approvers.verify(&self.signature, &payload, APPROVAL_CONTEXT)
+    }
+}
+
+impl PendingRequest {
+    fn signed_payload(&self, decision: ApprovalDecision, at: u64) -> Result<Vec<u8>> {
+        let signed = SignedDecision {
+            request_id: &self.id,
+            operation: &self.operation,
+            initiator: &self.initiator,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            decision,
+            at,
+        };
+        Ok(serde_json::to_vec(&signed)?)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Principals that approved so far
+    pub fn approvers(&self) -> impl Iterator<Item = &str> {
+        self.approvals
+            .iter()
+            .filter(|a| a.decision == ApprovalDecision::Approve)
+            .map(Approval::approver)
+    }
+}
+
+impl Default for ApprovalPolicy {
+    /// Four eyes: the initiator plus one approver, within a day
+    fn default() -> Self {
+        Self {
+            quorum: 1,
+            ttl: DEFAULT_APPROVAL_TTL,
+            exempt: BTreeSet::new(),
+        }
+    }
+}
+
+impl ApprovalPolicy {
+    pub fn new(quorum: usize, ttl: Duration) -> Self {
+        Self {
+            quorum,
+            ttl,
+            exempt: BTreeSet::new(),
+        }
+    }
+
+    pub fn exempt(mut self, principal: &str) -> Self {
+        self.exempt.insert(principal.to_string());
+        self
+    }
+
+    fn is_exempt(&self, caller: &CallerContext) -> bool {
+        self.exempt.contains(&caller.principal)
+    }
+}
+
+/// Re-checks a request rebuilt from the audit trail: every decision must be
+/// validly signed by a trusted approver other than the initiator, made
+/// before the request expired, and no approver may count twice
+pub fn verify_approvals(request: &PendingRequest, approvers: &TrustedSigners) -> Result<()> {
+    let mut seen = BTreeSet::new();
+    for approval in &request.approvals {
+        approval.verify(request, approvers)?;
+        if approval.approver() == request.initiator {
+            return Err(anyhow!("{} approved their own request {}", request.initiator, request.id));
+        }
+        if approval.at >= request.expires_at {
+            return Err(anyhow!("approval by {} was made after request {} expired", approval.approver(), request.id));
+        }
+        if !seen.insert(approval.approver()) {
+            return Err(anyhow!("{} decided twice on request {}", approval.approver(), request.id));
+        }
+    }
+    Ok(())
+}
+
+impl KeyManagementSystem {
+    /// Replaces the four-eyes policy; requests already pending keep their expiry
+    pub fn set_approval_policy(&mut self, policy: ApprovalPolicy) {
+        self.approvals.policy = policy;
+    }
+
+    pub fn approval_policy(&self) -> &ApprovalPolicy {
+        &self.approvals.policy
+    }
+
+    /// Registers the key `principal` signs its approvals with
+    pub fn trust_approver(&mut self, principal: &str, key: MlDsaVerifyingKey) -> Result<(), KmsError> {
+        self.approvals
+            .approvers
+            .add_signer(principal, key)
+            .map_err(|e| KmsError::OperationNotPermitted(e.to_string()))
+    }
+
+    pub fn approvers(&self) -> &TrustedSigners {
+        &self.approvals.approvers
+    }
+
+    // Refuses a sensitive operation made directly by a non-exempt caller
+    pub(super) fn require_approval(&self, caller: &CallerContext, action: KmsAction, key_id: &str) -> Result<(), KmsError> {
+        let policy = &self.approvals.policy;
+        if policy.quorum == 0 || policy.is_exempt(caller) {
+            return Ok(());
+        }
+        Err(KmsError::ApprovalRequired(format!(
+            "{} of key {} needs {} approval(s); file it with request_operation",
+            action, key_id, policy.quorum
+        )))
+    }
+
+    // Approved request of `caller` for exactly `operation`, for operations that
+    // are carried out directly once approved. `None` for exempt callers.
+    pub(super) fn approved_request(&self, caller: &CallerContext, operation: &SensitiveOperation) -> Result<Option<String>, KmsError> {
+        let policy = &self.approvals.policy;
+        if policy.quorum == 0 || policy.is_exempt(caller) {
+            return Ok(None);
+        }
+        let now = now();
+        self.approvals
+            .pending
+            .values()
+            .find(|r| {
+                r.initiator == caller.principal
+                    && &r.operation == operation
+                    && !r.is_expired(now)
+                    && r.approvers().count() >= policy.quorum
+            })
+            .map(|r| Some(r.id.clone()))
+            .ok_or_else(|| {
+                KmsError::ApprovalRequired(format!(
+                    "{} of {} needs {} approval(s); file it with request_operation",
+                    operation.action(), operation.key_id(), policy.quorum
+                ))
+            })
+    }
+
+    // Removes a request carried out by `approved_request`'s caller
+    pub(super) fn complete_request(&mut self, request_id: Option<&str>) {
+        if let Some(request_id) = request_id {
+            self.approvals.pending.remove(request_id);
+        }
+    }
+
+    /// Files `operation` for approval. The initiator must be allowed to perform it.
+    pub fn request_operation(
+        &mut self,
+        caller: &CallerContext,
+        operation: SensitiveOperation,
+    ) -> Result<PendingRequest, KmsError> {
+        let key_id = operation.key_id().to_string();
+        self.authorize(caller, operation.action(), &key_id)?;
+        let exists = if operation.is_key_operation() { self.record(&key_id).map(|_| ()) } else { Ok(()) };
+        let result = exists.map(|_| {
+            let created_at = now();
+            PendingRequest {
+                id: hex::encode(rand::random::<[u8; REQUEST_ID_LENGTH]>()),
+                operation,
+                initiator: caller.principal.clone(),
+                created_at,
+                expires_at: created_at.saturating_add(self.approvals.policy.ttl.as_secs()),
+                approvals: Vec::new(),
+            }
+        });
+        let detail = result.as_ref().ok().and_then(|request| serde_json::to_string(request).ok());
+        let request = self.audited(caller, AuditOperation::Request, &key_id, detail, result)?;
+        self.approvals.pending.insert(request.id.clone(), request.clone());
+        Ok(request)
+    }
+
+    /// Requests the caller may describe the keys of
+    pub fn pending_requests(&self, caller: &CallerContext) -> Vec<&PendingRequest> {
+        self.approvals
+            .pending
+            .values()
+            .filter(|r| self.access.is_allowed(caller, KmsAction::Describe, r.operation.key_id()))
+            .collect()
+    }
+
+    /// Records a signed decision on a pending request. The caller must have
+    /// signed it, must not be the initiator, must not have decided on the
+    /// request before and must be allowed to perform the operation itself.
+    pub fn approve(&mut self, caller: &CallerContext, approval: Approval) -> Result<RequestStatus, KmsError> {
+        let request = self.pending(&approval.request_id)?;
+        let key_id = request.operation.key_id().to_string();
+        self.authorize(caller, request.operation.action(), &key_id)?;
+
+        let result = self.check_approval(caller, &request, &approval);
+        let detail = serde_json::to_string(&approval).ok();
+        self.audited(caller, AuditOperation::Approve, &key_id, detail, result)?;
+
+        if approval.decision == ApprovalDecision::Reject {
+            self.approvals.pending.remove(&approval.request_id);
+            return Ok(RequestStatus::Rejected);
+        }
+        let quorum = self.approvals.policy.quorum;
+        let pending = self.approvals.pending.get_mut(&approval.request_id)
+            .ok_or_else(|| KmsError::RequestNotFound(approval.request_id.clone()))?;
+        pending.approvals.push(approval);
+        let approvals = pending.approvers().count();
+        if approvals >= quorum {
+            Ok(RequestStatus::Approved)
+        } else {
+            Ok(RequestStatus::Pending { approvals, quorum })
+        }
+    }
+
+    fn check_approval(&self, caller: &CallerContext, request: &PendingRequest, approval: &Approval) -> Result<(), KmsError> {
+        if approval.approver() != caller.principal {
+            return Err(KmsError::ApprovalRejected(format!(
+                "{} presented a decision signed by {}",
+                caller.principal, approval.approver()
+            )));
+        }
+        if caller.principal == request.initiator {
+            return Err(KmsError::ApprovalRejected(format!(
+                "{} cannot approve their own request {}",
+                caller.principal, request.id
+            )));
+        }
+        if request.approvals.iter().any(|a| a.approver() == caller.principal) {
+            return Err(KmsError::ApprovalRejected(format!(
+                "{} already decided on request {}",
+                caller.principal, request.id
+            )));
+        }
+        approval.verify(request, &self.approvals.approvers)
+            .map_err(|e| KmsError::ApprovalRejected(format!("decision by {}: {}", caller.principal, e)))
+    }
+
+    /// Executes an approved request and removes it from the queue. Only the
+    /// initiator can execute; an export returns the key material.
+    pub fn execute_request(&mut self, caller: &CallerContext, request_id: &str) -> Result<Option<SecureSecret>, KmsError> {
+        let request = self.pending(request_id)?;
+        let key_id = request.operation.key_id().to_string();
+        let action = request.operation.action();
+        self.authorize(caller, action, &key_id)?;
+
+        let quorum = self.approvals.policy.quorum;
+        let result = if !request.operation.is_key_operation() {
+            Err(KmsError::OperationNotPermitted(format!(
+                "request {} is carried out by repeating the {} call once approved",
+                request.id, action
+            )))
+        } else if caller.principal != request.initiator {
+            Err(KmsError::OperationNotPermitted(format!(
+                "request {} can only be executed by {}",
+                request.id, request.initiator
+            )))
+        } else if request.approvers().count() < quorum {
+            Err(KmsError::ApprovalRequired(format!(
+                "request {} has {} of {} approval(s)",
+                request.id, request.approvers().count(), quorum
+            )))
+        } else {
+            // A request whose operation fails stays queued with its approvals
+            let result = self.run(&request.operation);
+            if result.is_ok() {
+                self.approvals.pending.remove(request_id);
+            }
+            result
+        };
+        self.audited(caller, action.into(), &key_id, Some(format!("request {}", request.id)), result)
+    }
+
+    fn run(&mut self, operation: &SensitiveOperation) -> Result<Option<SecureSecret>, KmsError> {
+        match operation {
+            SensitiveOperation::Destroy { key_id, reason } => {
+                self.apply_transition(key_id, KeyState::Destroyed, *reason).map(|_| None)
+            }
+            SensitiveOperation::MarkCompromised { key_id } => {
+                self.apply_transition(key_id, KeyState::Compromised, TransitionReason::KeyCompromise).map(|_| None)
+            }
+            SensitiveOperation::Export { key_id } => {
+                let record = self.record(key_id)?;
+                record.metadata.status.check(key_id, KeyOperation::Export)?;
+                Ok(Some(record.secret))
+            }
+            SensitiveOperation::Delete { key_id } => self.delete_key(key_id).map(|_| None),
+            SensitiveOperation::Snapshot { .. }
+            | SensitiveOperation::ExportBackup { .. }
+            | SensitiveOperation::RestoreBackup { .. } => Err(KmsError::OperationNotPermitted(format!(
+                "{} is not executed from the queue",
+                operation.action()
+            ))),
+        }
+    }
+
+    // Pending request `request_id`; an expired one is dropped and audited
+    fn pending(&mut self, request_id: &str) -> Result<PendingRequest, KmsError> {
+        let request = self.approvals.pending.get(request_id).cloned()
+            .ok_or_else(|| KmsError::RequestNotFound(request_id.to_string()))?;
+        if !request.is_expired(now()) {
+            return Ok(request);
+        }
+        self.approvals.pending.remove(request_id);
+        if let Some(log) = &self.audit {
+            let detail = Some(format!("request {} expired", request.id));
+            log.record(&request.initiator, request.operation.key_id(), AuditOperation::Request, AuditOutcome::Failed, detail)?;
+        }
+        Err(KmsError::RequestExpired(request_id.to_string()))
+    }
+}
+
+// Names a KEK by id and public key, so an approval cannot be reused for
+// another key that was given the same id
+fn kek_fingerprint(kek: &KekPublicKey) -> String {
+    format!("{}:{}", kek.key_id(), hex::encode(Sha3_256::digest(kek.as_bytes())))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::access::administrator_policy;
+    use crate::crypto::kms::{Role, TransitionReason};
+    use crate::crypto::secure::KeyEncryptionKey;
+    use crate::crypto::signature::MlDsaParameterSet;
+    use crate::kms::{AuditLog, AuditRecord};
+    use std::sync::Arc;
+
+    struct Officer {
+        caller: CallerContext,
+        key: MlDsaSigningKey,
+    }
+
+    impl Officer {
+        fn decide(&self, request: &PendingRequest, decision: ApprovalDecision) -> Approval {
+            Approval::sign(request, &self.caller.principal, &self.key, decision).unwrap()
+        }
+    }
+
+    // KMS with the key `settlement` and three security officers who may
+    // destroy, delete, export, transition and back up any key
+    fn officers_kms(quorum: usize) -> (KeyManagementSystem, Vec<Officer>) {
+        let actions = [KmsAction::Destroy, KmsAction::Delete, KmsAction::Export, KmsAction::Transition, KmsAction::Backup];
+        let mut policy = administrator_policy("kms-admin")
+            .with_role(Role::new("security-officer").allow("*", actions));
+        let mut officers = Vec::new();
+        for name in ["alice", "bob", "carol"] {
+            policy = policy.assign(name, "security-officer");
+            officers.push(Officer {
+                caller: CallerContext::new(name),
+                key: MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65),
+            });
+        }
+
+        let mut kms = KeyManagementSystem::new();
+        kms.set_access_policy(policy);
+        kms.set_approval_policy(ApprovalPolicy::new(quorum, DEFAULT_APPROVAL_TTL));
+        for officer in &officers {
+            kms.trust_approver(&officer.caller.principal, officer.key.verifying_key().clone()).unwrap();
+        }
+        kms.add_secret(&CallerContext::new("kms-admin"), "settlement", SecureSecret::from_bytes(&[7u8; 32])).unwrap();
+        (kms, officers)
+    }
+
+    #[test]
+    fn test_single_operator_cannot_destroy_or_export() {
+        let (mut kms, officers) = officers_kms(1);
+        let alice = &officers[0];
+
+        let err = kms.transition_key(&alice.caller, "settlement", KeyState::Destroyed, TransitionReason::Unspecified).unwrap_err();
+        assert_eq!(err.code().name, "KMS_APPROVAL_REQUIRED");
+        assert!(matches!(
+            kms.get_secret(&alice.caller, "settlement", KeyOperation::Export),
+            Err(KmsError::ApprovalRequired(_))
+        ));
+        assert!(matches!(kms.remove_secret(&alice.caller, "settlement"), Err(KmsError::ApprovalRequired(_))));
+        let kek = KeyEncryptionKey::generate("alice-kek");
+        assert!(matches!(kms.snapshot(&alice.caller, kek.public_key()), Err(KmsError::ApprovalRequired(_))));
+
+        // Nor by approving their own request or presenting someone else's decision
+        let request = kms.request_operation(&alice.caller, SensitiveOperation::Export { key_id: "settlement".to_string() }).unwrap();
+        let own = alice.decide(&request, ApprovalDecision::Approve);
+        assert!(matches!(kms.approve(&alice.caller, own), Err(KmsError::ApprovalRejected(_))));
+        let bobs = officers[1].decide(&request, ApprovalDecision::Approve);
+        assert!(matches!(kms.approve(&officers[2].caller, bobs), Err(KmsError::ApprovalRejected(_))));
+        assert!(matches!(kms.execute_request(&alice.caller, &request.id), Err(KmsError::ApprovalRequired(_))));
+
+        // Approvers must be allowed to export themselves
+        assert!(matches!(
+            kms.approve(&CallerContext::new("kms-auditor"), officers[1].decide(&request, ApprovalDecision::Approve)),
+            Err(KmsError::AccessDenied(_))
+        ));
+        assert_eq!(kms.key_state(&CallerContext::new("kms-admin"), "settlement").unwrap(), KeyState::Active);
+    }
+
+    #[test]
+    fn test_quorum_approval_executes_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kms-approval.jsonl");
+        let (mut kms, officers) = officers_kms(2);
+        kms.set_audit_log(Arc::new(AuditLog::open(&path).unwrap()));
+        let (alice, bob, carol) = (&officers[0], &officers[1], &officers[2]);
+
+        let operation = SensitiveOperation::Destroy { key_id: "settlement".to_string(), reason: TransitionReason::CessationOfOperation };
+        let request = kms.request_operation(&alice.caller, operation).unwrap();
+        assert_eq!(
+            kms.approve(&bob.caller, bob.decide(&request, ApprovalDecision::Approve)).unwrap(),
+            RequestStatus::Pending { approvals: 1, quorum: 2 }
+        );
+        assert!(matches!(
+            kms.approve(&bob.caller, bob.decide(&request, ApprovalDecision::Approve)),
+            Err(KmsError::ApprovalRejected(_))
+        ));
+        assert_eq!(kms.approve(&carol.caller, carol.decide(&request, ApprovalDecision::Approve)).unwrap(), RequestStatus::Approved);
+
+        // Only the initiator executes, only once, and only what the lifecycle allows
+        assert!(kms.execute_request(&bob.caller, &request.id).is_err());
+        assert!(matches!(kms.execute_request(&alice.caller, &request.id), Err(KmsError::InvalidStateTransition(_))));
+        kms.transition_key(&CallerContext::new("kms-admin"), "settlement", KeyState::Deactivated, TransitionReason::CessationOfOperation).unwrap();
+        assert_eq!(kms.execute_request(&alice.caller, &request.id).unwrap(), None);
+        assert!(matches!(kms.execute_request(&alice.caller, &request.id), Err(KmsError::RequestNotFound(_))));
+        assert_eq!(kms.key_state(&CallerContext::new("kms-admin"), "settlement").unwrap(), KeyState::Destroyed);
+
+        // The request and its signed approvals can be re-verified from the trail alone
+        let entries: Vec<_> = std::fs::read_to_string(&path).unwrap()
+            .lines()
+            .filter_map(|line| match serde_json::from_str(line).unwrap() {
+                AuditRecord::Entry(entry) if entry.outcome == AuditOutcome::Success => Some(entry),
+                _ => None,
+            })
+            .collect();
+        let mut filed: PendingRequest = entries.iter()
+            .find(|entry| entry.operation == AuditOperation::Request)
+            .and_then(|entry| serde_json::from_str(entry.detail.as_deref()?).ok())
+            .unwrap();
+        filed.approvals = entries.iter()
+            .filter(|entry| entry.operation == AuditOperation::Approve)
+            .map(|entry| serde_json::from_str(entry.detail.as_deref().unwrap()).unwrap())
+            .collect();
+        assert_eq!(filed.approvers().collect::<Vec<_>>(), ["bob", "carol"]);
+        verify_approvals(&filed, kms.approvers()).unwrap();
+        assert_eq!(entries.last().unwrap().operation, AuditOperation::Destroy);
+        assert_eq!(entries.last().unwrap().actor, "alice");
+
+        filed.approvals[1].at += 1;
+        assert!(verify_approvals(&filed, kms.approvers()).is_err());
+    }
+
+    #[test]
+    fn test_rejected_and_expired_requests_are_dropped() {
+        let (mut kms, officers) = officers_kms(1);
+        let (alice, bob) = (&officers[0], &officers[1]);
+
+        let export = SensitiveOperation::Export { key_id: "settlement".to_string() };
+        let request = kms.request_operation(&alice.caller, export.clone()).unwrap();
+        assert_eq!(kms.approve(&bob.caller, bob.decide(&request, ApprovalDecision::Reject)).unwrap(), RequestStatus::Rejected);
+        assert!(matches!(kms.execute_request(&alice.caller, &request.id), Err(KmsError::RequestNotFound(_))));
+
+        kms.set_approval_policy(ApprovalPolicy::new(1, Duration::ZERO));
+        let request = kms.request_operation(&alice.caller, export.clone()).unwrap();
+        let err = kms.approve(&bob.caller, bob.decide(&request, ApprovalDecision::Approve)).unwrap_err();
+        assert_eq!(err.code().name, "KMS_REQUEST_EXPIRED");
+        assert!(kms.pending_requests(&CallerContext::new("kms-admin")).is_empty());
+
+        kms.set_approval_policy(ApprovalPolicy::default());
+        let request = kms.request_operation(&alice.caller, export).unwrap();
+        kms.approve(&bob.caller, bob.decide(&request, ApprovalDecision::Approve)).unwrap();
+        assert_eq!(
+            kms.execute_request(&alice.caller, &request.id).unwrap(),
+            Some(SecureSecret::from_bytes(&[7u8; 32]))
+        );
+    }
+
+    #[test]
+    fn test_snapshot_and_delete_need_approval() {
+        let (mut kms, officers) = officers_kms(1);
+        let (alice, bob) = (&officers[0], &officers[1]);
+        let kek = KeyEncryptionKey::generate("offsite");
+
+        // The approval names the KEK; another key with the same id does not match it
+        let request = kms.request_operation(&alice.caller, SensitiveOperation::snapshot(kek.public_key())).unwrap();
+        assert_eq!(request.operation.key_id(), "*");
+        kms.approve(&bob.caller, bob.decide(&request, ApprovalDecision::Approve)).unwrap();
+        assert!(matches!(kms.execute_request(&alice.caller, &request.id), Err(KmsError::OperationNotPermitted(_))));
+        let impostor = KeyEncryptionKey::generate("offsite");
+        assert!(matches!(kms.snapshot(&alice.caller, impostor.public_key()), Err(KmsError::ApprovalRequired(_))));
+        assert!(matches!(kms.snapshot(&bob.caller, kek.public_key()), Err(KmsError::ApprovalRequired(_))));
+        let snapshot = kms.snapshot(&alice.caller, kek.public_key()).unwrap();
+        assert_eq!(snapshot.secrets.len(), 1);
+        assert!(matches!(kms.snapshot(&alice.caller, kek.public_key()), Err(KmsError::ApprovalRequired(_))));
+
+        let delete = SensitiveOperation::Delete { key_id: "settlement".to_string() };
+        let request = kms.request_operation(&alice.caller, delete).unwrap();
+        kms.approve(&bob.caller, bob.decide(&request, ApprovalDecision::Approve)).unwrap();
+        assert_eq!(kms.execute_request(&alice.caller, &request.id).unwrap(), None);
+        assert!(matches!(kms.key_state(&CallerContext::new("kms-admin"), "settlement"), Err(KmsError::KeyNotFound(_))));
+    }
+}
@@ -0,0 +1,554 @@
+//! Signed backup bundles for moving keys to a disaster-recovery site.
+//!
+//! A [`BackupBundle`] carries every selected key sealed to each of one or
+//! more recovery Kyber public keys, together with its metadata and
+//! lifecycle history. Its [`BackupManifest`] lists the SHA3-256 digest of
+//! every entry and the audit-log head at the time of export, and is signed
+//! with the exporting site's ML-DSA key. Sealing purposes include the bundle
+//! id, so entries cannot be moved between bundles either.
+//!
+//! Restoring checks the manifest signature against trusted signers and
+//! refuses a bundle with missing, extra or altered entries before anything
+//! is written. Its keys and the new backup watermark are then written in a
+//! single store batch. It also refuses to go back in time, unless explicitly told
+//! to: a bundle older than the last one restored, or one that would undo a
+//! key's lifecycle transitions, e.g. revive a compromised key.
+//!
+//! Under a two-person policy an export, a restore and a restore with the
+//! downgrade override each need an approved
+//! [`SensitiveOperation`](super::approval::SensitiveOperation) naming the
+//! exact recovery keys or bundle; the call is then repeated by its initiator.
+
+use super::access::{CallerContext, KeyPattern, KmsAction};
+use super::approval::SensitiveOperation;
+use super::KeyManagementSystem;
+use crate::crypto::secure::{KekPublicKey, KeyEncryptionKey, SealedSecret};
+use crate::crypto::signature::{DetachedSignature, MlDsaSigningKey, SigningMode, TrustedSigners};
+use crate::error::KmsError;
+use crate::kms::audit::AuditOperation;
+use crate::kms::store::{KeyMetadata, KeyRecord};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// ML-DSA context string of manifest signatures
+pub const BACKUP_CONTEXT: &[u8] = b"pqc-kyber/kms/backup/v1";
+const BACKUP_FORMAT_VERSION: u8 = 1;
+const BACKUP_PURPOSE_PREFIX: &str = "kms/backup/";
+const BUNDLE_ID_LENGTH: usize = 16;
+
+/// Keys a backup covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupScope {
+    All,
+    Matching(KeyPattern),
+}
+
+/// Last audit-log entry when the bundle was exported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditHead {
+    pub sequence: u64,
+    /// Hex hash of the entry
+    pub hash: String,
+}
+
+/// Signed table of contents of a bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u8,
+    pub bundle_id: String,
+    pub created_at: u64,
+    /// Key pattern of a filtered backup; `None` for a full one
+    pub scope: Option<KeyPattern>,
+    /// Ids of the KEKs every entry is sealed to
+    pub recovery_keys: Vec<String>,
+    /// Hex SHA3-256 of each entry by key id
+    pub entries: BTreeMap<String, String>,
+    pub audit_head: Option<AuditHead>,
+}
+
+/// One key: its metadata and its material sealed to each recovery key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub metadata: KeyMetadata,
+    /// Sealed secret by recovery KEK id
+    pub sealed: BTreeMap<String, SealedSecret>,
+}
+
+/// Backup of a KMS, safe to write to disk and ship
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupBundle {
+    pub manifest: BackupManifest,
+    pub signature: DetachedSignature,
+    pub entries: BTreeMap<String, BackupEntry>,
+}
+
+/// How far a restore may deviate from the safe default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RestoreOptions {
+    /// Restore even an older bundle, or one that rolls key states back
+    pub allow_downgrade: bool,
+}
+
+impl BackupScope {
+    /// Pattern of the keys covered, `*` for all
+    pub fn pattern(&self) -> KeyPattern {
+        match self {
+            BackupScope::All => KeyPattern::new("*"),
+            BackupScope::Matching(pattern) => pattern.clone(),
+        }
+    }
+}
+
+impl BackupManifest {
+    /// Hex SHA3-256 of the encoded manifest, naming the bundle in approvals
+    pub fn digest(&self) -> Result<String, KmsError> {
+        let encoded = serde_json::to_vec(self)
+            .map_err(|e| KmsError::Storage(format!("encode backup manifest: {}", e)))?;
+        Ok(hex::encode(Sha3_256::digest(&encoded)))
+    }
+}
+
+impl BackupEntry {
+    fn digest(&self) -> Result<String, KmsError> {
+        let encoded = serde_json::to_vec(self)
+            .map_err(|e| KmsError::Storage(format!("encode backup entry: {}", e)))?;
+        Ok(hex::encode(Sha3_256::digest(&encoded)))
+    }
+}
+
+impl BackupBundle {
+    /// Checks the manifest signature and that the bundle holds exactly the
+    /// entries the manifest lists, unaltered and sealed to every recovery key
+    pub fn verify(&self, signers: &TrustedSigners) -> Result<(), KmsError> {
+        let manifest = &self.manifest;
+        if manifest.version != BACKUP_FORMAT_VERSION {
+            return Err(KmsError::IntegrityCheckFailed(format!("unsupported backup version {}", manifest.version)));
+        }
+        let signed = serde_json::to_vec(manifest)
+            .map_err(|e| KmsError::Storage(format!("encode backup manifest: {}", e)))?;
+        signers
+            .verify(&self.signature, &signed, BACKUP_CONTEXT)
+            .map_err(|e| KmsError::IntegrityCheckFailed(format!("backup {} manifest: {}", manifest.bundle_id, e)))?;
+
+        let listed: BTreeSet<&String> = manifest.entries.keys().collect();
+        let present: BTreeSet<&String> = self.entries.keys().collect();
+        if let Some(missing) = listed.difference(&present).next() {
+            return Err(KmsError::IntegrityCheckFailed(format!(
+                "partial backup {}: key {} is missing",
+                manifest.bundle_id, missing
+            )));
+        }
+        if let Some(extra) = present.difference(&listed).next() {
+            return Err(KmsError::IntegrityCheckFailed(format!(
+                "backup {} holds key {} not in its manifest",
+                manifest.bundle_id, extra
+            )));
+        }
+        for (key_id, entry) in &self.entries {
+            if entry.digest()? != manifest.entries[key_id] {
+                return Err(KmsError::IntegrityCheckFailed(format!("backup entry {} was altered", key_id)));
+            }
+            if manifest.recovery_keys.iter().any(|kek_id| !entry.sealed.contains_key(kek_id)) {
+                return Err(KmsError::IntegrityCheckFailed(format!(
+                    "backup entry {} is not sealed to every recovery key",
+                    key_id
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl KeyManagementSystem {
+    /// Exports the keys in `scope`, in every state, sealed to each of
+    /// `recovery_keys`, and signs the manifest as `signer_id`. The caller
+    /// needs Backup on every key exported and, under a two-person policy, an
+    /// approved [`SensitiveOperation::export_backup`] for the same arguments.
+    pub fn export_backup(
+        &mut self,
+        caller: &CallerContext,
+        scope: &BackupScope,
+        recovery_keys: &[KekPublicKey],
+        signer_id: &str,
+        signing_key: &MlDsaSigningKey,
+    ) -> Result<BackupBundle, KmsError> {
+        if recovery_keys.is_empty() {
+            return Err(KmsError::OperationNotPermitted("a backup needs at least one recovery key".to_string()));
+        }
+        let key_ids: Vec<String> = self
+            .store
+            .key_ids()?
+            .into_iter()
+            .filter(|key_id| match scope {
+                BackupScope::All => true,
+                BackupScope::Matching(pattern) => pattern.matches(key_id),
+            })
+            .collect();
+        for key_id in &key_ids {
+            self.authorize(caller, KmsAction::Backup, key_id)?;
+        }
+        let operation = SensitiveOperation::export_backup(scope, recovery_keys);
+        let request = self.approved_request(caller, &operation)
+            .or_else(|e| self.audited(caller, AuditOperation::Backup, operation.key_id(), None, Err(e)))?;
+
+        let bundle_id = hex::encode(rand::random::<[u8; BUNDLE_ID_LENGTH]>());
+        let audit_head = match &self.audit {
+            Some(log) => log.head().map(|(sequence, hash)| Some(AuditHead { sequence, hash }))?,
+            None => None,
+        };
+        let mut entries = BTreeMap::new();
+        for key_id in key_ids {
+            let purpose = backup_purpose(&bundle_id, &key_id);
+            let entry = self.record(&key_id).and_then(|record| {
+                let sealed = recovery_keys
+                    .iter()
+                    .map(|kek| {
+                        let sealed = record.secret.seal(kek, &purpose).map_err(|_| KmsError::SealFailed(key_id.clone()))?;
+                        Ok((kek.key_id().to_string(), sealed))
+                    })
+                    .collect::<Result<_, KmsError>>()?;
+                Ok(BackupEntry { metadata: record.metadata, sealed })
+            });
+            let detail = Some(bundle_detail(&bundle_id, request.as_deref()));
+            entries.insert(key_id.clone(), self.audited(caller, AuditOperation::Backup, &key_id, detail, entry)?);
+        }
+
+        let manifest = BackupManifest {
+            version: BACKUP_FORMAT_VERSION,
+            bundle_id,
+            created_at: now(),
+            scope: match scope {
+                BackupScope::All => None,
+                BackupScope::Matching(pattern) => Some(pattern.clone()),
+            },
+            recovery_keys: recovery_keys.iter().map(|kek| kek.key_id().to_string()).collect(),
+            entries: entries
+                .iter()
+                .map(|(key_id, entry)| Ok((key_id.clone(), entry.digest()?)))
+                .collect::<Result<_, KmsError>>()?,
+            audit_head,
+        };
+        let signed = serde_json::to_vec(&manifest)
+            .map_err(|e| KmsError::Storage(format!("encode backup manifest: {}", e)))?;
+        let signature = DetachedSignature::create(signer_id, signing_key, &signed, BACKUP_CONTEXT, SigningMode::Hedged)
+            .map_err(|e| KmsError::SealFailed(format!("backup manifest: {}", e)))?;
+        self.complete_request(request.as_deref());
+        Ok(BackupBundle { manifest, signature, entries })
+    }
+
+    /// Verifies `bundle` and writes its keys, replacing existing ones with
+    /// the same id. Every entry is unsealed with `recovery_key` first and
+    /// all of them are written in one store batch together with the new
+    /// watermark, so a bundle is restored completely or not at all.
+    /// The caller needs Restore on every key in the bundle and, under a
+    /// two-person policy, an approved [`SensitiveOperation::restore_backup`]
+    /// for this bundle and these options.
+    pub fn restore_backup(
+        &mut self,
+        caller: &CallerContext,
+        bundle: &BackupBundle,
+        recovery_key: &KeyEncryptionKey,
+        signers: &TrustedSigners,
+        options: RestoreOptions,
+    ) -> Result<Vec<String>, KmsError> {
+        for key_id in bundle.entries.keys() {
+            self.authorize(caller, KmsAction::Restore, key_id)?;
+        }
+        bundle.verify(signers)?;
+        let manifest = &bundle.manifest;
+        let operation = SensitiveOperation::restore_backup(bundle, options)?;
+        let request = self.approved_request(caller, &operation)
+            .or_else(|e| self.audited(caller, AuditOperation::Restore, operation.key_id(), None, Err(e)))?;
+        if !options.allow_downgrade {
+            self.check_not_downgrade(bundle)?;
+        }
+
+        let kek_id = recovery_key.key_id();
+        let mut records = Vec::with_capacity(bundle.entries.len());
+        for (key_id, entry) in &bundle.entries {
+            let sealed = entry.sealed.get(kek_id).ok_or_else(|| {
+                KmsError::UnsealFailed(format!("{}: not sealed to recovery key {}", key_id, kek_id))
+            })?;
+            let secret = recovery_key
+                .unseal(sealed, &backup_purpose(&manifest.bundle_id, key_id))
+                .map_err(|_| KmsError::UnsealFailed(key_id.clone()))?;
+            records.push(KeyRecord::with_metadata(key_id, secret, entry.metadata.clone()));
+        }
+
+        let restored: Vec<String> = records.iter().map(|record| record.key_id.clone()).collect();
+        let watermark = self.store.backup_watermark()?.max(Some(manifest.created_at));
+        let detail = Some(bundle_detail(&manifest.bundle_id, request.as_deref()));
+        if let Err(e) = self.store.put_batch(records, watermark) {
+            return self.audited(caller, AuditOperation::Restore, operation.key_id(), detail, Err(e));
+        }
+        for key_id in &restored {
+            self.audited(caller, AuditOperation::Restore, key_id, detail.clone(), Ok::<_, KmsError>(()))?;
+        }
+        self.complete_request(request.as_deref());
+        Ok(restored)
+    }
+
+    /// Export time of the newest bundle restored; older ones are refused.
+    /// Kept by the store, so it survives a restart.
+    pub fn backup_watermark(&self) -> Result<Option<u64>, KmsError> {
+        self.store.backup_watermark()
+    }
+
+    /// Carries the watermark over from another recovery store
+    pub fn set_backup_watermark(&mut self, created_at: u64) -> Result<(), KmsError> {
+        self.store.put_batch(Vec::new(), Some(created_at))
+    }
+
+    fn check_not_downgrade(&self, bundle: &BackupBundle) -> Result<(), KmsError> {
+        let manifest = &bundle.manifest;
+        if let Some(watermark) = self.store.backup_watermark()?.filter(|watermark| manifest.created_at < *watermark) {
+            return Err(KmsError::BackupDowngrade(format!(
+                "backup {} was exported at {}, before the restored one from {}",
+                manifest.bundle_id, manifest.created_at, watermark
+            )));
+        }
+        for (key_id, entry) in &bundle.entries {
+            let Some(current) = self.store.get(key_id)? else {
+                continue;
+            };
+            let history = &entry.metadata.transitions;
+            if !history.starts_with(&current.metadata.transitions) {
+                return Err(KmsError::BackupDowngrade(format!(
+                    "backup {} would roll key {} back from {} to {}",
+                    manifest.bundle_id, key_id, current.metadata.status, entry.metadata.status
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn backup_purpose(bundle_id: &str, key_id: &str) -> String {
+    format!("{}{}/{}", BACKUP_PURPOSE_PREFIX, bundle_id, key_id)
+}
+
+fn bundle_detail(bundle_id: &str, request_id: Option<&str>) -> String {
+    match request_id {
+        Some(request_id) => format!("bundle {}, request {}", bundle_id, request_id),
+        None => format!("bundle {}", bundle_id),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::access::{administrator_policy, test_kms, Role};
+    use super::super::approval::{Approval, ApprovalDecision, ApprovalPolicy};
+    use super::super::lifecycle::{KeyState, TransitionReason};
+    use crate::crypto::secure::SecureSecret;
+    use crate::crypto::signature::MlDsaParameterSet;
+    use crate::kms::store::FileKeyStore;
+    use crate::kms::AuditLog;
+    use std::sync::Arc;
+
+    struct Site {
+        signing_key: MlDsaSigningKey,
+        signers: TrustedSigners,
+        recovery: KeyEncryptionKey,
+        offsite: KeyEncryptionKey,
+    }
+
+    fn site() -> Site {
+        let signing_key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let mut signers = TrustedSigners::new();
+        signers.add_signer("primary", signing_key.verifying_key().clone()).unwrap();
+        Site {
+            signing_key,
+            signers,
+            recovery: KeyEncryptionKey::generate("recovery-1"),
+            offsite: KeyEncryptionKey::generate("recovery-offsite"),
+        }
+    }
+
+    fn export(kms: &mut KeyManagementSystem, site: &Site, scope: &BackupScope) -> BackupBundle {
+        let caller = CallerContext::new("kms-admin");
+        let keys = [site.recovery.public_key().clone(), site.offsite.public_key().clone()];
+        kms.export_backup(&caller, scope, &keys, "primary", &site.signing_key).unwrap()
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let site = site();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kms-backup.jsonl");
+        let (mut primary, admin) = test_kms();
+        primary.set_audit_log(Arc::new(AuditLog::open(&path).unwrap()));
+        primary.add_secret(&admin, "settlement", SecureSecret::from_bytes(&[1u8; 32])).unwrap();
+        primary.add_secret(&admin, "reporting", SecureSecret::from_bytes(&[2u8; 32])).unwrap();
+        primary.add_secret(&admin, "hr:payroll", SecureSecret::from_bytes(&[3u8; 32])).unwrap();
+        primary.transition_key(&admin, "reporting", KeyState::Deactivated, TransitionReason::Superseded).unwrap();
+
+        let bundle = export(&mut primary, &site, &BackupScope::All);
+        // Four operations and three authorized backups, each recorded as started
+        assert_eq!(bundle.manifest.audit_head.as_ref().unwrap().sequence, 11);
+        let json = serde_json::to_string(&bundle).unwrap();
+        let bundle: BackupBundle = serde_json::from_str(&json).unwrap();
+
+        // Either recovery key opens it
+        for recovery in [&site.recovery, &site.offsite] {
+            let (mut standby, admin) = test_kms();
+            let restored = standby.restore_backup(&admin, &bundle, recovery, &site.signers, RestoreOptions::default()).unwrap();
+            assert_eq!(restored, ["hr:payroll", "reporting", "settlement"]);
+            assert_eq!(standby.key_state(&admin, "reporting").unwrap(), KeyState::Deactivated);
+            let secret = standby.get_secret(&admin, "settlement", crate::crypto::kms::KeyOperation::Export).unwrap().unwrap();
+            assert!(secret.constant_time_eq(&SecureSecret::from_bytes(&[1u8; 32])));
+            assert_eq!(standby.backup_watermark().unwrap(), Some(bundle.manifest.created_at));
+        }
+
+        let filtered = export(&mut primary, &site, &BackupScope::Matching(KeyPattern::new("hr:*")));
+        assert_eq!(filtered.entries.keys().collect::<Vec<_>>(), ["hr:payroll"]);
+    }
+
+    #[test]
+    fn test_tampered_bundles_refused() {
+        let site = site();
+        let (mut primary, admin) = test_kms();
+        primary.add_secret(&admin, "settlement", SecureSecret::from_bytes(&[1u8; 32])).unwrap();
+        primary.add_secret(&admin, "reporting", SecureSecret::from_bytes(&[2u8; 32])).unwrap();
+        let bundle = export(&mut primary, &site, &BackupScope::All);
+        let (mut standby, admin) = test_kms();
+        let mut restore = |bundle: &BackupBundle| {
+            standby.restore_backup(&admin, bundle, &site.recovery, &site.signers, RestoreOptions::default())
+        };
+
+        let mut partial = bundle.clone();
+        partial.entries.remove("reporting");
+        assert!(matches!(restore(&partial), Err(KmsError::IntegrityCheckFailed(m)) if m.contains("partial")));
+
+        // Editing an entry breaks its digest
+        let mut altered = bundle.clone();
+        altered.entries.get_mut("reporting").unwrap().metadata.owner = Some("mallory".to_string());
+        assert!(matches!(restore(&altered), Err(KmsError::IntegrityCheckFailed(_))));
+
+        // Dropping an entry from the manifest as well breaks the signature
+        let mut trimmed = partial.clone();
+        trimmed.manifest.entries.remove("reporting");
+        assert!(matches!(restore(&trimmed), Err(KmsError::IntegrityCheckFailed(_))));
+
+        // Signed by a key the standby does not trust
+        let stranger = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let keys = [site.recovery.public_key().clone()];
+        let forged = primary.export_backup(&CallerContext::new("kms-admin"), &BackupScope::All, &keys, "primary", &stranger).unwrap();
+        assert!(matches!(restore(&forged), Err(KmsError::IntegrityCheckFailed(_))));
+
+        assert!(restore(&bundle).is_ok());
+    }
+
+    #[test]
+    fn test_downgrade_needs_override() {
+        let site = site();
+        let (mut primary, admin) = test_kms();
+        primary.add_secret(&admin, "settlement", SecureSecret::from_bytes(&[1u8; 32])).unwrap();
+        let before = export(&mut primary, &site, &BackupScope::All);
+        primary.transition_key(&admin, "settlement", KeyState::Compromised, TransitionReason::KeyCompromise).unwrap();
+        let after = export(&mut primary, &site, &BackupScope::All);
+
+        let (mut standby, admin) = test_kms();
+        let options = RestoreOptions::default();
+        standby.restore_backup(&admin, &after, &site.recovery, &site.signers, options).unwrap();
+
+        // The older bundle would make the compromised key usable again
+        let err = standby.restore_backup(&admin, &before, &site.recovery, &site.signers, options).unwrap_err();
+        assert!(matches!(err, KmsError::BackupDowngrade(_)));
+        assert_eq!(standby.key_state(&admin, "settlement").unwrap(), KeyState::Compromised);
+
+        standby.set_backup_watermark(after.manifest.created_at + 1).unwrap();
+        let err = standby.restore_backup(&admin, &after, &site.recovery, &site.signers, options).unwrap_err();
+        assert!(matches!(err, KmsError::BackupDowngrade(_)));
+
+        let allow = RestoreOptions { allow_downgrade: true };
+        standby.restore_backup(&admin, &before, &site.recovery, &site.signers, allow).unwrap();
+        assert_eq!(standby.key_state(&admin, "settlement").unwrap(), KeyState::Active);
+    }
+
+    #[test]
+    fn test_watermark_survives_restart() {
+        let site = site();
+        let (mut primary, admin) = test_kms();
+        primary.add_secret(&admin, "settlement", SecureSecret::from_bytes(&[1u8; 32])).unwrap();
+        let bundle = export(&mut primary, &site, &BackupScope::All);
+
+        let dir = tempfile::tempdir().unwrap();
+        let master_key = SecureSecret::from_bytes(&[0x33u8; 32]);
+        let open = || {
+            let mut kms = KeyManagementSystem::with_store(Box::new(FileKeyStore::open(dir.path(), &master_key).unwrap()));
+            kms.set_access_policy(administrator_policy("kms-admin"));
+            kms.set_approval_policy(ApprovalPolicy::default().exempt("kms-admin"));
+            kms
+        };
+        let mut standby = open();
+        standby.restore_backup(&admin, &bundle, &site.recovery, &site.signers, RestoreOptions::default()).unwrap();
+        drop(standby);
+
+        let standby = open();
+        assert_eq!(standby.backup_watermark().unwrap(), Some(bundle.manifest.created_at));
+        assert_eq!(standby.key_state(&admin, "settlement").unwrap(), KeyState::Active);
+    }
+
+    #[test]
+    fn test_export_and_restore_need_approval() {
+        let site = site();
+        let (mut primary, admin) = test_kms();
+        primary.add_secret(&admin, "settlement", SecureSecret::from_bytes(&[1u8; 32])).unwrap();
+        let before = export(&mut primary, &site, &BackupScope::All);
+        primary.transition_key(&admin, "settlement", KeyState::Compromised, TransitionReason::KeyCompromise).unwrap();
+        let after = export(&mut primary, &site, &BackupScope::All);
+
+        let (alice, bob) = (CallerContext::new("alice"), CallerContext::new("bob"));
+        let bob_key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let policy = administrator_policy("kms-admin")
+            .with_role(Role::new("recovery-officer").allow("*", [KmsAction::Backup, KmsAction::Restore]))
+            .assign("alice", "recovery-officer")
+            .assign("bob", "recovery-officer");
+        let mut standby = KeyManagementSystem::new();
+        standby.set_access_policy(policy);
+        standby.set_approval_policy(ApprovalPolicy::default());
+        standby.trust_approver("bob", bob_key.verifying_key().clone()).unwrap();
+        let approve = |kms: &mut KeyManagementSystem, operation| {
+            let request = kms.request_operation(&alice, operation).unwrap();
+            let approval = Approval::sign(&request, "bob", &bob_key, ApprovalDecision::Approve).unwrap();
+            kms.approve(&bob, approval).unwrap();
+        };
+        let restore = |kms: &mut KeyManagementSystem, bundle, options| {
+            kms.restore_backup(&alice, bundle, &site.recovery, &site.signers, options)
+        };
+
+        let options = RestoreOptions::default();
+        assert!(matches!(restore(&mut standby, &after, options), Err(KmsError::ApprovalRequired(_))));
+        approve(&mut standby, SensitiveOperation::restore_backup(&after, options).unwrap());
+        assert_eq!(restore(&mut standby, &after, options).unwrap(), ["settlement"]);
+        assert!(matches!(restore(&mut standby, &after, options), Err(KmsError::ApprovalRequired(_))));
+
+        // Approving a plain restore does not approve reviving the compromised key
+        let allow = RestoreOptions { allow_downgrade: true };
+        approve(&mut standby, SensitiveOperation::restore_backup(&before, options).unwrap());
+        assert!(matches!(restore(&mut standby, &before, allow), Err(KmsError::ApprovalRequired(_))));
+        assert!(matches!(restore(&mut standby, &before, options), Err(KmsError::BackupDowngrade(_))));
+        approve(&mut standby, SensitiveOperation::restore_backup(&before, allow).unwrap());
+        restore(&mut standby, &before, allow).unwrap();
+
+        // An export approval names the recovery keys it is sealed to
+        let keys = [site.recovery.public_key().clone()];
+        let offsite = [site.offsite.public_key().clone()];
+        approve(&mut standby, SensitiveOperation::export_backup(&BackupScope::All, &keys));
+        let export_to = |kms: &mut KeyManagementSystem, keys: &[KekPublicKey]| {
+            kms.export_backup(&alice, &BackupScope::All, keys, "primary", &site.signing_key)
+        };
+        assert!(matches!(export_to(&mut standby, &offsite), Err(KmsError::ApprovalRequired(_))));
+        assert_eq!(export_to(&mut standby, &keys).unwrap().entries.len(), 1);
+    }
+}
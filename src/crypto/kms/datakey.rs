@@ -0,0 +1,294 @@
+//! Data keys for application-side envelope encryption.
+//!
+//! [`generate_data_key`](KeyManagementSystem::generate_data_key) returns a
+//! fresh symmetric key twice: in plaintext, for the application to encrypt
+//! with and then drop, and as a ciphertext blob wrapped under the current
+//! version of a KMS master key, to store next to the data. Later the blob
+//! is handed back to [`decrypt_data_key`](KeyManagementSystem::decrypt_data_key).
+//!
+//! A blob is a version-tagged KMS envelope (see [`super::envelope`]) whose
+//! associated data is a domain label followed by the encryption context, a
+//! set of non-secret key-value pairs such as a tenant or a file name. The
+//! same context must be presented to unwrap the key, so a blob copied to
+//! another tenant's record does not open. The context is written to the
+//! audit log with every call.
+
+use super::access::{CallerContext, KmsAction};
+use super::envelope::EnvelopeHeader;
+use super::version::KeyVersionId;
+use super::KeyManagementSystem;
+use crate::crypto::secure::SecureSecret;
+use crate::error::{self, KmsError};
+use crate::kms::audit::AuditOperation;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+const DATA_KEY_DOMAIN: &[u8] = b"pqc-kyber/kms/datakey/v1";
+// Resource a blob is authorized and audited against when its header does not parse
+const MALFORMED_BLOB_KEY_ID: &str = "<malformed>";
+
+/// Non-secret key-value pairs bound to a data key blob
+pub type EncryptionContext = BTreeMap<String, String>;
+
+/// Kind of data key to generate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DataKeySpec {
+    Aes128,
+    #[default]
+    Aes256,
+}
+
+/// Fresh data key and its wrapped form
+#[derive(Debug)]
+pub struct DataKey {
+    /// Master key version the blob is wrapped under
+    pub key_id: KeyVersionId,
+    pub plaintext: SecureSecret,
+    pub ciphertext_blob: Vec<u8>,
+}
+
+impl DataKeySpec {
+    pub fn key_length(&self) -> usize {
+        match self {
+            DataKeySpec::Aes128 => 16,
+            DataKeySpec::Aes256 => 32,
+        }
+    }
+}
+
+impl KeyManagementSystem {
+    /// Generates a data key and wraps it under the current version of the
+    /// versioned key `name`, bound to `context`
+    pub fn generate_data_key(
+        &mut self,
+        caller: &CallerContext,
+        name: &str,
+        spec: DataKeySpec,
+        context: &EncryptionContext,
+    ) -> error::Result<DataKey> {
+        self.authorize(caller, KmsAction::Encrypt, name)?;
+        let mut bytes = Zeroizing::new(vec![0u8; spec.key_length()]);
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let plaintext = SecureSecret::from_bytes(&bytes);
+        let result = self.wrap_data_key(name, &plaintext, context).map(|(key_id, ciphertext_blob)| DataKey {
+            key_id,
+            plaintext,
+            ciphertext_blob,
+        });
+        self.audited(caller, AuditOperation::Use, name, data_key_detail("GENERATE_DATA_KEY", context), result)
+    }
+
+    /// Wraps an existing data key under the current version of `name`
+    pub fn encrypt_data_key(
+        &mut self,
+        caller: &CallerContext,
+        name: &str,
+        plaintext: &SecureSecret,
+        context: &EncryptionContext,
+    ) -> error::Result<Vec<u8>> {
+        self.authorize(caller, KmsAction::Encrypt, name)?;
+        let result = self.wrap_data_key(name, plaintext, context).map(|(_, blob)| blob);
+        self.audited(caller, AuditOperation::Use, name, data_key_detail("ENCRYPT_DATA_KEY", context), result)
+    }
+
+    /// Unwraps a data key blob with the key version named in it. Fails
+    /// unless `context` is the one the blob was made with. A blob whose
+    /// header does not parse is authorized and audited as `<malformed>`.
+    pub fn decrypt_data_key(
+        &mut self,
+        caller: &CallerContext,
+        blob: &[u8],
+        context: &EncryptionContext,
+    ) -> error::Result<SecureSecret> {
+        let (key_id, parsed) = blob_key_id(blob);
+        self.authorize(caller, KmsAction::Decrypt, &key_id)?;
+        let result = parsed.map_err(error::Error::from).and_then(|_| self.unwrap_data_key(blob, context));
+        self.audited(caller, AuditOperation::Use, &key_id, data_key_detail("DECRYPT_DATA_KEY", context), result)
+    }
+
+    /// Moves a data key blob to the current version of `destination`, and
+    /// optionally to a new context, without the key leaving the KMS. Also
+    /// how blobs under a superseded version are brought forward.
+    ///
+    /// The unwrap and the wrap are audited as two entries, RE_ENCRYPT_FROM
+    /// and RE_ENCRYPT_TO; in between, the unwrapped key is held only in a
+    /// [`SecureSecret`] that is dropped before this returns.
+    pub fn re_encrypt(
+        &mut self,
+        caller: &CallerContext,
+        blob: &[u8],
+        source_context: &EncryptionContext,
+        destination: &str,
+        destination_context: &EncryptionContext,
+    ) -> error::Result<Vec<u8>> {
+        let (source, parsed) = blob_key_id(blob);
+        self.authorize(caller, KmsAction::Decrypt, &source)?;
+        self.authorize(caller, KmsAction::Encrypt, destination)?;
+
+        let plaintext = parsed.map_err(error::Error::from).and_then(|_| self.unwrap_data_key(blob, source_context));
+        let plaintext = self.audited(caller, AuditOperation::Use, &source, data_key_detail("RE_ENCRYPT_FROM", source_context), plaintext)?;
+        let result = self.wrap_data_key(destination, &plaintext, destination_context).map(|(_, blob)| blob);
+        self.audited(caller, AuditOperation::Use, destination, data_key_detail("RE_ENCRYPT_TO", destination_context), result)
+    }
+
+    fn wrap_data_key(
+        &mut self,
+        name: &str,
+        plaintext: &SecureSecret,
+        context: &EncryptionContext,
+    ) -> error::Result<(KeyVersionId, Vec<u8>)> {
+        let key_id = KeyVersionId::new(name, self.latest_version(name)?);
+        let blob = plaintext.with_exposed(|bytes| self.seal_envelope(key_id.clone(), bytes, &associated_data(context)))??;
+        Ok((key_id, blob))
+    }
+
+    fn unwrap_data_key(&mut self, blob: &[u8], context: &EncryptionContext) -> error::Result<SecureSecret> {
+        let (header, ciphertext) = EnvelopeHeader::parse(blob)?;
+        let header_bytes = &blob[..blob.len() - ciphertext.len()];
+        let plaintext = self.open_envelope(&header, header_bytes, ciphertext, &associated_data(context))?;
+        Ok(SecureSecret::from_bytes(&plaintext))
+    }
+}
+
+// Key version named in the blob's header, or a placeholder with the parse error
+fn blob_key_id(blob: &[u8]) -> (String, Result<(), KmsError>) {
+    match EnvelopeHeader::parse(blob) {
+        Ok((header, _)) => (header.key_id.to_string(), Ok(())),
+        Err(e) => (MALFORMED_BLOB_KEY_ID.to_string(), Err(e)),
+    }
+}
+
+// Domain label, then each pair length-prefixed in key order
+fn associated_data(context: &EncryptionContext) -> Vec<u8> {
+    let mut aad = DATA_KEY_DOMAIN.to_vec();
+    aad.extend_from_slice(&(context.len() as u32).to_be_bytes());
+    for (key, value) in context {
+        for field in [key.as_bytes(), value.as_bytes()] {
+            aad.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            aad.extend_from_slice(field);
+        }
+    }
+    aad
+}
+
+fn data_key_detail(operation: &str, context: &EncryptionContext) -> Option<String> {
+    if context.is_empty() {
+        return Some(operation.to_string());
+    }
+    let context = serde_json::to_string(context).unwrap_or_default();
+    Some(format!("{} {}", operation, context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::access::test_kms;
+    use crate::kms::{AuditLog, AuditOutcome, AuditRecord};
+    use std::sync::Arc;
+
+    fn context(tenant: &str) -> EncryptionContext {
+        EncryptionContext::from([("tenant".to_string(), tenant.to_string()), ("purpose".to_string(), "ledger".to_string())])
+    }
+
+    #[test]
+    fn test_data_key_round_trip() {
+        let (mut kms, admin) = test_kms();
+        kms.generate_versioned_kem_key(&admin, "tenants").unwrap();
+
+        let data_key = kms.generate_data_key(&admin, "tenants", DataKeySpec::Aes256, &context("acme")).unwrap();
+        assert_eq!(data_key.plaintext.len(), 32);
+        assert_eq!(data_key.key_id.to_string(), "tenants:1");
+        let unwrapped = kms.decrypt_data_key(&admin, &data_key.ciphertext_blob, &context("acme")).unwrap();
+        assert!(unwrapped.constant_time_eq(&data_key.plaintext));
+
+        // The blob is bound to its context and is not a plain envelope
+        assert!(kms.decrypt_data_key(&admin, &data_key.ciphertext_blob, &context("globex")).is_err());
+        assert!(kms.decrypt_data_key(&admin, &data_key.ciphertext_blob, &EncryptionContext::new()).is_err());
+        assert!(kms.decrypt(&admin, &data_key.ciphertext_blob, b"").is_err());
+
+        let own_key = SecureSecret::from_bytes(&[7u8; 16]);
+        let blob = kms.encrypt_data_key(&admin, "tenants", &own_key, &EncryptionContext::new()).unwrap();
+        let unwrapped = kms.decrypt_data_key(&admin, &blob, &EncryptionContext::new()).unwrap();
+        assert!(unwrapped.constant_time_eq(&own_key));
+    }
+
+    #[test]
+    fn test_malformed_blob_is_authorized_and_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kms-datakey.jsonl");
+        let (mut kms, admin) = test_kms();
+        kms.set_audit_log(Arc::new(AuditLog::open(&path).unwrap()));
+        kms.generate_versioned_kem_key(&admin, "tenants").unwrap();
+
+        let outsider = CallerContext::new("outsider");
+        assert!(matches!(
+            kms.decrypt_data_key(&outsider, b"not a blob", &context("acme")),
+            Err(error::Error::Kms(KmsError::AccessDenied(_)))
+        ));
+        assert!(matches!(
+            kms.decrypt_data_key(&admin, b"not a blob", &context("acme")),
+            Err(error::Error::Kms(KmsError::InvalidEnvelope(_)))
+        ));
+        assert!(kms.re_encrypt(&admin, b"not a blob", &context("acme"), "tenants", &context("acme")).is_err());
+
+        let outcomes: Vec<_> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .filter_map(|line| match serde_json::from_str(line).unwrap() {
+                AuditRecord::Entry(entry) if entry.key_id == MALFORMED_BLOB_KEY_ID => Some((entry.actor, entry.outcome)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("outsider".to_string(), AuditOutcome::Denied),
+                ("kms-admin".to_string(), AuditOutcome::Started),
+                ("kms-admin".to_string(), AuditOutcome::Failed),
+                ("kms-admin".to_string(), AuditOutcome::Started),
+                ("kms-admin".to_string(), AuditOutcome::Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_re_encrypt_after_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kms-datakey.jsonl");
+        let (mut kms, admin) = test_kms();
+        kms.set_audit_log(Arc::new(AuditLog::open(&path).unwrap()));
+        kms.generate_versioned_kem_key(&admin, "tenants").unwrap();
+        let data_key = kms.generate_data_key(&admin, "tenants", DataKeySpec::Aes128, &context("acme")).unwrap();
+
+        kms.rotate_version(&admin, "tenants").unwrap();
+        let moved = kms
+            .re_encrypt(&admin, &data_key.ciphertext_blob, &context("acme"), "tenants", &context("acme-eu"))
+            .unwrap();
+        assert_eq!(EnvelopeHeader::parse(&moved).unwrap().0.key_id.to_string(), "tenants:2");
+        let unwrapped = kms.decrypt_data_key(&admin, &moved, &context("acme-eu")).unwrap();
+        assert!(unwrapped.constant_time_eq(&data_key.plaintext));
+        assert!(kms
+            .re_encrypt(&admin, &data_key.ciphertext_blob, &context("globex"), "tenants", &context("acme"))
+            .is_err());
+
+        let details: Vec<String> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .filter_map(|line| match serde_json::from_str(line).unwrap() {
+                AuditRecord::Entry(entry) => entry.detail,
+                _ => None,
+            })
+            .filter(|detail| detail.contains("DATA_KEY") || detail.contains("RE_ENCRYPT"))
+            .collect();
+        // Failed calls are recorded with their error code instead
+        assert_eq!(details.len(), 4);
+        assert!(details[0].starts_with("GENERATE_DATA_KEY {"));
+        assert!(details[0].contains("\"tenant\":\"acme\""));
+        assert!(details[1].starts_with("RE_ENCRYPT_FROM"));
+        assert!(details[2].contains("\"tenant\":\"acme-eu\""));
+        assert!(details[3].starts_with("DECRYPT_DATA_KEY"));
+    }
+}
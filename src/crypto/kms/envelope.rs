@@ -0,0 +1,262 @@
+//! Version-tagged envelope encryption under versioned KMS keys.
+//!
+//! An envelope encapsulates a fresh Kyber-1024 shared secret to one version of
+//! a KMS key, derives an AES-256-GCM key from it and encrypts the payload.
+//! The `name:version` of the key is stored in the header, so the KMS can find
+//! the right version to decrypt with long after the key has been rotated.
+//!
+//! ```text
+//! "PQKE" | format (1) | u16 key id length | key id | u16 KEM ciphertext length
+//!        | KEM ciphertext | nonce (12) | AES-256-GCM ciphertext + tag
+//! ```
+//!
+//! Everything before the AES ciphertext is the [`EnvelopeHeader`]. It is
+//! authenticated as associated data, and it can be read on its own from the
+//! start of a file to find out which key version the file needs.
+
+use super::access::{CallerContext, KmsAction};
+use super::version::KeyVersionId;
+use super::KeyManagementSystem;
+use crate::crypto::secure::{register_label, Aes256Key, SecureSecret};
+use crate::error::{self, KmsError};
+use crate::kms::audit::AuditOperation;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+use std::io::{Read, Write};
+use zeroize::Zeroizing;
+
+pub const ENVELOPE_MAGIC: &[u8; 4] = b"PQKE";
+const ENVELOPE_FORMAT_VERSION: u8 = 1;
+const NONCE_LENGTH: usize = 12;
+const ENVELOPE_LABEL: &[u8] = b"kms/envelope/aes-256-gcm";
+const ENVELOPE_LABEL_OWNER: &str = "crypto::kms::envelope";
+
+/// Authenticated envelope header naming the key version that opens it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvelopeHeader {
+    pub key_id: KeyVersionId,
+    pub kem_ciphertext: Vec<u8>,
+    pub nonce: [u8; NONCE_LENGTH],
+}
+
+impl EnvelopeHeader {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let key_id = self.key_id.to_string();
+        let mut out = Vec::with_capacity(9 + key_id.len() + self.kem_ciphertext.len() + NONCE_LENGTH);
+        out.extend_from_slice(ENVELOPE_MAGIC);
+        out.push(ENVELOPE_FORMAT_VERSION);
+        out.extend_from_slice(&(key_id.len() as u16).to_be_bytes());
+        out.extend_from_slice(key_id.as_bytes());
+        out.extend_from_slice(&(self.kem_ciphertext.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.kem_ciphertext);
+        out.extend_from_slice(&self.nonce);
+        out
+    }
+
+    /// Parses the header at the start of `bytes` and returns the remaining payload
+    pub fn parse(bytes: &[u8]) -> Result<(Self, &[u8]), KmsError> {
+        let mut cursor = bytes;
+        let header = Self::read_from(&mut cursor)?;
+        Ok((header, cursor))
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), KmsError> {
+        writer
+            .write_all(&self.to_bytes())
+            .map_err(|e| KmsError::Storage(format!("Failed to write envelope header: {}", e)))
+    }
+
+    /// Reads just the header, leaving the reader at the start of the ciphertext
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, KmsError> {
+        let mut fixed = [0u8; 5];
+        read_exact(reader, &mut fixed)?;
+        if &fixed[..4] != ENVELOPE_MAGIC {
+            return Err(KmsError::InvalidEnvelope("bad magic".to_string()));
+        }
+        if fixed[4] != ENVELOPE_FORMAT_VERSION {
+            return Err(KmsError::InvalidEnvelope(format!("unsupported format version {}", fixed[4])));
+        }
+
+        let key_id = String::from_utf8(read_prefixed(reader)?)
+            .map_err(|_| KmsError::InvalidEnvelope("key id is not UTF-8".to_string()))?;
+        let kem_ciphertext = read_prefixed(reader)?;
+        let mut nonce = [0u8; NONCE_LENGTH];
+        read_exact(reader, &mut nonce)?;
+
+        Ok(Self {
+            key_id: key_id.parse()?,
+            kem_ciphertext,
+            nonce,
+        })
+    }
+}
+
+impl KeyManagementSystem {
+    /// Encrypts `plaintext` under the current version of the versioned key `name`.
+    /// `aad` is authenticated but not stored; the same value must be passed to decrypt.
+    pub fn encrypt(&mut self, caller: &CallerContext, name: &str, plaintext: &[u8], aad: &[u8]) -> error::Result<Vec<u8>> {
+        self.authorize(caller, KmsAction::Encrypt, name)?;
+        let result = self
+            .latest_version(name)
+            .map_err(error::Error::from)
+            .and_then(|version| self.seal_envelope(KeyVersionId::new(name, version), plaintext, aad));
+        self.audited(caller, AuditOperation::Use, name, Some(KmsAction::Encrypt.as_str().to_string()), result)
+    }
+
+    /// Decrypts an envelope with the key version named in its header, which
+    /// may be an older, deactivated version at or above the pinned minimum
+    pub fn decrypt(&mut self, caller: &CallerContext, envelope: &[u8], aad: &[u8]) -> error::Result<Zeroizing<Vec<u8>>> {
+        let (header, ciphertext) = EnvelopeHeader::parse(envelope)?;
+        let key_id = header.key_id.to_string();
+        self.authorize(caller, KmsAction::Decrypt, &key_id)?;
+
+        let header_bytes = &envelope[..envelope.len() - ciphertext.len()];
+        let result = self.open_envelope(&header, header_bytes, ciphertext, aad);
+        self.audited(caller, AuditOperation::Use, &key_id, Some(KmsAction::Decrypt.as_str().to_string()), result)
+    }
+
+    pub(super) fn seal_envelope(&mut self, key_id: KeyVersionId, plaintext: &[u8], aad: &[u8]) -> error::Result<Vec<u8>> {
+        let (shared, kem_ciphertext) = self.encapsulate_unaudited(&key_id.to_string(), plaintext.len() as u64)?;
+
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let header = EnvelopeHeader {
+            key_id,
+            kem_ciphertext,
+            nonce,
+        };
+
+        let mut out = header.to_bytes();
+        let ciphertext = envelope_cipher(&shared, &out)?
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &associated_data(&out, aad) })
+            .map_err(|_| KmsError::SealFailed(header.key_id.to_string()))?;
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    pub(super) fn open_envelope(
+        &mut self,
+        header: &EnvelopeHeader,
+        header_bytes: &[u8],
+        ciphertext: &[u8],
+        aad: &[u8],
+    ) -> error::Result<Zeroizing<Vec<u8>>> {
+        let shared = self.decapsulate_unaudited(&header.key_id.to_string(), &header.kem_ciphertext)?;
+        let plaintext = envelope_cipher(&shared, header_bytes)?
+            .decrypt(
+                Nonce::from_slice(&header.nonce),
+                Payload { msg: ciphertext, aad: &associated_data(header_bytes, aad) },
+            )
+            .map_err(|_| KmsError::InvalidEnvelope("authentication failed".to_string()))?;
+        Ok(Zeroizing::new(plaintext))
+    }
+}
+
+fn envelope_cipher(shared: &SecureSecret, header: &[u8]) -> Result<Aes256Gcm, KmsError> {
+    register_label(ENVELOPE_LABEL, ENVELOPE_LABEL_OWNER).map_err(|e| KmsError::SealFailed(e.to_string()))?;
+    let key = shared
+        .derive::<Aes256Key>(ENVELOPE_LABEL_OWNER, ENVELOPE_LABEL, header)
+        .map_err(|e| KmsError::SealFailed(e.to_string()))?;
+    key.with_exposed(Aes256Gcm::new_from_slice)?
+        .map_err(|_| KmsError::SealFailed("invalid envelope key length".to_string()))
+}
+
+fn associated_data(header: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(header.len() + 4 + aad.len());
+    out.extend_from_slice(header);
+    out.extend_from_slice(&(aad.len() as u32).to_be_bytes());
+    out.extend_from_slice(aad);
+    out
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), KmsError> {
+    reader
+        .read_exact(buf)
+        .map_err(|_| KmsError::InvalidEnvelope("truncated header".to_string()))
+}
+
+fn read_prefixed(reader: &mut impl Read) -> Result<Vec<u8>, KmsError> {
+    let mut length = [0u8; 2];
+    read_exact(reader, &mut length)?;
+    let mut field = vec![0u8; u16::from_be_bytes(length) as usize];
+    read_exact(reader, &mut field)?;
+    Ok(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::access::test_kms;
+    use crate::crypto::kms::{KeyState, TransitionReason};
+
+    const AAD: &[u8] = b"archive/2024-01";
+
+    #[test]
+    fn test_decrypt_resolves_embedded_version() {
+        let (mut kms, admin) = test_kms();
+        kms.generate_versioned_kem_key(&admin, "archive").unwrap();
+        let january = kms.encrypt(&admin, "archive", b"settlement batch 1", AAD).unwrap();
+
+        kms.rotate_version(&admin, "archive").unwrap();
+        kms.rotate_version(&admin, "archive").unwrap();
+        let march = kms.encrypt(&admin, "archive", b"settlement batch 3", AAD).unwrap();
+
+        let (header, _) = EnvelopeHeader::parse(&january).unwrap();
+        assert_eq!(header.key_id.to_string(), "archive:1");
+        assert_eq!(EnvelopeHeader::parse(&march).unwrap().0.key_id.version, 3);
+        assert_eq!(kms.key_state(&admin, "archive:1").unwrap(), KeyState::Deactivated);
+
+        assert_eq!(kms.decrypt(&admin, &january, AAD).unwrap().as_slice(), b"settlement batch 1");
+        assert_eq!(kms.decrypt(&admin, &march, AAD).unwrap().as_slice(), b"settlement batch 3");
+        assert!(kms.decrypt(&admin, &march, b"archive/2024-02").is_err());
+    }
+
+    #[test]
+    fn test_minimum_version_pin() {
+        let (mut kms, admin) = test_kms();
+        kms.generate_versioned_kem_key(&admin, "archive").unwrap();
+        let old = kms.encrypt(&admin, "archive", b"old", AAD).unwrap();
+        kms.rotate_version(&admin, "archive").unwrap();
+        let new = kms.encrypt(&admin, "archive", b"new", AAD).unwrap();
+
+        kms.pin_minimum_version(&admin, "archive", 2).unwrap();
+        let err = kms.decrypt(&admin, &old, AAD).unwrap_err();
+        assert_eq!(err.code().name, "KMS_OPERATION_NOT_PERMITTED");
+        assert_eq!(kms.decrypt(&admin, &new, AAD).unwrap().as_slice(), b"new");
+
+        // The pin survives rotation and cannot be lowered
+        kms.rotate_version(&admin, "archive").unwrap();
+        assert_eq!(kms.minimum_version(&admin, "archive").unwrap(), Some(2));
+        assert!(kms.pin_minimum_version(&admin, "archive", 1).is_err());
+        assert!(kms.pin_minimum_version(&admin, "archive", 4).is_err());
+    }
+
+    #[test]
+    fn test_header_is_authenticated_and_streamable() {
+        let (mut kms, admin) = test_kms();
+        kms.generate_versioned_kem_key(&admin, "archive").unwrap();
+        kms.rotate_version(&admin, "archive").unwrap();
+        let envelope = kms.encrypt(&admin, "archive", b"payload", AAD).unwrap();
+
+        // Readable from the front of a file without the body
+        let mut file = std::io::Cursor::new(envelope.clone());
+        let header = EnvelopeHeader::read_from(&mut file).unwrap();
+        assert_eq!(file.position() as usize, header.to_bytes().len());
+
+        // Pointing the header at another version breaks authentication
+        let mut relabelled = header.clone();
+        relabelled.key_id.version = 1;
+        let mut forged = relabelled.to_bytes();
+        forged.extend_from_slice(&envelope[header.to_bytes().len()..]);
+        assert!(kms.decrypt(&admin, &forged, AAD).is_err());
+
+        assert!(matches!(
+            EnvelopeHeader::parse(&envelope[..10]),
+            Err(KmsError::InvalidEnvelope(_))
+        ));
+
+        kms.transition_key(&admin, "archive:2", KeyState::Compromised, TransitionReason::KeyCompromise).unwrap();
+        assert!(kms.decrypt(&admin, &envelope, AAD).is_err());
+    }
+}
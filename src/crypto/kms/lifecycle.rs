@@ -0,0 +1,212 @@
+//! Key lifecycle states after NIST SP 800-57 Part 1, section 7.
+//!
+//! ```text
+//! PreActivation -> Active | Compromised | Destroyed
+//! Active        -> Suspended | Deactivated | Compromised
+//! Suspended     -> Active | Deactivated | Compromised
+//! Deactivated   -> Compromised | Destroyed
+//! Compromised   -> Destroyed
+//! ```
+//!
+//! Every state permits a fixed set of [`KeyOperation`]s. Keys that may no
+//! longer protect new data (Suspended, Deactivated) can still process data
+//! protected earlier, e.g. decapsulate but not encapsulate. Compromised and
+//! Destroyed keys permit nothing.
+
+use crate::error::KmsError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// SP 800-57 key state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum KeyState {
+    PreActivation,
+    Active,
+    Suspended,
+    Deactivated,
+    Compromised,
+    Destroyed,
+}
+
+/// Cryptographic use of a key, checked against its state before every use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum KeyOperation {
+    Encapsulate,
+    Decapsulate,
+    Encrypt,
+    Decrypt,
+    Sign,
+    Verify,
+    /// Handing out the raw key material (get_secret, share splitting)
+    Export,
+}
+
+/// Why a key changed state; mirrors the KMIP revocation reason codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TransitionReason {
+    Unspecified,
+    Activated,
+    Suspended,
+    Resumed,
+    Superseded,
+    Expired,
+    CessationOfOperation,
+    AffiliationChanged,
+    PrivilegeWithdrawn,
+    KeyCompromise,
+}
+
+/// One recorded state change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateTransition {
+    pub from: KeyState,
+    pub to: KeyState,
+    pub reason: TransitionReason,
+    pub at: u64,
+}
+
+impl KeyState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyState::PreActivation => "PRE_ACTIVATION",
+            KeyState::Active => "ACTIVE",
+            KeyState::Suspended => "SUSPENDED",
+            KeyState::Deactivated => "DEACTIVATED",
+            KeyState::Compromised => "COMPROMISED",
+            KeyState::Destroyed => "DESTROYED",
+        }
+    }
+
+    /// Whether SP 800-57 allows moving from this state to `next`
+    pub fn can_transition_to(&self, next: KeyState) -> bool {
+        use KeyState::*;
+        matches!(
+            (self, next),
+            (PreActivation, Active | Compromised | Destroyed)
+                | (Active, Suspended | Deactivated | Compromised)
+                | (Suspended, Active | Deactivated | Compromised)
+                | (Deactivated, Compromised | Destroyed)
+                | (Compromised, Destroyed)
+        )
+    }
+
+    /// Whether a key in this state may be used for `operation`
+    pub fn permits(&self, operation: KeyOperation) -> bool {
+        use KeyOperation::*;
+        match self {
+            KeyState::Active => true,
+            // Process-only: data protected while the key was active stays readable
+            KeyState::Suspended | KeyState::Deactivated => matches!(operation, Decapsulate | Decrypt | Verify),
+            KeyState::PreActivation | KeyState::Compromised | KeyState::Destroyed => false,
+        }
+    }
+
+    /// Checks `operation` against this state, naming the key in the error
+    pub fn check(&self, key_id: &str, operation: KeyOperation) -> Result<(), KmsError> {
+        if self.permits(operation) {
+            return Ok(());
+        }
+        Err(KmsError::OperationNotPermitted(format!(
+            "{:?} on key {} in state {}",
+            operation, key_id, self
+        )))
+    }
+
+    /// Builds the transition record for moving to `next`, or fails if it is illegal
+    pub fn transition(&self, next: KeyState, reason: TransitionReason, at: u64) -> Result<StateTransition, KmsError> {
+        if !self.can_transition_to(next) {
+            return Err(KmsError::InvalidStateTransition(format!("{} -> {}", self, next)));
+        }
+        // Compromise must be recorded as such, and only compromise may say so
+        if (next == KeyState::Compromised) != (reason == TransitionReason::KeyCompromise) {
+            return Err(KmsError::InvalidStateTransition(format!(
+                "{} -> {} with reason {:?}",
+                self, next, reason
+            )));
+        }
+        Ok(StateTransition {
+            from: *self,
+            to: next,
+            reason,
+            at,
+        })
+    }
+}
+
+impl fmt::Display for KeyState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KeyState {
+    type Err = KmsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            KeyState::PreActivation,
+            KeyState::Active,
+            KeyState::Suspended,
+            KeyState::Deactivated,
+            KeyState::Compromised,
+            KeyState::Destroyed,
+        ]
+        .into_iter()
+        .find(|state| state.as_str() == s)
+        .ok_or_else(|| KmsError::IntegrityCheckFailed(format!("unknown key state {}", s)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legal_transitions() {
+        use KeyState::*;
+        assert!(PreActivation.can_transition_to(Active));
+        assert!(Active.can_transition_to(Suspended));
+        assert!(Suspended.can_transition_to(Active));
+        assert!(Active.can_transition_to(Deactivated));
+        assert!(Deactivated.can_transition_to(Destroyed));
+
+        // No way back once a key is retired, compromised or gone
+        assert!(!Deactivated.can_transition_to(Active));
+        assert!(!Compromised.can_transition_to(Active));
+        assert!(!Destroyed.can_transition_to(PreActivation));
+        // Active keys must be deactivated before destruction
+        assert!(!Active.can_transition_to(Destroyed));
+
+        let err = Active.transition(Destroyed, TransitionReason::Unspecified, 0).unwrap_err();
+        assert_eq!(err.code().name, "KMS_INVALID_STATE_TRANSITION");
+        assert!(Active.transition(Compromised, TransitionReason::Superseded, 0).is_err());
+        assert!(Active.transition(Deactivated, TransitionReason::KeyCompromise, 0).is_err());
+    }
+
+    #[test]
+    fn test_operations_per_state() {
+        use KeyOperation::*;
+        assert!(KeyState::Active.permits(Encapsulate));
+        assert!(KeyState::Deactivated.permits(Decapsulate));
+        assert!(!KeyState::Deactivated.permits(Encapsulate));
+        assert!(!KeyState::Suspended.permits(Sign));
+        assert!(!KeyState::PreActivation.permits(Decapsulate));
+        assert!(!KeyState::Compromised.permits(Verify));
+
+        let err = KeyState::Deactivated.check("settlement", Encapsulate).unwrap_err();
+        assert!(matches!(err, KmsError::OperationNotPermitted(ref msg) if msg.contains("settlement")));
+    }
+
+    #[test]
+    fn test_state_names_round_trip() {
+        for state in [KeyState::PreActivation, KeyState::Active, KeyState::Destroyed] {
+            assert_eq!(state.as_str().parse::<KeyState>().unwrap(), state);
+            assert_eq!(serde_json::to_string(&state).unwrap(), format!("\"{}\"", state));
+        }
+        assert!("RETIRED".parse::<KeyState>().is_err());
+    }
+}
@@ -0,0 +1,1330 @@
+pub mod access;
+pub mod approval;
+pub mod backup;
+pub mod datakey;
+pub mod envelope;
+pub mod lifecycle;
+pub mod usage;
+pub mod version;
+
+pub use access::{AccessPolicy, CallerContext, Effect, KeyPattern, KmsAction, Permission, Role};
+pub use approval::{verify_approvals, Approval, ApprovalDecision, ApprovalPolicy, PendingRequest, RequestStatus, SensitiveOperation};
+pub use backup::{AuditHead, BackupBundle, BackupEntry, BackupManifest, BackupScope, RestoreOptions, BACKUP_CONTEXT};
+pub use datakey::{DataKey, DataKeySpec, EncryptionContext};
+pub use envelope::EnvelopeHeader;
+pub use lifecycle::{KeyOperation, KeyState, StateTransition, TransitionReason};
+pub use usage::{KeyUsageReport, UsageCounters, UsageLimits, UsageMetrics, UsagePolicy, UsageQuota};
+pub use version::KeyVersionId;
+
+use approval::ApprovalQueue;
+use usage::KeyUse;
+use crate::crypto::x509::{Certificate, CertificateAuthority, Name, RevocationReason, RevocationRegistry, RevokedCertificate, Validity};
+use crate::crypto::secure::{shamir, KekPublicKey, KeyEncryptionKey, SealedSecret, SecureSecret, ShamirSplit, Share, ShareCommitments};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use crate::error::{self, CertificateError, ErrorCode, KemError, KmsError};
+use crate::kms::audit::{AuditLog, AuditOperation, AuditOutcome};
+use crate::kms::provider::MasterKeyProvider;
+use crate::kms::store::{FileKeyStore, KeyMetadata, KeyRecord, KeyStore, MemoryKeyStore, DEFAULT_KEY_ALGORITHM};
+use anyhow::{Result, anyhow};
+use pqcrypto_kyber::kyber1024;
+use pqcrypto_traits::kem::{Ciphertext as _, PublicKey as _, SecretKey as _};
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use sha3::{Digest, Sha3_256};
+use zeroize::Zeroizing;
+
+// Constants
+const SHARED_SECRET_LENGTH: usize = 32;
+const SNAPSHOT_PURPOSE_PREFIX: &str = "kms/snapshot/";
+pub const KYBER1024_ALGORITHM: &str = "KYBER-1024";
+// FIPS 203 decapsulation key layout: dk_pke || ek || H(ek) || z
+const KYBER1024_PKE_SECRET_KEY_BYTES: usize = 1536;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DummySharedSecret {
+    data: [u8; SHARED_SECRET_LENGTH],
+    timestamp: u64,
+}
+
+pub trait SharedSecret {
+    fn as_bytes(&self) -> &[u8];
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KemError>
+    where
+        Self: Clone + Copy;
+}
+
+/// Every KMS secret sealed under a key-encryption key, safe to write to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KmsSnapshot {
+    pub created_at: u64,
+    pub secrets: HashMap<String, SealedSecret>,
+    /// Lifecycle state and history per key. Each secret's sealing purpose
+    /// carries a digest of its metadata, so neither can be altered or
+    /// dropped without the secret failing to unseal.
+    pub metadata: HashMap<String, KeyMetadata>,
+}
+
+pub struct KeyManagementSystem {
+    store: Box<dyn KeyStore>,
+    certificates: HashMap<String, Certificate>,
+    revocations: Option<Arc<RevocationRegistry>>,
+    usage_policy: UsagePolicy,
+    access: AccessPolicy,
+    approvals: ApprovalQueue,
+    audit: Option<Arc<AuditLog>>,
+}
+
+// Errors an audited KMS call can end with; a failed audit write is reported as one
+trait AuditedError: From<KmsError> {
+    fn error_code(&self) -> ErrorCode;
+    fn is_access_denied(&self) -> bool;
+}
+
+impl AuditedError for KmsError {
+    fn error_code(&self) -> ErrorCode {
+        self.code()
+    }
+
+    fn is_access_denied(&self) -> bool {
+        matches!(self, KmsError::AccessDenied(_))
+    }
+}
+
+impl AuditedError for error::Error {
+    fn error_code(&self) -> ErrorCode {
+        self.code()
+    }
+
+    fn is_access_denied(&self) -> bool {
+        matches!(self, error::Error::Kms(KmsError::AccessDenied(_)))
+    }
+}
+
+// Encapsulation key stored inside a Kyber-1024 decapsulation key
+pub(crate) fn embedded_public_key(secret: &SecureSecret) -> error::Result<kyber1024::PublicKey> {
+    secret.with_exposed(|sk| {
+        let start = KYBER1024_PKE_SECRET_KEY_BYTES;
+        sk.get(start..start + kyber1024::public_key_bytes())
+            .and_then(|pk| kyber1024::PublicKey::from_bytes(pk).ok())
+    })?.ok_or_else(|| KemError::InvalidPublicKey.into())
+}
+
+// A revocation registry that could not write a change through
+fn registry_error(err: anyhow::Error) -> KmsError {
+    KmsError::Storage(format!("revocation registry: {}", err))
+}
+
+/// Store key id of one version of a versioned key, e.g. `settlement:3`
+pub fn versioned_key_id(name: &str, version: u32) -> String {
+    KeyVersionId::new(name, version).to_string()
+}
+
+impl Default for DummySharedSecret {
+    fn default() -> Self {
+        Self {
+            data: [0u8; SHARED_SECRET_LENGTH],
+            timestamp: Self::get_current_timestamp()
+                .unwrap_or_else(|_| UNIX_EPOCH.elapsed().unwrap_or_default().as_secs()),
+        }
+    }
+}
+
+impl DummySharedSecret {
+    pub fn new(data: [u8; SHARED_SECRET_LENGTH]) -> Result<Self> {
+        let timestamp = Self::get_current_timestamp()?;
+        Ok(Self { data, timestamp })
+    }
+
+    /// Unix time in seconds at which the secret was created
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn get_current_timestamp() -> Result<u64> {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .map_err(|e| anyhow!("Failed to get timestamp: {}", e))
+    }
+}
+
+impl SharedSecret for DummySharedSecret {
+    fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, KemError>
+    where
+        Self: Clone + Copy,
+    {
+        if bytes.len() != SHARED_SECRET_LENGTH {
+            return Err(KemError::BadLength {
+                what: "shared secret",
+                expected: SHARED_SECRET_LENGTH,
+                actual: bytes.len(),
+            });
+        }
+
+        let mut data = [0u8; SHARED_SECRET_LENGTH];
+        data.copy_from_slice(bytes);
+
+        let timestamp = Self::get_current_timestamp()
+            .unwrap_or_else(|_| {
+                UNIX_EPOCH.elapsed().unwrap_or_default().as_secs()
+            });
+
+        Ok(Self { data, timestamp })
+    }
+}
+
+impl Default for KeyManagementSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyManagementSystem {
+    /// KMS backed by a volatile in-memory store. Its access policy is empty,
+    /// so every call is denied until [`set_access_policy`](Self::set_access_policy).
+    /// Destroy, export and compromise need a second approver by default;
+    /// see [`set_approval_policy`](Self::set_approval_policy).
+    pub fn new() -> Self {
+        Self::with_store(Box::new(MemoryKeyStore::new()))
+    }
+
+    /// KMS backed by the given store, e.g. a [`FileKeyStore`](crate::kms::FileKeyStore)
+    pub fn with_store(store: Box<dyn KeyStore>) -> Self {
+        Self {
+            store,
+            certificates: HashMap::new(),
+            revocations: None,
+            usage_policy: UsagePolicy::default(),
+            access: AccessPolicy::new(),
+            approvals: ApprovalQueue::default(),
+            audit: None,
+        }
+    }
+
+    /// KMS over a [`FileKeyStore`] whose master key is kept wrapped by
+    /// `provider`, e.g. in a PKCS#11 slot or Vault Transit; see [`crate::kms::provider`]
+    pub fn unseal_store(root: impl AsRef<Path>, provider: &dyn MasterKeyProvider) -> Result<Self, KmsError> {
+        Ok(Self::with_store(Box::new(FileKeyStore::open_with_provider(root, provider)?)))
+    }
+
+    pub fn set_access_policy(&mut self, policy: AccessPolicy) {
+        self.access = policy;
+    }
+
+    pub fn access_policy(&self) -> &AccessPolicy {
+        &self.access
+    }
+
+    /// Records every key operation, and every denied call, in `log`. Auditing
+    /// fails closed: an operation is only run once a `STARTED` entry for it has
+    /// been written, and returns an error if its outcome cannot be written.
+    pub fn set_audit_log(&mut self, log: Arc<AuditLog>) {
+        self.audit = Some(log);
+    }
+
+    pub fn audit_log(&self) -> Option<&Arc<AuditLog>> {
+        self.audit.as_ref()
+    }
+
+    /// Records certificates issued by the registry's CA in `registry` and
+    /// revokes a key's certificate when the key is compromised or destroyed
+    pub fn set_revocation_registry(&mut self, registry: Arc<RevocationRegistry>) {
+        self.revocations = Some(registry);
+    }
+
+    pub fn revocation_registry(&self) -> Option<&Arc<RevocationRegistry>> {
+        self.revocations.as_ref()
+    }
+
+    // Checks the caller's access policy. Denials are audited here so that
+    // read-only calls, which are not audited otherwise, leave a trace too;
+    // anything else is recorded as started before it runs, so that an
+    // operation whose outcome cannot be written is still on the trail
+    fn authorize(&self, caller: &CallerContext, action: KmsAction, key_id: &str) -> Result<(), KmsError> {
+        let checked = self.access.check(caller, action, key_id);
+        let outcome = match checked {
+            Ok(()) if action == KmsAction::Describe => return Ok(()),
+            Ok(()) => AuditOutcome::Started,
+            Err(_) => AuditOutcome::Denied,
+        };
+        if let Some(log) = &self.audit {
+            let detail = Some(action.as_str().to_string());
+            log.record(&caller.principal, key_id, action.into(), outcome, detail)?;
+        }
+        checked
+    }
+
+    // Appends the outcome of an operation on `key_id` to the audit log, if any
+    fn audited<T, E: AuditedError>(
+        &self,
+        caller: &CallerContext,
+        operation: AuditOperation,
+        key_id: &str,
+        detail: Option<String>,
+        result: Result<T, E>,
+    ) -> Result<T, E> {
+        let Some(log) = &self.audit else {
+            return result;
+        };
+        let (outcome, detail) = match &result {
+            Ok(_) => (AuditOutcome::Success, detail),
+            // Already recorded by `authorize`
+            Err(e) if e.is_access_denied() => return result,
+            Err(e) => (AuditOutcome::from_error(e.error_code()), Some(e.error_code().name.to_string())),
+        };
+        log.record(&caller.principal, key_id, operation, outcome, detail)?;
+        result
+    }
+
+    pub fn add_secret(&mut self, caller: &CallerContext, key_id: &str, secret: SecureSecret) -> Result<(), KmsError> {
+        let result = self.authorize(caller, KmsAction::Create, key_id)
+            .and_then(|_| self.put_new(KeyRecord::new(key_id, secret)));
+        self.audited(caller, AuditOperation::Create, key_id, None, result)
+    }
+
+    /// Adds a key in PreActivation; it cannot be used until moved to Active
+    pub fn add_pending_secret(&mut self, caller: &CallerContext, key_id: &str, secret: SecureSecret) -> Result<(), KmsError> {
+        self.add_pending_key(caller, key_id, secret, DEFAULT_KEY_ALGORITHM)
+    }
+
+    /// Adds a key for `algorithm`, e.g. `AES`, in PreActivation
+    pub fn add_pending_key(
+        &mut self,
+        caller: &CallerContext,
+        key_id: &str,
+        secret: SecureSecret,
+        algorithm: &str,
+    ) -> Result<(), KmsError> {
+        let metadata = KeyMetadata {
+            algorithm: algorithm.to_string(),
+            status: KeyState::PreActivation,
+            ..KeyMetadata::default()
+        };
+        let result = self.authorize(caller, KmsAction::Create, key_id)
+            .and_then(|_| self.put_new(KeyRecord::with_metadata(key_id, secret, metadata)));
+        self.audited(caller, AuditOperation::Create, key_id, Some(KeyState::PreActivation.to_string()), result)
+    }
+
+    fn put_new(&mut self, record: KeyRecord) -> Result<(), KmsError> {
+        if self.store.contains(&record.key_id)? {
+            return Err(KmsError::KeyAlreadyExists(record.key_id));
+        }
+        self.store.put(record)
+    }
+
+    /// Returns the secret if the caller may use it for `operation` and the
+    /// key's state permits it. Exports need approval unless the caller is exempt.
+    pub fn get_secret(
+        &self,
+        caller: &CallerContext,
+        key_id: &str,
+        operation: KeyOperation,
+    ) -> Result<Option<SecureSecret>, KmsError> {
+        self.authorize(caller, operation.into(), key_id)?;
+        let approved = if operation == KeyOperation::Export {
+            self.require_approval(caller, KmsAction::Export, key_id)
+        } else {
+            Ok(())
+        };
+        let result = approved.and_then(|_| self.store.get(key_id)).and_then(|record| match record {
+            Some(record) => record.metadata.status.check(key_id, operation).map(|_| Some(record.secret)),
+            None => Ok(None),
+        });
+        if matches!(result, Ok(None)) {
+            return result;
+        }
+        let audit_operation = if operation == KeyOperation::Export { AuditOperation::Export } else { AuditOperation::Use };
+        self.audited(caller, audit_operation, key_id, Some(KmsAction::from(operation).as_str().to_string()), result)
+    }
+
+    pub fn key_state(&self, caller: &CallerContext, key_id: &str) -> Result<KeyState, KmsError> {
+        self.authorize(caller, KmsAction::Describe, key_id)?;
+        Ok(self.record(key_id)?.metadata.status)
+    }
+
+    pub fn key_metadata(&self, caller: &CallerContext, key_id: &str) -> Result<KeyMetadata, KmsError> {
+        self.authorize(caller, KmsAction::Describe, key_id)?;
+        Ok(self.record(key_id)?.metadata)
+    }
+
+    /// Length of the key material in bytes, without handing it out
+    pub fn key_size(&self, caller: &CallerContext, key_id: &str) -> Result<usize, KmsError> {
+        self.authorize(caller, KmsAction::Describe, key_id)?;
+        Ok(self.record(key_id)?.secret.len())
+    }
+
+    /// Moves a key to `next` if SP 800-57 allows it. Destroying a key erases
+    /// its material but keeps its metadata and history. Destroying and
+    /// marking compromised need approval unless the caller is exempt.
+    pub fn transition_key(
+        &mut self,
+        caller: &CallerContext,
+        key_id: &str,
+        next: KeyState,
+        reason: TransitionReason,
+    ) -> Result<(), KmsError> {
+        let (action, operation) = if next == KeyState::Destroyed {
+            (KmsAction::Destroy, AuditOperation::Destroy)
+        } else {
+            (KmsAction::Transition, AuditOperation::Transition)
+        };
+        let result = self.authorize(caller, action, key_id)
+            .and_then(|_| match next {
+                KeyState::Destroyed | KeyState::Compromised => self.require_approval(caller, action, key_id),
+                _ => Ok(()),
+            })
+            .and_then(|_| self.apply_transition(key_id, next, reason));
+        self.audited(caller, operation, key_id, Some(format!("{} ({:?})", next, reason)), result)
+    }
+
+    fn apply_transition(&mut self, key_id: &str, next: KeyState, reason: TransitionReason) -> Result<(), KmsError> {
+        let (record, now) = self.transitioned(key_id, next, reason)?;
+
+        // Partners must stop encapsulating to a key that is gone or exposed;
+        // the certificate is revoked first so a failed write cannot leave it good
+        let revocation = match next {
+            KeyState::Compromised => Some(RevocationReason::KeyCompromise),
+            KeyState::Destroyed => Some(RevocationReason::CessationOfOperation),
+            _ => None,
+        };
+        if let (Some(reason), Some(registry), Some(certificate)) =
+            (revocation, &self.revocations, self.certificates.get(key_id))
+        {
+            registry.revoke(certificate.serial(), reason, now).map_err(registry_error)?;
+        }
+        self.store.put(record)
+    }
+
+    // The record of `key_id` moved to `next`, not yet stored, and the time of the move
+    fn transitioned(&self, key_id: &str, next: KeyState, reason: TransitionReason) -> Result<(KeyRecord, u64), KmsError> {
+        let KeyRecord { mut metadata, secret, .. } = self.record(key_id)?;
+        let now = DummySharedSecret::get_current_timestamp()
+            .map_err(|e| KmsError::Clock(e.to_string()))?;
+        metadata.transitions.push(metadata.status.transition(next, reason, now)?);
+        metadata.status = next;
+
+        // The old material is zeroized when `secret` is dropped
+        let secret = if next == KeyState::Destroyed { SecureSecret::from_bytes(&[]) } else { secret };
+        Ok((KeyRecord::with_metadata(key_id, secret, metadata), now))
+    }
+
+    /// Generates a Kyber-1024 key pair under `key_id` and returns the public key
+    pub fn generate_kem_key(&mut self, caller: &CallerContext, key_id: &str) -> Result<Vec<u8>, KmsError> {
+        self.generate_kem_key_in(caller, key_id, KeyState::Active)
+    }
+
+    /// Generates a Kyber-1024 key pair in PreActivation and returns the public key
+    pub fn generate_pending_kem_key(&mut self, caller: &CallerContext, key_id: &str) -> Result<Vec<u8>, KmsError> {
+        self.generate_kem_key_in(caller, key_id, KeyState::PreActivation)
+    }
+
+    fn generate_kem_key_in(&mut self, caller: &CallerContext, key_id: &str, status: KeyState) -> Result<Vec<u8>, KmsError> {
+        self.authorize(caller, KmsAction::Create, key_id)?;
+        let result = self.create_kem_key(key_id, status);
+        self.audited(caller, AuditOperation::Create, key_id, Some(KYBER1024_ALGORITHM.to_string()), result)
+    }
+
+    fn create_kem_key(&mut self, key_id: &str, status: KeyState) -> Result<Vec<u8>, KmsError> {
+        let (public_key, secret_key) = kyber1024::keypair();
+        let metadata = KeyMetadata {
+            algorithm: KYBER1024_ALGORITHM.to_string(),
+            status,
+            ..KeyMetadata::default()
+        };
+        self.put_new(KeyRecord::with_metadata(
+            key_id,
+            SecureSecret::from_decapsulation_key(&secret_key),
+            metadata,
+        ))?;
+        Ok(public_key.as_bytes().to_vec())
+    }
+
+    /// Encapsulates a fresh shared secret to a stored Kyber-1024 key
+    pub fn encapsulate(&mut self, caller: &CallerContext, key_id: &str) -> error::Result<(SecureSecret, Vec<u8>)> {
+        self.authorize(caller, KmsAction::Encapsulate, key_id)?;
+        let result = self.encapsulate_unaudited(key_id, 0);
+        self.audited(caller, AuditOperation::Use, key_id, Some(KmsAction::Encapsulate.as_str().to_string()), result)
+    }
+
+    // `protected_bytes` is the plaintext the shared secret will encrypt, if known
+    fn encapsulate_unaudited(&mut self, key_id: &str, protected_bytes: u64) -> error::Result<(SecureSecret, Vec<u8>)> {
+        let record = self.kem_record(key_id, KeyOperation::Encapsulate)?;
+        let public_key = embedded_public_key(&record.secret)?;
+
+        self.record_use(record, KeyUse::Encapsulate { bytes: protected_bytes })?;
+        let (shared, ciphertext) = kyber1024::encapsulate(&public_key);
+        Ok((SecureSecret::from_shared(shared), ciphertext.as_bytes().to_vec()))
+    }
+
+    /// Public key of a stored Kyber-1024 key pair, in any state but Destroyed
+    pub fn kem_public_key(&self, caller: &CallerContext, key_id: &str) -> error::Result<Vec<u8>> {
+        self.authorize(caller, KmsAction::Describe, key_id)?;
+        let record = self.record(key_id)?;
+        if record.metadata.algorithm != KYBER1024_ALGORITHM {
+            return Err(KemError::UnsupportedParameterSet(record.metadata.algorithm).into());
+        }
+        Ok(embedded_public_key(&record.secret)?.as_bytes().to_vec())
+    }
+
+    /// Number of encapsulations to `key_id` over its lifetime
+    pub fn encapsulation_count(&self, caller: &CallerContext, key_id: &str) -> Result<u64, KmsError> {
+        Ok(self.key_usage(caller, key_id)?.counters.encapsulations)
+    }
+
+    /// Decapsulates a ciphertext with a stored Kyber-1024 key. Versions below
+    /// the pinned minimum of a versioned key are refused.
+    pub fn decapsulate(&mut self, caller: &CallerContext, key_id: &str, ciphertext: &[u8]) -> error::Result<SecureSecret> {
+        self.authorize(caller, KmsAction::Decapsulate, key_id)?;
+        let result = self.decapsulate_unaudited(key_id, ciphertext);
+        self.audited(caller, AuditOperation::Use, key_id, Some(KmsAction::Decapsulate.as_str().to_string()), result)
+    }
+
+    fn decapsulate_unaudited(&mut self, key_id: &str, ciphertext: &[u8]) -> error::Result<SecureSecret> {
+        if let Some(id) = self.stored_version(key_id)? {
+            self.check_minimum_version(&id)?;
+        }
+        let record = self.kem_record(key_id, KeyOperation::Decapsulate)?;
+        if ciphertext.len() != kyber1024::ciphertext_bytes() {
+            return Err(KemError::BadLength {
+                what: "ciphertext",
+                expected: kyber1024::ciphertext_bytes(),
+                actual: ciphertext.len(),
+            }.into());
+        }
+        let ciphertext = kyber1024::Ciphertext::from_bytes(ciphertext)
+            .map_err(|_| KemError::InvalidCiphertext)?;
+
+        let shared = record.secret.with_exposed(|sk| {
+            let secret_key = kyber1024::SecretKey::from_bytes(sk)
+                .map_err(|_| KemError::DecapsulationFailed)?;
+            Ok::<_, error::Error>(SecureSecret::from_shared(kyber1024::decapsulate(&ciphertext, &secret_key)))
+        })??;
+        self.record_use(record, KeyUse::Decapsulate)?;
+        Ok(shared)
+    }
+
+    fn record(&self, key_id: &str) -> Result<KeyRecord, KmsError> {
+        self.store.get(key_id)?
+            .ok_or_else(|| KmsError::KeyNotFound(key_id.to_string()))
+    }
+
+    fn kem_record(&self, key_id: &str, operation: KeyOperation) -> error::Result<KeyRecord> {
+        let record = self.record(key_id)?;
+        record.metadata.status.check(key_id, operation)?;
+        if record.metadata.algorithm != KYBER1024_ALGORITHM {
+            return Err(KemError::UnsupportedParameterSet(record.metadata.algorithm).into());
+        }
+        Ok(record)
+    }
+
+    /// Ids of the keys the caller may describe
+    pub fn key_ids(&self, caller: &CallerContext) -> Result<Vec<String>, KmsError> {
+        Ok(self.store.key_ids()?
+            .into_iter()
+            .filter(|key_id| self.access.is_allowed(caller, KmsAction::Describe, key_id))
+            .collect())
+    }
+
+    /// Issues an X.509 certificate for the Kyber key `key_id` under `ca` and
+    /// keeps it with the key, replacing any earlier one. Only Active keys,
+    /// the ones partners may encapsulate to, are certified.
+    pub fn issue_certificate(
+        &mut self,
+        caller: &CallerContext,
+        ca: &CertificateAuthority,
+        key_id: &str,
+        subject: Name,
+        validity: Validity,
+    ) -> error::Result<Certificate> {
+        self.authorize(caller, KmsAction::Create, key_id)?;
+        let result = self.kem_record(key_id, KeyOperation::Encapsulate).and_then(|record| {
+            let public_key = embedded_public_key(&record.secret)?;
+            let certificate = ca.issue_kem_certificate(subject, public_key.as_bytes(), validity)?;
+            if let Some(registry) = &self.revocations {
+                registry.record_issued(&certificate).map_err(registry_error)?;
+            }
+            Ok(certificate)
+        });
+        let result = self.audited(caller, AuditOperation::Create, key_id, Some("certificate".to_string()), result);
+        if let Ok(certificate) = &result {
+            self.certificates.insert(key_id.to_string(), certificate.clone());
+        }
+        result
+    }
+
+    /// Attaches a certificate issued elsewhere to the Kyber key `key_id`
+    pub fn add_certificate(&mut self, caller: &CallerContext, key_id: &str, certificate: Certificate) -> error::Result<()> {
+        self.authorize(caller, KmsAction::Create, key_id)?;
+        if self.certificates.contains_key(key_id) {
+            return Err(KmsError::CertificateExists(key_id.to_string()).into());
+        }
+        let record = self.record(key_id)?;
+        if record.metadata.algorithm != KYBER1024_ALGORITHM
+            || certificate.public_key().as_bytes() != embedded_public_key(&record.secret)?.as_bytes()
+        {
+            return Err(CertificateError::KeyMismatch(key_id.to_string()).into());
+        }
+        // Certificates from other CAs are not ours to revoke
+        if let Some(registry) = &self.revocations {
+            registry.record_issued(&certificate).map_err(registry_error)?;
+        }
+        self.certificates.insert(key_id.to_string(), certificate);
+        Ok(())
+    }
+
+    /// Revokes the certificate of `key_id` without touching the key itself,
+    /// e.g. when it is superseded. Fails unless the KMS has a revocation
+    /// registry covering the certificate.
+    pub fn revoke_certificate(
+        &mut self,
+        caller: &CallerContext,
+        key_id: &str,
+        reason: RevocationReason,
+    ) -> error::Result<RevokedCertificate> {
+        self.authorize(caller, KmsAction::Transition, key_id)?;
+        let not_found = || error::Error::from(CertificateError::NotFound(key_id.to_string()));
+        let result = DummySharedSecret::get_current_timestamp()
+            .map_err(|e| KmsError::Clock(e.to_string()).into())
+            .and_then(|now| {
+                let registry = self.revocations.as_ref().ok_or_else(not_found)?;
+                let certificate = self.certificates.get(key_id).ok_or_else(not_found)?;
+                registry.revoke(certificate.serial(), reason, now).map_err(registry_error)?.ok_or_else(not_found)
+            });
+        let detail = Some(format!("certificate revoked ({:?})", reason));
+        self.audited(caller, AuditOperation::Transition, key_id, detail, result)
+    }
+
+    pub fn get_certificate(&self, caller: &CallerContext, key_id: &str) -> Result<Option<&Certificate>, KmsError> {
+        self.authorize(caller, KmsAction::Describe, key_id)?;
+        Ok(self.certificates.get(key_id))
+    }
+
+    /// Versions of the versioned key `name` in ascending order; empty if it has none
+    pub fn key_versions(&self, caller: &CallerContext, name: &str) -> Result<Vec<u32>, KmsError> {
+        self.authorize(caller, KmsAction::Describe, name)?;
+        self.versions_of(name)
+    }
+
+    fn versions_of(&self, name: &str) -> Result<Vec<u32>, KmsError> {
+        let prefix = format!("{}{}", name, version::KEY_VERSION_SEPARATOR);
+        let mut versions: Vec<u32> = self.store.key_ids()?
+            .iter()
+            .filter_map(|key_id| key_id.strip_prefix(&prefix)?.parse().ok())
+            .collect();
+        versions.sort_unstable();
+        Ok(versions)
+    }
+
+    /// Highest version of the versioned key `name`
+    pub fn current_version(&self, caller: &CallerContext, name: &str) -> Result<u32, KmsError> {
+        self.authorize(caller, KmsAction::Describe, name)?;
+        self.latest_version(name)
+    }
+
+    fn latest_version(&self, name: &str) -> Result<u32, KmsError> {
+        self.versions_of(name)?
+            .last()
+            .copied()
+            .ok_or_else(|| KmsError::KeyNotFound(name.to_string()))
+    }
+
+    /// Refuses decryption with versions of `name` older than `version`,
+    /// e.g. once everything encrypted under them has been re-encrypted
+    pub fn pin_minimum_version(&mut self, caller: &CallerContext, name: &str, version: u32) -> Result<(), KmsError> {
+        self.authorize(caller, KmsAction::Rotate, name)?;
+        let current = self.latest_version(name)?;
+        if version == 0 || version > current {
+            return Err(KmsError::OperationNotPermitted(format!(
+                "cannot pin {} to version {}; current version is {}",
+                name, version, current
+            )));
+        }
+        let key_id = versioned_key_id(name, current);
+        let mut record = self.record(&key_id)?;
+        if record.metadata.minimum_version.is_some_and(|pinned| version < pinned) {
+            return Err(KmsError::OperationNotPermitted(format!(
+                "minimum version of {} can only be raised",
+                name
+            )));
+        }
+        record.metadata.minimum_version = Some(version);
+        let result = self.store.put(record);
+        self.audited(caller, AuditOperation::Rotate, name, Some(format!("minimum version {}", version)), result)
+    }
+
+    /// Oldest version of `name` that may still be decrypted, if one is pinned
+    pub fn minimum_version(&self, caller: &CallerContext, name: &str) -> Result<Option<u32>, KmsError> {
+        self.authorize(caller, KmsAction::Describe, name)?;
+        self.pinned_minimum(name)
+    }
+
+    fn pinned_minimum(&self, name: &str) -> Result<Option<u32>, KmsError> {
+        let current = self.latest_version(name)?;
+        Ok(self.record(&versioned_key_id(name, current))?.metadata.minimum_version)
+    }
+
+    // The version `key_id` names, if it is a stored version in canonical form;
+    // anything else, e.g. `host:443` or `port:08443` for an unversioned key, is
+    // a literal key id
+    fn stored_version(&self, key_id: &str) -> Result<Option<KeyVersionId>, KmsError> {
+        let Ok(id) = key_id.parse::<KeyVersionId>() else {
+            return Ok(None);
+        };
+        let stored = id.to_string() == key_id && self.versions_of(&id.name)?.contains(&id.version);
+        Ok(stored.then_some(id))
+    }
+
+    fn check_minimum_version(&self, id: &KeyVersionId) -> Result<(), KmsError> {
+        match self.pinned_minimum(&id.name)? {
+            Some(minimum) if id.version < minimum => Err(KmsError::OperationNotPermitted(format!(
+                "key {} is below the pinned minimum version {}",
+                id, minimum
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Creates version 1 of a versioned Kyber-1024 key and returns its public key
+    pub fn generate_versioned_kem_key(&mut self, caller: &CallerContext, name: &str) -> Result<Vec<u8>, KmsError> {
+        // Authorized before the lookup, so a denied caller cannot probe which names exist
+        let key_id = versioned_key_id(name, 1);
+        self.authorize(caller, KmsAction::Create, &key_id)?;
+        let result = self.versions_of(name).and_then(|versions| {
+            if versions.is_empty() {
+                self.create_kem_key(&key_id, KeyState::Active)
+            } else {
+                Err(KmsError::KeyAlreadyExists(name.to_string()))
+            }
+        });
+        self.audited(caller, AuditOperation::Create, &key_id, Some(KYBER1024_ALGORITHM.to_string()), result)
+    }
+
+    /// Adds a new version of `name` with fresh material of the same algorithm
+    /// and deactivates the previous one, which stays usable for decryption
+    pub fn rotate_version(&mut self, caller: &CallerContext, name: &str) -> Result<u32, KmsError> {
+        let result = self.authorize(caller, KmsAction::Rotate, name)
+            .and_then(|_| self.rotate_version_unaudited(caller, name));
+        let detail = result.as_ref().ok().map(|version| format!("version {}", version));
+        self.audited(caller, AuditOperation::Rotate, name, detail, result)
+    }
+
+    fn rotate_version_unaudited(&mut self, caller: &CallerContext, name: &str) -> Result<u32, KmsError> {
+        let current = self.record(&versioned_key_id(name, self.latest_version(name)?))?;
+        let secret = if current.metadata.algorithm == KYBER1024_ALGORITHM {
+            SecureSecret::from_decapsulation_key(&kyber1024::keypair().1)
+        } else {
+            let mut bytes = Zeroizing::new(vec![0u8; current.secret.len()]);
+            rand::thread_rng().fill_bytes(&mut bytes);
+            SecureSecret::from_bytes(&bytes)
+        };
+        self.add_next_version(caller, name, secret, current.metadata)
+    }
+
+    // Stores `secret` as the next version of `name` and retires the previous
+    // version in one store write, so a crash cannot leave both Active; the
+    // caller has already been authorized to rotate `name`
+    fn add_next_version(
+        &mut self,
+        caller: &CallerContext,
+        name: &str,
+        secret: SecureSecret,
+        previous: KeyMetadata,
+    ) -> Result<u32, KmsError> {
+        let version = self.latest_version(name)?;
+        if !previous.status.can_transition_to(KeyState::Deactivated) {
+            return Err(KmsError::OperationNotPermitted(format!(
+                "rotation of key {} in state {}",
+                versioned_key_id(name, version), previous.status
+            )));
+        }
+
+        let metadata = KeyMetadata {
+            algorithm: previous.algorithm,
+            owner: previous.owner,
+            minimum_version: previous.minimum_version,
+            ..KeyMetadata::default()
+        };
+        let (old_key_id, next, reason) = (versioned_key_id(name, version), KeyState::Deactivated, TransitionReason::Superseded);
+        let result = self.transitioned(&old_key_id, next, reason).and_then(|(retired, _)| {
+            let added = KeyRecord::with_metadata(&versioned_key_id(name, version + 1), secret, metadata);
+            self.store.put_batch(vec![retired, added], None)
+        });
+        self.audited(caller, AuditOperation::Transition, &old_key_id, Some(format!("{} ({:?})", next, reason)), result)?;
+        Ok(version + 1)
+    }
+
+    /// Rotates a versioned key to `new_secret`: it gains a new version and
+    /// keeps the old one for decryption. Unversioned keys are refused, since
+    /// replacing them would strand whatever they protect.
+    pub fn rotate_secret(&mut self, caller: &CallerContext, key_id: &str, new_secret: SecureSecret) -> Result<(), KmsError> {
+        let result = self.authorize(caller, KmsAction::Rotate, key_id)
+            .and_then(|_| self.rotate_secret_unaudited(caller, key_id, new_secret));
+        self.audited(caller, AuditOperation::Rotate, key_id, None, result)
+    }
+
+    fn rotate_secret_unaudited(&mut self, caller: &CallerContext, key_id: &str, new_secret: SecureSecret) -> Result<(), KmsError> {
+        let Some(&version) = self.versions_of(key_id)?.last() else {
+            self.record(key_id)?;
+            return Err(KmsError::OperationNotPermitted(format!(
+                "rotation of unversioned key {}; create it with generate_versioned_kem_key",
+                key_id
+            )));
+        };
+        let previous = self.record(&versioned_key_id(key_id, version))?.metadata;
+        self.add_next_version(caller, key_id, new_secret, previous).map(|_| ())
+    }
+
+    /// Deletes a key and its history. Needs approval unless the caller is
+    /// exempt; see [`SensitiveOperation::Delete`].
+    pub fn remove_secret(&mut self, caller: &CallerContext, key_id: &str) -> Result<(), KmsError> {
+        let result = self.authorize(caller, KmsAction::Delete, key_id)
+            .and_then(|_| self.require_approval(caller, KmsAction::Delete, key_id))
+            .and_then(|_| self.delete_key(key_id));
+        self.audited(caller, AuditOperation::Delete, key_id, None, result)
+    }
+
+    fn delete_key(&mut self, key_id: &str) -> Result<(), KmsError> {
+        if self.store.delete(key_id)? {
+            Ok(())
+        } else {
+            Err(KmsError::KeyNotFound(key_id.to_string()))
+        }
+    }
+
+    /// Splits a stored secret into custodian shares (e.g. 2-of-3 for dual control)
+    pub fn split_secret(
+        &self,
+        caller: &CallerContext,
+        key_id: &str,
+        threshold: u8,
+        total_shares: u8,
+    ) -> Result<ShamirSplit, KmsError> {
+        let secret = self.get_secret(caller, key_id, KeyOperation::Export)?
+            .ok_or_else(|| KmsError::KeyNotFound(key_id.to_string()))?;
+        shamir::split(&secret, threshold, total_shares)
+            .map_err(|e| KmsError::SharesRejected(e.to_string()))
+    }
+
+    /// Reassembles a secret from custodian shares and stores it under key_id
+    pub fn restore_secret_from_shares(
+        &mut self,
+        caller: &CallerContext,
+        key_id: &str,
+        shares: &[Share],
+        commitments: &ShareCommitments,
+    ) -> Result<(), KmsError> {
+        let secret = shamir::combine(shares, commitments)
+            .map_err(|e| KmsError::SharesRejected(e.to_string()))?;
+        self.add_secret(caller, key_id, secret)
+    }
+
+    /// Seals every secret under the given KEK. Each secret's purpose is bound to its key_id and metadata.
+    /// Keys in every state are included so that their lifecycle survives a restore,
+    /// which is why the caller needs Backup on every key. Unless the caller is
+    /// exempt, a [`SensitiveOperation::snapshot`] request for this very KEK
+    /// must have been approved; it is used up by the snapshot.
+    pub fn snapshot(&mut self, caller: &CallerContext, kek: &KekPublicKey) -> Result<KmsSnapshot, KmsError> {
+        let key_ids = self.store.key_ids()?;
+        for key_id in &key_ids {
+            self.authorize(caller, KmsAction::Backup, key_id)?;
+        }
+        let operation = SensitiveOperation::snapshot(kek);
+        let request = self.approved_request(caller, &operation)
+            .or_else(|e| self.audited(caller, AuditOperation::Backup, operation.key_id(), None, Err(e)))?;
+        let detail = request.as_ref().map(|id| format!("request {}", id));
+
+        let mut secrets = HashMap::with_capacity(key_ids.len());
+        let mut metadata = HashMap::with_capacity(key_ids.len());
+        for key_id in key_ids {
+            let record = self.record(&key_id)?;
+            let purpose = snapshot_purpose(&key_id, &record.metadata)?;
+            let sealed = record.secret.seal(kek, &purpose)
+                .map_err(|_| KmsError::SealFailed(key_id.clone()));
+            secrets.insert(key_id.clone(), self.audited(caller, AuditOperation::Backup, &key_id, detail.clone(), sealed)?);
+            metadata.insert(key_id, record.metadata);
+        }
+        self.complete_request(request.as_deref());
+
+        Ok(KmsSnapshot {
+            created_at: DummySharedSecret::get_current_timestamp()
+                .map_err(|e| KmsError::Clock(e.to_string()))?,
+            secrets,
+            metadata,
+        })
+    }
+
+    /// Unseals a snapshot into a fresh KMS. Access policy and audit log are
+    /// not part of a snapshot and have to be set on the restored KMS.
+    pub fn restore_snapshot(snapshot: &KmsSnapshot, kek: &KeyEncryptionKey) -> Result<Self, KmsError> {
+        if let Some(key_id) = snapshot.metadata.keys().find(|key_id| !snapshot.secrets.contains_key(*key_id)) {
+            return Err(KmsError::IntegrityCheckFailed(format!("snapshot has metadata but no secret for {}", key_id)));
+        }
+        let mut kms = Self::new();
+        for (key_id, sealed) in &snapshot.secrets {
+            let metadata = snapshot.metadata.get(key_id)
+                .ok_or_else(|| KmsError::IntegrityCheckFailed(format!("snapshot has no metadata for {}", key_id)))?;
+            let secret = kek.unseal(sealed, &snapshot_purpose(key_id, metadata)?)
+                .map_err(|_| KmsError::UnsealFailed(key_id.clone()))?;
+            kms.store.put(KeyRecord::with_metadata(key_id, secret, metadata.clone()))?;
+        }
+        Ok(kms)
+    }
+}
+
+// Purpose a key is sealed for in a snapshot: its id and a digest of its metadata
+fn snapshot_purpose(key_id: &str, metadata: &KeyMetadata) -> Result<String, KmsError> {
+    let encoded = serde_json::to_vec(metadata)
+        .map_err(|e| KmsError::SealFailed(format!("{} metadata: {}", key_id, e)))?;
+    Ok(format!("{}{}/{}", SNAPSHOT_PURPOSE_PREFIX, key_id, hex::encode(Sha3_256::digest(&encoded))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use access::{administrator_policy, test_kms};
+
+    fn create_test_secret() -> DummySharedSecret {
+        DummySharedSecret::new([0u8; SHARED_SECRET_LENGTH])
+            .expect("Failed to create test secret")
+    }
+
+    #[test]
+    fn test_shared_secret_creation() {
+        let data = [0u8; SHARED_SECRET_LENGTH];
+        let secret = DummySharedSecret::new(data)
+            .expect("Failed to create shared secret");
+        assert_eq!(secret.as_bytes(), &data);
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let data = [1u8; SHARED_SECRET_LENGTH];
+        let secret = DummySharedSecret::from_bytes(&data)
+            .expect("Failed to create secret from bytes");
+        assert_eq!(secret.as_bytes(), &data);
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        let data = [1u8; 16];
+        let err = DummySharedSecret::from_bytes(&data)
+            .expect_err("Expected error for invalid length");
+        assert!(matches!(
+            err,
+            KemError::BadLength { expected: SHARED_SECRET_LENGTH, actual: 16, .. }
+        ));
+        assert_eq!(err.code().name, "KEM_BAD_LENGTH");
+    }
+
+    #[test]
+    fn test_default_implementation() {
+        let secret = DummySharedSecret::default();
+        assert_eq!(secret.as_bytes(), &[0u8; SHARED_SECRET_LENGTH]);
+    }
+
+    #[test]
+    fn test_timestamp_creation() {
+        let secret = create_test_secret();
+        let now = DummySharedSecret::get_current_timestamp()
+            .expect("Failed to get current time");
+        assert!(secret.timestamp() <= now);
+        assert!(now - secret.timestamp() < 2);
+    }
+
+    #[test]
+    fn test_kms_operations() {
+        let (mut kms, admin) = test_kms();
+        let secret = SecureSecret::from_bytes(&[1u8; 32]);
+
+        // Test adding secret
+        assert!(kms.add_secret(&admin, "test-key", secret).is_ok());
+
+        // Test getting secret
+        assert!(kms.get_secret(&admin, "test-key", KeyOperation::Export).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_literal_key_id_with_colon() {
+        let (mut kms, admin) = test_kms();
+        kms.generate_kem_key(&admin, "host:443").unwrap();
+        let (shared, ciphertext) = kms.encapsulate(&admin, "host:443").unwrap();
+        assert_eq!(kms.decapsulate(&admin, "host:443", &ciphertext).unwrap(), shared);
+
+        for key_id in ["db:primary", "port:08443"] {
+            kms.generate_kem_key(&admin, key_id).unwrap();
+            let (shared, ciphertext) = kms.encapsulate(&admin, key_id).unwrap();
+            assert_eq!(kms.decapsulate(&admin, key_id, &ciphertext).unwrap(), shared);
+        }
+        let (_, ciphertext) = kms.encapsulate(&admin, "host:443").unwrap();
+
+        // A missing id is reported as given, not as its name part
+        assert!(matches!(
+            kms.decapsulate(&admin, "host:8443", &ciphertext),
+            Err(error::Error::Kms(KmsError::KeyNotFound(id))) if id == "host:8443"
+        ));
+    }
+
+    #[test]
+    fn test_certificate_issuance() {
+        use crate::crypto::signature::MlDsaParameterSet;
+        use crate::crypto::x509::TrustStore;
+
+        let (mut kms, admin) = test_kms();
+        let ca = CertificateAuthority::new_root(
+            Name::new("Example Root CA"),
+            MlDsaParameterSet::MlDsa65,
+            Validity::for_days(3650).unwrap(),
+        )
+        .unwrap();
+        let public_key = kms.generate_kem_key(&admin, "inbound").unwrap();
+        let subject = Name::new("inbound").with_organization("Example Bank");
+        let certificate = kms
+            .issue_certificate(&admin, &ca, "inbound", subject, Validity::for_days(365).unwrap())
+            .unwrap();
+        assert_eq!(kms.get_certificate(&admin, "inbound").unwrap(), Some(&certificate));
+
+        // What a partner does before encapsulating to us
+        let mut trust = TrustStore::new();
+        trust.add_anchor(ca.certificate().clone()).unwrap();
+        assert_eq!(trust.verify_kem_certificate(&certificate, &[]).unwrap(), public_key);
+
+        // A certificate for another key cannot be attached
+        kms.generate_kem_key(&admin, "outbound").unwrap();
+        let err = kms.add_certificate(&admin, "outbound", certificate.clone()).unwrap_err();
+        assert_eq!(err.code().name, "X509_KEY_MISMATCH");
+        let err = kms.add_certificate(&admin, "inbound", certificate).unwrap_err();
+        assert_eq!(err.code().name, "KMS_CERTIFICATE_EXISTS");
+
+        // Keys that may no longer be encapsulated to are not certified
+        kms.transition_key(&admin, "outbound", KeyState::Deactivated, TransitionReason::Superseded).unwrap();
+        let validity = Validity::for_days(1).unwrap();
+        assert!(kms.issue_certificate(&admin, &ca, "outbound", Name::new("outbound"), validity).is_err());
+    }
+
+    #[test]
+    fn test_certificate_revoked_with_key() {
+        use crate::crypto::signature::MlDsaParameterSet;
+        use crate::crypto::x509::{CertificateStatus, RevocationChecker, StatusResponder, TrustStore};
+
+        let (mut kms, admin) = test_kms();
+        let ca = CertificateAuthority::new_root(Name::new("Root"), MlDsaParameterSet::MlDsa65, Validity::for_days(30).unwrap())
+            .unwrap();
+        let registry = Arc::new(RevocationRegistry::new(ca.certificate()));
+        kms.set_revocation_registry(registry.clone());
+
+        let mut certificates = Vec::new();
+        for key_id in ["inbound", "archive", "retired"] {
+            kms.generate_kem_key(&admin, key_id).unwrap();
+            let validity = Validity::for_days(7).unwrap();
+            certificates.push(kms.issue_certificate(&admin, &ca, key_id, Name::new(key_id), validity).unwrap());
+        }
+        assert!(certificates.iter().all(|c| registry.status(c.serial()) == CertificateStatus::Good));
+
+        // Marking a key compromised revokes its certificate at once
+        kms.transition_key(&admin, "inbound", KeyState::Compromised, TransitionReason::KeyCompromise).unwrap();
+        assert!(matches!(
+            registry.status(certificates[0].serial()),
+            CertificateStatus::Revoked { reason: RevocationReason::KeyCompromise, .. }
+        ));
+        kms.transition_key(&admin, "archive", KeyState::Deactivated, TransitionReason::Superseded).unwrap();
+        kms.transition_key(&admin, "archive", KeyState::Destroyed, TransitionReason::Superseded).unwrap();
+        assert!(matches!(
+            registry.status(certificates[1].serial()),
+            CertificateStatus::Revoked { reason: RevocationReason::CessationOfOperation, .. }
+        ));
+
+        let revoked = kms.revoke_certificate(&admin, "retired", RevocationReason::Superseded).unwrap();
+        assert_eq!(revoked.serial, certificates[2].serial());
+        assert_eq!(kms.key_state(&admin, "retired").unwrap(), KeyState::Active);
+        let err = kms.revoke_certificate(&admin, "missing", RevocationReason::Superseded).unwrap_err();
+        assert_eq!(err.code().name, "X509_NOT_FOUND");
+
+        // A partner checking status refuses to encapsulate to the compromised key
+        let mut trust = TrustStore::new();
+        trust.add_anchor(ca.certificate().clone()).unwrap();
+        let responder = StatusResponder::new(ca, registry).unwrap();
+        trust.set_revocation_checker(Arc::new(RevocationChecker::new(responder)));
+        let err = crate::error::Error::from(trust.encapsulate(&certificates[0], &[]).unwrap_err());
+        assert_eq!(err.code().name, "X509_REVOKED");
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let kek = KeyEncryptionKey::generate("kek-backup");
+        let (mut kms, admin) = test_kms();
+        kms.add_secret(&admin, "settlement", SecureSecret::from_bytes(&[3u8; 32])).unwrap();
+        kms.add_secret(&admin, "reporting", SecureSecret::from_bytes(&[4u8; 32])).unwrap();
+        kms.transition_key(&admin, "reporting", KeyState::Deactivated, TransitionReason::Superseded).unwrap();
+
+        let snapshot = kms.snapshot(&admin, kek.public_key()).unwrap();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let snapshot: KmsSnapshot = serde_json::from_str(&json).unwrap();
+
+        let mut restored = KeyManagementSystem::restore_snapshot(&snapshot, &kek).unwrap();
+        restored.set_access_policy(administrator_policy(&admin.principal));
+        let decrypt = KeyOperation::Decrypt;
+        assert_eq!(restored.get_secret(&admin, "settlement", decrypt).unwrap(), kms.get_secret(&admin, "settlement", decrypt).unwrap());
+        assert_eq!(restored.get_secret(&admin, "reporting", decrypt).unwrap(), kms.get_secret(&admin, "reporting", decrypt).unwrap());
+        assert_eq!(restored.key_state(&admin, "reporting").unwrap(), KeyState::Deactivated);
+
+        // Swapping sealed entries between key ids must fail
+        let mut swapped = snapshot.clone();
+        let settlement = swapped.secrets.remove("settlement").unwrap();
+        let reporting = swapped.secrets.insert("reporting".to_string(), settlement).unwrap();
+        swapped.secrets.insert("settlement".to_string(), reporting);
+        assert!(matches!(
+            KeyManagementSystem::restore_snapshot(&swapped, &kek),
+            Err(KmsError::UnsealFailed(_))
+        ));
+
+        // So must reviving a deactivated key or dropping its metadata
+        let mut revived = snapshot.clone();
+        revived.metadata.get_mut("reporting").unwrap().status = KeyState::Active;
+        assert!(matches!(
+            KeyManagementSystem::restore_snapshot(&revived, &kek),
+            Err(KmsError::UnsealFailed(_))
+        ));
+        let mut stripped = snapshot.clone();
+        stripped.metadata.remove("reporting");
+        assert!(matches!(
+            KeyManagementSystem::restore_snapshot(&stripped, &kek),
+            Err(KmsError::IntegrityCheckFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_master_key_dual_control() {
+        use crate::crypto::secure::ShareEncoding;
+
+        let (mut kms, admin) = test_kms();
+        kms.add_secret(&admin, "kms-master", SecureSecret::from_bytes(&[8u8; 32])).unwrap();
+
+        // Three officers each receive one share; any two can unseal
+        let split = kms.split_secret(&admin, "kms-master", 2, 3).unwrap();
+        let handed_out: Vec<String> = split.shares.iter()
+            .map(|s| s.encode(ShareEncoding::Text))
+            .collect();
+
+        let presented = vec![
+            Share::decode(&handed_out[0], ShareEncoding::Text).unwrap(),
+            Share::decode(&handed_out[2], ShareEncoding::Text).unwrap(),
+        ];
+
+        let (mut recovered, _) = test_kms();
+        assert!(matches!(
+            recovered.restore_secret_from_shares(&admin, "kms-master", &presented[..1], &split.commitments),
+            Err(KmsError::SharesRejected(_))
+        ));
+        recovered.restore_secret_from_shares(&admin, "kms-master", &presented, &split.commitments).unwrap();
+        assert_eq!(
+            recovered.get_secret(&admin, "kms-master", KeyOperation::Export).unwrap(),
+            kms.get_secret(&admin, "kms-master", KeyOperation::Export).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_file_backed_kms_survives_restart() {
+        use crate::kms::FileKeyStore;
+
+        let dir = tempfile::tempdir().unwrap();
+        let master_key = SecureSecret::from_bytes(&[0x33u8; 32]);
+        let admin = CallerContext::new("kms-admin");
+        {
+            let store = FileKeyStore::open(dir.path(), &master_key).unwrap();
+            let mut kms = KeyManagementSystem::with_store(Box::new(store));
+            kms.set_access_policy(administrator_policy(&admin.principal));
+            kms.add_secret(&admin, "settlement", SecureSecret::from_bytes(&[6u8; 32])).unwrap();
+        }
+
+        let mut kms = KeyManagementSystem::with_store(Box::new(FileKeyStore::open(dir.path(), &master_key).unwrap()));
+        kms.set_access_policy(administrator_policy(&admin.principal));
+        kms.set_approval_policy(ApprovalPolicy::default().exempt(&admin.principal));
+        assert_eq!(kms.key_ids(&admin).unwrap(), vec!["settlement".to_string()]);
+        assert_eq!(
+            kms.get_secret(&admin, "settlement", KeyOperation::Export).unwrap(),
+            Some(SecureSecret::from_bytes(&[6u8; 32]))
+        );
+    }
+
+    #[test]
+    fn test_lifecycle_gates_operations() {
+        let (mut kms, admin) = test_kms();
+        let public_key = kms.generate_kem_key(&admin, "inbound").unwrap();
+        assert_eq!(public_key.len(), kyber1024::public_key_bytes());
+
+        let (shared, ciphertext) = kms.encapsulate(&admin, "inbound").unwrap();
+        assert_eq!(kms.decapsulate(&admin, "inbound", &ciphertext).unwrap(), shared);
+
+        kms.transition_key(&admin, "inbound", KeyState::Deactivated, TransitionReason::CessationOfOperation).unwrap();
+        // Old ciphertexts still open, but no new data may be protected
+        assert_eq!(kms.decapsulate(&admin, "inbound", &ciphertext).unwrap(), shared);
+        let err = kms.encapsulate(&admin, "inbound").unwrap_err();
+        assert_eq!(err.code().name, "KMS_OPERATION_NOT_PERMITTED");
+        assert!(matches!(
+            kms.get_secret(&admin, "inbound", KeyOperation::Export),
+            Err(KmsError::OperationNotPermitted(_))
+        ));
+
+        assert!(matches!(
+            kms.transition_key(&admin, "inbound", KeyState::Active, TransitionReason::Resumed),
+            Err(KmsError::InvalidStateTransition(_))
+        ));
+
+        kms.transition_key(&admin, "inbound", KeyState::Destroyed, TransitionReason::Unspecified).unwrap();
+        assert!(kms.decapsulate(&admin, "inbound", &ciphertext).is_err());
+        let metadata = kms.key_metadata(&admin, "inbound").unwrap();
+        assert_eq!(metadata.status, KeyState::Destroyed);
+        assert_eq!(metadata.transitions.len(), 2);
+        assert_eq!(metadata.transitions[0].reason, TransitionReason::CessationOfOperation);
+    }
+
+    #[test]
+    fn test_pending_key_requires_activation() {
+        let (mut kms, admin) = test_kms();
+        kms.add_pending_secret(&admin, "settlement", SecureSecret::from_bytes(&[5u8; 32])).unwrap();
+        assert!(kms.get_secret(&admin, "settlement", KeyOperation::Decrypt).is_err());
+        assert!(kms.rotate_secret(&admin, "settlement", SecureSecret::from_bytes(&[6u8; 32])).is_err());
+
+        kms.transition_key(&admin, "settlement", KeyState::Active, TransitionReason::Activated).unwrap();
+        assert!(kms.get_secret(&admin, "settlement", KeyOperation::Decrypt).unwrap().is_some());
+
+        kms.transition_key(&admin, "settlement", KeyState::Suspended, TransitionReason::Suspended).unwrap();
+        assert!(kms.get_secret(&admin, "settlement", KeyOperation::Decrypt).unwrap().is_some());
+        assert!(kms.get_secret(&admin, "settlement", KeyOperation::Encrypt).is_err());
+    }
+
+    #[test]
+    fn test_operations_are_audited() {
+        use crate::kms::{AuditOperation::*, AuditOutcome::*, AuditRecord};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kms-audit.jsonl");
+        let log = Arc::new(AuditLog::open(&path).unwrap());
+        let (mut kms, admin) = test_kms();
+        kms.set_audit_log(log.clone());
+
+        kms.generate_versioned_kem_key(&admin, "settlement").unwrap();
+        let (_, ciphertext) = kms.encapsulate(&admin, "settlement:1").unwrap();
+        kms.rotate_version(&admin, "settlement").unwrap();
+        assert!(kms.encapsulate(&admin, "settlement:1").is_err());
+        kms.decapsulate(&admin, "settlement:1", &ciphertext).unwrap();
+        kms.transition_key(&admin, "settlement:1", KeyState::Destroyed, TransitionReason::Unspecified).unwrap();
+
+        let trail: Vec<_> = std::fs::read_to_string(&path).unwrap()
+            .lines()
+            .filter_map(|line| match serde_json::from_str(line).unwrap() {
+                AuditRecord::Entry(entry) => Some((entry.key_id, entry.operation, entry.outcome)),
+                AuditRecord::Checkpoint(_) => None,
+            })
+            .collect();
+        assert_eq!(log.head().unwrap().0, trail.len() as u64);
+        // Every outcome is preceded by its STARTED entry
+        let trail: Vec<_> = trail.into_iter().filter(|(_, _, outcome)| *outcome != Started).collect();
+        let expected = [
+            ("settlement:1", Create, Success),
+            ("settlement:1", Use, Success),
+            // Rotation deactivates the old version before recording itself
+            ("settlement:1", Transition, Success),
+            ("settlement", Rotate, Success),
+            ("settlement:1", Use, Denied),
+            ("settlement:1", Use, Success),
+            ("settlement:1", Destroy, Success),
+        ];
+        assert_eq!(trail, expected.map(|(key_id, op, outcome)| (key_id.to_string(), op, outcome)));
+    }
+
+    #[test]
+    fn test_auditing_fails_closed() {
+        use crate::kms::{AuditOperation::*, AuditOutcome::*, AuditRecord};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kms-audit.jsonl");
+        let log = Arc::new(AuditLog::open(&path).unwrap());
+        let (mut kms, admin) = test_kms();
+        kms.set_audit_log(log.clone());
+
+        kms.generate_versioned_kem_key(&admin, "settlement").unwrap();
+        let trail: Vec<_> = std::fs::read_to_string(&path).unwrap()
+            .lines()
+            .filter_map(|line| match serde_json::from_str(line).unwrap() {
+                AuditRecord::Entry(entry) => Some((entry.operation, entry.outcome)),
+                AuditRecord::Checkpoint(_) => None,
+            })
+            .collect();
+        assert_eq!(trail, [(Create, Started), (Create, Success)]);
+
+        // Without a STARTED entry nothing runs
+        log.break_writes();
+        assert!(matches!(kms.rotate_version(&admin, "settlement"), Err(KmsError::Storage(_))));
+        assert!(kms.encapsulate(&admin, "settlement:1").is_err());
+        assert_eq!(kms.versions_of("settlement").unwrap(), [1]);
+    }
+
+    #[test]
+    fn test_callers_are_held_to_their_roles() {
+        use crate::kms::{AuditOperation, AuditOutcome, AuditRecord};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kms-access.jsonl");
+        let (mut kms, admin) = test_kms();
+        kms.set_access_policy(
+            administrator_policy(&admin.principal)
+                .with_role(
+                    Role::new("settlement-etl")
+                        .allow("settlement:*", [KmsAction::Encapsulate, KmsAction::Encrypt])
+                        .deny("*", [KmsAction::Export]),
+                )
+                .assign("etl-service", "settlement-etl"),
+        );
+        kms.set_audit_log(Arc::new(AuditLog::open(&path).unwrap()));
+        kms.generate_versioned_kem_key(&admin, "settlement").unwrap();
+        kms.generate_kem_key(&admin, "reporting").unwrap();
+
+        let etl = CallerContext::new("etl-service");
+        let envelope = kms.encrypt(&etl, "settlement", b"batch", b"").unwrap();
+        assert!(kms.encapsulate(&etl, "settlement:1").is_ok());
+        assert!(kms.decrypt(&etl, &envelope, b"").is_err());
+        assert!(kms.encapsulate(&etl, "reporting").is_err());
+        assert!(matches!(
+            kms.get_secret(&etl, "settlement:1", KeyOperation::Export),
+            Err(KmsError::AccessDenied(_))
+        ));
+        assert!(kms.key_ids(&etl).unwrap().is_empty());
+        assert_eq!(kms.decrypt(&admin, &envelope, b"").unwrap().as_slice(), b"batch");
+        // Denied before the lookup, so an existing name is not revealed
+        assert!(matches!(
+            kms.generate_versioned_kem_key(&etl, "settlement"),
+            Err(KmsError::AccessDenied(_))
+        ));
+
+        let denials: Vec<_> = std::fs::read_to_string(&path).unwrap()
+            .lines()
+            .filter_map(|line| match serde_json::from_str(line).unwrap() {
+                AuditRecord::Entry(entry) if entry.outcome == AuditOutcome::Denied => Some(entry),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(denials.len(), 4);
+        assert!(denials.iter().all(|entry| entry.actor == "etl-service"));
+        assert_eq!(denials[2].operation, AuditOperation::Export);
+        assert_eq!(denials[3].operation, AuditOperation::Create);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_kms_secrets_are_protected() {
+        let (mut kms, admin) = test_kms();
+        kms.add_secret(&admin, "test-key", SecureSecret::from_bytes(&[2u8; 32])).unwrap();
+
+        let stored = kms.get_secret(&admin, "test-key", KeyOperation::Export).unwrap().unwrap();
+        assert!(stored.is_protected());
+        stored.with_exposed(|bytes| assert_eq!(bytes, &[2u8; 32])).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,339 @@
+//! Usage counters and limits per key version.
+//!
+//! The KMS counts encapsulations, decapsulations and plaintext bytes
+//! protected for every key id, which for versioned keys means every
+//! `name:version`, and remembers when each was last used. A [`UsagePolicy`]
+//! sets quotas on keys matching a pattern:
+//!
+//! - past a soft limit the KMS logs a warning once and reports the key as
+//!   due, which makes the rotation engine rotate it if it manages the key;
+//! - a use that would pass a hard limit is refused with
+//!   [`KmsError::UsageLimitExceeded`].
+//!
+//! Decapsulation is counted but never limited: data protected under a key
+//! must stay readable. Counters are kept in each key version's metadata and
+//! written with every use, so they survive restarts and travel with
+//! snapshots and backups. They are exposed as JSON reports, and as
+//! monitoring metrics through [`UsageMetrics`].
+
+use super::access::{CallerContext, KeyPattern, KmsAction};
+use super::KeyManagementSystem;
+use crate::error::{Error, KmsError};
+use crate::kms::store::KeyRecord;
+use crate::monitoring::{MetricFamily, MetricKind, MetricsSource};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Name, type, help text and value of each exported metric
+type UsageMetric = (&'static str, MetricKind, &'static str, fn(&KeyUsageReport) -> u64);
+
+const USAGE_METRICS: [UsageMetric; 5] = [
+    ("kms_key_encapsulations_total", MetricKind::Counter, "Encapsulations per key version", |r| r.counters.encapsulations),
+    ("kms_key_decapsulations_total", MetricKind::Counter, "Decapsulations per key version", |r| r.counters.decapsulations),
+    ("kms_key_bytes_protected_total", MetricKind::Counter, "Plaintext bytes encrypted per key version", |r| {
+        r.counters.bytes_protected
+    }),
+    ("kms_key_last_used_timestamp_seconds", MetricKind::Gauge, "Last use of each key version", |r| {
+        r.counters.last_used_at.unwrap_or_default()
+    }),
+    ("kms_key_soft_limit_reached", MetricKind::Gauge, "1 once a key version reached its soft usage limit", |r| {
+        u64::from(r.soft_limit_reached)
+    }),
+];
+
+/// Use of one key version over its lifetime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageCounters {
+    pub encapsulations: u64,
+    pub decapsulations: u64,
+    /// Plaintext bytes encrypted under the key; raw encapsulations add none
+    pub bytes_protected: u64,
+    pub last_used_at: Option<u64>,
+}
+
+/// Ceilings on a key's counters; `None` is unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageLimits {
+    pub max_encapsulations: Option<u64>,
+    pub max_bytes_protected: Option<u64>,
+}
+
+/// Soft and hard limits for the keys matching a pattern
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageQuota {
+    pub keys: KeyPattern,
+    /// Warn and rotate once reached
+    #[serde(default)]
+    pub soft: UsageLimits,
+    /// Refuse any use that would exceed
+    #[serde(default)]
+    pub hard: UsageLimits,
+}
+
+/// Quotas by key pattern; the first matching quota applies
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsagePolicy {
+    pub quotas: Vec<UsageQuota>,
+}
+
+/// Counters of one key and where they stand against its quota
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyUsageReport {
+    pub key_id: String,
+    #[serde(flatten)]
+    pub counters: UsageCounters,
+    pub soft_limit_reached: bool,
+    pub hard_limit_reached: bool,
+}
+
+/// Usage counters of the keys a service principal may describe, for a
+/// [`MetricsRegistry`](crate::monitoring::MetricsRegistry)
+pub struct UsageMetrics {
+    kms: Arc<Mutex<KeyManagementSystem>>,
+    caller: CallerContext,
+}
+
+/// One counted use of a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum KeyUse {
+    /// Encapsulation protecting `bytes` of plaintext
+    Encapsulate { bytes: u64 },
+    Decapsulate,
+}
+
+impl UsageLimits {
+    /// Whether `counters` have reached any of these limits
+    pub fn reached_by(&self, counters: &UsageCounters) -> bool {
+        self.max_encapsulations.is_some_and(|max| counters.encapsulations >= max)
+            || self.max_bytes_protected.is_some_and(|max| counters.bytes_protected >= max)
+    }
+
+    /// The limit `counters` exceed, if any
+    fn exceeded_by(&self, counters: &UsageCounters) -> Option<String> {
+        if let Some(max) = self.max_encapsulations.filter(|max| counters.encapsulations > *max) {
+            return Some(format!("{} encapsulations", max));
+        }
+        if let Some(max) = self.max_bytes_protected.filter(|max| counters.bytes_protected > *max) {
+            return Some(format!("{} bytes protected", max));
+        }
+        None
+    }
+}
+
+impl UsagePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a quota for keys matching `keys`, e.g. `settlement:*`
+    pub fn with_quota(mut self, keys: &str, soft: UsageLimits, hard: UsageLimits) -> Self {
+        self.quotas.push(UsageQuota { keys: KeyPattern::new(keys), soft, hard });
+        self
+    }
+
+    pub fn quota(&self, key_id: &str) -> Option<&UsageQuota> {
+        self.quotas.iter().find(|quota| quota.keys.matches(key_id))
+    }
+}
+
+impl UsageMetrics {
+    pub fn new(kms: Arc<Mutex<KeyManagementSystem>>, caller: CallerContext) -> Self {
+        Self { kms, caller }
+    }
+}
+
+impl MetricsSource for UsageMetrics {
+    fn collect(&self) -> Result<Vec<MetricFamily>, Error> {
+        let kms = self.kms.lock().map_err(|_| KmsError::Storage("KMS lock poisoned".to_string()))?;
+        Ok(kms.usage_metrics(&self.caller)?)
+    }
+}
+
+impl KeyManagementSystem {
+    /// Replaces the usage quotas; counters are kept
+    pub fn set_usage_policy(&mut self, policy: UsagePolicy) {
+        self.usage_policy = policy;
+    }
+
+    pub fn usage_policy(&self) -> &UsagePolicy {
+        &self.usage_policy
+    }
+
+    pub fn key_usage(&self, caller: &CallerContext, key_id: &str) -> Result<KeyUsageReport, KmsError> {
+        self.authorize(caller, KmsAction::Describe, key_id)?;
+        self.usage_report_of(key_id)
+    }
+
+    /// Usage of every stored key the caller may describe, by key id
+    pub fn usage_report(&self, caller: &CallerContext) -> Result<Vec<KeyUsageReport>, KmsError> {
+        self.key_ids(caller)?
+            .iter()
+            .map(|key_id| self.usage_report_of(key_id))
+            .collect()
+    }
+
+    /// [`usage_report`](Self::usage_report) as monitoring metrics, labelled by key id
+    pub fn usage_metrics(&self, caller: &CallerContext) -> Result<Vec<MetricFamily>, KmsError> {
+        let reports = self.usage_report(caller)?;
+        Ok(USAGE_METRICS
+            .iter()
+            .map(|(name, kind, help, value)| {
+                reports.iter().fold(MetricFamily::new(name, *kind, help), |family, report| {
+                    family.with_sample(vec![("key", report.key_id.clone())], value(report))
+                })
+            })
+            .collect())
+    }
+
+    /// Counts one use of the key in `record` and writes the record back,
+    /// refusing the use if it would exceed a hard limit
+    pub(super) fn record_use(&mut self, mut record: KeyRecord, key_use: KeyUse) -> Result<(), KmsError> {
+        let key_id = record.key_id.as_str();
+        let current = record.metadata.usage;
+        let mut next = current;
+        match key_use {
+            KeyUse::Encapsulate { bytes } => {
+                next.encapsulations += 1;
+                next.bytes_protected = next.bytes_protected.saturating_add(bytes);
+            }
+            KeyUse::Decapsulate => next.decapsulations += 1,
+        }
+        next.last_used_at = Some(now());
+
+        if let Some(quota) = self.usage_policy.quota(key_id) {
+            if let Some(limit) = quota.hard.exceeded_by(&next) {
+                return Err(KmsError::UsageLimitExceeded(format!("{} is limited to {}", key_id, limit)));
+            }
+            if quota.soft.reached_by(&next) && !quota.soft.reached_by(&current) {
+                tracing::warn!(key_id, counters = ?next, "key reached its soft usage limit; rotate it");
+            }
+        }
+        record.metadata.usage = next;
+        self.store.put(record)
+    }
+
+    fn usage_report_of(&self, key_id: &str) -> Result<KeyUsageReport, KmsError> {
+        let counters = self.record(key_id)?.metadata.usage;
+        let quota = self.usage_policy.quota(key_id);
+        Ok(KeyUsageReport {
+            key_id: key_id.to_string(),
+            counters,
+            soft_limit_reached: quota.is_some_and(|quota| quota.soft.reached_by(&counters)),
+            hard_limit_reached: quota.is_some_and(|quota| quota.hard.reached_by(&counters)),
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::access::{administrator_policy, test_kms};
+    use super::super::ApprovalPolicy;
+    use crate::crypto::secure::SecureSecret;
+    use crate::kms::FileKeyStore;
+    use crate::monitoring::MetricsRegistry;
+
+    fn limits(encapsulations: u64, bytes: u64) -> UsageLimits {
+        UsageLimits { max_encapsulations: Some(encapsulations), max_bytes_protected: Some(bytes) }
+    }
+
+    #[test]
+    fn test_counters_and_hard_limit() {
+        let (mut kms, admin) = test_kms();
+        kms.set_usage_policy(UsagePolicy::new().with_quota("settlement:*", limits(2, 1 << 20), limits(3, 100)));
+        kms.generate_versioned_kem_key(&admin, "settlement").unwrap();
+        kms.generate_kem_key(&admin, "reporting").unwrap();
+
+        let envelope = kms.encrypt(&admin, "settlement", &[0u8; 60], b"").unwrap();
+        kms.decrypt(&admin, &envelope, b"").unwrap();
+        let usage = kms.key_usage(&admin, "settlement:1").unwrap();
+        assert_eq!((usage.counters.encapsulations, usage.counters.decapsulations), (1, 1));
+        assert_eq!(usage.counters.bytes_protected, 60);
+        assert!(usage.counters.last_used_at.is_some());
+        assert!(!usage.soft_limit_reached);
+
+        // 100 bytes may be protected in total
+        let err = kms.encrypt(&admin, "settlement", &[0u8; 41], b"").unwrap_err();
+        assert_eq!(err.code().name, "KMS_USAGE_LIMIT_EXCEEDED");
+        kms.encapsulate(&admin, "settlement:1").unwrap();
+        assert!(kms.key_usage(&admin, "settlement:1").unwrap().soft_limit_reached);
+        kms.encapsulate(&admin, "settlement:1").unwrap();
+        assert!(kms.encapsulate(&admin, "settlement:1").is_err());
+        // Refused uses are not counted, and decryption stays possible
+        assert_eq!(kms.key_usage(&admin, "settlement:1").unwrap().counters.encapsulations, 3);
+        assert!(kms.key_usage(&admin, "settlement:1").unwrap().hard_limit_reached);
+        kms.decrypt(&admin, &envelope, b"").unwrap();
+
+        // The next version starts from zero; unmatched keys are unlimited
+        kms.rotate_version(&admin, "settlement").unwrap();
+        kms.encapsulate(&admin, "settlement:2").unwrap();
+        for _ in 0..5 {
+            kms.encapsulate(&admin, "reporting").unwrap();
+        }
+        assert_eq!(kms.encapsulation_count(&admin, "reporting").unwrap(), 5);
+    }
+
+    #[test]
+    fn test_usage_metrics() {
+        let (mut kms, admin) = test_kms();
+        kms.set_usage_policy(UsagePolicy::new().with_quota("*", limits(1, 1 << 20), UsageLimits::default()));
+        kms.generate_kem_key(&admin, "inbound").unwrap();
+        kms.generate_kem_key(&admin, "idle").unwrap();
+        kms.encapsulate(&admin, "inbound").unwrap();
+
+        let report = kms.usage_report(&admin).unwrap();
+        assert_eq!(report.iter().map(|r| r.key_id.as_str()).collect::<Vec<_>>(), ["idle", "inbound"]);
+        let json = serde_json::to_value(&report[1]).unwrap();
+        assert_eq!(json["encapsulations"], 1);
+        assert_eq!(json["soft_limit_reached"], true);
+
+        let kms = Arc::new(Mutex::new(kms));
+        let registry = MetricsRegistry::new().register(Arc::new(UsageMetrics::new(kms.clone(), admin)));
+        let metrics = registry.render().unwrap();
+        assert!(metrics.contains("# TYPE kms_key_encapsulations_total counter\n"));
+        assert!(metrics.contains("kms_key_encapsulations_total{key=\"inbound\"} 1\n"));
+        assert!(metrics.contains("kms_key_encapsulations_total{key=\"idle\"} 0\n"));
+        assert!(metrics.contains("kms_key_soft_limit_reached{key=\"inbound\"} 1\n"));
+
+        // Keys the caller may not describe are left out
+        let outsider = CallerContext::new("outsider");
+        assert!(kms.lock().unwrap().usage_report(&outsider).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_counters_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let master_key = SecureSecret::from_bytes(&[0x33u8; 32]);
+        let admin = CallerContext::new("kms-admin");
+        let open = || {
+            let mut kms = KeyManagementSystem::with_store(Box::new(FileKeyStore::open(dir.path(), &master_key).unwrap()));
+            kms.set_access_policy(administrator_policy("kms-admin"));
+            kms.set_approval_policy(ApprovalPolicy::default().exempt("kms-admin"));
+            kms.set_usage_policy(UsagePolicy::new().with_quota("*", UsageLimits::default(), limits(2, 1 << 20)));
+            kms
+        };
+
+        let mut kms = open();
+        kms.generate_kem_key(&admin, "inbound").unwrap();
+        let (_, ciphertext) = kms.encapsulate(&admin, "inbound").unwrap();
+        kms.decapsulate(&admin, "inbound", &ciphertext).unwrap();
+        drop(kms);
+
+        let mut kms = open();
+        let usage = kms.key_usage(&admin, "inbound").unwrap();
+        assert_eq!((usage.counters.encapsulations, usage.counters.decapsulations), (1, 1));
+        // A restart does not reset the quota
+        kms.encapsulate(&admin, "inbound").unwrap();
+        let err = kms.encapsulate(&admin, "inbound").unwrap_err();
+        assert_eq!(err.code().name, "KMS_USAGE_LIMIT_EXCEEDED");
+    }
+}
@@ -0,0 +1,60 @@
+//! `name:version` identifiers for versioned KMS keys.
+
+use crate::error::KmsError;
+use std::fmt;
+use std::str::FromStr;
+
+pub const KEY_VERSION_SEPARATOR: char = ':';
+
+/// One version of a versioned key, written `name:version` (e.g. `settlement:3`)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KeyVersionId {
+    pub name: String,
+    pub version: u32,
+}
+
+impl KeyVersionId {
+    pub fn new(name: &str, version: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            version,
+        }
+    }
+}
+
+impl fmt::Display for KeyVersionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.name, KEY_VERSION_SEPARATOR, self.version)
+    }
+}
+
+impl FromStr for KeyVersionId {
+    type Err = KmsError;
+
+    /// Splits at the last separator, so names may themselves contain `:`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || KmsError::InvalidEnvelope(format!("not a name:version key id: {}", s));
+        let (name, version) = s.rsplit_once(KEY_VERSION_SEPARATOR).ok_or_else(invalid)?;
+        let version: u32 = version.parse().map_err(|_| invalid())?;
+        if name.is_empty() || version == 0 {
+            return Err(invalid());
+        }
+        Ok(Self::new(name, version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let id: KeyVersionId = "payments:eu:12".parse().unwrap();
+        assert_eq!(id, KeyVersionId::new("payments:eu", 12));
+        assert_eq!(id.to_string(), "payments:eu:12");
+
+        for bad in ["settlement", "settlement:", ":3", "settlement:0", "settlement:v2"] {
+            assert!(bad.parse::<KeyVersionId>().is_err(), "{}", bad);
+        }
+    }
+}
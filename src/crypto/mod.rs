@@ -1,3 +1,5 @@
 pub mod secure;
 pub mod validation;
-pub mod kms;
\ No newline at end of file
+pub mod kms;
+pub mod signature;
+pub mod x509;
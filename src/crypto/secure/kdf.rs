@@ -0,0 +1,247 @@
+//! Labeled key derivation from a [`SecureSecret`].
+//!
+//! Every derivation is bound to a label, the requested key type and a
+//! caller-supplied context, so one KEM shared secret can safely yield
+//! independent encryption, MAC and nonce-seed keys. Labels must be registered
+//! up front by the subsystem that owns them; the registry refuses to hand the
+//! same label to a second owner, and a derivation names its owner and fails
+//! for a label registered to anyone else.
+
+use super::SecureSecret;
+use anyhow::{anyhow, Result};
+use hkdf::{Hkdf, SimpleHkdf};
+use once_cell::sync::Lazy;
+use sha2::Sha256;
+use sha3::Sha3_256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tiny_keccak::{Hasher, Kmac};
+use zeroize::Zeroizing;
+
+// Domain separator prefixed to every derivation input
+const KDF_DOMAIN: &[u8] = b"pqc-kyber/kdf/v1";
+// Largest output HKDF-SHA256 can produce (255 * hash length)
+const MAX_OUTPUT_LENGTH: usize = 255 * 32;
+
+/// Key derivation functions available for labeled derivations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KdfAlgorithm {
+    /// HKDF (RFC 5869) over SHA-256
+    #[default]
+    HkdfSha256,
+    /// HKDF (RFC 5869) over SHA3-256
+    HkdfSha3_256,
+    /// KMAC256 (NIST SP 800-185) used as a KDF
+    Kmac256,
+}
+
+/// Type of key produced by a derivation; fixes its length and binds its name into the derivation
+pub trait DerivedKey {
+    const NAME: &'static str;
+    const LENGTH: usize;
+}
+
+/// 256-bit AES key
+pub struct Aes256Key;
+/// 256-bit HMAC-SHA256 key
+pub struct HmacSha256Key;
+/// 256-bit KMAC256 key
+pub struct Kmac256Key;
+/// Seed for deterministic nonce generation
+pub struct NonceSeed;
+
+impl DerivedKey for Aes256Key {
+    const NAME: &'static str = "AES-256";
+    const LENGTH: usize = 32;
+}
+
+impl DerivedKey for HmacSha256Key {
+    const NAME: &'static str = "HMAC-SHA256";
+    const LENGTH: usize = 32;
+}
+
+impl DerivedKey for Kmac256Key {
+    const NAME: &'static str = "KMAC256";
+    const LENGTH: usize = 32;
+}
+
+impl DerivedKey for NonceSeed {
+    const NAME: &'static str = "NONCE-SEED";
+    const LENGTH: usize = 32;
+}
+
+/// Maps every derivation label to the subsystem that owns it
+#[derive(Debug, Default)]
+pub struct LabelRegistry {
+    labels: HashMap<Vec<u8>, String>,
+}
+
+impl LabelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claims a label for a subsystem. Registering the same label twice for
+    /// the same owner is allowed; claiming another owner's label is not.
+    pub fn register(&mut self, label: &[u8], owner: &str) -> Result<()> {
+        if label.is_empty() {
+            return Err(anyhow!("Derivation label must not be empty"));
+        }
+
+        match self.labels.get(label) {
+            Some(existing) if existing != owner => Err(anyhow!(
+                "Derivation label '{}' is already owned by '{}'",
+                String::from_utf8_lossy(label),
+                existing
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.labels.insert(label.to_vec(), owner.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn owner_of(&self, label: &[u8]) -> Option<&str> {
+        self.labels.get(label).map(String::as_str)
+    }
+
+    pub fn is_registered(&self, label: &[u8]) -> bool {
+        self.labels.contains_key(label)
+    }
+}
+
+// Process-wide label registry shared by all subsystems
+static LABEL_REGISTRY: Lazy<Mutex<LabelRegistry>> = Lazy::new(|| Mutex::new(LabelRegistry::new()));
+
+/// Claims a derivation label for a subsystem in the global registry
+pub fn register_label(label: &[u8], owner: &str) -> Result<()> {
+    LABEL_REGISTRY
+        .lock()
+        .map_err(|_| anyhow!("Label registry lock poisoned"))?
+        .register(label, owner)
+}
+
+/// Returns the subsystem owning a label, if any
+pub fn label_owner(label: &[u8]) -> Option<String> {
+    LABEL_REGISTRY
+        .lock()
+        .ok()
+        .and_then(|registry| registry.owner_of(label).map(str::to_string))
+}
+
+/// Derives `length` bytes from `ikm` for a label registered to `owner`
+pub(crate) fn derive_bytes(
+    algorithm: KdfAlgorithm,
+    ikm: &[u8],
+    owner: &str,
+    label: &[u8],
+    key_name: &str,
+    context: &[u8],
+    length: usize,
+) -> Result<SecureSecret> {
+    if ikm.is_empty() {
+        return Err(anyhow!("Cannot derive keys from an empty secret"));
+    }
+    if length == 0 || length > MAX_OUTPUT_LENGTH {
+        return Err(anyhow!("Invalid derived key length: {}", length));
+    }
+    match label_owner(label) {
+        None => {
+            return Err(anyhow!(
+                "Derivation label '{}' is not registered",
+                String::from_utf8_lossy(label)
+            ))
+        }
+        Some(existing) if existing != owner => {
+            return Err(anyhow!(
+                "Derivation label '{}' is owned by '{}', not '{}'",
+                String::from_utf8_lossy(label),
+                existing,
+                owner
+            ))
+        }
+        Some(_) => {}
+    }
+
+    let info = encode_info(label, key_name, context, length);
+    let mut okm = Zeroizing::new(vec![0u8; length]);
+
+    match algorithm {
+        KdfAlgorithm::HkdfSha256 => Hkdf::<Sha256>::new(Some(KDF_DOMAIN), ikm)
+            .expand(&info, &mut okm)
+            .map_err(|_| anyhow!("HKDF-SHA256 expansion failed"))?,
+        KdfAlgorithm::HkdfSha3_256 => SimpleHkdf::<Sha3_256>::new(Some(KDF_DOMAIN), ikm)
+            .expand(&info, &mut okm)
+            .map_err(|_| anyhow!("HKDF-SHA3-256 expansion failed"))?,
+        KdfAlgorithm::Kmac256 => {
+            let mut kmac = Kmac::v256(ikm, KDF_DOMAIN);
+            kmac.update(&info);
+            kmac.finalize(&mut okm);
+        }
+    }
+
+    Ok(SecureSecret::from_bytes(&okm))
+}
+
+// Length-prefixed encoding so that no two (label, type, context) triples collide
+fn encode_info(label: &[u8], key_name: &str, context: &[u8], length: usize) -> Vec<u8> {
+    let mut info = Vec::with_capacity(label.len() + key_name.len() + context.len() + 24);
+    for field in [label, key_name.as_bytes(), context] {
+        info.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        info.extend_from_slice(field);
+    }
+    info.extend_from_slice(&(length as u32).to_be_bytes());
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_rejects_second_owner() {
+        let mut registry = LabelRegistry::new();
+        assert!(registry.register(b"etl/field/iban", "etl").is_ok());
+        assert!(registry.register(b"etl/field/iban", "etl").is_ok());
+        assert!(registry.register(b"etl/field/iban", "tls").is_err());
+        assert_eq!(registry.owner_of(b"etl/field/iban"), Some("etl"));
+        assert!(registry.register(b"", "etl").is_err());
+    }
+
+    #[test]
+    fn test_unregistered_label_rejected() {
+        let result = derive_bytes(KdfAlgorithm::HkdfSha256, &[1u8; 32], "tests", b"test/kdf/unregistered", "AES-256", b"", 32);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_foreign_label_rejected() {
+        register_label(b"test/kdf/foreign", "tests").unwrap();
+        let ikm = [3u8; 32];
+        assert!(derive_bytes(KdfAlgorithm::HkdfSha256, &ikm, "tests", b"test/kdf/foreign", "AES-256", b"", 32).is_ok());
+        let err = derive_bytes(KdfAlgorithm::HkdfSha256, &ikm, "etl", b"test/kdf/foreign", "AES-256", b"", 32).unwrap_err();
+        assert!(err.to_string().contains("owned by 'tests'"));
+    }
+
+    #[test]
+    fn test_algorithms_differ() {
+        register_label(b"test/kdf/algorithms", "tests").unwrap();
+        let ikm = [7u8; 32];
+
+        let outputs: Vec<SecureSecret> = [KdfAlgorithm::HkdfSha256, KdfAlgorithm::HkdfSha3_256, KdfAlgorithm::Kmac256]
+            .iter()
+            .map(|alg| derive_bytes(*alg, &ikm, "tests", b"test/kdf/algorithms", "AES-256", b"", 32).unwrap())
+            .collect();
+
+        assert_ne!(outputs[0], outputs[1]);
+        assert_ne!(outputs[1], outputs[2]);
+        assert_ne!(outputs[0], outputs[2]);
+    }
+
+    #[test]
+    fn test_info_encoding_is_unambiguous() {
+        assert_ne!(encode_info(b"ab", "c", b"", 32), encode_info(b"a", "bc", b"", 32));
+        assert_ne!(encode_info(b"a", "b", b"c", 32), encode_info(b"a", "b", b"c", 16));
+    }
+}
@@ -0,0 +1,13 @@
+mod secret;
+#[cfg(target_os = "linux")]
+mod protected;
+pub mod kdf;
+pub mod pkcs8;
+pub mod sealed;
+pub mod shamir;
+
+pub use secret::{IdleProtection, SecureSecret, SecureSecretError};
+pub use kdf::{register_label, Aes256Key, DerivedKey, HmacSha256Key, KdfAlgorithm, Kmac256Key, NonceSeed};
+pub use pkcs8::{Argon2Params, SecretKeyAlgorithm};
+pub use sealed::{KekPublicKey, KeyEncryptionKey, SealedSecret};
+pub use shamir::{ShamirSplit, Share, ShareCommitments, ShareEncoding};
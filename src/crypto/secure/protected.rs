@@ -0,0 +1,264 @@
+//! Protected memory backend for [`SecureSecret`](super::SecureSecret) on Linux.
+//!
+//! Each secret gets its own anonymous mapping laid out as
+//! `[guard page][data pages][guard page]`. The data pages are mlock'd so they
+//! never reach swap and are marked `MADV_DONTDUMP` so they are left out of core
+//! dumps. The secret is right-aligned against the trailing guard page, so a
+//! linear overflow faults immediately. Optionally the data pages are kept at
+//! `PROT_NONE` and only opened for the duration of a scoped read; a drop
+//! guard closes them again even if the reader panics.
+
+use super::secret::IdleProtection;
+use anyhow::{anyhow, Result};
+use std::ptr::{self, NonNull};
+use std::sync::Mutex;
+use zeroize::Zeroize;
+
+/// mlock'd, guard-paged allocation holding one secret
+pub struct ProtectedRegion {
+    base: NonNull<u8>,
+    mapping_len: usize,
+    data_pages: NonNull<u8>,
+    data_pages_len: usize,
+    data: NonNull<u8>,
+    len: usize,
+    idle: IdleProtection,
+    // Number of in-flight readers; pages are re-protected when it drops to zero
+    readers: Mutex<usize>,
+}
+
+// The raw pointers are owned exclusively by the region and every access is
+// serialised through `readers`, so the region can be shared across threads.
+unsafe impl Send for ProtectedRegion {}
+unsafe impl Sync for ProtectedRegion {}
+
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size <= 0 { 4096 } else { size as usize }
+}
+
+fn last_os_error(operation: &str) -> anyhow::Error {
+    anyhow!("{} failed: {}", operation, std::io::Error::last_os_error())
+}
+
+impl ProtectedRegion {
+    /// Copies `bytes` into a fresh protected mapping
+    pub fn new(bytes: &[u8], idle: IdleProtection) -> Result<Self> {
+        let page = page_size();
+        let data_pages_len = bytes.len().max(1).div_ceil(page) * page;
+        let mapping_len = data_pages_len + 2 * page;
+
+        // SAFETY: anonymous private mapping with no address hint
+        let base = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                mapping_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            return Err(last_os_error("mmap"));
+        }
+        let base = base as *mut u8;
+
+        // From here on the mapping is released by `unmap` on any error path
+        let unmap = |err: anyhow::Error| {
+            // SAFETY: base/mapping_len describe the mapping created above
+            unsafe { libc::munmap(base as *mut libc::c_void, mapping_len) };
+            err
+        };
+
+        // SAFETY: all offsets stay inside the mapping created above
+        unsafe {
+            let data_pages = base.add(page);
+            let trailing_guard = data_pages.add(data_pages_len);
+
+            if libc::mprotect(base as *mut libc::c_void, page, libc::PROT_NONE) != 0
+                || libc::mprotect(trailing_guard as *mut libc::c_void, page, libc::PROT_NONE) != 0
+            {
+                return Err(unmap(last_os_error("mprotect (guard pages)")));
+            }
+
+            if libc::mlock(data_pages as *const libc::c_void, data_pages_len) != 0 {
+                return Err(unmap(last_os_error("mlock")));
+            }
+
+            if libc::madvise(data_pages as *mut libc::c_void, data_pages_len, libc::MADV_DONTDUMP) != 0 {
+                libc::munlock(data_pages as *const libc::c_void, data_pages_len);
+                return Err(unmap(last_os_error("madvise(MADV_DONTDUMP)")));
+            }
+
+            let data = trailing_guard.sub(bytes.len());
+            ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len());
+
+            let region = Self {
+                base: NonNull::new_unchecked(base),
+                mapping_len,
+                data_pages: NonNull::new_unchecked(data_pages),
+                data_pages_len,
+                data: NonNull::new_unchecked(data),
+                len: bytes.len(),
+                idle,
+                readers: Mutex::new(0),
+            };
+
+            if idle == IdleProtection::NoAccess {
+                region.set_data_protection(libc::PROT_NONE)?;
+            }
+            Ok(region)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gives `f` read access to the secret for the duration of the call.
+    /// Fails if the pages cannot be made readable.
+    pub fn with_exposed<R>(&self, f: impl FnOnce(&[u8]) -> R) -> Result<R> {
+        let _reader = self.enter()?;
+        // SAFETY: data/len lie inside the readable data pages while a reader is registered
+        let bytes = unsafe { std::slice::from_raw_parts(self.data.as_ptr(), self.len) };
+        Ok(f(bytes))
+    }
+
+    fn enter(&self) -> Result<Reader<'_>> {
+        let mut readers = self.readers.lock().unwrap_or_else(|e| e.into_inner());
+        if *readers == 0 && self.idle == IdleProtection::NoAccess {
+            self.set_data_protection(libc::PROT_READ)?;
+        }
+        *readers += 1;
+        Ok(Reader(self))
+    }
+
+    fn leave(&self) {
+        let mut readers = self.readers.lock().unwrap_or_else(|e| e.into_inner());
+        *readers -= 1;
+        if *readers == 0 && self.idle == IdleProtection::NoAccess {
+            // Failing to re-protect leaves the pages readable but still locked; not fatal
+            let _ = self.set_data_protection(libc::PROT_NONE);
+        }
+    }
+
+    fn set_data_protection(&self, protection: libc::c_int) -> Result<()> {
+        // SAFETY: data_pages/data_pages_len describe whole pages inside our mapping
+        let rc = unsafe {
+            libc::mprotect(
+                self.data_pages.as_ptr() as *mut libc::c_void,
+                self.data_pages_len,
+                protection,
+            )
+        };
+        if rc != 0 {
+            return Err(last_os_error("mprotect"));
+        }
+        Ok(())
+    }
+}
+
+// One registered reader; leaving on drop also covers a panicking `f`
+struct Reader<'a>(&'a ProtectedRegion);
+
+impl Drop for Reader<'_> {
+    fn drop(&mut self) {
+        self.0.leave();
+    }
+}
+
+impl Drop for ProtectedRegion {
+    fn drop(&mut self) {
+        // SAFETY: we own the mapping; make it writable, wipe it, then release it
+        unsafe {
+            libc::mprotect(
+                self.data_pages.as_ptr() as *mut libc::c_void,
+                self.data_pages_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+            );
+            std::slice::from_raw_parts_mut(self.data_pages.as_ptr(), self.data_pages_len).zeroize();
+            libc::munlock(self.data_pages.as_ptr() as *const libc::c_void, self.data_pages_len);
+            libc::munmap(self.base.as_ptr() as *mut libc::c_void, self.mapping_len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let region = ProtectedRegion::new(&[1, 2, 3, 4], IdleProtection::Readable)?;
+        assert_eq!(region.len(), 4);
+        region.with_exposed(|bytes| assert_eq!(bytes, &[1, 2, 3, 4]))
+    }
+
+    #[test]
+    fn test_no_access_when_idle() -> Result<()> {
+        let data = [0x5Au8; 5000];
+        let region = ProtectedRegion::new(&data, IdleProtection::NoAccess)?;
+
+        // Repeated and nested reads must all see the data
+        for _ in 0..3 {
+            region.with_exposed(|outer| {
+                region.with_exposed(|inner| assert_eq!(outer, inner))?;
+                assert_eq!(outer, &data[..]);
+                Ok::<_, anyhow::Error>(())
+            })??;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_reprotected_after_panic() {
+        let region = ProtectedRegion::new(&[9u8; 16], IdleProtection::NoAccess).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            region.with_exposed(|_| panic!("reader failed"))
+        }));
+        assert!(result.is_err());
+        assert_eq!(*region.readers.lock().unwrap(), 0);
+        region.with_exposed(|bytes| assert_eq!(bytes, &[9u8; 16])).unwrap();
+    }
+
+    #[test]
+    fn test_secret_ends_at_guard_page() {
+        let region = ProtectedRegion::new(&[7u8; 10], IdleProtection::Readable).unwrap();
+        let end = region.data.as_ptr() as usize + region.len();
+        assert_eq!(end % page_size(), 0);
+    }
+
+    #[test]
+    fn test_empty_secret() {
+        let region = ProtectedRegion::new(&[], IdleProtection::NoAccess).unwrap();
+        assert!(region.is_empty());
+        region.with_exposed(|bytes| assert!(bytes.is_empty())).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_readers() {
+        use std::sync::Arc;
+
+        let region = Arc::new(ProtectedRegion::new(&[3u8; 64], IdleProtection::NoAccess).unwrap());
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let region = Arc::clone(&region);
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        region.with_exposed(|bytes| assert!(bytes.iter().all(|&b| b == 3))).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
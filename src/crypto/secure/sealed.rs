@@ -0,0 +1,286 @@
+//! Encrypted-at-rest form of a [`SecureSecret`].
+//!
+//! `SecureSecret` deliberately has no `Serialize` implementation; the only way
+//! to persist one is to seal it into a [`SealedSecret`]. Sealing encapsulates a
+//! fresh Kyber-1024 shared secret to a key-encryption key (KEK), derives an
+//! AES-256-GCM key from it and encrypts the secret with the KEK id and the
+//! secret's purpose as additional authenticated data. A sealed secret can only
+//! be opened with the matching KEK and for the purpose it was sealed for.
+
+use super::kdf::{register_label, Aes256Key};
+use super::{IdleProtection, SecureSecret};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, Result};
+use pqcrypto_kyber::kyber1024;
+use pqcrypto_traits::kem::{Ciphertext as _, PublicKey as _, SecretKey as _};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroizing;
+
+// Current sealed format version
+const SEALED_FORMAT_VERSION: u8 = 1;
+const NONCE_LENGTH: usize = 12;
+const SEAL_LABEL: &[u8] = b"secure/sealed/aes-256-gcm";
+const SEAL_LABEL_OWNER: &str = "crypto::secure::sealed";
+
+/// Public half of a key-encryption key; enough to seal, not to unseal
+#[derive(Clone)]
+pub struct KekPublicKey {
+    key_id: String,
+    public_key: kyber1024::PublicKey,
+}
+
+/// Kyber-1024 key-encryption key used to seal and unseal secrets
+pub struct KeyEncryptionKey {
+    public: KekPublicKey,
+    secret_key: SecureSecret,
+}
+
+/// Secret wrapped under a key-encryption key, safe to write to disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedSecret {
+    pub version: u8,
+    pub kek_id: String,
+    pub purpose: String,
+    pub kem_ciphertext: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl KeyEncryptionKey {
+    /// Generates a new Kyber-1024 KEK; the decapsulation key goes to protected memory
+    pub fn generate(key_id: &str) -> Self {
+        let (public_key, secret_key) = kyber1024::keypair();
+        Self {
+            public: KekPublicKey {
+                key_id: key_id.to_string(),
+                public_key,
+            },
+            secret_key: SecureSecret::from_decapsulation_key(&secret_key),
+        }
+    }
+
+    /// Rebuilds a KEK from raw Kyber-1024 key bytes
+    pub fn from_bytes(key_id: &str, public_key: &[u8], secret_key: &[u8]) -> Result<Self> {
+        let public_key = kyber1024::PublicKey::from_bytes(public_key)
+            .map_err(|e| anyhow!("Invalid KEK public key: {:?}", e))?;
+        kyber1024::SecretKey::from_bytes(secret_key)
+            .map_err(|e| anyhow!("Invalid KEK secret key: {:?}", e))?;
+
+        Ok(Self {
+            public: KekPublicKey {
+                key_id: key_id.to_string(),
+                public_key,
+            },
+            secret_key: SecureSecret::protected_or_heap(secret_key, IdleProtection::NoAccess),
+        })
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.public.key_id
+    }
+
+    pub fn public_key(&self) -> &KekPublicKey {
+        &self.public
+    }
+
+    /// Decrypts a sealed secret that was sealed for `purpose` under this KEK
+    pub fn unseal(&self, sealed: &SealedSecret, purpose: &str) -> Result<SecureSecret> {
+        if sealed.version != SEALED_FORMAT_VERSION {
+            return Err(anyhow!("Unsupported sealed secret version: {}", sealed.version));
+        }
+        if sealed.kek_id != self.public.key_id {
+            return Err(anyhow!(
+                "Secret was sealed under KEK '{}', not '{}'",
+                sealed.kek_id,
+                self.public.key_id
+            ));
+        }
+        if sealed.purpose != purpose {
+            return Err(anyhow!(
+                "Secret was sealed for purpose '{}', not '{}'",
+                sealed.purpose,
+                purpose
+            ));
+        }
+        if sealed.nonce.len() != NONCE_LENGTH {
+            return Err(anyhow!("BadLength: nonce expected {}, got {}", NONCE_LENGTH, sealed.nonce.len()));
+        }
+
+        let kem_ciphertext = kyber1024::Ciphertext::from_bytes(&sealed.kem_ciphertext)
+            .map_err(|e| anyhow!("Invalid KEM ciphertext: {:?}", e))?;
+        let shared = self.secret_key.with_exposed(|sk_bytes| -> Result<SecureSecret> {
+            let secret_key = kyber1024::SecretKey::from_bytes(sk_bytes)
+                .map_err(|e| anyhow!("Invalid KEK secret key: {:?}", e))?;
+            Ok(SecureSecret::from_shared(kyber1024::decapsulate(&kem_ciphertext, &secret_key)))
+        })??;
+
+        let cipher = wrapping_cipher(&shared, &sealed.kek_id, &sealed.purpose)?;
+        let plaintext = Zeroizing::new(
+            cipher
+                .decrypt(
+                    Nonce::from_slice(&sealed.nonce),
+                    Payload {
+                        msg: &sealed.ciphertext,
+                        aad: &associated_data(sealed.version, &sealed.kek_id, &sealed.purpose),
+                    },
+                )
+                .map_err(|_| anyhow!("Sealed secret failed authentication"))?,
+        );
+
+        Ok(SecureSecret::protected_or_heap(&plaintext, IdleProtection::Readable))
+    }
+}
+
+impl fmt::Debug for KeyEncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyEncryptionKey({}, [REDACTED])", self.public.key_id)
+    }
+}
+
+impl KekPublicKey {
+    pub fn from_bytes(key_id: &str, public_key: &[u8]) -> Result<Self> {
+        Ok(Self {
+            key_id: key_id.to_string(),
+            public_key: kyber1024::PublicKey::from_bytes(public_key)
+                .map_err(|e| anyhow!("Invalid KEK public key: {:?}", e))?,
+        })
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.public_key.as_bytes()
+    }
+
+    /// Encrypts a secret for `purpose` so that only the matching KEK can open it
+    pub fn seal(&self, secret: &SecureSecret, purpose: &str) -> Result<SealedSecret> {
+        let (shared, kem_ciphertext) = kyber1024::encapsulate(&self.public_key);
+        let shared = SecureSecret::from_shared(shared);
+        let cipher = wrapping_cipher(&shared, &self.key_id, purpose)?;
+
+        let mut nonce = vec![0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = secret.with_exposed(|plaintext| {
+            cipher.encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &associated_data(SEALED_FORMAT_VERSION, &self.key_id, purpose),
+                },
+            )
+        })?
+        .map_err(|_| anyhow!("Failed to seal secret"))?;
+
+        Ok(SealedSecret {
+            version: SEALED_FORMAT_VERSION,
+            kek_id: self.key_id.clone(),
+            purpose: purpose.to_string(),
+            kem_ciphertext: kem_ciphertext.as_bytes().to_vec(),
+            nonce,
+            ciphertext,
+        })
+    }
+}
+
+impl fmt::Debug for KekPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KekPublicKey({})", self.key_id)
+    }
+}
+
+impl SecureSecret {
+    /// Seals this secret under a key-encryption key for the given purpose
+    pub fn seal(&self, kek: &KekPublicKey, purpose: &str) -> Result<SealedSecret> {
+        kek.seal(self, purpose)
+    }
+}
+
+// AAD binds the format version, the KEK id and the purpose to the ciphertext
+fn associated_data(version: u8, kek_id: &str, purpose: &str) -> Vec<u8> {
+    let mut aad = vec![version];
+    for field in [kek_id.as_bytes(), purpose.as_bytes()] {
+        aad.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        aad.extend_from_slice(field);
+    }
+    aad
+}
+
+fn wrapping_cipher(shared: &SecureSecret, kek_id: &str, purpose: &str) -> Result<Aes256Gcm> {
+    register_label(SEAL_LABEL, SEAL_LABEL_OWNER)?;
+    let key = shared.derive::<Aes256Key>(SEAL_LABEL_OWNER, SEAL_LABEL, &associated_data(SEALED_FORMAT_VERSION, kek_id, purpose))?;
+    key.with_exposed(Aes256Gcm::new_from_slice)?
+        .map_err(|_| anyhow!("Invalid wrapping key length"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PURPOSE: &str = "kms/snapshot";
+
+    #[test]
+    fn test_seal_and_unseal() {
+        let kek = KeyEncryptionKey::generate("kek-1");
+        let secret = SecureSecret::from_bytes(&[0x42u8; 32]);
+
+        let sealed = secret.seal(kek.public_key(), PURPOSE).expect("Failed to seal");
+        assert_eq!(sealed.kek_id, "kek-1");
+        assert_eq!(sealed.ciphertext.len(), 32 + 16);
+
+        let opened = kek.unseal(&sealed, PURPOSE).expect("Failed to unseal");
+        assert_eq!(opened, secret);
+    }
+
+    #[test]
+    fn test_serialized_form_has_no_plaintext() {
+        let kek = KeyEncryptionKey::generate("kek-1");
+        let marker = b"PLAINTEXT-MARKER-0123456789abcdef";
+        let sealed = SecureSecret::from_bytes(marker).seal(kek.public_key(), PURPOSE).unwrap();
+
+        let json = serde_json::to_vec(&sealed).unwrap();
+        assert!(!json.windows(marker.len()).any(|w| w == marker));
+
+        let restored: SealedSecret = serde_json::from_slice(&json).unwrap();
+        kek.unseal(&restored, PURPOSE).unwrap()
+            .with_exposed(|bytes| assert_eq!(bytes, marker))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_wrong_purpose_rejected() {
+        let kek = KeyEncryptionKey::generate("kek-1");
+        let sealed = SecureSecret::from_bytes(&[1u8; 32]).seal(kek.public_key(), PURPOSE).unwrap();
+        assert!(kek.unseal(&sealed, "etl/checkpoint").is_err());
+
+        // Rewriting the purpose field breaks authentication
+        let mut relabeled = sealed.clone();
+        relabeled.purpose = "etl/checkpoint".to_string();
+        assert!(kek.unseal(&relabeled, "etl/checkpoint").is_err());
+    }
+
+    #[test]
+    fn test_wrong_kek_rejected() {
+        let kek = KeyEncryptionKey::generate("kek-1");
+        let other = KeyEncryptionKey::generate("kek-2");
+        let sealed = SecureSecret::from_bytes(&[1u8; 32]).seal(kek.public_key(), PURPOSE).unwrap();
+        assert!(other.unseal(&sealed, PURPOSE).is_err());
+
+        let mut relabeled = sealed.clone();
+        relabeled.kek_id = "kek-2".to_string();
+        assert!(other.unseal(&relabeled, PURPOSE).is_err());
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_rejected() {
+        let kek = KeyEncryptionKey::generate("kek-1");
+        let mut sealed = SecureSecret::from_bytes(&[1u8; 32]).seal(kek.public_key(), PURPOSE).unwrap();
+        sealed.ciphertext[0] ^= 0x01;
+        assert!(kek.unseal(&sealed, PURPOSE).is_err());
+    }
+}
@@ -1,16 +1,42 @@
-use pqcrypto_traits::kem::SharedSecret as SharedSecretTrait;
+use super::kdf::{self, DerivedKey, KdfAlgorithm};
+#[cfg(target_os = "linux")]
+use super::protected::ProtectedRegion;
+use crate::error::{self, KmsError};
+use anyhow::Result;
+use pqcrypto_traits::kem::{SecretKey as SecretKeyTrait, SharedSecret as SharedSecretTrait};
 use secrecy::{ExposeSecret, SecretBox};
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use zeroize::ZeroizeOnDrop;
 use std::fmt;
-use serde::{Serialize, Deserialize};
 
 
 
 /// SecureSecret wrapper for handling sensitive cryptographic material.
 /// Provides secure storage and controlled access to secret data with
 /// automatic memory zeroing when dropped.
-#[derive(Zeroize, ZeroizeOnDrop)]
-pub struct SecureSecret(SecretBox<Vec<u8>>);
+///
+/// Secrets live either on the ordinary heap or, on Linux, in a protected
+/// region (mlock'd, guard-paged, excluded from core dumps). Either way the
+/// bytes are only reachable through the scoped [`SecureSecret::with_exposed`].
+///
+/// There is intentionally no `Serialize` implementation: secrets can only be
+/// persisted in sealed form, see [`SecureSecret::seal`].
+pub struct SecureSecret(SecretStorage);
+
+enum SecretStorage {
+    Heap(SecretBox<Vec<u8>>),
+    #[cfg(target_os = "linux")]
+    Protected(ProtectedRegion),
+}
+
+/// Page protection applied to protected secrets while no caller is reading them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdleProtection {
+    /// Pages stay readable between accesses (cheaper)
+    #[default]
+    Readable,
+    /// Pages are mprotect'ed to PROT_NONE between accesses
+    NoAccess,
+}
 
 /// Custom error type for secure secret operations
 #[derive(Debug, thiserror::Error)]
@@ -19,51 +45,156 @@ pub enum SecureSecretError {
     ProcessingError,
     #[error("Invalid secret length")]
     InvalidLength,
+    #[error("Protected secret memory could not be read: {0}")]
+    Unreadable(String),
+}
+
+impl From<SecureSecretError> for KmsError {
+    fn from(err: SecureSecretError) -> Self {
+        KmsError::SecretUnavailable(err.to_string())
+    }
+}
+
+impl From<SecureSecretError> for error::Error {
+    fn from(err: SecureSecretError) -> Self {
+        KmsError::from(err).into()
+    }
 }
 
 impl SecureSecret {
     /// Creates a new SecureSecret from any type implementing SharedSecretTrait.
     /// Safely wraps the secret bytes in a protected memory location.
     pub fn from_shared<T: SharedSecretTrait>(ss: T) -> Self {
-        let secret_bytes = ss.as_bytes().to_vec();
-        Self(SecretBox::new(Box::new(secret_bytes)))
+        Self::from_bytes(ss.as_bytes())
     }
 
     /// Creates a new SecureSecret from raw bytes.
     pub fn from_bytes(bytes: &[u8]) -> Self {
-        Self(SecretBox::new(Box::new(bytes.to_vec())))
+        Self(SecretStorage::Heap(SecretBox::new(Box::new(bytes.to_vec()))))
+    }
+
+    /// Creates a SecureSecret in protected memory.
+    /// Fails if the platform is unsupported or the pages cannot be locked
+    /// (e.g. RLIMIT_MEMLOCK is exhausted).
+    pub fn protected(bytes: &[u8], idle: IdleProtection) -> Result<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            Ok(Self(SecretStorage::Protected(ProtectedRegion::new(bytes, idle)?)))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = (bytes, idle);
+            Err(anyhow::anyhow!("Protected memory is only supported on Linux"))
+        }
     }
 
-    /// Exposes the underlying secret bytes.
-    /// Warning: This method should be used with extreme caution.
-    pub fn expose(&self) -> &[u8] {
-        self.0.expose_secret()
+    /// Creates a SecureSecret in protected memory, falling back to the heap
+    /// when protected memory is unavailable.
+    pub fn protected_or_heap(bytes: &[u8], idle: IdleProtection) -> Self {
+        Self::protected(bytes, idle).unwrap_or_else(|e| {
+            tracing::warn!("Protected memory unavailable, storing secret on the heap: {}", e);
+            Self::from_bytes(bytes)
+        })
     }
 
-    /// Performs a constant-time comparison with another SecureSecret
-    pub fn constant_time_eq(&self, other: &Self) -> bool {
-        if self.expose().len() != other.expose().len() {
-            return false;
+    /// Stores a KEM decapsulation key in protected memory (PROT_NONE while idle)
+    pub fn from_decapsulation_key<T: SecretKeyTrait>(sk: &T) -> Self {
+        Self::protected_or_heap(sk.as_bytes(), IdleProtection::NoAccess)
+    }
+
+    /// Moves a heap-backed secret into protected memory. Secrets that are
+    /// already protected, or that cannot be protected, are returned unchanged.
+    pub fn into_protected(self, idle: IdleProtection) -> Self {
+        if self.is_protected() {
+            return self;
+        }
+        self.with_exposed(|bytes| Self::protected(bytes, idle).ok())
+            .ok()
+            .flatten()
+            .unwrap_or(self)
+    }
+
+    /// Returns true if the secret lives in protected memory
+    pub fn is_protected(&self) -> bool {
+        match &self.0 {
+            SecretStorage::Heap(_) => false,
+            #[cfg(target_os = "linux")]
+            SecretStorage::Protected(_) => true,
         }
+    }
 
-        let mut result = 0u8;
-        for (a, b) in self.expose().iter().zip(other.expose().iter()) {
-            result |= a ^ b;
+    /// Gives `f` read access to the secret bytes for the duration of the call.
+    /// The slice must not be copied out of the closure unless the copy is
+    /// itself zeroized. Fails only if protected pages cannot be made readable.
+    pub fn with_exposed<R>(&self, f: impl FnOnce(&[u8]) -> R) -> Result<R, SecureSecretError> {
+        match &self.0 {
+            SecretStorage::Heap(secret) => Ok(f(secret.expose_secret())),
+            #[cfg(target_os = "linux")]
+            SecretStorage::Protected(region) => {
+                region.with_exposed(f).map_err(|e| SecureSecretError::Unreadable(e.to_string()))
+            }
         }
-        result == 0
+    }
+
+    /// Performs a constant-time comparison with another SecureSecret.
+    /// Secrets that cannot be read compare unequal.
+    pub fn constant_time_eq(&self, other: &Self) -> bool {
+        self.with_exposed(|a| {
+            other.with_exposed(|b| {
+                if a.len() != b.len() {
+                    return false;
+                }
+
+                let mut result = 0u8;
+                for (x, y) in a.iter().zip(b.iter()) {
+                    result |= x ^ y;
+                }
+                result == 0
+            })
+        })
+        .and_then(|equal| equal)
+        .unwrap_or(false)
     }
 
     /// Returns the length of the secret in bytes
     pub fn len(&self) -> usize {
-        self.0.expose_secret().len()
+        match &self.0 {
+            SecretStorage::Heap(secret) => secret.expose_secret().len(),
+            #[cfg(target_os = "linux")]
+            SecretStorage::Protected(region) => region.len(),
+        }
     }
 
     /// Returns true if the secret is empty
     pub fn is_empty(&self) -> bool {
-        self.len() == 0
+        match &self.0 {
+            SecretStorage::Heap(secret) => secret.expose_secret().is_empty(),
+            #[cfg(target_os = "linux")]
+            SecretStorage::Protected(region) => region.is_empty(),
+        }
+    }
+
+    /// Derives a typed key for a label registered to `owner` using HKDF-SHA256.
+    /// The label, key type and context are all bound into the output.
+    pub fn derive<K: DerivedKey>(&self, owner: &str, label: &[u8], context: &[u8]) -> Result<SecureSecret> {
+        self.derive_with::<K>(KdfAlgorithm::default(), owner, label, context)
+    }
+
+    /// Derives a typed key for a label registered to `owner` using the given KDF
+    pub fn derive_with<K: DerivedKey>(
+        &self,
+        algorithm: KdfAlgorithm,
+        owner: &str,
+        label: &[u8],
+        context: &[u8],
+    ) -> Result<SecureSecret> {
+        self.with_exposed(|ikm| kdf::derive_bytes(algorithm, ikm, owner, label, K::NAME, context, K::LENGTH))?
     }
 }
 
+// Both storage backends wipe their memory when dropped
+impl ZeroizeOnDrop for SecureSecret {}
+
 // Prevent accidental exposure through Debug
 impl fmt::Debug for SecureSecret {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -89,7 +220,29 @@ mod tests {
     fn test_secure_secret_creation() {
         let test_data = vec![1, 2, 3, 4];
         let secret = SecureSecret::from_bytes(&test_data);
-        assert_eq!(secret.expose(), &test_data);
+        secret.with_exposed(|bytes| assert_eq!(bytes, &test_data[..])).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_protected_secret() {
+        let secret = SecureSecret::protected(&[5u8; 32], IdleProtection::NoAccess)
+            .expect("Failed to create protected secret");
+        assert!(secret.is_protected());
+        assert_eq!(secret.len(), 32);
+        secret.with_exposed(|bytes| assert_eq!(bytes, &[5u8; 32])).unwrap();
+
+        // Protected and heap secrets compare by content
+        assert_eq!(secret, SecureSecret::from_bytes(&[5u8; 32]));
+        assert_eq!(format!("{:?}", secret), "SecureSecret([REDACTED])");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_into_protected() {
+        let secret = SecureSecret::from_bytes(&[1, 2, 3]).into_protected(IdleProtection::Readable);
+        assert!(secret.is_protected());
+        secret.with_exposed(|bytes| assert_eq!(bytes, &[1, 2, 3])).unwrap();
     }
 
     #[test]
@@ -121,6 +274,35 @@ mod tests {
         assert_eq!(secret.len(), 0);
     }
 
+    #[test]
+    fn test_labeled_derivation() {
+        use crate::crypto::secure::kdf::{register_label, Aes256Key, HmacSha256Key, NonceSeed};
+
+        register_label(b"test/secret/enc", "tests").unwrap();
+        register_label(b"test/secret/mac", "tests").unwrap();
+        register_label(b"test/secret/nonce", "tests").unwrap();
+
+        // One shared secret, three independent keys
+        let shared = SecureSecret::from_bytes(&[9u8; 32]);
+        let enc = shared.derive::<Aes256Key>("tests", b"test/secret/enc", b"session-1").unwrap();
+        let mac = shared.derive::<HmacSha256Key>("tests", b"test/secret/mac", b"session-1").unwrap();
+        let nonce = shared.derive::<NonceSeed>("tests", b"test/secret/nonce", b"session-1").unwrap();
+
+        assert_eq!(enc.len(), 32);
+        assert_ne!(enc, mac);
+        assert_ne!(mac, nonce);
+
+        // Derivation is deterministic and bound to the context
+        let again = shared.derive::<Aes256Key>("tests", b"test/secret/enc", b"session-1").unwrap();
+        let other = shared.derive::<Aes256Key>("tests", b"test/secret/enc", b"session-2").unwrap();
+        assert_eq!(enc, again);
+        assert_ne!(enc, other);
+
+        // Same label, different key type gives a different key
+        let as_mac = shared.derive::<HmacSha256Key>("tests", b"test/secret/enc", b"session-1").unwrap();
+        assert_ne!(enc, as_mac);
+    }
+
     #[test]
     fn test_metadata() {
         println!("=== SecureSecret Implementation Test ===");
@@ -0,0 +1,498 @@
+//! Shamir m-of-n secret sharing over GF(2^8) for [`SecureSecret`].
+//!
+//! Each byte of the secret is shared with an independent random polynomial
+//! of degree `threshold - 1`. Splitting also produces [`ShareCommitments`]:
+//! a hash commitment to every share plus one to the secret itself. They are
+//! handed to every custodian so that a corrupted or substituted share is
+//! identified before reconstruction, and a wrong reconstruction is caught
+//! after it.
+//!
+//! Field arithmetic uses the AES polynomial and avoids lookup tables so that
+//! timing does not depend on secret bytes.
+
+use super::SecureSecret;
+use crate::utils::encoding;
+use anyhow::{anyhow, Result};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::HashSet;
+use std::fmt;
+use zeroize::Zeroizing;
+
+const SHARE_FORMAT_VERSION: u8 = 1;
+const SET_ID_LENGTH: usize = 16;
+const CHECKSUM_LENGTH: usize = 4;
+const TEXT_PREFIX: &str = "KSS1";
+const TEXT_GROUP_SIZE: usize = 4;
+
+/// Text encodings for distributing shares to custodians
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareEncoding {
+    Hex,
+    Base64,
+    /// Uppercase hex in dash-separated groups; fits the QR alphanumeric
+    /// mode and is easy to read out or type back in
+    Text,
+}
+
+/// One custodian's share of a secret
+pub struct Share {
+    set_id: [u8; SET_ID_LENGTH],
+    threshold: u8,
+    x: u8,
+    value: Zeroizing<Vec<u8>>,
+}
+
+/// Public commitments produced when a secret is split
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareCommitments {
+    pub set_id: [u8; SET_ID_LENGTH],
+    pub threshold: u8,
+    pub total_shares: u8,
+    pub share_commitments: Vec<[u8; 32]>,
+    pub secret_commitment: [u8; 32],
+}
+
+/// Result of splitting a secret: the shares and their public commitments
+pub struct ShamirSplit {
+    pub shares: Vec<Share>,
+    pub commitments: ShareCommitments,
+}
+
+// GF(2^8) multiplication modulo x^8 + x^4 + x^3 + x + 1, branch-free
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+// Multiplicative inverse as a^254 (a^-1 = a^(2^8 - 2)); inverse of 0 is 0
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn commit_share(set_id: &[u8], x: u8, value: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"pqc-kyber/shamir/share");
+    hasher.update(set_id);
+    hasher.update([x]);
+    hasher.update(value);
+    hasher.finalize().into()
+}
+
+fn commit_secret(set_id: &[u8], secret: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"pqc-kyber/shamir/secret");
+    hasher.update(set_id);
+    hasher.update(secret);
+    hasher.finalize().into()
+}
+
+/// Splits a secret into `total_shares` shares, any `threshold` of which recover it
+pub fn split(secret: &SecureSecret, threshold: u8, total_shares: u8) -> Result<ShamirSplit> {
+    if threshold < 2 {
+        return Err(anyhow!("Threshold must be at least 2, got {}", threshold));
+    }
+    if total_shares < threshold {
+        return Err(anyhow!(
+            "Cannot create {} shares with threshold {}",
+            total_shares,
+            threshold
+        ));
+    }
+    if secret.is_empty() {
+        return Err(anyhow!("Cannot split an empty secret"));
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut set_id = [0u8; SET_ID_LENGTH];
+    rng.fill_bytes(&mut set_id);
+
+    let (shares, secret_commitment) = secret.with_exposed(|bytes| {
+        let mut values: Vec<Zeroizing<Vec<u8>>> =
+            (0..total_shares).map(|_| Zeroizing::new(vec![0u8; bytes.len()])).collect();
+        let mut coefficients = Zeroizing::new(vec![0u8; threshold as usize]);
+
+        for (position, &secret_byte) in bytes.iter().enumerate() {
+            coefficients[0] = secret_byte;
+            rng.fill_bytes(&mut coefficients[1..]);
+
+            for (index, value) in values.iter_mut().enumerate() {
+                let x = index as u8 + 1;
+                // Horner evaluation of the polynomial at x
+                let mut y = 0u8;
+                for &c in coefficients.iter().rev() {
+                    y = gf_mul(y, x) ^ c;
+                }
+                value[position] = y;
+            }
+        }
+
+        let shares: Vec<Share> = values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| Share {
+                set_id,
+                threshold,
+                x: index as u8 + 1,
+                value,
+            })
+            .collect();
+
+        (shares, commit_secret(&set_id, bytes))
+    })?;
+
+    let commitments = ShareCommitments {
+        set_id,
+        threshold,
+        total_shares,
+        share_commitments: shares.iter().map(|s| commit_share(&s.set_id, s.x, &s.value)).collect(),
+        secret_commitment,
+    };
+
+    Ok(ShamirSplit { shares, commitments })
+}
+
+/// Recovers a secret from at least `threshold` shares, rejecting any share
+/// that does not match its commitment
+pub fn combine(shares: &[Share], commitments: &ShareCommitments) -> Result<SecureSecret> {
+    if commitments.threshold < 2 {
+        return Err(anyhow!("Threshold must be at least 2, got {}", commitments.threshold));
+    }
+    if commitments.share_commitments.len() != commitments.total_shares as usize {
+        return Err(anyhow!(
+            "Commitments list {} shares but cover {}",
+            commitments.total_shares,
+            commitments.share_commitments.len()
+        ));
+    }
+
+    let threshold = commitments.threshold as usize;
+    if shares.len() < threshold {
+        return Err(anyhow!(
+            "Need at least {} shares to reconstruct, got {}",
+            threshold,
+            shares.len()
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    for share in shares {
+        commitments.verify_share(share)?;
+        if !seen.insert(share.x) {
+            return Err(anyhow!("Share {} supplied more than once", share.x));
+        }
+    }
+
+    let selected = &shares[..threshold];
+    let length = selected[0].value.len();
+    if selected.iter().any(|s| s.value.len() != length) {
+        return Err(anyhow!("Shares have inconsistent lengths"));
+    }
+
+    // Lagrange basis coefficients evaluated at x = 0
+    let basis: Vec<u8> = selected
+        .iter()
+        .map(|share_i| {
+            let mut numerator = 1u8;
+            let mut denominator = 1u8;
+            for share_j in selected.iter().filter(|s| s.x != share_i.x) {
+                numerator = gf_mul(numerator, share_j.x);
+                denominator = gf_mul(denominator, share_i.x ^ share_j.x);
+            }
+            gf_mul(numerator, gf_inv(denominator))
+        })
+        .collect();
+
+    let mut secret = Zeroizing::new(vec![0u8; length]);
+    for (share, &coefficient) in selected.iter().zip(basis.iter()) {
+        for (out, &y) in secret.iter_mut().zip(share.value.iter()) {
+            *out ^= gf_mul(coefficient, y);
+        }
+    }
+
+    if commit_secret(&commitments.set_id, &secret) != commitments.secret_commitment {
+        return Err(anyhow!("Reconstructed secret does not match its commitment"));
+    }
+
+    Ok(SecureSecret::from_bytes(&secret))
+}
+
+impl ShareCommitments {
+    /// Checks that a share belongs to this set and matches its commitment
+    pub fn verify_share(&self, share: &Share) -> Result<()> {
+        if share.set_id != self.set_id {
+            return Err(anyhow!("Share {} belongs to a different share set", share.x));
+        }
+        if share.threshold != self.threshold {
+            return Err(anyhow!("Share {} has threshold {}, expected {}", share.x, share.threshold, self.threshold));
+        }
+
+        let expected = share
+            .x
+            .checked_sub(1)
+            .and_then(|i| self.share_commitments.get(i as usize))
+            .ok_or_else(|| anyhow!("Share index {} is out of range", share.x))?;
+
+        if commit_share(&share.set_id, share.x, &share.value) != *expected {
+            return Err(anyhow!("Share {} does not match its commitment", share.x));
+        }
+        Ok(())
+    }
+}
+
+impl Share {
+    /// Share index (x coordinate), 1-based
+    pub fn index(&self) -> u8 {
+        self.x
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    // version | set_id | threshold | x | len (u16) | value | checksum
+    fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut bytes = Zeroizing::new(Vec::with_capacity(
+            1 + SET_ID_LENGTH + 4 + self.value.len() + CHECKSUM_LENGTH,
+        ));
+        bytes.push(SHARE_FORMAT_VERSION);
+        bytes.extend_from_slice(&self.set_id);
+        bytes.push(self.threshold);
+        bytes.push(self.x);
+        bytes.extend_from_slice(&(self.value.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&self.value);
+        let checksum = Sha3_256::digest(&bytes[..]);
+        bytes.extend_from_slice(&checksum[..CHECKSUM_LENGTH]);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let header = 1 + SET_ID_LENGTH + 4;
+        if bytes.len() < header + CHECKSUM_LENGTH {
+            return Err(anyhow!("Share is too short"));
+        }
+
+        let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LENGTH);
+        if Sha3_256::digest(body)[..CHECKSUM_LENGTH] != *checksum {
+            return Err(anyhow!("Share checksum mismatch (transcription error?)"));
+        }
+        if body[0] != SHARE_FORMAT_VERSION {
+            return Err(anyhow!("Unsupported share format version: {}", body[0]));
+        }
+
+        let mut set_id = [0u8; SET_ID_LENGTH];
+        set_id.copy_from_slice(&body[1..1 + SET_ID_LENGTH]);
+        let threshold = body[1 + SET_ID_LENGTH];
+        let x = body[2 + SET_ID_LENGTH];
+        let length = u16::from_be_bytes([body[3 + SET_ID_LENGTH], body[4 + SET_ID_LENGTH]]) as usize;
+
+        if body.len() != header + length {
+            return Err(anyhow!("BadLength: share value expected {}, got {}", length, body.len() - header));
+        }
+        if x == 0 {
+            return Err(anyhow!("Share index must not be zero"));
+        }
+
+        Ok(Self {
+            set_id,
+            threshold,
+            x,
+            value: Zeroizing::new(body[header..].to_vec()),
+        })
+    }
+
+    /// Encodes the share for hand-over to a custodian.
+    /// Warning: the output is secret material.
+    pub fn encode(&self, share_encoding: ShareEncoding) -> String {
+        let bytes = self.to_bytes();
+        match share_encoding {
+            ShareEncoding::Hex => encoding::to_hex(&bytes),
+            ShareEncoding::Base64 => encoding::to_base64(&bytes),
+            ShareEncoding::Text => {
+                let hex = encoding::to_hex(&bytes).to_uppercase();
+                let groups: Vec<&str> = hex
+                    .as_bytes()
+                    .chunks(TEXT_GROUP_SIZE)
+                    .map(|g| std::str::from_utf8(g).unwrap_or_default())
+                    .collect();
+                format!("{}-{}", TEXT_PREFIX, groups.join("-"))
+            }
+        }
+    }
+
+    /// Decodes a share previously produced by [`Share::encode`]
+    pub fn decode(text: &str, share_encoding: ShareEncoding) -> Result<Self> {
+        let bytes = Zeroizing::new(match share_encoding {
+            ShareEncoding::Hex => encoding::from_hex(text)?,
+            ShareEncoding::Base64 => encoding::from_base64(text)?,
+            ShareEncoding::Text => {
+                let body = text
+                    .trim()
+                    .strip_prefix(TEXT_PREFIX)
+                    .ok_or_else(|| anyhow!("Share text must start with {}", TEXT_PREFIX))?;
+                let hex: String = body.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+                encoding::from_hex(&hex)?
+            }
+        });
+        Self::from_bytes(&bytes)
+    }
+}
+
+impl fmt::Debug for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Share(index: {}, threshold: {}, [REDACTED])", self.x, self.threshold)
+    }
+}
+
+impl Clone for Share {
+    fn clone(&self) -> Self {
+        Self {
+            set_id: self.set_id,
+            threshold: self.threshold,
+            x: self.x,
+            value: self.value.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master_key() -> SecureSecret {
+        SecureSecret::from_bytes(&(0..32).collect::<Vec<u8>>())
+    }
+
+    #[test]
+    fn test_gf_arithmetic() {
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1, "inverse failed for {}", a);
+        }
+        assert_eq!(gf_inv(0), 0);
+    }
+
+    #[test]
+    fn test_two_of_three() {
+        let secret = master_key();
+        let split = split(&secret, 2, 3).unwrap();
+        assert_eq!(split.shares.len(), 3);
+
+        for (a, b) in [(0, 1), (0, 2), (1, 2), (2, 0)] {
+            let pair = vec![split.shares[a].clone(), split.shares[b].clone()];
+            let recovered = combine(&pair, &split.commitments).unwrap();
+            assert_eq!(recovered, secret);
+        }
+    }
+
+    #[test]
+    fn test_below_threshold_rejected() {
+        let split = split(&master_key(), 3, 5).unwrap();
+        let two = vec![split.shares[0].clone(), split.shares[1].clone()];
+        assert!(combine(&two, &split.commitments).is_err());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(split(&master_key(), 1, 3).is_err());
+        assert!(split(&master_key(), 4, 3).is_err());
+        assert!(split(&SecureSecret::from_bytes(&[]), 2, 3).is_err());
+    }
+
+    #[test]
+    fn test_malformed_commitments_rejected() {
+        let split = split(&master_key(), 2, 3).unwrap();
+        let shares = vec![split.shares[0].clone(), split.shares[1].clone()];
+
+        for threshold in [0, 1] {
+            let commitments = ShareCommitments { threshold, ..split.commitments.clone() };
+            assert!(combine(&[], &commitments).is_err());
+            assert!(combine(&shares, &commitments).is_err());
+        }
+
+        let mut commitments = split.commitments.clone();
+        commitments.share_commitments.pop();
+        assert!(combine(&shares, &commitments).is_err());
+        commitments.total_shares = 2;
+        commitments.share_commitments.push([0u8; 32]);
+        assert!(combine(&shares, &commitments).is_err());
+    }
+
+    #[test]
+    fn test_bad_share_detected() {
+        let split = split(&master_key(), 2, 3).unwrap();
+        let mut bad = split.shares[1].clone();
+        bad.value[0] ^= 0xff;
+
+        let err = combine(&[split.shares[0].clone(), bad], &split.commitments).unwrap_err();
+        assert!(err.to_string().contains("Share 2"));
+    }
+
+    #[test]
+    fn test_duplicate_share_rejected() {
+        let split = split(&master_key(), 2, 3).unwrap();
+        let shares = vec![split.shares[0].clone(), split.shares[0].clone()];
+        assert!(combine(&shares, &split.commitments).is_err());
+    }
+
+    #[test]
+    fn test_shares_from_other_set_rejected() {
+        let first = split(&master_key(), 2, 3).unwrap();
+        let second = split(&master_key(), 2, 3).unwrap();
+        let mixed = vec![first.shares[0].clone(), second.shares[1].clone()];
+        assert!(combine(&mixed, &first.commitments).is_err());
+    }
+
+    #[test]
+    fn test_encoding_round_trip() {
+        let split = split(&master_key(), 2, 3).unwrap();
+
+        for share_encoding in [ShareEncoding::Hex, ShareEncoding::Base64, ShareEncoding::Text] {
+            let decoded: Vec<Share> = split.shares[..2]
+                .iter()
+                .map(|s| Share::decode(&s.encode(share_encoding), share_encoding).unwrap())
+                .collect();
+            assert_eq!(combine(&decoded, &split.commitments).unwrap(), master_key());
+        }
+    }
+
+    #[test]
+    fn test_text_encoding_is_qr_alphanumeric() {
+        let split = split(&master_key(), 2, 3).unwrap();
+        let text = split.shares[0].encode(ShareEncoding::Text);
+        assert!(text.starts_with("KSS1-"));
+        assert!(text.chars().all(|c| c.is_ascii_digit() || c.is_ascii_uppercase() || c == '-'));
+    }
+
+    #[test]
+    fn test_transcription_error_detected() {
+        let split = split(&master_key(), 2, 3).unwrap();
+        let mut text = split.shares[0].encode(ShareEncoding::Text);
+        let last = text.pop().unwrap();
+        text.push(if last == '0' { '1' } else { '0' });
+        assert!(Share::decode(&text, ShareEncoding::Text).is_err());
+    }
+
+    #[test]
+    fn test_debug_hides_value() {
+        let split = split(&master_key(), 2, 3).unwrap();
+        assert_eq!(format!("{:?}", split.shares[0]), "Share(index: 1, threshold: 2, [REDACTED])");
+    }
+}
@@ -0,0 +1,302 @@
+//! Long-term sealing of archived ledgers and audit checkpoints with SLH-DSA.
+//!
+//! A manifest lists every file of an archive with its size and streamed
+//! digest; the manifest as a whole is then signed. Verification checks the
+//! signature first and then re-hashes every file on disk.
+
+use super::slhdsa::{ArchiveDigest, ArchiveHasher, PreHashAlgorithm, SlhDsaSignature, SlhDsaSigningKey, SlhDsaVerifyingKey};
+use crate::config::{get_current_user, get_formatted_timestamp};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+
+// Context strings keeping manifest and checkpoint signatures apart
+pub const ARCHIVE_MANIFEST_CONTEXT: &[u8] = b"pqc-kyber/archive/manifest/v1";
+pub const AUDIT_CHECKPOINT_CONTEXT: &[u8] = b"pqc-kyber/kms/audit-checkpoint/v1";
+
+/// Single file covered by an archive manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the archive root
+    pub path: String,
+    pub size: u64,
+    pub digest: ArchiveDigest,
+}
+
+/// List of archived files and their digests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub archive_id: String,
+    pub algorithm: PreHashAlgorithm,
+    pub created_at: String,
+    pub created_by: String,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Manifest together with its SLH-DSA signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedManifest {
+    pub manifest: ArchiveManifest,
+    pub signer_id: String,
+    pub signature: SlhDsaSignature,
+}
+
+/// Head of an audit log at a given sequence number
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditCheckpoint {
+    pub sequence: u64,
+    pub head_hash: Vec<u8>,
+    pub timestamp: String,
+}
+
+/// Audit checkpoint together with its SLH-DSA signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedCheckpoint {
+    pub checkpoint: AuditCheckpoint,
+    pub signer_id: String,
+    pub signature: SlhDsaSignature,
+}
+
+impl ArchiveManifest {
+    pub fn new(archive_id: &str, algorithm: PreHashAlgorithm) -> Self {
+        Self {
+            archive_id: archive_id.to_string(),
+            algorithm,
+            created_at: get_formatted_timestamp(),
+            created_by: get_current_user(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Streams a file under `root` through the hasher and records it in the manifest
+    pub fn add_file(&mut self, root: impl AsRef<Path>, relative_path: &str) -> Result<()> {
+        let full_path = archive_path(root.as_ref(), relative_path)?;
+        if self.entries.iter().any(|e| e.path == relative_path) {
+            return Err(anyhow!("File {} is already in the manifest", relative_path));
+        }
+
+        let size = std::fs::metadata(&full_path)
+            .map_err(|e| anyhow!("Failed to stat {}: {}", full_path.display(), e))?
+            .len();
+        let digest = ArchiveHasher::hash_file(self.algorithm, &full_path)?;
+
+        self.entries.push(ManifestEntry {
+            path: relative_path.to_string(),
+            size,
+            digest,
+        });
+        Ok(())
+    }
+
+    /// Canonical bytes covered by the manifest signature
+    pub fn signing_payload(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    /// Signs the manifest with an SLH-DSA key
+    pub fn seal(self, signer_id: &str, key: &SlhDsaSigningKey) -> Result<SealedManifest> {
+        if self.algorithm != key.parameter_set().pre_hash() {
+            return Err(anyhow!(
+                "Manifest digests use {:?} but {} expects {:?}",
+                self.algorithm,
+                key.parameter_set(),
+                key.parameter_set().pre_hash()
+            ));
+        }
+
+        let signature = key.sign(&self.signing_payload(), ARCHIVE_MANIFEST_CONTEXT)?;
+        Ok(SealedManifest {
+            manifest: self,
+            signer_id: signer_id.to_string(),
+            signature,
+        })
+    }
+}
+
+impl SealedManifest {
+    /// Checks the manifest signature only
+    pub fn verify_signature(&self, key: &SlhDsaVerifyingKey) -> Result<()> {
+        key.verify(&self.manifest.signing_payload(), ARCHIVE_MANIFEST_CONTEXT, &self.signature)
+    }
+
+    /// Checks the signature and re-hashes every listed file under `root`
+    pub fn verify(&self, key: &SlhDsaVerifyingKey, root: impl AsRef<Path>) -> Result<()> {
+        self.verify_signature(key)?;
+
+        for entry in &self.manifest.entries {
+            let full_path = archive_path(root.as_ref(), &entry.path)?;
+            let size = std::fs::metadata(&full_path)
+                .map_err(|e| anyhow!("Archived file {} is missing: {}", entry.path, e))?
+                .len();
+            if size != entry.size {
+                return Err(anyhow!(
+                    "Archived file {} changed size: expected {}, found {}",
+                    entry.path, entry.size, size
+                ));
+            }
+
+            let digest = ArchiveHasher::hash_file(self.manifest.algorithm, &full_path)?;
+            if digest != entry.digest {
+                return Err(anyhow!("Archived file {} does not match its digest", entry.path));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AuditCheckpoint {
+    pub fn new(sequence: u64, head_hash: &[u8]) -> Self {
+        Self {
+            sequence,
+            head_hash: head_hash.to_vec(),
+            timestamp: get_formatted_timestamp(),
+        }
+    }
+
+    pub fn signing_payload(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+
+    pub fn seal(self, signer_id: &str, key: &SlhDsaSigningKey) -> Result<SealedCheckpoint> {
+        let signature = key.sign(&self.signing_payload(), AUDIT_CHECKPOINT_CONTEXT)?;
+        Ok(SealedCheckpoint {
+            checkpoint: self,
+            signer_id: signer_id.to_string(),
+            signature,
+        })
+    }
+}
+
+impl SealedCheckpoint {
+    pub fn verify(&self, key: &SlhDsaVerifyingKey) -> Result<()> {
+        key.verify(&self.checkpoint.signing_payload(), AUDIT_CHECKPOINT_CONTEXT, &self.signature)
+    }
+}
+
+// Manifest paths name files inside the archive root only: no absolute
+// paths, `..`, or `.` components that would alias another entry
+fn archive_path(root: &Path, relative_path: &str) -> Result<PathBuf> {
+    let path = Path::new(relative_path);
+    let inside_root = !relative_path.is_empty() && path.components().all(|component| matches!(component, Component::Normal(_)));
+    if !inside_root {
+        return Err(anyhow!("Archive path {:?} is not relative to the archive root", relative_path));
+    }
+    Ok(root.join(path))
+}
+
+/// Sealed archive registered for periodic re-verification
+#[derive(Debug, Clone)]
+pub struct RegisteredArchive {
+    pub sealed: SealedManifest,
+    pub root: PathBuf,
+    pub verifying_key: SlhDsaVerifyingKey,
+}
+
+/// Archives and checkpoints the security audit re-verifies on every run
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveRegistry {
+    archives: Vec<RegisteredArchive>,
+    checkpoints: Vec<(SealedCheckpoint, SlhDsaVerifyingKey)>,
+}
+
+impl ArchiveRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register_archive(&mut self, sealed: SealedManifest, root: impl Into<PathBuf>, verifying_key: SlhDsaVerifyingKey) {
+        self.archives.push(RegisteredArchive {
+            sealed,
+            root: root.into(),
+            verifying_key,
+        });
+    }
+
+    pub fn register_checkpoint(&mut self, sealed: SealedCheckpoint, verifying_key: SlhDsaVerifyingKey) {
+        self.checkpoints.push((sealed, verifying_key));
+    }
+
+    pub fn archives(&self) -> &[RegisteredArchive] {
+        &self.archives
+    }
+
+    pub fn checkpoints(&self) -> &[(SealedCheckpoint, SlhDsaVerifyingKey)] {
+        &self.checkpoints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signature::slhdsa::SlhDsaParameterSet;
+
+    fn create_archive_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("ledger-2023.csv"), b"id,amount\n1,100.00\n2,250.00\n").unwrap();
+        std::fs::write(dir.path().join("ledger-2024.csv"), vec![b'x'; 100_000]).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_seal_and_verify_manifest() {
+        let archive = create_archive_dir();
+        let dir = archive.path();
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128f);
+
+        let mut manifest = ArchiveManifest::new("ledger-archive-2024", key.parameter_set().pre_hash());
+        manifest.add_file(dir, "ledger-2023.csv").unwrap();
+        manifest.add_file(dir, "ledger-2024.csv").unwrap();
+        assert!(manifest.add_file(dir, "ledger-2024.csv").is_err());
+
+        let sealed = manifest.seal("archive-officer", &key).unwrap();
+        assert!(sealed.verify(key.verifying_key(), dir).is_ok());
+
+        std::fs::write(dir.join("ledger-2023.csv"), b"id,amount\n1,999.00\n2,250.00\n").unwrap();
+        assert!(sealed.verify(key.verifying_key(), dir).is_err());
+    }
+
+    #[test]
+    fn test_manifest_tampering_detected() {
+        let archive = create_archive_dir();
+        let dir = archive.path();
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Shake128f);
+
+        let mut manifest = ArchiveManifest::new("ledger-archive", key.parameter_set().pre_hash());
+        manifest.add_file(dir, "ledger-2023.csv").unwrap();
+        let mut sealed = manifest.seal("archive-officer", &key).unwrap();
+
+        sealed.manifest.entries.clear();
+        assert!(sealed.verify_signature(key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_paths_outside_root_rejected() {
+        let archive = create_archive_dir();
+        let dir = archive.path();
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128f);
+
+        let mut manifest = ArchiveManifest::new("ledger-archive", key.parameter_set().pre_hash());
+        let outside = dir.join("ledger-2023.csv").display().to_string();
+        for path in [outside.as_str(), "../ledger-2023.csv", "sub/../../ledger-2023.csv", "./ledger-2023.csv", ""] {
+            assert!(manifest.add_file(dir, path).is_err(), "{:?} accepted", path);
+        }
+
+        // A signed manifest naming a file outside the root still fails verification
+        manifest.add_file(dir, "ledger-2023.csv").unwrap();
+        manifest.entries[0].path = format!("../{}/ledger-2023.csv", dir.file_name().unwrap().to_str().unwrap());
+        let sealed = manifest.seal("archive-officer", &key).unwrap();
+        assert!(sealed.verify_signature(key.verifying_key()).is_ok());
+        assert!(sealed.verify(key.verifying_key(), dir).is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_seal() {
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128f);
+        let sealed = AuditCheckpoint::new(42, &[7u8; 32]).seal("kms", &key).unwrap();
+        assert!(sealed.verify(key.verifying_key()).is_ok());
+
+        let mut forged = sealed.clone();
+        forged.checkpoint.sequence = 41;
+        assert!(forged.verify(key.verifying_key()).is_err());
+    }
+}
@@ -0,0 +1,449 @@
+//! ML-DSA (FIPS 204) digital signatures.
+//!
+//! Keys are typed by parameter set so that a signature made with ML-DSA-65
+//! can never be checked against an ML-DSA-44 key by accident. Signing keys
+//! are held in a [`SecureSecret`] and are exported through the same
+//! [`KeyEncoding`] formats as the KEM keys.
+
+use crate::crypto::secure::SecureSecret;
+use crate::error::ValidationError;
+use crate::utils::encoding::{self, KeyEncoding};
+use anyhow::{anyhow, Result};
+use ml_dsa::{
+    EncodedSignature, EncodedSigningKey, EncodedVerifyingKey, KeyGen, MlDsa44, MlDsa65, MlDsa87,
+    MlDsaParams, Signature, SigningKey, VerifyingKey,
+};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha3::digest::ExtendableOutput;
+use sha3::Shake256;
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// Maximum length of an ML-DSA context string (FIPS 204, section 5.2)
+pub const MAX_CONTEXT_LENGTH: usize = 255;
+
+// Leading fields of an encoded secret key (FIPS 204, section 7.2)
+const RHO_LENGTH: usize = 32;
+const TR_LENGTH: usize = 64;
+// Signed when a key pair is restored, to check the halves belong together
+const KEY_PAIR_PROBE: &[u8] = b"pqc-kyber/ml-dsa/key-pair-check";
+
+/// ML-DSA parameter sets defined in FIPS 204
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MlDsaParameterSet {
+    /// NIST security category 2
+    MlDsa44,
+    /// NIST security category 3
+    MlDsa65,
+    /// NIST security category 5
+    MlDsa87,
+}
+
+/// Selects how the per-signature randomness is produced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningMode {
+    /// Same key, message and context always give the same signature
+    Deterministic,
+    /// Fresh randomness is mixed in, hardening against fault and side-channel attacks
+    Hedged,
+}
+
+impl MlDsaParameterSet {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::MlDsa44 => "ML-DSA-44",
+            Self::MlDsa65 => "ML-DSA-65",
+            Self::MlDsa87 => "ML-DSA-87",
+        }
+    }
+
+    pub fn verifying_key_size(&self) -> usize {
+        match self {
+            Self::MlDsa44 => 1312,
+            Self::MlDsa65 => 1952,
+            Self::MlDsa87 => 2592,
+        }
+    }
+
+    pub fn signing_key_size(&self) -> usize {
+        match self {
+            Self::MlDsa44 => 2560,
+            Self::MlDsa65 => 4032,
+            Self::MlDsa87 => 4896,
+        }
+    }
+
+    pub fn signature_size(&self) -> usize {
+        match self {
+            Self::MlDsa44 => 2420,
+            Self::MlDsa65 => 3309,
+            Self::MlDsa87 => 4627,
+        }
+    }
+
+    fn public_key_label(&self) -> String {
+        format!("{} PUBLIC KEY", self.name())
+    }
+
+    fn secret_key_label(&self) -> String {
+        format!("{} SECRET KEY", self.name())
+    }
+}
+
+impl fmt::Display for MlDsaParameterSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// Runs a generic helper with the concrete parameter type selected at runtime
+macro_rules! with_params {
+    ($set:expr, $func:ident ( $($arg:expr),* )) => {
+        match $set {
+            MlDsaParameterSet::MlDsa44 => $func::<MlDsa44>($($arg),*),
+            MlDsaParameterSet::MlDsa65 => $func::<MlDsa65>($($arg),*),
+            MlDsaParameterSet::MlDsa87 => $func::<MlDsa87>($($arg),*),
+        }
+    };
+}
+
+/// ML-DSA public (verifying) key
+#[derive(Clone, PartialEq, Eq)]
+pub struct MlDsaVerifyingKey {
+    parameter_set: MlDsaParameterSet,
+    bytes: Vec<u8>,
+}
+
+/// ML-DSA secret (signing) key with its matching verifying key
+pub struct MlDsaSigningKey {
+    parameter_set: MlDsaParameterSet,
+    secret: SecureSecret,
+    verifying_key: MlDsaVerifyingKey,
+}
+
+/// Detached ML-DSA signature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MlDsaSignature {
+    pub parameter_set: MlDsaParameterSet,
+    pub bytes: Vec<u8>,
+}
+
+impl MlDsaSigningKey {
+    /// Generates a fresh key pair for the given parameter set
+    pub fn generate(parameter_set: MlDsaParameterSet) -> Self {
+        let (signing_key, verifying_key) = with_params!(parameter_set, generate_with());
+
+        Self {
+            parameter_set,
+            secret: SecureSecret::from_bytes(&signing_key),
+            verifying_key: MlDsaVerifyingKey {
+                parameter_set,
+                bytes: verifying_key,
+            },
+        }
+    }
+
+    /// Restores a signing key from its encoded form.
+    /// The verifying key must be supplied as ML-DSA secret keys do not embed it in usable form;
+    /// it is checked against the seed and key hash the secret key does embed, and a probe
+    /// signature must verify under it, so a key pair cannot be assembled from mismatched halves.
+    pub fn decode(
+        parameter_set: MlDsaParameterSet,
+        data: &[u8],
+        encoding: KeyEncoding,
+        verifying_key: MlDsaVerifyingKey,
+    ) -> Result<Self> {
+        if verifying_key.parameter_set != parameter_set {
+            return Err(anyhow!(
+                "Parameter set mismatch: signing key is {}, verifying key is {}",
+                parameter_set,
+                verifying_key.parameter_set
+            ));
+        }
+
+        let bytes = Zeroizing::new(encoding::decode_key(data, &parameter_set.secret_key_label(), encoding)?);
+        check_length("signing key", parameter_set.signing_key_size(), bytes.len())?;
+        // The secret key starts rho || K || tr, with rho also leading the public key and
+        // tr = H(pk); both are public, so a plain comparison will do
+        let mut tr = [0u8; TR_LENGTH];
+        Shake256::digest_xof(&verifying_key.bytes, &mut tr);
+        if bytes[..RHO_LENGTH] != verifying_key.bytes[..RHO_LENGTH] || bytes[2 * RHO_LENGTH..2 * RHO_LENGTH + TR_LENGTH] != tr {
+            return Err(anyhow!("Signing key does not match the {} verifying key", parameter_set));
+        }
+
+        let key = Self {
+            parameter_set,
+            secret: SecureSecret::from_bytes(&bytes),
+            verifying_key,
+        };
+        let probe = key.sign(KEY_PAIR_PROBE, b"", SigningMode::Deterministic)?;
+        key.verifying_key
+            .verify(KEY_PAIR_PROBE, b"", &probe)
+            .map_err(|_| anyhow!("Signing key does not match the {} verifying key", parameter_set))?;
+        Ok(key)
+    }
+
+    /// Exports the signing key in the requested encoding.
+    /// Warning: the output contains secret key material.
+    pub fn encode(&self, encoding: KeyEncoding) -> Result<Vec<u8>> {
+        Ok(self.secret.with_exposed(|bytes| {
+            encoding::encode_key(bytes, &self.parameter_set.secret_key_label(), encoding)
+        })?)
+    }
+
+    pub fn parameter_set(&self) -> MlDsaParameterSet {
+        self.parameter_set
+    }
+
+    pub fn verifying_key(&self) -> &MlDsaVerifyingKey {
+        &self.verifying_key
+    }
+
+    /// Signs a message under the given context string
+    pub fn sign(&self, message: &[u8], context: &[u8], mode: SigningMode) -> Result<MlDsaSignature> {
+        check_context(context)?;
+
+        let bytes = self.secret.with_exposed(|secret| {
+            with_params!(self.parameter_set, sign_with(secret, message, context, mode))
+        })??;
+
+        Ok(MlDsaSignature {
+            parameter_set: self.parameter_set,
+            bytes,
+        })
+    }
+}
+
+impl fmt::Debug for MlDsaSigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MlDsaSigningKey({}, [REDACTED])", self.parameter_set)
+    }
+}
+
+impl MlDsaVerifyingKey {
+    /// Restores a verifying key from its encoded form
+    pub fn decode(parameter_set: MlDsaParameterSet, data: &[u8], encoding: KeyEncoding) -> Result<Self> {
+        let bytes = encoding::decode_key(data, &parameter_set.public_key_label(), encoding)?;
+        check_length("verifying key", parameter_set.verifying_key_size(), bytes.len())?;
+        Ok(Self { parameter_set, bytes })
+    }
+
+    /// Exports the verifying key in the requested encoding
+    pub fn encode(&self, encoding: KeyEncoding) -> Vec<u8> {
+        encoding::encode_key(&self.bytes, &self.parameter_set.public_key_label(), encoding)
+    }
+
+    pub fn parameter_set(&self) -> MlDsaParameterSet {
+        self.parameter_set
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Verifies a detached signature over a message and context string
+    pub fn verify(&self, message: &[u8], context: &[u8], signature: &MlDsaSignature) -> Result<()> {
+        check_context(context)?;
+
+        if signature.parameter_set != self.parameter_set {
+            return Err(anyhow!(
+                "Parameter set mismatch: key is {}, signature is {}",
+                self.parameter_set,
+                signature.parameter_set
+            ));
+        }
+        check_length("signature", self.parameter_set.signature_size(), signature.bytes.len())?;
+
+        let valid = with_params!(
+            self.parameter_set,
+            verify_with(&self.bytes, message, context, &signature.bytes)
+        );
+
+        if valid {
+            Ok(())
+        } else {
+            Err(ValidationError::SignatureInvalid(self.parameter_set.to_string()).into())
+        }
+    }
+}
+
+impl fmt::Debug for MlDsaVerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MlDsaVerifyingKey({}, {})", self.parameter_set, encoding::to_hex(&self.bytes[..8]))
+    }
+}
+
+fn check_context(context: &[u8]) -> Result<()> {
+    if context.len() > MAX_CONTEXT_LENGTH {
+        return Err(anyhow!(
+            "Context string too long: {} bytes (max {})",
+            context.len(),
+            MAX_CONTEXT_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+fn check_length(what: &str, expected: usize, actual: usize) -> Result<()> {
+    if expected != actual {
+        return Err(anyhow!("BadLength: {} expected {}, got {}", what, expected, actual));
+    }
+    Ok(())
+}
+
+fn generate_with<P: MlDsaParams>() -> (Vec<u8>, Vec<u8>) {
+    let key_pair = P::key_gen(&mut OsRng);
+    (
+        key_pair.signing_key().encode().to_vec(),
+        key_pair.verifying_key().encode().to_vec(),
+    )
+}
+
+fn sign_with<P: MlDsaParams>(
+    signing_key: &[u8],
+    message: &[u8],
+    context: &[u8],
+    mode: SigningMode,
+) -> Result<Vec<u8>> {
+    let encoded = EncodedSigningKey::<P>::try_from(signing_key)
+        .map_err(|_| anyhow!("Malformed ML-DSA signing key"))?;
+    let key = SigningKey::<P>::decode(&encoded);
+
+    let signature = match mode {
+        SigningMode::Deterministic => key.sign_deterministic(message, context),
+        SigningMode::Hedged => key.sign_randomized(message, context, &mut OsRng),
+    }
+    .map_err(|_| anyhow!("ML-DSA signing failed"))?;
+
+    Ok(signature.encode().to_vec())
+}
+
+fn verify_with<P: MlDsaParams>(
+    verifying_key: &[u8],
+    message: &[u8],
+    context: &[u8],
+    signature: &[u8],
+) -> bool {
+    let (Ok(encoded_key), Ok(encoded_sig)) = (
+        EncodedVerifyingKey::<P>::try_from(verifying_key),
+        EncodedSignature::<P>::try_from(signature),
+    ) else {
+        return false;
+    };
+
+    let key = VerifyingKey::<P>::decode(&encoded_key);
+    match Signature::<P>::decode(&encoded_sig) {
+        Some(signature) => key.verify_with_context(message, context, &signature),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_SETS: [MlDsaParameterSet; 3] = [
+        MlDsaParameterSet::MlDsa44,
+        MlDsaParameterSet::MlDsa65,
+        MlDsaParameterSet::MlDsa87,
+    ];
+
+    #[test]
+    fn test_sign_and_verify_all_parameter_sets() {
+        for set in ALL_SETS {
+            let key = MlDsaSigningKey::generate(set);
+            assert_eq!(key.verifying_key().as_bytes().len(), set.verifying_key_size());
+
+            let signature = key.sign(b"payment", b"ctx", SigningMode::Hedged)
+                .expect("Failed to sign");
+            assert_eq!(signature.bytes.len(), set.signature_size());
+            assert!(key.verifying_key().verify(b"payment", b"ctx", &signature).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_deterministic_signatures_repeat() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let first = key.sign(b"message", b"", SigningMode::Deterministic).unwrap();
+        let second = key.sign(b"message", b"", SigningMode::Deterministic).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_hedged_signatures_differ() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let first = key.sign(b"message", b"", SigningMode::Hedged).unwrap();
+        let second = key.sign(b"message", b"", SigningMode::Hedged).unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_context_is_bound() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44);
+        let signature = key.sign(b"message", b"etl", SigningMode::Hedged).unwrap();
+        assert!(key.verifying_key().verify(b"message", b"kms", &signature).is_err());
+        assert!(key.verifying_key().verify(b"tampered", b"etl", &signature).is_err());
+    }
+
+    #[test]
+    fn test_context_too_long() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44);
+        let context = [0u8; MAX_CONTEXT_LENGTH + 1];
+        assert!(key.sign(b"message", &context, SigningMode::Hedged).is_err());
+    }
+
+    #[test]
+    fn test_parameter_set_mismatch() {
+        let key44 = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44);
+        let key65 = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let signature = key44.sign(b"message", b"", SigningMode::Hedged).unwrap();
+        assert!(key65.verifying_key().verify(b"message", b"", &signature).is_err());
+    }
+
+    #[test]
+    fn test_key_encoding_round_trip() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+
+        let pem = key.verifying_key().encode(KeyEncoding::Pem);
+        let verifying_key = MlDsaVerifyingKey::decode(MlDsaParameterSet::MlDsa65, &pem, KeyEncoding::Pem)
+            .expect("Failed to decode verifying key");
+        assert_eq!(&verifying_key, key.verifying_key());
+
+        let exported = key.encode(KeyEncoding::Base64).unwrap();
+        let restored = MlDsaSigningKey::decode(
+            MlDsaParameterSet::MlDsa65,
+            &exported,
+            KeyEncoding::Base64,
+            verifying_key,
+        )
+        .expect("Failed to decode signing key");
+
+        let signature = restored.sign(b"message", b"", SigningMode::Deterministic).unwrap();
+        assert!(key.verifying_key().verify(b"message", b"", &signature).is_ok());
+    }
+
+    #[test]
+    fn test_mismatched_key_pair_rejected() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44);
+        let other = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44);
+        let exported = key.encode(KeyEncoding::Raw).unwrap();
+        let decode = |verifying_key: &MlDsaVerifyingKey| {
+            MlDsaSigningKey::decode(MlDsaParameterSet::MlDsa44, &exported, KeyEncoding::Raw, verifying_key.clone())
+        };
+        assert!(decode(other.verifying_key()).is_err());
+
+        // Right seed and hash, but secret vectors from another key
+        let mut spliced = other.encode(KeyEncoding::Raw).unwrap();
+        spliced[..RHO_LENGTH * 2 + TR_LENGTH].copy_from_slice(&exported[..RHO_LENGTH * 2 + TR_LENGTH]);
+        let decoded = MlDsaSigningKey::decode(MlDsaParameterSet::MlDsa44, &spliced, KeyEncoding::Raw, key.verifying_key().clone());
+        assert!(decoded.is_err());
+        assert!(decode(key.verifying_key()).is_ok());
+    }
+
+    #[test]
+    fn test_debug_hides_secret() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44);
+        assert_eq!(format!("{:?}", key), "MlDsaSigningKey(ML-DSA-44, [REDACTED])");
+    }
+}
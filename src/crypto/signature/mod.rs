@@ -0,0 +1,104 @@
+pub mod mldsa;
+pub mod slhdsa;
+pub mod archive;
+
+pub use mldsa::{
+    MlDsaParameterSet, MlDsaSignature, MlDsaSigningKey, MlDsaVerifyingKey, SigningMode,
+};
+pub use slhdsa::{
+    ArchiveDigest, ArchiveHasher, PreHashAlgorithm, SlhDsaParameterSet, SlhDsaSignature,
+    SlhDsaSigningKey, SlhDsaVerifyingKey,
+};
+pub use archive::{ArchiveManifest, ArchiveRegistry, AuditCheckpoint, SealedCheckpoint, SealedManifest};
+
+use crate::error::ValidationError;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Signature attached to a signed object together with the id of its signer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetachedSignature {
+    pub signer_id: String,
+    pub signature: MlDsaSignature,
+}
+
+/// Set of verifying keys accepted when checking detached signatures
+#[derive(Debug, Clone, Default)]
+pub struct TrustedSigners {
+    keys: HashMap<String, MlDsaVerifyingKey>,
+}
+
+impl DetachedSignature {
+    /// Signs a message and records the signer id alongside the signature
+    pub fn create(
+        signer_id: &str,
+        key: &MlDsaSigningKey,
+        message: &[u8],
+        context: &[u8],
+        mode: SigningMode,
+    ) -> Result<Self> {
+        Ok(Self {
+            signer_id: signer_id.to_string(),
+            signature: key.sign(message, context, mode)?,
+        })
+    }
+}
+
+impl TrustedSigners {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_signer(&mut self, signer_id: &str, key: MlDsaVerifyingKey) -> Result<()> {
+        if self.keys.contains_key(signer_id) {
+            return Err(anyhow!("Signer {} is already trusted", signer_id));
+        }
+        self.keys.insert(signer_id.to_string(), key);
+        Ok(())
+    }
+
+    pub fn remove_signer(&mut self, signer_id: &str) -> Option<MlDsaVerifyingKey> {
+        self.keys.remove(signer_id)
+    }
+
+    pub fn get_signer(&self, signer_id: &str) -> Option<&MlDsaVerifyingKey> {
+        self.keys.get(signer_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Verifies a detached signature against the key registered for its signer
+    pub fn verify(&self, detached: &DetachedSignature, message: &[u8], context: &[u8]) -> Result<()> {
+        let key = self
+            .keys
+            .get(&detached.signer_id)
+            .ok_or_else(|| ValidationError::UnknownSigner(detached.signer_id.clone()))?;
+        key.verify(message, context, &detached.signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trusted_signers() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let mut signers = TrustedSigners::new();
+        signers.add_signer("bank-a", key.verifying_key().clone()).unwrap();
+        assert!(signers.add_signer("bank-a", key.verifying_key().clone()).is_err());
+
+        let detached = DetachedSignature::create("bank-a", &key, b"msg", b"", SigningMode::Hedged).unwrap();
+        assert!(signers.verify(&detached, b"msg", b"").is_ok());
+
+        let unknown = DetachedSignature { signer_id: "bank-b".to_string(), ..detached };
+        assert!(signers.verify(&unknown, b"msg", b"").is_err());
+    }
+}
@@ -0,0 +1,582 @@
+//! SLH-DSA (FIPS 205, SPHINCS+) stateless hash-based signatures.
+//!
+//! Security rests only on the hash function, which makes these signatures the
+//! choice for records that must stay verifiable for a decade or more. They are
+//! large and slow to produce, so they are used to seal archive manifests and
+//! audit checkpoints rather than individual transactions.
+//!
+//! Large inputs are hashed incrementally with [`ArchiveHasher`]; the resulting
+//! [`ArchiveDigest`] (algorithm id + digest) is what gets signed.
+
+use crate::crypto::secure::SecureSecret;
+use crate::error::ValidationError;
+use crate::utils::encoding::{self, KeyEncoding};
+use anyhow::{anyhow, Result};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::Shake256;
+use slh_dsa::{
+    ParameterSet, Sha2_128f, Sha2_128s, Sha2_192f, Sha2_192s, Sha2_256f, Sha2_256s, Shake128f,
+    Shake128s, Shake192f, Shake192s, Shake256f, Shake256s, Signature, SigningKey, VerifyingKey,
+};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Maximum length of an SLH-DSA context string (FIPS 205, section 10.2)
+pub const MAX_CONTEXT_LENGTH: usize = 255;
+
+const DIGEST_LENGTH: usize = 64;
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// SLH-DSA parameter sets: SHA2 or SHAKE based, small ("s") or fast ("f")
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SlhDsaParameterSet {
+    Sha2_128s,
+    Sha2_128f,
+    Sha2_192s,
+    Sha2_192f,
+    Sha2_256s,
+    Sha2_256f,
+    Shake128s,
+    Shake128f,
+    Shake192s,
+    Shake192f,
+    Shake256s,
+    Shake256f,
+}
+
+/// Hash used to compress streamed input before signing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PreHashAlgorithm {
+    Sha512,
+    Shake256,
+}
+
+impl SlhDsaParameterSet {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sha2_128s => "SLH-DSA-SHA2-128s",
+            Self::Sha2_128f => "SLH-DSA-SHA2-128f",
+            Self::Sha2_192s => "SLH-DSA-SHA2-192s",
+            Self::Sha2_192f => "SLH-DSA-SHA2-192f",
+            Self::Sha2_256s => "SLH-DSA-SHA2-256s",
+            Self::Sha2_256f => "SLH-DSA-SHA2-256f",
+            Self::Shake128s => "SLH-DSA-SHAKE-128s",
+            Self::Shake128f => "SLH-DSA-SHAKE-128f",
+            Self::Shake192s => "SLH-DSA-SHAKE-192s",
+            Self::Shake192f => "SLH-DSA-SHAKE-192f",
+            Self::Shake256s => "SLH-DSA-SHAKE-256s",
+            Self::Shake256f => "SLH-DSA-SHAKE-256f",
+        }
+    }
+
+    /// Security parameter n in bytes
+    fn n(&self) -> usize {
+        match self {
+            Self::Sha2_128s | Self::Sha2_128f | Self::Shake128s | Self::Shake128f => 16,
+            Self::Sha2_192s | Self::Sha2_192f | Self::Shake192s | Self::Shake192f => 24,
+            Self::Sha2_256s | Self::Sha2_256f | Self::Shake256s | Self::Shake256f => 32,
+        }
+    }
+
+    pub fn verifying_key_size(&self) -> usize {
+        2 * self.n()
+    }
+
+    pub fn signing_key_size(&self) -> usize {
+        4 * self.n()
+    }
+
+    pub fn signature_size(&self) -> usize {
+        match self {
+            Self::Sha2_128s | Self::Shake128s => 7856,
+            Self::Sha2_128f | Self::Shake128f => 17088,
+            Self::Sha2_192s | Self::Shake192s => 16224,
+            Self::Sha2_192f | Self::Shake192f => 35664,
+            Self::Sha2_256s | Self::Shake256s => 29792,
+            Self::Sha2_256f | Self::Shake256f => 49856,
+        }
+    }
+
+    /// Pre-hash matching the family of the parameter set
+    pub fn pre_hash(&self) -> PreHashAlgorithm {
+        match self {
+            Self::Sha2_128s | Self::Sha2_128f | Self::Sha2_192s
+            | Self::Sha2_192f | Self::Sha2_256s | Self::Sha2_256f => PreHashAlgorithm::Sha512,
+            _ => PreHashAlgorithm::Shake256,
+        }
+    }
+
+    fn public_key_label(&self) -> String {
+        format!("{} PUBLIC KEY", self.name())
+    }
+
+    fn secret_key_label(&self) -> String {
+        format!("{} SECRET KEY", self.name())
+    }
+}
+
+impl fmt::Display for SlhDsaParameterSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for SlhDsaParameterSet {
+    type Err = anyhow::Error;
+
+    /// Parses the FIPS 205 name, e.g. `SLH-DSA-SHA2-128f`
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            Self::Sha2_128s,
+            Self::Sha2_128f,
+            Self::Sha2_192s,
+            Self::Sha2_192f,
+            Self::Sha2_256s,
+            Self::Sha2_256f,
+            Self::Shake128s,
+            Self::Shake128f,
+            Self::Shake192s,
+            Self::Shake192f,
+            Self::Shake256s,
+            Self::Shake256f,
+        ]
+        .into_iter()
+        .find(|set| set.name() == s)
+        .ok_or_else(|| anyhow!("Unknown SLH-DSA parameter set: {}", s))
+    }
+}
+
+// Runs a generic helper with the concrete parameter type selected at runtime
+macro_rules! with_params {
+    ($set:expr, $func:ident ( $($arg:expr),* )) => {
+        match $set {
+            SlhDsaParameterSet::Sha2_128s => $func::<Sha2_128s>($($arg),*),
+            SlhDsaParameterSet::Sha2_128f => $func::<Sha2_128f>($($arg),*),
+            SlhDsaParameterSet::Sha2_192s => $func::<Sha2_192s>($($arg),*),
+            SlhDsaParameterSet::Sha2_192f => $func::<Sha2_192f>($($arg),*),
+            SlhDsaParameterSet::Sha2_256s => $func::<Sha2_256s>($($arg),*),
+            SlhDsaParameterSet::Sha2_256f => $func::<Sha2_256f>($($arg),*),
+            SlhDsaParameterSet::Shake128s => $func::<Shake128s>($($arg),*),
+            SlhDsaParameterSet::Shake128f => $func::<Shake128f>($($arg),*),
+            SlhDsaParameterSet::Shake192s => $func::<Shake192s>($($arg),*),
+            SlhDsaParameterSet::Shake192f => $func::<Shake192f>($($arg),*),
+            SlhDsaParameterSet::Shake256s => $func::<Shake256s>($($arg),*),
+            SlhDsaParameterSet::Shake256f => $func::<Shake256f>($($arg),*),
+        }
+    };
+}
+
+/// Digest of a streamed message, tagged with the algorithm that produced it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveDigest {
+    pub algorithm: PreHashAlgorithm,
+    pub bytes: Vec<u8>,
+}
+
+impl ArchiveDigest {
+    /// Bytes actually passed to SLH-DSA: algorithm tag followed by the digest
+    fn to_message(&self) -> Vec<u8> {
+        let tag: &[u8] = match self.algorithm {
+            PreHashAlgorithm::Sha512 => b"SHA-512",
+            PreHashAlgorithm::Shake256 => b"SHAKE256-512",
+        };
+        let mut message = Vec::with_capacity(1 + tag.len() + self.bytes.len());
+        message.push(tag.len() as u8);
+        message.extend_from_slice(tag);
+        message.extend_from_slice(&self.bytes);
+        message
+    }
+
+    pub fn to_hex(&self) -> String {
+        encoding::to_hex(&self.bytes)
+    }
+}
+
+enum HasherState {
+    Sha512(Sha512),
+    Shake256(Shake256),
+}
+
+/// Incremental hasher for inputs too large to hold in memory
+pub struct ArchiveHasher {
+    state: HasherState,
+    bytes_hashed: u64,
+}
+
+impl ArchiveHasher {
+    pub fn new(algorithm: PreHashAlgorithm) -> Self {
+        let state = match algorithm {
+            PreHashAlgorithm::Sha512 => HasherState::Sha512(Sha512::new()),
+            PreHashAlgorithm::Shake256 => HasherState::Shake256(Shake256::default()),
+        };
+        Self { state, bytes_hashed: 0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match &mut self.state {
+            HasherState::Sha512(hasher) => Digest::update(hasher, data),
+            HasherState::Shake256(hasher) => Update::update(hasher, data),
+        }
+        self.bytes_hashed += data.len() as u64;
+    }
+
+    /// Feeds everything from a reader into the hasher in fixed-size chunks
+    pub fn update_reader<R: Read>(&mut self, mut reader: R) -> Result<u64> {
+        let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+        let mut total = 0u64;
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            self.update(&buffer[..read]);
+            total += read as u64;
+        }
+        Ok(total)
+    }
+
+    pub fn bytes_hashed(&self) -> u64 {
+        self.bytes_hashed
+    }
+
+    pub fn finalize(self) -> ArchiveDigest {
+        match self.state {
+            HasherState::Sha512(hasher) => ArchiveDigest {
+                algorithm: PreHashAlgorithm::Sha512,
+                bytes: hasher.finalize().to_vec(),
+            },
+            HasherState::Shake256(hasher) => {
+                let mut bytes = vec![0u8; DIGEST_LENGTH];
+                XofReader::read(&mut hasher.finalize_xof(), &mut bytes);
+                ArchiveDigest {
+                    algorithm: PreHashAlgorithm::Shake256,
+                    bytes,
+                }
+            }
+        }
+    }
+
+    /// Hashes a whole file without loading it into memory
+    pub fn hash_file(algorithm: PreHashAlgorithm, path: impl AsRef<Path>) -> Result<ArchiveDigest> {
+        let file = File::open(path.as_ref())
+            .map_err(|e| anyhow!("Failed to open {}: {}", path.as_ref().display(), e))?;
+        let mut hasher = Self::new(algorithm);
+        hasher.update_reader(BufReader::new(file))?;
+        Ok(hasher.finalize())
+    }
+}
+
+/// SLH-DSA public (verifying) key
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlhDsaVerifyingKey {
+    parameter_set: SlhDsaParameterSet,
+    bytes: Vec<u8>,
+}
+
+/// SLH-DSA secret (signing) key with its matching verifying key
+pub struct SlhDsaSigningKey {
+    parameter_set: SlhDsaParameterSet,
+    secret: SecureSecret,
+    verifying_key: SlhDsaVerifyingKey,
+}
+
+/// Detached SLH-DSA signature over an [`ArchiveDigest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlhDsaSignature {
+    pub parameter_set: SlhDsaParameterSet,
+    pub bytes: Vec<u8>,
+}
+
+impl SlhDsaSigningKey {
+    pub fn generate(parameter_set: SlhDsaParameterSet) -> Self {
+        let (signing_key, verifying_key) = with_params!(parameter_set, generate_with());
+
+        Self {
+            parameter_set,
+            secret: SecureSecret::from_bytes(&signing_key),
+            verifying_key: SlhDsaVerifyingKey {
+                parameter_set,
+                bytes: verifying_key,
+            },
+        }
+    }
+
+    /// Restores a signing key; SLH-DSA secret keys embed the public key, so no extra input is needed
+    pub fn decode(parameter_set: SlhDsaParameterSet, data: &[u8], encoding: KeyEncoding) -> Result<Self> {
+        let bytes = encoding::decode_key(data, &parameter_set.secret_key_label(), encoding)?;
+        check_length("signing key", parameter_set.signing_key_size(), bytes.len())?;
+
+        let public_start = parameter_set.signing_key_size() - parameter_set.verifying_key_size();
+        let verifying_key = SlhDsaVerifyingKey {
+            parameter_set,
+            bytes: bytes[public_start..].to_vec(),
+        };
+
+        Ok(Self {
+            parameter_set,
+            secret: SecureSecret::from_bytes(&bytes),
+            verifying_key,
+        })
+    }
+
+    /// Exports the signing key in the requested encoding.
+    /// Warning: the output contains secret key material.
+    pub fn encode(&self, encoding: KeyEncoding) -> Result<Vec<u8>> {
+        Ok(self.secret.with_exposed(|bytes| {
+            encoding::encode_key(bytes, &self.parameter_set.secret_key_label(), encoding)
+        })?)
+    }
+
+    pub fn parameter_set(&self) -> SlhDsaParameterSet {
+        self.parameter_set
+    }
+
+    pub fn verifying_key(&self) -> &SlhDsaVerifyingKey {
+        &self.verifying_key
+    }
+
+    /// Creates a streaming hasher matching this key's parameter set
+    pub fn hasher(&self) -> ArchiveHasher {
+        ArchiveHasher::new(self.parameter_set.pre_hash())
+    }
+
+    /// Signs a digest produced by [`ArchiveHasher`]
+    pub fn sign_digest(&self, digest: &ArchiveDigest, context: &[u8]) -> Result<SlhDsaSignature> {
+        check_context(context)?;
+        if digest.algorithm != self.parameter_set.pre_hash() {
+            return Err(anyhow!(
+                "Digest algorithm {:?} does not match {}",
+                digest.algorithm,
+                self.parameter_set
+            ));
+        }
+
+        let bytes = self.secret.with_exposed(|secret| {
+            with_params!(self.parameter_set, sign_with(secret, &digest.to_message(), context, self.parameter_set.n()))
+        })??;
+
+        Ok(SlhDsaSignature {
+            parameter_set: self.parameter_set,
+            bytes,
+        })
+    }
+
+    /// Hashes and signs an in-memory message
+    pub fn sign(&self, message: &[u8], context: &[u8]) -> Result<SlhDsaSignature> {
+        let mut hasher = self.hasher();
+        hasher.update(message);
+        self.sign_digest(&hasher.finalize(), context)
+    }
+
+    /// Streams a file through the hasher and signs the digest
+    pub fn sign_file(&self, path: impl AsRef<Path>, context: &[u8]) -> Result<SlhDsaSignature> {
+        let digest = ArchiveHasher::hash_file(self.parameter_set.pre_hash(), path)?;
+        self.sign_digest(&digest, context)
+    }
+}
+
+impl fmt::Debug for SlhDsaSigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SlhDsaSigningKey({}, [REDACTED])", self.parameter_set)
+    }
+}
+
+impl SlhDsaVerifyingKey {
+    pub fn decode(parameter_set: SlhDsaParameterSet, data: &[u8], encoding: KeyEncoding) -> Result<Self> {
+        let bytes = encoding::decode_key(data, &parameter_set.public_key_label(), encoding)?;
+        check_length("verifying key", parameter_set.verifying_key_size(), bytes.len())?;
+        Ok(Self { parameter_set, bytes })
+    }
+
+    pub fn encode(&self, encoding: KeyEncoding) -> Vec<u8> {
+        encoding::encode_key(&self.bytes, &self.parameter_set.public_key_label(), encoding)
+    }
+
+    pub fn parameter_set(&self) -> SlhDsaParameterSet {
+        self.parameter_set
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn verify_digest(&self, digest: &ArchiveDigest, context: &[u8], signature: &SlhDsaSignature) -> Result<()> {
+        check_context(context)?;
+
+        if signature.parameter_set != self.parameter_set {
+            return Err(anyhow!(
+                "Parameter set mismatch: key is {}, signature is {}",
+                self.parameter_set,
+                signature.parameter_set
+            ));
+        }
+        check_length("signature", self.parameter_set.signature_size(), signature.bytes.len())?;
+
+        let valid = with_params!(
+            self.parameter_set,
+            verify_with(&self.bytes, &digest.to_message(), context, &signature.bytes)
+        );
+
+        if valid {
+            Ok(())
+        } else {
+            Err(ValidationError::SignatureInvalid(self.parameter_set.to_string()).into())
+        }
+    }
+
+    pub fn verify(&self, message: &[u8], context: &[u8], signature: &SlhDsaSignature) -> Result<()> {
+        let mut hasher = ArchiveHasher::new(self.parameter_set.pre_hash());
+        hasher.update(message);
+        self.verify_digest(&hasher.finalize(), context, signature)
+    }
+
+    pub fn verify_file(&self, path: impl AsRef<Path>, context: &[u8], signature: &SlhDsaSignature) -> Result<()> {
+        let digest = ArchiveHasher::hash_file(self.parameter_set.pre_hash(), path)?;
+        self.verify_digest(&digest, context, signature)
+    }
+}
+
+impl fmt::Debug for SlhDsaVerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SlhDsaVerifyingKey({}, {})", self.parameter_set, encoding::to_hex(&self.bytes))
+    }
+}
+
+fn check_context(context: &[u8]) -> Result<()> {
+    if context.len() > MAX_CONTEXT_LENGTH {
+        return Err(anyhow!(
+            "Context string too long: {} bytes (max {})",
+            context.len(),
+            MAX_CONTEXT_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+fn check_length(what: &str, expected: usize, actual: usize) -> Result<()> {
+    if expected != actual {
+        return Err(anyhow!("BadLength: {} expected {}, got {}", what, expected, actual));
+    }
+    Ok(())
+}
+
+fn generate_with<P: ParameterSet>() -> (Vec<u8>, Vec<u8>) {
+    let signing_key = SigningKey::<P>::new(&mut OsRng);
+    let verifying_key: &VerifyingKey<P> = signing_key.as_ref();
+    (signing_key.to_bytes().to_vec(), verifying_key.to_bytes().to_vec())
+}
+
+fn sign_with<P: ParameterSet>(
+    signing_key: &[u8],
+    message: &[u8],
+    context: &[u8],
+    randomizer_len: usize,
+) -> Result<Vec<u8>> {
+    let key = SigningKey::<P>::try_from(signing_key)
+        .map_err(|_| anyhow!("Malformed SLH-DSA signing key"))?;
+
+    // Hedged signing: fresh randomness per signature as recommended by FIPS 205
+    let mut randomizer = vec![0u8; randomizer_len];
+    rand::RngCore::fill_bytes(&mut OsRng, &mut randomizer);
+
+    let signature = key
+        .try_sign_with_context(message, context, Some(&randomizer))
+        .map_err(|_| anyhow!("SLH-DSA signing failed"))?;
+    Ok(signature.to_bytes().to_vec())
+}
+
+fn verify_with<P: ParameterSet>(verifying_key: &[u8], message: &[u8], context: &[u8], signature: &[u8]) -> bool {
+    let (Ok(key), Ok(signature)) = (
+        VerifyingKey::<P>::try_from(verifying_key),
+        Signature::<P>::try_from(signature),
+    ) else {
+        return false;
+    };
+    key.try_verify_with_context(message, context, &signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_sign_and_verify_fast_sets() {
+        for set in [SlhDsaParameterSet::Sha2_128f, SlhDsaParameterSet::Shake128f] {
+            let key = SlhDsaSigningKey::generate(set);
+            assert_eq!(key.verifying_key().as_bytes().len(), set.verifying_key_size());
+
+            let signature = key.sign(b"ledger-2024", b"archive").expect("Failed to sign");
+            assert_eq!(signature.bytes.len(), set.signature_size());
+            assert!(key.verifying_key().verify(b"ledger-2024", b"archive", &signature).is_ok());
+            assert!(key.verifying_key().verify(b"ledger-2025", b"archive", &signature).is_err());
+            assert!(key.verifying_key().verify(b"ledger-2024", b"other", &signature).is_err());
+        }
+    }
+
+    #[test]
+    fn test_small_signature_set() {
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128s);
+        let signature = key.sign(b"manifest", b"").unwrap();
+        assert_eq!(signature.bytes.len(), 7856);
+        assert!(key.verifying_key().verify(b"manifest", b"", &signature).is_ok());
+    }
+
+    #[test]
+    fn test_streaming_matches_one_shot() {
+        let data = vec![0xA5u8; 200_000];
+
+        let mut streamed = ArchiveHasher::new(PreHashAlgorithm::Shake256);
+        for chunk in data.chunks(4096) {
+            streamed.update(chunk);
+        }
+        assert_eq!(streamed.bytes_hashed(), data.len() as u64);
+
+        let mut one_shot = ArchiveHasher::new(PreHashAlgorithm::Shake256);
+        one_shot.update(&data);
+
+        assert_eq!(streamed.finalize(), one_shot.finalize());
+    }
+
+    #[test]
+    fn test_sign_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.bin");
+        {
+            let mut file = File::create(&path).unwrap();
+            for _ in 0..64 {
+                file.write_all(&[0x42u8; 8192]).unwrap();
+            }
+        }
+
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128f);
+        let signature = key.sign_file(&path, b"archive").unwrap();
+        assert!(key.verifying_key().verify_file(&path, b"archive", &signature).is_ok());
+
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap()
+            .write_all(b"tamper").unwrap();
+        assert!(key.verifying_key().verify_file(&path, b"archive", &signature).is_err());
+    }
+
+    #[test]
+    fn test_key_encoding_round_trip() {
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Shake128f);
+        let exported = key.encode(KeyEncoding::Pem).unwrap();
+        let restored = SlhDsaSigningKey::decode(SlhDsaParameterSet::Shake128f, &exported, KeyEncoding::Pem)
+            .expect("Failed to decode signing key");
+        assert_eq!(restored.verifying_key(), key.verifying_key());
+
+        let signature = restored.sign(b"checkpoint", b"").unwrap();
+        assert!(key.verifying_key().verify(b"checkpoint", b"", &signature).is_ok());
+
+        let parameter_set: SlhDsaParameterSet = key.parameter_set().name().parse().unwrap();
+        assert_eq!(parameter_set, SlhDsaParameterSet::Shake128f);
+        assert!("SLH-DSA-SHAKE-128".parse::<SlhDsaParameterSet>().is_err());
+    }
+}
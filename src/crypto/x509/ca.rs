@@ -0,0 +1,161 @@
+//! Local certificate authority holding an ML-DSA signing key.
+
+use super::certificate::{Extensions, TbsCertificate};
+use super::crl::{CertificateRevocationList, RevokedCertificate};
+use super::{BasicConstraints, Certificate, CertificateRequest, KeyUsage, Name, PublicKeyInfo, Validity};
+use crate::crypto::signature::{MlDsaParameterSet, MlDsaSigningKey, MlDsaVerifyingKey};
+use crate::error::CertificateError;
+use anyhow::{anyhow, Result};
+use rand::RngCore;
+
+// Random positive serials, well below the 20-octet limit
+const SERIAL_LENGTH: usize = 16;
+
+/// Issues certificates under its own certificate, root or subordinate
+#[derive(Debug)]
+pub struct CertificateAuthority {
+    signing_key: MlDsaSigningKey,
+    certificate: Certificate,
+}
+
+impl CertificateAuthority {
+    /// Self-signed root CA with a fresh ML-DSA key
+    pub fn new_root(subject: Name, parameter_set: MlDsaParameterSet, validity: Validity) -> Result<Self> {
+        let signing_key = MlDsaSigningKey::generate(parameter_set);
+        let public_key = PublicKeyInfo::MlDsa(signing_key.verifying_key().clone());
+        let tbs = TbsCertificate {
+            serial: random_serial(),
+            issuer: subject.clone(),
+            validity,
+            subject,
+            extensions: Extensions {
+                basic_constraints: Some(BasicConstraints { ca: true, path_len: None }),
+                key_usage: Some(ca_key_usage()),
+                subject_key_id: Some(public_key.key_identifier()),
+                authority_key_id: Some(public_key.key_identifier()),
+            },
+            public_key,
+        };
+        let certificate = Certificate::sign(tbs, &signing_key)?;
+        Ok(Self { signing_key, certificate })
+    }
+
+    /// CA from a stored signing key and the certificate issued for it
+    pub fn from_parts(signing_key: MlDsaSigningKey, certificate: Certificate) -> Result<Self> {
+        if certificate.verifying_key() != Some(signing_key.verifying_key()) {
+            return Err(CertificateError::KeyMismatch(certificate.subject().to_string()).into());
+        }
+        if !certificate.is_ca() || !certificate.key_usage().is_some_and(|usage| usage.contains(KeyUsage::KEY_CERT_SIGN)) {
+            return Err(CertificateError::InvalidChain(format!("{} is not a CA certificate", certificate.subject())).into());
+        }
+        Ok(Self { signing_key, certificate })
+    }
+
+    pub fn certificate(&self) -> &Certificate {
+        &self.certificate
+    }
+
+    /// Signing key, for persisting the CA.
+    /// Warning: its encoding contains secret key material.
+    pub fn signing_key(&self) -> &MlDsaSigningKey {
+        &self.signing_key
+    }
+
+    /// End-entity certificate for an ML-KEM-1024 public key, usable for key
+    /// encipherment only
+    pub fn issue_kem_certificate(&self, subject: Name, public_key: &[u8], validity: Validity) -> Result<Certificate> {
+        let public_key = PublicKeyInfo::ml_kem_1024(public_key)?;
+        self.issue(subject, public_key, validity, None, KeyUsage::KEY_ENCIPHERMENT)
+    }
+
+    /// Subordinate CA certificate for another ML-DSA key. `path_len` limits
+    /// how many further CAs may sit below it.
+    pub fn issue_subordinate(
+        &self,
+        subject: Name,
+        key: &MlDsaVerifyingKey,
+        validity: Validity,
+        path_len: Option<u8>,
+    ) -> Result<Certificate> {
+        if let Some(0) = self.certificate.basic_constraints().and_then(|constraints| constraints.path_len) {
+            return Err(CertificateError::InvalidChain(format!(
+                "{} may not issue subordinate CAs",
+                self.certificate.subject()
+            ))
+            .into());
+        }
+        let constraints = BasicConstraints { ca: true, path_len };
+        self.issue(subject, PublicKeyInfo::MlDsa(key.clone()), validity, Some(constraints), ca_key_usage())
+    }
+
+    /// Certificate for a verified PKCS#10 request: key encipherment for an
+    /// ML-KEM key, digital signature for an ML-DSA key. See
+    /// [`CertificateRequest::verify`] for the role of `requester`.
+    pub fn issue_from_request(
+        &self,
+        request: &CertificateRequest,
+        requester: Option<&MlDsaVerifyingKey>,
+        validity: Validity,
+    ) -> Result<Certificate> {
+        request.verify(requester)?;
+        let usage = match request.public_key() {
+            PublicKeyInfo::MlKem1024(_) => KeyUsage::KEY_ENCIPHERMENT,
+            PublicKeyInfo::MlDsa(_) => KeyUsage::DIGITAL_SIGNATURE,
+        };
+        self.issue(request.subject().clone(), request.public_key().clone(), validity, None, usage)
+    }
+
+    /// Revocation list numbered `number`; `validity` runs from thisUpdate to nextUpdate
+    pub fn issue_crl(
+        &self,
+        number: u64,
+        entries: Vec<RevokedCertificate>,
+        validity: Validity,
+    ) -> Result<CertificateRevocationList> {
+        CertificateRevocationList::sign(&self.certificate, &self.signing_key, number, validity, entries)
+    }
+
+    // Issued certificates never outlive the CA's own
+    fn issue(
+        &self,
+        subject: Name,
+        public_key: PublicKeyInfo,
+        validity: Validity,
+        basic_constraints: Option<BasicConstraints>,
+        key_usage: KeyUsage,
+    ) -> Result<Certificate> {
+        let ca_validity = self.certificate.validity();
+        let validity = Validity::new(
+            validity.not_before.max(ca_validity.not_before),
+            validity.not_after.min(ca_validity.not_after),
+        )
+        .map_err(|_| anyhow!("Requested validity lies outside the validity of {}", self.certificate.subject()))?;
+
+        let tbs = TbsCertificate {
+            serial: random_serial(),
+            issuer: self.certificate.subject().clone(),
+            validity,
+            subject,
+            extensions: Extensions {
+                basic_constraints,
+                key_usage: Some(key_usage),
+                subject_key_id: Some(public_key.key_identifier()),
+                authority_key_id: self.certificate.subject_key_id().map(<[u8]>::to_vec),
+            },
+            public_key,
+        };
+        Certificate::sign(tbs, &self.signing_key)
+    }
+}
+
+fn ca_key_usage() -> KeyUsage {
+    KeyUsage::DIGITAL_SIGNATURE | KeyUsage::KEY_CERT_SIGN | KeyUsage::CRL_SIGN
+}
+
+fn random_serial() -> Vec<u8> {
+    let mut serial = vec![0u8; SERIAL_LENGTH];
+    rand::thread_rng().fill_bytes(&mut serial);
+    // Positive and with no leading zero octet to trim
+    serial[0] = (serial[0] & 0x7f) | 0x40;
+    serial
+}
@@ -0,0 +1,445 @@
+//! Certificate encoding and parsing.
+//!
+//! ```text
+//! Certificate ::= SEQUENCE {
+//!     tbsCertificate      TBSCertificate,
+//!     signatureAlgorithm  AlgorithmIdentifier,
+//!     signatureValue      BIT STRING }
+//!
+//! TBSCertificate ::= SEQUENCE {
+//!     version         [0] EXPLICIT INTEGER (v3),
+//!     serialNumber        INTEGER,
+//!     signature           AlgorithmIdentifier,
+//!     issuer              Name,
+//!     validity            Validity,
+//!     subject             Name,
+//!     subjectPublicKeyInfo SubjectPublicKeyInfo,
+//!     extensions      [3] EXPLICIT Extensions }
+//! ```
+//!
+//! Issuer and subject unique identifiers are refused, as are unknown
+//! critical extensions.
+
+use super::{
+    algorithm_identifier, invalid_extension, read_algorithm_identifier, signature_algorithm_oid,
+    signature_parameter_set, KeyUsage, Name, Validity, CERTIFICATE_LABEL, OID_AUTHORITY_KEY_IDENTIFIER,
+    OID_BASIC_CONSTRAINTS, OID_KEY_USAGE, OID_ML_KEM_1024, OID_SUBJECT_KEY_IDENTIFIER, SIGNATURE_CONTEXT,
+};
+use crate::crypto::signature::{MlDsaSignature, MlDsaSigningKey, MlDsaVerifyingKey, SigningMode};
+use crate::error::{CertificateError, EncodingError};
+use crate::utils::der::{self, DerReader};
+use crate::utils::encoding::{self, KeyEncoding};
+use anyhow::{anyhow, Result};
+use pqcrypto_kyber::kyber1024;
+use pqcrypto_traits::kem::PublicKey as _;
+use sha2::{Digest, Sha256};
+
+const VERSION_3: u64 = 2;
+// RFC 7093, method 1: leftmost 160 bits of the SHA-256 of the key
+const KEY_IDENTIFIER_LENGTH: usize = 20;
+// RFC 5280 caps serial numbers at 20 octets
+const MAX_SERIAL_LENGTH: usize = 20;
+
+/// Subject public key of a certificate or certification request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicKeyInfo {
+    /// ML-KEM-1024 encapsulation key
+    MlKem1024(Vec<u8>),
+    /// ML-DSA verifying key of a CA or a requester
+    MlDsa(MlDsaVerifyingKey),
+}
+
+impl PublicKeyInfo {
+    /// Checks the length and encoding of an ML-KEM-1024 public key
+    pub fn ml_kem_1024(public_key: &[u8]) -> Result<Self> {
+        kyber1024::PublicKey::from_bytes(public_key).map_err(|_| {
+            anyhow!(
+                "Invalid ML-KEM-1024 public key: expected {} bytes, got {}",
+                kyber1024::public_key_bytes(),
+                public_key.len()
+            )
+        })?;
+        Ok(PublicKeyInfo::MlKem1024(public_key.to_vec()))
+    }
+
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            PublicKeyInfo::MlKem1024(_) => "ML-KEM-1024",
+            PublicKeyInfo::MlDsa(key) => key.parameter_set().name(),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            PublicKeyInfo::MlKem1024(bytes) => bytes,
+            PublicKeyInfo::MlDsa(key) => key.as_bytes(),
+        }
+    }
+
+    /// Identifier used in the subject and authority key identifier extensions
+    pub fn key_identifier(&self) -> Vec<u8> {
+        Sha256::digest(self.as_bytes())[..KEY_IDENTIFIER_LENGTH].to_vec()
+    }
+
+    pub(super) fn encode(&self) -> Result<Vec<u8>> {
+        let oid = match self {
+            PublicKeyInfo::MlKem1024(_) => OID_ML_KEM_1024,
+            PublicKeyInfo::MlDsa(key) => signature_algorithm_oid(key.parameter_set()),
+        };
+        Ok(der::sequence(&[algorithm_identifier(oid)?, der::bit_string(self.as_bytes())]))
+    }
+
+    pub(super) fn read(reader: &mut DerReader<'_>) -> Result<Self> {
+        let mut spki = reader.read_sequence()?;
+        let oid = read_algorithm_identifier(&mut spki)?;
+        let key = spki.read_bit_string()?;
+        spki.finish()?;
+        if oid == OID_ML_KEM_1024 {
+            return Self::ml_kem_1024(key);
+        }
+        let parameter_set = signature_parameter_set(&oid)?;
+        Ok(PublicKeyInfo::MlDsa(MlDsaVerifyingKey::decode(parameter_set, key, KeyEncoding::Raw)?))
+    }
+}
+
+/// basicConstraints extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BasicConstraints {
+    pub ca: bool,
+    /// Maximum number of intermediate CAs allowed below this one
+    pub path_len: Option<u8>,
+}
+
+// The extensions this crate writes and understands
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Extensions {
+    pub basic_constraints: Option<BasicConstraints>,
+    pub key_usage: Option<KeyUsage>,
+    pub subject_key_id: Option<Vec<u8>>,
+    pub authority_key_id: Option<Vec<u8>>,
+}
+
+impl Extensions {
+    fn encode(&self) -> Result<Vec<u8>> {
+        fn extension(oid: &str, critical: bool, value: &[u8]) -> Result<Vec<u8>> {
+            let mut fields = vec![der::oid(oid)?];
+            if critical {
+                fields.push(der::boolean(true));
+            }
+            fields.push(der::octet_string(value));
+            Ok(der::sequence(&fields))
+        }
+
+        let mut extensions = Vec::new();
+        if let Some(constraints) = &self.basic_constraints {
+            let mut fields = Vec::new();
+            if constraints.ca {
+                fields.push(der::boolean(true));
+            }
+            if let Some(path_len) = constraints.path_len {
+                fields.push(der::integer_u64(path_len as u64));
+            }
+            extensions.push(extension(OID_BASIC_CONSTRAINTS, true, &der::sequence(&fields))?);
+        }
+        if let Some(usage) = &self.key_usage {
+            extensions.push(extension(OID_KEY_USAGE, true, &usage.encode())?);
+        }
+        if let Some(id) = &self.subject_key_id {
+            extensions.push(extension(OID_SUBJECT_KEY_IDENTIFIER, false, &der::octet_string(id))?);
+        }
+        if let Some(id) = &self.authority_key_id {
+            let key_identifier = der::tlv(der::context_tag(0, false), id);
+            extensions.push(extension(OID_AUTHORITY_KEY_IDENTIFIER, false, &der::sequence(&[key_identifier]))?);
+        }
+        Ok(der::sequence(&extensions))
+    }
+
+    fn read(reader: &mut DerReader<'_>) -> Result<Self> {
+        let mut list = reader.read_sequence()?;
+        let mut extensions = Extensions::default();
+        let mut seen = Vec::new();
+        while !list.is_empty() {
+            let mut extension = list.read_sequence()?;
+            let oid = extension.read_oid()?;
+            let critical = if extension.peek_tag() == Some(der::TAG_BOOLEAN) { extension.read_boolean()? } else { false };
+            let value = extension.read_octet_string()?;
+            extension.finish()?;
+            if seen.contains(&oid) {
+                return Err(invalid_extension(&format!("duplicate extension {}", oid)));
+            }
+
+            let mut inner = DerReader::new(value);
+            match oid.as_str() {
+                OID_BASIC_CONSTRAINTS => {
+                    let mut fields = inner.read_sequence()?;
+                    let ca = if fields.peek_tag() == Some(der::TAG_BOOLEAN) { fields.read_boolean()? } else { false };
+                    let path_len = if fields.is_empty() {
+                        None
+                    } else {
+                        let value = fields.read_integer_u64()?;
+                        Some(u8::try_from(value).map_err(|_| invalid_extension("pathLenConstraint too large"))?)
+                    };
+                    fields.finish()?;
+                    extensions.basic_constraints = Some(BasicConstraints { ca, path_len });
+                }
+                OID_KEY_USAGE => {
+                    extensions.key_usage = Some(KeyUsage::decode(inner.read_expected(der::TAG_BIT_STRING)?)?);
+                }
+                OID_SUBJECT_KEY_IDENTIFIER => {
+                    extensions.subject_key_id = Some(inner.read_octet_string()?.to_vec());
+                }
+                OID_AUTHORITY_KEY_IDENTIFIER => {
+                    let mut fields = inner.read_sequence()?;
+                    if fields.peek_tag() == Some(der::context_tag(0, false)) {
+                        extensions.authority_key_id = Some(fields.read_expected(der::context_tag(0, false))?.to_vec());
+                    }
+                    // authorityCertIssuer and authorityCertSerialNumber are not used
+                }
+                _ if critical => return Err(invalid_extension(&format!("unsupported critical extension {}", oid))),
+                _ => inner = DerReader::new(&[]),
+            }
+            inner.finish()?;
+            seen.push(oid);
+        }
+        Ok(extensions)
+    }
+}
+
+/// Certificate contents covered by the issuer's signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct TbsCertificate {
+    pub serial: Vec<u8>,
+    pub issuer: Name,
+    pub validity: Validity,
+    pub subject: Name,
+    pub public_key: PublicKeyInfo,
+    pub extensions: Extensions,
+}
+
+impl TbsCertificate {
+    fn encode(&self, signer: &MlDsaSigningKey) -> Result<Vec<u8>> {
+        Ok(der::sequence(&[
+            der::explicit(0, &der::integer_u64(VERSION_3)),
+            der::integer_bytes(&self.serial),
+            algorithm_identifier(signature_algorithm_oid(signer.parameter_set()))?,
+            self.issuer.encode()?,
+            self.validity.encode(),
+            self.subject.encode()?,
+            self.public_key.encode()?,
+            der::explicit(3, &self.extensions.encode()?),
+        ]))
+    }
+}
+
+/// Signed X.509 v3 certificate, kept together with its DER encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    der: Vec<u8>,
+    tbs_der: Vec<u8>,
+    tbs: TbsCertificate,
+    signature: MlDsaSignature,
+}
+
+impl Certificate {
+    pub(super) fn sign(tbs: TbsCertificate, signer: &MlDsaSigningKey) -> Result<Self> {
+        let tbs_der = tbs.encode(signer)?;
+        let signature = signer.sign(&tbs_der, SIGNATURE_CONTEXT, SigningMode::Hedged)?;
+        let der = der::sequence(&[
+            tbs_der.clone(),
+            algorithm_identifier(signature_algorithm_oid(signer.parameter_set()))?,
+            der::bit_string(&signature.bytes),
+        ]);
+        Ok(Self { der, tbs_der, tbs, signature })
+    }
+
+    pub fn from_der(der_bytes: &[u8]) -> Result<Self> {
+        let mut outer = DerReader::new(der_bytes);
+        let mut certificate = outer.read_sequence()?;
+        outer.finish()?;
+
+        let (tag, tbs_content, tbs_der) = certificate.read_any()?;
+        if tag != der::TAG_SEQUENCE {
+            return Err(EncodingError::InvalidDer("TBSCertificate is not a SEQUENCE".to_string()).into());
+        }
+        let outer_algorithm = read_algorithm_identifier(&mut certificate)?;
+        let signature_bytes = certificate.read_bit_string()?;
+        certificate.finish()?;
+
+        let mut tbs = DerReader::new(tbs_content);
+        let version = tbs
+            .read_optional_explicit(0)?
+            .ok_or_else(|| anyhow!("Only X.509 v3 certificates are supported"))?
+            .read_integer_u64()?;
+        if version != VERSION_3 {
+            return Err(anyhow!("Only X.509 v3 certificates are supported, got version {}", version + 1));
+        }
+        let serial = tbs.read_integer_bytes()?.to_vec();
+        if serial.len() > MAX_SERIAL_LENGTH {
+            return Err(EncodingError::InvalidDer("Serial number longer than 20 octets".to_string()).into());
+        }
+        let algorithm = read_algorithm_identifier(&mut tbs)?;
+        if algorithm != outer_algorithm {
+            return Err(CertificateError::UnsupportedAlgorithm(format!("{} signed as {}", algorithm, outer_algorithm)).into());
+        }
+        let parameter_set = signature_parameter_set(&algorithm)?;
+        let issuer = Name::read(&mut tbs)?;
+        let validity = Validity::read(&mut tbs)?;
+        let subject = Name::read(&mut tbs)?;
+        let public_key = PublicKeyInfo::read(&mut tbs)?;
+        let extensions = match tbs.read_optional_explicit(3)? {
+            Some(mut field) => {
+                let extensions = Extensions::read(&mut field)?;
+                field.finish()?;
+                extensions
+            }
+            None => Extensions::default(),
+        };
+        // Leaves [1] and [2] unique identifiers as trailing data
+        tbs.finish()?;
+
+        Ok(Self {
+            der: der_bytes.to_vec(),
+            tbs_der: tbs_der.to_vec(),
+            tbs: TbsCertificate { serial, issuer, validity, subject, public_key, extensions },
+            signature: MlDsaSignature { parameter_set, bytes: signature_bytes.to_vec() },
+        })
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_der(&encoding::from_pem(CERTIFICATE_LABEL, pem)?)
+    }
+
+    pub fn as_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    pub fn to_pem(&self) -> String {
+        encoding::to_pem(CERTIFICATE_LABEL, &self.der)
+    }
+
+    /// Serial number as big-endian magnitude bytes
+    pub fn serial(&self) -> &[u8] {
+        &self.tbs.serial
+    }
+
+    pub fn serial_hex(&self) -> String {
+        encoding::to_hex(&self.tbs.serial)
+    }
+
+    pub fn issuer(&self) -> &Name {
+        &self.tbs.issuer
+    }
+
+    pub fn subject(&self) -> &Name {
+        &self.tbs.subject
+    }
+
+    pub fn validity(&self) -> Validity {
+        self.tbs.validity
+    }
+
+    pub fn public_key(&self) -> &PublicKeyInfo {
+        &self.tbs.public_key
+    }
+
+    /// ML-DSA key of a CA or requester certificate; `None` for ML-KEM keys
+    pub fn verifying_key(&self) -> Option<&MlDsaVerifyingKey> {
+        match &self.tbs.public_key {
+            PublicKeyInfo::MlDsa(key) => Some(key),
+            PublicKeyInfo::MlKem1024(_) => None,
+        }
+    }
+
+    pub fn key_usage(&self) -> Option<KeyUsage> {
+        self.tbs.extensions.key_usage
+    }
+
+    pub fn basic_constraints(&self) -> Option<BasicConstraints> {
+        self.tbs.extensions.basic_constraints
+    }
+
+    pub fn is_ca(&self) -> bool {
+        self.basic_constraints().is_some_and(|constraints| constraints.ca)
+    }
+
+    pub fn subject_key_id(&self) -> Option<&[u8]> {
+        self.tbs.extensions.subject_key_id.as_deref()
+    }
+
+    pub fn authority_key_id(&self) -> Option<&[u8]> {
+        self.tbs.extensions.authority_key_id.as_deref()
+    }
+
+    /// Whether issuer and subject are the same name, as in a root certificate
+    pub fn is_self_issued(&self) -> bool {
+        self.tbs.issuer == self.tbs.subject
+    }
+
+    /// Checks the certificate signature with the issuer's verifying key
+    pub fn verify_signature(&self, issuer_key: &MlDsaVerifyingKey) -> Result<()> {
+        issuer_key.verify(&self.tbs_der, SIGNATURE_CONTEXT, &self.signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signature::MlDsaParameterSet;
+    use crate::crypto::x509::CertificateAuthority;
+
+    fn kem_public_key() -> Vec<u8> {
+        kyber1024::keypair().0.as_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_der_round_trip() {
+        let ca = CertificateAuthority::new_root(
+            Name::new("Example Root CA").with_organization("Example Bank"),
+            MlDsaParameterSet::MlDsa65,
+            Validity::for_days(3650).unwrap(),
+        )
+        .unwrap();
+        let public_key = kem_public_key();
+        let certificate = ca
+            .issue_kem_certificate(Name::new("settlement-inbound"), &public_key, Validity::for_days(365).unwrap())
+            .unwrap();
+
+        let parsed = Certificate::from_pem(&certificate.to_pem()).unwrap();
+        assert_eq!(parsed, certificate);
+        assert_eq!(parsed.public_key(), &PublicKeyInfo::MlKem1024(public_key));
+        assert_eq!(parsed.key_usage(), Some(KeyUsage::KEY_ENCIPHERMENT));
+        assert!(!parsed.is_ca());
+        assert_eq!(parsed.authority_key_id(), ca.certificate().subject_key_id());
+        parsed.verify_signature(ca.certificate().verifying_key().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_tampered_certificate_rejected() {
+        let ca = CertificateAuthority::new_root(Name::new("Root"), MlDsaParameterSet::MlDsa44, Validity::for_days(30).unwrap())
+            .unwrap();
+        let certificate = ca
+            .issue_kem_certificate(Name::new("inbound"), &kem_public_key(), Validity::for_days(1).unwrap())
+            .unwrap();
+
+        // Flip a bit inside the ML-KEM key
+        let mut der_bytes = certificate.as_der().to_vec();
+        let offset = der_bytes.len() / 3;
+        der_bytes[offset] ^= 0x01;
+        let tampered = Certificate::from_der(&der_bytes).unwrap();
+        assert!(tampered.verify_signature(ca.certificate().verifying_key().unwrap()).is_err());
+
+        assert!(Certificate::from_der(&der_bytes[..der_bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_unknown_critical_extension_rejected() {
+        let extension = der::sequence(&[der::oid("1.2.3.4").unwrap(), der::boolean(true), der::octet_string(&der::null())]);
+        let encoded = der::sequence(&[extension]);
+        assert!(Extensions::read(&mut DerReader::new(&encoded)).is_err());
+
+        // Unknown but non-critical extensions are skipped
+        let extension = der::sequence(&[der::oid("1.2.3.4").unwrap(), der::octet_string(&der::null())]);
+        let encoded = der::sequence(&[extension]);
+        assert_eq!(Extensions::read(&mut DerReader::new(&encoded)).unwrap(), Extensions::default());
+    }
+}
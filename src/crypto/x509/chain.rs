@@ -0,0 +1,429 @@
+//! Certificate path validation from an end-entity certificate to a trusted root.
+
+use super::{now, Certificate, KeyUsage, PublicKeyInfo, RevocationChecker};
+use crate::crypto::secure::SecureSecret;
+use crate::error::CertificateError;
+use anyhow::{anyhow, Result};
+use pqcrypto_kyber::kyber1024;
+use pqcrypto_traits::kem::{Ciphertext as _, PublicKey as _};
+use std::sync::Arc;
+
+/// Longest path accepted, counting the leaf and the trust anchor
+pub const MAX_CHAIN_DEPTH: usize = 8;
+// Issuer signatures checked while building one path; the intermediates come
+// from the peer, so a pile of same-named CAs must not make the search explode
+const MAX_ISSUER_CHECKS: usize = 64;
+
+/// Root certificates trusted to anchor a chain
+#[derive(Debug, Clone, Default)]
+pub struct TrustStore {
+    anchors: Vec<Certificate>,
+    revocation: Option<Arc<RevocationChecker>>,
+}
+
+impl TrustStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts a self-signed CA certificate, e.g. the root a partner was given
+    /// out of band
+    pub fn add_anchor(&mut self, certificate: Certificate) -> Result<()> {
+        let key = certificate
+            .verifying_key()
+            .filter(|_| certificate.is_self_issued() && certificate.is_ca())
+            .ok_or_else(|| anyhow!("{} is not a self-signed CA certificate", certificate.subject()))?;
+        certificate.verify_signature(key)?;
+        if !self.anchors.contains(&certificate) {
+            self.anchors.push(certificate);
+        }
+        Ok(())
+    }
+
+    /// Checks the revocation status of the leaf and of every intermediate CA
+    /// before vouching for a key
+    pub fn set_revocation_checker(&mut self, checker: Arc<RevocationChecker>) {
+        self.revocation = Some(checker);
+    }
+
+    pub fn anchors(&self) -> &[Certificate] {
+        &self.anchors
+    }
+
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// Builds and checks the path from `leaf` through `intermediates` to a
+    /// trust anchor as of `at`, returning it leaf first. Every anchor and
+    /// intermediate whose name matches is tried, so an expired or unrelated
+    /// certificate with the issuer's name does not hide a valid path; when
+    /// none works, the error is the one from the first candidate tried.
+    pub fn verify(&self, leaf: &Certificate, intermediates: &[Certificate], at: u64) -> Result<Vec<Certificate>> {
+        leaf.validity().check(at, leaf.subject())?;
+        let mut path = vec![leaf.clone()];
+        let mut checks_left = MAX_ISSUER_CHECKS;
+        self.extend_path(&mut path, intermediates, at, &mut checks_left)?;
+        Ok(path)
+    }
+
+    // Completes `path` up to an anchor, or leaves it as it was and fails
+    fn extend_path(&self, path: &mut Vec<Certificate>, intermediates: &[Certificate], at: u64, checks_left: &mut usize) -> Result<()> {
+        if path.len() >= MAX_CHAIN_DEPTH {
+            return Err(CertificateError::InvalidChain(format!("path longer than {} certificates", MAX_CHAIN_DEPTH)).into());
+        }
+        let current = path[path.len() - 1].clone();
+        // CA certificates between the leaf and the next issuer
+        let cas_below = path.len() - 1;
+        let mut first_failure = None;
+
+        let anchors = self.anchors.iter().filter(|anchor| issued_by(&current, anchor)).map(|anchor| (anchor, true));
+        let others = intermediates
+            .iter()
+            .filter(|candidate| issued_by(&current, candidate) && !path.contains(candidate))
+            .map(|candidate| (candidate, false));
+        let candidates: Vec<_> = anchors.chain(others).collect();
+        for (issuer, is_anchor) in candidates {
+            if *checks_left == 0 {
+                return Err(CertificateError::InvalidChain("too many candidate issuers".to_string()).into());
+            }
+            *checks_left -= 1;
+            let result = check_issuer(issuer, &current, cas_below, at).and_then(|()| {
+                if is_anchor {
+                    if *issuer != current {
+                        path.push(issuer.clone());
+                    }
+                    return Ok(());
+                }
+                path.push(issuer.clone());
+                self.extend_path(path, intermediates, at, checks_left).inspect_err(|_| {
+                    path.pop();
+                })
+            });
+            match result {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    first_failure.get_or_insert(err);
+                }
+            }
+        }
+        Err(first_failure.unwrap_or_else(|| CertificateError::UntrustedIssuer(current.issuer().to_string()).into()))
+    }
+
+    /// Validates an end-entity certificate for an ML-KEM key against the current time,
+    /// and, if a checker is set, the revocation status of every certificate
+    /// on its path below the trust anchor, and returns the public key it
+    /// vouches for, ready to encapsulate to
+    pub fn verify_kem_certificate(&self, leaf: &Certificate, intermediates: &[Certificate]) -> Result<Vec<u8>> {
+        let path = self.verify(leaf, intermediates, now())?;
+        if leaf.is_ca() {
+            return Err(CertificateError::InvalidExtension(format!("{} is a CA certificate", leaf.subject())).into());
+        }
+        if !leaf.key_usage().is_some_and(|usage| usage.contains(KeyUsage::KEY_ENCIPHERMENT)) {
+            return Err(CertificateError::InvalidExtension(format!(
+                "{} is not certified for key encipherment",
+                leaf.subject()
+            ))
+            .into());
+        }
+        let key = match leaf.public_key() {
+            PublicKeyInfo::MlKem1024(key) => key.clone(),
+            other => return Err(CertificateError::UnsupportedAlgorithm(other.algorithm().to_string()).into()),
+        };
+        if let Some(checker) = &self.revocation {
+            // A revoked intermediate takes everything it issued with it
+            for pair in path.windows(2) {
+                checker.check(&pair[0], &pair[1])?;
+            }
+        }
+        Ok(key)
+    }
+
+    /// Encapsulates to the key in a validated certificate, returning the
+    /// shared secret and the ciphertext for the key's holder
+    pub fn encapsulate(&self, leaf: &Certificate, intermediates: &[Certificate]) -> Result<(SecureSecret, Vec<u8>)> {
+        let public_key = kyber1024::PublicKey::from_bytes(&self.verify_kem_certificate(leaf, intermediates)?)
+            .map_err(|_| anyhow!("Invalid ML-KEM-1024 public key in {}", leaf.subject()))?;
+        let (shared, ciphertext) = kyber1024::encapsulate(&public_key);
+        Ok((SecureSecret::from_shared(shared), ciphertext.as_bytes().to_vec()))
+    }
+}
+
+// Name chaining, narrowed by key identifiers when both are present
+fn issued_by(child: &Certificate, issuer: &Certificate) -> bool {
+    if child.issuer() != issuer.subject() {
+        return false;
+    }
+    match (child.authority_key_id(), issuer.subject_key_id()) {
+        (Some(authority), Some(subject)) => authority == subject,
+        _ => true,
+    }
+}
+
+fn check_issuer(issuer: &Certificate, child: &Certificate, cas_below: usize, at: u64) -> Result<()> {
+    issuer.validity().check(at, issuer.subject())?;
+    let not_allowed = |reason: &str| -> anyhow::Error {
+        CertificateError::InvalidChain(format!("{} {}", issuer.subject(), reason)).into()
+    };
+
+    let constraints = issuer.basic_constraints().filter(|constraints| constraints.ca).ok_or_else(|| not_allowed("is not a CA"))?;
+    if issuer.key_usage().is_some_and(|usage| !usage.contains(KeyUsage::KEY_CERT_SIGN)) {
+        return Err(not_allowed("may not sign certificates"));
+    }
+    if constraints.path_len.is_some_and(|limit| cas_below > limit as usize) {
+        return Err(not_allowed("exceeds its path length constraint"));
+    }
+    let key = issuer.verifying_key().ok_or_else(|| not_allowed("has no signing key"))?;
+    child.verify_signature(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::certificate::{Extensions, TbsCertificate};
+    use crate::crypto::signature::{MlDsaParameterSet, MlDsaSigningKey};
+    use crate::crypto::x509::{
+        BasicConstraints, CertificateAuthority, CertificateRequest, Name, RevocationReason, RevocationRegistry, StatusResponder, Validity,
+    };
+    use crate::error::Error;
+    use pqcrypto_kyber::kyber1024;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn new_root(name: &str) -> CertificateAuthority {
+        CertificateAuthority::new_root(Name::new(name), MlDsaParameterSet::MlDsa65, Validity::for_days(3650).unwrap()).unwrap()
+    }
+
+    fn subordinate(parent: &CertificateAuthority, name: &str, path_len: Option<u8>) -> CertificateAuthority {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let certificate = parent
+            .issue_subordinate(Name::new(name), key.verifying_key(), Validity::for_days(365).unwrap(), path_len)
+            .unwrap();
+        CertificateAuthority::from_parts(key, certificate).unwrap()
+    }
+
+    fn kem_public_key() -> Vec<u8> {
+        kyber1024::keypair().0.as_bytes().to_vec()
+    }
+
+    fn error_name(err: anyhow::Error) -> &'static str {
+        Error::from(err).code().name
+    }
+
+    #[test]
+    fn test_chain_through_intermediate() {
+        let root = new_root("Example Root CA");
+        let issuing = subordinate(&root, "Example Issuing CA", Some(0));
+        let public_key = kem_public_key();
+        let leaf = issuing
+            .issue_kem_certificate(Name::new("settlement-inbound"), &public_key, Validity::for_days(90).unwrap())
+            .unwrap();
+
+        let mut trust = TrustStore::new();
+        trust.add_anchor(root.certificate().clone()).unwrap();
+        let intermediates = [issuing.certificate().clone()];
+        assert_eq!(trust.verify_kem_certificate(&leaf, &intermediates).unwrap(), public_key);
+
+        let path = trust.verify(&leaf, &intermediates, now()).unwrap();
+        let subjects: Vec<_> = path.iter().map(|c| c.subject().to_string()).collect();
+        assert_eq!(subjects, ["CN=settlement-inbound", "CN=Example Issuing CA", "CN=Example Root CA"]);
+
+        // Without the intermediate, or under another root, the chain breaks
+        assert_eq!(error_name(trust.verify_kem_certificate(&leaf, &[]).unwrap_err()), "X509_UNTRUSTED_ISSUER");
+        let mut other = TrustStore::new();
+        other.add_anchor(new_root("Example Root CA").certificate().clone()).unwrap();
+        assert!(other.verify_kem_certificate(&leaf, &intermediates).is_err());
+    }
+
+    #[test]
+    fn test_expiry_checked_on_every_certificate() {
+        let root = new_root("Root");
+        let mut trust = TrustStore::new();
+        trust.add_anchor(root.certificate().clone()).unwrap();
+        let leaf = root
+            .issue_kem_certificate(Name::new("inbound"), &kem_public_key(), Validity::for_days(30).unwrap())
+            .unwrap();
+
+        let issued = leaf.validity().not_before;
+        trust.verify(&leaf, &[], issued + DAY).unwrap();
+        assert_eq!(error_name(trust.verify(&leaf, &[], issued + 31 * DAY).unwrap_err()), "X509_EXPIRED");
+        assert_eq!(error_name(trust.verify(&leaf, &[], issued - DAY).unwrap_err()), "X509_NOT_YET_VALID");
+        // Issued certificates are cut off when their issuer expires
+        let late = root.certificate().validity().not_after + 1;
+        let long_lived = Validity::new(issued, late + DAY).unwrap();
+        let leaf = root.issue_kem_certificate(Name::new("inbound"), &kem_public_key(), long_lived).unwrap();
+        assert_eq!(leaf.validity().not_after, root.certificate().validity().not_after);
+        assert_eq!(error_name(trust.verify(&leaf, &[], late).unwrap_err()), "X509_EXPIRED");
+    }
+
+    #[test]
+    fn test_constraints_enforced() {
+        let root = new_root("Root");
+        let mut trust = TrustStore::new();
+        trust.add_anchor(root.certificate().clone()).unwrap();
+
+        // An end-entity key cannot act as an issuer
+        let rogue_key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let request = CertificateRequest::new(
+            Name::new("partner-signing"),
+            PublicKeyInfo::MlDsa(rogue_key.verifying_key().clone()),
+            &rogue_key,
+        )
+        .unwrap();
+        let end_entity = root.issue_from_request(&request, None, Validity::for_days(30).unwrap()).unwrap();
+        assert!(CertificateAuthority::from_parts(rogue_key, end_entity).is_err());
+
+        // pathLen 0 forbids a CA below the issuing CA
+        let issuing = subordinate(&root, "Issuing", Some(0));
+        assert!(issuing
+            .issue_subordinate(Name::new("Deeper"), rogue_ca_key().verifying_key(), Validity::for_days(1).unwrap(), None)
+            .is_err());
+
+        // A leaf certificate for a signing key is not one to encrypt to
+        let leaf = issuing.issue_from_request(&request, None, Validity::for_days(30).unwrap()).unwrap();
+        let err = trust.verify_kem_certificate(&leaf, &[issuing.certificate().clone()]).unwrap_err();
+        assert_eq!(error_name(err), "X509_INVALID_EXTENSION");
+    }
+
+    #[test]
+    fn test_path_search_backtracks() {
+        let root = new_root("Root");
+        let mut trust = TrustStore::new();
+        trust.add_anchor(root.certificate().clone()).unwrap();
+
+        // The same issuing key certified twice; the first copy lapses early
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let short_lived = root
+            .issue_subordinate(Name::new("Issuing"), key.verifying_key(), Validity::for_days(1).unwrap(), Some(0))
+            .unwrap();
+        let renewed = root
+            .issue_subordinate(Name::new("Issuing"), key.verifying_key(), Validity::for_days(365).unwrap(), Some(0))
+            .unwrap();
+        let issuing = CertificateAuthority::from_parts(key, renewed.clone()).unwrap();
+        let leaf = issuing
+            .issue_kem_certificate(Name::new("inbound"), &kem_public_key(), Validity::for_days(30).unwrap())
+            .unwrap();
+
+        let later = leaf.validity().not_before + 2 * DAY;
+        let path = trust.verify(&leaf, &[short_lived.clone(), renewed.clone()], later).unwrap();
+        assert_eq!(path[1], renewed);
+        assert_eq!(error_name(trust.verify(&leaf, &[short_lived], later).unwrap_err()), "X509_EXPIRED");
+    }
+
+    #[test]
+    fn test_ca_leaf_not_encapsulated_to() {
+        let root = new_root("Root");
+        let mut trust = TrustStore::new();
+        trust.add_anchor(root.certificate().clone()).unwrap();
+
+        let public_key = PublicKeyInfo::ml_kem_1024(&kem_public_key()).unwrap();
+        let tbs = TbsCertificate {
+            serial: vec![0x41; 16],
+            issuer: root.certificate().subject().clone(),
+            validity: Validity::for_days(30).unwrap(),
+            subject: Name::new("inbound"),
+            extensions: Extensions {
+                basic_constraints: Some(BasicConstraints { ca: true, path_len: None }),
+                key_usage: Some(KeyUsage::KEY_ENCIPHERMENT),
+                subject_key_id: Some(public_key.key_identifier()),
+                authority_key_id: root.certificate().subject_key_id().map(<[u8]>::to_vec),
+            },
+            public_key,
+        };
+        let leaf = Certificate::sign(tbs, root.signing_key()).unwrap();
+        trust.verify(&leaf, &[], now()).unwrap();
+        assert_eq!(error_name(trust.verify_kem_certificate(&leaf, &[]).unwrap_err()), "X509_INVALID_EXTENSION");
+    }
+
+    fn rogue_ca_key() -> MlDsaSigningKey {
+        MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44)
+    }
+
+    #[test]
+    fn test_impostor_root_rejected() {
+        let root = new_root("Root");
+        let mut trust = TrustStore::new();
+        trust.add_anchor(root.certificate().clone()).unwrap();
+
+        // The real root certificate is useless without its key
+        let err = CertificateAuthority::from_parts(rogue_ca_key(), root.certificate().clone()).unwrap_err();
+        assert_eq!(error_name(err), "X509_KEY_MISMATCH");
+
+        // A lookalike root with the same name issues nothing we accept
+        let impostor = new_root("Root");
+        let forged = impostor
+            .issue_kem_certificate(Name::new("inbound"), &kem_public_key(), Validity::for_days(1).unwrap())
+            .unwrap();
+        assert_eq!(error_name(trust.verify_kem_certificate(&forged, &[]).unwrap_err()), "X509_UNTRUSTED_ISSUER");
+    }
+
+    #[test]
+    fn test_revoked_key_not_encapsulated_to() {
+        let root = new_root("Root");
+        let (public_key, secret_key) = kyber1024::keypair();
+        let leaf = root
+            .issue_kem_certificate(Name::new("inbound"), public_key.as_bytes(), Validity::for_days(30).unwrap())
+            .unwrap();
+        let registry = Arc::new(RevocationRegistry::new(root.certificate()));
+        registry.record_issued(&leaf).unwrap();
+
+        let mut trust = TrustStore::new();
+        trust.add_anchor(root.certificate().clone()).unwrap();
+        let responder = Arc::new(StatusResponder::new(root, registry.clone()).unwrap());
+        trust.set_revocation_checker(Arc::new(RevocationChecker::new(responder.clone())));
+
+        let (shared, ciphertext) = trust.encapsulate(&leaf, &[]).unwrap();
+        let ciphertext = kyber1024::Ciphertext::from_bytes(&ciphertext).unwrap();
+        let expected = SecureSecret::from_shared(kyber1024::decapsulate(&ciphertext, &secret_key));
+        assert!(shared.constant_time_eq(&expected));
+
+        // A cached good answer stands until it expires; a fresh checker sees the revocation
+        registry.revoke(leaf.serial(), RevocationReason::KeyCompromise, now()).unwrap().unwrap();
+        assert!(trust.encapsulate(&leaf, &[]).is_ok());
+        trust.set_revocation_checker(Arc::new(RevocationChecker::new(responder)));
+        assert_eq!(error_name(trust.encapsulate(&leaf, &[]).unwrap_err()), "X509_REVOKED");
+    }
+
+    #[test]
+    fn test_revoked_intermediate_rejected() {
+        let root = new_root("Root");
+        let issuing = subordinate(&root, "Issuing", Some(0));
+        let leaf = issuing
+            .issue_kem_certificate(Name::new("inbound"), &kem_public_key(), Validity::for_days(30).unwrap())
+            .unwrap();
+        let intermediates = [issuing.certificate().clone()];
+
+        let root_registry = Arc::new(RevocationRegistry::new(root.certificate()));
+        root_registry.record_issued(issuing.certificate()).unwrap();
+        let issuing_registry = Arc::new(RevocationRegistry::new(issuing.certificate()));
+        issuing_registry.record_issued(&leaf).unwrap();
+
+        let mut trust = TrustStore::new();
+        trust.add_anchor(root.certificate().clone()).unwrap();
+        let root_responder = Arc::new(StatusResponder::new(root, root_registry.clone()).unwrap());
+        let issuing_responder = Arc::new(StatusResponder::new(issuing, issuing_registry).unwrap());
+        let checker = || {
+            RevocationChecker::new(issuing_responder.clone())
+                .with_issuer_source(root_responder.ca_certificate().subject(), root_responder.clone())
+        };
+        trust.set_revocation_checker(Arc::new(checker()));
+        assert!(trust.verify_kem_certificate(&leaf, &intermediates).is_ok());
+
+        // The leaf itself is still good, but its issuer is not
+        root_registry
+            .revoke(intermediates[0].serial(), RevocationReason::CaCompromise, now())
+            .unwrap()
+            .unwrap();
+        trust.set_revocation_checker(Arc::new(checker()));
+        let err = trust.verify_kem_certificate(&leaf, &intermediates).unwrap_err();
+        assert_eq!(error_name(err), "X509_REVOKED");
+
+        // Without a source for the root's answers the check fails closed
+        trust.set_revocation_checker(Arc::new(RevocationChecker::new(issuing_responder.clone())));
+        assert!(trust.verify_kem_certificate(&leaf, &intermediates).is_err());
+    }
+}
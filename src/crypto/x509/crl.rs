@@ -0,0 +1,364 @@
+//! Certificate revocation lists (RFC 5280, section 5).
+//!
+//! ```text
+//! CertificateList ::= SEQUENCE {
+//!     tbsCertList         SEQUENCE {
+//!         version             INTEGER (v2),
+//!         signature           AlgorithmIdentifier,
+//!         issuer              Name,
+//!         thisUpdate          Time,
+//!         nextUpdate          Time,
+//!         revokedCertificates SEQUENCE OF SEQUENCE {
+//!             userCertificate     INTEGER,
+//!             revocationDate      Time,
+//!             crlEntryExtensions  Extensions OPTIONAL } OPTIONAL,
+//!         crlExtensions   [0] EXPLICIT Extensions },
+//!     signatureAlgorithm  AlgorithmIdentifier,
+//!     signatureValue      BIT STRING }
+//! ```
+//!
+//! Every list carries a cRLNumber and the issuer's key identifier; entries
+//! carry a reasonCode unless the reason is unspecified.
+
+use super::{
+    algorithm_identifier, encode_time, invalid_extension, read_algorithm_identifier, read_time,
+    signature_algorithm_oid, signature_parameter_set, Certificate, KeyUsage, Name, Validity, SIGNATURE_CONTEXT,
+    OID_AUTHORITY_KEY_IDENTIFIER,
+};
+use crate::crypto::signature::{MlDsaSignature, MlDsaSigningKey, SigningMode};
+use crate::error::{CertificateError, EncodingError};
+use crate::utils::der::{self, DerReader};
+use crate::utils::encoding;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+pub const CRL_LABEL: &str = "X509 CRL";
+
+const VERSION_2: u64 = 1;
+const OID_CRL_NUMBER: &str = "2.5.29.20";
+const OID_REASON_CODE: &str = "2.5.29.21";
+
+/// CRLReason codes this crate issues
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RevocationReason {
+    Unspecified,
+    KeyCompromise,
+    CaCompromise,
+    AffiliationChanged,
+    Superseded,
+    CessationOfOperation,
+    PrivilegeWithdrawn,
+}
+
+impl RevocationReason {
+    pub fn code(&self) -> u8 {
+        match self {
+            RevocationReason::Unspecified => 0,
+            RevocationReason::KeyCompromise => 1,
+            RevocationReason::CaCompromise => 2,
+            RevocationReason::AffiliationChanged => 3,
+            RevocationReason::Superseded => 4,
+            RevocationReason::CessationOfOperation => 5,
+            RevocationReason::PrivilegeWithdrawn => 9,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(RevocationReason::Unspecified),
+            1 => Some(RevocationReason::KeyCompromise),
+            2 => Some(RevocationReason::CaCompromise),
+            3 => Some(RevocationReason::AffiliationChanged),
+            4 => Some(RevocationReason::Superseded),
+            5 => Some(RevocationReason::CessationOfOperation),
+            9 => Some(RevocationReason::PrivilegeWithdrawn),
+            _ => None,
+        }
+    }
+}
+
+/// One entry of a revocation list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevokedCertificate {
+    pub serial: Vec<u8>,
+    pub revoked_at: u64,
+    pub reason: RevocationReason,
+}
+
+impl RevokedCertificate {
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut fields = vec![der::integer_bytes(&self.serial), encode_time(self.revoked_at)];
+        if self.reason != RevocationReason::Unspecified {
+            let reason = der::sequence(&[
+                der::oid(OID_REASON_CODE)?,
+                der::octet_string(&der::enumerated(self.reason.code())),
+            ]);
+            fields.push(der::sequence(&[reason]));
+        }
+        Ok(der::sequence(&fields))
+    }
+
+    fn read(reader: &mut DerReader<'_>) -> Result<Self> {
+        let mut entry = reader.read_sequence()?;
+        let serial = entry.read_integer_bytes()?.to_vec();
+        let revoked_at = read_time(&mut entry)?;
+        let mut reason = RevocationReason::Unspecified;
+        if !entry.is_empty() {
+            for (oid, critical, value) in read_extensions(&mut entry.read_sequence()?)? {
+                match oid.as_str() {
+                    OID_REASON_CODE => {
+                        let code = DerReader::new(value).read_enumerated()?;
+                        reason = RevocationReason::from_code(code)
+                            .ok_or_else(|| invalid_extension(&format!("unknown reason code {}", code)))?;
+                    }
+                    _ if critical => return Err(invalid_extension(&format!("unsupported critical entry extension {}", oid))),
+                    _ => {}
+                }
+            }
+        }
+        entry.finish()?;
+        Ok(Self { serial, revoked_at, reason })
+    }
+}
+
+/// Signed list of revoked certificates of one issuer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateRevocationList {
+    der: Vec<u8>,
+    tbs_der: Vec<u8>,
+    issuer: Name,
+    number: u64,
+    validity: Validity,
+    entries: Vec<RevokedCertificate>,
+    authority_key_id: Option<Vec<u8>>,
+    signature: MlDsaSignature,
+}
+
+impl CertificateRevocationList {
+    /// Signs a list for the CA `issuer`; `validity` spans thisUpdate to nextUpdate
+    pub(super) fn sign(
+        issuer: &Certificate,
+        signer: &MlDsaSigningKey,
+        number: u64,
+        validity: Validity,
+        entries: Vec<RevokedCertificate>,
+    ) -> Result<Self> {
+        let algorithm = algorithm_identifier(signature_algorithm_oid(signer.parameter_set()))?;
+        let mut fields = vec![
+            der::integer_u64(VERSION_2),
+            algorithm.clone(),
+            issuer.subject().encode()?,
+            encode_time(validity.not_before),
+            encode_time(validity.not_after),
+        ];
+        if !entries.is_empty() {
+            fields.push(der::sequence(&entries.iter().map(RevokedCertificate::encode).collect::<Result<Vec<_>>>()?));
+        }
+        let mut extensions = vec![der::sequence(&[der::oid(OID_CRL_NUMBER)?, der::octet_string(&der::integer_u64(number))])];
+        if let Some(id) = issuer.subject_key_id() {
+            let key_identifier = der::sequence(&[der::tlv(der::context_tag(0, false), id)]);
+            extensions.push(der::sequence(&[der::oid(OID_AUTHORITY_KEY_IDENTIFIER)?, der::octet_string(&key_identifier)]));
+        }
+        fields.push(der::explicit(0, &der::sequence(&extensions)));
+
+        let tbs_der = der::sequence(&fields);
+        let signature = signer.sign(&tbs_der, SIGNATURE_CONTEXT, SigningMode::Hedged)?;
+        let der = der::sequence(&[tbs_der.clone(), algorithm, der::bit_string(&signature.bytes)]);
+        Ok(Self {
+            der,
+            tbs_der,
+            issuer: issuer.subject().clone(),
+            number,
+            validity,
+            entries,
+            authority_key_id: issuer.subject_key_id().map(<[u8]>::to_vec),
+            signature,
+        })
+    }
+
+    pub fn from_der(der_bytes: &[u8]) -> Result<Self> {
+        let mut outer = DerReader::new(der_bytes);
+        let mut list = outer.read_sequence()?;
+        outer.finish()?;
+
+        let (tag, tbs_content, tbs_der) = list.read_any()?;
+        if tag != der::TAG_SEQUENCE {
+            return Err(EncodingError::InvalidDer("TBSCertList is not a SEQUENCE".to_string()).into());
+        }
+        let outer_algorithm = read_algorithm_identifier(&mut list)?;
+        let signature_bytes = list.read_bit_string()?;
+        list.finish()?;
+
+        let mut tbs = DerReader::new(tbs_content);
+        let version = tbs.read_integer_u64()?;
+        if version != VERSION_2 {
+            return Err(anyhow!("Only v2 CRLs are supported, got version {}", version + 1));
+        }
+        let algorithm = read_algorithm_identifier(&mut tbs)?;
+        if algorithm != outer_algorithm {
+            return Err(CertificateError::UnsupportedAlgorithm(format!("{} signed as {}", algorithm, outer_algorithm)).into());
+        }
+        let parameter_set = signature_parameter_set(&algorithm)?;
+        let issuer = Name::read(&mut tbs)?;
+        let this_update = read_time(&mut tbs)?;
+        let next_update = read_time(&mut tbs)?;
+
+        let mut entries = Vec::new();
+        if tbs.peek_tag() == Some(der::TAG_SEQUENCE) {
+            let mut revoked = tbs.read_sequence()?;
+            while !revoked.is_empty() {
+                entries.push(RevokedCertificate::read(&mut revoked)?);
+            }
+        }
+
+        let mut number = None;
+        let mut authority_key_id = None;
+        if let Some(mut field) = tbs.read_optional_explicit(0)? {
+            for (oid, critical, value) in read_extensions(&mut field.read_sequence()?)? {
+                let mut inner = DerReader::new(value);
+                match oid.as_str() {
+                    OID_CRL_NUMBER => number = Some(inner.read_integer_u64()?),
+                    OID_AUTHORITY_KEY_IDENTIFIER => {
+                        let mut fields = inner.read_sequence()?;
+                        if fields.peek_tag() == Some(der::context_tag(0, false)) {
+                            authority_key_id = Some(fields.read_expected(der::context_tag(0, false))?.to_vec());
+                        }
+                    }
+                    _ if critical => return Err(invalid_extension(&format!("unsupported critical CRL extension {}", oid))),
+                    _ => {}
+                }
+            }
+            field.finish()?;
+        }
+        tbs.finish()?;
+
+        Ok(Self {
+            der: der_bytes.to_vec(),
+            tbs_der: tbs_der.to_vec(),
+            issuer,
+            number: number.ok_or_else(|| invalid_extension("CRL without cRLNumber"))?,
+            validity: Validity { not_before: this_update, not_after: next_update },
+            entries,
+            authority_key_id,
+            signature: MlDsaSignature { parameter_set, bytes: signature_bytes.to_vec() },
+        })
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_der(&encoding::from_pem(CRL_LABEL, pem)?)
+    }
+
+    pub fn as_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    pub fn to_pem(&self) -> String {
+        encoding::to_pem(CRL_LABEL, &self.der)
+    }
+
+    pub fn issuer(&self) -> &Name {
+        &self.issuer
+    }
+
+    /// Monotonic cRLNumber; a relying party never goes back to a lower one
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    pub fn this_update(&self) -> u64 {
+        self.validity.not_before
+    }
+
+    pub fn next_update(&self) -> u64 {
+        self.validity.not_after
+    }
+
+    pub fn entries(&self) -> &[RevokedCertificate] {
+        &self.entries
+    }
+
+    /// The entry for `serial`, if that certificate is revoked
+    pub fn find(&self, serial: &[u8]) -> Option<&RevokedCertificate> {
+        self.entries.iter().find(|entry| entry.serial == serial)
+    }
+
+    /// Checks that `issuer` signed this list and that it is current at `at`
+    pub fn verify(&self, issuer: &Certificate, at: u64) -> Result<()> {
+        let matches_key = match (&self.authority_key_id, issuer.subject_key_id()) {
+            (Some(authority), Some(subject)) => authority == subject,
+            _ => true,
+        };
+        if &self.issuer != issuer.subject() || !matches_key {
+            return Err(CertificateError::UntrustedIssuer(self.issuer.to_string()).into());
+        }
+        if issuer.key_usage().is_some_and(|usage| !usage.contains(KeyUsage::CRL_SIGN)) {
+            return Err(CertificateError::InvalidChain(format!("{} may not sign CRLs", issuer.subject())).into());
+        }
+        let key = issuer
+            .verifying_key()
+            .ok_or_else(|| CertificateError::InvalidChain(format!("{} has no signing key", issuer.subject())))?;
+        key.verify(&self.tbs_der, SIGNATURE_CONTEXT, &self.signature)?;
+        self.validity.check(at, &self.issuer)
+    }
+}
+
+// (OID, critical, value) of each extension in a SEQUENCE OF Extension
+fn read_extensions<'a>(list: &mut DerReader<'a>) -> Result<Vec<(String, bool, &'a [u8])>> {
+    let mut extensions = Vec::new();
+    while !list.is_empty() {
+        let mut extension = list.read_sequence()?;
+        let oid = extension.read_oid()?;
+        let critical = if extension.peek_tag() == Some(der::TAG_BOOLEAN) { extension.read_boolean()? } else { false };
+        let value = extension.read_octet_string()?;
+        extension.finish()?;
+        extensions.push((oid, critical, value));
+    }
+    Ok(extensions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signature::MlDsaParameterSet;
+    use crate::crypto::x509::{now, CertificateAuthority};
+
+    const HOUR: u64 = 60 * 60;
+
+    #[test]
+    fn test_crl_round_trip() {
+        let ca = CertificateAuthority::new_root(Name::new("Root"), MlDsaParameterSet::MlDsa65, Validity::for_days(30).unwrap())
+            .unwrap();
+        let entries = vec![
+            RevokedCertificate { serial: vec![0x41, 0x02], revoked_at: 1_700_000_000, reason: RevocationReason::KeyCompromise },
+            RevokedCertificate { serial: vec![0x7f; 16], revoked_at: 1_700_000_100, reason: RevocationReason::Unspecified },
+        ];
+        let validity = Validity::new(now(), now() + HOUR).unwrap();
+        let crl = ca.issue_crl(7, entries.clone(), validity).unwrap();
+
+        let parsed = CertificateRevocationList::from_pem(&crl.to_pem()).unwrap();
+        assert_eq!(parsed, crl);
+        assert_eq!(parsed.number(), 7);
+        assert_eq!(parsed.entries(), &entries[..]);
+        assert_eq!(parsed.find(&[0x41, 0x02]).unwrap().reason, RevocationReason::KeyCompromise);
+        assert!(parsed.find(&[0x41, 0x03]).is_none());
+
+        parsed.verify(ca.certificate(), now()).unwrap();
+        let err = parsed.verify(ca.certificate(), now() + 2 * HOUR).unwrap_err();
+        assert_eq!(crate::error::Error::from(err).code().name, "X509_EXPIRED");
+
+        let other = CertificateAuthority::new_root(Name::new("Root"), MlDsaParameterSet::MlDsa65, Validity::for_days(30).unwrap())
+            .unwrap();
+        assert!(parsed.verify(other.certificate(), now()).is_err());
+    }
+
+    #[test]
+    fn test_empty_crl() {
+        let ca = CertificateAuthority::new_root(Name::new("Root"), MlDsaParameterSet::MlDsa44, Validity::for_days(1).unwrap())
+            .unwrap();
+        let crl = ca.issue_crl(1, Vec::new(), Validity::new(now(), now() + HOUR).unwrap()).unwrap();
+        let parsed = CertificateRevocationList::from_der(crl.as_der()).unwrap();
+        assert!(parsed.entries().is_empty());
+        parsed.verify(ca.certificate(), now()).unwrap();
+    }
+}
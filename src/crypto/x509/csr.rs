@@ -0,0 +1,168 @@
+//! PKCS#10 certification requests (RFC 2986).
+//!
+//! ```text
+//! CertificationRequest ::= SEQUENCE {
+//!     certificationRequestInfo  SEQUENCE {
+//!         version       INTEGER (0),
+//!         subject       Name,
+//!         subjectPKInfo SubjectPublicKeyInfo,
+//!         attributes    [0] IMPLICIT SET OF Attribute },
+//!     signatureAlgorithm        AlgorithmIdentifier,
+//!     signature                 BIT STRING }
+//! ```
+//!
+//! Requested attributes and extensions are accepted but ignored: the CA alone
+//! decides the key usage and constraints of what it issues.
+
+use super::{
+    algorithm_identifier, read_algorithm_identifier, signature_algorithm_oid, signature_parameter_set, Name,
+    PublicKeyInfo, CERTIFICATE_REQUEST_LABEL, SIGNATURE_CONTEXT,
+};
+use crate::crypto::signature::{MlDsaSignature, MlDsaSigningKey, MlDsaVerifyingKey, SigningMode};
+use crate::error::{CertificateError, EncodingError};
+use crate::utils::der::{self, DerReader};
+use crate::utils::encoding;
+use anyhow::{anyhow, Result};
+
+const VERSION_1: u64 = 0;
+
+/// Signed request for a certificate over `public_key`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateRequest {
+    der: Vec<u8>,
+    info_der: Vec<u8>,
+    subject: Name,
+    public_key: PublicKeyInfo,
+    signature: MlDsaSignature,
+}
+
+impl CertificateRequest {
+    /// Builds and signs a request. An ML-DSA key must sign its own request;
+    /// a request for an ML-KEM key is signed with the requester's identity key.
+    pub fn new(subject: Name, public_key: PublicKeyInfo, signer: &MlDsaSigningKey) -> Result<Self> {
+        if let PublicKeyInfo::MlDsa(key) = &public_key {
+            if key != signer.verifying_key() {
+                return Err(anyhow!("An ML-DSA certificate request must be signed by the requested key"));
+            }
+        }
+
+        let info_der = der::sequence(&[
+            der::integer_u64(VERSION_1),
+            subject.encode()?,
+            public_key.encode()?,
+            der::tlv(der::context_tag(0, true), &[]),
+        ]);
+        let signature = signer.sign(&info_der, SIGNATURE_CONTEXT, SigningMode::Hedged)?;
+        let der = der::sequence(&[
+            info_der.clone(),
+            algorithm_identifier(signature_algorithm_oid(signer.parameter_set()))?,
+            der::bit_string(&signature.bytes),
+        ]);
+        Ok(Self { der, info_der, subject, public_key, signature })
+    }
+
+    pub fn from_der(der_bytes: &[u8]) -> Result<Self> {
+        let mut outer = DerReader::new(der_bytes);
+        let mut request = outer.read_sequence()?;
+        outer.finish()?;
+
+        let (tag, info_content, info_der) = request.read_any()?;
+        if tag != der::TAG_SEQUENCE {
+            return Err(EncodingError::InvalidDer("CertificationRequestInfo is not a SEQUENCE".to_string()).into());
+        }
+        let parameter_set = signature_parameter_set(&read_algorithm_identifier(&mut request)?)?;
+        let signature_bytes = request.read_bit_string()?;
+        request.finish()?;
+
+        let mut info = DerReader::new(info_content);
+        let version = info.read_integer_u64()?;
+        if version != VERSION_1 {
+            return Err(anyhow!("Unsupported certification request version {}", version));
+        }
+        let subject = Name::read(&mut info)?;
+        let public_key = PublicKeyInfo::read(&mut info)?;
+        info.read_expected(der::context_tag(0, true))?;
+        info.finish()?;
+
+        Ok(Self {
+            der: der_bytes.to_vec(),
+            info_der: info_der.to_vec(),
+            subject,
+            public_key,
+            signature: MlDsaSignature { parameter_set, bytes: signature_bytes.to_vec() },
+        })
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_der(&encoding::from_pem(CERTIFICATE_REQUEST_LABEL, pem)?)
+    }
+
+    pub fn as_der(&self) -> &[u8] {
+        &self.der
+    }
+
+    pub fn to_pem(&self) -> String {
+        encoding::to_pem(CERTIFICATE_REQUEST_LABEL, &self.der)
+    }
+
+    pub fn subject(&self) -> &Name {
+        &self.subject
+    }
+
+    pub fn public_key(&self) -> &PublicKeyInfo {
+        &self.public_key
+    }
+
+    /// Checks proof of possession. ML-DSA requests verify against their own
+    /// key; ML-KEM requests against `requester`, the identity key the CA
+    /// holds for whoever is asking.
+    pub fn verify(&self, requester: Option<&MlDsaVerifyingKey>) -> Result<()> {
+        let key = match (&self.public_key, requester) {
+            (PublicKeyInfo::MlDsa(key), _) => key,
+            (PublicKeyInfo::MlKem1024(_), Some(key)) => key,
+            (PublicKeyInfo::MlKem1024(_), None) => {
+                return Err(CertificateError::UntrustedIssuer(format!(
+                    "ML-KEM request from {} needs a requester identity key",
+                    self.subject
+                ))
+                .into())
+            }
+        };
+        key.verify(&self.info_der, SIGNATURE_CONTEXT, &self.signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signature::MlDsaParameterSet;
+    use pqcrypto_kyber::kyber1024;
+    use pqcrypto_traits::kem::PublicKey as _;
+
+    #[test]
+    fn test_kem_request_signed_by_identity_key() {
+        let identity = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let public_key = PublicKeyInfo::ml_kem_1024(kyber1024::keypair().0.as_bytes()).unwrap();
+        let request = CertificateRequest::new(Name::new("partner-inbound"), public_key.clone(), &identity).unwrap();
+
+        let parsed = CertificateRequest::from_pem(&request.to_pem()).unwrap();
+        assert_eq!(parsed, request);
+        assert_eq!(parsed.public_key(), &public_key);
+        parsed.verify(Some(identity.verifying_key())).unwrap();
+        assert!(parsed.verify(None).is_err());
+
+        let stranger = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        assert!(parsed.verify(Some(stranger.verifying_key())).is_err());
+    }
+
+    #[test]
+    fn test_dsa_request_is_self_signed() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44);
+        let public_key = PublicKeyInfo::MlDsa(key.verifying_key().clone());
+        let request = CertificateRequest::new(Name::new("partner-signing"), public_key.clone(), &key).unwrap();
+        CertificateRequest::from_der(request.as_der()).unwrap().verify(None).unwrap();
+
+        let other = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44);
+        assert!(CertificateRequest::new(Name::new("partner-signing"), public_key, &other).is_err());
+    }
+}
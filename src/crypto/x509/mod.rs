@@ -0,0 +1,427 @@
+//! X.509 v3 certificates for ML-KEM public keys, issued by an ML-DSA CA.
+//!
+//! Partners encrypt to our Kyber keys, so they need proof that a public key
+//! really belongs to us. A [`CertificateAuthority`] holding an ML-DSA signing
+//! key issues RFC 5280 certificates over ML-KEM-1024 `SubjectPublicKeyInfo`s
+//! with `keyUsage=keyEncipherment`; a partner holding our root certificate in
+//! a [`TrustStore`] validates the chain, including validity periods, basic
+//! constraints, key usage and path length, and gets the ML-KEM key back.
+//!
+//! Algorithm identifiers are the NIST ones, always with absent parameters:
+//!
+//! | algorithm   | OID                       |
+//! |-------------|---------------------------|
+//! | ML-KEM-1024 | `2.16.840.1.101.3.4.4.3`  |
+//! | ML-DSA-44   | `2.16.840.1.101.3.4.3.17` |
+//! | ML-DSA-65   | `2.16.840.1.101.3.4.3.18` |
+//! | ML-DSA-87   | `2.16.840.1.101.3.4.3.19` |
+//!
+//! Certificates and PKCS#10 requests are signed with pure ML-DSA and an
+//! empty context string. An ML-KEM key cannot sign, so a
+//! [`CertificateRequest`] for one is signed with the requester's ML-DSA
+//! identity key instead, which the CA must already know.
+//!
+//! Certificates are revocable: the CA signs [`CertificateRevocationList`]s
+//! and answers status queries through a [`StatusResponder`], and a
+//! [`TrustStore`] given a [`RevocationChecker`] refuses to encapsulate to
+//! a revoked key.
+
+mod ca;
+mod certificate;
+mod chain;
+mod crl;
+mod csr;
+mod status;
+
+pub use ca::CertificateAuthority;
+pub use certificate::{BasicConstraints, Certificate, PublicKeyInfo};
+pub use chain::{TrustStore, MAX_CHAIN_DEPTH};
+pub use crl::{CertificateRevocationList, RevocationReason, RevokedCertificate, CRL_LABEL};
+pub use csr::CertificateRequest;
+pub use status::{
+    CertificateStatus, RevocationChecker, RevocationRegistry, SignedStatus, StatusResponder, StatusResponse,
+    StatusSource, DEFAULT_UPDATE_INTERVAL, STATUS_CONTEXT,
+};
+
+use crate::crypto::signature::MlDsaParameterSet;
+use crate::error::{CertificateError, EncodingError};
+use crate::utils::der::{self, DerReader, TAG_GENERALIZED_TIME, TAG_PRINTABLE_STRING, TAG_UTC_TIME, TAG_UTF8_STRING};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::ops::BitOr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const CERTIFICATE_LABEL: &str = "CERTIFICATE";
+pub const CERTIFICATE_REQUEST_LABEL: &str = "CERTIFICATE REQUEST";
+
+const OID_ML_KEM_1024: &str = "2.16.840.1.101.3.4.4.3";
+const OID_ML_DSA_44: &str = "2.16.840.1.101.3.4.3.17";
+const OID_ML_DSA_65: &str = "2.16.840.1.101.3.4.3.18";
+const OID_ML_DSA_87: &str = "2.16.840.1.101.3.4.3.19";
+
+const OID_COMMON_NAME: &str = "2.5.4.3";
+const OID_COUNTRY: &str = "2.5.4.6";
+const OID_ORGANIZATION: &str = "2.5.4.10";
+const OID_ORGANIZATIONAL_UNIT: &str = "2.5.4.11";
+
+const OID_SUBJECT_KEY_IDENTIFIER: &str = "2.5.29.14";
+const OID_KEY_USAGE: &str = "2.5.29.15";
+const OID_BASIC_CONSTRAINTS: &str = "2.5.29.19";
+const OID_AUTHORITY_KEY_IDENTIFIER: &str = "2.5.29.35";
+
+// Certificates and requests are signed with pure ML-DSA and no context
+const SIGNATURE_CONTEXT: &[u8] = b"";
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Distinguished name as an ordered list of (attribute OID, value) pairs
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Name {
+    attributes: Vec<(String, String)>,
+}
+
+impl Name {
+    /// Name made of a single common name
+    pub fn new(common_name: &str) -> Self {
+        Self::default().with(OID_COMMON_NAME, common_name)
+    }
+
+    pub fn with_organization(self, organization: &str) -> Self {
+        self.with(OID_ORGANIZATION, organization)
+    }
+
+    pub fn with_organizational_unit(self, unit: &str) -> Self {
+        self.with(OID_ORGANIZATIONAL_UNIT, unit)
+    }
+
+    /// Two-letter ISO 3166 country code
+    pub fn with_country(self, country: &str) -> Self {
+        self.with(OID_COUNTRY, country)
+    }
+
+    fn with(mut self, oid: &str, value: &str) -> Self {
+        self.attributes.push((oid.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn common_name(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(oid, _)| oid == OID_COMMON_NAME)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let rdns = self
+            .attributes
+            .iter()
+            .map(|(oid, value)| {
+                // Country codes must be PrintableString (RFC 5280, appendix A.1)
+                let tag = if oid == OID_COUNTRY { TAG_PRINTABLE_STRING } else { TAG_UTF8_STRING };
+                Ok(der::set(&[der::sequence(&[der::oid(oid)?, der::tlv(tag, value.as_bytes())])]))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(der::sequence(&rdns))
+    }
+
+    fn read(reader: &mut DerReader<'_>) -> Result<Self> {
+        let mut rdns = reader.read_sequence()?;
+        let mut attributes = Vec::new();
+        while !rdns.is_empty() {
+            let mut rdn = rdns.read_set()?;
+            while !rdn.is_empty() {
+                let mut attribute = rdn.read_sequence()?;
+                let oid = attribute.read_oid()?;
+                let (tag, value, _) = attribute.read_any()?;
+                if tag != TAG_UTF8_STRING && tag != TAG_PRINTABLE_STRING {
+                    return Err(EncodingError::InvalidDer(format!("Unsupported string type 0x{:02x} in name", tag)).into());
+                }
+                let value = String::from_utf8(value.to_vec())
+                    .map_err(|_| EncodingError::InvalidDer("Invalid string in name".to_string()))?;
+                attribute.finish()?;
+                attributes.push((oid, value));
+            }
+        }
+        Ok(Self { attributes })
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (oid, value)) in self.attributes.iter().enumerate() {
+            let key = match oid.as_str() {
+                OID_COMMON_NAME => "CN",
+                OID_COUNTRY => "C",
+                OID_ORGANIZATION => "O",
+                OID_ORGANIZATIONAL_UNIT => "OU",
+                other => other,
+            };
+            write!(f, "{}{}={}", if i == 0 { "" } else { ", " }, key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validity period in seconds since the Unix epoch, both ends inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Validity {
+    pub not_before: u64,
+    pub not_after: u64,
+}
+
+impl Validity {
+    pub fn new(not_before: u64, not_after: u64) -> Result<Self> {
+        if not_after <= not_before {
+            return Err(anyhow!("Validity period ends before it starts"));
+        }
+        Ok(Self { not_before, not_after })
+    }
+
+    /// Period starting now and lasting `days` days
+    pub fn for_days(days: u64) -> Result<Self> {
+        let start = now();
+        Self::new(start, start.saturating_add(days.saturating_mul(SECONDS_PER_DAY)))
+    }
+
+    pub fn contains(&self, at: u64) -> bool {
+        self.not_before <= at && at <= self.not_after
+    }
+
+    // Fails with the typed error a relying party can act on
+    fn check(&self, at: u64, subject: &Name) -> Result<()> {
+        if at < self.not_before {
+            return Err(CertificateError::NotYetValid(subject.to_string()).into());
+        }
+        if at > self.not_after {
+            return Err(CertificateError::Expired(subject.to_string()).into());
+        }
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        der::sequence(&[encode_time(self.not_before), encode_time(self.not_after)])
+    }
+
+    fn read(reader: &mut DerReader<'_>) -> Result<Self> {
+        let mut validity = reader.read_sequence()?;
+        let not_before = read_time(&mut validity)?;
+        let not_after = read_time(&mut validity)?;
+        validity.finish()?;
+        Ok(Self { not_before, not_after })
+    }
+}
+
+/// keyUsage bits (RFC 5280, section 4.2.1.3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeyUsage(u16);
+
+impl KeyUsage {
+    pub const DIGITAL_SIGNATURE: KeyUsage = KeyUsage(1 << 0);
+    pub const KEY_ENCIPHERMENT: KeyUsage = KeyUsage(1 << 2);
+    pub const KEY_CERT_SIGN: KeyUsage = KeyUsage(1 << 5);
+    pub const CRL_SIGN: KeyUsage = KeyUsage(1 << 6);
+
+    pub fn contains(&self, other: KeyUsage) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // Named bit list: bit 0 is the most significant bit of the first octet,
+    // trailing zero bits are dropped
+    fn encode(&self) -> Vec<u8> {
+        if self.0 == 0 {
+            return der::tlv(der::TAG_BIT_STRING, &[0]);
+        }
+        let highest = 15 - self.0.leading_zeros() as usize;
+        let octets = highest / 8 + 1;
+        let mut content = vec![(7 - highest % 8) as u8];
+        content.extend((0..octets).map(|octet| {
+            (0..8).fold(0u8, |byte, bit| {
+                let set = self.0 & (1 << (octet * 8 + bit)) != 0;
+                byte | if set { 0x80 >> bit } else { 0 }
+            })
+        }));
+        der::tlv(der::TAG_BIT_STRING, &content)
+    }
+
+    // From the content octets of the BIT STRING
+    fn decode(content: &[u8]) -> Result<Self> {
+        let (unused, octets) = content.split_first().ok_or_else(|| invalid_extension("empty keyUsage"))?;
+        if *unused > 7 || octets.len() > 2 {
+            return Err(invalid_extension("malformed keyUsage"));
+        }
+        let mut bits = 0u16;
+        for (octet, byte) in octets.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    bits |= 1 << (octet * 8 + bit);
+                }
+            }
+        }
+        Ok(Self(bits))
+    }
+}
+
+impl BitOr for KeyUsage {
+    type Output = KeyUsage;
+
+    fn bitor(self, other: KeyUsage) -> KeyUsage {
+        KeyUsage(self.0 | other.0)
+    }
+}
+
+fn signature_algorithm_oid(parameter_set: MlDsaParameterSet) -> &'static str {
+    match parameter_set {
+        MlDsaParameterSet::MlDsa44 => OID_ML_DSA_44,
+        MlDsaParameterSet::MlDsa65 => OID_ML_DSA_65,
+        MlDsaParameterSet::MlDsa87 => OID_ML_DSA_87,
+    }
+}
+
+fn signature_parameter_set(oid: &str) -> Result<MlDsaParameterSet> {
+    match oid {
+        OID_ML_DSA_44 => Ok(MlDsaParameterSet::MlDsa44),
+        OID_ML_DSA_65 => Ok(MlDsaParameterSet::MlDsa65),
+        OID_ML_DSA_87 => Ok(MlDsaParameterSet::MlDsa87),
+        other => Err(CertificateError::UnsupportedAlgorithm(other.to_string()).into()),
+    }
+}
+
+// AlgorithmIdentifier with absent parameters
+fn algorithm_identifier(oid: &str) -> Result<Vec<u8>> {
+    Ok(der::sequence(&[der::oid(oid)?]))
+}
+
+fn read_algorithm_identifier(reader: &mut DerReader<'_>) -> Result<String> {
+    let mut algorithm = reader.read_sequence()?;
+    let oid = algorithm.read_oid()?;
+    if !algorithm.is_empty() {
+        return Err(CertificateError::UnsupportedAlgorithm(format!("{} with parameters", oid)).into());
+    }
+    Ok(oid)
+}
+
+fn invalid_extension(message: &str) -> anyhow::Error {
+    CertificateError::InvalidExtension(message.to_string()).into()
+}
+
+// UTCTime through 2049, GeneralizedTime from 2050 (RFC 5280, section 4.1.2.5)
+fn encode_time(seconds: u64) -> Vec<u8> {
+    let (year, month, day) = civil_from_days((seconds / SECONDS_PER_DAY) as i64);
+    let time_of_day = seconds % SECONDS_PER_DAY;
+    let clock = format!(
+        "{:02}{:02}{:02}{:02}{:02}Z",
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60
+    );
+    if year < 2050 {
+        der::tlv(TAG_UTC_TIME, format!("{:02}{}", year % 100, clock).as_bytes())
+    } else {
+        der::generalized_time(&format!("{:04}{}", year, clock))
+    }
+}
+
+fn read_time(reader: &mut DerReader<'_>) -> Result<u64> {
+    let (tag, content, _) = reader.read_any()?;
+    let invalid = || EncodingError::InvalidDer("Invalid certificate time".to_string());
+    let text = std::str::from_utf8(content).map_err(|_| invalid())?;
+    let (year, rest) = match (tag, text.len()) {
+        (TAG_UTC_TIME, 13) => {
+            let year: i64 = text[..2].parse().map_err(|_| invalid())?;
+            (if year < 50 { 2000 + year } else { 1900 + year }, &text[2..])
+        }
+        (TAG_GENERALIZED_TIME, 15) => (text[..4].parse().map_err(|_| invalid())?, &text[4..]),
+        _ => return Err(invalid().into()),
+    };
+    if !rest.ends_with('Z') || !rest[..10].bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid().into());
+    }
+    let field = |range: std::ops::Range<usize>| rest[range].parse::<u64>().unwrap_or(u64::MAX);
+    let (month, day, hour, minute, second) = (field(0..2), field(2..4), field(4..6), field(6..8), field(8..10));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+        return Err(invalid().into());
+    }
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return Err(invalid().into());
+    }
+    Ok(days as u64 * SECONDS_PER_DAY + hour * 3600 + minute * 60 + second)
+}
+
+// Days since 1970-01-01 in the proleptic Gregorian calendar (H. Hinnant's algorithm)
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u64, u64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u64;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u64;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_encoding() {
+        // 2024-02-29 12:34:56 UTC
+        let encoded = encode_time(1_709_210_096);
+        assert_eq!(encoded[0], TAG_UTC_TIME);
+        assert_eq!(&encoded[2..], b"240229123456Z");
+        // 2050-01-01 00:00:00 UTC switches to GeneralizedTime
+        let encoded = encode_time(2_524_608_000);
+        assert_eq!(&encoded[2..], b"20500101000000Z");
+
+        for seconds in [0, 1_709_210_096, 2_524_607_999, 2_524_608_000, 4_102_444_800] {
+            assert_eq!(read_time(&mut DerReader::new(&encode_time(seconds))).unwrap(), seconds);
+        }
+        let bad = der::tlv(TAG_UTC_TIME, b"241329123456Z");
+        assert!(read_time(&mut DerReader::new(&bad)).is_err());
+    }
+
+    #[test]
+    fn test_key_usage_bits() {
+        assert_eq!(KeyUsage::KEY_ENCIPHERMENT.encode(), vec![0x03, 0x02, 0x05, 0x20]);
+        let ca = KeyUsage::DIGITAL_SIGNATURE | KeyUsage::KEY_CERT_SIGN | KeyUsage::CRL_SIGN;
+        assert_eq!(ca.encode(), vec![0x03, 0x02, 0x01, 0x86]);
+        assert_eq!(KeyUsage::decode(&ca.encode()[2..]).unwrap(), ca);
+        assert!(ca.contains(KeyUsage::KEY_CERT_SIGN));
+        assert!(!ca.contains(KeyUsage::KEY_ENCIPHERMENT));
+    }
+
+    #[test]
+    fn test_name_round_trip() {
+        let name = Name::new("settlement-inbound").with_organization("Example Bank").with_country("PL");
+        let encoded = name.encode().unwrap();
+        assert_eq!(Name::read(&mut DerReader::new(&encoded)).unwrap(), name);
+        assert_eq!(name.to_string(), "CN=settlement-inbound, O=Example Bank, C=PL");
+        assert_eq!(name.common_name(), Some("settlement-inbound"));
+    }
+}
@@ -0,0 +1,639 @@
+//! Signed certificate status, OCSP style.
+//!
+//! A CA's [`StatusResponder`] answers whether a serial number is good,
+//! revoked or unknown with a [`SignedStatus`]: a JSON response signed with
+//! the CA's ML-DSA key and valid until its `next_update`. It answers from a
+//! [`RevocationRegistry`] shared with the KMS, which records every
+//! certificate it issues there and revokes a key's certificate as soon as
+//! the key is marked compromised or destroyed. A registry opened on a file
+//! writes every change through before acknowledging it, so revocations
+//! survive a restart. Signed responses and the CRL
+//! are kept and served again until their `next_update`, and signed afresh
+//! only once they expire or the registry's answer changes, so the public
+//! routes cost no signature per request.
+//!
+//! Relying parties use a [`RevocationChecker`], which fetches statuses from
+//! a [`StatusSource`] per issuer, checks them against the issuer's
+//! certificate and caches each answer until it expires. Checks fail closed: an unknown
+//! serial or an unreachable responder is an error, not a pass.
+
+use super::crl::{CertificateRevocationList, RevocationReason, RevokedCertificate};
+use super::{now, Certificate, CertificateAuthority, Name, Validity};
+use crate::crypto::signature::{MlDsaSignature, SigningMode};
+use crate::error::CertificateError;
+use crate::utils::encoding::{from_hex, to_hex};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// ML-DSA context string of status responses
+pub const STATUS_CONTEXT: &[u8] = b"pqc-kyber/x509/status/v1";
+/// Seconds a status response or CRL stays current
+pub const DEFAULT_UPDATE_INTERVAL: u64 = 60 * 60;
+// Tolerated clock difference between responder and relying party
+const CLOCK_SKEW: u64 = 5 * 60;
+// Signed status responses a responder keeps; past this the cache starts over
+const MAX_CACHED_RESPONSES: usize = 4096;
+
+/// Status of one certificate as seen by its issuer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CertificateStatus {
+    Good,
+    Revoked { revoked_at: u64, reason: RevocationReason },
+    /// Never issued by this CA
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusResponse {
+    /// Serial number in lowercase hex
+    pub serial: String,
+    pub status: CertificateStatus,
+    pub produced_at: u64,
+    pub next_update: u64,
+    /// Subject key identifier of the responding CA in lowercase hex
+    pub responder_key_id: String,
+}
+
+/// Status response with the responding CA's signature over its JSON encoding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedStatus {
+    pub response: StatusResponse,
+    pub signature: MlDsaSignature,
+}
+
+impl SignedStatus {
+    /// Checks that `issuer` signed this response about `certificate` and that
+    /// it is current at `at`
+    pub fn verify(&self, certificate: &Certificate, issuer: &Certificate, at: u64) -> Result<()> {
+        let response = &self.response;
+        if response.serial != certificate.serial_hex() {
+            return Err(anyhow!("Status response is for serial {}, not {}", response.serial, certificate.serial_hex()));
+        }
+        if issuer.subject_key_id().is_some_and(|id| to_hex(id) != response.responder_key_id) {
+            return Err(CertificateError::UntrustedIssuer(format!("status responder {}", response.responder_key_id)).into());
+        }
+        let key = issuer
+            .verifying_key()
+            .ok_or_else(|| CertificateError::InvalidChain(format!("{} has no signing key", issuer.subject())))?;
+        key.verify(&serde_json::to_vec(response)?, STATUS_CONTEXT, &self.signature)?;
+
+        if response.produced_at > at.saturating_add(CLOCK_SKEW) {
+            return Err(CertificateError::NotYetValid(format!("status of {}", certificate.subject())).into());
+        }
+        if response.next_update < at {
+            return Err(CertificateError::Expired(format!("status of {}", certificate.subject())).into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct RegistryState {
+    issued: HashSet<Vec<u8>>,
+    revoked: BTreeMap<Vec<u8>, RevokedCertificate>,
+    crl_number: u64,
+}
+
+// On-disk form of a registry; serials are lowercase hex
+#[derive(Serialize, Deserialize)]
+struct RegistryFile {
+    issuer: String,
+    issuer_key_id: Option<String>,
+    issued: Vec<String>,
+    revoked: Vec<RevokedEntry>,
+    crl_number: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RevokedEntry {
+    serial: String,
+    revoked_at: u64,
+    reason: RevocationReason,
+}
+
+/// Certificates one CA has issued and revoked
+#[derive(Debug)]
+pub struct RevocationRegistry {
+    issuer: Name,
+    issuer_key_id: Option<Vec<u8>>,
+    path: Option<PathBuf>,
+    state: Mutex<RegistryState>,
+}
+
+impl RevocationRegistry {
+    /// Empty in-memory registry for the CA with certificate `ca`
+    pub fn new(ca: &Certificate) -> Self {
+        Self {
+            issuer: ca.subject().clone(),
+            issuer_key_id: ca.subject_key_id().map(<[u8]>::to_vec),
+            path: None,
+            state: Mutex::new(RegistryState::default()),
+        }
+    }
+
+    /// Registry for `ca` kept in the file at `path`, created on first
+    /// change. Fails if the file belongs to another CA.
+    pub fn open(ca: &Certificate, path: impl Into<PathBuf>) -> Result<Self> {
+        let mut registry = Self::new(ca);
+        let path = path.into();
+        if path.exists() {
+            let bytes = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            *registry.lock() = registry.decode(&bytes)?;
+        }
+        registry.path = Some(path);
+        Ok(registry)
+    }
+
+    pub fn issuer(&self) -> &Name {
+        &self.issuer
+    }
+
+    /// Whether `certificate` was issued by this registry's CA
+    pub fn covers(&self, certificate: &Certificate) -> bool {
+        certificate.issuer() == &self.issuer
+            && match (certificate.authority_key_id(), &self.issuer_key_id) {
+                (Some(authority), Some(subject)) => authority == subject.as_slice(),
+                _ => true,
+            }
+    }
+
+    /// Records a certificate; returns false, recording nothing, if another CA issued it
+    pub fn record_issued(&self, certificate: &Certificate) -> Result<bool> {
+        if !self.covers(certificate) {
+            return Ok(false);
+        }
+        self.update(|state| {
+            state.issued.insert(certificate.serial().to_vec());
+        })?;
+        Ok(true)
+    }
+
+    /// Revokes an issued certificate. Revocation is final: revoking again
+    /// returns the original entry. `None` if the serial was never issued.
+    pub fn revoke(&self, serial: &[u8], reason: RevocationReason, at: u64) -> Result<Option<RevokedCertificate>> {
+        self.update(|state| {
+            if !state.issued.contains(serial) {
+                return None;
+            }
+            let entry = state.revoked.entry(serial.to_vec()).or_insert_with(|| RevokedCertificate {
+                serial: serial.to_vec(),
+                revoked_at: at,
+                reason,
+            });
+            Some(entry.clone())
+        })
+    }
+
+    pub fn status(&self, serial: &[u8]) -> CertificateStatus {
+        let state = self.lock();
+        match state.revoked.get(serial) {
+            Some(entry) => CertificateStatus::Revoked { revoked_at: entry.revoked_at, reason: entry.reason },
+            None if state.issued.contains(serial) => CertificateStatus::Good,
+            None => CertificateStatus::Unknown,
+        }
+    }
+
+    /// Revoked certificates ordered by serial number
+    pub fn revoked(&self) -> Vec<RevokedCertificate> {
+        self.lock().revoked.values().cloned().collect()
+    }
+
+    // Persisted like any other change, so CRL numbers keep increasing across restarts
+    fn next_crl_number(&self) -> Result<u64> {
+        self.update(|state| {
+            state.crl_number += 1;
+            state.crl_number
+        })
+    }
+
+    // Applies `change` to a copy of the state and writes it through before
+    // making it current, so a failed write changes nothing
+    fn update<R>(&self, change: impl FnOnce(&mut RegistryState) -> R) -> Result<R> {
+        let mut state = self.lock();
+        let mut next = state.clone();
+        let result = change(&mut next);
+        if next != *state {
+            if let Some(path) = &self.path {
+                self.write(path, &next)?;
+            }
+            *state = next;
+        }
+        Ok(result)
+    }
+
+    // write temp → fsync → rename over target
+    fn write(&self, path: &Path, state: &RegistryState) -> Result<()> {
+        let mut issued: Vec<String> = state.issued.iter().map(|serial| to_hex(serial)).collect();
+        issued.sort();
+        let contents = RegistryFile {
+            issuer: self.issuer.to_string(),
+            issuer_key_id: self.issuer_key_id.as_deref().map(to_hex),
+            issued,
+            revoked: state
+                .revoked
+                .values()
+                .map(|entry| RevokedEntry { serial: to_hex(&entry.serial), revoked_at: entry.revoked_at, reason: entry.reason })
+                .collect(),
+            crl_number: state.crl_number,
+        };
+
+        let temp = path.with_extension("tmp");
+        let mut file = File::create(&temp).with_context(|| format!("Failed to create {}", temp.display()))?;
+        file.write_all(&serde_json::to_vec_pretty(&contents)?)
+            .and_then(|_| file.sync_all())
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        fs::rename(&temp, path).with_context(|| format!("Failed to replace {}", path.display()))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<RegistryState> {
+        let contents: RegistryFile = serde_json::from_slice(bytes).context("Malformed revocation registry")?;
+        if contents.issuer != self.issuer.to_string()
+            || contents.issuer_key_id != self.issuer_key_id.as_deref().map(to_hex)
+        {
+            return Err(anyhow!("Revocation registry of {} cannot be opened for {}", contents.issuer, self.issuer));
+        }
+
+        let mut state = RegistryState { crl_number: contents.crl_number, ..RegistryState::default() };
+        for serial in &contents.issued {
+            state.issued.insert(from_hex(serial)?);
+        }
+        for entry in contents.revoked {
+            let serial = from_hex(&entry.serial)?;
+            if !state.issued.contains(&serial) {
+                return Err(anyhow!("Revocation registry revokes serial {} it never issued", entry.serial));
+            }
+            state
+                .revoked
+                .insert(serial.clone(), RevokedCertificate { serial, revoked_at: entry.revoked_at, reason: entry.reason });
+        }
+        Ok(state)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, RegistryState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[derive(Debug, Default)]
+struct SignedCache {
+    statuses: HashMap<Vec<u8>, SignedStatus>,
+    crl: Option<CertificateRevocationList>,
+}
+
+/// Signs status responses and CRLs for one CA
+pub struct StatusResponder {
+    ca: CertificateAuthority,
+    registry: Arc<RevocationRegistry>,
+    update_interval: u64,
+    cache: Mutex<SignedCache>,
+}
+
+impl StatusResponder {
+    pub fn new(ca: CertificateAuthority, registry: Arc<RevocationRegistry>) -> Result<Self> {
+        if ca.certificate().subject() != registry.issuer() {
+            return Err(anyhow!("Registry of {} cannot be served by {}", registry.issuer(), ca.certificate().subject()));
+        }
+        Ok(Self { ca, registry, update_interval: DEFAULT_UPDATE_INTERVAL, cache: Mutex::default() })
+    }
+
+    /// How long responses and CRLs stay current, in seconds
+    pub fn with_update_interval(mut self, seconds: u64) -> Self {
+        self.update_interval = seconds;
+        self
+    }
+
+    pub fn ca_certificate(&self) -> &Certificate {
+        self.ca.certificate()
+    }
+
+    pub fn registry(&self) -> &Arc<RevocationRegistry> {
+        &self.registry
+    }
+
+    /// Signed status of `serial`; the last response is served again while it
+    /// is current and still gives the registry's answer
+    pub fn respond(&self, serial: &[u8]) -> Result<SignedStatus> {
+        let status = self.registry.status(serial);
+        let produced_at = now();
+        let mut cache = self.lock();
+        if let Some(signed) = cache
+            .statuses
+            .get(serial)
+            .filter(|signed| signed.response.status == status && signed.response.next_update > produced_at)
+        {
+            return Ok(signed.clone());
+        }
+
+        let response = StatusResponse {
+            serial: to_hex(serial),
+            status,
+            produced_at,
+            next_update: produced_at + self.update_interval,
+            responder_key_id: self.ca.certificate().subject_key_id().map(to_hex).unwrap_or_default(),
+        };
+        let signature = self.ca.signing_key().sign(&serde_json::to_vec(&response)?, STATUS_CONTEXT, SigningMode::Hedged)?;
+        let signed = SignedStatus { response, signature };
+
+        if cache.statuses.len() >= MAX_CACHED_RESPONSES {
+            cache.statuses.retain(|_, cached| cached.response.next_update > produced_at);
+            if cache.statuses.len() >= MAX_CACHED_RESPONSES {
+                cache.statuses.clear();
+            }
+        }
+        cache.statuses.insert(serial.to_vec(), signed.clone());
+        Ok(signed)
+    }
+
+    /// CRL of everything revoked so far. The last CRL is served again until
+    /// its next update; a new one, with the next number, is signed once it
+    /// expires or another certificate is revoked.
+    pub fn crl(&self) -> Result<CertificateRevocationList> {
+        let revoked = self.registry.revoked();
+        let this_update = now();
+        let mut cache = self.lock();
+        if let Some(crl) = cache
+            .crl
+            .as_ref()
+            .filter(|crl| crl.next_update() > this_update && crl.entries() == revoked.as_slice())
+        {
+            return Ok(crl.clone());
+        }
+
+        let validity = Validity::new(this_update, this_update + self.update_interval)?;
+        let crl = self.ca.issue_crl(self.registry.next_crl_number()?, revoked, validity)?;
+        cache.crl = Some(crl.clone());
+        Ok(crl)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SignedCache> {
+        self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for StatusResponder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StatusResponder({})", self.ca.certificate().subject())
+    }
+}
+
+/// Where a relying party gets signed statuses from
+pub trait StatusSource {
+    fn fetch(&self, serial: &[u8]) -> Result<SignedStatus>;
+}
+
+/// A responder in the same process answers directly
+impl StatusSource for StatusResponder {
+    fn fetch(&self, serial: &[u8]) -> Result<SignedStatus> {
+        self.respond(serial)
+    }
+}
+
+impl<S: StatusSource + ?Sized> StatusSource for Arc<S> {
+    fn fetch(&self, serial: &[u8]) -> Result<SignedStatus> {
+        (**self).fetch(serial)
+    }
+}
+
+// Cached responses are keyed by issuer and serial; serials are only unique per issuer
+type StatusCache = HashMap<(String, Vec<u8>), SignedStatus>;
+
+/// Client-side status checks with a cache of verified responses
+pub struct RevocationChecker {
+    source: Box<dyn StatusSource + Send + Sync>,
+    issuer_sources: Vec<(Name, Box<dyn StatusSource + Send + Sync>)>,
+    cache: Mutex<StatusCache>,
+}
+
+impl RevocationChecker {
+    /// Checker asking `source` about certificates of any issuer
+    pub fn new(source: impl StatusSource + Send + Sync + 'static) -> Self {
+        Self { source: Box::new(source), issuer_sources: Vec::new(), cache: Mutex::new(HashMap::new()) }
+    }
+
+    /// Asks `source` instead about certificates issued by `issuer`, e.g. the
+    /// root's responder about the intermediate CAs below it
+    pub fn with_issuer_source(mut self, issuer: &Name, source: impl StatusSource + Send + Sync + 'static) -> Self {
+        self.issuer_sources.retain(|(name, _)| name != issuer);
+        self.issuer_sources.push((issuer.clone(), Box::new(source)));
+        self
+    }
+
+    /// Fails unless `issuer` vouches, now, that `certificate` is good
+    pub fn check(&self, certificate: &Certificate, issuer: &Certificate) -> Result<()> {
+        self.check_at(certificate, issuer, now())
+    }
+
+    pub fn check_at(&self, certificate: &Certificate, issuer: &Certificate, at: u64) -> Result<()> {
+        let key = (certificate.issuer().to_string(), certificate.serial().to_vec());
+        let cached = self.lock().get(&key).filter(|signed| signed.response.next_update >= at).cloned();
+        let signed = match cached {
+            Some(signed) => signed,
+            None => {
+                let source = self
+                    .issuer_sources
+                    .iter()
+                    .find(|(name, _)| name == certificate.issuer())
+                    .map_or(&self.source, |(_, source)| source);
+                let signed = source
+                    .fetch(certificate.serial())
+                    .map_err(|e| CertificateError::StatusUnavailable(format!("{}: {}", certificate.subject(), e)))?;
+                signed.verify(certificate, issuer, at)?;
+                self.lock().insert(key, signed.clone());
+                signed
+            }
+        };
+
+        match signed.response.status {
+            CertificateStatus::Good => Ok(()),
+            CertificateStatus::Revoked { .. } => Err(CertificateError::Revoked(certificate.subject().to_string()).into()),
+            CertificateStatus::Unknown => Err(CertificateError::StatusUnknown(certificate.subject().to_string()).into()),
+        }
+    }
+
+    /// Number of cached responses, current or not
+    pub fn cached(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, StatusCache> {
+        self.cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for RevocationChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RevocationChecker({} cached)", self.cached())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signature::MlDsaParameterSet;
+    use crate::error::Error;
+    use pqcrypto_kyber::kyber1024;
+    use pqcrypto_traits::kem::PublicKey as _;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Counting {
+        responder: Arc<StatusResponder>,
+        fetches: AtomicUsize,
+    }
+
+    impl StatusSource for Counting {
+        fn fetch(&self, serial: &[u8]) -> Result<SignedStatus> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            self.responder.respond(serial)
+        }
+    }
+
+    fn new_responder() -> (Arc<StatusResponder>, Certificate) {
+        let ca = CertificateAuthority::new_root(Name::new("Root"), MlDsaParameterSet::MlDsa65, Validity::for_days(30).unwrap())
+            .unwrap();
+        let registry = Arc::new(RevocationRegistry::new(ca.certificate()));
+        let leaf = ca
+            .issue_kem_certificate(Name::new("inbound"), kyber1024::keypair().0.as_bytes(), Validity::for_days(1).unwrap())
+            .unwrap();
+        assert!(registry.record_issued(&leaf).unwrap());
+        (Arc::new(StatusResponder::new(ca, registry).unwrap()), leaf)
+    }
+
+    fn error_name(err: anyhow::Error) -> &'static str {
+        Error::from(err).code().name
+    }
+
+    #[test]
+    fn test_signed_status_responses() {
+        let (responder, leaf) = new_responder();
+        let root = responder.ca_certificate().clone();
+
+        let signed = responder.respond(leaf.serial()).unwrap();
+        assert_eq!(signed.response.status, CertificateStatus::Good);
+        signed.verify(&leaf, &root, now()).unwrap();
+
+        // Tampering with the status breaks the signature
+        let mut forged = responder.respond(leaf.serial()).unwrap();
+        forged.response.next_update += 3600;
+        assert!(forged.verify(&leaf, &root, now()).is_err());
+
+        responder.registry().revoke(leaf.serial(), RevocationReason::KeyCompromise, 1_700_000_000).unwrap().unwrap();
+        let signed = responder.respond(leaf.serial()).unwrap();
+        assert_eq!(
+            signed.response.status,
+            CertificateStatus::Revoked { revoked_at: 1_700_000_000, reason: RevocationReason::KeyCompromise }
+        );
+        // Revocation is final
+        let again = responder.registry().revoke(leaf.serial(), RevocationReason::Superseded, 1_800_000_000).unwrap().unwrap();
+        assert_eq!(again.reason, RevocationReason::KeyCompromise);
+
+        assert_eq!(responder.respond(&[0x40, 0x01]).unwrap().response.status, CertificateStatus::Unknown);
+        assert!(responder.registry().revoke(&[0x40, 0x01], RevocationReason::Unspecified, 0).unwrap().is_none());
+
+        let crl = responder.crl().unwrap();
+        crl.verify(&root, now()).unwrap();
+        assert!(crl.find(leaf.serial()).is_some());
+    }
+
+    #[test]
+    fn test_responses_signed_once_per_change() {
+        let (responder, leaf) = new_responder();
+
+        // Unchanged answers are served from the cache, signature and all
+        let signed = responder.respond(leaf.serial()).unwrap();
+        assert_eq!(responder.respond(leaf.serial()).unwrap(), signed);
+        let crl = responder.crl().unwrap();
+        assert_eq!(responder.crl().unwrap(), crl);
+
+        // A revocation invalidates both, and the new CRL gets the next number
+        responder.registry().revoke(leaf.serial(), RevocationReason::KeyCompromise, now()).unwrap().unwrap();
+        assert!(matches!(responder.respond(leaf.serial()).unwrap().response.status, CertificateStatus::Revoked { .. }));
+        let next = responder.crl().unwrap();
+        assert!(next.find(leaf.serial()).is_some());
+        assert_eq!(next.number(), crl.number() + 1);
+
+        // Expired responses are signed afresh
+        let responder = Arc::try_unwrap(new_responder().0).unwrap().with_update_interval(1);
+        let crl = responder.crl().unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        assert!(responder.crl().unwrap().number() > crl.number());
+    }
+
+    #[test]
+    fn test_registry_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("revocations.json");
+        let (responder, leaf) = new_responder();
+        let ca = responder.ca_certificate();
+
+        let registry = RevocationRegistry::open(ca, &path).unwrap();
+        assert_eq!(registry.status(leaf.serial()), CertificateStatus::Unknown);
+        assert!(registry.record_issued(&leaf).unwrap());
+        registry.revoke(leaf.serial(), RevocationReason::KeyCompromise, 1_700_000_000).unwrap().unwrap();
+        let number = registry.next_crl_number().unwrap();
+        drop(registry);
+
+        let reopened = RevocationRegistry::open(ca, &path).unwrap();
+        assert_eq!(
+            reopened.status(leaf.serial()),
+            CertificateStatus::Revoked { revoked_at: 1_700_000_000, reason: RevocationReason::KeyCompromise }
+        );
+        assert_eq!(reopened.next_crl_number().unwrap(), number + 1);
+
+        // Another CA's registry file is refused
+        let (other, _) = new_responder();
+        assert!(RevocationRegistry::open(other.ca_certificate(), &path).is_err());
+    }
+
+    #[test]
+    fn test_checker_caches_until_next_update() {
+        let (responder, leaf) = new_responder();
+        let root = responder.ca_certificate().clone();
+        let source = Arc::new(Counting { responder: responder.clone(), fetches: AtomicUsize::new(0) });
+        let checker = RevocationChecker::new(source.clone());
+
+        checker.check(&leaf, &root).unwrap();
+        checker.check(&leaf, &root).unwrap();
+        assert_eq!(source.fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(checker.cached(), 1);
+
+        // The cached answer stands until its next update, then is fetched again
+        responder.registry().revoke(leaf.serial(), RevocationReason::KeyCompromise, now()).unwrap().unwrap();
+        checker.check(&leaf, &root).unwrap();
+        assert_eq!(source.fetches.load(Ordering::SeqCst), 1);
+        let err = checker.check_at(&leaf, &root, now() + DEFAULT_UPDATE_INTERVAL + 1).unwrap_err();
+        assert_eq!(source.fetches.load(Ordering::SeqCst), 2);
+        // The new answer is produced now, so it is stale from the future's point of view
+        assert_eq!(error_name(err), "X509_EXPIRED");
+
+        let checker = RevocationChecker::new(source.clone());
+        assert_eq!(error_name(checker.check(&leaf, &root).unwrap_err()), "X509_REVOKED");
+    }
+
+    #[test]
+    fn test_checker_fails_closed() {
+        struct Offline;
+        impl StatusSource for Offline {
+            fn fetch(&self, _serial: &[u8]) -> Result<SignedStatus> {
+                Err(anyhow!("connection refused"))
+            }
+        }
+
+        let (responder, leaf) = new_responder();
+        let checker = RevocationChecker::new(Offline);
+        let err = checker.check(&leaf, responder.ca_certificate()).unwrap_err();
+        assert_eq!(error_name(err), "X509_STATUS_UNAVAILABLE");
+
+        // Answers signed by someone else are not accepted
+        let (impostor, _) = new_responder();
+        let checker = RevocationChecker::new(impostor);
+        assert!(checker.check(&leaf, responder.ca_certificate()).is_err());
+        assert_eq!(checker.cached(), 0);
+    }
+}
@@ -0,0 +1,546 @@
+//! Typed errors with stable codes.
+//!
+//! Each subsystem has its own `thiserror` enum; [`Error`] wraps them all. Every
+//! variant maps to an [`ErrorCode`] whose number and name never change once
+//! released, so clients can branch on them instead of on message text. Codes
+//! are grouped by subsystem:
+//!
+//! | range | subsystem  |
+//! |-------|------------|
+//! | 1xxx  | KEM        |
+//! | 2xxx  | encoding   |
+//! | 3xxx  | KMS        |
+//! | 4xxx  | TLS        |
+//! | 5xxx  | ETL        |
+//! | 6xxx  | validation |
+//! | 7xxx  | KMIP       |
+//! | 8xxx  | X.509      |
+//! | 9xxx  | internal   |
+//!
+//! Messages carry identifiers and lengths only, never key or secret bytes.
+
+use std::fmt;
+
+/// Stable machine-readable error identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ErrorCode {
+    pub number: u32,
+    pub name: &'static str,
+    pub category: ErrorCategory,
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{}:{}", self.number, self.name)
+    }
+}
+
+/// Coarse classification used to pick transport status codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    InvalidInput,
+    NotFound,
+    AlreadyExists,
+    Unauthenticated,
+    PermissionDenied,
+    FailedPrecondition,
+    ResourceExhausted,
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCategory {
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorCategory::InvalidInput => 400,
+            ErrorCategory::Unauthenticated => 401,
+            ErrorCategory::PermissionDenied => 403,
+            ErrorCategory::NotFound => 404,
+            ErrorCategory::AlreadyExists => 409,
+            ErrorCategory::FailedPrecondition => 412,
+            ErrorCategory::ResourceExhausted => 429,
+            ErrorCategory::Unavailable => 503,
+            ErrorCategory::Internal => 500,
+        }
+    }
+
+    /// Numeric gRPC status code (google.rpc.Code)
+    pub fn grpc_code(&self) -> i32 {
+        match self {
+            ErrorCategory::InvalidInput => 3,
+            ErrorCategory::NotFound => 5,
+            ErrorCategory::AlreadyExists => 6,
+            ErrorCategory::PermissionDenied => 7,
+            ErrorCategory::ResourceExhausted => 8,
+            ErrorCategory::FailedPrecondition => 9,
+            ErrorCategory::Internal => 13,
+            ErrorCategory::Unavailable => 14,
+            ErrorCategory::Unauthenticated => 16,
+        }
+    }
+}
+
+const fn code(number: u32, name: &'static str, category: ErrorCategory) -> ErrorCode {
+    ErrorCode { number, name, category }
+}
+
+use ErrorCategory::*;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KemError {
+    #[error("BadLength: {what} expected {expected}, got {actual}")]
+    BadLength { what: &'static str, expected: usize, actual: usize },
+    #[error("Invalid public key")]
+    InvalidPublicKey,
+    #[error("Invalid ciphertext")]
+    InvalidCiphertext,
+    #[error("Decapsulation failed")]
+    DecapsulationFailed,
+    #[error("Unsupported parameter set: {0}")]
+    UnsupportedParameterSet(String),
+}
+
+impl KemError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            KemError::BadLength { .. } => code(1001, "KEM_BAD_LENGTH", InvalidInput),
+            KemError::InvalidPublicKey => code(1002, "KEM_INVALID_PUBLIC_KEY", InvalidInput),
+            KemError::InvalidCiphertext => code(1003, "KEM_INVALID_CIPHERTEXT", InvalidInput),
+            KemError::DecapsulationFailed => code(1004, "KEM_DECAPSULATION_FAILED", InvalidInput),
+            KemError::UnsupportedParameterSet(_) => code(1005, "KEM_UNSUPPORTED_PARAMETER_SET", InvalidInput),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncodingError {
+    #[error("Invalid hex encoding")]
+    InvalidHex,
+    #[error("Invalid base64 encoding")]
+    InvalidBase64,
+    #[error("Invalid PEM document: expected label '{expected}'")]
+    PemLabelMismatch { expected: String },
+    #[error("Encoded key is not valid UTF-8")]
+    InvalidUtf8,
+    #[error("Invalid DER: {0}")]
+    InvalidDer(String),
+}
+
+impl EncodingError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            EncodingError::InvalidHex => code(2001, "ENCODING_INVALID_HEX", InvalidInput),
+            EncodingError::InvalidBase64 => code(2002, "ENCODING_INVALID_BASE64", InvalidInput),
+            EncodingError::PemLabelMismatch { .. } => code(2003, "ENCODING_PEM_LABEL_MISMATCH", InvalidInput),
+            EncodingError::InvalidUtf8 => code(2004, "ENCODING_INVALID_UTF8", InvalidInput),
+            EncodingError::InvalidDer(_) => code(2005, "ENCODING_INVALID_DER", InvalidInput),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KmsError {
+    #[error("No secret found for key_id {0}")]
+    KeyNotFound(String),
+    #[error("Secret with key_id {0} already exists")]
+    KeyAlreadyExists(String),
+    #[error("Certificate already exists for key_id {0}")]
+    CertificateExists(String),
+    #[error("Failed to seal secret for key_id {0}")]
+    SealFailed(String),
+    #[error("Failed to unseal secret for key_id {0}")]
+    UnsealFailed(String),
+    #[error("Key shares rejected: {0}")]
+    SharesRejected(String),
+    #[error("Failed to get timestamp: {0}")]
+    Clock(String),
+    #[error("Key store I/O failed: {0}")]
+    Storage(String),
+    #[error("Key store is locked by another process: {0}")]
+    StoreLocked(String),
+    #[error("Key store integrity check failed: {0}")]
+    IntegrityCheckFailed(String),
+    #[error("Illegal key state transition: {0}")]
+    InvalidStateTransition(String),
+    #[error("Operation not permitted: {0}")]
+    OperationNotPermitted(String),
+    #[error("Invalid key envelope: {0}")]
+    InvalidEnvelope(String),
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+    #[error("Approval required: {0}")]
+    ApprovalRequired(String),
+    #[error("Approval rejected: {0}")]
+    ApprovalRejected(String),
+    #[error("No pending request {0}")]
+    RequestNotFound(String),
+    #[error("Pending request {0} has expired")]
+    RequestExpired(String),
+    #[error("Backup downgrade refused: {0}")]
+    BackupDowngrade(String),
+    #[error("Usage limit exceeded: {0}")]
+    UsageLimitExceeded(String),
+    #[error("Master key provider unavailable: {0}")]
+    ProviderUnavailable(String),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Secret unavailable: {0}")]
+    SecretUnavailable(String),
+}
+
+impl KmsError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            KmsError::KeyNotFound(_) => code(3001, "KMS_KEY_NOT_FOUND", NotFound),
+            KmsError::KeyAlreadyExists(_) => code(3002, "KMS_KEY_ALREADY_EXISTS", AlreadyExists),
+            KmsError::CertificateExists(_) => code(3003, "KMS_CERTIFICATE_EXISTS", AlreadyExists),
+            KmsError::SealFailed(_) => code(3004, "KMS_SEAL_FAILED", Internal),
+            KmsError::UnsealFailed(_) => code(3005, "KMS_UNSEAL_FAILED", FailedPrecondition),
+            KmsError::SharesRejected(_) => code(3006, "KMS_SHARES_REJECTED", InvalidInput),
+            KmsError::Clock(_) => code(3007, "KMS_CLOCK", Internal),
+            KmsError::Storage(_) => code(3008, "KMS_STORAGE", Internal),
+            KmsError::StoreLocked(_) => code(3009, "KMS_STORE_LOCKED", Unavailable),
+            KmsError::IntegrityCheckFailed(_) => code(3010, "KMS_INTEGRITY_CHECK_FAILED", Internal),
+            KmsError::InvalidStateTransition(_) => code(3011, "KMS_INVALID_STATE_TRANSITION", FailedPrecondition),
+            KmsError::OperationNotPermitted(_) => code(3012, "KMS_OPERATION_NOT_PERMITTED", FailedPrecondition),
+            KmsError::InvalidEnvelope(_) => code(3013, "KMS_INVALID_ENVELOPE", InvalidInput),
+            KmsError::AccessDenied(_) => code(3014, "KMS_ACCESS_DENIED", PermissionDenied),
+            KmsError::ApprovalRequired(_) => code(3015, "KMS_APPROVAL_REQUIRED", FailedPrecondition),
+            KmsError::ApprovalRejected(_) => code(3016, "KMS_APPROVAL_REJECTED", PermissionDenied),
+            KmsError::RequestNotFound(_) => code(3017, "KMS_REQUEST_NOT_FOUND", NotFound),
+            KmsError::RequestExpired(_) => code(3018, "KMS_REQUEST_EXPIRED", FailedPrecondition),
+            KmsError::BackupDowngrade(_) => code(3019, "KMS_BACKUP_DOWNGRADE", FailedPrecondition),
+            KmsError::UsageLimitExceeded(_) => code(3020, "KMS_USAGE_LIMIT_EXCEEDED", ResourceExhausted),
+            KmsError::ProviderUnavailable(_) => code(3021, "KMS_PROVIDER_UNAVAILABLE", Unavailable),
+            KmsError::InvalidRequest(_) => code(3022, "KMS_INVALID_REQUEST", InvalidInput),
+            KmsError::SecretUnavailable(_) => code(3023, "KMS_SECRET_UNAVAILABLE", Internal),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("TLS handshake failed: {0}")]
+    HandshakeFailed(String),
+    #[error("Peer certificate rejected: {0}")]
+    CertificateRejected(String),
+    #[error("Unsupported cipher suite: {0}")]
+    UnsupportedCipherSuite(String),
+}
+
+impl TlsError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            TlsError::HandshakeFailed(_) => code(4001, "TLS_HANDSHAKE_FAILED", Unavailable),
+            TlsError::CertificateRejected(_) => code(4002, "TLS_CERTIFICATE_REJECTED", Unauthenticated),
+            TlsError::UnsupportedCipherSuite(_) => code(4003, "TLS_UNSUPPORTED_CIPHER_SUITE", InvalidInput),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EtlError {
+    #[error("Batch is full")]
+    BatchFull,
+    #[error("Transaction {0} is not signed")]
+    TransactionUnsigned(String),
+    #[error("Batch is not signed")]
+    BatchUnsigned,
+    #[error("Invalid transaction {0}")]
+    InvalidTransaction(String),
+}
+
+impl EtlError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            EtlError::BatchFull => code(5001, "ETL_BATCH_FULL", ResourceExhausted),
+            EtlError::TransactionUnsigned(_) => code(5002, "ETL_TRANSACTION_UNSIGNED", Unauthenticated),
+            EtlError::BatchUnsigned => code(5003, "ETL_BATCH_UNSIGNED", Unauthenticated),
+            EtlError::InvalidTransaction(_) => code(5004, "ETL_INVALID_TRANSACTION", InvalidInput),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ValidationError {
+    #[error("Insufficient entropy in key material")]
+    InsufficientEntropy,
+    #[error("Invalid {0} signature")]
+    SignatureInvalid(String),
+    #[error("Unknown signer: {0}")]
+    UnknownSigner(String),
+    #[error("WeakParameters: {0}")]
+    WeakParameters(String),
+    #[error("WeakPassphrase: at least {minimum} bytes required")]
+    WeakPassphrase { minimum: usize },
+}
+
+impl ValidationError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ValidationError::InsufficientEntropy => code(6001, "VALIDATION_INSUFFICIENT_ENTROPY", InvalidInput),
+            ValidationError::SignatureInvalid(_) => code(6002, "VALIDATION_SIGNATURE_INVALID", Unauthenticated),
+            ValidationError::UnknownSigner(_) => code(6003, "VALIDATION_UNKNOWN_SIGNER", PermissionDenied),
+            ValidationError::WeakParameters(_) => code(6004, "VALIDATION_WEAK_PARAMETERS", InvalidInput),
+            ValidationError::WeakPassphrase { .. } => code(6005, "VALIDATION_WEAK_PASSPHRASE", InvalidInput),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KmipError {
+    #[error("Malformed KMIP message: {0}")]
+    Malformed(String),
+    #[error("Unsupported KMIP operation: {0}")]
+    UnsupportedOperation(String),
+    #[error("KMIP connection failed: {0}")]
+    Connection(String),
+    #[error("KMIP operation failed: {0}")]
+    OperationFailed(String),
+    #[error("Invalid KMIP field: {0}")]
+    InvalidField(String),
+}
+
+impl KmipError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            KmipError::Malformed(_) => code(7001, "KMIP_MALFORMED", InvalidInput),
+            KmipError::UnsupportedOperation(_) => code(7002, "KMIP_UNSUPPORTED_OPERATION", InvalidInput),
+            KmipError::Connection(_) => code(7003, "KMIP_CONNECTION", Unavailable),
+            KmipError::OperationFailed(_) => code(7004, "KMIP_OPERATION_FAILED", FailedPrecondition),
+            KmipError::InvalidField(_) => code(7005, "KMIP_INVALID_FIELD", InvalidInput),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CertificateError {
+    #[error("Unsupported certificate algorithm: {0}")]
+    UnsupportedAlgorithm(String),
+    #[error("Certificate expired: {0}")]
+    Expired(String),
+    #[error("Certificate not yet valid: {0}")]
+    NotYetValid(String),
+    #[error("Untrusted issuer: {0}")]
+    UntrustedIssuer(String),
+    #[error("Invalid certificate chain: {0}")]
+    InvalidChain(String),
+    #[error("Invalid certificate extension: {0}")]
+    InvalidExtension(String),
+    #[error("Certificate does not match key {0}")]
+    KeyMismatch(String),
+    #[error("Certificate revoked: {0}")]
+    Revoked(String),
+    #[error("Certificate status unknown: {0}")]
+    StatusUnknown(String),
+    #[error("Certificate status unavailable: {0}")]
+    StatusUnavailable(String),
+    #[error("No certificate for {0}")]
+    NotFound(String),
+}
+
+impl CertificateError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CertificateError::UnsupportedAlgorithm(_) => code(8001, "X509_UNSUPPORTED_ALGORITHM", InvalidInput),
+            CertificateError::Expired(_) => code(8002, "X509_EXPIRED", FailedPrecondition),
+            CertificateError::NotYetValid(_) => code(8003, "X509_NOT_YET_VALID", FailedPrecondition),
+            CertificateError::UntrustedIssuer(_) => code(8004, "X509_UNTRUSTED_ISSUER", PermissionDenied),
+            CertificateError::InvalidChain(_) => code(8005, "X509_INVALID_CHAIN", PermissionDenied),
+            CertificateError::InvalidExtension(_) => code(8006, "X509_INVALID_EXTENSION", InvalidInput),
+            CertificateError::KeyMismatch(_) => code(8007, "X509_KEY_MISMATCH", InvalidInput),
+            CertificateError::Revoked(_) => code(8008, "X509_REVOKED", PermissionDenied),
+            CertificateError::StatusUnknown(_) => code(8009, "X509_STATUS_UNKNOWN", PermissionDenied),
+            CertificateError::StatusUnavailable(_) => code(8010, "X509_STATUS_UNAVAILABLE", Unavailable),
+            CertificateError::NotFound(_) => code(8011, "X509_NOT_FOUND", NotFound),
+        }
+    }
+}
+
+const INTERNAL: ErrorCode = code(9000, "INTERNAL", Internal);
+
+/// Top-level error covering every subsystem
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Kem(#[from] KemError),
+    #[error(transparent)]
+    Encoding(#[from] EncodingError),
+    #[error(transparent)]
+    Kms(#[from] KmsError),
+    #[error(transparent)]
+    Tls(#[from] TlsError),
+    #[error(transparent)]
+    Etl(#[from] EtlError),
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    #[error(transparent)]
+    Kmip(#[from] KmipError),
+    #[error(transparent)]
+    Certificate(#[from] CertificateError),
+    /// Untyped failure from code that still reports through `anyhow`
+    #[error("Internal error")]
+    Internal(#[source] anyhow::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::Kem(e) => e.code(),
+            Error::Encoding(e) => e.code(),
+            Error::Kms(e) => e.code(),
+            Error::Tls(e) => e.code(),
+            Error::Etl(e) => e.code(),
+            Error::Validation(e) => e.code(),
+            Error::Kmip(e) => e.code(),
+            Error::Certificate(e) => e.code(),
+            Error::Internal(_) => INTERNAL,
+        }
+    }
+
+    pub fn http_status(&self) -> u16 {
+        self.code().category.http_status()
+    }
+
+    pub fn grpc_code(&self) -> i32 {
+        self.code().category.grpc_code()
+    }
+
+    /// Recovers the typed error from an `anyhow` error, if it wraps one
+    pub fn from_anyhow(err: anyhow::Error) -> Self {
+        macro_rules! recover {
+            ($err:ident, $($ty:ty => $variant:ident),*) => {
+                $(
+                    let $err = match $err.downcast::<$ty>() {
+                        Ok(inner) => return Error::$variant(inner),
+                        Err(other) => other,
+                    };
+                )*
+            };
+        }
+        recover!(
+            err,
+            KemError => Kem,
+            EncodingError => Encoding,
+            KmsError => Kms,
+            TlsError => Tls,
+            EtlError => Etl,
+            ValidationError => Validation,
+            KmipError => Kmip,
+            CertificateError => Certificate
+        );
+        Error::Internal(err)
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        Error::from_anyhow(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn all_codes() -> Vec<ErrorCode> {
+        vec![
+            KemError::BadLength { what: "secret", expected: 32, actual: 16 }.code(),
+            KemError::InvalidPublicKey.code(),
+            KemError::InvalidCiphertext.code(),
+            KemError::DecapsulationFailed.code(),
+            KemError::UnsupportedParameterSet(String::new()).code(),
+            EncodingError::InvalidHex.code(),
+            EncodingError::InvalidBase64.code(),
+            EncodingError::PemLabelMismatch { expected: String::new() }.code(),
+            EncodingError::InvalidUtf8.code(),
+            EncodingError::InvalidDer(String::new()).code(),
+            KmsError::KeyNotFound(String::new()).code(),
+            KmsError::KeyAlreadyExists(String::new()).code(),
+            KmsError::CertificateExists(String::new()).code(),
+            KmsError::SealFailed(String::new()).code(),
+            KmsError::UnsealFailed(String::new()).code(),
+            KmsError::SharesRejected(String::new()).code(),
+            KmsError::Clock(String::new()).code(),
+            KmsError::Storage(String::new()).code(),
+            KmsError::StoreLocked(String::new()).code(),
+            KmsError::IntegrityCheckFailed(String::new()).code(),
+            KmsError::InvalidStateTransition(String::new()).code(),
+            KmsError::OperationNotPermitted(String::new()).code(),
+            KmsError::InvalidEnvelope(String::new()).code(),
+            KmsError::AccessDenied(String::new()).code(),
+            KmsError::ApprovalRequired(String::new()).code(),
+            KmsError::ApprovalRejected(String::new()).code(),
+            KmsError::RequestNotFound(String::new()).code(),
+            KmsError::RequestExpired(String::new()).code(),
+            KmsError::BackupDowngrade(String::new()).code(),
+            KmsError::UsageLimitExceeded(String::new()).code(),
+            KmsError::ProviderUnavailable(String::new()).code(),
+            KmsError::InvalidRequest(String::new()).code(),
+            KmsError::SecretUnavailable(String::new()).code(),
+            TlsError::HandshakeFailed(String::new()).code(),
+            TlsError::CertificateRejected(String::new()).code(),
+            TlsError::UnsupportedCipherSuite(String::new()).code(),
+            EtlError::BatchFull.code(),
+            EtlError::TransactionUnsigned(String::new()).code(),
+            EtlError::BatchUnsigned.code(),
+            EtlError::InvalidTransaction(String::new()).code(),
+            ValidationError::InsufficientEntropy.code(),
+            ValidationError::SignatureInvalid(String::new()).code(),
+            ValidationError::UnknownSigner(String::new()).code(),
+            ValidationError::WeakParameters(String::new()).code(),
+            ValidationError::WeakPassphrase { minimum: 12 }.code(),
+            KmipError::Malformed(String::new()).code(),
+            KmipError::UnsupportedOperation(String::new()).code(),
+            KmipError::Connection(String::new()).code(),
+            KmipError::OperationFailed(String::new()).code(),
+            KmipError::InvalidField(String::new()).code(),
+            CertificateError::UnsupportedAlgorithm(String::new()).code(),
+            CertificateError::Expired(String::new()).code(),
+            CertificateError::NotYetValid(String::new()).code(),
+            CertificateError::UntrustedIssuer(String::new()).code(),
+            CertificateError::InvalidChain(String::new()).code(),
+            CertificateError::InvalidExtension(String::new()).code(),
+            CertificateError::KeyMismatch(String::new()).code(),
+            CertificateError::Revoked(String::new()).code(),
+            CertificateError::StatusUnknown(String::new()).code(),
+            CertificateError::StatusUnavailable(String::new()).code(),
+            CertificateError::NotFound(String::new()).code(),
+            INTERNAL,
+        ]
+    }
+
+    #[test]
+    fn test_codes_are_unique() {
+        let codes = all_codes();
+        let numbers: HashSet<u32> = codes.iter().map(|c| c.number).collect();
+        let names: HashSet<&str> = codes.iter().map(|c| c.name).collect();
+        assert_eq!(numbers.len(), codes.len());
+        assert_eq!(names.len(), codes.len());
+    }
+
+    #[test]
+    fn test_status_mapping() {
+        let err: Error = KmsError::KeyNotFound("settlement".to_string()).into();
+        assert_eq!(err.code().name, "KMS_KEY_NOT_FOUND");
+        assert_eq!(err.http_status(), 404);
+        assert_eq!(err.grpc_code(), 5);
+
+        let err: Error = KemError::BadLength { what: "shared secret", expected: 32, actual: 16 }.into();
+        assert_eq!(err.http_status(), 400);
+        assert_eq!(err.to_string(), "BadLength: shared secret expected 32, got 16");
+    }
+
+    #[test]
+    fn test_recovered_from_anyhow() {
+        let err = Error::from(anyhow::Error::new(EtlError::BatchFull));
+        assert!(matches!(err, Error::Etl(EtlError::BatchFull)));
+
+        let err = Error::from(anyhow::anyhow!("something else"));
+        assert_eq!(err.code().name, "INTERNAL");
+        assert_eq!(err.to_string(), "Internal error");
+    }
+}
@@ -1,12 +1,134 @@
-use anyhow::Result;
+pub mod status;
+pub mod transit;
+pub mod usage;
+
+use crate::error::{Error, Result};
+use crate::utils::tls::{self, TlsIdentity};
+use actix_web::http::StatusCode;
+use actix_web::{web, App, HttpResponse, HttpServer, ResponseError};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+// Response header carrying the numeric gRPC status for gateway clients
+pub const GRPC_STATUS_HEADER: &str = "grpc-status";
+pub const HEALTH_PATH: &str = "/health";
 
-#[derive(Default)]
 pub struct ApiConfig {
     pub port: u16,
     pub host: String,
+    /// Serves HTTPS with this certificate and key instead of plain HTTP
+    pub tls: Option<TlsIdentity>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            host: "127.0.0.1".to_string(),
+            tls: None,
+        }
+    }
+}
+
+/// JSON body returned for every failed request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: u32,
+    pub error: String,
+    pub message: String,
 }
 
+impl From<&Error> for ErrorResponse {
+    fn from(err: &Error) -> Self {
+        let code = err.code();
+        Self {
+            code: code.number,
+            error: code.name.to_string(),
+            // Internal errors keep their detail in the logs, not in the response
+            message: err.to_string(),
+        }
+    }
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let Error::Internal(source) = self {
+            tracing::error!(error = ?source, "internal error while handling request");
+        }
+        HttpResponse::build(self.status_code())
+            .insert_header((GRPC_STATUS_HEADER, self.grpc_code().to_string()))
+            .json(ErrorResponse::from(self))
+    }
+}
+
+/// Registers the routes served by [`start_api_server`]
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route(HEALTH_PATH, web::get().to(health));
+}
+
+async fn health() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Serves [`configure`]'s routes until the server is stopped. A bad TLS
+/// identity fails with [`TlsError`](crate::error::TlsError).
 pub async fn start_api_server(config: ApiConfig) -> Result<()> {
-    println!("Starting API server on {}:{}", config.host, config.port);
+    let address = (config.host.as_str(), config.port);
+    let server = HttpServer::new(|| App::new().configure(configure));
+    let server = match &config.tls {
+        Some(identity) => {
+            let server_config = tls::server_config(identity, None)?;
+            println!("Starting API server on https://{}:{}", config.host, config.port);
+            server.bind_rustls_0_23(address, (*server_config).clone())
+        }
+        None => {
+            println!("Starting API server on http://{}:{}", config.host, config.port);
+            server.bind(address)
+        }
+    }
+    .with_context(|| format!("Failed to bind {}:{}", config.host, config.port))?;
+    server.run().await.context("API server stopped")?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{KmsError, ValidationError};
+
+    #[test]
+    fn test_error_response_mapping() {
+        let err = Error::from(KmsError::KeyNotFound("settlement".to_string()));
+        let response = err.error_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers().get(GRPC_STATUS_HEADER).unwrap(), "5");
+
+        let body = ErrorResponse::from(&err);
+        assert_eq!(body.code, 3001);
+        assert_eq!(body.error, "KMS_KEY_NOT_FOUND");
+
+        let err = Error::from(ValidationError::UnknownSigner("mallory".to_string()));
+        assert_eq!(err.error_response().status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_health_route() {
+        let app = actix_web::test::init_service(App::new().configure(configure)).await;
+        let request = actix_web::test::TestRequest::get().uri(HEALTH_PATH).to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_internal_error_hides_detail() {
+        let err = Error::from(anyhow::anyhow!("disk /var/lib/kms unreadable"));
+        let body = ErrorResponse::from(&err);
+        assert_eq!(err.error_response().status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body.error, "INTERNAL");
+        assert!(!body.message.contains("/var/lib/kms"));
+    }
+}
@@ -0,0 +1,141 @@
+//! HTTP endpoints of the certificate status responder, and a client for them.
+//!
+//! | route                    | response                                   |
+//! |--------------------------|--------------------------------------------|
+//! | `GET /pki/status/{hex}`  | [`SignedStatus`] as JSON                   |
+//! | `GET /pki/crl`           | current CRL, DER (`application/pkix-crl`)  |
+//! | `GET /pki/ca`            | CA certificate, DER (`application/pkix-cert`) |
+//!
+//! Mount with `App::new().app_data(web::Data::from(responder)).configure(configure)`.
+
+use crate::crypto::x509::{SignedStatus, StatusResponder, StatusSource};
+use crate::error::Error;
+use crate::utils::{encoding, http};
+use super::ErrorResponse;
+use actix_web::{web, HttpResponse};
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+
+pub const STATUS_PATH: &str = "/pki/status";
+pub const CRL_PATH: &str = "/pki/crl";
+pub const CA_PATH: &str = "/pki/ca";
+pub use http::DEFAULT_TIMEOUT;
+
+/// Registers the status routes; the app must hold a `web::Data<StatusResponder>`
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route(&format!("{}/{{serial}}", STATUS_PATH), web::get().to(status))
+        .route(CRL_PATH, web::get().to(crl))
+        .route(CA_PATH, web::get().to(ca_certificate));
+}
+
+async fn status(responder: web::Data<StatusResponder>, serial: web::Path<String>) -> Result<HttpResponse, Error> {
+    let serial = encoding::from_hex(&serial)?;
+    Ok(HttpResponse::Ok().json(responder.respond(&serial)?))
+}
+
+async fn crl(responder: web::Data<StatusResponder>) -> Result<HttpResponse, Error> {
+    let crl = responder.crl()?;
+    Ok(HttpResponse::Ok().content_type("application/pkix-crl").body(crl.as_der().to_vec()))
+}
+
+async fn ca_certificate(responder: web::Data<StatusResponder>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/pkix-cert")
+        .body(responder.ca_certificate().as_der().to_vec())
+}
+
+/// Fetches statuses from a remote responder over plain HTTP. Responses are
+/// signed, so the transport needs no protection of its own; blocking, so
+/// call it off the async runtime.
+#[derive(Debug, Clone)]
+pub struct HttpStatusSource {
+    address: String,
+    timeout: Duration,
+}
+
+impl HttpStatusSource {
+    /// Responder at `address`, as `host:port`
+    pub fn new(address: impl Into<String>) -> Self {
+        Self { address: address.into(), timeout: DEFAULT_TIMEOUT }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn get(&self, path: &str) -> Result<Vec<u8>> {
+        let response = http::request(&self.address, "GET", path, &[("Accept", "application/json")], None, self.timeout)?;
+        if response.status != 200 {
+            return Err(match serde_json::from_slice::<ErrorResponse>(&response.body) {
+                Ok(error) => anyhow!("{} answered {}: {}", self.address, error.error, error.message),
+                Err(_) => anyhow!("{} answered HTTP {}", self.address, response.status),
+            });
+        }
+        Ok(response.body)
+    }
+}
+
+impl StatusSource for HttpStatusSource {
+    fn fetch(&self, serial: &[u8]) -> Result<SignedStatus> {
+        let body = self.get(&format!("{}/{}", STATUS_PATH, encoding::to_hex(serial)))?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signature::MlDsaParameterSet;
+    use crate::crypto::x509::{
+        CertificateAuthority, CertificateRevocationList, CertificateStatus, Name, RevocationChecker, RevocationReason,
+        RevocationRegistry, Validity,
+    };
+    use actix_web::{App, HttpServer};
+    use pqcrypto_kyber::kyber1024;
+    use pqcrypto_traits::kem::PublicKey as _;
+    use std::sync::Arc;
+
+    #[actix_web::test]
+    async fn test_status_over_http() {
+        let ca = CertificateAuthority::new_root(Name::new("Root"), MlDsaParameterSet::MlDsa65, Validity::for_days(30).unwrap())
+            .unwrap();
+        let registry = Arc::new(RevocationRegistry::new(ca.certificate()));
+        let leaf = ca
+            .issue_kem_certificate(Name::new("inbound"), kyber1024::keypair().0.as_bytes(), Validity::for_days(1).unwrap())
+            .unwrap();
+        registry.record_issued(&leaf).unwrap();
+        registry.revoke(leaf.serial(), RevocationReason::KeyCompromise, 1_700_000_000).unwrap().unwrap();
+        let root = ca.certificate().clone();
+        let responder = web::Data::new(StatusResponder::new(ca, registry).unwrap());
+
+        let server = HttpServer::new(move || App::new().app_data(responder.clone()).configure(configure))
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .unwrap();
+        let address = server.addrs()[0].to_string();
+        let handle = server.run();
+        let server = actix_web::rt::spawn(handle);
+
+        let source = HttpStatusSource::new(address);
+        let (signed, crl, bad_serial, checked) = web::block(move || {
+            let signed = source.fetch(leaf.serial());
+            let crl = source.get(CRL_PATH);
+            let bad_serial = source.get(&format!("{}/not-hex", STATUS_PATH));
+            let checked = RevocationChecker::new(source).check(&leaf, &root);
+            (signed, crl.map(|der| (der, root)), bad_serial, checked)
+        })
+        .await
+        .unwrap();
+        server.abort();
+
+        let signed = signed.unwrap();
+        assert!(matches!(signed.response.status, CertificateStatus::Revoked { .. }));
+        let (crl, root) = crl.unwrap();
+        let crl = CertificateRevocationList::from_der(&crl).unwrap();
+        crl.verify(&root, crl.this_update()).unwrap();
+        assert_eq!(crl.entries().len(), 1);
+        assert!(bad_serial.unwrap_err().to_string().contains("ENCODING"));
+        assert_eq!(Error::from(checked.unwrap_err()).code().name, "X509_REVOKED");
+    }
+}
@@ -0,0 +1,609 @@
+//! Vault Transit-compatible HTTP facade over the KMS.
+//!
+//! Services written against HashiCorp Vault's Transit engine can switch to
+//! Kyber-1024 envelope encryption by changing only the address they call:
+//!
+//! | route                                      | KMS call                          |
+//! |--------------------------------------------|-----------------------------------|
+//! | `POST /v1/transit/keys/{name}`             | create a versioned Kyber key      |
+//! | `GET  /v1/transit/keys/{name}`             | versions and their creation times |
+//! | `POST /v1/transit/keys/{name}/rotate`      | add a version                     |
+//! | `POST /v1/transit/encrypt/{name}`          | envelope-encrypt under the newest version |
+//! | `POST /v1/transit/decrypt/{name}`          | decrypt with the version named in the ciphertext |
+//! | `POST /v1/transit/rewrap/{name}`           | re-encrypt under the newest version |
+//! | `POST /v1/transit/datakey/{plaintext,wrapped}/{name}` | fresh data key, optionally returned in plaintext |
+//!
+//! PUT is accepted wherever POST is, as Vault does. Ciphertexts keep Vault's
+//! `vault:v<version>:<base64>` shape, with a KMS envelope (see
+//! [`crate::crypto::kms::envelope`]) as the payload. `context` and
+//! `associated_data` are both bound as AEAD associated data and must be sent
+//! again to decrypt. Data keys come from
+//! [`generate_data_key`](KeyManagementSystem::generate_data_key), with the
+//! two fields as its encryption context, so they are audited as data keys;
+//! `decrypt` and `rewrap` accept their ciphertexts too. Encrypting under an unknown key creates it, if the
+//! caller may create keys. Batch requests, convergent encryption, key export
+//! and the other key types are not offered.
+//!
+//! Each `X-Vault-Token` (or `Authorization: Bearer`) token maps to a KMS
+//! principal, so the KMS access policy and audit log apply to every call.
+//! Unknown tokens get 403, and errors come back as Vault's `{"errors": [..]}`.
+//! Mount with `App::new().app_data(web::Data::new(transit_api)).configure(configure)`.
+
+use crate::crypto::kms::{CallerContext, DataKeySpec, EncryptionContext, EnvelopeHeader, KeyManagementSystem, KeyVersionId};
+use crate::error::{EncodingError, Error, KmsError};
+use crate::utils::encoding;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use zeroize::Zeroizing;
+
+pub const TRANSIT_PATH: &str = "/v1/transit";
+pub const TOKEN_HEADER: &str = "X-Vault-Token";
+const CIPHERTEXT_PREFIX: &str = "vault:v";
+const KEY_TYPE: &str = "kyber-1024";
+const DEFAULT_DATA_KEY_BITS: usize = 256;
+// Encryption context keys a data key's `context` and `associated_data` go under
+const CONTEXT_FIELD: &str = "transit.context";
+const ASSOCIATED_DATA_FIELD: &str = "transit.associated_data";
+
+/// Shared KMS and the principal behind each accepted token
+pub struct TransitApi {
+    kms: Arc<Mutex<KeyManagementSystem>>,
+    // SHA3-256 of each token, so the tokens themselves are not kept
+    tokens: HashMap<[u8; 32], CallerContext>,
+}
+
+/// Error in Vault's response shape; status and message come from [`Error`]
+#[derive(Debug)]
+pub struct TransitError(Error);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransitErrors {
+    pub errors: Vec<String>,
+}
+
+/// Every successful response wraps its result in `data`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransitResponse<T> {
+    pub data: T,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EncryptRequest {
+    pub plaintext: String,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default)]
+    pub associated_data: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CiphertextRequest {
+    pub ciphertext: String,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default)]
+    pub associated_data: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DataKeyRequest {
+    #[serde(default)]
+    pub bits: Option<usize>,
+    #[serde(default)]
+    pub context: Option<String>,
+    #[serde(default)]
+    pub associated_data: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CiphertextData {
+    pub ciphertext: String,
+    pub key_version: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlaintextData {
+    pub plaintext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataKeyData {
+    pub ciphertext: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plaintext: Option<String>,
+    pub key_version: u32,
+}
+
+/// Vault's key description, limited to what applies to KMS keys
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyData {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub key_type: String,
+    pub latest_version: u32,
+    pub min_decryption_version: u32,
+    /// Creation time of each version, by version number
+    pub keys: BTreeMap<String, u64>,
+    pub supports_encryption: bool,
+    pub supports_decryption: bool,
+    pub exportable: bool,
+    pub deletion_allowed: bool,
+}
+
+impl TransitApi {
+    pub fn new(kms: Arc<Mutex<KeyManagementSystem>>) -> Self {
+        Self { kms, tokens: HashMap::new() }
+    }
+
+    /// Accepts `token` as `caller`
+    pub fn with_token(mut self, token: &str, caller: CallerContext) -> Self {
+        self.tokens.insert(token_digest(token), caller);
+        self
+    }
+
+    fn kms(&self) -> Result<MutexGuard<'_, KeyManagementSystem>, KmsError> {
+        self.kms
+            .lock()
+            .map_err(|_| KmsError::Storage("KMS lock poisoned".to_string()))
+    }
+
+    fn caller(&self, request: &HttpRequest) -> Result<&CallerContext, KmsError> {
+        let headers = request.headers();
+        let token = headers
+            .get(TOKEN_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .or_else(|| {
+                headers
+                    .get("Authorization")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "))
+            })
+            .ok_or_else(|| KmsError::AccessDenied("missing client token".to_string()))?;
+        self.tokens
+            .get(&token_digest(token))
+            .ok_or_else(|| KmsError::AccessDenied("permission denied".to_string()))
+    }
+}
+
+impl fmt::Display for TransitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Error> for TransitError {
+    fn from(err: Error) -> Self {
+        Self(err)
+    }
+}
+
+impl From<KmsError> for TransitError {
+    fn from(err: KmsError) -> Self {
+        Self(err.into())
+    }
+}
+
+impl From<EncodingError> for TransitError {
+    fn from(err: EncodingError) -> Self {
+        Self(err.into())
+    }
+}
+
+impl ResponseError for TransitError {
+    fn status_code(&self) -> StatusCode {
+        self.0.status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let Error::Internal(source) = &self.0 {
+            tracing::error!(error = ?source, "internal error while handling transit request");
+        }
+        HttpResponse::build(self.status_code()).json(TransitErrors { errors: vec![self.0.to_string()] })
+    }
+}
+
+/// Registers the Transit routes; the app must hold a `web::Data<TransitApi>`
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource(format!("{}/keys/{{name}}", TRANSIT_PATH))
+            .route(web::post().to(create_key))
+            .route(web::put().to(create_key))
+            .route(web::get().to(read_key)),
+    )
+    .service(
+        web::resource(format!("{}/keys/{{name}}/rotate", TRANSIT_PATH))
+            .route(web::post().to(rotate_key))
+            .route(web::put().to(rotate_key)),
+    )
+    .service(
+        web::resource(format!("{}/encrypt/{{name}}", TRANSIT_PATH))
+            .app_data(json_config())
+            .route(web::post().to(encrypt))
+            .route(web::put().to(encrypt)),
+    )
+    .service(
+        web::resource(format!("{}/decrypt/{{name}}", TRANSIT_PATH))
+            .app_data(json_config())
+            .route(web::post().to(decrypt))
+            .route(web::put().to(decrypt)),
+    )
+    .service(
+        web::resource(format!("{}/rewrap/{{name}}", TRANSIT_PATH))
+            .app_data(json_config())
+            .route(web::post().to(rewrap))
+            .route(web::put().to(rewrap)),
+    )
+    .service(
+        web::resource(format!("{}/datakey/{{kind}}/{{name}}", TRANSIT_PATH))
+            .app_data(json_config())
+            .route(web::post().to(data_key))
+            .route(web::put().to(data_key)),
+    );
+}
+
+// Vault takes JSON whatever the Content-Type, and its clients often send none
+fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default()
+        .content_type_required(false)
+        .error_handler(|err, _| TransitError::from(KmsError::InvalidRequest(err.to_string())).into())
+}
+
+type Response<T> = Result<web::Json<TransitResponse<T>>, TransitError>;
+
+fn respond<T>(data: T) -> Response<T> {
+    Ok(web::Json(TransitResponse { data }))
+}
+
+async fn create_key(api: web::Data<TransitApi>, request: HttpRequest, name: web::Path<String>) -> Response<KeyData> {
+    let caller = api.caller(&request)?;
+    let mut kms = api.kms()?;
+    kms.generate_versioned_kem_key(caller, &name)?;
+    respond(key_data(&kms, caller, &name)?)
+}
+
+async fn read_key(api: web::Data<TransitApi>, request: HttpRequest, name: web::Path<String>) -> Response<KeyData> {
+    let caller = api.caller(&request)?;
+    let kms = api.kms()?;
+    respond(key_data(&kms, caller, &name)?)
+}
+
+async fn rotate_key(api: web::Data<TransitApi>, request: HttpRequest, name: web::Path<String>) -> Response<KeyData> {
+    let caller = api.caller(&request)?;
+    let mut kms = api.kms()?;
+    kms.rotate_version(caller, &name)?;
+    respond(key_data(&kms, caller, &name)?)
+}
+
+async fn encrypt(
+    api: web::Data<TransitApi>,
+    request: HttpRequest,
+    name: web::Path<String>,
+    body: web::Json<EncryptRequest>,
+) -> Response<CiphertextData> {
+    let caller = api.caller(&request)?;
+    let plaintext = Zeroizing::new(encoding::from_base64(&body.plaintext)?);
+    let aad = associated_data(body.context.as_deref(), body.associated_data.as_deref())?;
+    let mut kms = api.kms()?;
+    if kms.key_versions(caller, &name)?.is_empty() {
+        kms.generate_versioned_kem_key(caller, &name)?;
+    }
+    respond(seal(&mut kms, caller, &name, &plaintext, &aad)?)
+}
+
+async fn decrypt(
+    api: web::Data<TransitApi>,
+    request: HttpRequest,
+    name: web::Path<String>,
+    body: web::Json<CiphertextRequest>,
+) -> Response<PlaintextData> {
+    let caller = api.caller(&request)?;
+    let envelope = parse_ciphertext(&name, &body.ciphertext)?;
+    let aad = associated_data(body.context.as_deref(), body.associated_data.as_deref())?;
+    let mut kms = api.kms()?;
+    let plaintext = match kms.decrypt(caller, &envelope, &aad) {
+        Err(Error::Kms(KmsError::InvalidEnvelope(_))) => {
+            let context = data_key_context(body.context.as_deref(), body.associated_data.as_deref())?;
+            kms.decrypt_data_key(caller, &envelope, &context)?.with_exposed(encoding::to_base64).map_err(Error::from)?
+        }
+        result => encoding::to_base64(&result?),
+    };
+    respond(PlaintextData { plaintext })
+}
+
+async fn rewrap(
+    api: web::Data<TransitApi>,
+    request: HttpRequest,
+    name: web::Path<String>,
+    body: web::Json<CiphertextRequest>,
+) -> Response<CiphertextData> {
+    let caller = api.caller(&request)?;
+    let envelope = parse_ciphertext(&name, &body.ciphertext)?;
+    let aad = associated_data(body.context.as_deref(), body.associated_data.as_deref())?;
+    let mut kms = api.kms()?;
+    match kms.decrypt(caller, &envelope, &aad) {
+        Err(Error::Kms(KmsError::InvalidEnvelope(_))) => {
+            let context = data_key_context(body.context.as_deref(), body.associated_data.as_deref())?;
+            let blob = kms.re_encrypt(caller, &envelope, &context, &name, &context)?;
+            respond(ciphertext_data(&blob)?)
+        }
+        result => respond(seal(&mut kms, caller, &name, &result?, &aad)?),
+    }
+}
+
+async fn data_key(
+    api: web::Data<TransitApi>,
+    request: HttpRequest,
+    path: web::Path<(String, String)>,
+    body: Option<web::Json<DataKeyRequest>>,
+) -> Response<DataKeyData> {
+    let caller = api.caller(&request)?;
+    let (kind, name) = path.into_inner();
+    let include_plaintext = match kind.as_str() {
+        "plaintext" => true,
+        "wrapped" => false,
+        other => return Err(KmsError::InvalidRequest(format!("unknown data key type {}", other)).into()),
+    };
+    let body = body.map(web::Json::into_inner).unwrap_or_default();
+    let spec = match body.bits.unwrap_or(DEFAULT_DATA_KEY_BITS) {
+        128 => DataKeySpec::Aes128,
+        256 => DataKeySpec::Aes256,
+        bits => return Err(KmsError::InvalidRequest(format!("invalid bit size {}", bits)).into()),
+    };
+    let context = data_key_context(body.context.as_deref(), body.associated_data.as_deref())?;
+
+    let data_key = api.kms()?.generate_data_key(caller, &name, spec, &context)?;
+    let plaintext = match include_plaintext {
+        true => Some(data_key.plaintext.with_exposed(encoding::to_base64).map_err(Error::from)?),
+        false => None,
+    };
+    respond(DataKeyData {
+        ciphertext: format_ciphertext(data_key.key_id.version, &data_key.ciphertext_blob),
+        plaintext,
+        key_version: data_key.key_id.version,
+    })
+}
+
+fn seal(kms: &mut KeyManagementSystem, caller: &CallerContext, name: &str, plaintext: &[u8], aad: &[u8]) -> Result<CiphertextData, Error> {
+    let envelope = kms.encrypt(caller, name, plaintext, aad)?;
+    ciphertext_data(&envelope)
+}
+
+fn ciphertext_data(envelope: &[u8]) -> Result<CiphertextData, Error> {
+    let (header, _) = EnvelopeHeader::parse(envelope)?;
+    Ok(CiphertextData {
+        ciphertext: format_ciphertext(header.key_id.version, envelope),
+        key_version: header.key_id.version,
+    })
+}
+
+fn format_ciphertext(version: u32, envelope: &[u8]) -> String {
+    format!("{}{}:{}", CIPHERTEXT_PREFIX, version, encoding::to_base64(envelope))
+}
+
+// The version in the prefix must match the envelope, and both must be `name`'s
+fn parse_ciphertext(name: &str, ciphertext: &str) -> Result<Vec<u8>, Error> {
+    let invalid = || KmsError::InvalidEnvelope("invalid ciphertext".to_string());
+    let (version, payload) = ciphertext
+        .strip_prefix(CIPHERTEXT_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .ok_or_else(invalid)?;
+    let version: u32 = version.parse().map_err(|_| invalid())?;
+    let envelope = encoding::from_base64(payload)?;
+    let (header, _) = EnvelopeHeader::parse(&envelope)?;
+    if header.key_id != KeyVersionId::new(name, version) {
+        return Err(KmsError::InvalidEnvelope(format!("ciphertext is for key {}, not {}", header.key_id, name)).into());
+    }
+    Ok(envelope)
+}
+
+// Each part length-prefixed, so moving bytes between them changes the result
+fn associated_data(context: Option<&str>, associated_data: Option<&str>) -> Result<Vec<u8>, Error> {
+    let mut aad = Vec::new();
+    for part in [context, associated_data] {
+        let part = part.map(encoding::from_base64).transpose()?.unwrap_or_default();
+        aad.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        aad.extend_from_slice(&part);
+    }
+    Ok(aad)
+}
+
+// Checked as base64 so both forms of a request agree on what is valid
+fn data_key_context(context: Option<&str>, associated_data: Option<&str>) -> Result<EncryptionContext, Error> {
+    let mut fields = EncryptionContext::new();
+    for (field, value) in [(CONTEXT_FIELD, context), (ASSOCIATED_DATA_FIELD, associated_data)] {
+        if let Some(value) = value {
+            encoding::from_base64(value)?;
+            fields.insert(field.to_string(), value.to_string());
+        }
+    }
+    Ok(fields)
+}
+
+fn key_data(kms: &KeyManagementSystem, caller: &CallerContext, name: &str) -> Result<KeyData, KmsError> {
+    let versions = kms.key_versions(caller, name)?;
+    let latest_version = versions.last().copied().ok_or_else(|| KmsError::KeyNotFound(name.to_string()))?;
+    let mut keys = BTreeMap::new();
+    for version in versions {
+        let metadata = kms.key_metadata(caller, &KeyVersionId::new(name, version).to_string())?;
+        keys.insert(version.to_string(), metadata.created_at);
+    }
+    Ok(KeyData {
+        name: name.to_string(),
+        key_type: KEY_TYPE.to_string(),
+        latest_version,
+        min_decryption_version: kms.minimum_version(caller, name)?.unwrap_or(1),
+        keys,
+        supports_encryption: true,
+        supports_decryption: true,
+        exportable: false,
+        deletion_allowed: false,
+    })
+}
+
+fn token_digest(token: &str) -> [u8; 32] {
+    Sha3_256::digest(token.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::access::administrator_policy;
+    use crate::crypto::kms::{KmsAction, Role};
+    use crate::crypto::secure::SecureSecret;
+    use crate::kms::{MasterKeyProvider, VaultTransitProvider};
+    use crate::utils::tls::{self, TestPki};
+    use actix_web::{test, App, HttpServer};
+    use serde_json::json;
+
+    fn api() -> TransitApi {
+        let admin = CallerContext::new("kms-admin");
+        let mut kms = KeyManagementSystem::new();
+        kms.set_access_policy(
+            administrator_policy(&admin.principal)
+                .with_role(Role::new("reader").allow("*", [KmsAction::Describe, KmsAction::Encrypt]))
+                .assign("billing", "reader"),
+        );
+        TransitApi::new(Arc::new(Mutex::new(kms)))
+            .with_token("s.admin", admin)
+            .with_token("s.billing", CallerContext::new("billing"))
+    }
+
+    fn post(path: &str, token: &str, body: serde_json::Value) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri(&format!("{}{}", TRANSIT_PATH, path))
+            .insert_header((TOKEN_HEADER, token))
+            .set_json(body)
+    }
+
+    #[actix_web::test]
+    async fn test_transit_round_trip() {
+        let app = test::init_service(App::new().app_data(web::Data::new(api())).configure(configure)).await;
+        let plaintext = encoding::to_base64(b"card 4111 1111 1111 1111");
+        let context = encoding::to_base64(b"tenant=acme");
+
+        let encrypted: TransitResponse<CiphertextData> = test::call_and_read_body_json(
+            &app,
+            post("/encrypt/payments", "s.admin", json!({ "plaintext": plaintext, "context": context })).to_request(),
+        )
+        .await;
+        assert!(encrypted.data.ciphertext.starts_with("vault:v1:"));
+
+        let rotated: TransitResponse<KeyData> = test::call_and_read_body_json(&app, post("/keys/payments/rotate", "s.admin", json!({})).to_request()).await;
+        assert_eq!(rotated.data.latest_version, 2);
+        assert_eq!(rotated.data.keys.len(), 2);
+
+        let rewrapped: TransitResponse<CiphertextData> = test::call_and_read_body_json(
+            &app,
+            post("/rewrap/payments", "s.admin", json!({ "ciphertext": encrypted.data.ciphertext, "context": context })).to_request(),
+        )
+        .await;
+        assert!(rewrapped.data.ciphertext.starts_with("vault:v2:"));
+        let decrypted: TransitResponse<PlaintextData> = test::call_and_read_body_json(
+            &app,
+            post("/decrypt/payments", "s.admin", json!({ "ciphertext": rewrapped.data.ciphertext, "context": context })).to_request(),
+        )
+        .await;
+        assert_eq!(decrypted.data.plaintext, plaintext);
+
+        // Wrong context, a ciphertext moved to another key, and a caller without Decrypt
+        let response = test::call_service(&app, post("/decrypt/payments", "s.admin", json!({ "ciphertext": rewrapped.data.ciphertext })).to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let errors: TransitErrors = test::read_body_json(response).await;
+        assert_eq!(errors.errors.len(), 1);
+        let response = test::call_service(&app, post("/decrypt/orders", "s.admin", json!({ "ciphertext": rewrapped.data.ciphertext, "context": context })).to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = test::call_service(&app, post("/decrypt/payments", "s.billing", json!({ "ciphertext": rewrapped.data.ciphertext, "context": context })).to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = test::call_service(&app, post("/encrypt/payments", "s.unknown", json!({ "plaintext": plaintext })).to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // JSON is read without a Content-Type, and a malformed body gets Vault's error shape
+        let request = test::TestRequest::post()
+            .uri(&format!("{}/encrypt/payments", TRANSIT_PATH))
+            .insert_header((TOKEN_HEADER, "s.admin"))
+            .set_payload(json!({ "plaintext": plaintext }).to_string())
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+        let request = test::TestRequest::post()
+            .uri(&format!("{}/encrypt/payments", TRANSIT_PATH))
+            .insert_header((TOKEN_HEADER, "s.admin"))
+            .set_payload("plaintext=")
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let _: TransitErrors = test::read_body_json(response).await;
+
+        // A wrapped data key opens through decrypt like any other ciphertext
+        let data_key: TransitResponse<DataKeyData> =
+            test::call_and_read_body_json(&app, post("/datakey/plaintext/payments", "s.billing", json!({ "bits": 128 })).to_request()).await;
+        let key = data_key.data.plaintext.unwrap();
+        assert_eq!(encoding::from_base64(&key).unwrap().len(), 16);
+        let decrypted: TransitResponse<PlaintextData> =
+            test::call_and_read_body_json(&app, post("/decrypt/payments", "s.admin", json!({ "ciphertext": data_key.data.ciphertext })).to_request()).await;
+        assert_eq!(decrypted.data.plaintext, key);
+        let wrapped: TransitResponse<DataKeyData> =
+            test::call_and_read_body_json(&app, post("/datakey/wrapped/payments", "s.billing", json!({})).to_request()).await;
+        assert!(wrapped.data.plaintext.is_none());
+        let response = test::call_service(&app, post("/datakey/wrapped/payments", "s.billing", json!({ "bits": 512 })).to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // A data key's context is its encryption context, and rewrap keeps it a data key
+        let data_key: TransitResponse<DataKeyData> = test::call_and_read_body_json(
+            &app,
+            post("/datakey/plaintext/payments", "s.admin", json!({ "context": context })).to_request(),
+        )
+        .await;
+        let response = test::call_service(&app, post("/decrypt/payments", "s.admin", json!({ "ciphertext": data_key.data.ciphertext })).to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let rewrapped: TransitResponse<CiphertextData> = test::call_and_read_body_json(
+            &app,
+            post("/rewrap/payments", "s.admin", json!({ "ciphertext": data_key.data.ciphertext, "context": context })).to_request(),
+        )
+        .await;
+        assert!(rewrapped.data.ciphertext.starts_with("vault:v2:"));
+        let decrypted: TransitResponse<PlaintextData> = test::call_and_read_body_json(
+            &app,
+            post("/decrypt/payments", "s.admin", json!({ "ciphertext": rewrapped.data.ciphertext, "context": context })).to_request(),
+        )
+        .await;
+        assert_eq!(Some(decrypted.data.plaintext), data_key.data.plaintext);
+    }
+
+    // The master-key provider's Vault client works against the facade too
+    #[actix_web::test]
+    async fn test_vault_client_against_facade() {
+        let api = web::Data::new(api());
+        let pki = TestPki::new();
+        let server_config = tls::server_config(&pki.server, None).unwrap();
+        let server = HttpServer::new(move || App::new().app_data(api.clone()).configure(configure))
+            .workers(1)
+            .bind_rustls_0_23(("127.0.0.1", 0), (*server_config).clone())
+            .unwrap();
+        let address = server.addrs()[0].to_string();
+        let server_handle = server.run();
+        let server = actix_web::rt::spawn(server_handle);
+
+        let client_config = tls::client_config(&pki.ca_pem, None).unwrap();
+        let provider = VaultTransitProvider::new(address, "kms-master", SecureSecret::from_bytes(b"s.admin"), client_config);
+        let result = web::block(move || {
+            let key = SecureSecret::from_bytes(&[5u8; 32]);
+            let wrapped = provider.wrap(&key)?;
+            provider.rotate()?;
+            let rewrapped = provider.rewrap(&wrapped)?;
+            let unwrapped = provider.unwrap(&rewrapped)?;
+            Ok::<_, KmsError>((String::from_utf8_lossy(&rewrapped).into_owned(), unwrapped.constant_time_eq(&key)))
+        })
+        .await
+        .unwrap();
+        server.abort();
+
+        let (rewrapped, same_key) = result.unwrap();
+        assert!(rewrapped.starts_with("vault:v2:"));
+        assert!(same_key);
+    }
+}
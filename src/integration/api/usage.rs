@@ -0,0 +1,83 @@
+//! HTTP endpoints for KMS key usage.
+//!
+//! | route                  | response                                     |
+//! |------------------------|----------------------------------------------|
+//! | `GET /kms/usage`       | [`KeyUsageReport`] of every key, as JSON     |
+//! | `GET /kms/usage/{id}`  | [`KeyUsageReport`] of one key version        |
+//!
+//! Like the KMIP server, the endpoints call the KMS as one service principal,
+//! which needs Describe on the keys it should report. For Prometheus, register
+//! a [`UsageMetrics`](crate::crypto::kms::UsageMetrics) with the
+//! [`monitoring`](crate::monitoring) registry instead.
+//! Mount with `App::new().app_data(web::Data::new(usage_api)).configure(configure)`.
+
+use crate::crypto::kms::{CallerContext, KeyManagementSystem, KeyUsageReport};
+use crate::error::{Error, KmsError};
+use actix_web::web;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub const USAGE_PATH: &str = "/kms/usage";
+
+/// Shared KMS and the principal the endpoints act as
+pub struct UsageApi {
+    kms: Arc<Mutex<KeyManagementSystem>>,
+    caller: CallerContext,
+}
+
+impl UsageApi {
+    pub fn new(kms: Arc<Mutex<KeyManagementSystem>>, caller: CallerContext) -> Self {
+        Self { kms, caller }
+    }
+
+    fn kms(&self) -> Result<MutexGuard<'_, KeyManagementSystem>, KmsError> {
+        self.kms
+            .lock()
+            .map_err(|_| KmsError::Storage("KMS lock poisoned".to_string()))
+    }
+}
+
+/// Registers the usage routes; the app must hold a `web::Data<UsageApi>`
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route(USAGE_PATH, web::get().to(usage_report))
+        .route(&format!("{}/{{key_id}}", USAGE_PATH), web::get().to(key_usage));
+}
+
+async fn usage_report(api: web::Data<UsageApi>) -> Result<web::Json<Vec<KeyUsageReport>>, Error> {
+    Ok(web::Json(api.kms()?.usage_report(&api.caller)?))
+}
+
+async fn key_usage(api: web::Data<UsageApi>, key_id: web::Path<String>) -> Result<web::Json<KeyUsageReport>, Error> {
+    Ok(web::Json(api.kms()?.key_usage(&api.caller, &key_id)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::access::administrator_policy;
+    use crate::crypto::kms::{UsageLimits, UsagePolicy};
+    use actix_web::http::StatusCode;
+    use actix_web::{test, App};
+
+    #[actix_web::test]
+    async fn test_usage_endpoints() {
+        let admin = CallerContext::new("kms-admin");
+        let mut kms = KeyManagementSystem::new();
+        kms.set_access_policy(administrator_policy(&admin.principal));
+        let hard = UsageLimits { max_encapsulations: Some(1), ..UsageLimits::default() };
+        kms.set_usage_policy(UsagePolicy::new().with_quota("*", UsageLimits::default(), hard));
+        kms.generate_kem_key(&admin, "inbound").unwrap();
+        kms.encapsulate(&admin, "inbound").unwrap();
+
+        let api = web::Data::new(UsageApi::new(Arc::new(Mutex::new(kms)), admin));
+        let app = test::init_service(App::new().app_data(api).configure(configure)).await;
+
+        let reports: Vec<KeyUsageReport> = test::call_and_read_body_json(&app, test::TestRequest::get().uri(USAGE_PATH).to_request()).await;
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].hard_limit_reached);
+
+        let response = test::call_service(&app, test::TestRequest::get().uri("/kms/usage/inbound").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = test::call_service(&app, test::TestRequest::get().uri("/kms/usage/missing").to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,10 +1,16 @@
 use super::transaction::Transaction;
+use crate::crypto::signature::{DetachedSignature, MlDsaSigningKey, SigningMode, TrustedSigners};
+use crate::error::EtlError;
 use anyhow::Result;
 use std::vec::Vec;
 
+//Context string binding signatures to the batch format
+pub const BATCH_SIGNING_CONTEXT: &[u8] = b"pqc-kyber/etl/batch/v1";
+
 pub struct TransactionBatch {
     pub transactions: Vec<Transaction>,
     pub max_size: usize,
+    pub signature: Option<DetachedSignature>,
 }
 
 impl TransactionBatch {
@@ -12,14 +18,16 @@ impl TransactionBatch {
         Self {
             transactions: Vec::with_capacity(max_size),
             max_size,
+            signature: None,
         }
     }
 
-    pub fn add_transaction(&mut self, transaction: Transaction) -> Result<()> {
+    pub fn add_transaction(&mut self, transaction: Transaction) -> Result<(), EtlError> {
         if self.transactions.len() >= self.max_size {
-            anyhow::bail!("Batch is full");
+            return Err(EtlError::BatchFull);
         }
         self.transactions.push(transaction);
+        self.signature = None;
         Ok(())
     }
 
@@ -29,6 +37,7 @@ impl TransactionBatch {
 
     pub fn clear(&mut self) {
         self.transactions.clear();
+        self.signature = None;
     }
 
     pub fn len(&self) -> usize {
@@ -38,4 +47,89 @@ impl TransactionBatch {
     pub fn is_empty(&self) -> bool {
         self.transactions.is_empty()
     }
-}
\ No newline at end of file
+
+    //Length-prefixed concatenation of every transaction payload, in batch order
+    pub fn signing_payload(&self) -> Result<Vec<u8>, EtlError> {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&(self.transactions.len() as u64).to_be_bytes());
+        for transaction in &self.transactions {
+            let tx_payload = transaction.signing_payload()?;
+            payload.extend_from_slice(&(tx_payload.len() as u64).to_be_bytes());
+            payload.extend_from_slice(&tx_payload);
+        }
+        Ok(payload)
+    }
+
+    //Signs the batch as a whole; adding transactions afterwards drops the signature
+    pub fn sign(&mut self, signer_id: &str, key: &MlDsaSigningKey, mode: SigningMode) -> Result<()> {
+        self.signature = Some(DetachedSignature::create(
+            signer_id,
+            key,
+            &self.signing_payload()?,
+            BATCH_SIGNING_CONTEXT,
+            mode,
+        )?);
+        Ok(())
+    }
+
+    pub fn verify_signature(&self, signers: &TrustedSigners) -> Result<()> {
+        let signature = self.signature.as_ref()
+            .ok_or(EtlError::BatchUnsigned)?;
+        signers.verify(signature, &self.signing_payload()?, BATCH_SIGNING_CONTEXT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::signature::MlDsaParameterSet;
+
+    fn sample_transaction(i: usize) -> Transaction {
+        Transaction::new(
+            format!("ACCSRC{:04}", i),
+            format!("ACCDST{:04}", i),
+            100.0 + i as f64,
+            "EUR".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_batch_signature() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let mut signers = TrustedSigners::new();
+        signers.add_signer("clearing", key.verifying_key().clone()).unwrap();
+
+        let mut batch = TransactionBatch::new(10);
+        for i in 0..3 {
+            batch.add_transaction(sample_transaction(i)).unwrap();
+        }
+
+        batch.sign("clearing", &key, SigningMode::Deterministic).unwrap();
+        assert!(batch.verify_signature(&signers).is_ok());
+
+        //Reordering transactions must break the signature
+        batch.transactions.swap(0, 2);
+        assert!(batch.verify_signature(&signers).is_err());
+    }
+
+    #[test]
+    fn test_adding_transaction_drops_signature() {
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa44);
+        let mut batch = TransactionBatch::new(10);
+        batch.add_transaction(sample_transaction(0)).unwrap();
+        batch.sign("clearing", &key, SigningMode::Hedged).unwrap();
+
+        batch.add_transaction(sample_transaction(1)).unwrap();
+        assert!(batch.signature.is_none());
+    }
+
+    #[test]
+    fn test_full_batch_rejected() {
+        let mut batch = TransactionBatch::new(1);
+        batch.add_transaction(sample_transaction(0)).unwrap();
+        assert!(matches!(
+            batch.add_transaction(sample_transaction(1)),
+            Err(EtlError::BatchFull)
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 
 use crate::config::{get_formatted_timestamp, get_current_user};
 
-use crate::etl::{
+use crate::integration::etl::{
     pipeline::ETLPipeline,
     transaction::Transaction,
 };
@@ -45,4 +45,72 @@ async fn test_large_transaction_processing() -> Result<()> {
     println!("-> End time: {}", get_formatted_timestamp());
 
     Ok(())
-}
\ No newline at end of file
+}
+
+//Signed ingest test: unsigned and forged transactions must be rejected
+#[tokio::test]
+async fn test_signed_transaction_ingest() -> Result<()> {
+    use crate::crypto::signature::{MlDsaParameterSet, MlDsaSigningKey, SigningMode, TrustedSigners};
+
+    let bank_key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+    let forger_key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+
+    let mut signers = TrustedSigners::new();
+    signers.add_signer("bank-a", bank_key.verifying_key().clone())?;
+
+    let mut transactions = Vec::new();
+    for i in 0..10 {
+        let mut tx = Transaction::new(
+            format!("ACCSRC{:04}", i),
+            format!("ACCDST{:04}", i),
+            500.0 + (i as f64),
+            "EUR".to_string(),
+        );
+        match i % 3 {
+            0 => tx.sign("bank-a", &bank_key, SigningMode::Hedged)?,
+            1 => tx.sign("bank-a", &forger_key, SigningMode::Hedged)?,
+            _ => {}
+        }
+        transactions.push(tx);
+    }
+
+    let (public_key, _) = keypair();
+    let mut pipeline = ETLPipeline::new(5, public_key).with_trusted_signers(signers);
+    let metrics = pipeline.process_transactions(transactions).await?;
+
+    assert_eq!(metrics.processed_transactions, 4);
+    assert_eq!(metrics.failed_transactions, 6);
+
+    Ok(())
+}
+
+//Batch ingest test: a valid batch signature vouches for unsigned transactions inside it
+#[tokio::test]
+async fn test_signed_batch_ingest() -> Result<()> {
+    use crate::crypto::signature::{MlDsaParameterSet, MlDsaSigningKey, SigningMode, TrustedSigners};
+    use crate::integration::etl::batch::TransactionBatch;
+
+    let clearing_key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+    let mut signers = TrustedSigners::new();
+    signers.add_signer("clearing", clearing_key.verifying_key().clone())?;
+
+    let mut batch = TransactionBatch::new(5);
+    for i in 0..5 {
+        batch.add_transaction(Transaction::new(
+            format!("ACCSRC{:04}", i),
+            format!("ACCDST{:04}", i),
+            500.0 + (i as f64),
+            "EUR".to_string(),
+        ))?;
+    }
+    batch.sign("clearing", &clearing_key, SigningMode::Hedged)?;
+
+    let (public_key, _) = keypair();
+    let mut pipeline = ETLPipeline::new(5, public_key).with_trusted_signers(signers);
+    let metrics = pipeline.process_batch(batch).await?;
+
+    assert_eq!(metrics.processed_transactions, 5);
+    assert_eq!(metrics.failed_transactions, 0);
+
+    Ok(())
+}
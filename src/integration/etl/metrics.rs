@@ -32,10 +32,9 @@ impl BatchMetrics {
         let new_duration = batch_metrics.last_batch_duration.as_nanos() as u64;
         let total_batches = self.total_batches as u64;
 
-        if total_batches > 0 {
-            self.average_batch_duration = Duration::from_nanos(
-                (avg_duration * (total_batches - 1) + new_duration) / total_batches
-            );
+        let total_duration = avg_duration * total_batches.saturating_sub(1) + new_duration;
+        if let Some(average) = total_duration.checked_div(total_batches) {
+            self.average_batch_duration = Duration::from_nanos(average);
         }
 
         println!("\n[Batch Metrics Update]");
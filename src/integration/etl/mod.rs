@@ -0,0 +1,9 @@
+//Public modules
+pub mod transaction;  //Transaction processing and validation
+pub mod batch;       //Batch operations handling
+pub mod metrics;     //Performance and operational metrics
+pub mod pipeline;    //ETL pipeline implementation
+
+//Private modules
+#[cfg(test)]
+mod etl_tests;      //Internal testing utilities
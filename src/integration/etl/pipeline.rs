@@ -12,6 +12,7 @@ use indicatif::{ProgressBar, ProgressStyle};
 use std::sync::Arc;
 use chrono::Utc;
 use crate::config::{get_formatted_timestamp, get_current_user};
+use crate::crypto::signature::TrustedSigners;
 
 
 
@@ -24,6 +25,7 @@ pub struct ETLPipeline {
     public_key: Box<dyn PublicKey>,
     processed_count: usize,
     failed_count: usize,
+    trusted_signers: Option<TrustedSigners>,
 }
 
 impl ETLPipeline {
@@ -35,11 +37,39 @@ impl ETLPipeline {
             public_key: Box::new(public_key),
             processed_count: 0,
             failed_count: 0,
+            trusted_signers: None,
         }
     }
 
+    //Requires every ingested transaction to carry a valid signature from one of the given signers
+    pub fn with_trusted_signers(mut self, signers: TrustedSigners) -> Self {
+        self.trusted_signers = Some(signers);
+        self
+    }
+
+    //Checks the transaction signature when signature verification is enabled
+    fn is_authentic(&self, transaction: &Transaction) -> bool {
+        match &self.trusted_signers {
+            Some(signers) => transaction.verify_signature(signers).is_ok(),
+            None => true,
+        }
+    }
+
+    //Verifies the batch signature and then processes the batch contents; the batch signature
+    //covers every transaction, so their own signatures are not checked again
+    pub async fn process_batch(&mut self, batch: TransactionBatch) -> Result<BatchMetrics> {
+        if let Some(signers) = &self.trusted_signers {
+            batch.verify_signature(signers)?;
+        }
+        self.process(batch.transactions, false).await
+    }
+
     //Processes a vector of transactions asynchronously with progress tracking
     pub async fn process_transactions(&mut self, transactions: Vec<Transaction>) -> Result<BatchMetrics> {
+        self.process(transactions, true).await
+    }
+
+    async fn process(&mut self, transactions: Vec<Transaction>, verify_each: bool) -> Result<BatchMetrics> {
         println!("\n[Starting ETL Pipeline]");
         println!("-> Time: {}", get_formatted_timestamp());
         println!("-> User: {}", get_current_user());
@@ -47,8 +77,10 @@ impl ETLPipeline {
 
         let start = Instant::now();
         let (tx, mut rx) = mpsc::channel(self.batch_size);
-        let mut metrics = BatchMetrics::default();
-        metrics.start_time = Some(Utc::now());
+        let mut metrics = BatchMetrics {
+            start_time: Some(Utc::now()),
+            ..BatchMetrics::default()
+        };
 
         //Process transactions in parallel using channels with increased buffer
         let tx = Arc::new(tx);
@@ -80,7 +112,7 @@ impl ETLPipeline {
         let mut failed = 0;
 
         while let Some(transaction) = rx.recv().await {
-            if transaction.validate() {
+            if transaction.validate() && (!verify_each || self.is_authentic(&transaction)) {
                 processed += 1;
                 self.processed_count += 1;
                 metrics.processed_transactions += 1;
@@ -110,7 +142,7 @@ impl ETLPipeline {
         metrics.end_time = Some(Utc::now());
         metrics.processing_duration = start.elapsed();
         metrics.total_transactions = transactions.len();
-        metrics.total_batches = (transactions.len() + self.batch_size - 1) / self.batch_size;
+        metrics.total_batches = transactions.len().div_ceil(self.batch_size);
 
         println!("\n[ETL Pipeline Results]");
         println!("-> Time: {}", get_formatted_timestamp());
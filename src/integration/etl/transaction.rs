@@ -1,6 +1,12 @@
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
+use anyhow::Result;
 use crate::config::{self};
+use crate::error::EtlError;
+use crate::crypto::signature::{DetachedSignature, MlDsaSigningKey, SigningMode, TrustedSigners};
+
+//Context string binding signatures to the transaction format
+pub const TRANSACTION_SIGNING_CONTEXT: &[u8] = b"pqc-kyber/etl/transaction/v1";
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +18,8 @@ pub struct Transaction {
     pub currency: String,
     pub timestamp: String,
     pub created_by: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<DetachedSignature>,
 }
 
 impl Transaction {
@@ -24,6 +32,7 @@ impl Transaction {
             currency,
             timestamp: config::get_formatted_timestamp(),
             created_by: config::get_current_user(),
+            signature: None,
         }
     }
 
@@ -33,6 +42,43 @@ impl Transaction {
             && self.amount > 0.0
             && !self.currency.is_empty()
     }
+
+    //Canonical bytes covered by the transaction signature (everything except the signature).
+    //JSON writes every non-finite amount as null, so those cannot be signed.
+    pub fn signing_payload(&self) -> Result<Vec<u8>, EtlError> {
+        if !self.amount.is_finite() {
+            return Err(EtlError::InvalidTransaction(self.id.clone()));
+        }
+        serde_json::to_vec(&(
+            &self.id,
+            &self.source,
+            &self.target,
+            self.amount,
+            &self.currency,
+            &self.timestamp,
+            &self.created_by,
+        ))
+        .map_err(|_| EtlError::InvalidTransaction(self.id.clone()))
+    }
+
+    //Attaches a detached ML-DSA signature made by the given signer
+    pub fn sign(&mut self, signer_id: &str, key: &MlDsaSigningKey, mode: SigningMode) -> Result<()> {
+        self.signature = Some(DetachedSignature::create(
+            signer_id,
+            key,
+            &self.signing_payload()?,
+            TRANSACTION_SIGNING_CONTEXT,
+            mode,
+        )?);
+        Ok(())
+    }
+
+    //Checks the attached signature against the trusted signer set
+    pub fn verify_signature(&self, signers: &TrustedSigners) -> Result<()> {
+        let signature = self.signature.as_ref()
+            .ok_or_else(|| EtlError::TransactionUnsigned(self.id.clone()))?;
+        signers.verify(signature, &self.signing_payload()?, TRANSACTION_SIGNING_CONTEXT)
+    }
 }
 
 #[cfg(test)]
@@ -42,6 +88,7 @@ mod tests {
     #[test]
     fn test_transaction_creation() {
         // Initialize config with test user
+        config::initialize_config(Some("test_user".to_string()));
 
         let tx = Transaction::new(
             "PL12345678".to_string(),
@@ -77,4 +124,33 @@ mod tests {
         );
         assert!(!invalid_tx.validate());
     }
+
+    #[test]
+    fn test_transaction_signature() {
+        use crate::crypto::signature::MlDsaParameterSet;
+
+        let key = MlDsaSigningKey::generate(MlDsaParameterSet::MlDsa65);
+        let mut signers = TrustedSigners::new();
+        signers.add_signer("bank-a", key.verifying_key().clone()).unwrap();
+
+        let mut tx = Transaction::new(
+            "PL12345678".to_string(),
+            "PL87654321".to_string(),
+            100.0,
+            "PLN".to_string()
+        );
+        assert!(tx.verify_signature(&signers).is_err());
+
+        tx.sign("bank-a", &key, SigningMode::Hedged).unwrap();
+        assert!(tx.verify_signature(&signers).is_ok());
+
+        //Any change to the signed fields must invalidate the signature
+        tx.amount = 1_000_000.0;
+        assert!(tx.verify_signature(&signers).is_err());
+
+        //Non-finite amounts all encode alike, so they are never signed
+        tx.amount = f64::NAN;
+        assert!(matches!(tx.signing_payload(), Err(EtlError::InvalidTransaction(_))));
+        assert!(tx.sign("bank-a", &key, SigningMode::Hedged).is_err());
+    }
 }
\ No newline at end of file
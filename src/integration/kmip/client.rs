@@ -0,0 +1,257 @@
+//! KMIP client for the operations [`KmipServer`](super::KmipServer) implements.
+
+use super::message::{
+    parse_response, request_message, revocation_reason_code, Attributes, KeyBlock, ObjectType, Operation,
+    RequestItem, ResultStatus, AES_ALGORITHM, KYBER1024_ALGORITHM,
+};
+use super::ttlv::{io_error, Tag, Ttlv};
+use crate::crypto::kms::TransitionReason;
+use crate::error::KmipError;
+use crate::utils::tls;
+use rustls::ClientConfig;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+/// One TLS connection to a KMIP server; requests are sent one at a time
+pub struct KmipClient {
+    stream: TlsStream<TcpStream>,
+}
+
+impl KmipClient {
+    /// Connects to `address` (`host:port`) with `tls`, which must carry the
+    /// client certificate the server has registered
+    pub async fn connect(address: &str, tls: Arc<ClientConfig>) -> Result<Self, KmipError> {
+        let server_name = tls::server_name(address).map_err(|e| KmipError::Connection(e.to_string()))?;
+        let stream = TcpStream::connect(address).await.map_err(io_error("connect"))?;
+        let stream = TlsConnector::from(tls)
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| KmipError::Connection(tls::handshake_error(address, e).to_string()))?;
+        Ok(Self { stream })
+    }
+
+    /// Creates a PreActive AES key and returns its unique identifier
+    pub async fn create_symmetric_key(&mut self, name: &str, length_bits: i32) -> Result<String, KmipError> {
+        let attributes = Attributes {
+            cryptographic_algorithm: Some(AES_ALGORITHM),
+            cryptographic_length: Some(length_bits),
+            name: Some(name.to_string()),
+            ..Attributes::default()
+        };
+        let payload = self.call(Operation::Create, vec![
+            Ttlv::enumeration(Tag::OBJECT_TYPE, ObjectType::SymmetricKey.code()),
+            attributes.to_ttlv(Tag::ATTRIBUTES),
+        ]).await?;
+        Ok(payload.require(Tag::UNIQUE_IDENTIFIER)?.as_text()?.to_string())
+    }
+
+    /// Creates a PreActive Kyber-1024 key pair; returns the private and public key identifiers
+    pub async fn create_key_pair(&mut self, name: &str) -> Result<(String, String), KmipError> {
+        let attributes = Attributes {
+            cryptographic_algorithm: Some(KYBER1024_ALGORITHM),
+            name: Some(name.to_string()),
+            ..Attributes::default()
+        };
+        let payload = self.call(Operation::CreateKeyPair, vec![attributes.to_ttlv(Tag::COMMON_ATTRIBUTES)]).await?;
+        Ok((
+            payload.require(Tag::PRIVATE_KEY_UNIQUE_IDENTIFIER)?.as_text()?.to_string(),
+            payload.require(Tag::PUBLIC_KEY_UNIQUE_IDENTIFIER)?.as_text()?.to_string(),
+        ))
+    }
+
+    pub async fn get(&mut self, unique_identifier: &str) -> Result<KeyBlock, KmipError> {
+        let payload = self.call(Operation::Get, vec![Ttlv::text(Tag::UNIQUE_IDENTIFIER, unique_identifier)]).await?;
+        KeyBlock::from_payload(&payload)
+    }
+
+    pub async fn get_attributes(&mut self, unique_identifier: &str) -> Result<Attributes, KmipError> {
+        let payload = self
+            .call(Operation::GetAttributes, vec![Ttlv::text(Tag::UNIQUE_IDENTIFIER, unique_identifier)])
+            .await?;
+        Attributes::from_ttlv(payload.require(Tag::ATTRIBUTES)?)
+    }
+
+    /// Identifiers of the objects whose attributes match every attribute set in `filter`
+    pub async fn locate(&mut self, filter: &Attributes, maximum_items: Option<i32>) -> Result<Vec<String>, KmipError> {
+        let mut request = Vec::new();
+        if let Some(maximum) = maximum_items {
+            request.push(Ttlv::integer(Tag::MAXIMUM_ITEMS, maximum));
+        }
+        request.push(filter.to_ttlv(Tag::ATTRIBUTES));
+        let payload = self.call(Operation::Locate, request).await?;
+        payload
+            .find_all(Tag::UNIQUE_IDENTIFIER)
+            .map(|id| id.as_text().map(str::to_string))
+            .collect()
+    }
+
+    pub async fn activate(&mut self, unique_identifier: &str) -> Result<(), KmipError> {
+        self.call(Operation::Activate, vec![Ttlv::text(Tag::UNIQUE_IDENTIFIER, unique_identifier)]).await?;
+        Ok(())
+    }
+
+    /// Deactivates the key, or marks it compromised for [`TransitionReason::KeyCompromise`]
+    pub async fn revoke(&mut self, unique_identifier: &str, reason: TransitionReason, message: Option<&str>) -> Result<(), KmipError> {
+        let mut revocation = vec![Ttlv::enumeration(Tag::REVOCATION_REASON_CODE, revocation_reason_code(reason))];
+        revocation.extend(message.map(|message| Ttlv::text(Tag::REVOCATION_MESSAGE, message)));
+        self.call(Operation::Revoke, vec![
+            Ttlv::text(Tag::UNIQUE_IDENTIFIER, unique_identifier),
+            Ttlv::structure(Tag::REVOCATION_REASON, revocation),
+        ]).await?;
+        Ok(())
+    }
+
+    pub async fn destroy(&mut self, unique_identifier: &str) -> Result<(), KmipError> {
+        self.call(Operation::Destroy, vec![Ttlv::text(Tag::UNIQUE_IDENTIFIER, unique_identifier)]).await?;
+        Ok(())
+    }
+
+    // Sends a single-item request and returns the response payload
+    async fn call(&mut self, operation: Operation, payload: Vec<Ttlv>) -> Result<Ttlv, KmipError> {
+        let request = request_message(&[RequestItem {
+            operation: operation.code(),
+            payload: Ttlv::structure(Tag::REQUEST_PAYLOAD, payload),
+        }]);
+        self.stream.write_all(&request.encode()).await.map_err(io_error("write request"))?;
+        let response = Ttlv::read_message(&mut self.stream)
+            .await?
+            .ok_or_else(|| KmipError::Connection("server closed the connection".to_string()))?;
+
+        let item = parse_response(&response)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| KmipError::Malformed("response without batch items".to_string()))?;
+        if item.status != ResultStatus::Success {
+            let reason = item.reason.map(|reason| reason.as_str()).unwrap_or("GENERAL_FAILURE");
+            return Err(KmipError::OperationFailed(format!(
+                "{}: {}",
+                reason,
+                item.message.unwrap_or_default()
+            )));
+        }
+        Ok(item.payload.unwrap_or_else(|| Ttlv::structure(Tag::RESPONSE_PAYLOAD, Vec::new())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::{AccessPolicy, ApprovalPolicy, CallerContext, KeyManagementSystem, KeyState, KmsAction, Role};
+    use crate::integration::kmip::{KmipServer, PUBLIC_KEY_SUFFIX};
+    use crate::utils::tls::{client_config, TestPki};
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+
+    // KMIP server on an ephemeral localhost port; the client's certificate
+    // is registered as `kmip-gateway`
+    async fn start_server(approval: ApprovalPolicy) -> (Arc<Mutex<KeyManagementSystem>>, KmipClient) {
+        let mut kms = KeyManagementSystem::new();
+        kms.set_access_policy(
+            AccessPolicy::new()
+                .with_role(Role::new("hsm-tooling").allow("*", KmsAction::ALL))
+                .assign("kmip-gateway", "hsm-tooling"),
+        );
+        kms.set_approval_policy(approval);
+        let kms = Arc::new(Mutex::new(kms));
+
+        let pki = TestPki::new();
+        let (identity, certificate) = pki.client("kmip-gateway");
+        let server = KmipServer::new(kms.clone(), &pki.server, &pki.ca_pem)
+            .unwrap()
+            .with_client(&certificate, CallerContext::new("kmip-gateway"))
+            .unwrap();
+        let address = listen(server).await;
+        let client = KmipClient::connect(&address, client_config(&pki.ca_pem, Some(&identity)).unwrap()).await.unwrap();
+        (kms, client)
+    }
+
+    async fn listen(server: KmipServer) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(Arc::new(server).serve(listener));
+        address
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_clients_refused() {
+        let pki = TestPki::new();
+        let (registered, certificate) = pki.client("kmip-gateway");
+        let (stranger, _) = pki.client("stranger");
+        let server = KmipServer::new(Arc::new(Mutex::new(KeyManagementSystem::new())), &pki.server, &pki.ca_pem)
+            .unwrap()
+            .with_client(&certificate, CallerContext::new("kmip-gateway"))
+            .unwrap();
+        let address = listen(server).await;
+
+        // TLS 1.3 client authentication completes after the client's handshake,
+        // so refusal shows on the first request
+        for identity in [None, Some(&stranger)] {
+            let refused = match KmipClient::connect(&address, client_config(&pki.ca_pem, identity).unwrap()).await {
+                Ok(mut client) => client.locate(&Attributes::default(), None).await.map(|_| ()),
+                Err(e) => Err(e),
+            };
+            assert!(matches!(refused, Err(KmipError::Connection(_))));
+        }
+        let mut client =
+            KmipClient::connect(&address, client_config(&pki.ca_pem, Some(&registered)).unwrap()).await.unwrap();
+        assert!(client.locate(&Attributes::default(), None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_symmetric_key_lifecycle() {
+        let (_, mut client) = start_server(ApprovalPolicy::default().exempt("kmip-gateway")).await;
+
+        let id = client.create_symmetric_key("payments-dek", 256).await.unwrap();
+        assert_eq!(id, "payments-dek");
+        let attributes = client.get_attributes(&id).await.unwrap();
+        assert_eq!(attributes.state, Some(KeyState::PreActivation));
+        assert_eq!(attributes.cryptographic_length, Some(256));
+
+        client.activate(&id).await.unwrap();
+        let key = client.get(&id).await.unwrap();
+        assert_eq!(key.object_type, ObjectType::SymmetricKey);
+        assert_eq!(key.cryptographic_algorithm, Some(AES_ALGORITHM));
+        assert_eq!(key.key_material.len(), 32);
+
+        // Active keys must be revoked before they can be destroyed
+        let err = client.destroy(&id).await.unwrap_err();
+        assert!(err.to_string().contains("ILLEGAL_OPERATION"));
+        client.revoke(&id, TransitionReason::CessationOfOperation, Some("retired")).await.unwrap();
+        client.destroy(&id).await.unwrap();
+        assert_eq!(client.get_attributes(&id).await.unwrap().state, Some(KeyState::Destroyed));
+
+        let err = client.create_symmetric_key("short", 100).await.unwrap_err();
+        assert!(err.to_string().contains("INVALID_FIELD"));
+        assert!(client.get("missing").await.unwrap_err().to_string().contains("ITEM_NOT_FOUND"));
+    }
+
+    #[tokio::test]
+    async fn test_kyber_key_pair_and_locate() {
+        let (kms, mut client) = start_server(ApprovalPolicy::default()).await;
+
+        let (private_id, public_id) = client.create_key_pair("inbound").await.unwrap();
+        assert_eq!(public_id, format!("{}{}", private_id, PUBLIC_KEY_SUFFIX));
+        client.create_symmetric_key("payments-dek", 128).await.unwrap();
+        client.activate(&public_id).await.unwrap();
+
+        let public_key = client.get(&public_id).await.unwrap();
+        assert_eq!(public_key.object_type, ObjectType::PublicKey);
+        let gateway = CallerContext::new("kmip-gateway");
+        assert_eq!(*public_key.key_material, kms.lock().unwrap().kem_public_key(&gateway, "inbound").unwrap());
+
+        let public_keys = Attributes { object_type: Some(ObjectType::PublicKey), ..Attributes::default() };
+        assert_eq!(client.locate(&public_keys, None).await.unwrap(), vec![public_id.clone()]);
+        let active = Attributes { state: Some(KeyState::Active), ..Attributes::default() };
+        assert_eq!(client.locate(&active, None).await.unwrap(), vec![private_id.clone(), public_id]);
+        assert_eq!(client.locate(&Attributes::default(), Some(1)).await.unwrap().len(), 1);
+
+        // Without an exemption the gateway is a single operator: no export, no compromise
+        let err = client.get(&private_id).await.unwrap_err();
+        assert!(err.to_string().contains("ILLEGAL_OPERATION: Approval required"));
+        assert!(client.revoke(&private_id, TransitionReason::KeyCompromise, None).await.is_err());
+        assert_eq!(client.get_attributes(&private_id).await.unwrap().state, Some(KeyState::Active));
+    }
+}
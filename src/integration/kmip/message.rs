@@ -0,0 +1,433 @@
+//! KMIP 2.0 messages, enumerations and attributes used by the server and client.
+
+use super::ttlv::{Tag, Ttlv, Value};
+use crate::crypto::kms::{KeyState, TransitionReason};
+use crate::error::{Error, ErrorCategory, KmipError};
+use zeroize::Zeroizing;
+
+pub const PROTOCOL_VERSION_MAJOR: i32 = 2;
+pub const PROTOCOL_VERSION_MINOR: i32 = 0;
+pub const AES_ALGORITHM: u32 = 0x03;
+/// Vendor extension value for Kyber-1024; KMIP 2.x has no standard one
+pub const KYBER1024_ALGORITHM: u32 = 0x8000_0001;
+/// Appended to a Kyber key pair's id to name its public half
+pub const PUBLIC_KEY_SUFFIX: &str = "/public";
+const KEY_FORMAT_RAW: u32 = 0x01;
+const NAME_TYPE_TEXT: u32 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Create,
+    CreateKeyPair,
+    Locate,
+    Get,
+    GetAttributes,
+    Activate,
+    Revoke,
+    Destroy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectType {
+    SymmetricKey,
+    PublicKey,
+    PrivateKey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultStatus {
+    Success,
+    OperationFailed,
+}
+
+/// Why an operation failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultReason {
+    ItemNotFound,
+    InvalidMessage,
+    OperationNotSupported,
+    InvalidField,
+    IllegalOperation,
+    PermissionDenied,
+    ObjectAlreadyExists,
+    GeneralFailure,
+}
+
+/// Attributes the server reports and Locate filters on
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attributes {
+    pub object_type: Option<ObjectType>,
+    pub cryptographic_algorithm: Option<u32>,
+    /// In bits
+    pub cryptographic_length: Option<i32>,
+    pub name: Option<String>,
+    pub state: Option<KeyState>,
+    pub initial_date: Option<i64>,
+}
+
+/// Raw key material returned by Get
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBlock {
+    pub object_type: ObjectType,
+    pub cryptographic_algorithm: Option<u32>,
+    pub cryptographic_length: Option<i32>,
+    pub key_material: Zeroizing<Vec<u8>>,
+}
+
+/// One operation of a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestItem {
+    /// Raw code, so that unsupported operations can still be answered
+    pub operation: u32,
+    pub payload: Ttlv,
+}
+
+/// Result of one operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseItem {
+    pub operation: u32,
+    pub status: ResultStatus,
+    pub reason: Option<ResultReason>,
+    pub message: Option<String>,
+    pub payload: Option<Ttlv>,
+}
+
+impl Operation {
+    pub fn code(&self) -> u32 {
+        match self {
+            Operation::Create => 0x01,
+            Operation::CreateKeyPair => 0x02,
+            Operation::Locate => 0x08,
+            Operation::Get => 0x0A,
+            Operation::GetAttributes => 0x0B,
+            Operation::Activate => 0x12,
+            Operation::Revoke => 0x13,
+            Operation::Destroy => 0x14,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        [
+            Operation::Create,
+            Operation::CreateKeyPair,
+            Operation::Locate,
+            Operation::Get,
+            Operation::GetAttributes,
+            Operation::Activate,
+            Operation::Revoke,
+            Operation::Destroy,
+        ]
+        .into_iter()
+        .find(|operation| operation.code() == code)
+    }
+}
+
+impl ObjectType {
+    pub fn code(&self) -> u32 {
+        match self {
+            ObjectType::SymmetricKey => 0x02,
+            ObjectType::PublicKey => 0x03,
+            ObjectType::PrivateKey => 0x04,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Result<Self, KmipError> {
+        match code {
+            0x02 => Ok(ObjectType::SymmetricKey),
+            0x03 => Ok(ObjectType::PublicKey),
+            0x04 => Ok(ObjectType::PrivateKey),
+            other => Err(KmipError::UnsupportedOperation(format!("object type {:#x}", other))),
+        }
+    }
+
+    fn tag(&self) -> Tag {
+        match self {
+            ObjectType::SymmetricKey => Tag::SYMMETRIC_KEY,
+            ObjectType::PublicKey => Tag::PUBLIC_KEY,
+            ObjectType::PrivateKey => Tag::PRIVATE_KEY,
+        }
+    }
+}
+
+impl ResultReason {
+    pub fn code(&self) -> u32 {
+        match self {
+            ResultReason::ItemNotFound => 0x01,
+            ResultReason::InvalidMessage => 0x04,
+            ResultReason::OperationNotSupported => 0x05,
+            ResultReason::InvalidField => 0x07,
+            ResultReason::IllegalOperation => 0x0B,
+            ResultReason::PermissionDenied => 0x0C,
+            ResultReason::ObjectAlreadyExists => 0x18,
+            ResultReason::GeneralFailure => 0x100,
+        }
+    }
+
+    /// Unknown reasons read as GeneralFailure
+    pub fn from_code(code: u32) -> Self {
+        [
+            ResultReason::ItemNotFound,
+            ResultReason::InvalidMessage,
+            ResultReason::OperationNotSupported,
+            ResultReason::InvalidField,
+            ResultReason::IllegalOperation,
+            ResultReason::PermissionDenied,
+            ResultReason::ObjectAlreadyExists,
+        ]
+        .into_iter()
+        .find(|reason| reason.code() == code)
+        .unwrap_or(ResultReason::GeneralFailure)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResultReason::ItemNotFound => "ITEM_NOT_FOUND",
+            ResultReason::InvalidMessage => "INVALID_MESSAGE",
+            ResultReason::OperationNotSupported => "OPERATION_NOT_SUPPORTED",
+            ResultReason::InvalidField => "INVALID_FIELD",
+            ResultReason::IllegalOperation => "ILLEGAL_OPERATION",
+            ResultReason::PermissionDenied => "PERMISSION_DENIED",
+            ResultReason::ObjectAlreadyExists => "OBJECT_ALREADY_EXISTS",
+            ResultReason::GeneralFailure => "GENERAL_FAILURE",
+        }
+    }
+
+    /// Reason reported for a failed KMS call
+    pub fn from_error(err: &Error) -> Self {
+        match err {
+            Error::Kmip(KmipError::Malformed(_)) => return ResultReason::InvalidMessage,
+            Error::Kmip(KmipError::UnsupportedOperation(_)) => return ResultReason::OperationNotSupported,
+            _ => {}
+        }
+        match err.code().category {
+            ErrorCategory::NotFound => ResultReason::ItemNotFound,
+            ErrorCategory::AlreadyExists => ResultReason::ObjectAlreadyExists,
+            ErrorCategory::PermissionDenied | ErrorCategory::Unauthenticated => ResultReason::PermissionDenied,
+            ErrorCategory::FailedPrecondition => ResultReason::IllegalOperation,
+            ErrorCategory::InvalidInput => ResultReason::InvalidField,
+            _ => ResultReason::GeneralFailure,
+        }
+    }
+}
+
+/// KMIP State enumeration; Suspended has no KMIP counterpart and reads as Deactivated
+pub fn state_code(state: KeyState) -> u32 {
+    match state {
+        KeyState::PreActivation => 0x01,
+        KeyState::Active => 0x02,
+        KeyState::Suspended | KeyState::Deactivated => 0x03,
+        KeyState::Compromised => 0x04,
+        KeyState::Destroyed => 0x05,
+    }
+}
+
+pub fn state_from_code(code: u32) -> Result<KeyState, KmipError> {
+    match code {
+        0x01 => Ok(KeyState::PreActivation),
+        0x02 => Ok(KeyState::Active),
+        0x03 => Ok(KeyState::Deactivated),
+        0x04 => Ok(KeyState::Compromised),
+        0x05 | 0x06 => Ok(KeyState::Destroyed),
+        other => Err(KmipError::Malformed(format!("state {:#x}", other))),
+    }
+}
+
+/// KMIP Revocation Reason Code; CA compromise counts as key compromise
+pub fn revocation_reason_code(reason: TransitionReason) -> u32 {
+    match reason {
+        TransitionReason::KeyCompromise => 0x02,
+        TransitionReason::AffiliationChanged => 0x04,
+        TransitionReason::Superseded => 0x05,
+        TransitionReason::CessationOfOperation => 0x06,
+        TransitionReason::PrivilegeWithdrawn => 0x07,
+        _ => 0x01,
+    }
+}
+
+pub fn revocation_reason_from_code(code: u32) -> TransitionReason {
+    match code {
+        0x02 | 0x03 => TransitionReason::KeyCompromise,
+        0x04 => TransitionReason::AffiliationChanged,
+        0x05 => TransitionReason::Superseded,
+        0x06 => TransitionReason::CessationOfOperation,
+        0x07 => TransitionReason::PrivilegeWithdrawn,
+        _ => TransitionReason::Unspecified,
+    }
+}
+
+impl Attributes {
+    pub fn to_ttlv(&self, tag: Tag) -> Ttlv {
+        let mut items = Vec::new();
+        if let Some(object_type) = self.object_type {
+            items.push(Ttlv::enumeration(Tag::OBJECT_TYPE, object_type.code()));
+        }
+        if let Some(algorithm) = self.cryptographic_algorithm {
+            items.push(Ttlv::enumeration(Tag::CRYPTOGRAPHIC_ALGORITHM, algorithm));
+        }
+        if let Some(length) = self.cryptographic_length {
+            items.push(Ttlv::integer(Tag::CRYPTOGRAPHIC_LENGTH, length));
+        }
+        if let Some(name) = &self.name {
+            items.push(Ttlv::structure(Tag::NAME, vec![
+                Ttlv::text(Tag::NAME_VALUE, name),
+                Ttlv::enumeration(Tag::NAME_TYPE, NAME_TYPE_TEXT),
+            ]));
+        }
+        if let Some(state) = self.state {
+            items.push(Ttlv::enumeration(Tag::STATE, state_code(state)));
+        }
+        if let Some(initial_date) = self.initial_date {
+            items.push(Ttlv::new(Tag::INITIAL_DATE, Value::DateTime(initial_date)));
+        }
+        Ttlv::structure(tag, items)
+    }
+
+    /// Reads the attributes this crate knows about and skips the rest
+    pub fn from_ttlv(ttlv: &Ttlv) -> Result<Self, KmipError> {
+        Ok(Self {
+            object_type: ttlv.find(Tag::OBJECT_TYPE).map(|t| ObjectType::from_code(t.as_enumeration()?)).transpose()?,
+            cryptographic_algorithm: ttlv.find(Tag::CRYPTOGRAPHIC_ALGORITHM).map(Ttlv::as_enumeration).transpose()?,
+            cryptographic_length: ttlv.find(Tag::CRYPTOGRAPHIC_LENGTH).map(Ttlv::as_integer).transpose()?,
+            name: ttlv
+                .find(Tag::NAME)
+                .map(|name| name.require(Tag::NAME_VALUE)?.as_text().map(str::to_string))
+                .transpose()?,
+            state: ttlv.find(Tag::STATE).map(|t| state_from_code(t.as_enumeration()?)).transpose()?,
+            initial_date: ttlv.find(Tag::INITIAL_DATE).map(Ttlv::as_date_time).transpose()?,
+        })
+    }
+
+    /// True if every attribute set in `filter` has the same value here
+    pub fn matches(&self, filter: &Attributes) -> bool {
+        fn same<T: PartialEq>(filter: &Option<T>, value: &Option<T>) -> bool {
+            filter.is_none() || filter == value
+        }
+        same(&filter.object_type, &self.object_type)
+            && same(&filter.cryptographic_algorithm, &self.cryptographic_algorithm)
+            && same(&filter.cryptographic_length, &self.cryptographic_length)
+            && same(&filter.name, &self.name)
+            && same(&filter.state, &self.state)
+            && same(&filter.initial_date, &self.initial_date)
+    }
+}
+
+impl KeyBlock {
+    /// The managed object, e.g. a Symmetric Key structure wrapping the key block
+    pub fn to_ttlv(&self) -> Ttlv {
+        let mut block = vec![
+            Ttlv::enumeration(Tag::KEY_FORMAT_TYPE, KEY_FORMAT_RAW),
+            Ttlv::structure(Tag::KEY_VALUE, vec![Ttlv::bytes(Tag::KEY_MATERIAL, &self.key_material)]),
+        ];
+        if let Some(algorithm) = self.cryptographic_algorithm {
+            block.push(Ttlv::enumeration(Tag::CRYPTOGRAPHIC_ALGORITHM, algorithm));
+        }
+        if let Some(length) = self.cryptographic_length {
+            block.push(Ttlv::integer(Tag::CRYPTOGRAPHIC_LENGTH, length));
+        }
+        Ttlv::structure(self.object_type.tag(), vec![Ttlv::structure(Tag::KEY_BLOCK, block)])
+    }
+
+    /// Reads the managed object out of a Get response payload
+    pub fn from_payload(payload: &Ttlv) -> Result<Self, KmipError> {
+        let object_type = ObjectType::from_code(payload.require(Tag::OBJECT_TYPE)?.as_enumeration()?)?;
+        let block = payload.require(object_type.tag())?.require(Tag::KEY_BLOCK)?;
+        if block.require(Tag::KEY_FORMAT_TYPE)?.as_enumeration()? != KEY_FORMAT_RAW {
+            return Err(KmipError::UnsupportedOperation("key format other than Raw".to_string()));
+        }
+        Ok(Self {
+            object_type,
+            cryptographic_algorithm: block.find(Tag::CRYPTOGRAPHIC_ALGORITHM).map(Ttlv::as_enumeration).transpose()?,
+            cryptographic_length: block.find(Tag::CRYPTOGRAPHIC_LENGTH).map(Ttlv::as_integer).transpose()?,
+            key_material: Zeroizing::new(block.require(Tag::KEY_VALUE)?.require(Tag::KEY_MATERIAL)?.as_bytes()?.to_vec()),
+        })
+    }
+}
+
+fn protocol_version() -> Ttlv {
+    Ttlv::structure(Tag::PROTOCOL_VERSION, vec![
+        Ttlv::integer(Tag::PROTOCOL_VERSION_MAJOR, PROTOCOL_VERSION_MAJOR),
+        Ttlv::integer(Tag::PROTOCOL_VERSION_MINOR, PROTOCOL_VERSION_MINOR),
+    ])
+}
+
+pub fn request_message(items: &[RequestItem]) -> Ttlv {
+    let mut message = vec![Ttlv::structure(Tag::REQUEST_HEADER, vec![
+        protocol_version(),
+        Ttlv::integer(Tag::BATCH_COUNT, items.len() as i32),
+    ])];
+    message.extend(items.iter().map(|item| {
+        Ttlv::structure(Tag::BATCH_ITEM, vec![
+            Ttlv::enumeration(Tag::OPERATION, item.operation),
+            item.payload.clone(),
+        ])
+    }));
+    Ttlv::structure(Tag::REQUEST_MESSAGE, message)
+}
+
+pub fn parse_request(message: &Ttlv) -> Result<Vec<RequestItem>, KmipError> {
+    if message.tag != Tag::REQUEST_MESSAGE {
+        return Err(KmipError::Malformed(format!("expected a request message, got {:06X}", message.tag.0)));
+    }
+    let version = message.require(Tag::REQUEST_HEADER)?.require(Tag::PROTOCOL_VERSION)?;
+    let major = version.require(Tag::PROTOCOL_VERSION_MAJOR)?.as_integer()?;
+    if major != PROTOCOL_VERSION_MAJOR {
+        return Err(KmipError::UnsupportedOperation(format!("protocol version {}", major)));
+    }
+    message
+        .find_all(Tag::BATCH_ITEM)
+        .map(|item| {
+            Ok(RequestItem {
+                operation: item.require(Tag::OPERATION)?.as_enumeration()?,
+                payload: item
+                    .find(Tag::REQUEST_PAYLOAD)
+                    .cloned()
+                    .unwrap_or_else(|| Ttlv::structure(Tag::REQUEST_PAYLOAD, Vec::new())),
+            })
+        })
+        .collect()
+}
+
+pub fn response_message(items: &[ResponseItem], timestamp: i64) -> Ttlv {
+    let mut message = vec![Ttlv::structure(Tag::RESPONSE_HEADER, vec![
+        protocol_version(),
+        Ttlv::new(Tag::TIME_STAMP, Value::DateTime(timestamp)),
+        Ttlv::integer(Tag::BATCH_COUNT, items.len() as i32),
+    ])];
+    message.extend(items.iter().map(|item| {
+        let status = match item.status {
+            ResultStatus::Success => 0x00,
+            ResultStatus::OperationFailed => 0x01,
+        };
+        let mut fields = vec![
+            Ttlv::enumeration(Tag::OPERATION, item.operation),
+            Ttlv::enumeration(Tag::RESULT_STATUS, status),
+        ];
+        fields.extend(item.reason.map(|reason| Ttlv::enumeration(Tag::RESULT_REASON, reason.code())));
+        fields.extend(item.message.as_deref().map(|message| Ttlv::text(Tag::RESULT_MESSAGE, message)));
+        fields.extend(item.payload.clone());
+        Ttlv::structure(Tag::BATCH_ITEM, fields)
+    }));
+    Ttlv::structure(Tag::RESPONSE_MESSAGE, message)
+}
+
+pub fn parse_response(message: &Ttlv) -> Result<Vec<ResponseItem>, KmipError> {
+    if message.tag != Tag::RESPONSE_MESSAGE {
+        return Err(KmipError::Malformed(format!("expected a response message, got {:06X}", message.tag.0)));
+    }
+    message
+        .find_all(Tag::BATCH_ITEM)
+        .map(|item| {
+            let status = match item.require(Tag::RESULT_STATUS)?.as_enumeration()? {
+                0x00 => ResultStatus::Success,
+                _ => ResultStatus::OperationFailed,
+            };
+            Ok(ResponseItem {
+                operation: item.require(Tag::OPERATION)?.as_enumeration()?,
+                status,
+                reason: item.find(Tag::RESULT_REASON).map(|r| r.as_enumeration().map(ResultReason::from_code)).transpose()?,
+                message: item.find(Tag::RESULT_MESSAGE).map(|m| m.as_text().map(str::to_string)).transpose()?,
+                payload: item.find(Tag::RESPONSE_PAYLOAD).cloned(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,17 @@
+//! KMIP 2.0 subset for the key store.
+//!
+//! Existing HSM tooling can manage KMS keys over KMIP's TTLV encoding: the
+//! server implements Create (AES keys), CreateKeyPair (Kyber-1024), Get,
+//! Locate, Activate, Revoke, Destroy and GetAttributes on top of
+//! [`KeyManagementSystem`](crate::crypto::kms::KeyManagementSystem), and the
+//! client speaks the same subset.
+
+pub mod client;
+pub mod message;
+pub mod server;
+pub mod ttlv;
+
+pub use client::KmipClient;
+pub use message::{Attributes, KeyBlock, ObjectType, Operation, ResultReason, PUBLIC_KEY_SUFFIX};
+pub use server::KmipServer;
+pub use ttlv::{Tag, Ttlv, Value};
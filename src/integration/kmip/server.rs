@@ -0,0 +1,346 @@
+//! KMIP server backed by [`KeyManagementSystem`].
+//!
+//! Clients connect over mutual TLS: a client must present a certificate
+//! issued by the configured client CA *and* registered with
+//! [`KmipServer::with_client`], which names the KMS principal its requests
+//! run as. A valid certificate that was never registered is refused. All
+//! calls therefore go through that principal's access policy, four-eyes
+//! approval and audit trail like any other KMS caller.
+//!
+//! A Kyber key pair is one KMS key; its private key has the KMS key id as
+//! unique identifier and its public key the same id with
+//! [`PUBLIC_KEY_SUFFIX`]. Symmetric keys are AES keys.
+
+use super::message::{
+    self, parse_request, response_message, Attributes, KeyBlock, ObjectType, Operation, RequestItem, ResponseItem,
+    ResultReason, ResultStatus, AES_ALGORITHM, KYBER1024_ALGORITHM, PUBLIC_KEY_SUFFIX,
+};
+use super::ttlv::{io_error, Tag, Ttlv};
+use crate::crypto::kms::{CallerContext, KeyManagementSystem, KeyOperation, KeyState, TransitionReason, KYBER1024_ALGORITHM as KMS_KYBER1024};
+use crate::crypto::secure::SecureSecret;
+use crate::error::{self, KmipError, KmsError, TlsError};
+use crate::utils::tls::{self, TlsIdentity};
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use zeroize::Zeroizing;
+
+const KMS_AES_ALGORITHM: &str = "AES";
+const DEFAULT_AES_LENGTH_BITS: i32 = 256;
+const GENERATED_ID_LENGTH: usize = 16;
+
+pub struct KmipServer {
+    kms: Arc<Mutex<KeyManagementSystem>>,
+    acceptor: TlsAcceptor,
+    /// Principal per registered client certificate, by certificate fingerprint
+    clients: HashMap<[u8; 32], CallerContext>,
+}
+
+impl KmipServer {
+    /// Server on `kms` presenting `identity`, accepting clients whose
+    /// certificate was issued by a CA in `client_ca_pem`; no client is
+    /// served until registered with [`with_client`](Self::with_client)
+    pub fn new(
+        kms: Arc<Mutex<KeyManagementSystem>>,
+        identity: &TlsIdentity,
+        client_ca_pem: &[u8],
+    ) -> Result<Self, TlsError> {
+        let acceptor = TlsAcceptor::from(tls::server_config(identity, Some(client_ca_pem))?);
+        Ok(Self { kms, acceptor, clients: HashMap::new() })
+    }
+
+    /// Serves the client presenting `certificate_pem` as `caller`
+    pub fn with_client(mut self, certificate_pem: &[u8], caller: CallerContext) -> Result<Self, TlsError> {
+        self.clients.insert(tls::certificate_fingerprint(certificate_pem)?, caller);
+        Ok(self)
+    }
+
+    /// Accepts connections until the listener fails; each runs on its own task
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<(), KmipError> {
+        loop {
+            let (stream, peer) = listener.accept().await.map_err(io_error("accept connection"))?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.serve_connection(stream, &peer.to_string()).await {
+                    tracing::warn!(%peer, error = %e, "KMIP connection closed");
+                }
+            });
+        }
+    }
+
+    async fn serve_connection(&self, stream: TcpStream, peer: &str) -> error::Result<()> {
+        let mut stream = self.acceptor.accept(stream).await.map_err(|e| tls::handshake_error(peer, e))?;
+        let caller = stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certificates| certificates.first())
+            .and_then(|leaf| self.clients.get(&tls::fingerprint(leaf)))
+            .cloned()
+            .ok_or_else(|| TlsError::CertificateRejected(format!("{}: client certificate is not registered", peer)))?;
+
+        while let Some(request) = Ttlv::read_message(&mut stream).await? {
+            let response = self.handle(&caller, &request);
+            stream.write_all(&response.encode()).await.map_err(io_error("write response"))?;
+        }
+        stream.shutdown().await.map_err(io_error("close connection"))?;
+        Ok(())
+    }
+
+    /// Answers one request message from `caller`. Batch items are processed
+    /// in order and fail independently; a message that cannot be parsed gets
+    /// a single failed item back.
+    pub fn handle(&self, caller: &CallerContext, request: &Ttlv) -> Ttlv {
+        let responses = match parse_request(request) {
+            Ok(items) => items.iter().map(|item| self.handle_item(caller, item)).collect(),
+            Err(e) => vec![failure(0, &e.into())],
+        };
+        response_message(&responses, now())
+    }
+
+    fn handle_item(&self, caller: &CallerContext, item: &RequestItem) -> ResponseItem {
+        let result = match Operation::from_code(item.operation) {
+            Some(operation) => self.dispatch(caller, operation, &item.payload),
+            None => Err(KmipError::UnsupportedOperation(format!("operation {:#x}", item.operation)).into()),
+        };
+        match result {
+            Ok(payload) => ResponseItem {
+                operation: item.operation,
+                status: ResultStatus::Success,
+                reason: None,
+                message: None,
+                payload: Some(Ttlv::structure(Tag::RESPONSE_PAYLOAD, payload)),
+            },
+            Err(e) => failure(item.operation, &e),
+        }
+    }
+
+    fn dispatch(&self, caller: &CallerContext, operation: Operation, payload: &Ttlv) -> error::Result<Vec<Ttlv>> {
+        let mut kms = self.kms.lock().map_err(|_| KmsError::Storage("KMS lock poisoned".to_string()))?;
+        match operation {
+            Operation::Create => self.create(caller, &mut kms, payload),
+            Operation::CreateKeyPair => self.create_key_pair(caller, &mut kms, payload),
+            Operation::Locate => self.locate(caller, &kms, payload),
+            Operation::Get => self.get(caller, &kms, payload),
+            Operation::GetAttributes => {
+                let unique_identifier = unique_identifier(payload)?;
+                let attributes = self.attributes(caller, &kms, unique_identifier)?;
+                Ok(vec![
+                    Ttlv::text(Tag::UNIQUE_IDENTIFIER, unique_identifier),
+                    attributes.to_ttlv(Tag::ATTRIBUTES),
+                ])
+            }
+            Operation::Activate => {
+                self.transition(caller, &mut kms, payload, KeyState::Active, TransitionReason::Activated)
+            }
+            Operation::Revoke => {
+                let reason = payload.require(Tag::REVOCATION_REASON)?.require(Tag::REVOCATION_REASON_CODE)?;
+                let reason = message::revocation_reason_from_code(reason.as_enumeration()?);
+                let next = if reason == TransitionReason::KeyCompromise { KeyState::Compromised } else { KeyState::Deactivated };
+                self.transition(caller, &mut kms, payload, next, reason)
+            }
+            Operation::Destroy => {
+                self.transition(caller, &mut kms, payload, KeyState::Destroyed, TransitionReason::Unspecified)
+            }
+        }
+    }
+
+    fn create(&self, caller: &CallerContext, kms: &mut KeyManagementSystem, payload: &Ttlv) -> error::Result<Vec<Ttlv>> {
+        let object_type = ObjectType::from_code(payload.require(Tag::OBJECT_TYPE)?.as_enumeration()?)?;
+        if object_type != ObjectType::SymmetricKey {
+            return Err(KmipError::UnsupportedOperation("Create of objects other than symmetric keys".to_string()).into());
+        }
+        let attributes = Attributes::from_ttlv(payload.require(Tag::ATTRIBUTES)?)?;
+        if attributes.cryptographic_algorithm.is_some_and(|algorithm| algorithm != AES_ALGORITHM) {
+            return Err(KmipError::UnsupportedOperation("symmetric keys other than AES".to_string()).into());
+        }
+        let length = attributes.cryptographic_length.unwrap_or(DEFAULT_AES_LENGTH_BITS);
+        if ![128, 192, 256].contains(&length) {
+            return Err(KmipError::InvalidField(format!("AES key length {}", length)).into());
+        }
+
+        let key_id = attributes.name.unwrap_or_else(generated_id);
+        let mut material = Zeroizing::new(vec![0u8; length as usize / 8]);
+        rand::thread_rng().fill_bytes(&mut material);
+        kms.add_pending_key(caller, &key_id, SecureSecret::from_bytes(&material), KMS_AES_ALGORITHM)?;
+        Ok(vec![
+            Ttlv::enumeration(Tag::OBJECT_TYPE, object_type.code()),
+            Ttlv::text(Tag::UNIQUE_IDENTIFIER, &key_id),
+        ])
+    }
+
+    fn create_key_pair(&self, caller: &CallerContext, kms: &mut KeyManagementSystem, payload: &Ttlv) -> error::Result<Vec<Ttlv>> {
+        let attributes = match payload.find(Tag::COMMON_ATTRIBUTES) {
+            Some(common) => Attributes::from_ttlv(common)?,
+            None => Attributes::default(),
+        };
+        if attributes.cryptographic_algorithm.is_some_and(|algorithm| algorithm != KYBER1024_ALGORITHM) {
+            return Err(KmipError::UnsupportedOperation("key pairs other than Kyber-1024".to_string()).into());
+        }
+        let key_id = attributes.name.unwrap_or_else(generated_id);
+        kms.generate_pending_kem_key(caller, &key_id)?;
+        Ok(vec![
+            Ttlv::text(Tag::PRIVATE_KEY_UNIQUE_IDENTIFIER, &key_id),
+            Ttlv::text(Tag::PUBLIC_KEY_UNIQUE_IDENTIFIER, &format!("{}{}", key_id, PUBLIC_KEY_SUFFIX)),
+        ])
+    }
+
+    fn locate(&self, caller: &CallerContext, kms: &KeyManagementSystem, payload: &Ttlv) -> error::Result<Vec<Ttlv>> {
+        let filter = match payload.find(Tag::ATTRIBUTES) {
+            Some(attributes) => Attributes::from_ttlv(attributes)?,
+            None => Attributes::default(),
+        };
+        let maximum = match payload.find(Tag::MAXIMUM_ITEMS) {
+            Some(maximum) => usize::try_from(maximum.as_integer()?).unwrap_or_default(),
+            None => usize::MAX,
+        };
+
+        let mut key_ids = kms.key_ids(caller)?;
+        key_ids.sort();
+        let mut found = Vec::new();
+        for key_id in key_ids {
+            let mut unique_identifiers = vec![key_id.clone()];
+            if kms.key_metadata(caller, &key_id)?.algorithm == KMS_KYBER1024 {
+                unique_identifiers.push(format!("{}{}", key_id, PUBLIC_KEY_SUFFIX));
+            }
+            for unique_identifier in unique_identifiers {
+                if self.attributes(caller, kms, &unique_identifier)?.matches(&filter) {
+                    found.push(Ttlv::text(Tag::UNIQUE_IDENTIFIER, &unique_identifier));
+                }
+            }
+        }
+        found.truncate(maximum);
+        Ok(found)
+    }
+
+    fn get(&self, caller: &CallerContext, kms: &KeyManagementSystem, payload: &Ttlv) -> error::Result<Vec<Ttlv>> {
+        let unique_identifier = unique_identifier(payload)?;
+        let attributes = self.attributes(caller, kms, unique_identifier)?;
+        let object_type = attributes.object_type.unwrap_or(ObjectType::SymmetricKey);
+        let key_material = match unique_identifier.strip_suffix(PUBLIC_KEY_SUFFIX) {
+            Some(key_id) => Zeroizing::new(kms.kem_public_key(caller, key_id)?),
+            None => kms
+                .get_secret(caller, unique_identifier, KeyOperation::Export)?
+                .ok_or_else(|| KmsError::KeyNotFound(unique_identifier.to_string()))?
+                .with_exposed(|material| Zeroizing::new(material.to_vec()))?,
+        };
+        let block = KeyBlock {
+            object_type,
+            cryptographic_algorithm: attributes.cryptographic_algorithm,
+            cryptographic_length: attributes.cryptographic_length,
+            key_material,
+        };
+        Ok(vec![
+            Ttlv::enumeration(Tag::OBJECT_TYPE, object_type.code()),
+            Ttlv::text(Tag::UNIQUE_IDENTIFIER, unique_identifier),
+            block.to_ttlv(),
+        ])
+    }
+
+    // Activate, Revoke and Destroy; a public key follows its private key
+    fn transition(
+        &self,
+        caller: &CallerContext,
+        kms: &mut KeyManagementSystem,
+        payload: &Ttlv,
+        next: KeyState,
+        reason: TransitionReason,
+    ) -> error::Result<Vec<Ttlv>> {
+        let unique_identifier = unique_identifier(payload)?;
+        let key_id = unique_identifier.strip_suffix(PUBLIC_KEY_SUFFIX).unwrap_or(unique_identifier);
+        kms.transition_key(caller, key_id, next, reason)?;
+        Ok(vec![Ttlv::text(Tag::UNIQUE_IDENTIFIER, unique_identifier)])
+    }
+
+    fn attributes(&self, caller: &CallerContext, kms: &KeyManagementSystem, unique_identifier: &str) -> error::Result<Attributes> {
+        let public_of = unique_identifier.strip_suffix(PUBLIC_KEY_SUFFIX);
+        let metadata = kms.key_metadata(caller, public_of.unwrap_or(unique_identifier))?;
+        let (object_type, algorithm, length) = match (metadata.algorithm.as_str(), public_of) {
+            (KMS_KYBER1024, Some(_)) => (ObjectType::PublicKey, Some(KYBER1024_ALGORITHM), None),
+            (KMS_KYBER1024, None) => (ObjectType::PrivateKey, Some(KYBER1024_ALGORITHM), None),
+            (_, Some(_)) => return Err(KmsError::KeyNotFound(unique_identifier.to_string()).into()),
+            (KMS_AES_ALGORITHM, None) => {
+                // Destroyed keys have no material left to measure
+                let bits = match kms.key_size(caller, unique_identifier)? {
+                    0 => None,
+                    bytes => i32::try_from(bytes * 8).ok(),
+                };
+                (ObjectType::SymmetricKey, Some(AES_ALGORITHM), bits)
+            }
+            (_, None) => (ObjectType::SymmetricKey, None, None),
+        };
+        Ok(Attributes {
+            object_type: Some(object_type),
+            cryptographic_algorithm: algorithm,
+            cryptographic_length: length,
+            name: Some(unique_identifier.to_string()),
+            state: Some(metadata.status),
+            initial_date: i64::try_from(metadata.created_at).ok(),
+        })
+    }
+}
+
+fn unique_identifier(payload: &Ttlv) -> Result<&str, KmipError> {
+    payload.require(Tag::UNIQUE_IDENTIFIER)?.as_text()
+}
+
+fn failure(operation: u32, err: &error::Error) -> ResponseItem {
+    ResponseItem {
+        operation,
+        status: ResultStatus::OperationFailed,
+        reason: Some(ResultReason::from_error(err)),
+        message: Some(err.to_string()),
+        payload: None,
+    }
+}
+
+fn generated_id() -> String {
+    hex::encode(rand::random::<[u8; GENERATED_ID_LENGTH]>())
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::kms::access::test_kms;
+    use crate::utils::tls::TestPki;
+    use message::{parse_response, request_message};
+
+    #[test]
+    fn test_batch_items_fail_independently() {
+        let (kms, admin) = test_kms();
+        let pki = TestPki::new();
+        let server = KmipServer::new(Arc::new(Mutex::new(kms)), &pki.server, &pki.ca_pem).unwrap();
+
+        let items = [
+            RequestItem { operation: 0x1F, payload: Ttlv::structure(Tag::REQUEST_PAYLOAD, Vec::new()) },
+            RequestItem {
+                operation: Operation::CreateKeyPair.code(),
+                payload: Ttlv::structure(Tag::REQUEST_PAYLOAD, Vec::new()),
+            },
+            RequestItem {
+                operation: Operation::Get.code(),
+                payload: Ttlv::structure(Tag::REQUEST_PAYLOAD, vec![Ttlv::text(Tag::UNIQUE_IDENTIFIER, "missing")]),
+            },
+        ];
+        let responses = parse_response(&server.handle(&admin, &request_message(&items))).unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0].reason, Some(ResultReason::OperationNotSupported));
+        assert_eq!(responses[1].status, ResultStatus::Success);
+        assert_eq!(responses[2].reason, Some(ResultReason::ItemNotFound));
+
+        let garbage = Ttlv::structure(Tag::RESPONSE_MESSAGE, Vec::new());
+        let responses = parse_response(&server.handle(&admin, &garbage)).unwrap();
+        assert_eq!(responses[0].reason, Some(ResultReason::InvalidMessage));
+    }
+}
@@ -0,0 +1,403 @@
+//! KMIP Tag-Type-Length-Value encoding.
+//!
+//! ```text
+//! tag (3) | type (1) | u32 length | value, zero-padded to a multiple of 8
+//! ```
+//!
+//! All integers are big-endian. Structures nest further TTLV items in their
+//! value; the length never includes the padding of the item itself.
+//!
+//! Byte and big-integer values, and whole encoded messages, are wiped when
+//! dropped: Get responses carry raw key material.
+
+use crate::error::KmipError;
+use std::io::ErrorKind;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use zeroize::Zeroizing;
+
+const HEADER_LENGTH: usize = 8;
+const ALIGNMENT: usize = 8;
+/// Largest message the codec accepts, so a bad length cannot exhaust memory
+pub const MAX_MESSAGE_LENGTH: usize = 1 << 20;
+/// Deepest structure nesting the codec accepts, so a message of nested empty
+/// structures cannot exhaust the stack; real KMIP messages nest a few levels
+pub const MAX_NESTING_DEPTH: usize = 32;
+
+/// KMIP tag, a 3-byte value in the 0x42xxxx range for standard tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tag(pub u32);
+
+impl Tag {
+    pub const ACTIVATION_DATE: Tag = Tag(0x420001);
+    pub const BATCH_COUNT: Tag = Tag(0x42000D);
+    pub const BATCH_ITEM: Tag = Tag(0x42000F);
+    pub const CRYPTOGRAPHIC_ALGORITHM: Tag = Tag(0x420028);
+    pub const CRYPTOGRAPHIC_LENGTH: Tag = Tag(0x42002A);
+    pub const INITIAL_DATE: Tag = Tag(0x420039);
+    pub const KEY_BLOCK: Tag = Tag(0x420040);
+    pub const KEY_FORMAT_TYPE: Tag = Tag(0x420042);
+    pub const KEY_MATERIAL: Tag = Tag(0x420043);
+    pub const KEY_VALUE: Tag = Tag(0x420045);
+    pub const MAXIMUM_ITEMS: Tag = Tag(0x42004F);
+    pub const NAME: Tag = Tag(0x420053);
+    pub const NAME_TYPE: Tag = Tag(0x420054);
+    pub const NAME_VALUE: Tag = Tag(0x420055);
+    pub const OBJECT_TYPE: Tag = Tag(0x420057);
+    pub const OPERATION: Tag = Tag(0x42005C);
+    pub const PRIVATE_KEY: Tag = Tag(0x420064);
+    pub const PRIVATE_KEY_UNIQUE_IDENTIFIER: Tag = Tag(0x420066);
+    pub const PROTOCOL_VERSION: Tag = Tag(0x420069);
+    pub const PROTOCOL_VERSION_MAJOR: Tag = Tag(0x42006A);
+    pub const PROTOCOL_VERSION_MINOR: Tag = Tag(0x42006B);
+    pub const PUBLIC_KEY: Tag = Tag(0x42006D);
+    pub const PUBLIC_KEY_UNIQUE_IDENTIFIER: Tag = Tag(0x42006F);
+    pub const REQUEST_HEADER: Tag = Tag(0x420077);
+    pub const REQUEST_MESSAGE: Tag = Tag(0x420078);
+    pub const REQUEST_PAYLOAD: Tag = Tag(0x420079);
+    pub const RESPONSE_HEADER: Tag = Tag(0x42007A);
+    pub const RESPONSE_MESSAGE: Tag = Tag(0x42007B);
+    pub const RESPONSE_PAYLOAD: Tag = Tag(0x42007C);
+    pub const RESULT_MESSAGE: Tag = Tag(0x42007D);
+    pub const RESULT_REASON: Tag = Tag(0x42007E);
+    pub const RESULT_STATUS: Tag = Tag(0x42007F);
+    pub const REVOCATION_MESSAGE: Tag = Tag(0x420080);
+    pub const REVOCATION_REASON: Tag = Tag(0x420081);
+    pub const REVOCATION_REASON_CODE: Tag = Tag(0x420082);
+    pub const STATE: Tag = Tag(0x42008D);
+    pub const SYMMETRIC_KEY: Tag = Tag(0x42008F);
+    pub const TIME_STAMP: Tag = Tag(0x420092);
+    pub const UNIQUE_IDENTIFIER: Tag = Tag(0x420094);
+    pub const ATTRIBUTES: Tag = Tag(0x420125);
+    pub const COMMON_ATTRIBUTES: Tag = Tag(0x420126);
+}
+
+/// Typed TTLV value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Structure(Vec<Ttlv>),
+    Integer(i32),
+    LongInteger(i64),
+    /// Two's complement, big-endian, a multiple of 8 bytes long
+    BigInteger(Zeroizing<Vec<u8>>),
+    Enumeration(u32),
+    Boolean(bool),
+    TextString(String),
+    ByteString(Zeroizing<Vec<u8>>),
+    /// Seconds since the Unix epoch
+    DateTime(i64),
+    Interval(u32),
+}
+
+/// One tagged item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ttlv {
+    pub tag: Tag,
+    pub value: Value,
+}
+
+impl Value {
+    fn type_byte(&self) -> u8 {
+        match self {
+            Value::Structure(_) => 0x01,
+            Value::Integer(_) => 0x02,
+            Value::LongInteger(_) => 0x03,
+            Value::BigInteger(_) => 0x04,
+            Value::Enumeration(_) => 0x05,
+            Value::Boolean(_) => 0x06,
+            Value::TextString(_) => 0x07,
+            Value::ByteString(_) => 0x08,
+            Value::DateTime(_) => 0x09,
+            Value::Interval(_) => 0x0A,
+        }
+    }
+}
+
+impl Ttlv {
+    pub fn new(tag: Tag, value: Value) -> Self {
+        Self { tag, value }
+    }
+
+    pub fn structure(tag: Tag, items: Vec<Ttlv>) -> Self {
+        Self::new(tag, Value::Structure(items))
+    }
+
+    pub fn enumeration(tag: Tag, value: u32) -> Self {
+        Self::new(tag, Value::Enumeration(value))
+    }
+
+    pub fn integer(tag: Tag, value: i32) -> Self {
+        Self::new(tag, Value::Integer(value))
+    }
+
+    pub fn text(tag: Tag, value: &str) -> Self {
+        Self::new(tag, Value::TextString(value.to_string()))
+    }
+
+    pub fn bytes(tag: Tag, value: &[u8]) -> Self {
+        Self::new(tag, Value::ByteString(Zeroizing::new(value.to_vec())))
+    }
+
+    pub fn encode(&self) -> Zeroizing<Vec<u8>> {
+        // Sized up front so that growing the buffer leaves no stray copies
+        let mut out = Zeroizing::new(Vec::with_capacity(self.encoded_length()));
+        self.encode_into(&mut out);
+        out
+    }
+
+    fn encoded_length(&self) -> usize {
+        let length = match &self.value {
+            Value::Structure(items) => items.iter().map(Ttlv::encoded_length).sum(),
+            Value::Integer(_) | Value::Enumeration(_) | Value::Interval(_) => 4,
+            Value::LongInteger(_) | Value::DateTime(_) | Value::Boolean(_) => 8,
+            Value::TextString(v) => v.len(),
+            Value::BigInteger(v) | Value::ByteString(v) => v.len(),
+        };
+        HEADER_LENGTH + padded(length)
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.tag.0.to_be_bytes()[1..]);
+        out.push(self.value.type_byte());
+        let length_at = out.len();
+        out.extend_from_slice(&[0u8; 4]);
+        let start = out.len();
+        match &self.value {
+            Value::Structure(items) => items.iter().for_each(|item| item.encode_into(out)),
+            Value::Integer(v) => out.extend_from_slice(&v.to_be_bytes()),
+            Value::LongInteger(v) | Value::DateTime(v) => out.extend_from_slice(&v.to_be_bytes()),
+            Value::Enumeration(v) | Value::Interval(v) => out.extend_from_slice(&v.to_be_bytes()),
+            Value::Boolean(v) => out.extend_from_slice(&u64::from(*v).to_be_bytes()),
+            Value::TextString(v) => out.extend_from_slice(v.as_bytes()),
+            Value::BigInteger(v) | Value::ByteString(v) => out.extend_from_slice(v.as_slice()),
+        }
+        let length = (out.len() - start) as u32;
+        out[length_at..start].copy_from_slice(&length.to_be_bytes());
+        out.resize(start + padded(length as usize), 0);
+    }
+
+    /// Decodes exactly one item spanning all of `bytes`
+    pub fn decode(bytes: &[u8]) -> Result<Self, KmipError> {
+        let (item, used) = Self::decode_prefix(bytes, 0)?;
+        if used != bytes.len() {
+            return Err(KmipError::Malformed(format!("{} trailing bytes", bytes.len() - used)));
+        }
+        Ok(item)
+    }
+
+    // Decodes the item at the start of `bytes`, nested in `depth` structures;
+    // returns it and its padded length
+    fn decode_prefix(bytes: &[u8], depth: usize) -> Result<(Self, usize), KmipError> {
+        let header = bytes
+            .get(..HEADER_LENGTH)
+            .ok_or_else(|| KmipError::Malformed("truncated item header".to_string()))?;
+        let tag = Tag(u32::from_be_bytes([0, header[0], header[1], header[2]]));
+        let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let end = HEADER_LENGTH + padded(length);
+        let body = bytes
+            .get(HEADER_LENGTH..end)
+            .ok_or_else(|| KmipError::Malformed(format!("item {:06X} overruns its message", tag.0)))?;
+        let raw = &body[..length];
+
+        let fixed = |expected: usize| -> Result<&[u8], KmipError> {
+            if length != expected {
+                return Err(KmipError::Malformed(format!(
+                    "item {:06X} has length {}, expected {}",
+                    tag.0, length, expected
+                )));
+            }
+            Ok(raw)
+        };
+        let value = match header[3] {
+            0x01 => {
+                if depth >= MAX_NESTING_DEPTH {
+                    return Err(KmipError::Malformed(format!(
+                        "item {:06X} nests deeper than {} structures",
+                        tag.0, MAX_NESTING_DEPTH
+                    )));
+                }
+                let mut items = Vec::new();
+                let mut offset = 0;
+                while offset < raw.len() {
+                    let (item, used) = Self::decode_prefix(&raw[offset..], depth + 1)?;
+                    items.push(item);
+                    offset += used;
+                }
+                Value::Structure(items)
+            }
+            0x02 => Value::Integer(i32::from_be_bytes(array(fixed(4)?))),
+            0x03 => Value::LongInteger(i64::from_be_bytes(array(fixed(8)?))),
+            0x04 => Value::BigInteger(Zeroizing::new(raw.to_vec())),
+            0x05 => Value::Enumeration(u32::from_be_bytes(array(fixed(4)?))),
+            0x06 => match u64::from_be_bytes(array(fixed(8)?)) {
+                0 => Value::Boolean(false),
+                1 => Value::Boolean(true),
+                other => return Err(KmipError::Malformed(format!("boolean value {}", other))),
+            },
+            0x07 => Value::TextString(
+                String::from_utf8(raw.to_vec()).map_err(|_| KmipError::Malformed(format!("item {:06X} is not UTF-8", tag.0)))?,
+            ),
+            0x08 => Value::ByteString(Zeroizing::new(raw.to_vec())),
+            0x09 => Value::DateTime(i64::from_be_bytes(array(fixed(8)?))),
+            0x0A => Value::Interval(u32::from_be_bytes(array(fixed(4)?))),
+            other => return Err(KmipError::Malformed(format!("unknown item type {:#04x}", other))),
+        };
+        Ok((Self { tag, value }, end))
+    }
+
+    /// Reads one complete message; `None` if the peer closed the stream
+    /// cleanly before sending another one
+    pub async fn read_message(reader: &mut (impl AsyncRead + Unpin)) -> Result<Option<Self>, KmipError> {
+        let mut header = [0u8; HEADER_LENGTH];
+        match reader.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(io_error("read message header")(e)),
+        }
+        let mut message = Zeroizing::new(vec![0u8; HEADER_LENGTH + message_length(&header)?]);
+        message[..HEADER_LENGTH].copy_from_slice(&header);
+        reader.read_exact(&mut message[HEADER_LENGTH..]).await.map_err(io_error("read message body"))?;
+        Self::decode(&message).map(Some)
+    }
+
+    /// Children of a structure; empty for any other value
+    pub fn items(&self) -> &[Ttlv] {
+        match &self.value {
+            Value::Structure(items) => items,
+            _ => &[],
+        }
+    }
+
+    pub fn find(&self, tag: Tag) -> Option<&Ttlv> {
+        self.items().iter().find(|item| item.tag == tag)
+    }
+
+    pub fn find_all(&self, tag: Tag) -> impl Iterator<Item = &Ttlv> {
+        self.items().iter().filter(move |item| item.tag == tag)
+    }
+
+    pub fn require(&self, tag: Tag) -> Result<&Ttlv, KmipError> {
+        self.find(tag)
+            .ok_or_else(|| KmipError::Malformed(format!("{:06X} is missing {:06X}", self.tag.0, tag.0)))
+    }
+
+    pub fn as_enumeration(&self) -> Result<u32, KmipError> {
+        match self.value {
+            Value::Enumeration(v) => Ok(v),
+            _ => Err(self.wrong_type("an enumeration")),
+        }
+    }
+
+    pub fn as_integer(&self) -> Result<i32, KmipError> {
+        match self.value {
+            Value::Integer(v) => Ok(v),
+            _ => Err(self.wrong_type("an integer")),
+        }
+    }
+
+    pub fn as_text(&self) -> Result<&str, KmipError> {
+        match &self.value {
+            Value::TextString(v) => Ok(v),
+            _ => Err(self.wrong_type("a text string")),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Result<&[u8], KmipError> {
+        match &self.value {
+            Value::ByteString(v) => Ok(v.as_slice()),
+            _ => Err(self.wrong_type("a byte string")),
+        }
+    }
+
+    pub fn as_date_time(&self) -> Result<i64, KmipError> {
+        match self.value {
+            Value::DateTime(v) => Ok(v),
+            _ => Err(self.wrong_type("a date-time")),
+        }
+    }
+
+    fn wrong_type(&self, expected: &str) -> KmipError {
+        KmipError::Malformed(format!("item {:06X} is not {}", self.tag.0, expected))
+    }
+}
+
+/// Length of the message body announced by an 8-byte item header
+pub fn message_length(header: &[u8; HEADER_LENGTH]) -> Result<usize, KmipError> {
+    let length = padded(u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize);
+    if length > MAX_MESSAGE_LENGTH {
+        return Err(KmipError::Malformed(format!("message of {} bytes is too large", length)));
+    }
+    Ok(length)
+}
+
+pub(super) fn io_error(operation: &'static str) -> impl Fn(std::io::Error) -> KmipError {
+    move |e| KmipError::Connection(format!("{}: {}", operation, e))
+}
+
+fn padded(length: usize) -> usize {
+    length.div_ceil(ALIGNMENT) * ALIGNMENT
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut out = [0u8; N];
+    out.copy_from_slice(bytes);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_encodings() {
+        // Examples from the KMIP specification, section 9.1.2
+        let integer = Ttlv::integer(Tag(0x420020), 8);
+        assert_eq!(hex::encode(integer.encode()), "42002002000000040000000800000000");
+        let text = Ttlv::text(Tag(0x420020), "Hello World");
+        assert_eq!(hex::encode(text.encode()), "420020070000000b48656c6c6f20576f726c640000000000");
+        let boolean = Ttlv::new(Tag(0x420020), Value::Boolean(true));
+        assert_eq!(hex::encode(boolean.encode()), "42002006000000080000000000000001");
+
+        let nested = Ttlv::structure(
+            Tag(0x420020),
+            vec![Ttlv::enumeration(Tag(0x420004), 254), Ttlv::integer(Tag(0x420005), 255)],
+        );
+        assert_eq!(
+            hex::encode(nested.encode()),
+            "42002001000000204200040500000004000000fe000000004200050200000004000000ff00000000"
+        );
+        assert_eq!(Ttlv::decode(&nested.encode()).unwrap(), nested);
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_and_rejects() {
+        let message = Ttlv::structure(Tag::REQUEST_MESSAGE, vec![
+            Ttlv::new(Tag::TIME_STAMP, Value::DateTime(1_700_000_000)),
+            Ttlv::new(Tag::MAXIMUM_ITEMS, Value::LongInteger(-3)),
+            Ttlv::bytes(Tag::KEY_MATERIAL, &[1, 2, 3]),
+            Ttlv::new(Tag::ACTIVATION_DATE, Value::Interval(60)),
+            Ttlv::new(Tag(0x420004), Value::BigInteger(Zeroizing::new(vec![0xFF; 8]))),
+        ]);
+        let bytes = message.encode();
+        assert_eq!(bytes.len() % 8, 0);
+        assert_eq!(Ttlv::read_message(&mut bytes.as_slice()).await.unwrap(), Some(message));
+        assert_eq!(Ttlv::read_message(&mut &[][..]).await.unwrap(), None);
+
+        assert!(Ttlv::decode(&bytes[..bytes.len() - 8]).is_err());
+        let mut bad_type = bytes.clone();
+        bad_type[11] = 0x0F;
+        assert!(matches!(Ttlv::decode(&bad_type), Err(KmipError::Malformed(_))));
+        let mut huge = bytes;
+        huge[4] = 0x7F;
+        assert!(Ttlv::read_message(&mut huge.as_slice()).await.is_err());
+
+        // Nested empty structures: fine up to the limit, refused beyond it
+        let nested = |depth: usize| {
+            (1..depth).fold(Ttlv::structure(Tag::BATCH_ITEM, Vec::new()), |inner, _| {
+                Ttlv::structure(Tag::BATCH_ITEM, vec![inner])
+            })
+        };
+        assert!(Ttlv::decode(&nested(MAX_NESTING_DEPTH).encode()).is_ok());
+        let err = Ttlv::decode(&nested(MAX_NESTING_DEPTH + 1).encode()).unwrap_err();
+        assert!(matches!(err, KmipError::Malformed(m) if m.contains("nests deeper")));
+    }
+}
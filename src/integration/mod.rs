@@ -1,2 +1,4 @@
+pub mod api;
 pub mod tls;
-pub mod etl;
\ No newline at end of file
+pub mod etl;
+pub mod kmip;
\ No newline at end of file
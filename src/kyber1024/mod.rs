@@ -1,5 +1,3 @@
 pub mod kem;
 #[cfg(test)]
 mod tests;
-
-pub use kem::{Kyber1024, KeyPair, PublicKey, SecretKey};
\ No newline at end of file
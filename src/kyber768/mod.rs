@@ -1,5 +1,3 @@
 pub mod kem;
 #[cfg(test)]
 mod tests;
-
-pub use kem::{Kyber768, KeyPair, PublicKey, SecretKey};
\ No newline at end of file
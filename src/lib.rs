@@ -1,12 +1,19 @@
 pub mod kyber768;
 pub mod kyber1024;
 pub mod utils;
+pub mod config;
+pub mod error;
+pub mod crypto;
+#[path = "../kms/mod.rs"]
+pub mod kms;
+pub mod integration;
+pub mod monitoring;
+pub mod security;
 
 // Re-eksporty głównych komponentów
-pub use kyber768::kem as kem768;
-pub use kyber1024::kem as kem1024;
 pub use utils::{entropy, encoding, validation};
+pub use error::{Error, ErrorCode};
 
 // Stałe konfiguracyjne
 pub const KYBER_768_KEY_SIZE: usize = 1088;
-pub const KYBER_1024_KEY_SIZE: usize = 1568;
\ No newline at end of file
+pub const KYBER_1024_KEY_SIZE: usize = 1568;
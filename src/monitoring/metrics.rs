@@ -1,6 +1,19 @@
+//! Metrics for operators: ETL batch figures, and every registered
+//! [`MetricsSource`] in the Prometheus text exposition format, served at
+//! [`METRICS_PATH`] for Prometheus to scrape.
+//!
+//! Mount with `App::new().app_data(web::Data::new(registry)).configure(configure)`.
+
+use crate::error::Error;
+use actix_web::{web, HttpResponse};
 use chrono::{DateTime, Utc};
+use std::fmt::Write as _;
+use std::sync::Arc;
 use std::time::Duration;
 
+pub const METRICS_PATH: &str = "/metrics";
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
 #[derive(Debug, Default, Clone)]
 pub struct BatchMetrics {
     pub total_transactions: usize,
@@ -12,4 +25,132 @@ pub struct BatchMetrics {
     pub start_time: Option<DateTime<Utc>>,
     pub end_time: Option<DateTime<Utc>>,
     pub average_batch_duration: Duration,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+/// One metric with a sample per label set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricFamily {
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub help: &'static str,
+    pub samples: Vec<Sample>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub labels: Vec<(&'static str, String)>,
+    pub value: u64,
+}
+
+/// Reports its metrics when scraped, e.g. the KMS usage counters
+pub trait MetricsSource: Send + Sync {
+    fn collect(&self) -> Result<Vec<MetricFamily>, Error>;
+}
+
+/// Sources scraped together at [`METRICS_PATH`]
+#[derive(Clone, Default)]
+pub struct MetricsRegistry {
+    sources: Vec<Arc<dyn MetricsSource>>,
+}
+
+impl MetricKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+impl MetricFamily {
+    pub fn new(name: &'static str, kind: MetricKind, help: &'static str) -> Self {
+        Self { name, kind, help, samples: Vec::new() }
+    }
+
+    pub fn with_sample(mut self, labels: Vec<(&'static str, String)>, value: u64) -> Self {
+        self.samples.push(Sample { labels, value });
+        self
+    }
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(mut self, source: Arc<dyn MetricsSource>) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Every source's metrics in the Prometheus text exposition format
+    pub fn render(&self) -> Result<String, Error> {
+        let mut out = String::new();
+        for source in &self.sources {
+            for family in source.collect()? {
+                let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+                let _ = writeln!(out, "# TYPE {} {}", family.name, family.kind.as_str());
+                for sample in &family.samples {
+                    let labels = sample
+                        .labels
+                        .iter()
+                        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+                        .collect::<Vec<_>>();
+                    let labels = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels.join(",")) };
+                    let _ = writeln!(out, "{}{} {}", family.name, labels, sample.value);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Registers [`METRICS_PATH`]; the app must hold a `web::Data<MetricsRegistry>`
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route(METRICS_PATH, web::get().to(metrics));
+}
+
+async fn metrics(registry: web::Data<MetricsRegistry>) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().content_type(METRICS_CONTENT_TYPE).body(registry.render()?))
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    struct Fixed;
+
+    impl MetricsSource for Fixed {
+        fn collect(&self) -> Result<Vec<MetricFamily>, Error> {
+            Ok(vec![
+                MetricFamily::new("queue_depth", MetricKind::Gauge, "Items waiting").with_sample(Vec::new(), 3),
+                MetricFamily::new("requests_total", MetricKind::Counter, "Requests served")
+                    .with_sample(vec![("route", "/a\"b".to_string())], 7),
+            ])
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_registry_served_in_text_format() {
+        let registry = web::Data::new(MetricsRegistry::new().register(Arc::new(Fixed)));
+        let app = test::init_service(App::new().app_data(registry).configure(configure)).await;
+
+        let body = test::call_and_read_body(&app, test::TestRequest::get().uri(METRICS_PATH).to_request()).await;
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "# HELP queue_depth Items waiting\n# TYPE queue_depth gauge\nqueue_depth 3\n\
+             # HELP requests_total Requests served\n# TYPE requests_total counter\nrequests_total{route=\"/a\\\"b\"} 7\n"
+        );
+    }
+}
@@ -0,0 +1,5 @@
+//! Operational metrics of the service.
+
+pub mod metrics;
+
+pub use metrics::{MetricFamily, MetricKind, MetricsRegistry, MetricsSource, Sample, METRICS_PATH};
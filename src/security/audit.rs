@@ -2,6 +2,9 @@ use std::time::{Duration, Instant};
 use pqcrypto_kyber::kyber1024::{self, encapsulate, decapsulate};
 use pqcrypto_traits::kem::{PublicKey as KemPublicKey, SecretKey as KemSecretKey};
 use crate::config::{get_formatted_timestamp, get_current_user};
+use crate::crypto::signature::ArchiveRegistry;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
 use zeroize::Zeroize;
 
 #[derive(Debug)]
@@ -11,6 +14,7 @@ pub struct SecurityAuditMetrics {
     pub memory_security_checks: Vec<SecurityCheck>,
     pub key_security_checks: Vec<SecurityCheck>,
     pub protocol_security_checks: Vec<SecurityCheck>,
+    pub archive_security_checks: Vec<SecurityCheck>,
     pub total_checks: usize,
     pub passed_checks: usize,
     pub critical_issues: usize,
@@ -54,6 +58,12 @@ impl Drop for SecureKeyPair {
     }
 }
 
+impl Default for SecurityAuditMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SecurityAuditMetrics {
     pub fn new() -> Self {
         SecurityAuditMetrics {
@@ -62,6 +72,7 @@ impl SecurityAuditMetrics {
             memory_security_checks: Vec::new(),
             key_security_checks: Vec::new(),
             protocol_security_checks: Vec::new(),
+            archive_security_checks: Vec::new(),
             total_checks: 0,
             passed_checks: 0,
             critical_issues: 0,
@@ -81,6 +92,9 @@ impl SecurityAuditMetrics {
         self.print_section("Memory Security", &self.memory_security_checks);
         self.print_section("Key Security", &self.key_security_checks);
         self.print_section("Protocol Security", &self.protocol_security_checks);
+        if !self.archive_security_checks.is_empty() {
+            self.print_section("Archive Integrity", &self.archive_security_checks);
+        }
     }
 
     fn print_section(&self, title: &str, checks: &[SecurityCheck]) {
@@ -107,6 +121,87 @@ pub async fn run_security_audit() -> SecurityAuditMetrics {
     metrics
 }
 
+/// Runs the full audit and additionally re-verifies every registered archive signature
+pub async fn run_security_audit_with_archives(registry: &ArchiveRegistry) -> SecurityAuditMetrics {
+    let mut metrics = run_security_audit().await;
+    check_archive_signatures(&mut metrics, registry);
+    metrics
+}
+
+/// Re-verifies archived signatures on a fixed interval until the returned task is aborted
+pub fn spawn_archive_reverification(registry: Arc<ArchiveRegistry>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+
+            let mut metrics = SecurityAuditMetrics::new();
+            check_archive_signatures(&mut metrics, &registry);
+            if metrics.critical_issues > 0 {
+                metrics.print_audit_report();
+            }
+        }
+    })
+}
+
+/// Verifies every sealed archive manifest and audit checkpoint in the registry
+pub fn check_archive_signatures(metrics: &mut SecurityAuditMetrics, registry: &ArchiveRegistry) {
+    for archive in registry.archives() {
+        let result = archive.sealed.verify(&archive.verifying_key, &archive.root);
+        record_archive_check(
+            metrics,
+            format!("Archive Seal: {}", archive.sealed.manifest.archive_id),
+            format!(
+                "Verify {} signature by {} over {} archived files",
+                archive.verifying_key.parameter_set(),
+                archive.sealed.signer_id,
+                archive.sealed.manifest.entries.len()
+            ),
+            result.err().map(|e| e.to_string()),
+        );
+    }
+
+    for (checkpoint, key) in registry.checkpoints() {
+        let result = checkpoint.verify(key);
+        record_archive_check(
+            metrics,
+            format!("Audit Checkpoint #{}", checkpoint.checkpoint.sequence),
+            format!("Verify {} checkpoint signature by {}", key.parameter_set(), checkpoint.signer_id),
+            result.err().map(|e| e.to_string()),
+        );
+    }
+}
+
+fn record_archive_check(
+    metrics: &mut SecurityAuditMetrics,
+    name: String,
+    description: String,
+    error: Option<String>,
+) {
+    let passed = error.is_none();
+    metrics.archive_security_checks.push(SecurityCheck {
+        name,
+        status: if passed { SecurityCheckStatus::Passed } else { SecurityCheckStatus::Failed },
+        severity: SecuritySeverity::Critical,
+        description: match &error {
+            Some(e) => format!("{} (failed: {})", description, e),
+            None => description,
+        },
+        remediation: if passed {
+            None
+        } else {
+            Some("Restore the archive from a trusted copy and investigate the modification".to_string())
+        },
+    });
+
+    metrics.total_checks += 1;
+    if passed {
+        metrics.passed_checks += 1;
+    } else {
+        metrics.critical_issues += 1;
+    }
+}
+
 fn check_memory_security(metrics: &mut SecurityAuditMetrics) {
     let result = test_memory_zeroization();
     metrics.memory_security_checks.push(SecurityCheck {
@@ -276,4 +371,35 @@ mod tests {
             "Security check pass rate below 95%"
         );
     }
+
+    #[test]
+    fn test_archive_signature_checks() {
+        use crate::crypto::signature::{ArchiveManifest, AuditCheckpoint, SlhDsaParameterSet, SlhDsaSigningKey};
+
+        let archive = tempfile::tempdir().unwrap();
+        let dir = archive.path();
+        std::fs::write(dir.join("ledger.csv"), b"id,amount\n1,100.00\n").unwrap();
+
+        let key = SlhDsaSigningKey::generate(SlhDsaParameterSet::Sha2_128f);
+        let mut manifest = ArchiveManifest::new("ledger-archive", key.parameter_set().pre_hash());
+        manifest.add_file(dir, "ledger.csv").unwrap();
+
+        let mut registry = ArchiveRegistry::new();
+        registry.register_archive(manifest.seal("archive-officer", &key).unwrap(), dir, key.verifying_key().clone());
+        registry.register_checkpoint(
+            AuditCheckpoint::new(1, &[0u8; 32]).seal("kms", &key).unwrap(),
+            key.verifying_key().clone(),
+        );
+
+        let mut metrics = SecurityAuditMetrics::new();
+        check_archive_signatures(&mut metrics, &registry);
+        assert_eq!(metrics.total_checks, 2);
+        assert_eq!(metrics.critical_issues, 0);
+
+        std::fs::write(dir.join("ledger.csv"), b"id,amount\n1,900.00\n").unwrap();
+        let mut metrics = SecurityAuditMetrics::new();
+        check_archive_signatures(&mut metrics, &registry);
+        assert_eq!(metrics.critical_issues, 1);
+        assert_eq!(metrics.archive_security_checks[0].status, SecurityCheckStatus::Failed);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,400 @@
+//! Minimal DER (X.690) writer and reader for the ASN.1 structures the crate
+//! produces itself: PKCS#8 key files, certificates and related formats.
+//!
+//! Only definite-length encodings and the universal types we actually use are
+//! supported. Lengths up to 4 bytes long-form are accepted.
+
+use crate::error::EncodingError;
+
+type Result<T> = std::result::Result<T, EncodingError>;
+
+// Universal tags
+pub const TAG_BOOLEAN: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_ENUMERATED: u8 = 0x0a;
+pub const TAG_UTF8_STRING: u8 = 0x0c;
+pub const TAG_PRINTABLE_STRING: u8 = 0x13;
+pub const TAG_UTC_TIME: u8 = 0x17;
+pub const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+
+/// Tag of an explicit context-specific field `[n]`
+pub fn context_tag(number: u8, constructed: bool) -> u8 {
+    0x80 | if constructed { 0x20 } else { 0 } | (number & 0x1f)
+}
+
+/// Encodes a single tag-length-value triple
+pub fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = header(tag, content.len(), content.len());
+    out.extend_from_slice(content);
+    out
+}
+
+// Tag and length octets, with room reserved for the content so that appending
+// never reallocates (and never leaves stray copies of key material behind)
+fn header(tag: u8, len: usize, reserve: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(reserve + 6);
+    out.push(tag);
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out
+}
+
+fn constructed<T: AsRef<[u8]>>(tag: u8, items: &[T]) -> Vec<u8> {
+    let len = items.iter().map(|item| item.as_ref().len()).sum();
+    let mut out = header(tag, len, len);
+    for item in items {
+        out.extend_from_slice(item.as_ref());
+    }
+    out
+}
+
+pub fn sequence<T: AsRef<[u8]>>(items: &[T]) -> Vec<u8> {
+    constructed(TAG_SEQUENCE, items)
+}
+
+pub fn set<T: AsRef<[u8]>>(items: &[T]) -> Vec<u8> {
+    constructed(TAG_SET, items)
+}
+
+/// Explicitly tagged context-specific field wrapping already-encoded content
+pub fn explicit(number: u8, inner: &[u8]) -> Vec<u8> {
+    tlv(context_tag(number, true), inner)
+}
+
+pub fn boolean(value: bool) -> Vec<u8> {
+    tlv(TAG_BOOLEAN, &[if value { 0xff } else { 0x00 }])
+}
+
+/// Non-negative INTEGER
+pub fn integer_u64(value: u64) -> Vec<u8> {
+    integer_bytes(&value.to_be_bytes())
+}
+
+/// Non-negative INTEGER from big-endian magnitude bytes (e.g. a serial number)
+pub fn integer_bytes(magnitude: &[u8]) -> Vec<u8> {
+    let trimmed: &[u8] = {
+        let skip = magnitude.iter().take_while(|&&b| b == 0).count();
+        if skip == magnitude.len() { &[0] } else { &magnitude[skip..] }
+    };
+    let mut content = Vec::with_capacity(trimmed.len() + 1);
+    if trimmed[0] & 0x80 != 0 {
+        content.push(0);
+    }
+    content.extend_from_slice(trimmed);
+    tlv(TAG_INTEGER, &content)
+}
+
+pub fn enumerated(value: u8) -> Vec<u8> {
+    tlv(TAG_ENUMERATED, &[value])
+}
+
+pub fn octet_string(bytes: &[u8]) -> Vec<u8> {
+    tlv(TAG_OCTET_STRING, bytes)
+}
+
+/// BIT STRING with no unused bits
+pub fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    let mut content = Vec::with_capacity(bytes.len() + 1);
+    content.push(0);
+    content.extend_from_slice(bytes);
+    tlv(TAG_BIT_STRING, &content)
+}
+
+pub fn null() -> Vec<u8> {
+    tlv(TAG_NULL, &[])
+}
+
+pub fn utf8_string(value: &str) -> Vec<u8> {
+    tlv(TAG_UTF8_STRING, value.as_bytes())
+}
+
+/// GeneralizedTime from a `YYYYMMDDHHMMSSZ` string
+pub fn generalized_time(value: &str) -> Vec<u8> {
+    tlv(TAG_GENERALIZED_TIME, value.as_bytes())
+}
+
+/// OBJECT IDENTIFIER from dotted notation, e.g. `1.2.840.113549.1.5.13`
+pub fn oid(dotted: &str) -> Result<Vec<u8>> {
+    Ok(tlv(TAG_OID, &encode_oid_content(dotted)?))
+}
+
+fn encode_oid_content(dotted: &str) -> Result<Vec<u8>> {
+    let arcs: Vec<u128> = dotted
+        .split('.')
+        .map(|arc| arc.parse::<u128>().map_err(|_| invalid(format!("Invalid OID arc '{}' in {}", arc, dotted))))
+        .collect::<Result<_>>()?;
+    if arcs.len() < 2 || arcs[0] > 2 || (arcs[0] < 2 && arcs[1] >= 40) {
+        return Err(invalid(format!("Invalid OID: {}", dotted)));
+    }
+
+    let mut content = Vec::new();
+    let first = arcs[0] * 40 + arcs[1];
+    for arc in std::iter::once(first).chain(arcs[2..].iter().copied()) {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        chunk.reverse();
+        content.extend_from_slice(&chunk);
+    }
+    Ok(content)
+}
+
+fn decode_oid_content(content: &[u8]) -> Result<String> {
+    let mut arcs: Vec<u128> = Vec::new();
+    let mut value: u128 = 0;
+    for (i, &byte) in content.iter().enumerate() {
+        if value > (u128::MAX >> 7) {
+            return Err(invalid("OID arc too large"));
+        }
+        value = (value << 7) | (byte & 0x7f) as u128;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = if value < 80 { value / 40 } else { 2 };
+                arcs.push(first);
+                arcs.push(value - first * 40);
+            } else {
+                arcs.push(value);
+            }
+            value = 0;
+        } else if i == content.len() - 1 {
+            return Err(invalid("Truncated OID"));
+        }
+    }
+    if arcs.is_empty() {
+        return Err(invalid("Empty OID"));
+    }
+    Ok(arcs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join("."))
+}
+
+fn invalid(message: impl Into<String>) -> EncodingError {
+    EncodingError::InvalidDer(message.into())
+}
+
+/// Sequential reader over DER-encoded content
+#[derive(Debug, Clone)]
+pub struct DerReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> DerReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    /// Fails unless every byte has been consumed
+    pub fn finish(&self) -> Result<()> {
+        if !self.is_empty() {
+            return Err(invalid(format!("{} trailing bytes after DER structure", self.data.len() - self.position)));
+        }
+        Ok(())
+    }
+
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    /// Reads the next element, returning its tag, content and full encoding
+    pub fn read_any(&mut self) -> Result<(u8, &'a [u8], &'a [u8])> {
+        let start = self.position;
+        let tag = *self.data.get(self.position).ok_or_else(|| invalid("Unexpected end of DER data"))?;
+        let first_len = *self.data.get(self.position + 1).ok_or_else(|| invalid("Missing DER length"))?;
+        let mut cursor = self.position + 2;
+
+        let length = if first_len < 0x80 {
+            first_len as usize
+        } else {
+            let count = (first_len & 0x7f) as usize;
+            if count == 0 || count > 4 {
+                return Err(invalid("Unsupported DER length encoding"));
+            }
+            let bytes = self.data.get(cursor..cursor + count).ok_or_else(|| invalid("Truncated DER length"))?;
+            if bytes[0] == 0 {
+                return Err(invalid("Non-minimal DER length"));
+            }
+            cursor += count;
+            let length = bytes.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            if length < 0x80 {
+                return Err(invalid("Non-minimal DER length"));
+            }
+            length
+        };
+
+        let end = cursor.checked_add(length).ok_or_else(|| invalid("DER length overflow"))?;
+        let content = self.data.get(cursor..end).ok_or_else(|| invalid("Truncated DER content"))?;
+        self.position = end;
+        Ok((tag, content, &self.data[start..end]))
+    }
+
+    /// Reads the next element and checks its tag
+    pub fn read_expected(&mut self, tag: u8) -> Result<&'a [u8]> {
+        let (actual, content, _) = self.read_any()?;
+        if actual != tag {
+            return Err(invalid(format!("Expected DER tag 0x{:02x}, found 0x{:02x}", tag, actual)));
+        }
+        Ok(content)
+    }
+
+    pub fn read_sequence(&mut self) -> Result<DerReader<'a>> {
+        Ok(DerReader::new(self.read_expected(TAG_SEQUENCE)?))
+    }
+
+    pub fn read_set(&mut self) -> Result<DerReader<'a>> {
+        Ok(DerReader::new(self.read_expected(TAG_SET)?))
+    }
+
+    /// Reads an explicit `[n]` field if it is next, returning a reader over its content
+    pub fn read_optional_explicit(&mut self, number: u8) -> Result<Option<DerReader<'a>>> {
+        if self.peek_tag() == Some(context_tag(number, true)) {
+            Ok(Some(DerReader::new(self.read_expected(context_tag(number, true))?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn read_boolean(&mut self) -> Result<bool> {
+        match self.read_expected(TAG_BOOLEAN)? {
+            [0x00] => Ok(false),
+            [0xff] => Ok(true),
+            _ => Err(invalid("Invalid DER BOOLEAN")),
+        }
+    }
+
+    /// Reads a non-negative INTEGER that fits in a u64
+    pub fn read_integer_u64(&mut self) -> Result<u64> {
+        let magnitude = self.read_integer_bytes()?;
+        if magnitude.len() > 8 {
+            return Err(invalid("INTEGER does not fit in 64 bits"));
+        }
+        Ok(magnitude.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    /// Reads a non-negative INTEGER as big-endian magnitude bytes
+    pub fn read_integer_bytes(&mut self) -> Result<&'a [u8]> {
+        let content = self.read_expected(TAG_INTEGER)?;
+        match content {
+            [] => Err(invalid("Empty DER INTEGER")),
+            [first, ..] if first & 0x80 != 0 => Err(invalid("Negative INTEGER not supported")),
+            [0, rest @ ..] if !rest.is_empty() => Ok(rest),
+            _ => Ok(content),
+        }
+    }
+
+    pub fn read_enumerated(&mut self) -> Result<u8> {
+        match self.read_expected(TAG_ENUMERATED)? {
+            [value] => Ok(*value),
+            _ => Err(invalid("Unsupported ENUMERATED value")),
+        }
+    }
+
+    pub fn read_octet_string(&mut self) -> Result<&'a [u8]> {
+        self.read_expected(TAG_OCTET_STRING)
+    }
+
+    /// Reads a BIT STRING with no unused bits
+    pub fn read_bit_string(&mut self) -> Result<&'a [u8]> {
+        match self.read_expected(TAG_BIT_STRING)? {
+            [0, rest @ ..] => Ok(rest),
+            _ => Err(invalid("BIT STRING with unused bits not supported")),
+        }
+    }
+
+    pub fn read_null(&mut self) -> Result<()> {
+        if !self.read_expected(TAG_NULL)?.is_empty() {
+            return Err(invalid("Invalid DER NULL"));
+        }
+        Ok(())
+    }
+
+    pub fn read_oid(&mut self) -> Result<String> {
+        decode_oid_content(self.read_expected(TAG_OID)?)
+    }
+
+    pub fn read_utf8_string(&mut self) -> Result<String> {
+        String::from_utf8(self.read_expected(TAG_UTF8_STRING)?.to_vec())
+            .map_err(|_| invalid("Invalid UTF8String"))
+    }
+
+    pub fn read_generalized_time(&mut self) -> Result<String> {
+        String::from_utf8(self.read_expected(TAG_GENERALIZED_TIME)?.to_vec())
+            .map_err(|_| invalid("Invalid GeneralizedTime"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oid_round_trip() {
+        for dotted in ["1.2.840.113549.1.5.13", "2.16.840.1.101.3.4.1.46", "2.25.329800735698586629295641978511506172918"] {
+            let encoded = oid(dotted).unwrap();
+            assert_eq!(DerReader::new(&encoded).read_oid().unwrap(), dotted);
+        }
+        // Known encoding of PBES2
+        assert_eq!(
+            oid("1.2.840.113549.1.5.13").unwrap(),
+            vec![0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0d]
+        );
+    }
+
+    #[test]
+    fn test_long_lengths() {
+        let content = vec![0xabu8; 70_000];
+        let encoded = octet_string(&content);
+        assert_eq!(&encoded[..5], &[0x04, 0x83, 0x01, 0x11, 0x70]);
+        assert_eq!(DerReader::new(&encoded).read_octet_string().unwrap(), &content[..]);
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(integer_u64(0), vec![0x02, 0x01, 0x00]);
+        assert_eq!(integer_u64(128), vec![0x02, 0x02, 0x00, 0x80]);
+        for value in [0u64, 1, 127, 128, 255, 65_536, u64::MAX] {
+            assert_eq!(DerReader::new(&integer_u64(value)).read_integer_u64().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_nested_sequence() {
+        let encoded = sequence(&[integer_u64(3), sequence(&[octet_string(b"abc"), null()]), explicit(0, &boolean(true))]);
+
+        let mut outer = DerReader::new(&encoded);
+        let mut seq = outer.read_sequence().unwrap();
+        outer.finish().unwrap();
+
+        assert_eq!(seq.read_integer_u64().unwrap(), 3);
+        let mut inner = seq.read_sequence().unwrap();
+        assert_eq!(inner.read_octet_string().unwrap(), b"abc");
+        inner.read_null().unwrap();
+        assert!(seq.read_optional_explicit(1).unwrap().is_none());
+        assert!(seq.read_optional_explicit(0).unwrap().unwrap().read_boolean().unwrap());
+        seq.finish().unwrap();
+    }
+
+    #[test]
+    fn test_truncated_input_rejected() {
+        let encoded = octet_string(&[1, 2, 3, 4]);
+        assert!(DerReader::new(&encoded[..4]).read_octet_string().is_err());
+        assert!(DerReader::new(&[0x04, 0x81, 0x05, 1, 2, 3, 4, 5]).read_octet_string().is_err());
+    }
+}
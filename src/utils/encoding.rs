@@ -0,0 +1,140 @@
+//! Text and binary encodings shared by every kind of key material in the crate.
+//!
+//! KEM keys, signature keys and signatures are all exported through the same
+//! [`KeyEncoding`] formats so that operators only have to deal with one set of
+//! file layouts.
+
+use crate::error::EncodingError;
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+// PEM labels for key material produced by the crate
+pub const KYBER_PUBLIC_KEY_LABEL: &str = "KYBER PUBLIC KEY";
+pub const KYBER_SECRET_KEY_LABEL: &str = "KYBER SECRET KEY";
+
+const PEM_LINE_WIDTH: usize = 64;
+
+/// Supported encodings for exported key material
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEncoding {
+    /// Raw bytes, no framing
+    Raw,
+    /// Lowercase hexadecimal
+    Hex,
+    /// Standard base64 with padding
+    Base64,
+    /// PEM armour (base64 body between BEGIN/END lines)
+    Pem,
+}
+
+/// Encodes bytes as lowercase hexadecimal
+pub fn to_hex(bytes: &[u8]) -> String {
+    hex::encode(bytes)
+}
+
+/// Decodes hexadecimal text, ignoring surrounding whitespace
+pub fn from_hex(text: &str) -> Result<Vec<u8>, EncodingError> {
+    hex::decode(text.trim()).map_err(|_| EncodingError::InvalidHex)
+}
+
+/// Encodes bytes as standard base64
+pub fn to_base64(bytes: &[u8]) -> String {
+    STANDARD.encode(bytes)
+}
+
+/// Decodes standard base64 text, ignoring surrounding whitespace
+pub fn from_base64(text: &str) -> Result<Vec<u8>, EncodingError> {
+    STANDARD
+        .decode(text.trim())
+        .map_err(|_| EncodingError::InvalidBase64)
+}
+
+/// Wraps bytes in PEM armour with the given label
+pub fn to_pem(label: &str, bytes: &[u8]) -> String {
+    let body = to_base64(bytes);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in body.as_bytes().chunks(PEM_LINE_WIDTH) {
+        pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+/// Extracts the body of a PEM document, checking that its label matches
+pub fn from_pem(label: &str, text: &str) -> Result<Vec<u8>, EncodingError> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+
+    let text = text.trim();
+    let body = text
+        .strip_prefix(&begin)
+        .and_then(|rest| rest.strip_suffix(&end))
+        .ok_or_else(|| EncodingError::PemLabelMismatch { expected: label.to_string() })?;
+
+    let joined: String = body.lines().map(str::trim).collect();
+    from_base64(&joined)
+}
+
+/// Encodes key material in the requested format
+pub fn encode_key(bytes: &[u8], label: &str, encoding: KeyEncoding) -> Vec<u8> {
+    match encoding {
+        KeyEncoding::Raw => bytes.to_vec(),
+        KeyEncoding::Hex => to_hex(bytes).into_bytes(),
+        KeyEncoding::Base64 => to_base64(bytes).into_bytes(),
+        KeyEncoding::Pem => to_pem(label, bytes).into_bytes(),
+    }
+}
+
+/// Decodes key material previously produced by [`encode_key`]
+pub fn decode_key(data: &[u8], label: &str, encoding: KeyEncoding) -> Result<Vec<u8>, EncodingError> {
+    if encoding == KeyEncoding::Raw {
+        return Ok(data.to_vec());
+    }
+
+    let text = std::str::from_utf8(data)
+        .map_err(|_| EncodingError::InvalidUtf8)?;
+
+    match encoding {
+        KeyEncoding::Raw => unreachable!(),
+        KeyEncoding::Hex => from_hex(text),
+        KeyEncoding::Base64 => from_base64(text),
+        KeyEncoding::Pem => from_pem(label, text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_all_encodings() {
+        let data: Vec<u8> = (0..=255).collect();
+
+        for encoding in [KeyEncoding::Raw, KeyEncoding::Hex, KeyEncoding::Base64, KeyEncoding::Pem] {
+            let encoded = encode_key(&data, KYBER_PUBLIC_KEY_LABEL, encoding);
+            let decoded = decode_key(&encoded, KYBER_PUBLIC_KEY_LABEL, encoding)
+                .expect("Failed to decode key");
+            assert_eq!(decoded, data, "Round trip failed for {:?}", encoding);
+        }
+    }
+
+    #[test]
+    fn test_pem_line_width() {
+        let pem = to_pem(KYBER_PUBLIC_KEY_LABEL, &[7u8; 200]);
+        assert!(pem.lines().all(|line| line.len() <= PEM_LINE_WIDTH || line.starts_with("-----")));
+    }
+
+    #[test]
+    fn test_pem_label_mismatch() {
+        let pem = to_pem(KYBER_PUBLIC_KEY_LABEL, &[1, 2, 3]);
+        assert!(matches!(
+            from_pem(KYBER_SECRET_KEY_LABEL, &pem),
+            Err(EncodingError::PemLabelMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_hex() {
+        assert!(matches!(from_hex("zz"), Err(EncodingError::InvalidHex)));
+    }
+}
@@ -0,0 +1,42 @@
+//! Entropy estimates for candidate key material.
+//!
+//! This is a sanity check against stuck or badly seeded sources, not a
+//! measure of unpredictability: a counter or a text file can still score high.
+
+/// Shannon entropy of the byte distribution of `data`, in bits per byte,
+/// from 0.0 (empty or a single repeated byte) to 8.0
+pub fn calculate(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    let total = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculate() {
+        assert_eq!(calculate(&[]), 0.0);
+        assert_eq!(calculate(&[0x42; 64]), 0.0);
+        assert!((calculate(&[0, 1, 0, 1]) - 1.0).abs() < 1e-9);
+
+        let every_byte: Vec<u8> = (0..=255).collect();
+        assert!((calculate(&every_byte) - 8.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,118 @@
+//! Minimal blocking HTTP/1.0 client for the crate's own service calls.
+//!
+//! Certificate status lookups and the Vault Transit master-key provider only
+//! need one request per connection against a known endpoint, which does not
+//! justify a full HTTP stack. HTTP/1.0 keeps the response unchunked and the
+//! connection single-use. [`request_tls`] verifies the server against the
+//! CAs of its [`client_config`](super::tls::client_config); plain
+//! [`request`] is only for data that is protected on its own, such as signed
+//! status responses.
+
+use super::tls;
+use crate::error::TlsError;
+use anyhow::{anyhow, Result};
+use rustls::{ClientConfig, ClientConnection, StreamOwned};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+// Responses here are a few kilobytes; anything far larger is not one
+const MAX_RESPONSE_LENGTH: u64 = 1 << 20;
+
+/// Status code and body of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Sends one request to `address` (`host:port`) and reads the whole response
+pub fn request(
+    address: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<&[u8]>,
+    timeout: Duration,
+) -> Result<HttpResponse> {
+    exchange(connect(address, timeout)?, address, method, path, headers, body)
+}
+
+/// Sends one request over TLS to `address` (`host:port`), whose certificate
+/// must be valid for `host` and issued by a CA `tls` trusts
+pub fn request_tls(
+    address: &str,
+    tls: &Arc<ClientConfig>,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<&[u8]>,
+    timeout: Duration,
+) -> Result<HttpResponse> {
+    let connection = ClientConnection::new(tls.clone(), tls::server_name(address)?)
+        .map_err(|e| TlsError::HandshakeFailed(e.to_string()))?;
+    let mut stream = StreamOwned::new(connection, connect(address, timeout)?);
+    while stream.conn.is_handshaking() {
+        stream
+            .conn
+            .complete_io(&mut stream.sock)
+            .map_err(|e| tls::handshake_error(address, e))?;
+    }
+    exchange(stream, address, method, path, headers, body)
+}
+
+fn connect(address: &str, timeout: Duration) -> Result<TcpStream> {
+    let socket = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Cannot resolve {}", address))?;
+    let stream = TcpStream::connect_timeout(&socket, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(stream)
+}
+
+fn exchange(
+    mut stream: impl Read + Write,
+    address: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Option<&[u8]>,
+) -> Result<HttpResponse> {
+    let mut head = format!("{} {} HTTP/1.0\r\nHost: {}\r\n", method, path, address);
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if let Some(body) = body {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    if let Some(body) = body {
+        stream.write_all(body)?;
+    }
+    stream.flush()?;
+
+    let mut response = Vec::new();
+    stream.take(MAX_RESPONSE_LENGTH).read_to_end(&mut response)?;
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| anyhow!("Malformed response from {}", address))?;
+    let body = response.split_off(header_end + 4);
+    let status = String::from_utf8_lossy(&response)
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| anyhow!("Malformed status line from {}", address))?;
+    Ok(HttpResponse { status, body })
+}
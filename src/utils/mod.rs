@@ -0,0 +1,7 @@
+pub mod der;
+pub mod encoding;
+pub mod entropy;
+pub mod http;
+pub mod tls;
+pub mod validation;
+pub mod zeroize;
@@ -0,0 +1,177 @@
+//! rustls configuration for the crate's own TLS clients and servers.
+//!
+//! Trust is always explicit: a client trusts only the CA certificates it is
+//! given, never the system roots, and a server that is given client CAs
+//! refuses clients without a certificate issued by one of them.
+
+use crate::error::TlsError;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use sha3::{Digest, Sha3_256};
+use std::sync::Arc;
+
+/// Certificate chain and private key a TLS endpoint presents
+#[derive(Debug)]
+pub struct TlsIdentity {
+    certificates: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+}
+
+impl TlsIdentity {
+    /// Reads a PEM certificate chain, leaf first, and its PEM private key
+    pub fn from_pem(certificate_pem: &[u8], key_pem: &[u8]) -> Result<Self, TlsError> {
+        let certificates = certificates_from_pem(certificate_pem)?;
+        let key = PrivateKeyDer::from_pem_slice(key_pem)
+            .map_err(|e| TlsError::CertificateRejected(format!("private key: {}", e)))?;
+        Ok(Self { certificates, key })
+    }
+
+    pub fn from_files(certificate_path: &str, key_path: &str) -> Result<Self, TlsError> {
+        Self::from_pem(&read(certificate_path)?, &read(key_path)?)
+    }
+}
+
+impl Clone for TlsIdentity {
+    fn clone(&self) -> Self {
+        Self { certificates: self.certificates.clone(), key: self.key.clone_key() }
+    }
+}
+
+/// Client configuration trusting only the CAs in `ca_pem`, presenting
+/// `identity` when the server asks for a client certificate
+pub fn client_config(ca_pem: &[u8], identity: Option<&TlsIdentity>) -> Result<Arc<ClientConfig>, TlsError> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TlsError::UnsupportedCipherSuite(e.to_string()))?
+        .with_root_certificates(root_store(ca_pem)?);
+    let config = match identity {
+        Some(identity) => {
+            let identity = identity.clone();
+            builder
+                .with_client_auth_cert(identity.certificates, identity.key)
+                .map_err(|e| TlsError::CertificateRejected(format!("client identity: {}", e)))?
+        }
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+/// Server configuration presenting `identity`. With `client_ca_pem`, every
+/// client must present a certificate issued by one of those CAs.
+pub fn server_config(identity: &TlsIdentity, client_ca_pem: Option<&[u8]>) -> Result<Arc<ServerConfig>, TlsError> {
+    let builder = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TlsError::UnsupportedCipherSuite(e.to_string()))?;
+    let builder = match client_ca_pem {
+        Some(ca_pem) => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(root_store(ca_pem)?), provider())
+                .build()
+                .map_err(|e| TlsError::CertificateRejected(format!("client CAs: {}", e)))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let identity = identity.clone();
+    let config = builder
+        .with_single_cert(identity.certificates, identity.key)
+        .map_err(|e| TlsError::CertificateRejected(format!("server identity: {}", e)))?;
+    Ok(Arc::new(config))
+}
+
+/// SHA3-256 of the DER encoding of the first certificate in `certificate_pem`
+pub fn certificate_fingerprint(certificate_pem: &[u8]) -> Result<[u8; 32], TlsError> {
+    Ok(fingerprint(&certificates_from_pem(certificate_pem)?[0]))
+}
+
+/// SHA3-256 of a DER certificate, e.g. one a peer presented
+pub fn fingerprint(certificate: &CertificateDer<'_>) -> [u8; 32] {
+    Sha3_256::digest(certificate.as_ref()).into()
+}
+
+/// Name the server certificate must be valid for: the host part of `address` (`host:port`)
+pub fn server_name(address: &str) -> Result<ServerName<'static>, TlsError> {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    ServerName::try_from(host.trim_start_matches('[').trim_end_matches(']').to_string())
+        .map_err(|e| TlsError::HandshakeFailed(format!("server name {}: {}", host, e)))
+}
+
+/// Classifies a failed handshake with `peer`: a certificate the other side
+/// refused, or anything else
+pub fn handshake_error(peer: &str, e: std::io::Error) -> TlsError {
+    let message = format!("{}: {}", peer, e);
+    match e.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) {
+        Some(rustls::Error::InvalidCertificate(_) | rustls::Error::NoCertificatesPresented)
+        | Some(rustls::Error::AlertReceived(rustls::AlertDescription::BadCertificate)) => {
+            TlsError::CertificateRejected(message)
+        }
+        _ => TlsError::HandshakeFailed(message),
+    }
+}
+
+/// Reads the file at `path`, for PEM settings given as paths
+pub fn read(path: &str) -> Result<Vec<u8>, TlsError> {
+    std::fs::read(path).map_err(|e| TlsError::CertificateRejected(format!("{}: {}", path, e)))
+}
+
+fn provider() -> Arc<rustls::crypto::CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn certificates_from_pem(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certificates = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| TlsError::CertificateRejected(format!("certificate: {}", e)))?;
+    if certificates.is_empty() {
+        return Err(TlsError::CertificateRejected("no PEM certificate found".to_string()));
+    }
+    Ok(certificates)
+}
+
+fn root_store(ca_pem: &[u8]) -> Result<RootCertStore, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for certificate in certificates_from_pem(ca_pem)? {
+        roots
+            .add(certificate)
+            .map_err(|e| TlsError::CertificateRejected(format!("CA certificate: {}", e)))?;
+    }
+    Ok(roots)
+}
+
+/// Test CA and a server certificate for `localhost` and 127.0.0.1
+#[cfg(test)]
+pub(crate) struct TestPki {
+    pub ca_pem: Vec<u8>,
+    pub server: TlsIdentity,
+    ca: rcgen::CertifiedKey,
+}
+
+#[cfg(test)]
+impl TestPki {
+    pub fn new() -> Self {
+        let key_pair = rcgen::KeyPair::generate().expect("CA key");
+        let mut params = rcgen::CertificateParams::new(Vec::<String>::new()).expect("CA parameters");
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        params.distinguished_name.push(rcgen::DnType::CommonName, "pqc-kyber test CA");
+        let ca = rcgen::CertifiedKey { cert: params.self_signed(&key_pair).expect("CA certificate"), key_pair };
+        let (server, _) = issue(&ca, &["localhost", "127.0.0.1"], "localhost");
+        Self { ca_pem: ca.cert.pem().into_bytes(), server, ca }
+    }
+
+    /// Client identity with `principal` as common name, and its certificate in PEM
+    pub fn client(&self, principal: &str) -> (TlsIdentity, Vec<u8>) {
+        issue(&self.ca, &[], principal)
+    }
+}
+
+#[cfg(test)]
+fn issue(ca: &rcgen::CertifiedKey, names: &[&str], common_name: &str) -> (TlsIdentity, Vec<u8>) {
+    let key = rcgen::KeyPair::generate().expect("leaf key");
+    let names = names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+    let mut params = rcgen::CertificateParams::new(names).expect("leaf parameters");
+    params.distinguished_name.push(rcgen::DnType::CommonName, common_name);
+    let cert = params.signed_by(&key, &ca.cert, &ca.key_pair).expect("leaf certificate");
+    let identity = TlsIdentity::from_pem(cert.pem().as_bytes(), key.serialize_pem().as_bytes()).expect("leaf identity");
+    (identity, cert.pem().into_bytes())
+}
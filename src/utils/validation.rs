@@ -1,10 +1,10 @@
 use crate::utils::entropy;
-use anyhow::Result;
+use crate::error::ValidationError;
 
-pub fn validate_key_material(data: &[u8], min_entropy: f64) -> Result<()> {
+pub fn validate_key_material(data: &[u8], min_entropy: f64) -> Result<(), ValidationError> {
     let entropy_score = entropy::calculate(data);
     if entropy_score < min_entropy {
-        return Err(anyhow!("Insufficient entropy in key material"));
+        return Err(ValidationError::InsufficientEntropy);
     }
     Ok(())
 }
\ No newline at end of file